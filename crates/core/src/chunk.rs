@@ -117,9 +117,9 @@ impl<const MTU: usize> ChunkList<MTU> {
     /// dedup and sort elements in list
     pub fn formalize(&self) -> Self {
         let mut chunks = self.to_vec();
-        // dedup same chunk id
-        chunks.dedup_by_key(|c| c.chunk[0]);
+        // sort before dedup, so that out-of-order duplicated chunks are removed
         chunks.sort_by_key(|a| a.chunk[0]);
+        chunks.dedup_by_key(|c| c.chunk[0]);
         Self::from(chunks)
     }
 
@@ -152,6 +152,40 @@ impl<const MTU: usize> ChunkList<MTU> {
             Some(ret)
         }
     }
+
+    /// if list is completed, return a reader of data, or return None.
+    /// Unlike [ChunkList::try_withdraw], the data of chunks is read in order without
+    /// being copied into one buffer, so that large messages can be decoded as a stream.
+    pub fn try_withdraw_reader(&self) -> Option<ChunkReader> {
+        if !self.is_completed() {
+            None
+        } else {
+            Some(ChunkReader {
+                chunks: self.formalize().to_vec().into_iter(),
+                current: Bytes::new(),
+            })
+        }
+    }
+}
+
+/// Reader of data of completed chunks, see [ChunkList::try_withdraw_reader].
+pub struct ChunkReader {
+    chunks: std::vec::IntoIter<Chunk>,
+    current: Bytes,
+}
+
+impl std::io::Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.current.is_empty() {
+            match self.chunks.next() {
+                Some(c) => self.current = c.data,
+                None => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.current.len());
+        buf[..n].copy_from_slice(&self.current.split_to(n));
+        Ok(n)
+    }
 }
 
 impl<const MTU: usize> Default for ChunkList<MTU> {
@@ -178,9 +212,10 @@ impl<const MTU: usize> IntoIterator for ChunkList<MTU> {
     }
 }
 
-impl<const MTU: usize> From<&Bytes> for ChunkList<MTU> {
-    fn from(bytes: &Bytes) -> Self {
-        let chunks: Vec<Bytes> = bytes.chunks(MTU).map(|c| c.to_vec().into()).collect();
+impl<const MTU: usize> ChunkList<MTU> {
+    /// Split bytes to chunks of at most `mtu` bytes, for a size known only at runtime.
+    pub fn split(bytes: &Bytes, mtu: usize) -> Self {
+        let chunks: Vec<Bytes> = bytes.chunks(mtu).map(|c| c.to_vec().into()).collect();
         let chunks_len: usize = chunks.len();
         let meta = ChunkMeta::default();
         Self(
@@ -197,6 +232,12 @@ impl<const MTU: usize> From<&Bytes> for ChunkList<MTU> {
     }
 }
 
+impl<const MTU: usize> From<&Bytes> for ChunkList<MTU> {
+    fn from(bytes: &Bytes) -> Self {
+        Self::split(bytes, MTU)
+    }
+}
+
 impl<const MTU: usize> From<ChunkList<MTU>> for Vec<Chunk> {
     fn from(l: ChunkList<MTU>) -> Self {
         l.to_vec()
@@ -301,6 +342,26 @@ mod test {
         assert_eq!(wd, data);
     }
 
+    #[test]
+    fn test_withdraw_reader() {
+        use std::io::Read;
+
+        let data: Bytes = "helloworld".repeat(1024).into();
+        let mut ret: Vec<Chunk> = ChunkList::<32>::from(&data).into();
+        assert!(ChunkList::<32>::from(ret[0..30].to_vec())
+            .try_withdraw_reader()
+            .is_none());
+
+        ret.reverse();
+        let mut read = vec![];
+        ChunkList::<32>::from(ret)
+            .try_withdraw_reader()
+            .unwrap()
+            .read_to_end(&mut read)
+            .unwrap();
+        assert_eq!(read, data.to_vec());
+    }
+
     #[test]
     fn test_query_complete() {
         let data1 = "hello".repeat(1024).into();
@@ -9,4 +9,92 @@ pub const DEFAULT_SESSION_TTL_MS: u64 = 30 * 24 * 3600 * 1000;
 pub const TRANSPORT_MTU: usize = 60000;
 /// 60M
 pub const TRANSPORT_MAX_SIZE: usize = TRANSPORT_MTU * 1000;
+/// Default max size of messages sent or received by swarm, 16M.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
 pub const VNODE_DATA_MAX_LEN: usize = 1024;
+/// Max time to wait for replies of a vnode lookup.
+pub const VNODE_LOOKUP_TIMEOUT_MS: u64 = 10 * 1000;
+/// Max time to wait for a hop answering its routing decision of a traced lookup.
+pub const LOOKUP_PROBE_TIMEOUT_MS: u64 = 5 * 1000;
+/// Max time to wait for the report of a find successor request.
+pub const FIND_SUCCESSOR_TIMEOUT_MS: u64 = 5 * 1000;
+/// Max number of lookups or connects running at the same time when warming fingers.
+pub const WARM_FINGERS_CONCURRENCY: usize = 8;
+/// Max number of connects running at the same time when warming up successors and predecessor.
+pub const WARM_UP_CONCURRENCY: usize = 4;
+/// Max number of messages sent at the same time by [crate::swarm::Swarm::broadcast].
+pub const BROADCAST_CONCURRENCY: usize = 8;
+/// Default max time to wait for data channel of a new connection to open.
+pub const CONNECT_WAIT_TIMEOUT_MS: u64 = 8 * 1000;
+/// Max number of fresh handshakes asked by `ConnectNodeRenegotiate` after a handshake fails
+/// before its data channel opens.
+pub const MAX_HANDSHAKE_RENEGOTIATIONS: u8 = 3;
+/// Max age of connections being established, older ones are closed in stabilization.
+pub const PENDING_CONNECTION_MAX_AGE_MS: u64 = 60 * 1000;
+/// Max number of trickled ICE candidates of a peer kept before its remote description is set.
+pub const MAX_PENDING_ICE_CANDIDATES: usize = 64;
+/// Max number of senders tracked by inbound rate limiter.
+pub const RATE_LIMIT_MAX_TRACKED: usize = 1024;
+/// Max number of sent messages waiting for report tracked by relay metrics.
+pub const RELAY_METRICS_MAX_TRACKED: usize = 1024;
+/// Number of frames failed to decode from a peer within [DECODE_FAILURES_WINDOW_MS] before
+/// it's disconnected as misbehaving.
+pub const DECODE_FAILURES_THRESHOLD: u64 = 8;
+/// Time window of counting frames failed to decode from a peer, older failures are forgotten.
+pub const DECODE_FAILURES_WINDOW_MS: u128 = 60 * 1000;
+/// Version of the wire format, which leads each frame sent through data channels, see
+/// [crate::message::encode_frame], and each encoded [crate::message::MessagePayload].
+/// Peers of another version are disconnected instead of failing to decode each frame.
+///
+/// Bump it on any change of the bincode layout of messages. Version 2 changes:
+/// - [crate::message::ConnectNodeSend] and [crate::message::ConnectNodeReport] carry the
+///   capabilities of their senders.
+/// - [crate::message::ConnectNodeSend] and [crate::message::ConnectNodeReport] carry the id of
+///   the connection attempt.
+/// - [crate::message::ConnectNodeSend] carries whether it's an ICE restart offer.
+/// - [crate::message::Transaction] carries a signed `expires_at`, and its hash length-prefixes
+///   the data.
+/// - [crate::session::Session] carries its scope and the [crate::session::ParentSession] of a
+///   sub-session.
+pub const PROTOCOL_VERSION: u8 = 2;
+/// Time to live of a topic subscription, which is refreshed in each stabilization.
+pub const TOPIC_SUBSCRIPTION_TTL_MS: u64 = 3 * 60 * 1000;
+/// Max number of subscribers of a topic recorded by the node responsible for it.
+pub const TOPIC_MAX_SUBSCRIBERS: usize = 1024;
+/// Max number of topics of a subscriber recorded by a node.
+pub const TOPIC_MAX_SUBSCRIPTIONS_PER_SUBSCRIBER: usize = 64;
+/// Max size in bytes of data carried by a chunk of [crate::swarm::Swarm::send_file].
+pub const FILE_CHUNK_SIZE: usize = 32 * 1024;
+/// Max number of chunks of a file transfer sent but not acked by receiver yet.
+pub const FILE_TRANSFER_WINDOW: usize = 16;
+/// Max time to wait for the next ack of a file transfer.
+pub const FILE_ACK_TIMEOUT_MS: u64 = 10 * 1000;
+/// Max number of files being received at the same time.
+pub const FILE_MAX_INCOMING_TRANSFERS: usize = 16;
+/// Default number of events buffered by [crate::swarm::Swarm::iter_events].
+pub const DEFAULT_EVENT_CHANNEL_CAPACITY: usize = 1024;
+/// Default time for a message to wait for room in a full buffer of
+/// [crate::swarm::OverflowMode::Block], before it's dropped.
+pub const DEFAULT_INBOX_BLOCK_TIMEOUT_MS: u64 = 5 * 1000;
+/// Default number of recently handled messages kept to drop duplicates of them.
+/// Dropping duplicates is disabled by default.
+pub const DEFAULT_DEDUP_WINDOW: usize = 0;
+/// Max time to wait for the response of each STUN binding request of NAT detection.
+pub const NAT_DETECTION_TIMEOUT_MS: u64 = 3 * 1000;
+/// Default delay before the second attempt of reconnecting a closed connection, which is
+/// doubled for each following attempt.
+pub const DEFAULT_RECONNECT_BACKOFF_MS: u64 = 1000;
+/// Default max delay between attempts of reconnecting a closed connection.
+pub const DEFAULT_RECONNECT_MAX_BACKOFF_MS: u64 = 60 * 1000;
+/// Default max number of attempts of reconnecting a closed connection.
+pub const DEFAULT_RECONNECT_MAX_ATTEMPTS: u32 = 8;
+/// Default max number of chunked messages being reassembled at the same time.
+pub const DEFAULT_REASSEMBLY_MAX_TRANSFERS: usize = 64;
+/// Default max number of chunked messages from a single peer being reassembled at the same time.
+pub const DEFAULT_REASSEMBLY_MAX_TRANSFERS_PER_PEER: usize = 16;
+/// Default max number of bytes reserved by chunked messages being reassembled.
+pub const DEFAULT_REASSEMBLY_MAX_BYTES: usize = 64 * 1024 * 1024;
+/// Default time of reassembling a chunked message, incomplete ones are evicted after it.
+pub const DEFAULT_REASSEMBLY_TIMEOUT_MS: u64 = 60 * 1000;
+/// Max number of records waiting to be written by a message recorder, more are dropped.
+pub const MESSAGE_RECORDER_QUEUE_SIZE: usize = 1024;
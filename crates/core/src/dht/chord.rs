@@ -4,6 +4,7 @@ use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::sync::MutexGuard;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use num_bigint::BigUint;
@@ -11,6 +12,8 @@ use serde::Deserialize;
 use serde::Serialize;
 
 use super::did::BiasId;
+use super::stabilization::StabilizationControl;
+use super::stabilization::StabilizationStats;
 use super::successor::SuccessorSeq;
 use super::types::Chord;
 use super::types::ChordStorage;
@@ -59,6 +62,8 @@ pub struct PeerRing {
     pub storage: VNodeStorage,
     /// Local cache for [ChordStorage].
     pub cache: VNodeStorage,
+    /// Interval, pausing and counters of [crate::dht::Stabilizer].
+    pub(crate) stabilization: StabilizationControl,
 }
 
 /// Type alias is just for making the code easy to read.
@@ -201,6 +206,7 @@ impl PeerRing {
             storage,
             cache: Box::new(MemStorage::new()),
             did,
+            stabilization: StabilizationControl::default(),
         }
     }
 
@@ -215,6 +221,30 @@ impl PeerRing {
         self.successor_seq.clone()
     }
 
+    /// Change the max length of successor sequence at runtime.
+    /// When it shrinks, farthest successors are dropped. When it grows, the sequence is
+    /// refilled by known peers in finger table, and [crate::dht::Stabilizer] will discover
+    /// more successors until it's full.
+    pub fn set_succ_max(&self, succ_max: u8) -> Result<()> {
+        if succ_max < 1 {
+            return Err(Error::InvalidSuccessorMax(succ_max));
+        }
+        let successors = self.successors();
+        let grown = succ_max > successors.capacity();
+        successors.set_capacity(succ_max)?;
+        if grown {
+            let known: Vec<Did> = self
+                .lock_finger()?
+                .list()
+                .iter()
+                .flatten()
+                .copied()
+                .collect();
+            successors.extend(&known)?;
+        }
+        Ok(())
+    }
+
     /// Lock and return MutexGuard of finger table.
     pub fn lock_finger(&self) -> Result<MutexGuard<FingerTable>> {
         self.finger.lock().map_err(|_| Error::DHTSyncLockError)
@@ -247,10 +277,56 @@ impl PeerRing {
         Ok(())
     }
 
+    /// Change the interval of [crate::dht::Stabilizer::wait] at runtime, which overrides the
+    /// interval it's started with. A longer interval reduces background traffic on large rings.
+    pub fn set_stabilize_interval(&self, interval: Duration) {
+        self.stabilization.set_interval(interval)
+    }
+
+    /// Get the interval set by [PeerRing::set_stabilize_interval].
+    pub fn stabilize_interval(&self) -> Option<Duration> {
+        self.stabilization.interval()
+    }
+
+    /// Pause stabilization, no stabilization message is sent until it's resumed.
+    /// It's useful for tests which need a quiet ring.
+    pub fn pause_stabilization(&self) {
+        self.stabilization.set_paused(true)
+    }
+
+    /// Resume stabilization paused by [PeerRing::pause_stabilization].
+    pub fn resume_stabilization(&self) {
+        self.stabilization.set_paused(false)
+    }
+
+    /// Check if stabilization is paused by [PeerRing::pause_stabilization].
+    pub fn is_stabilization_paused(&self) -> bool {
+        self.stabilization.is_paused()
+    }
+
+    /// Get counters of stabilization performed, see [StabilizationStats].
+    pub fn stabilization_stats(&self) -> StabilizationStats {
+        self.stabilization.stats()
+    }
+
     /// Calculate bias of the Did on the ring.
     pub fn bias(&self, did: Did) -> BiasId {
         BiasId::new(self.did, did)
     }
+
+    /// List peers known by successor sequence, finger table and predecessor, sorted by their
+    /// position on the ring clockwise from the did of current node.
+    /// Unlike the tables, the order doesn't depend on how peers are learned, so tests can
+    /// assert it exactly.
+    pub fn ordered_peers(&self) -> Result<Vec<Did>> {
+        let mut peers = self.successors().list()?;
+        peers.extend(self.lock_finger()?.list().iter().flatten());
+        peers.extend(*self.lock_predecessor()?);
+        peers.retain(|did| *did != self.did);
+        peers.sort_by_key(|did| self.bias(*did));
+        peers.dedup();
+        Ok(peers)
+    }
 }
 
 impl Chord<PeerRingAction> for PeerRing {
@@ -639,6 +715,70 @@ mod tests {
     use crate::ecc::SecretKey;
     use crate::tests::default::gen_sorted_dht;
 
+    #[test]
+    fn test_set_succ_max() -> Result<()> {
+        // Each peer takes a slot of finger table.
+        let dids: Vec<Did> = (0..8u32).map(|i| Did::from((1u32 << i) - 1)).collect();
+        let node = PeerRing::new_with_storage(dids[0], 3, Box::new(MemStorage::new()));
+        for did in &dids[1..5] {
+            node.join(*did)?;
+        }
+        assert_eq!(node.successors().list()?, dids[1..4]);
+
+        // Known peers are refilled once it grows.
+        node.set_succ_max(6)?;
+        assert_eq!(node.successors().capacity(), 6);
+        assert_eq!(node.successors().list()?, dids[1..5]);
+
+        // Then expands as more peers are learned.
+        for did in &dids[5..] {
+            node.join(*did)?;
+        }
+        assert_eq!(node.successors().list()?, dids[1..7]);
+
+        node.set_succ_max(2)?;
+        assert_eq!(node.successors().list()?, dids[1..3]);
+
+        assert!(matches!(
+            node.set_succ_max(0),
+            Err(Error::InvalidSuccessorMax(0))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_ordered_peers() -> Result<()> {
+        use rand::seq::SliceRandom;
+
+        let node_did: Did = SecretKey::random().address().into();
+        let mut dids: Vec<Did> = (0..16)
+            .map(|_| SecretKey::random().address().into())
+            .collect();
+        let predecessor: Did = SecretKey::random().address().into();
+
+        let mut expected: Option<Vec<Did>> = None;
+        for _ in 0..5 {
+            dids.shuffle(&mut rand::thread_rng());
+            let node = PeerRing::new_with_storage(node_did, 3, Box::new(MemStorage::new()));
+            for did in &dids {
+                node.join(*did)?;
+            }
+            *node.lock_predecessor()? = Some(predecessor);
+
+            let peers = node.ordered_peers()?;
+            assert!(peers.contains(&predecessor));
+            assert!(peers.windows(2).all(|w| node.bias(w[0]) < node.bias(w[1])));
+            for did in node.successors().list()? {
+                assert!(peers.contains(&did));
+            }
+            match &expected {
+                None => expected = Some(peers),
+                Some(expected) => assert_eq!(&peers, expected),
+            }
+        }
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_chord_finger() -> Result<()> {
         // Setup did a, b, c, d in a clockwise order.
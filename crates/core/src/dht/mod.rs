@@ -7,6 +7,7 @@ mod chord;
 pub mod did;
 /// Finger table for Rings
 pub mod finger;
+mod range;
 mod stabilization;
 /// Implement Subring with VNode
 pub mod subring;
@@ -22,6 +23,7 @@ pub use chord::TopoInfo;
 pub use chord::VNodeStorage;
 pub use did::Did;
 pub use finger::FingerTable;
+pub use stabilization::StabilizationStats;
 pub use stabilization::Stabilizer;
 pub use successor::SuccessorReader;
 pub use successor::SuccessorWriter;
@@ -0,0 +1,277 @@
+#![warn(missing_docs)]
+//! Range queries of [Did] on the ring.
+//!
+//! All ranges go clockwise from `start` to `end`, so a range wraps around zero when
+//! `start > end`. For example, `(0xf0.., 0x10..)` contains `0xff..` and `0x00..`.
+
+use super::Did;
+
+impl Did {
+    /// Clockwise distance from `a` to `b` on the ring, which is `b - a`.
+    /// It's not symmetric, `distance(a, b) + distance(b, a)` is zero for a full turn.
+    pub fn distance(a: Self, b: Self) -> Self {
+        b - a
+    }
+
+    /// Test `key` in `[start, end]`, walking clockwise from `start`.
+    /// When `start == end`, only `start` itself is in range.
+    pub fn in_range_inclusive(key: Self, start: Self, end: Self) -> bool {
+        Self::distance(start, key) <= Self::distance(start, end)
+    }
+
+    /// Test `key` in `(start, end)`, walking clockwise from `start`.
+    /// When `start == end`, the range is the whole ring except `start`, as in Chord.
+    pub fn in_range_exclusive(key: Self, start: Self, end: Self) -> bool {
+        if key == start {
+            return false;
+        }
+        if start == end {
+            return true;
+        }
+        Self::distance(start, key) < Self::distance(start, end)
+    }
+
+    /// Find the first of `targets` walking clockwise from `key`, which is the successor of `key`
+    /// among `targets`. A target equal to `key` is the closest one.
+    /// Return None if `targets` is empty.
+    pub fn closest_of(targets: &[Self], key: Self) -> Option<Self> {
+        targets
+            .iter()
+            .min_by_key(|target| Self::distance(key, **target))
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigUint;
+
+    use super::*;
+
+    fn max() -> Did {
+        -Did::from(1u32)
+    }
+
+    fn half() -> Did {
+        Did::from(BigUint::from(2u16).pow(159))
+    }
+
+    #[test]
+    fn test_distance() {
+        let a = Did::from(10u32);
+        let b = Did::from(25u32);
+        assert_eq!(Did::distance(a, b), Did::from(15u32));
+        assert_eq!(Did::distance(b, a), -Did::from(15u32));
+        assert_eq!(Did::distance(a, a), Did::from(0u32));
+        assert_eq!(Did::distance(a, b) + Did::distance(b, a), Did::from(0u32));
+
+        // Across zero.
+        assert_eq!(Did::distance(max(), Did::from(0u32)), Did::from(1u32));
+        assert_eq!(Did::distance(max(), Did::from(9u32)), Did::from(10u32));
+        assert_eq!(Did::distance(Did::from(0u32), max()), max());
+
+        // Half turn is the same both ways.
+        assert_eq!(Did::distance(Did::from(0u32), half()), half());
+        assert_eq!(Did::distance(half(), Did::from(0u32)), half());
+    }
+
+    #[test]
+    fn test_in_range_without_wraparound() {
+        let (start, end) = (Did::from(10u32), Did::from(20u32));
+        for i in 0..30u32 {
+            let key = Did::from(i);
+            assert_eq!(
+                Did::in_range_inclusive(key, start, end),
+                (10..=20).contains(&i),
+                "{i} in [10, 20]"
+            );
+            assert_eq!(
+                Did::in_range_exclusive(key, start, end),
+                (11..20).contains(&i),
+                "{i} in (10, 20)"
+            );
+        }
+        assert!(!Did::in_range_inclusive(max(), start, end));
+        assert!(!Did::in_range_exclusive(max(), start, end));
+        assert!(!Did::in_range_inclusive(half(), start, end));
+        assert!(!Did::in_range_exclusive(half(), start, end));
+    }
+
+    #[test]
+    fn test_in_range_with_wraparound() {
+        // [max - 4, 5] contains max - 4..=max and 0..=5.
+        let start = max() - Did::from(4u32);
+        let end = Did::from(5u32);
+        assert!(start > end);
+
+        for i in 0..10u32 {
+            let key = max() - Did::from(i);
+            assert_eq!(
+                Did::in_range_inclusive(key, start, end),
+                i <= 4,
+                "max - {i} in [max - 4, 5]"
+            );
+            assert_eq!(
+                Did::in_range_exclusive(key, start, end),
+                i < 4,
+                "max - {i} in (max - 4, 5)"
+            );
+
+            let key = Did::from(i);
+            assert_eq!(
+                Did::in_range_inclusive(key, start, end),
+                i <= 5,
+                "{i} in [max - 4, 5]"
+            );
+            assert_eq!(
+                Did::in_range_exclusive(key, start, end),
+                i < 5,
+                "{i} in (max - 4, 5)"
+            );
+        }
+        assert!(!Did::in_range_inclusive(half(), start, end));
+        assert!(!Did::in_range_exclusive(half(), start, end));
+
+        // Swapping start and end gives the complement, except the bounds.
+        for key in [Did::from(0u32), Did::from(7u32), half(), max()] {
+            assert_ne!(
+                Did::in_range_exclusive(key, start, end),
+                Did::in_range_exclusive(key, end, start)
+            );
+        }
+    }
+
+    #[test]
+    fn test_in_range_of_half_turns() {
+        let zero = Did::from(0u32);
+        let quarter = Did::from(BigUint::from(2u16).pow(158));
+        let three_quarters = half() + quarter;
+
+        assert!(Did::in_range_exclusive(quarter, zero, half()));
+        assert!(!Did::in_range_exclusive(three_quarters, zero, half()));
+        assert!(Did::in_range_exclusive(three_quarters, half(), zero));
+        assert!(!Did::in_range_exclusive(quarter, half(), zero));
+        assert!(Did::in_range_inclusive(zero, half(), zero));
+        assert!(Did::in_range_inclusive(half(), half(), zero));
+        assert!(Did::in_range_exclusive(max(), half(), zero));
+    }
+
+    #[test]
+    fn test_in_range_of_same_start_and_end() {
+        let start = Did::from(42u32);
+        for key in [
+            Did::from(0u32),
+            Did::from(41u32),
+            Did::from(43u32),
+            half(),
+            max(),
+        ] {
+            assert!(!Did::in_range_inclusive(key, start, start));
+            assert!(Did::in_range_exclusive(key, start, start));
+        }
+        assert!(Did::in_range_inclusive(start, start, start));
+        assert!(!Did::in_range_exclusive(start, start, start));
+    }
+
+    #[test]
+    fn test_in_range_bounds() {
+        let pairs = [
+            (Did::from(10u32), Did::from(20u32)),
+            (max() - Did::from(4u32), Did::from(5u32)),
+            (half(), Did::from(0u32)),
+            (Did::from(1u32), max()),
+        ];
+        for (start, end) in pairs {
+            assert!(Did::in_range_inclusive(start, start, end));
+            assert!(Did::in_range_inclusive(end, start, end));
+            assert!(!Did::in_range_exclusive(start, start, end));
+            assert!(!Did::in_range_exclusive(end, start, end));
+            assert!(Did::in_range_inclusive(start + Did::from(1u32), start, end));
+            assert!(!Did::in_range_inclusive(end + Did::from(1u32), start, end));
+            assert!(!Did::in_range_inclusive(
+                start - Did::from(1u32),
+                start,
+                end
+            ));
+        }
+    }
+
+    #[test]
+    fn test_closest_of() {
+        assert_eq!(Did::closest_of(&[], Did::from(1u32)), None);
+
+        let targets = [Did::from(10u32), Did::from(20u32), half(), max()];
+        assert_eq!(
+            Did::closest_of(&targets, Did::from(0u32)),
+            Some(Did::from(10u32))
+        );
+        assert_eq!(
+            Did::closest_of(&targets, Did::from(10u32)),
+            Some(Did::from(10u32))
+        );
+        assert_eq!(
+            Did::closest_of(&targets, Did::from(11u32)),
+            Some(Did::from(20u32))
+        );
+        assert_eq!(Did::closest_of(&targets, Did::from(21u32)), Some(half()));
+        assert_eq!(
+            Did::closest_of(&targets, half() + Did::from(1u32)),
+            Some(max())
+        );
+        assert_eq!(Did::closest_of(&targets, max()), Some(max()));
+
+        // Wrap around zero when nothing follows key.
+        let targets = [Did::from(10u32), Did::from(20u32)];
+        assert_eq!(
+            Did::closest_of(&targets, Did::from(21u32)),
+            Some(Did::from(10u32))
+        );
+        assert_eq!(Did::closest_of(&targets, max()), Some(Did::from(10u32)));
+
+        // Order of targets doesn't matter.
+        let reversed = [Did::from(20u32), Did::from(10u32)];
+        assert_eq!(
+            Did::closest_of(&reversed, Did::from(21u32)),
+            Some(Did::from(10u32))
+        );
+    }
+
+    #[test]
+    fn test_closest_of_equidistant_targets() {
+        // Both targets are 5 away from key, one before it and one after it.
+        let key = Did::from(15u32);
+        let (before, after) = (Did::from(10u32), Did::from(20u32));
+        let orders = [
+            vec![before, after],
+            vec![after, before],
+            vec![after, before, after],
+            vec![before, before, after, after],
+        ];
+        for targets in orders {
+            assert_eq!(Did::closest_of(&targets, key), Some(after), "{targets:?}");
+        }
+
+        // The same across zero.
+        let key = Did::from(0u32);
+        let (before, after) = (max() - Did::from(4u32), Did::from(5u32));
+        assert_eq!(Did::closest_of(&[before, after], key), Some(after));
+        assert_eq!(Did::closest_of(&[after, before], key), Some(after));
+    }
+
+    #[test]
+    fn test_closest_of_is_in_range() {
+        // No target falls between key and its closest target.
+        let targets = [
+            Did::from(3u32),
+            Did::from(7u32),
+            half(),
+            max() - Did::from(2u32),
+        ];
+        for key in [Did::from(0u32), Did::from(5u32), half(), max()] {
+            let closest = Did::closest_of(&targets, key).unwrap();
+            for target in targets {
+                assert!(!Did::in_range_exclusive(target, key, closest) || key == closest);
+            }
+        }
+    }
+}
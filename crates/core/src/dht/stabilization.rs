@@ -1,16 +1,24 @@
 //! Stabilization run daemons to maintain dht.
 
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Duration;
 
 use rings_transport::core::transport::WebrtcConnectionState;
 
+use crate::consts::PENDING_CONNECTION_MAX_AGE_MS;
+use crate::consts::TOPIC_SUBSCRIPTION_TTL_MS;
 use crate::dht::successor::SuccessorReader;
 use crate::dht::types::CorrectChord;
 use crate::dht::Chord;
+use crate::dht::Did;
 use crate::dht::PeerRing;
 use crate::dht::PeerRingAction;
 use crate::dht::PeerRingRemoteAction;
 use crate::error::Result;
+use crate::message::handlers::pubsub::subscribe_topic;
 use crate::message::FindSuccessorReportHandler;
 use crate::message::FindSuccessorSend;
 use crate::message::FindSuccessorThen;
@@ -19,24 +27,103 @@ use crate::message::MessagePayload;
 use crate::message::NotifyPredecessorSend;
 use crate::message::PayloadSender;
 use crate::message::QueryForTopoInfoSend;
+use crate::swarm::callback::DisconnectReason;
+use crate::swarm::callback::InnerSwarmCallback;
+use crate::swarm::callback::SharedSwarmCallback;
+use crate::swarm::callback::SwarmEvent;
 use crate::swarm::transport::SwarmTransport;
+use crate::utils::Clock;
+
+/// Counters of stabilization performed by [Stabilizer], see [PeerRing::stabilization_stats].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StabilizationStats {
+    /// Rounds of [Stabilizer::stabilize] run while not paused.
+    pub rounds: u64,
+    /// Finger fixes sent, each of which is a `FindSuccessorSend` message.
+    pub finger_fixes: u64,
+    /// Successor checks sent, which are `NotifyPredecessorSend` messages to successors and
+    /// `FindSuccessorSend` messages to discover more successors.
+    pub successor_checks: u64,
+}
+
+/// Interval, pausing and counters of stabilization, shared by all [Stabilizer]s of a [PeerRing].
+#[derive(Debug, Default)]
+pub(crate) struct StabilizationControl {
+    /// Interval set by [PeerRing::set_stabilize_interval] in milliseconds, 0 if not set.
+    interval_ms: AtomicU64,
+    paused: AtomicBool,
+    rounds: AtomicU64,
+    finger_fixes: AtomicU64,
+    successor_checks: AtomicU64,
+}
+
+impl StabilizationControl {
+    pub(crate) fn interval(&self) -> Option<Duration> {
+        match self.interval_ms.load(Ordering::Relaxed) {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        }
+    }
+
+    pub(crate) fn set_interval(&self, interval: Duration) {
+        // Sub-millisecond intervals are rounded up, since 0 means not set.
+        let ms = u64::try_from(interval.as_millis())
+            .unwrap_or(u64::MAX)
+            .max(1);
+        self.interval_ms.store(ms, Ordering::Relaxed);
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    pub(crate) fn stats(&self) -> StabilizationStats {
+        StabilizationStats {
+            rounds: self.rounds.load(Ordering::Relaxed),
+            finger_fixes: self.finger_fixes.load(Ordering::Relaxed),
+            successor_checks: self.successor_checks.load(Ordering::Relaxed),
+        }
+    }
+}
 
 /// The stabilization runner.
 #[derive(Clone)]
 pub struct Stabilizer {
     transport: Arc<SwarmTransport>,
     dht: Arc<PeerRing>,
+    /// Callback notified of events of stabilization, such as closing idle connections.
+    callback: Option<SharedSwarmCallback>,
 }
 
 impl Stabilizer {
     /// Create a new stabilization runner.
     pub fn new(transport: Arc<SwarmTransport>) -> Self {
         let dht = transport.dht.clone();
-        Self { transport, dht }
+        Self {
+            transport,
+            dht,
+            callback: None,
+        }
+    }
+
+    pub(crate) fn with_callback(mut self, callback: Option<SharedSwarmCallback>) -> Self {
+        self.callback = callback;
+        self
     }
 
     /// Run stabilization once.
+    /// Nothing is done if stabilization is paused by [PeerRing::pause_stabilization].
     pub async fn stabilize(&self) -> Result<()> {
+        let control = &self.dht.stabilization;
+        if control.is_paused() {
+            tracing::debug!("STABILIZATION paused");
+            return Ok(());
+        }
+        control.rounds.fetch_add(1, Ordering::Relaxed);
         tracing::debug!("STABILIZATION notify_predecessor start");
         if let Err(e) = self.notify_predecessor().await {
             tracing::error!("[stabilize] Failed on notify predecessor {:?}", e);
@@ -47,6 +134,11 @@ impl Stabilizer {
             tracing::error!("[stabilize] Failed on fix_finger {:?}", e);
         }
         tracing::debug!("STABILIZATION fix_fingers end");
+        tracing::debug!("STABILIZATION discover_successors start");
+        if let Err(e) = self.discover_successors().await {
+            tracing::error!("[stabilize] Failed on discover successors {:?}", e);
+        }
+        tracing::debug!("STABILIZATION discover_successors end");
         tracing::debug!("STABILIZATION clean_unavailable_connections start");
         if let Err(e) = self.clean_unavailable_connections().await {
             tracing::error!(
@@ -55,6 +147,35 @@ impl Stabilizer {
             );
         }
         tracing::debug!("STABILIZATION clean_unavailable_connections end");
+        tracing::debug!("STABILIZATION gc_pending_connections start");
+        if let Err(e) = self
+            .transport
+            .gc_pending_connections(Duration::from_millis(PENDING_CONNECTION_MAX_AGE_MS))
+            .await
+        {
+            tracing::error!("[stabilize] Failed on gc pending connections {:?}", e);
+        }
+        tracing::debug!("STABILIZATION gc_pending_connections end");
+        tracing::debug!("STABILIZATION disconnect_idle_connections start");
+        if let Err(e) = self.disconnect_idle_connections().await {
+            tracing::error!("[stabilize] Failed on disconnect idle connections {:?}", e);
+        }
+        tracing::debug!("STABILIZATION disconnect_idle_connections end");
+        tracing::debug!("STABILIZATION reconnect_pinned_peers start");
+        if let Err(e) = self.reconnect_pinned_peers().await {
+            tracing::error!("[stabilize] Failed on reconnect pinned peers {:?}", e);
+        }
+        tracing::debug!("STABILIZATION reconnect_pinned_peers end");
+        tracing::debug!("STABILIZATION reconnect_closed_peers start");
+        if let Err(e) = self.reconnect_closed_peers().await {
+            tracing::error!("[stabilize] Failed on reconnect closed peers {:?}", e);
+        }
+        tracing::debug!("STABILIZATION reconnect_closed_peers end");
+        tracing::debug!("STABILIZATION refresh_subscriptions start");
+        if let Err(e) = self.refresh_subscriptions().await {
+            tracing::error!("[stabilize] Failed on refresh subscriptions {:?}", e);
+        }
+        tracing::debug!("STABILIZATION refresh_subscriptions end");
         #[cfg(feature = "experimental")]
         {
             tracing::debug!("STABILIZATION correct_stabilize start");
@@ -66,6 +187,99 @@ impl Stabilizer {
         Ok(())
     }
 
+    /// Close connections idle longer than [crate::swarm::SwarmBuilder::idle_timeout], except
+    /// successors. Return the peers of closed connections.
+    pub async fn disconnect_idle_connections(&self) -> Result<Vec<Did>> {
+        let idle = self
+            .transport
+            .disconnect_idle_connections_at(self.transport.clock.now_ms())
+            .await?;
+        if let Some(callback) = &self.callback {
+            for peer in idle.iter() {
+                let event = SwarmEvent::Disconnected {
+                    peer: *peer,
+                    reason: DisconnectReason::Idle,
+                };
+                if let Err(e) = callback.on_event(&event).await {
+                    tracing::error!("Failed on handle event {event:?}: {e:?}");
+                }
+            }
+        }
+        Ok(idle)
+    }
+
+    /// Connect peers pinned by [crate::swarm::Swarm::pin] which are not connected.
+    /// Unavailable connections are cleaned before, so they're connected again here.
+    /// Return the peers being connected.
+    pub async fn reconnect_pinned_peers(&self) -> Result<Vec<Did>> {
+        let Some(callback) = &self.callback else {
+            return Ok(vec![]);
+        };
+        let mut reconnecting = vec![];
+        for peer in self.transport.pinned_peers() {
+            if self.transport.get_connection(peer).is_some() {
+                continue;
+            }
+            tracing::info!("STABILIZATION reconnect_pinned_peers: {:?}", peer);
+            let callback = InnerSwarmCallback::new(self.transport.clone(), callback.clone());
+            match self.transport.connect(peer, callback, None).await {
+                Ok(()) => reconnecting.push(peer),
+                Err(e) => tracing::warn!("Failed to reconnect pinned peer {peer}: {e:?}"),
+            }
+        }
+        Ok(reconnecting)
+    }
+
+    /// Connect peers whose connection is closed and wanted by
+    /// [crate::swarm::ReconnectPolicy], once their backoff elapses.
+    /// Return the peers being connected.
+    pub async fn reconnect_closed_peers(&self) -> Result<Vec<Did>> {
+        let Some(callback) = &self.callback else {
+            return Ok(vec![]);
+        };
+        let mut reconnecting = vec![];
+        for peer in self.transport.take_due_reconnects() {
+            if let Some(conn) = self.transport.get_connection(peer) {
+                if !matches!(
+                    conn.webrtc_connection_state(),
+                    WebrtcConnectionState::Disconnected
+                        | WebrtcConnectionState::Failed
+                        | WebrtcConnectionState::Closed
+                ) {
+                    continue;
+                }
+                self.transport.disconnect(peer).await?;
+            }
+            tracing::info!("STABILIZATION reconnect_closed_peers: {:?}", peer);
+            let callback = InnerSwarmCallback::new(self.transport.clone(), callback.clone());
+            match self.transport.connect(peer, callback, None).await {
+                Ok(()) => reconnecting.push(peer),
+                Err(e) => tracing::warn!("Failed to reconnect closed peer {peer}: {e:?}"),
+            }
+        }
+        Ok(reconnecting)
+    }
+
+    /// Refresh subscriptions of topics made by [crate::swarm::Swarm::subscribe], so that they
+    /// don't expire, and move to the new responsible node when the ring changes.
+    /// A failed topic is logged, others are still refreshed.
+    pub async fn refresh_subscriptions(&self) -> Result<()> {
+        let topics: Vec<String> = self
+            .transport
+            .subscriptions
+            .iter()
+            .map(|t| t.clone())
+            .collect();
+        for topic in topics {
+            if let Err(e) =
+                subscribe_topic(&self.transport, &topic, TOPIC_SUBSCRIPTION_TTL_MS).await
+            {
+                tracing::warn!("Failed to refresh subscription of {topic}: {e:?}");
+            }
+        }
+        Ok(())
+    }
+
     /// Clean unavailable connections in transport.
     pub async fn clean_unavailable_connections(&self) -> Result<()> {
         let conns = self.transport.get_connections();
@@ -99,6 +313,10 @@ impl Stabilizer {
                 let payload =
                     MessagePayload::new_send(msg.clone(), self.transport.session_sk(), s, s)?;
                 self.transport.send_payload(payload).await?;
+                self.dht
+                    .stabilization
+                    .successor_checks
+                    .fetch_add(1, Ordering::Relaxed);
             }
             Ok(())
         } else {
@@ -106,6 +324,30 @@ impl Stabilizer {
         }
     }
 
+    /// Ask the farthest successor for the next one if successor sequence is not full,
+    /// the reported node will be connected then joined into successor sequence.
+    /// This is a DHT operation.
+    pub async fn discover_successors(&self) -> Result<()> {
+        let successors = self.dht.successors();
+        if successors.is_empty()? || successors.is_full()? {
+            return Ok(());
+        }
+
+        let farthest = successors.max()?;
+        tracing::debug!("STABILIZATION discover_successors: after {:?}", farthest);
+        let msg = Message::FindSuccessorSend(FindSuccessorSend {
+            did: farthest + Did::from(1u32),
+            then: FindSuccessorThen::Report(FindSuccessorReportHandler::Connect),
+            strict: false,
+        });
+        self.transport.send_direct_message(msg, farthest).await?;
+        self.dht
+            .stabilization
+            .successor_checks
+            .fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
     /// Fix fingers from finger table, this is a DHT operation.
     async fn fix_fingers(&self) -> Result<()> {
         match self.dht.fix_fingers() {
@@ -128,6 +370,10 @@ impl Stabilizer {
                         closest_predecessor,
                     )?;
                     self.transport.send_payload(payload).await?;
+                    self.dht
+                        .stabilization
+                        .finger_fixes
+                        .fetch_add(1, Ordering::Relaxed);
                     Ok(())
                 }
                 _ => {
@@ -174,8 +420,11 @@ mod stabilizer {
 
     impl Stabilizer {
         /// Run stabilization in a loop.
+        /// The interval set by [PeerRing::set_stabilize_interval] overrides `interval`, and
+        /// is read again before each round.
         pub async fn wait(self: Arc<Self>, interval: Duration) {
             loop {
+                let interval = self.dht.stabilization.interval().unwrap_or(interval);
                 let timeout = Delay::new(interval).fuse();
                 pin_mut!(timeout);
                 select! {
@@ -201,7 +450,10 @@ mod stabilizer {
 
     impl Stabilizer {
         /// Run stabilization in a loop.
+        /// The interval set by [PeerRing::set_stabilize_interval] overrides `interval`. It's
+        /// read once when the loop starts, since the timer of browser is not reset.
         pub async fn wait(self: Arc<Self>, interval: Duration) {
+            let interval = self.dht.stabilization.interval().unwrap_or(interval);
             let caller = Arc::clone(&self);
             let func = move || {
                 let caller = caller.clone();
@@ -1,5 +1,7 @@
 #![warn(missing_docs)]
 //! Successor Sequance for PeerRing
+use std::sync::atomic::AtomicU8;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::sync::RwLock;
 use std::sync::RwLockReadGuard;
@@ -18,8 +20,8 @@ use crate::error::Result;
 pub struct SuccessorSeq {
     /// The identifier of a node
     did: Did,
-    /// The maximum number of successors
-    max: u8,
+    /// The maximum number of successors, shared by clones so that it can be changed at runtime.
+    max: Arc<AtomicU8>,
     /// The list of successor nodes
     successors: Arc<RwLock<Vec<Did>>>,
 }
@@ -61,11 +63,28 @@ impl SuccessorSeq {
     pub fn new(did: Did, max: u8) -> Self {
         Self {
             did,
-            max,
+            max: Arc::new(AtomicU8::new(max)),
             successors: Arc::new(RwLock::new(vec![])),
         }
     }
 
+    /// The maximum number of successors.
+    pub fn capacity(&self) -> u8 {
+        self.max.load(Ordering::SeqCst)
+    }
+
+    /// Change the maximum number of successors.
+    /// Farthest successors are dropped if the sequence is longer than `max`.
+    pub fn set_capacity(&self, max: u8) -> Result<()> {
+        let mut succs = self
+            .successors
+            .write()
+            .map_err(|_| Error::FailedToWriteSuccessors)?;
+        self.max.store(max, Ordering::SeqCst);
+        succs.truncate(max.into());
+        Ok(())
+    }
+
     /// Returns the list of successors in a read lock.
     pub fn successors(&self) -> Result<RwLockReadGuard<Vec<Did>>> {
         self.successors
@@ -108,7 +127,7 @@ impl SuccessorReader for SuccessorSeq {
     /// Check if the successors list has reached its maximum capacity
     fn is_full(&self) -> Result<bool> {
         let succs = self.successors()?;
-        Ok(succs.len() as u8 >= self.max)
+        Ok(succs.len() >= self.capacity().into())
     }
 
     /// Retrieve a successor from the list by index
@@ -173,7 +192,7 @@ impl SuccessorWriter for SuccessorSeq {
 
         succs.push(successor);
         succs.sort(self.did);
-        succs.truncate(self.max.into());
+        succs.truncate(self.capacity().into());
         if succs.contains(&successor) {
             Ok(Some(successor))
         } else {
@@ -247,4 +266,24 @@ mod tests {
         assert_eq!(succ.list()?, vec![dids[1], dids[3]]);
         Ok(())
     }
+
+    #[test]
+    fn test_successor_set_capacity() -> Result<()> {
+        let dids = gen_ordered_dids(6);
+
+        let succ = SuccessorSeq::new(dids[0], 3);
+        succ.extend(&dids[1..])?;
+        assert_eq!(succ.list()?, dids[1..4]);
+
+        // Clones share the capacity.
+        succ.clone().set_capacity(5)?;
+        assert_eq!(succ.capacity(), 5);
+        assert!(!succ.is_full()?);
+        succ.extend(&dids[1..])?;
+        assert_eq!(succ.list()?, dids[1..6]);
+
+        succ.set_capacity(2)?;
+        assert_eq!(succ.list()?, dids[1..3]);
+        Ok(())
+    }
 }
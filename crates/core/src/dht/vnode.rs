@@ -12,10 +12,12 @@ use crate::dht::Did;
 use crate::ecc::HashStr;
 use crate::error::Error;
 use crate::error::Result;
+use crate::message::Decoder;
 use crate::message::Encoded;
 use crate::message::Encoder;
 use crate::message::MessagePayload;
 use crate::message::MessageVerificationExt;
+use crate::utils::get_epoch_ms;
 
 /// VNode Types
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -64,6 +66,64 @@ pub struct VirtualNode {
     pub kind: VNodeType,
 }
 
+/// A value with expiry, stored in a [VNodeType::Data] VirtualNode by
+/// [Swarm::vnode_put](crate::swarm::Swarm::vnode_put).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VNodeEntry {
+    /// The stored value.
+    pub value: Vec<u8>,
+    /// Timestamp when the entry is written, used to pick the freshest replica.
+    pub ts_ms: u128,
+    /// Time to live of the entry.
+    pub ttl_ms: u64,
+}
+
+impl VNodeEntry {
+    /// Create a new entry with current timestamp.
+    pub fn new(value: Vec<u8>, ttl_ms: u64) -> Self {
+        Self {
+            value,
+            ts_ms: get_epoch_ms(),
+            ttl_ms,
+        }
+    }
+
+    /// Check if the entry is expired.
+    pub fn is_expired(&self) -> bool {
+        get_epoch_ms() > self.ts_ms + self.ttl_ms as u128
+    }
+
+    /// Wrap the entry into a VirtualNode with did.
+    pub fn to_vnode(&self, did: Did) -> Result<VirtualNode> {
+        let data = bincode::serialize(self).map_err(Error::BincodeSerialize)?;
+        Ok(VirtualNode {
+            did,
+            data: vec![data.encode()?],
+            kind: VNodeType::Data,
+        })
+    }
+
+    /// Pick the freshest entry which is not expired.
+    pub fn freshest(entries: impl IntoIterator<Item = Self>) -> Option<Self> {
+        entries
+            .into_iter()
+            .filter(|e| !e.is_expired())
+            .max_by_key(|e| e.ts_ms)
+    }
+}
+
+impl TryFrom<&VirtualNode> for VNodeEntry {
+    type Error = Error;
+    fn try_from(vnode: &VirtualNode) -> Result<Self> {
+        if vnode.kind != VNodeType::Data {
+            return Err(Error::InvalidVNodeType);
+        }
+        let encoded = vnode.data.last().ok_or(Error::InvalidVNodeType)?;
+        let data = Vec::<u8>::from_encoded(encoded)?;
+        bincode::deserialize(&data).map_err(Error::BincodeDeserialize)
+    }
+}
+
 impl VirtualNode {
     /// Generate did from topic.
     pub fn gen_did(topic: &str) -> Result<Did> {
@@ -274,6 +334,34 @@ impl VirtualNode {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_vnode_entry_freshest() {
+        let did = VirtualNode::gen_did("test").unwrap();
+        let old = VNodeEntry {
+            value: b"old".to_vec(),
+            ts_ms: get_epoch_ms() - 1000,
+            ttl_ms: 60 * 1000,
+        };
+        let new = VNodeEntry::new(b"new".to_vec(), 60 * 1000);
+        assert!(!new.is_expired());
+
+        let vnode = new.to_vnode(did).unwrap();
+        assert_eq!(VNodeEntry::try_from(&vnode).unwrap(), new);
+
+        let entries = vec![old.clone(), new.clone()];
+        assert_eq!(VNodeEntry::freshest(entries), Some(new));
+        assert_eq!(VNodeEntry::freshest(vec![old.clone()]), Some(old));
+
+        // An expired entry is skipped even if its timestamp is newer.
+        let expired = VNodeEntry {
+            value: b"expired".to_vec(),
+            ts_ms: get_epoch_ms() - 10,
+            ttl_ms: 0,
+        };
+        assert!(expired.is_expired());
+        assert_eq!(VNodeEntry::freshest(vec![expired]), None);
+    }
+
     #[test]
     fn test_vnode_extend_over_max_len() {
         let topic = "test0".to_string();
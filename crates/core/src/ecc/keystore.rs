@@ -0,0 +1,286 @@
+//! Encrypted keystore of [SecretKey], compatible with the
+//! [Web3 Secret Storage Definition](https://ethereum.org/en/developers/docs/data-structures-and-encoding/web3-secret-storage/),
+//! which is known as keystore v3.
+//!
+//! The key is encrypted by aes-128-ctr, with the first half of a key derived from password by
+//! scrypt. The second half of derived key is hashed with the ciphertext by keccak256 as mac,
+//! which tells a wrong password from a broken keystore.
+//!
+//! Scrypt parameters of an imported keystore are bounded in memory and work, so that a crafted
+//! keystore cannot exhaust memory or cpu.
+
+use aes::cipher::KeyIvInit;
+use aes::cipher::StreamCipher;
+use rand::RngCore;
+use serde::Deserialize;
+use serde::Serialize;
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+use super::keccak256;
+use super::SecretKey;
+use crate::error::Error;
+use crate::error::Result;
+
+type Aes128Ctr = ctr::Ctr128BE<aes::Aes128>;
+
+/// Cost parameter of scrypt in log2, the standard one of keystore v3.
+const SCRYPT_LOG_N: u8 = 18;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+const DKLEN: usize = 32;
+/// Max memory used by scrypt, which is `128 * r * n` bytes. The standard cost takes 256MiB.
+const MAX_SCRYPT_MEMORY: u64 = 256 * 1024 * 1024;
+/// Max work of scrypt, which is the memory multiplied by `p`.
+const MAX_SCRYPT_WORK: u64 = 1024 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Keystore {
+    version: u8,
+    id: uuid::Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    address: Option<String>,
+    crypto: CryptoJson,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CryptoJson {
+    cipher: String,
+    cipherparams: CipherParams,
+    ciphertext: String,
+    kdf: String,
+    kdfparams: ScryptParams,
+    mac: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CipherParams {
+    iv: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ScryptParams {
+    dklen: usize,
+    n: u64,
+    p: u32,
+    r: u32,
+    salt: String,
+}
+
+fn invalid(reason: impl ToString) -> Error {
+    Error::InvalidKeystore(reason.to_string())
+}
+
+/// Check that the cost of scrypt is bounded, before any work is done.
+fn check_scrypt_cost(n: u64, r: u32, p: u32) -> Result<()> {
+    if !n.is_power_of_two() || r == 0 || p == 0 {
+        return Err(invalid("bad kdfparams"));
+    }
+    let memory = 128u64.saturating_mul(r as u64).saturating_mul(n);
+    if memory > MAX_SCRYPT_MEMORY || memory.saturating_mul(p as u64) > MAX_SCRYPT_WORK {
+        return Err(invalid("kdfparams too costly"));
+    }
+    Ok(())
+}
+
+fn derive_key(
+    password: &str,
+    salt: &[u8],
+    log_n: u8,
+    r: u32,
+    p: u32,
+) -> Result<Zeroizing<[u8; DKLEN]>> {
+    let params = scrypt::Params::new(log_n, r, p, DKLEN).map_err(invalid)?;
+    let mut key = Zeroizing::new([0u8; DKLEN]);
+    scrypt::scrypt(password.as_bytes(), salt, &params, &mut key[..]).map_err(invalid)?;
+    Ok(key)
+}
+
+fn mac(derived_key: &[u8; DKLEN], ciphertext: &[u8]) -> [u8; 32] {
+    keccak256(&[&derived_key[16..32], ciphertext].concat())
+}
+
+fn apply_cipher(derived_key: &[u8; DKLEN], iv: &[u8], data: &mut [u8]) -> Result<()> {
+    let mut cipher = Aes128Ctr::new_from_slices(&derived_key[..16], iv).map_err(invalid)?;
+    cipher.apply_keystream(data);
+    Ok(())
+}
+
+pub(super) fn encrypt(key: &SecretKey, password: &str, log_n: u8) -> Result<String> {
+    let mut rng = rand::thread_rng();
+    let mut salt = [0u8; 32];
+    let mut iv = [0u8; 16];
+    rng.fill_bytes(&mut salt);
+    rng.fill_bytes(&mut iv);
+
+    let derived_key = derive_key(password, &salt, log_n, SCRYPT_R, SCRYPT_P)?;
+    let mut ciphertext = Zeroizing::new(key.ser());
+    apply_cipher(&derived_key, &iv, &mut ciphertext[..])?;
+
+    let keystore = Keystore {
+        version: 3,
+        id: uuid::Uuid::new_v4(),
+        address: Some(hex::encode(key.address())),
+        crypto: CryptoJson {
+            cipher: "aes-128-ctr".to_string(),
+            cipherparams: CipherParams {
+                iv: hex::encode(iv),
+            },
+            ciphertext: hex::encode(&ciphertext[..]),
+            kdf: "scrypt".to_string(),
+            kdfparams: ScryptParams {
+                dklen: DKLEN,
+                n: 1 << log_n,
+                p: SCRYPT_P,
+                r: SCRYPT_R,
+                salt: hex::encode(salt),
+            },
+            mac: hex::encode(mac(&derived_key, &ciphertext[..])),
+        },
+    };
+    serde_json::to_string(&keystore).map_err(Error::Serialize)
+}
+
+pub(super) fn decrypt(json: &str, password: &str) -> Result<SecretKey> {
+    let keystore: Keystore = serde_json::from_str(json).map_err(invalid)?;
+    let crypto = keystore.crypto;
+    if keystore.version != 3 {
+        return Err(invalid(format!("unsupported version {}", keystore.version)));
+    }
+    if crypto.cipher != "aes-128-ctr" {
+        return Err(invalid(format!("unsupported cipher {}", crypto.cipher)));
+    }
+    if crypto.kdf != "scrypt" {
+        return Err(invalid(format!("unsupported kdf {}", crypto.kdf)));
+    }
+    let params = crypto.kdfparams;
+    if params.dklen != DKLEN {
+        return Err(invalid("bad kdfparams"));
+    }
+    check_scrypt_cost(params.n, params.r, params.p)?;
+
+    let salt = hex::decode(params.salt).map_err(invalid)?;
+    let iv = hex::decode(crypto.cipherparams.iv).map_err(invalid)?;
+    let mut data = Zeroizing::new(hex::decode(crypto.ciphertext).map_err(invalid)?);
+    let expected_mac = hex::decode(crypto.mac).map_err(invalid)?;
+
+    let log_n = params.n.trailing_zeros() as u8;
+    let derived_key = derive_key(password, &salt, log_n, params.r, params.p)?;
+    if !bool::from(mac(&derived_key, &data)[..].ct_eq(&expected_mac[..])) {
+        return Err(Error::KeystoreWrongPassword);
+    }
+    apply_cipher(&derived_key, &iv, &mut data[..])?;
+    let key = libsecp256k1::SecretKey::parse_slice(&data).map_err(invalid)?;
+    Ok(key.into())
+}
+
+impl SecretKey {
+    /// Encrypt the key by `password` into a keystore v3 json, which can be restored by
+    /// [SecretKey::from_encrypted_keystore], or imported by wallets supporting keystore v3.
+    /// See [crate::ecc::keystore].
+    pub fn to_encrypted_keystore(&self, password: &str) -> Result<String> {
+        encrypt(self, password, SCRYPT_LOG_N)
+    }
+
+    /// Decrypt a keystore v3 json by `password`. Return [Error::KeystoreWrongPassword] if the
+    /// password is wrong.
+    pub fn from_encrypted_keystore(json: &str, password: &str) -> Result<Self> {
+        decrypt(json, password)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A light cost of scrypt, so that tests don't take long.
+    const TEST_LOG_N: u8 = 10;
+
+    #[test]
+    fn test_keystore_round_trip() {
+        let key = SecretKey::random();
+        let json = encrypt(&key, "correct horse", TEST_LOG_N).unwrap();
+        let restored = SecretKey::from_encrypted_keystore(&json, "correct horse").unwrap();
+        assert_eq!(restored, key);
+
+        let keystore: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(keystore["version"], 3);
+        assert_eq!(keystore["crypto"]["kdfparams"]["n"], 1 << TEST_LOG_N);
+        assert_eq!(keystore["address"], hex::encode(key.address()));
+    }
+
+    #[test]
+    fn test_keystore_wrong_password() {
+        let key = SecretKey::random();
+        let json = encrypt(&key, "correct horse", TEST_LOG_N).unwrap();
+        assert!(matches!(
+            SecretKey::from_encrypted_keystore(&json, "battery staple"),
+            Err(Error::KeystoreWrongPassword)
+        ));
+        assert!(matches!(
+            SecretKey::from_encrypted_keystore("{}", "correct horse"),
+            Err(Error::InvalidKeystore(_))
+        ));
+    }
+
+    /// The scrypt test vector of Web3 Secret Storage Definition.
+    #[test]
+    fn test_keystore_official_vector() {
+        let json = r#"{
+            "crypto": {
+                "cipher": "aes-128-ctr",
+                "cipherparams": { "iv": "83dbcc02d8ccb40e466191a123791e0e" },
+                "ciphertext": "d172bf743a674da9cdad04534d56926ef8358534d458fffccd4e6ad2fbde479c",
+                "kdf": "scrypt",
+                "kdfparams": {
+                    "dklen": 32,
+                    "n": 262144,
+                    "p": 8,
+                    "r": 1,
+                    "salt": "ab0c7876052600dd703518d6fc3fe8984592145b591fc8fb5c6d43190334ba19"
+                },
+                "mac": "2103ac29920d71da29f15d75b4a16dbe95cfd7ff8faea1056c33131d846e3097"
+            },
+            "id": "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+            "version": 3
+        }"#;
+        let key = SecretKey::from_encrypted_keystore(json, "testpassword").unwrap();
+        assert_eq!(
+            hex::encode(key.ser()),
+            "7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d"
+        );
+    }
+
+    #[test]
+    fn test_keystore_costly_kdfparams() {
+        let key = SecretKey::random();
+        let json = encrypt(&key, "correct horse", TEST_LOG_N).unwrap();
+        let with_params = |n: u64, r: u32, p: u32| {
+            let mut keystore: serde_json::Value = serde_json::from_str(&json).unwrap();
+            let params = &mut keystore["crypto"]["kdfparams"];
+            params["n"] = n.into();
+            params["r"] = r.into();
+            params["p"] = p.into();
+            keystore.to_string()
+        };
+
+        for (n, r, p) in [
+            (1 << 40, SCRYPT_R, SCRYPT_P),
+            (1 << 10, u32::MAX, 1),
+            (1 << 18, SCRYPT_R, 8),
+            (1 << 10, 0, 1),
+            (1 << 10, SCRYPT_R, 0),
+        ] {
+            assert!(matches!(
+                SecretKey::from_encrypted_keystore(&with_params(n, r, p), "correct horse"),
+                Err(Error::InvalidKeystore(_))
+            ));
+        }
+        let restored = SecretKey::from_encrypted_keystore(
+            &with_params(1 << TEST_LOG_N, SCRYPT_R, SCRYPT_P),
+            "correct horse",
+        )
+        .unwrap();
+        assert_eq!(restored, key);
+    }
+}
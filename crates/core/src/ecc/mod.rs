@@ -17,6 +17,7 @@ use subtle::CtOption;
 use crate::error::Error;
 use crate::error::Result;
 pub mod elgamal;
+pub mod keystore;
 pub mod signers;
 mod types;
 use elliptic_curve::generic_array::typenum::U32;
@@ -286,6 +287,13 @@ impl SecretKey {
         Self(libsecp256k1::SecretKey::random(&mut rng))
     }
 
+    /// Generate a key deterministically from `seed`, the same seed always gives the same key.
+    /// It's useful for reproducible tests, never use a guessable seed for a real key.
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        let mut rng = Hc128Rng::from_seed(seed);
+        Self(libsecp256k1::SecretKey::random(&mut rng))
+    }
+
     pub fn address(&self) -> PublicKeyAddress {
         secret_key_address(self)
     }
@@ -353,6 +361,21 @@ pub mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_secret_key_from_seed() {
+        let key1 = SecretKey::from_seed([1u8; 32]);
+        let key2 = SecretKey::from_seed([1u8; 32]);
+        let key3 = SecretKey::from_seed([2u8; 32]);
+
+        assert_eq!(key1.ser(), key2.ser());
+        assert_eq!(key1.address(), key2.address());
+        assert_eq!(key1.sign("rings"), key2.sign("rings"));
+
+        assert_ne!(key1.ser(), key3.ser());
+        assert_ne!(key1.address(), key3.address());
+        assert_ne!(key1.sign("rings"), key3.sign("rings"));
+    }
+
     #[test]
     fn test_parse_to_string_with_sha10x00() {
         let s = "65860affb4b570dba06db294aa7c676f68e04a5bf2721243ad3cbc05a79c68c0";
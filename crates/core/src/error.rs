@@ -34,6 +34,12 @@ pub enum Error {
     #[error("private bad format")]
     PrivateKeyBadFormat,
 
+    #[error("Invalid keystore: {0}")]
+    InvalidKeystore(String),
+
+    #[error("Wrong password of keystore")]
+    KeystoreWrongPassword,
+
     #[error("Invalid Transport")]
     InvalidTransport,
 
@@ -112,6 +118,30 @@ pub enum Error {
     #[error("Gzip decode error.")]
     GzipDecode,
 
+    #[error("Unsupported protocol version {0}")]
+    UnsupportedProtocolVersion(u8),
+
+    #[error("Unsupported compression algorithm tag {0}")]
+    UnsupportedCompression(u8),
+
+    #[error("Compression level {1} is out of range for {0:?}")]
+    InvalidCompressionLevel(crate::message::CompressionAlgorithm, u32),
+
+    #[error("Compress data failed: {0}")]
+    Compress(String),
+
+    #[error("Decompress data failed: {0}")]
+    Decompress(String),
+
+    #[error("Handshake codec error: {0}")]
+    HandshakeCodec(String),
+
+    #[error("Sdp is encoded by {0:?} instead of {1:?}")]
+    HandshakeCodecMismatch(
+        crate::message::HandshakeCodec,
+        crate::message::HandshakeCodec,
+    ),
+
     #[error("Failed on promise, state is not succeeded")]
     PromiseStateFailed,
 
@@ -160,6 +190,9 @@ pub enum Error {
     #[error("Failed on write successors")]
     FailedToWriteSuccessors,
 
+    #[error("Max number of successors should be at least 1, got {0}")]
+    InvalidSuccessorMax(u8),
+
     #[error("Failed on TryInto VNode")]
     PeerRingInvalidVNode,
 
@@ -175,6 +208,9 @@ pub enum Error {
     #[error("PeerRing RWLock unlock failed")]
     PeerRingUnlockFailed,
 
+    #[error("Measure error: {0}")]
+    Measure(String),
+
     #[error("Cannot seek did in swarm table, {0}")]
     SwarmMissDidInTable(crate::dht::Did),
 
@@ -190,6 +226,9 @@ pub enum Error {
     #[error("Load message failed with message: {0}")]
     SwarmLoadMessageRecvFailed(String),
 
+    #[error("Failed to handle message: {0}")]
+    SwarmHandleMessage(String),
+
     #[error("Default transport is not connected")]
     SwarmDefaultTransportNotConnected,
 
@@ -297,6 +336,9 @@ pub enum Error {
     #[error("Current node is not the next hop of message")]
     InvalidNextHop,
 
+    #[error("Route through more than one relay hop is not supported")]
+    MultiHopRoute,
+
     #[error("Adjacent elements in path cannot be equal")]
     InvalidRelayPath,
 
@@ -312,6 +354,9 @@ pub enum Error {
     #[error("Cannot get next hop when sending message")]
     NoNextHop,
 
+    #[error("Data channel to {0} is not open yet")]
+    ChannelNotReady(crate::dht::Did),
+
     #[error("To generate REPORT, you should provide SEND")]
     ReportNeedSend,
 
@@ -329,6 +374,12 @@ pub enum Error {
     #[error("Sled error, {0}")]
     SledError(sled::Error),
 
+    #[error("Unsupported storage codec tag {0}")]
+    UnsupportedStorageCodec(u8),
+
+    #[error("Storage codec error: {0}")]
+    StorageCodec(String),
+
     #[error("entry not found")]
     EntryNotFound,
 
@@ -344,6 +395,9 @@ pub enum Error {
     #[error("Failed to build swarm: {0}")]
     SwarmBuildFailed(String),
 
+    #[error("Invalid swarm config: {0}")]
+    InvalidSwarmConfig(String),
+
     #[error("Message invalid: {0}")]
     InvalidMessage(String),
 
@@ -356,6 +410,42 @@ pub enum Error {
     #[error("Message has {0} bytes which is too large")]
     MessageTooLarge(usize),
 
+    #[error("Failed to encode payload of {0}")]
+    PayloadEncode(String),
+
+    #[error("Send buffer of data channel is full, {0} bytes buffered")]
+    SendBufferFull(usize),
+
+    #[error("Timeout when waiting for reply of transaction {0}")]
+    WaitReplyTimeout(uuid::Uuid),
+
+    #[error("Timeout when waiting for data channel of {0} to open")]
+    WaitConnectionTimeout(crate::dht::Did),
+
+    #[error("Handshake with {0} failed after too many renegotiations")]
+    HandshakeRenegotiationExhausted(crate::dht::Did),
+
+    #[error("Connection to {0} is in an invalid signaling state: {1}")]
+    InvalidSignalingState(crate::dht::Did, String),
+
+    #[error("Outbound queue is dropped before the message is sent")]
+    OutboundQueueDropped,
+
+    #[error("File transfer {0} is rejected by receiver")]
+    FileTransferRejected(uuid::Uuid),
+
+    #[error("Timeout when waiting for ack of file transfer {0}")]
+    FileTransferTimeout(uuid::Uuid),
+
+    #[error("Reassembly budget is exceeded, chunked message {0} is rejected")]
+    ReassemblyBudgetExceeded(uuid::Uuid),
+
+    #[error("Chunk of message {0} is rejected: {1}")]
+    InvalidChunk(uuid::Uuid, String),
+
+    #[error("Message recorder is full or stopped, the record is dropped")]
+    RecorderUnavailable,
+
     #[cfg(feature = "wasm")]
     #[error("Cannot get property {0} from JsValue")]
     FailedOnGetProperty(String),
@@ -375,6 +465,15 @@ pub enum Error {
     #[error("Session is expired")]
     SessionExpired,
 
+    #[error("Capacity of inbox should be at least 1")]
+    InvalidInboxCapacity,
+
+    #[error("Session of account {0} doesn't belong to this node")]
+    SessionAccountMismatch(crate::dht::Did),
+
+    #[error("Session of account {0} has no permission to {1}")]
+    NoPermission(crate::dht::Did, String),
+
     #[error("Transport error: {0}")]
     Transport(#[from] rings_transport::error::Error),
 
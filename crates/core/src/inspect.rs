@@ -19,6 +19,8 @@ pub struct SwarmInspect {
 pub struct ConnectionInspect {
     pub did: String,
     pub state: String,
+    #[serde(default)]
+    pub label: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
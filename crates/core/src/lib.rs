@@ -54,6 +54,15 @@
 //! - Then we can sign the auth message via some web3 provider like metamask or just with a raw private key, and create the SessionManger with
 //!   `SessionSk::new(sig, auth_info, temp_key)`.
 
+//! # Logging
+//!
+//! Logs of the hot paths are tagged by `tracing` targets of their subsystem, so they can be
+//! filtered separately, such as by `RUST_LOG=rings::relay=debug`:
+//! - `rings::swarm`: sending and receiving messages, rate limiting and connection management.
+//! - `rings::handshake`: offers, answers and ICE candidates of connections.
+//! - `rings::relay`: forwarding and rerouting messages for other nodes.
+//! - `rings::snark`: proving and verifying SNARK tasks, in `rings-node` and `rings-snark`.
+
 //! # WASM Supported
 //! ```shell
 //! cargo build -p rings-core --target=wasm32-unknown-unknown --features wasm --no-default-features
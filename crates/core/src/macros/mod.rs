@@ -32,7 +32,7 @@
 //!                 _ = timeout => self
 //!                     .stabilize()
 //!                     .await
-//!                     .unwrap_or_else(|e| log::error!("failed to stabilize {:?}", e)),
+//!                     .unwrap_or_else(|e| tracing::error!("failed to stabilize {:?}", e)),
 //!             }
 //!         }
 //!     }
@@ -65,7 +65,7 @@
 //!                 caller
 //!                     .stabilize()
 //!                     .await
-//!                     .unwrap_or_else(|e| log::error!("failed to stabilize {:?}", e));
+//!                     .unwrap_or_else(|e| tracing::error!("failed to stabilize {:?}", e));
 //!             }))
 //!         };
 //!         poll!(func, 25000);
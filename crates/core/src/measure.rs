@@ -2,8 +2,12 @@
 //! It is used to assess the reliability of remote peers.
 #![warn(missing_docs)]
 use async_trait::async_trait;
+use rings_transport::core::transport::WebrtcConnectionState;
+use serde::Deserialize;
+use serde::Serialize;
 
 use crate::dht::Did;
+use crate::error::Result;
 
 /// Type of Measure, see [Measure].
 #[cfg(not(feature = "wasm"))]
@@ -13,6 +17,19 @@ pub type MeasureImpl = Box<dyn BehaviourJudgement + Send + Sync>;
 #[cfg(feature = "wasm")]
 pub type MeasureImpl = Box<dyn BehaviourJudgement>;
 
+/// Function to score the quality of a connection, see [default_quality].
+#[cfg(not(feature = "wasm"))]
+pub type QualityFn = Box<dyn Fn(&QualityInput) -> f64 + Send + Sync>;
+
+/// Function to score the quality of a connection, see [crate::measure::default_quality].
+#[cfg(feature = "wasm")]
+pub type QualityFn = Box<dyn Fn(&QualityInput) -> f64>;
+
+/// Weight of connection state in [default_quality].
+pub const QUALITY_STATE_WEIGHT: f64 = 0.3;
+/// Weight of send success ratio in [default_quality].
+pub const QUALITY_DELIVERY_WEIGHT: f64 = 0.7;
+
 /// The tag of counters in measure.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MeasureCounter {
@@ -28,18 +45,106 @@ pub enum MeasureCounter {
     Connect,
     /// The number of disconnect.
     Disconnected,
+    /// The number of bytes of payloads sent.
+    BytesSent,
+    /// The number of bytes of frames received.
+    BytesReceived,
+}
+
+/// All counters of a peer read from [Measure], see [crate::swarm::Swarm::measure_snapshot].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MeasureSnapshot {
+    /// Count of [MeasureCounter::Sent].
+    pub sent: u64,
+    /// Count of [MeasureCounter::FailedToSend].
+    pub failed_to_send: u64,
+    /// Count of [MeasureCounter::Received].
+    pub received: u64,
+    /// Count of [MeasureCounter::FailedToReceive].
+    pub failed_to_receive: u64,
+    /// Count of [MeasureCounter::Connect].
+    pub connect: u64,
+    /// Count of [MeasureCounter::Disconnected].
+    pub disconnected: u64,
+    /// Count of [MeasureCounter::BytesSent].
+    pub bytes_sent: u64,
+    /// Count of [MeasureCounter::BytesReceived].
+    pub bytes_received: u64,
+}
+
+impl MeasureSnapshot {
+    /// Read all counters of `did` from `measure`.
+    pub async fn read<M>(measure: &M, did: Did) -> Result<Self>
+    where M: Measure + ?Sized {
+        Ok(Self {
+            sent: measure.get_count(did, MeasureCounter::Sent).await?,
+            failed_to_send: measure.get_count(did, MeasureCounter::FailedToSend).await?,
+            received: measure.get_count(did, MeasureCounter::Received).await?,
+            failed_to_receive: measure
+                .get_count(did, MeasureCounter::FailedToReceive)
+                .await?,
+            connect: measure.get_count(did, MeasureCounter::Connect).await?,
+            disconnected: measure.get_count(did, MeasureCounter::Disconnected).await?,
+            bytes_sent: measure.get_count(did, MeasureCounter::BytesSent).await?,
+            bytes_received: measure
+                .get_count(did, MeasureCounter::BytesReceived)
+                .await?,
+        })
+    }
+}
+
+/// Inputs of a [QualityFn] for scoring a connection.
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct QualityInput {
+    /// Current state of the connection.
+    pub state: WebrtcConnectionState,
+    /// Count of [MeasureCounter::Sent] of the peer.
+    pub sent: u64,
+    /// Count of [MeasureCounter::FailedToSend] of the peer.
+    pub failed_to_send: u64,
+}
+
+impl QualityInput {
+    /// Ratio of successfully sent messages. It's 1.0 if nothing has been sent.
+    pub fn delivery_ratio(&self) -> f64 {
+        let total = self.sent + self.failed_to_send;
+        if total == 0 {
+            return 1.0;
+        }
+        self.sent as f64 / total as f64
+    }
+}
+
+/// The default [QualityFn], returns a score in 0.0..=1.0, higher is better.
+///
+/// A peer which is neither connected nor connecting always scores 0.0. Otherwise the score is
+/// `QUALITY_STATE_WEIGHT * state + QUALITY_DELIVERY_WEIGHT * delivery_ratio`, where `state` is
+/// 1.0 when connected and 0.5 when the connection is still being established.
+pub fn default_quality(input: &QualityInput) -> f64 {
+    let state = match input.state {
+        WebrtcConnectionState::Connected => 1.0,
+        WebrtcConnectionState::New | WebrtcConnectionState::Connecting => 0.5,
+        _ => return 0.0,
+    };
+    QUALITY_STATE_WEIGHT * state + QUALITY_DELIVERY_WEIGHT * input.delivery_ratio()
 }
 
 /// `Measure` is used to assess the reliability of peers by counting their behaviour.
 /// It currently count the number of sent and received messages in a given period (1 hour).
 /// The method [Measure::incr] should be called in the proper places.
+///
+/// Both methods may fail, for example when the counters are kept in a database. Swarm logs
+/// such errors and carries on, so a flaky measure never blocks connectivity.
 #[cfg_attr(feature = "wasm", async_trait(?Send))]
 #[cfg_attr(not(feature = "wasm"), async_trait)]
 pub trait Measure {
     /// `incr` increments the counter of the given peer.
-    async fn incr(&self, did: Did, counter: MeasureCounter);
+    async fn incr(&self, did: Did, counter: MeasureCounter) -> Result<()>;
+    /// `incr_by` increases the counter of the given peer by `n`, such as bytes of a message.
+    async fn incr_by(&self, did: Did, counter: MeasureCounter, n: u64) -> Result<()>;
     /// `get_count` returns the counter of the given peer.
-    async fn get_count(&self, did: Did, counter: MeasureCounter) -> u64;
+    async fn get_count(&self, did: Did, counter: MeasureCounter) -> Result<u64>;
 }
 
 /// `BehaviourJudgement` trait defines a method `good` for assessing whether a node behaves well.
@@ -58,9 +163,19 @@ pub trait BehaviourJudgement: Measure {
 #[cfg_attr(not(feature = "wasm"), async_trait)]
 pub trait ConnectBehaviour<const THRESHOLD: i64>: Measure {
     /// This asynchronous method returns a boolean indicating whether the node identified by `did` has a satisfactory connection behavior.
+    /// A node is considered good if its counters cannot be read.
     async fn good(&self, did: Did) -> bool {
-        let conn = self.get_count(did, MeasureCounter::Connect).await as i64;
-        let disconn = self.get_count(did, MeasureCounter::Disconnected).await as i64;
+        let counts = (
+            self.get_count(did, MeasureCounter::Connect).await,
+            self.get_count(did, MeasureCounter::Disconnected).await,
+        );
+        let (conn, disconn) = match counts {
+            (Ok(conn), Ok(disconn)) => (conn as i64, disconn as i64),
+            (Err(e), _) | (_, Err(e)) => {
+                tracing::warn!("[ConnectBehaviour] Failed to get counters of {did}: {e:?}");
+                return true;
+            }
+        };
         tracing::debug!(
             "[ConnectBehaviour] in Threadhold: {:}, connect: {:}, disconn: {:}, delta: {:}",
             THRESHOLD,
@@ -79,9 +194,15 @@ pub trait ConnectBehaviour<const THRESHOLD: i64>: Measure {
 #[cfg_attr(not(feature = "wasm"), async_trait)]
 pub trait MessageSendBehaviour<const THRESHOLD: i64>: Measure {
     /// This asynchronous method returns a boolean indicating whether the node identified by `did` has a satisfactory message sending behavior.
+    /// A node is considered good if its counters cannot be read.
     async fn good(&self, did: Did) -> bool {
-        let failed = self.get_count(did, MeasureCounter::FailedToSend).await;
-        (failed as i64) < THRESHOLD
+        match self.get_count(did, MeasureCounter::FailedToSend).await {
+            Ok(failed) => (failed as i64) < THRESHOLD,
+            Err(e) => {
+                tracing::warn!("[MessageSendBehaviour] Failed to get counters of {did}: {e:?}");
+                true
+            }
+        }
     }
 }
 
@@ -92,8 +213,14 @@ pub trait MessageSendBehaviour<const THRESHOLD: i64>: Measure {
 #[cfg_attr(not(feature = "wasm"), async_trait)]
 pub trait MessageRecvBehaviour<const THRESHOLD: i64>: Measure {
     /// This asynchronous method returns a boolean indicating whether the node identified by `did` has a satisfactory message receiving behavior.
+    /// A node is considered good if its counters cannot be read.
     async fn good(&self, did: Did) -> bool {
-        let failed = self.get_count(did, MeasureCounter::FailedToReceive).await;
-        (failed as i64) < THRESHOLD
+        match self.get_count(did, MeasureCounter::FailedToReceive).await {
+            Ok(failed) => (failed as i64) < THRESHOLD,
+            Err(e) => {
+                tracing::warn!("[MessageRecvBehaviour] Failed to get counters of {did}: {e:?}");
+                true
+            }
+        }
     }
 }
@@ -0,0 +1,309 @@
+#![warn(missing_docs)]
+//! Compression of frames sent through transport.
+//!
+//! Every frame starts with a byte of [PROTOCOL_VERSION], so that peers of another version of
+//! wire format are told apart instead of failing to decode. It's followed by a byte tagging
+//! the algorithm used to compress the rest of it, so the receiver can always decode a frame
+//! regardless of its own [CompressionConfig].
+
+use std::io::Read;
+use std::io::Write;
+
+use bytes::Bytes;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::consts::PROTOCOL_VERSION;
+use crate::consts::TRANSPORT_MAX_SIZE;
+use crate::error::Error;
+use crate::error::Result;
+
+/// Tag of frames that are not compressed.
+const FRAME_TAG_RAW: u8 = 0;
+
+/// Supported compression algorithms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CompressionAlgorithm {
+    /// Deflate, level is in 0..=9.
+    Deflate,
+    /// Zstandard, level is in 1..=22. Not available on wasm.
+    Zstd,
+    /// LZ4 block format. It has no levels, so level should be 0.
+    Lz4,
+}
+
+impl CompressionAlgorithm {
+    /// The tag written in frame header.
+    pub fn tag(&self) -> u8 {
+        match self {
+            Self::Deflate => 1,
+            Self::Zstd => 2,
+            Self::Lz4 => 3,
+        }
+    }
+
+    /// Get algorithm from the tag in frame header.
+    pub fn from_tag(tag: u8) -> Result<Self> {
+        match tag {
+            1 => Ok(Self::Deflate),
+            2 => Ok(Self::Zstd),
+            3 => Ok(Self::Lz4),
+            _ => Err(Error::UnsupportedCompression(tag)),
+        }
+    }
+
+    fn level_range(&self) -> std::ops::RangeInclusive<u32> {
+        match self {
+            Self::Deflate => 0..=9,
+            Self::Zstd => 1..=22,
+            Self::Lz4 => 0..=0,
+        }
+    }
+
+    fn compress(&self, data: &[u8], level: u32) -> Result<Vec<u8>> {
+        match self {
+            Self::Deflate => {
+                let mut ec = DeflateEncoder::new(Vec::new(), Compression::new(level));
+                ec.write_all(data)
+                    .map_err(|e| Error::Compress(e.to_string()))?;
+                ec.finish().map_err(|e| Error::Compress(e.to_string()))
+            }
+            #[cfg(feature = "std")]
+            Self::Zstd => {
+                zstd::bulk::compress(data, level as i32).map_err(|e| Error::Compress(e.to_string()))
+            }
+            #[cfg(not(feature = "std"))]
+            Self::Zstd => Err(Error::UnsupportedCompression(self.tag())),
+            Self::Lz4 => Ok(lz4_flex::block::compress_prepend_size(data)),
+        }
+    }
+
+    fn decompress(&self, data: &[u8], max_size: usize) -> Result<Vec<u8>> {
+        match self {
+            Self::Deflate => read_limited(DeflateDecoder::new(data), max_size),
+            #[cfg(feature = "std")]
+            Self::Zstd => {
+                let decoder = zstd::stream::read::Decoder::new(data)
+                    .map_err(|e| Error::Decompress(e.to_string()))?;
+                read_limited(decoder, max_size)
+            }
+            #[cfg(not(feature = "std"))]
+            Self::Zstd => Err(Error::UnsupportedCompression(self.tag())),
+            Self::Lz4 => {
+                let size = data
+                    .get(..4)
+                    .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+                    .ok_or_else(|| Error::Decompress("Missing size of lz4 block".to_string()))?;
+                if size > max_size {
+                    return Err(Error::MessageTooLarge(size));
+                }
+                lz4_flex::block::decompress_size_prepended(data)
+                    .map_err(|e| Error::Decompress(e.to_string()))
+            }
+        }
+    }
+}
+
+/// Read all decompressed data, but no more than `max_size`.
+fn read_limited(reader: impl Read, max_size: usize) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    reader
+        .take(max_size as u64 + 1)
+        .read_to_end(&mut buf)
+        .map_err(|e| Error::Decompress(e.to_string()))?;
+    if buf.len() > max_size {
+        return Err(Error::MessageTooLarge(buf.len()));
+    }
+    Ok(buf)
+}
+
+/// Config of compressing frames before sending.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompressionConfig {
+    /// Algorithm used to compress frames.
+    pub algorithm: CompressionAlgorithm,
+    /// Compression level, the valid range depends on algorithm.
+    pub level: u32,
+    /// Frames smaller than threshold in bytes are sent without compression.
+    pub threshold: usize,
+}
+
+impl CompressionConfig {
+    /// Check if the level is valid for the algorithm.
+    pub fn validate(&self) -> Result<()> {
+        if !self.algorithm.level_range().contains(&self.level) {
+            return Err(Error::InvalidCompressionLevel(self.algorithm, self.level));
+        }
+        Ok(())
+    }
+}
+
+/// Wrap data into a frame, compress it if config is provided and data reaches the threshold.
+/// The data is kept uncompressed if compression doesn't make it smaller.
+/// The config should be checked by [CompressionConfig::validate] first, which is done once when
+/// swarm is built.
+pub fn encode_frame(data: &[u8], config: Option<&CompressionConfig>) -> Result<Bytes> {
+    if let Some(config) = config.filter(|c| data.len() >= c.threshold) {
+        let compressed = config.algorithm.compress(data, config.level)?;
+        if compressed.len() < data.len() {
+            let mut frame = Vec::with_capacity(compressed.len() + 2);
+            frame.push(PROTOCOL_VERSION);
+            frame.push(config.algorithm.tag());
+            frame.extend_from_slice(&compressed);
+            return Ok(frame.into());
+        }
+    }
+
+    let mut frame = Vec::with_capacity(data.len() + 2);
+    frame.push(PROTOCOL_VERSION);
+    frame.push(FRAME_TAG_RAW);
+    frame.extend_from_slice(data);
+    Ok(frame.into())
+}
+
+/// Unwrap a frame, decompress it by the algorithm tagged in its header.
+pub fn decode_frame(frame: &[u8]) -> Result<Bytes> {
+    decode_frame_limited(frame, TRANSPORT_MAX_SIZE)
+}
+
+/// Unwrap a frame like [decode_frame], but fail with [Error::MessageTooLarge] if the data
+/// is larger than `max_size`, before decompressing more than that.
+/// A frame of another protocol version fails with [Error::UnsupportedProtocolVersion].
+pub fn decode_frame_limited(frame: &[u8], max_size: usize) -> Result<Bytes> {
+    let (version, frame) = frame
+        .split_first()
+        .ok_or_else(|| Error::Decompress("Empty frame".to_string()))?;
+    if *version != PROTOCOL_VERSION {
+        return Err(Error::UnsupportedProtocolVersion(*version));
+    }
+    let (tag, data) = frame
+        .split_first()
+        .ok_or_else(|| Error::Decompress("Empty frame".to_string()))?;
+    if *tag == FRAME_TAG_RAW {
+        if data.len() > max_size {
+            return Err(Error::MessageTooLarge(data.len()));
+        }
+        return Ok(Bytes::copy_from_slice(data));
+    }
+    let algorithm = CompressionAlgorithm::from_tag(*tag)?;
+    algorithm.decompress(data, max_size).map(Bytes::from)
+}
+
+#[cfg(not(feature = "wasm"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_data() -> Vec<u8> {
+        "The quick brown fox jumps over the lazy dog. "
+            .repeat(100)
+            .into_bytes()
+    }
+
+    #[test]
+    fn test_round_trip_each_algorithm() {
+        let data = sample_data();
+        for (algorithm, level) in [
+            (CompressionAlgorithm::Deflate, 6),
+            (CompressionAlgorithm::Zstd, 3),
+            (CompressionAlgorithm::Lz4, 0),
+        ] {
+            let config = CompressionConfig {
+                algorithm,
+                level,
+                threshold: 0,
+            };
+            let frame = encode_frame(&data, Some(&config)).unwrap();
+            assert_eq!(frame[0], PROTOCOL_VERSION);
+            assert_eq!(frame[1], algorithm.tag());
+            assert!(frame.len() < data.len());
+            assert_eq!(decode_frame(&frame).unwrap().to_vec(), data);
+        }
+    }
+
+    #[test]
+    fn test_decode_selects_algorithm_by_header() {
+        let data = sample_data();
+        let deflate = CompressionConfig {
+            algorithm: CompressionAlgorithm::Deflate,
+            level: 6,
+            threshold: 0,
+        };
+        let frame = encode_frame(&data, Some(&deflate)).unwrap();
+
+        // Pretend the frame is lz4, it should not be decoded as deflate.
+        let mut retagged = frame.to_vec();
+        retagged[1] = CompressionAlgorithm::Lz4.tag();
+        assert!(decode_frame(&retagged).is_err());
+
+        let mut unknown = frame.to_vec();
+        unknown[1] = 42;
+        assert!(matches!(
+            decode_frame(&unknown),
+            Err(Error::UnsupportedCompression(42))
+        ));
+
+        let mut old = frame.to_vec();
+        old[0] = PROTOCOL_VERSION - 1;
+        assert!(matches!(
+            decode_frame(&old),
+            Err(Error::UnsupportedProtocolVersion(v)) if v == PROTOCOL_VERSION - 1
+        ));
+
+        assert_eq!(decode_frame(&frame).unwrap().to_vec(), data);
+    }
+
+    #[test]
+    fn test_decode_frame_limited() {
+        let data = sample_data();
+        let deflate = CompressionConfig {
+            algorithm: CompressionAlgorithm::Deflate,
+            level: 6,
+            threshold: 0,
+        };
+        for config in [None, Some(&deflate)] {
+            let frame = encode_frame(&data, config).unwrap();
+            assert_eq!(
+                decode_frame_limited(&frame, data.len()).unwrap().to_vec(),
+                data
+            );
+            assert!(matches!(
+                decode_frame_limited(&frame, data.len() - 1),
+                Err(Error::MessageTooLarge(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_threshold_and_level_bounds() {
+        let data = sample_data();
+        let config = CompressionConfig {
+            algorithm: CompressionAlgorithm::Zstd,
+            level: 3,
+            threshold: data.len() + 1,
+        };
+        let frame = encode_frame(&data, Some(&config)).unwrap();
+        assert_eq!(frame[1], FRAME_TAG_RAW);
+        assert_eq!(decode_frame(&frame).unwrap().to_vec(), data);
+
+        for (algorithm, level) in [
+            (CompressionAlgorithm::Deflate, 10),
+            (CompressionAlgorithm::Zstd, 0),
+            (CompressionAlgorithm::Zstd, 23),
+            (CompressionAlgorithm::Lz4, 1),
+        ] {
+            let config = CompressionConfig {
+                algorithm,
+                level,
+                threshold: 0,
+            };
+            assert!(matches!(
+                config.validate(),
+                Err(Error::InvalidCompressionLevel(a, l)) if a == algorithm && l == level
+            ));
+        }
+    }
+}
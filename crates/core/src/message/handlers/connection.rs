@@ -3,13 +3,18 @@ use async_trait::async_trait;
 use crate::dht::types::Chord;
 use crate::dht::types::CorrectChord;
 use crate::dht::PeerRingAction;
+use crate::dht::SuccessorWriter;
 use crate::dht::TopoInfo;
 use crate::error::Error;
 use crate::error::Result;
+use crate::message::types::ConnectNodeRenegotiate;
 use crate::message::types::ConnectNodeReport;
 use crate::message::types::ConnectNodeSend;
 use crate::message::types::FindSuccessorReport;
 use crate::message::types::FindSuccessorSend;
+use crate::message::types::IceCandidate;
+use crate::message::types::LookupProbeReport;
+use crate::message::types::LookupProbeSend;
 use crate::message::types::Message;
 use crate::message::types::QueryForTopoInfoReport;
 use crate::message::types::QueryForTopoInfoSend;
@@ -92,6 +97,35 @@ impl HandleMsg<ConnectNodeReport> for MessageHandler {
     }
 }
 
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<ConnectNodeRenegotiate> for MessageHandler {
+    async fn handle(&self, ctx: &MessagePayload, msg: &ConnectNodeRenegotiate) -> Result<()> {
+        if self.dht.did != ctx.relay.destination {
+            self.transport.forward_payload(ctx, None).await
+        } else {
+            self.transport
+                .answer_renegotiation(ctx.relay.origin_sender(), self.inner_callback(), msg)
+                .await
+        }
+    }
+}
+
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<IceCandidate> for MessageHandler {
+    async fn handle(&self, ctx: &MessagePayload, msg: &IceCandidate) -> Result<()> {
+        if self.dht.did != ctx.relay.destination {
+            self.transport.forward_payload(ctx, None).await
+        } else {
+            self.transport
+                .add_ice_candidate(ctx.relay.origin_sender(), msg.clone())
+                .await;
+            Ok(())
+        }
+    }
+}
+
 #[cfg_attr(feature = "wasm", async_trait(?Send))]
 #[cfg_attr(not(feature = "wasm"), async_trait)]
 impl HandleMsg<FindSuccessorSend> for MessageHandler {
@@ -134,10 +168,13 @@ impl HandleMsg<FindSuccessorReport> for MessageHandler {
 
         match &msg.handler {
             FindSuccessorReportHandler::FixFingerTable | FindSuccessorReportHandler::Connect => {
-                if msg.did != self.dht.did {
+                if msg.did != self.dht.did && self.transport.is_connected(msg.did) {
+                    // Already connected, it may be a successor dropped by a shorter sequence.
+                    self.dht.successors().update(msg.did)?;
+                } else if msg.did != self.dht.did {
                     let offer_msg = self
                         .transport
-                        .prepare_connection_offer(msg.did, self.inner_callback())
+                        .prepare_connection_offer(msg.did, self.inner_callback(), None)
                         .await?;
                     self.transport
                         .send_message(Message::ConnectNodeSend(offer_msg), msg.did)
@@ -151,6 +188,45 @@ impl HandleMsg<FindSuccessorReport> for MessageHandler {
     }
 }
 
+/// Answer the routing decision of a lookup without forwarding it.
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<LookupProbeSend> for MessageHandler {
+    async fn handle(&self, ctx: &MessagePayload, msg: &LookupProbeSend) -> Result<()> {
+        if self.dht.did != ctx.relay.destination {
+            return self.transport.forward_payload(ctx, None).await;
+        }
+
+        let (candidate, resolved) = match self.dht.find_successor(msg.did)? {
+            PeerRingAction::Some(did) => (did, true),
+            PeerRingAction::RemoteAction(next, _) => (next, false),
+            act => return Err(Error::PeerRingUnexpectedAction(act)),
+        };
+        self.transport
+            .send_report_message(
+                ctx,
+                Message::LookupProbeReport(LookupProbeReport {
+                    did: msg.did,
+                    candidate,
+                    resolved,
+                }),
+            )
+            .await
+    }
+}
+
+/// The report is delivered to the waiting sender by swarm callback, just forward it here.
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<LookupProbeReport> for MessageHandler {
+    async fn handle(&self, ctx: &MessagePayload, _msg: &LookupProbeReport) -> Result<()> {
+        if self.dht.did != ctx.relay.destination {
+            return self.transport.forward_payload(ctx, None).await;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(not(feature = "wasm"))]
 #[cfg(test)]
 pub mod tests {
@@ -1,11 +1,35 @@
 use async_trait::async_trait;
 
+use crate::dht::Did;
 use crate::error::Result;
+use crate::message::handlers::pubsub::next_hop_to_key;
 use crate::message::types::CustomMessage;
+use crate::message::types::EncryptedMessage;
+use crate::message::types::Message;
+use crate::message::types::RouteToKey;
 use crate::message::HandleMsg;
 use crate::message::MessageHandler;
 use crate::message::MessagePayload;
 use crate::message::PayloadSender;
+use crate::swarm::Swarm;
+
+impl Swarm {
+    /// Send [Message] to the node responsible for `key`, such as the node storing the virtual
+    /// node of `key`, wrapped in [RouteToKey]. It's received by
+    /// [crate::swarm::callback::SwarmCallback::on_inbound] of the responsible node, including
+    /// current node. A [RouteToKey] cannot be sent by it again.
+    pub async fn send_to_key(&self, key: Did, msg: Message) -> Result<uuid::Uuid> {
+        let msg = Message::RouteToKey(RouteToKey::new(key, &msg)?);
+        match next_hop_to_key(&self.dht, key)? {
+            None => {
+                let did = self.did();
+                let payload = MessagePayload::new_send(msg, self.transport.session_sk(), did, key)?;
+                self.deliver_to_self(payload).await
+            }
+            Some(next) => self.transport.send_message_by_hop(msg, key, next).await,
+        }
+    }
+}
 
 #[cfg_attr(feature = "wasm", async_trait(?Send))]
 #[cfg_attr(not(feature = "wasm"), async_trait)]
@@ -17,3 +41,37 @@ impl HandleMsg<CustomMessage> for MessageHandler {
         Ok(())
     }
 }
+
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<EncryptedMessage> for MessageHandler {
+    /// Relays forward the message without reading it.
+    /// It's decrypted before handling when reaching the destination.
+    async fn handle(&self, ctx: &MessagePayload, _: &EncryptedMessage) -> Result<()> {
+        if self.dht.did != ctx.relay.destination {
+            self.transport.forward_payload(ctx, None).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<RouteToKey> for MessageHandler {
+    /// Forward the message to the next hop to the node responsible for the key, which passes
+    /// it to the callback. The destination of payload is the key, so it's never passed to the
+    /// callback by destination.
+    async fn handle(&self, ctx: &MessagePayload, msg: &RouteToKey) -> Result<()> {
+        match next_hop_to_key(&self.dht, msg.key)? {
+            None => {
+                // Check it before passing to the callback, which reads it by RouteToKey::message.
+                msg.message()?;
+                if let Err(e) = self.swarm_callback.on_inbound(ctx).await {
+                    tracing::error!("Failed to handle message to key {}: {e:?}", msg.key);
+                }
+                Ok(())
+            }
+            Some(next) => self.transport.forward_payload(ctx, Some(next)).await,
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use async_trait::async_trait;
+
+use crate::error::Result;
+use crate::message::types::FileChunk;
+use crate::message::types::FileChunkAck;
+use crate::message::types::Message;
+use crate::message::HandleMsg;
+use crate::message::MessageHandler;
+use crate::message::MessagePayload;
+use crate::message::PayloadSender;
+
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<FileChunk> for MessageHandler {
+    /// Write the chunk by the file receiver of swarm, then ack it to the sender.
+    async fn handle(&self, ctx: &MessagePayload, msg: &FileChunk) -> Result<()> {
+        if self.dht.did != ctx.relay.destination {
+            return self.transport.forward_payload(ctx, None).await;
+        }
+        let ack = self
+            .transport
+            .receive_file_chunk(ctx.transaction.signer(), msg)
+            .await;
+        self.transport
+            .send_report_message(ctx, Message::FileChunkAck(ack))
+            .await
+    }
+}
+
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<FileChunkAck> for MessageHandler {
+    async fn handle(&self, ctx: &MessagePayload, msg: &FileChunkAck) -> Result<()> {
+        if self.dht.did != ctx.relay.destination {
+            return self.transport.forward_payload(ctx, None).await;
+        }
+        self.transport.resolve_file_ack(msg);
+        Ok(())
+    }
+}
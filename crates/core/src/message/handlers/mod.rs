@@ -30,6 +30,10 @@ use crate::swarm::transport::SwarmTransport;
 pub mod connection;
 /// Operator and Handler for CustomMessage
 pub mod custom;
+/// Operator and Handler for file transfer
+pub mod file;
+/// Operator and Handler for topic publishing and subscribing
+pub mod pubsub;
 /// Operator and handler for DHT stablization
 pub mod stabilization;
 /// Operator and Handler for Storage
@@ -64,6 +68,16 @@ impl MessageHandler {
         }
     }
 
+    /// Check the session signing the transaction of `payload` is allowed to send `msg`.
+    /// Return [Error::NoPermission] if it's a delegated session out of its scope.
+    pub(crate) fn check_scope(&self, payload: &MessagePayload, msg: &Message) -> Result<()> {
+        payload
+            .transaction
+            .verification
+            .session
+            .check_scope(msg.required_scope(), msg.method())
+    }
+
     fn inner_callback(&self) -> InnerSwarmCallback {
         InnerSwarmCallback::new(self.transport.clone(), self.swarm_callback.clone())
     }
@@ -137,7 +151,9 @@ impl MessageHandler {
                 Ok(())
             }
             PeerRingAction::RemoteAction(did, PeerRingRemoteAction::TryConnect) => {
-                self.transport.connect(*did, self.inner_callback()).await?;
+                self.transport
+                    .connect(*did, self.inner_callback(), None)
+                    .await?;
                 Ok(())
             }
             PeerRingAction::RemoteAction(did, PeerRingRemoteAction::Notify(target_id)) => {
@@ -0,0 +1,245 @@
+#![warn(missing_docs)]
+
+use async_trait::async_trait;
+
+use crate::consts::MAX_TTL_MS;
+use crate::consts::TOPIC_MAX_SUBSCRIBERS;
+use crate::consts::TOPIC_MAX_SUBSCRIPTIONS_PER_SUBSCRIBER;
+use crate::consts::TOPIC_SUBSCRIPTION_TTL_MS;
+use crate::dht::vnode::VirtualNode;
+use crate::dht::Chord;
+use crate::dht::Did;
+use crate::dht::PeerRing;
+use crate::dht::PeerRingAction;
+use crate::error::Error;
+use crate::error::Result;
+use crate::message::types::Message;
+use crate::message::types::PublishTopic;
+use crate::message::types::SubscribeTopic;
+use crate::message::HandleMsg;
+use crate::message::MessageHandler;
+use crate::message::MessagePayload;
+use crate::message::PayloadSender;
+use crate::swarm::transport::SwarmTransport;
+use crate::swarm::Swarm;
+use crate::utils::Clock;
+
+/// Get the next hop to the node responsible for `key`, which is the node storing the
+/// virtual node of `key`. Return None if it's current node.
+pub(crate) fn next_hop_to_key(dht: &PeerRing, key: Did) -> Result<Option<Did>> {
+    match dht.find_successor(key)? {
+        PeerRingAction::Some(_) => Ok(None),
+        PeerRingAction::RemoteAction(next, _) if next == dht.did => Ok(None),
+        PeerRingAction::RemoteAction(next, _) => Ok(Some(next)),
+        act => Err(Error::PeerRingUnexpectedAction(act)),
+    }
+}
+
+/// Get the next hop to the node responsible for `topic`, see [next_hop_to_key].
+fn next_hop_to_topic(dht: &PeerRing, topic: &str) -> Result<Option<Did>> {
+    next_hop_to_key(dht, VirtualNode::gen_did(topic)?)
+}
+
+/// Record a subscription on the node responsible for the topic, or remove it if `ttl_ms` is 0.
+/// A new subscription beyond [TOPIC_MAX_SUBSCRIBERS] of the topic or
+/// [TOPIC_MAX_SUBSCRIPTIONS_PER_SUBSCRIBER] of the subscriber is dropped.
+fn record_subscription(transport: &SwarmTransport, subscriber: Did, msg: &SubscribeTopic) {
+    if msg.ttl_ms == 0 {
+        if let Some(mut subscribers) = transport.topic_subscribers.get_mut(&msg.topic) {
+            subscribers.remove(&subscriber);
+        }
+        transport
+            .topic_subscribers
+            .remove_if(&msg.topic, |_, subscribers| subscribers.is_empty());
+        return;
+    }
+
+    let now = transport.clock.now_ms();
+    let subscribed = transport
+        .topic_subscribers
+        .get(&msg.topic)
+        .is_some_and(|subscribers| subscribers.contains_key(&subscriber));
+    if !subscribed {
+        let topics = transport
+            .topic_subscribers
+            .iter()
+            .filter(|e| e.value().get(&subscriber).is_some_and(|t| *t > now))
+            .count();
+        if topics >= TOPIC_MAX_SUBSCRIPTIONS_PER_SUBSCRIBER {
+            tracing::warn!(
+                "Drop subscription of {subscriber} to {}, too many topics",
+                msg.topic
+            );
+            return;
+        }
+    }
+
+    let expires_at = now + msg.ttl_ms.min(MAX_TTL_MS) as u128;
+    let mut subscribers = transport
+        .topic_subscribers
+        .entry(msg.topic.clone())
+        .or_default();
+    if !subscribed {
+        subscribers.retain(|_, t| *t > now);
+        if subscribers.len() >= TOPIC_MAX_SUBSCRIBERS {
+            tracing::warn!(
+                "Drop subscription of {subscriber} to {}, too many subscribers",
+                msg.topic
+            );
+            return;
+        }
+    }
+    subscribers.insert(subscriber, expires_at);
+}
+
+/// Send [SubscribeTopic] of current node to the node responsible for the topic.
+pub(crate) async fn subscribe_topic(
+    transport: &SwarmTransport,
+    topic: &str,
+    ttl_ms: u64,
+) -> Result<()> {
+    let msg = SubscribeTopic {
+        topic: topic.to_string(),
+        ttl_ms,
+    };
+    match next_hop_to_topic(&transport.dht, topic)? {
+        None => record_subscription(transport, transport.dht.did, &msg),
+        Some(next) => {
+            transport
+                .send_message(Message::SubscribeTopic(msg), next)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+impl MessageHandler {
+    /// Send published data to each subscriber of a topic that current node is responsible for.
+    /// Expired subscriptions are removed. Data for current node is passed to the callback
+    /// directly, like a message received.
+    async fn fan_out(&self, msg: PublishTopic) -> Result<()> {
+        let now = self.transport.clock.now_ms();
+        let subscribers = match self.transport.topic_subscribers.get_mut(&msg.topic) {
+            Some(mut subscribers) => {
+                subscribers.retain(|_, expires_at| *expires_at > now);
+                subscribers.keys().copied().collect::<Vec<_>>()
+            }
+            None => vec![],
+        };
+        self.transport
+            .topic_subscribers
+            .remove_if(&msg.topic, |_, subscribers| subscribers.is_empty());
+
+        let msg = PublishTopic {
+            delivered: true,
+            ..msg
+        };
+        for subscriber in subscribers {
+            let sent = if subscriber == self.dht.did {
+                self.deliver_locally(msg.clone()).await
+            } else {
+                self.transport
+                    .send_message(Message::PublishTopic(msg.clone()), subscriber)
+                    .await
+                    .map(|_| ())
+            };
+            if let Err(e) = sent {
+                tracing::warn!("Failed to publish {} to {subscriber}: {e:?}", msg.topic);
+            }
+        }
+        Ok(())
+    }
+
+    async fn deliver_locally(&self, msg: PublishTopic) -> Result<()> {
+        let payload = MessagePayload::new_send(
+            Message::PublishTopic(msg),
+            self.transport.session_sk(),
+            self.dht.did,
+            self.dht.did,
+        )?;
+        if let Err(e) = self.swarm_callback.on_validate(&payload).await {
+            tracing::warn!("Published message is rejected by callback: {e:?}");
+            return Ok(());
+        }
+        if let Err(e) = self.swarm_callback.on_inbound(&payload).await {
+            tracing::error!("Failed to handle published message: {e:?}");
+        }
+        Ok(())
+    }
+}
+
+impl Swarm {
+    /// Subscribe a topic, then messages published to it by [Swarm::publish] will be received
+    /// as [Message::PublishTopic] in [crate::swarm::callback::SwarmCallback::on_inbound].
+    ///
+    /// The subscription is recorded by the node responsible for the topic. It expires after
+    /// [TOPIC_SUBSCRIPTION_TTL_MS], and is refreshed in each stabilization, so that it
+    /// follows the responsible node when the ring changes.
+    pub async fn subscribe(&self, topic: &str) -> Result<()> {
+        self.transport.subscriptions.insert(topic.to_string());
+        subscribe_topic(&self.transport, topic, TOPIC_SUBSCRIPTION_TTL_MS).await
+    }
+
+    /// Unsubscribe a topic subscribed by [Swarm::subscribe].
+    pub async fn unsubscribe(&self, topic: &str) -> Result<()> {
+        self.transport.subscriptions.remove(topic);
+        subscribe_topic(&self.transport, topic, 0).await
+    }
+
+    /// Publish data to all subscribers of a topic, through the node responsible for it.
+    pub async fn publish(&self, topic: &str, data: Vec<u8>) -> Result<()> {
+        let msg = PublishTopic::new(topic, data, self.transport.session_sk())?;
+        match next_hop_to_topic(&self.dht, topic)? {
+            None => {
+                MessageHandler::new(self.transport.clone(), self.callback()?)
+                    .fan_out(msg)
+                    .await
+            }
+            Some(next) => {
+                self.transport
+                    .send_message(Message::PublishTopic(msg), next)
+                    .await?;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<SubscribeTopic> for MessageHandler {
+    /// Route the subscription to the node responsible for the topic, then record it there.
+    async fn handle(&self, ctx: &MessagePayload, msg: &SubscribeTopic) -> Result<()> {
+        match next_hop_to_topic(&self.dht, &msg.topic)? {
+            None => record_subscription(&self.transport, ctx.transaction.signer(), msg),
+            Some(next) => self.transport.reset_destination(ctx, next).await?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<PublishTopic> for MessageHandler {
+    /// Route the message to the node responsible for the topic, which sends it to subscribers
+    /// if it's signed by the signer of transaction. A delivered message is left to the
+    /// callback.
+    async fn handle(&self, ctx: &MessagePayload, msg: &PublishTopic) -> Result<()> {
+        if msg.delivered {
+            return Ok(());
+        }
+        match next_hop_to_topic(&self.dht, &msg.topic)? {
+            None => {
+                let publisher = msg.publisher()?;
+                if publisher != ctx.transaction.signer() {
+                    return Err(Error::InvalidMessage(format!(
+                        "Publication of {publisher} is sent by {}",
+                        ctx.transaction.signer()
+                    )));
+                }
+                self.fan_out(msg.clone()).await
+            }
+            Some(next) => self.transport.reset_destination(ctx, next).await,
+        }
+    }
+}
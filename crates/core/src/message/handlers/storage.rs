@@ -1,21 +1,27 @@
 #![warn(missing_docs)]
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_recursion::async_recursion;
 use async_trait::async_trait;
 
+use crate::consts::VNODE_LOOKUP_TIMEOUT_MS;
+use crate::dht::vnode::VNodeEntry;
 use crate::dht::vnode::VirtualNode;
+use crate::dht::Chord;
 use crate::dht::ChordStorage;
 use crate::dht::ChordStorageCache;
 use crate::dht::Did;
 use crate::dht::PeerRing;
 use crate::dht::PeerRingAction;
 use crate::dht::PeerRingRemoteAction;
+use crate::dht::SuccessorReader;
 use crate::error::Error;
 use crate::error::Result;
 use crate::message::types::FoundVNode;
 use crate::message::types::Message;
+use crate::message::types::ReplicateVNode;
 use crate::message::types::SearchVNode;
 use crate::message::types::SyncVNodeWithSuccessor;
 use crate::message::Encoded;
@@ -118,7 +124,12 @@ async fn handle_storage_search_act(
     act: PeerRingAction,
 ) -> Result<()> {
     match act {
-        PeerRingAction::None => Ok(()),
+        // Current node is responsible for it but holds nothing, tell the sender not found.
+        PeerRingAction::None => {
+            transport
+                .send_report_message(ctx, Message::FoundVNode(FoundVNode { data: vec![] }))
+                .await
+        }
         PeerRingAction::SomeVNode(v) => {
             transport
                 .send_report_message(ctx, Message::FoundVNode(FoundVNode { data: vec![v] }))
@@ -172,6 +183,150 @@ pub(super) async fn handle_storage_operate_act(
     }
 }
 
+/// Store a replica of [VNodeEntry] locally.
+/// If a fresher and unexpired one is already stored, keep it.
+async fn store_replica(dht: &PeerRing, vnode: VirtualNode) -> Result<()> {
+    let vid = vnode.did.to_string();
+    let incoming = VNodeEntry::try_from(&vnode)?;
+    if let Some(existed) = dht.storage.get(&vid).await? {
+        if let Ok(existed) = VNodeEntry::try_from(&existed) {
+            if !existed.is_expired() && existed.ts_ms > incoming.ts_ms {
+                return Ok(());
+            }
+        }
+    }
+    dht.storage.put(&vid, &vnode).await
+}
+
+/// Route [ReplicateVNode] to the node responsible for the vnode.
+/// Once arrived, store it and pass it on to the successor until all replicas are placed.
+async fn handle_replicate_vnode(transport: Arc<SwarmTransport>, msg: ReplicateVNode) -> Result<()> {
+    if !msg.placed {
+        match transport.dht.find_successor(msg.vnode.did)? {
+            PeerRingAction::Some(_) => {}
+            PeerRingAction::RemoteAction(next, _) => {
+                transport
+                    .send_message(Message::ReplicateVNode(msg), next)
+                    .await?;
+                return Ok(());
+            }
+            act => return Err(Error::PeerRingUnexpectedAction(act)),
+        }
+    }
+
+    store_replica(&transport.dht, msg.vnode.clone()).await?;
+
+    if msg.replication <= 1 {
+        return Ok(());
+    }
+
+    let Some(next) = transport
+        .dht
+        .successors()
+        .list()?
+        .into_iter()
+        .find(|did| *did != transport.dht.did)
+    else {
+        return Ok(());
+    };
+
+    let msg = ReplicateVNode {
+        vnode: msg.vnode,
+        replication: msg.replication - 1,
+        placed: true,
+    };
+    transport
+        .send_message(Message::ReplicateVNode(msg), next)
+        .await?;
+    Ok(())
+}
+
+impl Swarm {
+    /// Store `value` under `key` on DHT, which will expire after `ttl`.
+    ///
+    /// The value is stored on the node responsible for `key` and `replication - 1` successors
+    /// of it, so that it's still available when some of them fail.
+    pub async fn vnode_put(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Duration,
+        replication: u8,
+    ) -> Result<()> {
+        let did = VirtualNode::gen_did(key)?;
+        let vnode = VNodeEntry::new(value, ttl.as_millis() as u64).to_vnode(did)?;
+        let msg = ReplicateVNode {
+            vnode,
+            replication: replication.max(1),
+            placed: false,
+        };
+        handle_replicate_vnode(self.transport.clone(), msg).await
+    }
+
+    /// Get the value stored by [Swarm::vnode_put].
+    ///
+    /// Both local replica and the one found on DHT are taken into account, and the freshest
+    /// unexpired one wins. Expired local replica will be removed.
+    /// The node responsible for `key` is asked and its answer is awaited, return `None` if it
+    /// has nothing or doesn't answer in [VNODE_LOOKUP_TIMEOUT_MS].
+    pub async fn vnode_get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let did = VirtualNode::gen_did(key)?;
+        let vid = did.to_string();
+        let mut entries = vec![];
+
+        if let Some(vnode) = self.dht.storage.get(&vid).await? {
+            match VNodeEntry::try_from(&vnode) {
+                Ok(entry) if entry.is_expired() => self.dht.storage.remove(&vid).await?,
+                Ok(entry) => entries.push(entry),
+                Err(e) => tracing::warn!("Failed to load vnode entry {vid}: {e:?}"),
+            }
+        }
+
+        // Ask the node responsible for it, which answers by FoundVNode.
+        match <PeerRing as ChordStorage<_, 1>>::vnode_lookup(&self.dht, did).await? {
+            PeerRingAction::None => {}
+            PeerRingAction::SomeVNode(vnode) => match VNodeEntry::try_from(&vnode) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => tracing::warn!("Failed to load vnode entry {vid}: {e:?}"),
+            },
+            PeerRingAction::RemoteAction(next, _) => {
+                let msg = Message::SearchVNode(SearchVNode { vid: did });
+                match self
+                    .transport
+                    .send_message_and_wait(msg, next, VNODE_LOOKUP_TIMEOUT_MS)
+                    .await
+                {
+                    Ok(reply) => match reply.transaction.data()? {
+                        Message::FoundVNode(found) => {
+                            for vnode in found.data.iter().filter(|v| v.did == did) {
+                                match VNodeEntry::try_from(vnode) {
+                                    Ok(entry) => entries.push(entry),
+                                    Err(e) => {
+                                        tracing::warn!("Failed to load vnode entry {vid}: {e:?}")
+                                    }
+                                }
+                            }
+                        }
+                        msg => {
+                            return Err(Error::InvalidMessage(format!(
+                                "Unexpected reply of SearchVNode: {:?}",
+                                msg
+                            )))
+                        }
+                    },
+                    Err(Error::WaitReplyTimeout(_)) => {
+                        tracing::warn!("No reply of vnode {vid} from {next}")
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            act => return Err(Error::PeerRingUnexpectedAction(act)),
+        }
+
+        Ok(VNodeEntry::freshest(entries).map(|e| e.value))
+    }
+}
+
 #[cfg_attr(feature = "wasm", async_trait(?Send))]
 #[cfg_attr(not(feature = "wasm"), async_trait)]
 impl ChordStorageInterfaceCacheChecker for Swarm {
@@ -257,6 +412,14 @@ impl HandleMsg<VNodeOperation> for MessageHandler {
     }
 }
 
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<ReplicateVNode> for MessageHandler {
+    async fn handle(&self, _ctx: &MessagePayload, msg: &ReplicateVNode) -> Result<()> {
+        handle_replicate_vnode(self.transport.clone(), msg.clone()).await
+    }
+}
+
 #[cfg_attr(feature = "wasm", async_trait(?Send))]
 #[cfg_attr(not(feature = "wasm"), async_trait)]
 impl HandleMsg<SyncVNodeWithSuccessor> for MessageHandler {
@@ -466,4 +629,66 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_vnode_put_get_with_replication() -> Result<()> {
+        let keys = gen_ordered_keys(4);
+        let mut nodes = vec![];
+        for key in keys {
+            nodes.push(prepare_node(key).await);
+        }
+
+        for (i, node1) in nodes.iter().enumerate() {
+            for node2 in nodes.iter().skip(i + 1) {
+                manually_establish_connection(&node1.swarm, &node2.swarm).await;
+            }
+        }
+        wait_for_msgs(nodes.iter()).await;
+        assert_no_more_msg(nodes.iter()).await;
+
+        let key = "The quick brown fox jumps over the lazy dog";
+        let value = b"replicated value".to_vec();
+        nodes[0]
+            .swarm
+            .vnode_put(key, value.clone(), Duration::from_secs(600), 3)
+            .await?;
+        wait_for_msgs(nodes.iter()).await;
+        assert_no_more_msg(nodes.iter()).await;
+
+        let vid = VirtualNode::gen_did(key)?;
+        let mut holders = vec![];
+        for (i, node) in nodes.iter().enumerate() {
+            if node.dht().storage.get(&vid.to_string()).await?.is_some() {
+                holders.push(i);
+            }
+        }
+        assert_eq!(holders.len(), 3);
+
+        // Take the node responsible for the key offline.
+        let responsible = *holders
+            .iter()
+            .find(|i| {
+                matches!(
+                    nodes[**i].dht().find_successor(vid),
+                    Ok(PeerRingAction::Some(_))
+                )
+            })
+            .unwrap();
+        for (i, node) in nodes.iter().enumerate() {
+            if i != responsible {
+                nodes[responsible].swarm.disconnect(node.did()).await?;
+            }
+        }
+        wait_for_msgs(nodes.iter()).await;
+
+        // Read it back from every alive node.
+        for (i, node) in nodes.iter().enumerate() {
+            if i == responsible {
+                continue;
+            }
+            assert_eq!(node.swarm.vnode_get(key).await?, Some(value.clone()));
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,144 @@
+#![warn(missing_docs)]
+//! Encoding of sdp carried by [ConnectNodeSend](super::ConnectNodeSend) and
+//! [ConnectNodeReport](super::ConnectNodeReport).
+//!
+//! Sdp encoded by JSON is sent as it is, which is what old nodes send and expect. Sdp encoded
+//! by other codecs starts with a byte tagging the codec, which never starts a JSON text,
+//! so the receiver can always decode sdp regardless of its own [HandshakeCodec].
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::error::Error;
+use crate::error::Result;
+
+/// Tag of sdp encoded by CBOR.
+const SDP_TAG_CBOR: u8 = 1;
+
+/// Codecs used to encode sdp in handshake messages.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HandshakeCodec {
+    /// JSON, readable and understood by all nodes.
+    #[default]
+    Json,
+    /// CBOR, which is more compact than JSON. Old nodes cannot decode it.
+    Cbor,
+}
+
+impl HandshakeCodec {
+    /// Get the codec of encoded sdp by its tag.
+    pub fn of(data: &[u8]) -> Self {
+        match data.first() {
+            Some(&SDP_TAG_CBOR) => Self::Cbor,
+            _ => Self::Json,
+        }
+    }
+
+    /// Encode sdp, with the tag of codec if it's not JSON.
+    pub fn encode<T: Serialize>(&self, sdp: &T) -> Result<Vec<u8>> {
+        match self {
+            Self::Json => serde_json::to_vec(sdp).map_err(Error::Serialize),
+            Self::Cbor => {
+                let mut data = vec![SDP_TAG_CBOR];
+                ciborium::into_writer(sdp, &mut data)
+                    .map_err(|e| Error::HandshakeCodec(e.to_string()))?;
+                Ok(data)
+            }
+        }
+    }
+
+    /// Decode sdp encoded by this codec.
+    /// Fails with [Error::HandshakeCodecMismatch] if it's encoded by another codec.
+    pub fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T> {
+        let codec = Self::of(data);
+        if codec != *self {
+            return Err(Error::HandshakeCodecMismatch(codec, *self));
+        }
+        match self {
+            Self::Json => serde_json::from_slice(data).map_err(Error::Deserialize),
+            Self::Cbor => {
+                ciborium::from_reader(&data[1..]).map_err(|e| Error::HandshakeCodec(e.to_string()))
+            }
+        }
+    }
+}
+
+/// Decode sdp by the codec in its tag.
+pub fn decode_sdp<T: DeserializeOwned>(data: &[u8]) -> Result<T> {
+    HandshakeCodec::of(data).decode(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_sdp() -> serde_json::Value {
+        let sdp = [
+            "v=0",
+            "o=- 8317487932386591826 277366504 IN IP4 0.0.0.0",
+            "s=-",
+            "t=0 0",
+            "a=fingerprint:sha-256 5C:C1:0B:9D:42:EA:3F:0C:32:F9:6E:4B:B8:BF:F3:95:64:5C:41:63",
+            "a=group:BUNDLE 0",
+            "m=application 9 UDP/DTLS/SCTP webrtc-datachannel",
+            "c=IN IP4 0.0.0.0",
+            "a=setup:actpass",
+            "a=mid:0",
+            "a=sendrecv",
+            "a=sctp-port:5000",
+            "a=ice-ufrag:hbGQpSdBnQmEkwWS",
+            "a=ice-pwd:KYsWPBtxzgZRJBaUwEXKQsdhcaXRmFeh",
+            "a=candidate:1966762133 1 udp 2130706431 192.168.1.2 51432 typ host",
+            "a=candidate:233762139 1 udp 1694498815 203.0.113.7 51432 typ srflx",
+            "a=end-of-candidates",
+        ]
+        .map(|line| format!("{line}\r\n"))
+        .concat();
+        serde_json::json!({ "type": "offer", "sdp": sdp })
+    }
+
+    #[test]
+    fn test_round_trip_each_codec() {
+        let sdp = sample_sdp();
+        for codec in [HandshakeCodec::Json, HandshakeCodec::Cbor] {
+            let data = codec.encode(&sdp).unwrap();
+            assert_eq!(HandshakeCodec::of(&data), codec);
+            assert_eq!(codec.decode::<serde_json::Value>(&data).unwrap(), sdp);
+            assert_eq!(decode_sdp::<serde_json::Value>(&data).unwrap(), sdp);
+        }
+
+        let json = HandshakeCodec::Json.encode(&sdp).unwrap();
+        let cbor = HandshakeCodec::Cbor.encode(&sdp).unwrap();
+        assert!(cbor.len() < json.len());
+
+        // Sdp of old nodes is untagged JSON.
+        let legacy = serde_json::to_string(&sdp).unwrap();
+        assert_eq!(
+            decode_sdp::<serde_json::Value>(legacy.as_bytes()).unwrap(),
+            sdp
+        );
+    }
+
+    #[test]
+    fn test_decode_rejects_other_codec() {
+        let sdp = sample_sdp();
+        let json = HandshakeCodec::Json.encode(&sdp).unwrap();
+        let cbor = HandshakeCodec::Cbor.encode(&sdp).unwrap();
+
+        assert!(matches!(
+            HandshakeCodec::Cbor.decode::<serde_json::Value>(&json),
+            Err(Error::HandshakeCodecMismatch(
+                HandshakeCodec::Json,
+                HandshakeCodec::Cbor
+            ))
+        ));
+        assert!(matches!(
+            HandshakeCodec::Json.decode::<serde_json::Value>(&cbor),
+            Err(Error::HandshakeCodecMismatch(
+                HandshakeCodec::Cbor,
+                HandshakeCodec::Json
+            ))
+        ));
+    }
+}
@@ -1,4 +1,15 @@
 //! Message and MessageHandler
+mod compression;
+pub use compression::decode_frame;
+pub use compression::decode_frame_limited;
+pub use compression::encode_frame;
+pub use compression::CompressionAlgorithm;
+pub use compression::CompressionConfig;
+
+mod handshake_codec;
+pub use handshake_codec::decode_sdp;
+pub use handshake_codec::HandshakeCodec;
+
 mod encoder;
 pub use encoder::Decoder;
 pub use encoder::Encoded;
@@ -27,3 +38,6 @@ mod protocols;
 pub use protocols::MessageRelay;
 pub use protocols::MessageVerification;
 pub use protocols::MessageVerificationExt;
+pub use protocols::RelayTrace;
+pub use protocols::VerifiedInfo;
+pub use protocols::VerifyFailure;
@@ -19,11 +19,18 @@ use super::encoder::Encoder;
 use super::protocols::MessageRelay;
 use super::protocols::MessageVerification;
 use super::protocols::MessageVerificationExt;
+use super::protocols::VerifiedInfo;
+use super::protocols::VerifyFailure;
+use super::types::EncryptedMessage;
+use super::types::Message;
+use super::types::Priority;
+use crate::consts::PROTOCOL_VERSION;
 use crate::dht::Chord;
 use crate::dht::Did;
 use crate::dht::PeerRing;
 use crate::dht::PeerRingAction;
 use crate::ecc::keccak256;
+use crate::ecc::PublicKey;
 use crate::error::Error;
 use crate::error::Result;
 use crate::session::SessionSk;
@@ -61,12 +68,27 @@ where T: DeserializeOwned {
     Ok(m)
 }
 
-fn hash_transaction(destination: Did, tx_id: uuid::Uuid, data: &[u8]) -> [u8; 32] {
+fn hash_transaction(
+    destination: Did,
+    tx_id: uuid::Uuid,
+    data: &[u8],
+    expires_at: Option<u128>,
+) -> [u8; 32] {
     let mut msg = vec![];
 
     msg.extend_from_slice(destination.as_bytes());
     msg.extend_from_slice(tx_id.as_bytes());
+    // Length of data is prefixed and expires_at is always tagged, so that bytes can never be
+    // moved between them without changing the hash.
+    msg.extend_from_slice(&(data.len() as u64).to_be_bytes());
     msg.extend_from_slice(data);
+    match expires_at {
+        Some(expires_at) => {
+            msg.push(1);
+            msg.extend_from_slice(&expires_at.to_be_bytes());
+        }
+        None => msg.push(0),
+    }
 
     keccak256(&msg)
 }
@@ -90,6 +112,9 @@ pub struct Transaction {
     /// which is used to prove that the transaction was created by that node.
     #[derivative(Debug = "ignore")]
     pub verification: MessageVerification,
+    /// Deadline of the message in milliseconds since epoch, signed with the transaction.
+    /// Each hop drops the message once it's passed, instead of delivering it late.
+    pub expires_at: Option<u128>,
 }
 
 /// `MessagePayload` is used to transmit data between nodes.
@@ -121,16 +146,46 @@ impl Transaction {
         T: Serialize,
     {
         let data = bincode::serialize(&data).map_err(Error::BincodeSerialize)?;
-        let msg_hash = hash_transaction(destination, tx_id, &data);
+        Self::new_with_data(destination, tx_id, data, session_sk)
+    }
+
+    /// Wrap data which is already serialized, then sign [MessageVerification] by session_sk.
+    pub fn new_with_data(
+        destination: Did,
+        tx_id: uuid::Uuid,
+        data: Vec<u8>,
+        session_sk: &SessionSk,
+    ) -> Result<Self> {
+        Self::new_with_expiry(destination, tx_id, data, None, session_sk)
+    }
+
+    /// Wrap data which is already serialized with a deadline `expires_at` in milliseconds
+    /// since epoch, then sign [MessageVerification] by session_sk.
+    pub fn new_with_expiry(
+        destination: Did,
+        tx_id: uuid::Uuid,
+        data: Vec<u8>,
+        expires_at: Option<u128>,
+        session_sk: &SessionSk,
+    ) -> Result<Self> {
+        let msg_hash = hash_transaction(destination, tx_id, &data, expires_at);
         let verification = MessageVerification::new(&msg_hash, session_sk)?;
         Ok(Self {
             destination,
             tx_id,
             data,
+            expires_at,
             verification,
         })
     }
 
+    /// Whether the deadline of the transaction is passed at `now_ms`.
+    /// A transaction without deadline never expires by this.
+    pub fn is_expired_at(&self, now_ms: u128) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| now_ms > expires_at)
+    }
+
     /// Deserializes the data field into a `T` instance.
     pub fn data<T>(&self) -> Result<T>
     where T: DeserializeOwned {
@@ -150,6 +205,7 @@ impl MessagePayload {
             transaction.destination,
             transaction.tx_id,
             &transaction.data,
+            transaction.expires_at,
         );
         let verification = MessageVerification::new(&msg_hash, session_sk)?;
         Ok(Self {
@@ -166,11 +222,28 @@ impl MessagePayload {
         next_hop: Did,
         destination: Did,
     ) -> Result<Self>
+    where
+        T: Serialize,
+    {
+        Self::new_send_with_expiry(data, session_sk, next_hop, destination, None)
+    }
+
+    /// Helps to create sending message from data, which is dropped by any hop after
+    /// `expires_at` in milliseconds since epoch, see [Transaction::expires_at].
+    pub fn new_send_with_expiry<T>(
+        data: T,
+        session_sk: &SessionSk,
+        next_hop: Did,
+        destination: Did,
+        expires_at: Option<u128>,
+    ) -> Result<Self>
     where
         T: Serialize,
     {
         let tx_id = uuid::Uuid::new_v4();
-        let transaction = Transaction::new(destination, tx_id, data, session_sk)?;
+        let data = bincode::serialize(&data).map_err(Error::BincodeSerialize)?;
+        let transaction =
+            Transaction::new_with_expiry(destination, tx_id, data, expires_at, session_sk)?;
         let relay = MessageRelay::new(
             vec![session_sk.account_did()],
             next_hop,
@@ -179,6 +252,47 @@ impl MessagePayload {
         Self::new(transaction, session_sk, relay)
     }
 
+    /// Helps to create sending message from data, with data encrypted end to end.
+    ///
+    /// The data is encrypted to `destination_pubkey`, the public key of destination's session,
+    /// by ECIES and wrapped in [Message::Encrypted]. Relays can route the payload by its
+    /// headers, but only the destination can decrypt the data. The public key can be got
+    /// from a message signed by destination, see [MessageVerificationExt::session_pubkey].
+    pub fn new_send_encrypted<T>(
+        data: T,
+        session_sk: &SessionSk,
+        next_hop: Did,
+        destination: Did,
+        destination_pubkey: PublicKey<33>,
+    ) -> Result<Self>
+    where
+        T: Serialize,
+    {
+        let data = bincode::serialize(&data).map_err(Error::BincodeSerialize)?;
+        let encrypted =
+            ecies::encrypt(&destination_pubkey.0, &data).map_err(Error::MessageEncryptionFailed)?;
+        Self::new_send(
+            Message::Encrypted(EncryptedMessage(encrypted)),
+            session_sk,
+            next_hop,
+            destination,
+        )
+    }
+
+    /// Decrypt the data of a payload created by [MessagePayload::new_send_encrypted].
+    /// Return the payload as it is if the data is not encrypted.
+    ///
+    /// The signatures of payload are made for the encrypted data, so the decrypted payload
+    /// should be verified before decryption instead of after.
+    pub fn decrypt(&self, session_sk: &SessionSk) -> Result<Self> {
+        let Ok(Message::Encrypted(encrypted)) = self.transaction.data() else {
+            return Ok(self.clone());
+        };
+        let mut payload = self.clone();
+        payload.transaction.data = session_sk.decrypt(&encrypted.0)?;
+        Ok(payload)
+    }
+
     /// Deserializes a `MessagePayload` instance from the given binary data.
     pub fn from_bincode(data: &[u8]) -> Result<Self> {
         bincode::deserialize(data).map_err(Error::BincodeDeserialize)
@@ -190,11 +304,20 @@ impl MessagePayload {
             .map(Bytes::from)
             .map_err(Error::BincodeSerialize)
     }
+
+    /// Default priority of sending this payload, see [Message::priority].
+    /// It's [Priority::Normal] if the data is not a [Message].
+    pub fn priority(&self) -> Priority {
+        self.transaction
+            .data::<Message>()
+            .map(|msg| msg.priority())
+            .unwrap_or_default()
+    }
 }
 
 impl MessageVerificationExt for Transaction {
     fn verification_data(&self) -> Result<Vec<u8>> {
-        Ok(hash_transaction(self.destination, self.tx_id, &self.data).to_vec())
+        Ok(hash_transaction(self.destination, self.tx_id, &self.data, self.expires_at).to_vec())
     }
 
     fn verification(&self) -> &MessageVerification {
@@ -208,6 +331,7 @@ impl MessageVerificationExt for MessagePayload {
             self.transaction.destination,
             self.transaction.tx_id,
             &self.transaction.data,
+            self.transaction.expires_at,
         )
         .to_vec())
     }
@@ -215,18 +339,53 @@ impl MessageVerificationExt for MessagePayload {
     fn verification(&self) -> &MessageVerification {
         &self.verification
     }
+
+    /// Verify both the payload signed by the last hop and the transaction signed by origin.
+    /// Return the [VerifiedInfo] of the transaction, whose `origin` created the message.
+    fn verify_detailed(&self) -> std::result::Result<VerifiedInfo, VerifyFailure> {
+        if self.is_expired() {
+            return Err(VerifyFailure::MessageExpired);
+        }
+        let data = self
+            .verification_data()
+            .map_err(|e| VerifyFailure::Malformed(e.to_string()))?;
+        self.verification.verify_detailed(&data)?;
+        self.transaction.verify_detailed()
+    }
+}
+
+impl MessagePayload {
+    /// Encode with a leading byte of protocol version.
+    fn encode_with_version(&self, version: u8) -> Result<Encoded> {
+        let data = self.to_bincode()?;
+        let mut frame = Vec::with_capacity(data.len() + 1);
+        frame.push(version);
+        frame.extend_from_slice(&data);
+        frame.encode()
+    }
+
+    /// Decode a frame, reject it if the leading version byte is not `supported_version`.
+    fn decode_with_version(encoded: &Encoded, supported_version: u8) -> Result<Self> {
+        let v: Bytes = encoded.decode()?;
+        let (version, data) = v
+            .split_first()
+            .ok_or_else(|| Error::InvalidMessage("Encoded payload is empty".to_string()))?;
+        if *version != supported_version {
+            return Err(Error::UnsupportedProtocolVersion(*version));
+        }
+        Self::from_bincode(data)
+    }
 }
 
 impl Encoder for MessagePayload {
     fn encode(&self) -> Result<Encoded> {
-        self.to_bincode()?.encode()
+        self.encode_with_version(PROTOCOL_VERSION)
     }
 }
 
 impl Decoder for MessagePayload {
     fn from_encoded(encoded: &Encoded) -> Result<Self> {
-        let v: Bytes = encoded.decode()?;
-        Self::from_bincode(&v)
+        Self::decode_with_version(encoded, PROTOCOL_VERSION)
     }
 }
 
@@ -243,8 +402,20 @@ pub trait PayloadSender {
     /// Used to check if destination is already connected when `infer_next_hop`
     fn is_connected(&self, did: Did) -> bool;
 
+    /// Next hop to destination chosen instead of the one inferred by DHT, if any.
+    /// See [crate::swarm::Swarm::reroute].
+    fn rerouted_hop(&self, _destination: Did) -> Option<Did> {
+        None
+    }
+
     /// Send a message payload to a specified DID.
-    async fn do_send_payload(&self, did: Did, payload: MessagePayload) -> Result<()>;
+    /// Payloads queued to the same DID are sent in the order of `priority`.
+    async fn do_send_payload(
+        &self,
+        did: Did,
+        payload: MessagePayload,
+        priority: Priority,
+    ) -> Result<()>;
 
     /// Infer the next hop for a message by calling `dht.find_successor()`.
     fn infer_next_hop(&self, destination: Did, next_hop: Option<Did>) -> Result<Did> {
@@ -256,6 +427,10 @@ pub trait PayloadSender {
             return Ok(next_hop);
         }
 
+        if let Some(next_hop) = self.rerouted_hop(destination) {
+            return Ok(next_hop);
+        }
+
         match self.dht().find_successor(destination)? {
             PeerRingAction::Some(did) => Ok(did),
             PeerRingAction::RemoteAction(did, _) => Ok(did),
@@ -264,8 +439,36 @@ pub trait PayloadSender {
     }
 
     /// Alias for `do_send_payload` that sets the next hop to `payload.relay.next_hop`.
+    /// The priority is inferred by [MessagePayload::priority].
     async fn send_payload(&self, payload: MessagePayload) -> Result<()> {
-        self.do_send_payload(payload.relay.next_hop, payload).await
+        let priority = payload.priority();
+        self.send_payload_with_priority(payload, priority).await
+    }
+
+    /// Send a payload to `payload.relay.next_hop` with specified priority.
+    async fn send_payload_with_priority(
+        &self,
+        payload: MessagePayload,
+        priority: Priority,
+    ) -> Result<()> {
+        self.do_send_payload(payload.relay.next_hop, payload, priority)
+            .await
+    }
+
+    /// Check if the data channel to `did` is open, so that sending to it doesn't wait.
+    fn is_channel_open(&self, did: Did) -> bool {
+        self.is_connected(did)
+    }
+
+    /// Send a message payload to a specified DID like [PayloadSender::do_send_payload], but fail
+    /// with [Error::ChannelNotReady] at once if the data channel isn't open yet, instead of
+    /// waiting for it. Used by best-effort messages which are useless if they're late.
+    async fn try_send_payload(&self, did: Did, payload: MessagePayload) -> Result<()> {
+        if !self.is_channel_open(did) {
+            return Err(Error::ChannelNotReady(did));
+        }
+        let priority = payload.priority();
+        self.do_send_payload(did, payload, priority).await
     }
 
     /// Send a message to a specified destination by specified next hop.
@@ -291,6 +494,71 @@ pub trait PayloadSender {
         self.send_message_by_hop(msg, destination, next_hop).await
     }
 
+    /// Send a message to a specified destination, which is dropped by any hop after
+    /// `expires_at` in milliseconds since epoch instead of being delivered late.
+    async fn send_message_with_expiry<T>(
+        &self,
+        msg: T,
+        destination: Did,
+        expires_at: u128,
+    ) -> Result<uuid::Uuid>
+    where
+        T: Serialize + Send,
+    {
+        let next_hop = self.infer_next_hop(destination, None)?;
+        let payload = MessagePayload::new_send_with_expiry(
+            msg,
+            self.session_sk(),
+            next_hop,
+            destination,
+            Some(expires_at),
+        )?;
+        let tx_id = payload.transaction.tx_id;
+        self.send_payload(payload).await?;
+        Ok(tx_id)
+    }
+
+    /// Send a message to a specified destination, encrypted to `destination_pubkey`.
+    /// See [MessagePayload::new_send_encrypted].
+    async fn send_encrypted_message<T>(
+        &self,
+        msg: T,
+        destination: Did,
+        destination_pubkey: PublicKey<33>,
+    ) -> Result<uuid::Uuid>
+    where
+        T: Serialize + Send,
+    {
+        let next_hop = self.infer_next_hop(destination, None)?;
+        let payload = MessagePayload::new_send_encrypted(
+            msg,
+            self.session_sk(),
+            next_hop,
+            destination,
+            destination_pubkey,
+        )?;
+        let tx_id = payload.transaction.tx_id;
+        self.send_payload(payload).await?;
+        Ok(tx_id)
+    }
+
+    /// Send a message to a specified destination with specified priority.
+    async fn send_message_with_priority<T>(
+        &self,
+        msg: T,
+        destination: Did,
+        priority: Priority,
+    ) -> Result<uuid::Uuid>
+    where
+        T: Serialize + Send,
+    {
+        let next_hop = self.infer_next_hop(destination, None)?;
+        let payload = MessagePayload::new_send(msg, self.session_sk(), next_hop, destination)?;
+        let tx_id = payload.transaction.tx_id;
+        self.send_payload_with_priority(payload, priority).await?;
+        Ok(tx_id)
+    }
+
     /// Send a direct message to a specified destination.
     async fn send_direct_message<T>(&self, msg: T, destination: Did) -> Result<uuid::Uuid>
     where T: Serialize + Send {
@@ -299,6 +567,8 @@ pub trait PayloadSender {
     }
 
     /// Send a report message to a specified destination.
+    /// If the node retraced by the report is gone, the report is rerouted by DHT,
+    /// see [MessageRelay::reroute_report].
     async fn send_report_message<T>(&self, payload: &MessagePayload, msg: T) -> Result<()>
     where T: Serialize + Send {
         let relay = payload.relay.report(self.dht().did)?;
@@ -310,8 +580,27 @@ pub trait PayloadSender {
             self.session_sk(),
         )?;
 
-        let pl = MessagePayload::new(transaction, self.session_sk(), relay)?;
-        self.send_payload(pl).await
+        let pl = MessagePayload::new(transaction.clone(), self.session_sk(), relay.clone())?;
+        match self.send_payload(pl).await {
+            // The node retraced by the report is gone, route it toward destination by DHT.
+            Err(e) if !self.is_connected(relay.next_hop) => {
+                let via = self.infer_next_hop(relay.destination, None)?;
+                if via == relay.next_hop {
+                    return Err(e);
+                }
+                tracing::warn!(
+                    target: "rings::relay",
+                    "Failed to send report to {}: {:?}, reroute it via {}",
+                    relay.next_hop,
+                    e,
+                    via
+                );
+                let relay = relay.reroute_report(via)?;
+                let pl = MessagePayload::new(transaction, self.session_sk(), relay)?;
+                self.send_payload(pl).await
+            }
+            result => result,
+        }
     }
 
     /// Forward a payload message by relay.
@@ -324,6 +613,13 @@ pub trait PayloadSender {
     /// Forward a payload message, with the next hop inferred by the DHT.
     async fn forward_payload(&self, payload: &MessagePayload, next_hop: Option<Did>) -> Result<()> {
         let next_hop = self.infer_next_hop(payload.relay.destination, next_hop)?;
+        tracing::debug!(
+            target: "rings::relay",
+            "Forward {} of {} to {next_hop}, destination {}",
+            payload.transaction.tx_id,
+            payload.relay.origin_sender(),
+            payload.relay.destination
+        );
         let relay = payload.relay.forward(self.dht().did, next_hop)?;
         self.forward_by_relay(payload, relay).await
     }
@@ -343,8 +639,12 @@ pub mod test {
     use rand::Rng;
 
     use super::*;
+    use crate::consts::DEFAULT_SESSION_TTL_MS;
     use crate::ecc::SecretKey;
     use crate::message::Message;
+    use crate::session::SessionSkBuilder;
+    use crate::session::SignatureScheme;
+    use crate::utils::get_epoch_ms;
 
     #[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
     pub struct TestData {
@@ -381,6 +681,37 @@ pub mod test {
         assert!(payload.verify());
     }
 
+    #[test]
+    fn test_expires_at_is_signed() {
+        let session_sk = SessionSk::new_with_seckey(&SecretKey::random()).unwrap();
+        let destination = SecretKey::random().address().into();
+        let payload = MessagePayload::new_send_with_expiry(
+            Message::custom(b"hello").unwrap(),
+            &session_sk,
+            destination,
+            destination,
+            Some(1000),
+        )
+        .unwrap();
+        assert!(payload.verify() && payload.transaction.verify());
+        assert!(!payload.transaction.is_expired_at(1000));
+        assert!(payload.transaction.is_expired_at(1001));
+
+        // A relay can't extend the deadline.
+        let mut extended = payload.clone();
+        extended.transaction.expires_at = Some(2000);
+        assert!(!extended.transaction.verify());
+
+        // Nor strip the deadline by moving it into data.
+        let mut stripped = payload.clone();
+        stripped.transaction.expires_at = None;
+        stripped
+            .transaction
+            .data
+            .extend_from_slice(&1000u128.to_be_bytes());
+        assert!(!stripped.transaction.verify());
+    }
+
     #[test]
     fn test_message_payload_from_auto() {
         let next_hop = SecretKey::random().address().into();
@@ -390,11 +721,33 @@ pub mod test {
         let payload2: MessagePayload = gzipped_encoded_payload.decode().unwrap();
         assert_eq!(payload, payload2);
 
-        let gunzip_encoded_payload = payload.to_bincode().unwrap().encode().unwrap();
+        let mut frame = vec![PROTOCOL_VERSION];
+        frame.extend_from_slice(&payload.to_bincode().unwrap());
+        let gunzip_encoded_payload = frame.encode().unwrap();
         let payload2: MessagePayload = gunzip_encoded_payload.decode().unwrap();
         assert_eq!(payload, payload2);
     }
 
+    #[test]
+    fn test_message_payload_reject_unknown_version() {
+        let next_hop = SecretKey::random().address().into();
+        let payload = new_test_payload(next_hop);
+
+        let encoded = payload.encode_with_version(PROTOCOL_VERSION + 1).unwrap();
+        let err = MessagePayload::decode_with_version(&encoded, PROTOCOL_VERSION).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::UnsupportedProtocolVersion(v) if v == PROTOCOL_VERSION + 1
+        ));
+
+        let decoded = MessagePayload::decode_with_version(&encoded, PROTOCOL_VERSION + 1).unwrap();
+        assert_eq!(payload, decoded);
+
+        // Payloads encoded without version are rejected too.
+        let unversioned = payload.to_bincode().unwrap().encode().unwrap();
+        assert!(MessagePayload::from_encoded(&unversioned).is_err());
+    }
+
     #[test]
     fn test_message_payload_encode_len() {
         let next_hop = SecretKey::random().address().into();
@@ -420,4 +773,127 @@ pub mod test {
             encoded_bytes2.len() - data2.len()
         );
     }
+
+    #[test]
+    fn test_verify_detailed() {
+        let key = SecretKey::random();
+        let session_sk = SessionSk::new_with_seckey(&key).unwrap();
+        let destination = SecretKey::random().address().into();
+        let msg = Message::custom(b"hello").unwrap();
+        let payload = MessagePayload::new_send(msg, &session_sk, destination, destination).unwrap();
+
+        let info = payload.verify_detailed().unwrap();
+        assert_eq!(info.origin, key.address().into());
+        assert_eq!(info.scheme, SignatureScheme::Secp256k1);
+        let now = get_epoch_ms();
+        assert!(info.session_ts_ms <= now && now < info.session_expires_at_ms);
+        assert_eq!(
+            info.session_expires_at_ms - info.session_ts_ms,
+            DEFAULT_SESSION_TTL_MS as u128
+        );
+
+        // The origin is the signer of transaction, not the relay.
+        let relay_sk = SessionSk::new_with_seckey(&SecretKey::random()).unwrap();
+        let relayed = MessagePayload::new(
+            payload.transaction.clone(),
+            &relay_sk,
+            payload.relay.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            relayed.verify_detailed().unwrap().origin,
+            key.address().into()
+        );
+
+        let mut expired = payload.clone();
+        expired.verification.ts_ms -= expired.verification.ttl_ms as u128 + 1;
+        assert_eq!(
+            expired.verify_detailed(),
+            Err(VerifyFailure::MessageExpired)
+        );
+
+        let mut malformed = payload.clone();
+        malformed.verification.sig.truncate(10);
+        assert!(matches!(
+            malformed.verify_detailed(),
+            Err(VerifyFailure::Malformed(_))
+        ));
+
+        let mut tampered = payload.clone();
+        tampered.transaction.data = b"bye".to_vec();
+        assert_eq!(
+            tampered.verify_detailed(),
+            Err(VerifyFailure::InvalidSignature)
+        );
+
+        // The relay re-signs a tampered transaction, which is still rejected.
+        let forwarded =
+            MessagePayload::new(tampered.transaction, &relay_sk, payload.relay.clone()).unwrap();
+        assert!(forwarded.verify());
+        assert_eq!(
+            forwarded.verify_detailed(),
+            Err(VerifyFailure::InvalidSignature)
+        );
+
+        let mut forged = serde_json::to_value(&payload).unwrap();
+        forged["verification"]["session"]["sig"] = serde_json::json!(vec![0u8; 65]);
+        let forged: MessagePayload = serde_json::from_value(forged).unwrap();
+        assert_eq!(
+            forged.verify_detailed(),
+            Err(VerifyFailure::InvalidSessionSignature)
+        );
+    }
+
+    #[test]
+    fn test_verify_detailed_session_expired() {
+        let key = SecretKey::random();
+        let builder = SessionSkBuilder::new(
+            Did::from(key.address()).to_string(),
+            "secp256k1".to_string(),
+        )
+        .set_ttl(50);
+        let sig = key.sign(&builder.unsigned_proof());
+        let session_sk = builder.set_session_sig(sig.to_vec()).build().unwrap();
+        let destination = SecretKey::random().address().into();
+        let msg = Message::custom(b"hello").unwrap();
+        let payload = MessagePayload::new_send(msg, &session_sk, destination, destination).unwrap();
+        assert!(payload.verify_detailed().is_ok());
+
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert!(!payload.is_expired());
+        assert_eq!(
+            payload.verify_detailed(),
+            Err(VerifyFailure::SessionExpired)
+        );
+    }
+
+    #[test]
+    fn test_verify_by_clock() {
+        use crate::utils::MockClock;
+
+        let key = SecretKey::random();
+        let session_sk = SessionSk::new_with_seckey(&key).unwrap();
+        let destination = SecretKey::random().address().into();
+        let msg = Message::custom(b"hello").unwrap();
+        let payload = MessagePayload::new_send(msg, &session_sk, destination, destination).unwrap();
+
+        let clock = MockClock::new(get_epoch_ms());
+        assert!(payload.verify_by(&clock));
+
+        // The message expires by the clock, not by the system time.
+        clock.advance(std::time::Duration::from_millis(
+            payload.verification.ttl_ms + 1,
+        ));
+        assert!(payload.is_expired_by(&clock));
+        assert!(!payload.verify_by(&clock));
+        assert!(payload.verify());
+
+        // So does the session signing it.
+        let clock = MockClock::new(get_epoch_ms());
+        let session = payload.verification.session.clone();
+        assert!(session.verify_self_by(&clock).is_ok());
+        clock.advance(std::time::Duration::from_millis(DEFAULT_SESSION_TTL_MS + 1));
+        assert!(session.verify_self_by(&clock).is_err());
+        assert!(session.verify_self().is_ok());
+    }
 }
@@ -2,5 +2,8 @@ mod relay;
 mod verify;
 
 pub use self::relay::MessageRelay;
+pub use self::relay::RelayTrace;
 pub use self::verify::MessageVerification;
 pub use self::verify::MessageVerificationExt;
+pub use self::verify::VerifiedInfo;
+pub use self::verify::VerifyFailure;
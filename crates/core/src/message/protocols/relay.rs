@@ -28,6 +28,36 @@ pub struct MessageRelay {
     pub destination: Did,
 }
 
+/// A snapshot of [MessageRelay] for routing postmortems.
+///
+/// It's displayed as the hop chain `did0 -> did1 -> [cursor] did2 ~> did3`, where the nodes
+/// before the cursor have handled the message, the one after it is the next hop, and the
+/// one after `~>` is the destination, omitted if it's the next hop.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct RelayTrace {
+    /// Nodes handled the message, in order.
+    pub path: Vec<Did>,
+    /// Position of the cursor in path, which is where the message is now.
+    pub path_end_cursor: usize,
+    /// The next node to handle the message.
+    pub next_hop: Did,
+    /// The destination of the message.
+    pub destination: Did,
+}
+
+impl std::fmt::Display for RelayTrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for did in &self.path[..self.path_end_cursor] {
+            write!(f, "{did} -> ")?;
+        }
+        write!(f, "[cursor] {}", self.next_hop)?;
+        if self.destination != self.next_hop {
+            write!(f, " ~> {}", self.destination)?;
+        }
+        Ok(())
+    }
+}
+
 impl MessageRelay {
     /// Create a new `MessageRelay`.
     pub fn new(path: Vec<Did>, next_hop: Did, destination: Did) -> Self {
@@ -41,11 +71,7 @@ impl MessageRelay {
     /// Validate relay, then create a new `MessageRelay` that have `current` did in the end of path.
     /// The new relay will use `next_hop` as `next_hop` and `self.destination` as `destination`.
     pub fn forward(&self, current: Did, next_hop: Did) -> Result<Self> {
-        self.validate(current)?;
-
-        if self.next_hop != current {
-            return Err(Error::InvalidNextHop);
-        }
+        self.traced(self.validate(current))?;
 
         let mut path = self.path.clone();
         path.push(current);
@@ -61,10 +87,10 @@ impl MessageRelay {
     /// The new relay will use `self.path[self.path.len() - 1]` as `next_hop` and `self.sender()` as `destination`.
     /// In the new relay, the path will be cleared and only have `current` did.
     pub fn report(&self, current: Did) -> Result<Self> {
-        self.validate(current)?;
+        self.traced(self.validate(current))?;
 
         if self.path.is_empty() {
-            return Err(Error::CannotInferNextHop);
+            return self.traced(Err(Error::CannotInferNextHop));
         }
 
         Ok(Self {
@@ -74,6 +100,21 @@ impl MessageRelay {
         })
     }
 
+    /// Create a new report relay like `self` that is sent to `via` instead of retracing path,
+    /// used when the node it retraces is gone. Following nodes route the report toward
+    /// destination by DHT as usual.
+    pub fn reroute_report(&self, via: Did) -> Result<Self> {
+        if self.path.last() == Some(&via) {
+            return self.traced(Err(Error::InvalidNextHop));
+        }
+
+        Ok(Self {
+            path: self.path.clone(),
+            next_hop: via,
+            destination: self.destination,
+        })
+    }
+
     /// Sometime the sender may not know the destination of the message. They just use next_hop as destination.
     /// The next node can find a new next_hop, and may use this function to set that next_hop as destination again.
     pub fn reset_destination(&self, destination: Did) -> Self {
@@ -82,6 +123,28 @@ impl MessageRelay {
         relay
     }
 
+    /// Get a [RelayTrace] of the relay, which can be displayed as a hop chain.
+    pub fn trace(&self) -> RelayTrace {
+        RelayTrace {
+            path: self.path.clone(),
+            path_end_cursor: self.path.len(),
+            next_hop: self.next_hop,
+            destination: self.destination,
+        }
+    }
+
+    /// Log the trace of relay if the result is an error of finding next hop.
+    fn traced<T>(&self, result: Result<T>) -> Result<T> {
+        if let Err(e @ (Error::InvalidNextHop | Error::CannotInferNextHop)) = &result {
+            tracing::error!(
+                target: "rings::relay",
+                "Failed to relay message: {e}, trace: {}",
+                self.trace()
+            );
+        }
+        result
+    }
+
     /// Check if path and destination is valid.
     pub fn validate(&self, current: Did) -> Result<()> {
         if self.next_hop != current {
@@ -95,7 +158,7 @@ impl MessageRelay {
 
         // Prevent infinite loop
         if has_infinite_loop(&self.path) {
-            tracing::error!("Infinite path detected {:?}", self.path);
+            tracing::error!(target: "rings::relay", "Infinite path detected {:?}", self.path);
             return Err(Error::InfiniteRelayPath);
         }
 
@@ -166,8 +229,96 @@ where T: PartialEq + std::fmt::Debug {
 
 #[cfg(test)]
 mod test {
+    use std::str::FromStr;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use tracing_subscriber::layer::SubscriberExt;
+
     use super::*;
 
+    #[test]
+    fn test_display_trace() {
+        let dids = (1..=4)
+            .map(|i| Did::from_str(&format!("0x{i:040x}")).unwrap())
+            .collect::<Vec<_>>();
+        let relay = MessageRelay::new(vec![dids[0]], dids[1], dids[3])
+            .forward(dids[1], dids[2])
+            .unwrap();
+
+        let trace = relay.trace();
+        assert_eq!(trace.path, vec![dids[0], dids[1]]);
+        assert_eq!(trace.path_end_cursor, 2);
+        assert_eq!(
+            trace.to_string(),
+            format!(
+                "{} -> {} -> [cursor] {} ~> {}",
+                dids[0], dids[1], dids[2], dids[3]
+            )
+        );
+
+        let relay = relay.reset_destination(dids[2]);
+        assert_eq!(
+            relay.trace().to_string(),
+            format!("{} -> {} -> [cursor] {}", dids[0], dids[1], dids[2])
+        );
+    }
+
+    #[test]
+    fn test_reroute_report() {
+        let dids = (1..=4)
+            .map(|i| Did::from_str(&format!("0x{i:040x}")).unwrap())
+            .collect::<Vec<_>>();
+        let relay = MessageRelay::new(vec![dids[0], dids[1]], dids[2], dids[2])
+            .report(dids[2])
+            .unwrap();
+        assert_eq!(relay.next_hop, dids[1]);
+
+        let rerouted = relay.reroute_report(dids[3]).unwrap();
+        assert_eq!(rerouted.path, vec![dids[2]]);
+        assert_eq!(rerouted.next_hop, dids[3]);
+        assert_eq!(rerouted.destination, dids[0]);
+        assert!(rerouted.forward(dids[3], dids[0]).is_ok());
+
+        assert!(matches!(
+            relay.reroute_report(dids[2]),
+            Err(Error::InvalidNextHop)
+        ));
+    }
+
+    /// Collect targets of events.
+    struct TargetCollector(Arc<Mutex<Vec<String>>>);
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for TargetCollector {
+        fn on_event(
+            &self,
+            event: &tracing::Event<'_>,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let target = event.metadata().target().to_string();
+            self.0.lock().unwrap().push(target);
+        }
+    }
+
+    #[test]
+    fn test_log_target() {
+        let dids = (1..=2)
+            .map(|i| Did::from_str(&format!("0x{i:040x}")).unwrap())
+            .collect::<Vec<_>>();
+        let path = [dids.clone(), dids.clone(), dids.clone()].concat();
+        let relay = MessageRelay::new(path, dids[0], dids[1]);
+
+        let targets = Arc::new(Mutex::new(vec![]));
+        let subscriber = tracing_subscriber::registry().with(TargetCollector(targets.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            assert!(matches!(
+                relay.validate(dids[0]),
+                Err(Error::InfiniteRelayPath)
+            ));
+        });
+        assert_eq!(*targets.lock().unwrap(), vec!["rings::relay".to_string()]);
+    }
+
     #[test]
     #[rustfmt::skip]
     fn test_has_infinite_loop() {
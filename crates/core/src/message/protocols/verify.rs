@@ -9,10 +9,15 @@ use crate::consts::DEFAULT_TTL_MS;
 use crate::consts::MAX_TTL_MS;
 use crate::consts::TS_OFFSET_TOLERANCE_MS;
 use crate::dht::Did;
+use crate::ecc::signers;
+use crate::ecc::PublicKey;
 use crate::error::Result;
 use crate::session::Session;
 use crate::session::SessionSk;
+use crate::session::SignatureScheme;
 use crate::utils::get_epoch_ms;
+use crate::utils::Clock;
+use crate::utils::SystemClock;
 
 /// Message Verification is based on session, and sig.
 /// it also included ttl time and created ts.
@@ -55,15 +60,88 @@ impl MessageVerification {
 
     /// Verify a MessageVerification
     pub fn verify(&self, data: &[u8]) -> bool {
+        self.verify_by(data, &SystemClock)
+    }
+
+    /// Verify a MessageVerification, checking expiry of session by the time of `clock`.
+    pub fn verify_by(&self, data: &[u8], clock: &dyn Clock) -> bool {
         let msg = pack_msg(data, self.ts_ms, self.ttl_ms);
 
         self.session
-            .verify(&msg, &self.sig)
+            .verify_by(&msg, &self.sig, clock)
             .map_err(|e| {
                 tracing::warn!("MessageVerification verify failed: {:?}", e);
             })
             .is_ok()
     }
+
+    /// Recover the public key of the session that signed `data`.
+    pub fn session_pubkey(&self, data: &[u8]) -> Result<PublicKey<33>> {
+        let msg = pack_msg(data, self.ts_ms, self.ttl_ms);
+        signers::secp256k1::recover(&msg, &self.sig)
+    }
+}
+
+/// Details of a message which passed [MessageVerificationExt::verify_detailed].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedInfo {
+    /// Did of the account which signed the session of message.
+    pub origin: Did,
+    /// Timestamp when the session was created, in milliseconds.
+    pub session_ts_ms: u128,
+    /// Timestamp when the session expires, in milliseconds.
+    pub session_expires_at_ms: u128,
+    /// Signature scheme of the account which signed the session.
+    pub scheme: SignatureScheme,
+}
+
+/// Why a message failed [MessageVerificationExt::verify_detailed].
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum VerifyFailure {
+    /// The ttl of message passed.
+    #[error("Message expired")]
+    MessageExpired,
+    /// The session which signed message expired.
+    #[error("Session expired")]
+    SessionExpired,
+    /// The session is not signed by its account.
+    #[error("Session is not signed by its account")]
+    InvalidSessionSignature,
+    /// The message is not signed by its session, or it's modified after signing.
+    #[error("Message is not signed by its session")]
+    InvalidSignature,
+    /// The message cannot be verified at all, such as a signature of wrong length.
+    #[error("Malformed message: {0}")]
+    Malformed(String),
+}
+
+impl MessageVerification {
+    /// Verify a MessageVerification like [MessageVerification::verify], but tell why it fails.
+    /// Expiry of the message itself is not checked here.
+    pub fn verify_detailed(&self, data: &[u8]) -> std::result::Result<VerifiedInfo, VerifyFailure> {
+        if self.sig.len() != 65 {
+            return Err(VerifyFailure::Malformed(format!(
+                "signature length is {}",
+                self.sig.len()
+            )));
+        }
+        if self.session.is_expired() {
+            return Err(VerifyFailure::SessionExpired);
+        }
+        if self.session.verify_self().is_err() {
+            return Err(VerifyFailure::InvalidSessionSignature);
+        }
+        let msg = pack_msg(data, self.ts_ms, self.ttl_ms);
+        if self.session.verify(&msg, &self.sig).is_err() {
+            return Err(VerifyFailure::InvalidSignature);
+        }
+        Ok(VerifiedInfo {
+            origin: self.session.account_did(),
+            session_ts_ms: self.session.ts_ms(),
+            session_expires_at_ms: self.session.ts_ms() + self.session.ttl_ms() as u128,
+            scheme: self.session.signature_scheme(),
+        })
+    }
 }
 
 /// This trait helps a struct with `MessageVerification` field to `verify` itself.
@@ -77,11 +155,16 @@ pub trait MessageVerificationExt {
 
     /// Checks whether the message is expired.
     fn is_expired(&self) -> bool {
+        self.is_expired_by(&SystemClock)
+    }
+
+    /// Checks whether the message is expired by the time of `clock`.
+    fn is_expired_by(&self, clock: &dyn Clock) -> bool {
         if self.verification().ttl_ms > MAX_TTL_MS {
             return false;
         }
 
-        let now = get_epoch_ms();
+        let now = clock.now_ms();
 
         if self.verification().ts_ms - TS_OFFSET_TOLERANCE_MS > now {
             return false;
@@ -92,7 +175,13 @@ pub trait MessageVerificationExt {
 
     /// Verifies that the message is not expired and that the signature is valid.
     fn verify(&self) -> bool {
-        if self.is_expired() {
+        self.verify_by(&SystemClock)
+    }
+
+    /// Verifies like [MessageVerificationExt::verify], checking expiry of the message and its
+    /// session by the time of `clock`.
+    fn verify_by(&self, clock: &dyn Clock) -> bool {
+        if self.is_expired_by(clock) {
             tracing::warn!("message expired");
             return false;
         }
@@ -102,11 +191,30 @@ pub trait MessageVerificationExt {
             return false;
         };
 
-        self.verification().verify(&data)
+        self.verification().verify_by(&data, clock)
+    }
+
+    /// Verifies like [MessageVerificationExt::verify], but returns who signed the message and
+    /// why it fails, instead of a bool.
+    fn verify_detailed(&self) -> std::result::Result<VerifiedInfo, VerifyFailure> {
+        if self.is_expired() {
+            return Err(VerifyFailure::MessageExpired);
+        }
+        let data = self
+            .verification_data()
+            .map_err(|e| VerifyFailure::Malformed(e.to_string()))?;
+        self.verification().verify_detailed(&data)
     }
 
     /// Get signer did from verification.
     fn signer(&self) -> Did {
         self.verification().session.account_did()
     }
+
+    /// Get public key of signer's session, which can be used to encrypt messages to the
+    /// signer by [crate::message::MessagePayload::new_send_encrypted].
+    fn session_pubkey(&self) -> Result<PublicKey<33>> {
+        self.verification()
+            .session_pubkey(&self.verification_data()?)
+    }
 }
@@ -11,7 +11,11 @@ use crate::dht::vnode::VNodeOperation;
 use crate::dht::vnode::VirtualNode;
 use crate::dht::Did;
 use crate::dht::TopoInfo;
+use crate::error::Error;
 use crate::error::Result;
+use crate::message::MessageVerification;
+use crate::session::SessionScope;
+use crate::session::SessionSk;
 
 /// The `Then` trait is used to associate a type with a "then" scenario.
 pub trait Then {
@@ -22,18 +26,50 @@ pub trait Then {
 /// MessageType use to ask for connection, send to remote with transport_uuid and handshake_info.
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ConnectNodeSend {
-    /// sdp offer of webrtc
-    pub sdp: String,
+    /// sdp offer of webrtc, encoded by [crate::message::HandshakeCodec]
+    pub sdp: Vec<u8>,
     /// The network_id is used to distinguish different networks.
     /// Use 1 for main network.
     pub network_id: u32,
+    /// Capabilities advertised by the sender of offer.
+    pub capabilities: Vec<String>,
+    /// Id of the connection attempt, shared by the offer, answer and accept of a handshake.
+    pub attempt_id: Option<uuid::Uuid>,
+    /// Whether it's an ICE restart offer of an existing connection, which should be answered
+    /// by that connection instead of a new one.
+    pub ice_restart: bool,
 }
 
 /// MessageType report to origin with own transport_uuid and handshake_info.
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ConnectNodeReport {
-    /// sdp answer of webrtc
-    pub sdp: String,
+    /// sdp answer of webrtc, encoded by [crate::message::HandshakeCodec]
+    pub sdp: Vec<u8>,
+    /// Capabilities advertised by the sender of answer.
+    pub capabilities: Vec<String>,
+    /// Id of the connection attempt, echoed from [ConnectNodeSend].
+    pub attempt_id: Option<uuid::Uuid>,
+}
+
+/// MessageType use to ask peer for a fresh handshake, after the handshake of their connection
+/// failed before the data channel opens. The receiver closes its side of the failed connection
+/// and sends a new [ConnectNodeSend].
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ConnectNodeRenegotiate {
+    /// Id of the failed connection attempt.
+    pub attempt_id: Option<uuid::Uuid>,
+}
+
+/// MessageType use to trickle an ICE candidate to the peer of a handshake.
+/// It's sent after [ConnectNodeSend] or [ConnectNodeReport] when trickle ICE is enabled.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct IceCandidate {
+    /// The candidate line of sdp.
+    pub candidate: String,
+    /// The media stream identification tag of the candidate.
+    pub mid: Option<String>,
+    /// The index of media description in sdp of the candidate.
+    pub mline_index: Option<u16>,
 }
 
 /// MessageType use to find successor in a chord ring.
@@ -59,6 +95,25 @@ pub struct FindSuccessorReport {
     pub handler: FindSuccessorReportHandler,
 }
 
+/// MessageType use to ask a node which candidate it will route a lookup of `did` to.
+/// The receiver answers with [LookupProbeReport] without forwarding the lookup.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct LookupProbeSend {
+    /// did of lookup target
+    pub did: Did,
+}
+
+/// MessageType use to report the routing decision of a [LookupProbeSend].
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct LookupProbeReport {
+    /// did of lookup target
+    pub did: Did,
+    /// The next hop chosen by the reporter, or the successor of target if resolved.
+    pub candidate: Did,
+    /// Indicates the reporter is the predecessor of target and `candidate` is its successor.
+    pub resolved: bool,
+}
+
 /// MessageType use notify the successor about the predecessor inferred by current node.
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct NotifyPredecessorSend {
@@ -155,10 +210,174 @@ pub struct SyncVNodeWithSuccessor {
     pub data: Vec<VirtualNode>,
 }
 
+/// MessageType for storing a virtual node on its responsible node and successors of it.
+///
+/// The message is routed to the node responsible for `vnode.did` first. The responsible
+/// node stores the vnode and forwards it to its successor with `replication` decreased,
+/// until `replication` copies are placed.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ReplicateVNode {
+    /// The virtual node to be stored.
+    pub vnode: VirtualNode,
+    /// Number of copies still need to be placed, including the receiver's one.
+    pub replication: u8,
+    /// Indicates the receiver should store the vnode directly instead of routing it.
+    pub placed: bool,
+}
+
+/// MessageType for subscribing a topic on the node responsible for it.
+///
+/// The subscriber is the signer of transaction. The subscription expires after `ttl_ms`
+/// unless it's refreshed, and it's removed at once if `ttl_ms` is 0.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SubscribeTopic {
+    /// The topic to subscribe.
+    pub topic: String,
+    /// Time to live of the subscription.
+    pub ttl_ms: u64,
+}
+
+/// MessageType for publishing data to subscribers of a topic.
+///
+/// The message is routed to the node responsible for the topic first, which sends it to
+/// each subscriber with `delivered` set. The topic and data are signed by the publisher, so
+/// that subscribers get the publisher by [PublishTopic::publisher] instead of trusting the
+/// responsible node.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PublishTopic {
+    /// The topic published to.
+    pub topic: String,
+    /// The published data.
+    pub data: Vec<u8>,
+    /// Signature of topic and data by the publisher.
+    verification: MessageVerification,
+    /// Indicates the message is sent to a subscriber instead of the responsible node.
+    pub delivered: bool,
+}
+
+impl PublishTopic {
+    /// Publish `data` to `topic`, signed by `session_sk` of publisher.
+    pub fn new(topic: &str, data: Vec<u8>, session_sk: &SessionSk) -> Result<Self> {
+        let verification = MessageVerification::new(&Self::signed_data(topic, &data), session_sk)?;
+        Ok(Self {
+            topic: topic.to_string(),
+            data,
+            verification,
+            delivered: false,
+        })
+    }
+
+    fn signed_data(topic: &str, data: &[u8]) -> Vec<u8> {
+        let mut signed = (topic.len() as u64).to_be_bytes().to_vec();
+        signed.extend_from_slice(topic.as_bytes());
+        signed.extend_from_slice(data);
+        signed
+    }
+
+    /// Get the publisher from the signature of topic and data.
+    /// Return [Error::InvalidMessage] if the signature doesn't match.
+    pub fn publisher(&self) -> Result<Did> {
+        if !self
+            .verification
+            .verify(&Self::signed_data(&self.topic, &self.data))
+        {
+            return Err(Error::InvalidMessage(
+                "Publication is not signed by publisher".to_string(),
+            ));
+        }
+        Ok(self.verification.session.account_did())
+    }
+}
+
+/// Metadata of a file sent by [crate::swarm::Swarm::send_file].
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct FileMetadata {
+    /// Name of the file.
+    pub name: String,
+    /// Size of the whole file in bytes.
+    pub size: u64,
+}
+
+/// MessageType carrying a piece of file sent by [crate::swarm::Swarm::send_file].
+/// Each chunk is acked by [FileChunkAck].
+#[derive(Deserialize, Serialize, Clone)]
+pub struct FileChunk {
+    /// Id of the transfer, the same for all chunks of a file.
+    pub transfer_id: uuid::Uuid,
+    /// Metadata of the file, only carried by the first chunk of a transfer.
+    pub metadata: Option<FileMetadata>,
+    /// Position of `data` in the file.
+    pub offset: u64,
+    /// Bytes of the file starting at `offset`.
+    pub data: Vec<u8>,
+    /// Indicates it's the last chunk of the file.
+    pub last: bool,
+}
+
+/// MessageType reporting bytes of a file received, in response to [FileChunk].
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct FileChunkAck {
+    /// Id of the transfer.
+    pub transfer_id: uuid::Uuid,
+    /// Position in the file up to which bytes are written by receiver.
+    pub received: u64,
+    /// Indicates the receiver doesn't accept the transfer.
+    pub rejected: bool,
+}
+
+/// MessageType carrying a message to the node responsible for `key`, see
+/// [crate::swarm::Swarm::send_to_key].
+///
+/// The destination of payload is `key`. Each node on the way resolves the next hop to the
+/// responsible node again, so that the message follows the ring if it changes in flight.
+///
+/// The message is carried as bincode bytes instead of a nested [Message], so that decoding a
+/// payload never recurses into a chain of [RouteToKey]. Nesting is rejected both ways.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RouteToKey {
+    /// The key, such as the did of a virtual node.
+    pub key: Did,
+    /// The message for the responsible node, serialized by bincode.
+    message: Vec<u8>,
+}
+
+impl RouteToKey {
+    /// Wrap `message` to the node responsible for `key`.
+    /// Return [Error::InvalidMessage] if it's a [RouteToKey] itself.
+    pub fn new(key: Did, message: &Message) -> Result<Self> {
+        if matches!(message, Message::RouteToKey(_)) {
+            return Err(Error::InvalidMessage(
+                "RouteToKey cannot be nested".to_string(),
+            ));
+        }
+        let message = bincode::serialize(message).map_err(Error::BincodeSerialize)?;
+        Ok(Self { key, message })
+    }
+
+    /// Decode the message for the responsible node.
+    /// Return [Error::InvalidMessage] if it's a [RouteToKey] itself.
+    pub fn message(&self) -> Result<Message> {
+        let message: Message =
+            bincode::deserialize(&self.message).map_err(Error::BincodeDeserialize)?;
+        if matches!(message, Message::RouteToKey(_)) {
+            return Err(Error::InvalidMessage(
+                "RouteToKey cannot be nested".to_string(),
+            ));
+        }
+        Ok(message)
+    }
+}
+
 /// MessageType use to customize message, will be handle by `custom_message` method.
 #[derive(Deserialize, Serialize, Clone)]
 pub struct CustomMessage(pub Vec<u8>);
 
+/// MessageType use to carry a message encrypted to the session of destination.
+/// Relays route it by the headers of payload, only the destination can decrypt the body.
+/// See [crate::message::MessagePayload::new_send_encrypted].
+#[derive(Deserialize, Serialize, Clone)]
+pub struct EncryptedMessage(pub Vec<u8>);
+
 /// MessageType enum Report contain FindSuccessorSend.
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[non_exhaustive]
@@ -213,6 +432,28 @@ pub enum Message {
     QueryForTopoInfoReport(QueryForTopoInfoReport),
     /// A chunk that can be deserialized to a payload.
     Chunk(Chunk),
+    /// An ICE candidate trickled after offer or answer.
+    IceCandidate(IceCandidate),
+    /// A message encrypted end to end.
+    Encrypted(EncryptedMessage),
+    /// Remote message of subscribing a topic.
+    SubscribeTopic(SubscribeTopic),
+    /// Remote message of publishing to a topic.
+    PublishTopic(PublishTopic),
+    /// A piece of file.
+    FileChunk(FileChunk),
+    /// Response of FileChunk.
+    FileChunkAck(FileChunkAck),
+    /// A message to the node responsible for a key.
+    RouteToKey(RouteToKey),
+    /// Remote message of storing replicas of a virtual node.
+    ReplicateVNode(ReplicateVNode),
+    /// Remote message of probing routing decision of a lookup.
+    LookupProbeSend(LookupProbeSend),
+    /// Response of LookupProbeSend.
+    LookupProbeReport(LookupProbeReport),
+    /// Remote message of asking for a fresh handshake after a failed one.
+    ConnectNodeRenegotiate(ConnectNodeRenegotiate),
 }
 
 impl std::fmt::Display for Message {
@@ -226,6 +467,103 @@ impl Message {
     pub fn custom(msg: &[u8]) -> Result<Message> {
         Ok(Message::CustomMessage(CustomMessage(msg.to_vec())))
     }
+
+    /// Name of the message type, used in logs and errors.
+    pub fn method(&self) -> &'static str {
+        match self {
+            Message::ConnectNodeSend(_) => "ConnectNodeSend",
+            Message::ConnectNodeReport(_) => "ConnectNodeReport",
+            Message::ConnectNodeRenegotiate(_) => "ConnectNodeRenegotiate",
+            Message::FindSuccessorSend(_) => "FindSuccessorSend",
+            Message::FindSuccessorReport(_) => "FindSuccessorReport",
+            Message::LookupProbeSend(_) => "LookupProbeSend",
+            Message::LookupProbeReport(_) => "LookupProbeReport",
+            Message::NotifyPredecessorSend(_) => "NotifyPredecessorSend",
+            Message::NotifyPredecessorReport(_) => "NotifyPredecessorReport",
+            Message::SearchVNode(_) => "SearchVNode",
+            Message::FoundVNode(_) => "FoundVNode",
+            Message::OperateVNode(_) => "OperateVNode",
+            Message::SyncVNodeWithSuccessor(_) => "SyncVNodeWithSuccessor",
+            Message::ReplicateVNode(_) => "ReplicateVNode",
+            Message::CustomMessage(_) => "CustomMessage",
+            Message::QueryForTopoInfoSend(_) => "QueryForTopoInfoSend",
+            Message::QueryForTopoInfoReport(_) => "QueryForTopoInfoReport",
+            Message::Chunk(_) => "Chunk",
+            Message::IceCandidate(_) => "IceCandidate",
+            Message::Encrypted(_) => "Encrypted",
+            Message::SubscribeTopic(_) => "SubscribeTopic",
+            Message::PublishTopic(_) => "PublishTopic",
+            Message::FileChunk(_) => "FileChunk",
+            Message::FileChunkAck(_) => "FileChunkAck",
+            Message::RouteToKey(_) => "RouteToKey",
+        }
+    }
+
+    /// Scope of session required to sign this message, see [SessionScope].
+    /// Messages maintaining DHT and chunks of other messages require none.
+    pub fn required_scope(&self) -> SessionScope {
+        match self {
+            Message::ConnectNodeSend(_)
+            | Message::ConnectNodeReport(_)
+            | Message::ConnectNodeRenegotiate(_)
+            | Message::IceCandidate(_) => SessionScope::CONNECT,
+            Message::OperateVNode(_)
+            | Message::SyncVNodeWithSuccessor(_)
+            | Message::ReplicateVNode(_) => SessionScope::VNODE_WRITE,
+            Message::CustomMessage(_)
+            | Message::Encrypted(_)
+            | Message::SubscribeTopic(_)
+            | Message::PublishTopic(_)
+            | Message::FileChunk(_)
+            | Message::FileChunkAck(_)
+            | Message::RouteToKey(_) => SessionScope::SEND_MESSAGE,
+            Message::FindSuccessorSend(_)
+            | Message::FindSuccessorReport(_)
+            | Message::LookupProbeSend(_)
+            | Message::LookupProbeReport(_)
+            | Message::NotifyPredecessorSend(_)
+            | Message::NotifyPredecessorReport(_)
+            | Message::SearchVNode(_)
+            | Message::FoundVNode(_)
+            | Message::QueryForTopoInfoSend(_)
+            | Message::QueryForTopoInfoReport(_)
+            | Message::Chunk(_) => SessionScope::empty(),
+        }
+    }
+
+    /// Default priority of sending this message.
+    /// Connect handshake is [Priority::Control], DHT maintenance is [Priority::High],
+    /// file chunks are [Priority::Bulk], others are [Priority::Normal].
+    pub fn priority(&self) -> Priority {
+        match self {
+            Message::ConnectNodeSend(_)
+            | Message::ConnectNodeReport(_)
+            | Message::ConnectNodeRenegotiate(_)
+            | Message::IceCandidate(_) => Priority::Control,
+            Message::FindSuccessorSend(_)
+            | Message::FindSuccessorReport(_)
+            | Message::NotifyPredecessorSend(_)
+            | Message::NotifyPredecessorReport(_) => Priority::High,
+            Message::FileChunk(_) => Priority::Bulk,
+            _ => Priority::Normal,
+        }
+    }
+}
+
+/// Priority of an outbound message.
+/// Messages waiting in the outbound queue of a connection are sent in the order of
+/// priority, then in the order of sending. Variants are ordered from the most urgent.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Control-plane messages, such as connect handshake.
+    Control,
+    /// Messages maintaining DHT.
+    High,
+    /// Default priority.
+    #[default]
+    Normal,
+    /// Bulk data transfer, which can be delayed by any other message.
+    Bulk,
 }
 
 impl std::fmt::Debug for CustomMessage {
@@ -235,3 +573,43 @@ impl std::fmt::Debug for CustomMessage {
             .finish()
     }
 }
+
+impl std::fmt::Debug for FileChunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileChunk")
+            .field("transfer_id", &self.transfer_id)
+            .field("metadata", &self.metadata)
+            .field("offset", &self.offset)
+            .field("size", &self.data.len())
+            .field("last", &self.last)
+            .finish()
+    }
+}
+
+impl std::fmt::Debug for EncryptedMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EncryptedMessage")
+            .field("size", &self.0.len())
+            .finish()
+    }
+}
+
+impl From<rings_transport::core::transport::IceCandidate> for IceCandidate {
+    fn from(c: rings_transport::core::transport::IceCandidate) -> Self {
+        Self {
+            candidate: c.candidate,
+            mid: c.sdp_mid,
+            mline_index: c.sdp_mline_index,
+        }
+    }
+}
+
+impl From<IceCandidate> for rings_transport::core::transport::IceCandidate {
+    fn from(c: IceCandidate) -> Self {
+        Self {
+            candidate: c.candidate,
+            sdp_mid: c.mid,
+            sdp_mline_index: c.mline_index,
+        }
+    }
+}
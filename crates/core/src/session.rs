@@ -68,11 +68,23 @@
 //!    let sessionSk: SessionSk = sessionBuilder.build()
 //! ```
 
+//!
+//! To keep the account key out of memory, such as in an HSM or a remote KMS, implement [Signer]
+//! for it and use [SessionSk::new_with_signer]. Only the session proof is signed by the account,
+//! messages are signed by the delegated session key.
+//!
+//! A session can issue sub-sessions by [SessionSk::delegate], which are signed by the session
+//! instead of the account, and are allowed to sign only messages permitted by their
+//! [SessionScope]. Sub-sessions cannot delegate again, so a sub-session carries the proof of
+//! its parent as a flat [ParentSession].
 //!
 //! See [SessionSk] and [SessionSkBuilder] for details.
 
 use std::str::FromStr;
+use std::time::Duration;
 
+use async_trait::async_trait;
+use bitflags::bitflags;
 use rings_derive::wasm_export;
 use serde::Deserialize;
 use serde::Serialize;
@@ -85,10 +97,78 @@ use crate::ecc::PublicKey;
 use crate::ecc::SecretKey;
 use crate::error::Error;
 use crate::error::Result;
+use crate::storage::KvStorageInterface;
 use crate::utils;
+use crate::utils::Clock;
+
+/// Key of [SessionSk] in storage, see [SessionSk::save].
+const SESSION_SK_STORAGE_KEY: &str = "session_sk";
+
+/// Type of boxed [Signer].
+#[cfg(not(feature = "wasm"))]
+pub type BoxedSigner = Box<dyn Signer + Send + Sync>;
 
-fn pack_session(session_id: Did, ts_ms: u128, ttl_ms: u64) -> String {
-    format!("{}\n{}\n{}", session_id, ts_ms, ttl_ms)
+/// Type of boxed [Signer].
+#[cfg(feature = "wasm")]
+pub type BoxedSigner = Box<dyn Signer>;
+
+/// Signer of an [Account], used to sign the proof of session when creating [SessionSk].
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+pub trait Signer {
+    /// Sign message by the account.
+    async fn sign(&self, msg: &[u8]) -> Result<Vec<u8>>;
+
+    /// Did of the account.
+    fn did(&self) -> Did;
+
+    /// Type of the account, which is lower case of [Account] variant.
+    fn account_type(&self) -> String {
+        "secp256k1".to_string()
+    }
+
+    /// Entity of the account, see [SessionSkBuilder::new].
+    fn account_entity(&self) -> String {
+        self.did().to_string()
+    }
+}
+
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl Signer for SecretKey {
+    async fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        Ok(self.sign_raw(msg).to_vec())
+    }
+
+    fn did(&self) -> Did {
+        self.address().into()
+    }
+}
+
+bitflags! {
+    /// Messages a [Session] is allowed to sign. Sessions built by [SessionSkBuilder] are
+    /// allowed to sign all of them, sub-sessions issued by [SessionSk::delegate] are limited.
+    /// See [crate::message::Message::required_scope].
+    #[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone, Copy)]
+    #[serde(transparent)]
+    pub struct SessionScope: u8 {
+        /// Send custom messages, publish and subscribe topics, and transfer files.
+        const SEND_MESSAGE = 1;
+        /// Connect to peers, which is the handshake of connection.
+        const CONNECT = 1 << 1;
+        /// Write to virtual nodes of DHT, such as storing data.
+        const VNODE_WRITE = 1 << 2;
+    }
+}
+
+fn pack_session(session_id: Did, ts_ms: u128, ttl_ms: u64, scope: SessionScope) -> String {
+    let packed = format!("{}\n{}\n{}", session_id, ts_ms, ttl_ms);
+    // The proof of an unlimited session is kept the same as before scope is introduced.
+    if scope == SessionScope::all() {
+        packed
+    } else {
+        format!("{}\n{}", packed, scope.bits())
+    }
 }
 
 /// SessionSkBuilder is used to build a [SessionSk].
@@ -145,10 +225,50 @@ pub struct Session {
     ttl_ms: u64,
     /// Timestamp when session created
     ts_ms: u128,
-    /// Signature to verify that the session was signed by the account.
+    /// Signature to verify that the session was signed by the account,
+    /// or by the parent session if it's delegated.
+    sig: Vec<u8>,
+    /// Messages the session is allowed to sign.
+    /// The defaults only help sessions dumped to json before, see
+    /// [crate::consts::PROTOCOL_VERSION] for the wire format.
+    #[serde(default = "SessionScope::all")]
+    scope: SessionScope,
+    /// The session which delegated this one, see [SessionSk::delegate].
+    #[serde(default)]
+    parent: Option<ParentSession>,
+}
+
+/// Proof of the session which delegated a sub-session, see [SessionSk::delegate].
+///
+/// Only sessions signed by the account can delegate, so the parent shares the account of the
+/// sub-session and is allowed to sign all messages. It never has a parent itself.
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone)]
+pub struct ParentSession {
+    /// Did of parent session.
+    session_id: Did,
+    /// Lifetime of parent session.
+    ttl_ms: u64,
+    /// Timestamp when parent session created.
+    ts_ms: u128,
+    /// Signature of parent session signed by the account.
     sig: Vec<u8>,
 }
 
+impl ParentSession {
+    /// Restore the parent session of a sub-session of `account`.
+    fn session(&self, account: &Account) -> Session {
+        Session {
+            session_id: self.session_id,
+            account: account.clone(),
+            ttl_ms: self.ttl_ms,
+            ts_ms: self.ts_ms,
+            sig: self.sig.clone(),
+            scope: SessionScope::all(),
+            parent: None,
+        }
+    }
+}
+
 /// We will support as many protocols/algorithms as possible.
 /// Currently, it comprises Secp256k1, EIP191, BIP137, and Ed25519.
 /// We welcome any issues and PRs for additional implementations.
@@ -166,6 +286,21 @@ pub enum Account {
     Ed25519(PublicKey<33>),
 }
 
+/// Signature scheme of the [Account] which signed a [Session].
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum SignatureScheme {
+    /// See [Account::Secp256k1].
+    Secp256k1,
+    /// See [Account::Secp256r1].
+    Secp256r1,
+    /// See [Account::EIP191].
+    EIP191,
+    /// See [Account::BIP137].
+    BIP137,
+    /// See [Account::Ed25519].
+    Ed25519,
+}
+
 impl TryFrom<(String, String)> for Account {
     type Error = Error;
 
@@ -224,7 +359,12 @@ impl SessionSkBuilder {
 
     /// Construct unsigned_info string for signing.
     pub fn unsigned_proof(&self) -> String {
-        pack_session(self.sk.address().into(), self.ts_ms, self.ttl_ms)
+        pack_session(
+            self.sk.address().into(),
+            self.ts_ms,
+            self.ttl_ms,
+            SessionScope::all(),
+        )
     }
 
     /// Set the signature of session that signed by account.
@@ -248,6 +388,8 @@ impl SessionSkBuilder {
             ttl_ms: self.ttl_ms,
             ts_ms: self.ts_ms,
             sig: self.sig,
+            scope: SessionScope::all(),
+            parent: None,
         };
 
         session.verify_self()?;
@@ -262,25 +404,84 @@ impl SessionSkBuilder {
 impl Session {
     /// Pack the session into a string for verification or public key recovery.
     pub fn pack(&self) -> Vec<u8> {
-        pack_session(self.session_id, self.ts_ms, self.ttl_ms)
+        pack_session(self.session_id, self.ts_ms, self.ttl_ms, self.scope)
             .as_bytes()
             .to_vec()
     }
 
+    /// Get the messages session is allowed to sign.
+    pub fn scope(&self) -> SessionScope {
+        self.scope
+    }
+
+    /// Check session is allowed to sign messages of `scope`, return [Error::NoPermission] if not.
+    pub fn check_scope(&self, scope: SessionScope, action: &str) -> Result<()> {
+        if !self.scope.contains(scope) {
+            return Err(Error::NoPermission(self.account_did(), action.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Get the timestamp when session created, in milliseconds.
+    pub fn ts_ms(&self) -> u128 {
+        self.ts_ms
+    }
+
+    /// Get the lifetime of session, in milliseconds.
+    pub fn ttl_ms(&self) -> u64 {
+        self.ttl_ms
+    }
+
+    /// Get the signature scheme of the account which signed session.
+    pub fn signature_scheme(&self) -> SignatureScheme {
+        match self.account {
+            Account::Secp256k1(_) => SignatureScheme::Secp256k1,
+            Account::Secp256r1(_) => SignatureScheme::Secp256r1,
+            Account::EIP191(_) => SignatureScheme::EIP191,
+            Account::BIP137(_) => SignatureScheme::BIP137,
+            Account::Ed25519(_) => SignatureScheme::Ed25519,
+        }
+    }
+
     /// Check session is expired or not.
     pub fn is_expired(&self) -> bool {
-        let now = utils::get_epoch_ms();
-        now > self.ts_ms + self.ttl_ms as u128
+        self.is_expired_by(&utils::SystemClock)
+    }
+
+    /// Check session is expired or not by the time of `clock`.
+    pub fn is_expired_by(&self, clock: &dyn Clock) -> bool {
+        clock.now_ms() > self.ts_ms + self.ttl_ms as u128
+    }
+
+    /// Get the lifetime left of session by the time of `clock`, which is zero once it's expired.
+    pub fn remaining_ttl_by(&self, clock: &dyn Clock) -> Duration {
+        let expires_at = self.ts_ms + self.ttl_ms as u128;
+        let remaining = expires_at.saturating_sub(clock.now_ms());
+        Duration::from_millis(remaining as u64)
     }
 
     /// Verify session.
     pub fn verify_self(&self) -> Result<()> {
-        if self.is_expired() {
+        self.verify_self_by(&utils::SystemClock)
+    }
+
+    /// Verify session, checking its expiry by the time of `clock`.
+    pub fn verify_self_by(&self, clock: &dyn Clock) -> Result<()> {
+        if self.is_expired_by(clock) {
             return Err(Error::SessionExpired);
         }
 
         let auth_bytes = self.pack();
 
+        if let Some(ref parent) = self.parent {
+            // The parent has no parent, so it's verified by the account without recursion.
+            parent.session(&self.account).verify_self_by(clock)?;
+            if !signers::secp256k1::verify(&auth_bytes, &parent.session_id, &self.sig) {
+                return Err(Error::VerifySignatureFailed);
+            }
+            return Ok(());
+        }
+
         if !(match self.account {
             Account::Secp256k1(did) => {
                 signers::secp256k1::verify(&auth_bytes, &did.into(), &self.sig)
@@ -302,7 +503,12 @@ impl Session {
 
     /// Verify message.
     pub fn verify(&self, msg: &[u8], sig: impl AsRef<[u8]>) -> Result<()> {
-        self.verify_self()?;
+        self.verify_by(msg, sig, &utils::SystemClock)
+    }
+
+    /// Verify message, checking expiry of session by the time of `clock`.
+    pub fn verify_by(&self, msg: &[u8], sig: impl AsRef<[u8]>, clock: &dyn Clock) -> Result<()> {
+        self.verify_self_by(clock)?;
         if !signers::secp256k1::verify(msg, &self.session_id, sig) {
             return Err(Error::VerifySignatureFailed);
         }
@@ -311,6 +517,9 @@ impl Session {
 
     /// Get public key from session for encryption.
     pub fn account_pubkey(&self) -> Result<PublicKey<33>> {
+        if let Some(ref parent) = self.parent {
+            return parent.session(&self.account).account_pubkey();
+        }
         let auth_bytes = self.pack();
         match self.account {
             Account::Secp256k1(_) => signers::secp256k1::recover(&auth_bytes, &self.sig),
@@ -348,6 +557,45 @@ impl SessionSk {
         builder.build()
     }
 
+    /// Generate Session signed by a [Signer] of account.
+    pub async fn new_with_signer(signer: &BoxedSigner) -> Result<Self> {
+        let builder = SessionSkBuilder::new(signer.account_entity(), signer.account_type());
+        let sig = signer.sign(builder.unsigned_proof().as_bytes()).await?;
+        builder.set_session_sig(sig).build()
+    }
+
+    /// Issue a sub-session for `ttl_ms`, which is allowed to sign only messages of `scope`.
+    /// It's signed by this session, so that a less trusted client, such as a web page, can send
+    /// messages on behalf of the account without the account key or this session key.
+    /// A sub-session cannot delegate again, which returns [Error::NoPermission].
+    pub fn delegate(&self, scope: SessionScope, ttl_ms: u64) -> Result<SessionSk> {
+        if self.session.parent.is_some() {
+            return Err(Error::NoPermission(
+                self.account_did(),
+                "delegate".to_string(),
+            ));
+        }
+        let parent = ParentSession {
+            session_id: self.session.session_id,
+            ttl_ms: self.session.ttl_ms,
+            ts_ms: self.session.ts_ms,
+            sig: self.session.sig.clone(),
+        };
+        let sk = SecretKey::random();
+        let mut session = Session {
+            session_id: sk.address().into(),
+            account: self.session.account.clone(),
+            ttl_ms,
+            ts_ms: utils::get_epoch_ms(),
+            sig: vec![],
+            scope,
+            parent: Some(parent),
+        };
+        session.sig = self.sign(&session.pack())?;
+        session.verify_self()?;
+        Ok(SessionSk { session, sk })
+    }
+
     /// Get session from SessionSk.
     pub fn session(&self) -> Session {
         self.session.clone()
@@ -365,17 +613,59 @@ impl SessionSk {
         self.session.account_did()
     }
 
+    /// Get public key of session, which others use to encrypt messages to this session.
+    pub fn pubkey(&self) -> PublicKey<33> {
+        self.sk.pubkey()
+    }
+
+    /// Decrypt data encrypted to [SessionSk::pubkey] by ECIES.
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        ecies::decrypt(&self.sk.ser(), data).map_err(Error::MessageDecryptionFailed)
+    }
+
     /// Dump session_sk to string, allowing user to save it in a config file.
     /// It can be restored using `SessionSk::from_str`.
     pub fn dump(&self) -> Result<String> {
         let s = serde_json::to_string(&self).map_err(|_| Error::SerializeError)?;
         base58_monero::encode_check(s.as_bytes()).map_err(|_| Error::Encode)
     }
+
+    /// Save session_sk, including its session key and validity window, to `storage`, so that
+    /// it can be restored by [SessionSk::load] after restart without delegating again.
+    /// The session key is saved in plain text, like [SessionSk::dump].
+    pub async fn save<S>(&self, storage: &S) -> Result<()>
+    where S: KvStorageInterface<SessionSk> + ?Sized {
+        storage.put(SESSION_SK_STORAGE_KEY, self).await
+    }
+
+    /// Restore session_sk saved by [SessionSk::save]. If there is none, or it's expired, or it's
+    /// not delegated by the account of `key`, a new one is created by `key` and saved instead.
+    pub async fn load<S>(storage: &S, key: &SecretKey) -> Result<Self>
+    where S: KvStorageInterface<SessionSk> + ?Sized {
+        let account: Did = key.address().into();
+        match storage.get(SESSION_SK_STORAGE_KEY).await {
+            Ok(Some(sk)) if sk.account_did() != account => {
+                tracing::info!("Saved session belongs to another account, create a new one");
+            }
+            Ok(Some(sk)) => match sk.session.verify_self() {
+                Ok(()) => return Ok(sk),
+                Err(e) => tracing::info!("Saved session is invalid, create a new one: {e}"),
+            },
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Failed to load saved session, create a new one: {e}"),
+        }
+        let sk = Self::new_with_seckey(key)?;
+        sk.save(storage).await?;
+        Ok(sk)
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::message::Message;
+    use crate::message::MessagePayload;
+    use crate::message::MessageVerificationExt;
 
     #[test]
     pub fn test_session_verify() {
@@ -385,6 +675,19 @@ mod test {
         assert!(session.verify_self().is_ok());
     }
 
+    #[test]
+    pub fn test_session_expired_by_mock_clock() {
+        let key = SecretKey::random();
+        let sm = SessionSk::new_with_seckey(&key).unwrap();
+        let session = sm.session();
+        let clock = utils::MockClock::new(utils::get_epoch_ms());
+        assert!(!session.is_expired_by(&clock));
+
+        clock.advance(std::time::Duration::from_millis(DEFAULT_SESSION_TTL_MS + 1));
+        assert!(session.is_expired_by(&clock));
+        assert!(!session.is_expired());
+    }
+
     #[test]
     pub fn test_account_pubkey() {
         let key = SecretKey::random();
@@ -394,6 +697,136 @@ mod test {
         assert_eq!(key.pubkey(), pubkey);
     }
 
+    #[cfg(not(feature = "wasm"))]
+    struct RemoteSigner {
+        key: SecretKey,
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    #[async_trait]
+    impl Signer for RemoteSigner {
+        async fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+            // Pretend the key is held by another task, such as a remote KMS.
+            let (tx, rx) = futures::channel::oneshot::channel();
+            let key = self.key;
+            let msg = msg.to_vec();
+            std::thread::spawn(move || tx.send(key.sign_raw(&msg).to_vec()));
+            rx.await.map_err(|_| Error::VerifySignatureFailed)
+        }
+
+        fn did(&self) -> Did {
+            self.key.address().into()
+        }
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    #[test]
+    pub fn test_new_with_signer() {
+        let key = SecretKey::random();
+        let signer: BoxedSigner = Box::new(RemoteSigner { key });
+        let sk = futures::executor::block_on(SessionSk::new_with_signer(&signer)).unwrap();
+        assert_eq!(sk.account_did(), key.address().into());
+
+        let payload = MessagePayload::new_send(
+            Message::custom(b"hello").unwrap(),
+            &sk,
+            sk.account_did(),
+            sk.account_did(),
+        )
+        .unwrap();
+        assert!(payload.verify());
+        assert!(payload.transaction.verify());
+        assert_eq!(payload.transaction.signer(), key.address().into());
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    #[test]
+    pub fn test_save_load() {
+        use futures::executor::block_on;
+
+        use crate::storage::MemStorage;
+
+        let key = SecretKey::random();
+        let storage = MemStorage::<SessionSk>::new();
+
+        // Nothing saved, a new session is created and saved.
+        let sk = block_on(SessionSk::load(&storage, &key)).unwrap();
+        assert_eq!(sk.account_did(), key.address().into());
+        let restored = block_on(SessionSk::load(&storage, &key)).unwrap();
+        assert_eq!(restored, sk);
+        assert_eq!(restored.session().ts_ms(), sk.session().ts_ms());
+        assert_eq!(restored.session().ttl_ms(), sk.session().ttl_ms());
+
+        let payload = MessagePayload::new_send(
+            Message::custom(b"hello").unwrap(),
+            &restored,
+            restored.account_did(),
+            restored.account_did(),
+        )
+        .unwrap();
+        assert!(payload.verify());
+        assert!(payload.transaction.verify());
+        assert_eq!(payload.transaction.signer(), key.address().into());
+
+        // Session of another account is not restored.
+        let other = SecretKey::random();
+        let other_sk = block_on(SessionSk::load(&storage, &other)).unwrap();
+        assert_ne!(other_sk, sk);
+        assert_eq!(other_sk.account_did(), other.address().into());
+
+        // Expired session is not restored.
+        let mut builder = SessionSkBuilder::new(
+            Did::from(key.address()).to_string(),
+            "secp256k1".to_string(),
+        )
+        .set_ttl(50);
+        let sig = key.sign(&builder.unsigned_proof());
+        builder = builder.set_session_sig(sig.to_vec());
+        let expiring = builder.build().unwrap();
+        block_on(expiring.save(&storage)).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+
+        let renewed = block_on(SessionSk::load(&storage, &key)).unwrap();
+        assert_ne!(renewed, expiring);
+        assert!(renewed.session().verify_self().is_ok());
+        assert_eq!(block_on(SessionSk::load(&storage, &key)).unwrap(), renewed);
+    }
+
+    #[test]
+    pub fn test_delegate() {
+        let key = SecretKey::random();
+        let sk = SessionSk::new_with_seckey(&key).unwrap();
+        assert_eq!(sk.session().scope(), SessionScope::all());
+
+        let sub = sk.delegate(SessionScope::SEND_MESSAGE, 60 * 1000).unwrap();
+        let session = sub.session();
+        assert!(session.verify_self().is_ok());
+        assert_eq!(session.account_did(), key.address().into());
+        assert_eq!(session.account_pubkey().unwrap(), key.pubkey());
+        assert!(session
+            .check_scope(SessionScope::SEND_MESSAGE, "send")
+            .is_ok());
+        assert!(matches!(
+            session.check_scope(SessionScope::CONNECT, "connect"),
+            Err(Error::NoPermission(_, action)) if action == "connect"
+        ));
+
+        // A sub-session cannot delegate, even within its own scope.
+        assert!(matches!(
+            sub.delegate(SessionScope::CONNECT, 60 * 1000),
+            Err(Error::NoPermission(_, _))
+        ));
+        assert!(matches!(
+            sub.delegate(SessionScope::SEND_MESSAGE, 60 * 1000),
+            Err(Error::NoPermission(_, _))
+        ));
+
+        // The scope is signed by the parent session, so it cannot be widened.
+        let mut forged = session.clone();
+        forged.scope = SessionScope::all();
+        assert!(forged.verify_self().is_err());
+    }
+
     #[test]
     pub fn test_dump_restore() {
         let key = SecretKey::random();
@@ -6,15 +6,81 @@ use async_trait::async_trait;
 use itertools::Itertools;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use sled::transaction::ConflictableTransactionError;
+use sled::transaction::TransactionError;
+use sled::Transactional;
 
 use crate::error::Error;
 use crate::error::Result;
 use crate::storage::KvStorageInterface;
 
+/// Name of the sled tree keeping metadata of the storage, apart from values.
+const META_TREE: &str = "__rings_storage_meta";
+/// Key of the [StorageCodec] tag in metadata.
+const CODEC_KEY: &str = "codec";
+
+/// Codecs of values in [SledStorage].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StorageCodec {
+    /// Bincode, the codec of storage created before codecs are configurable.
+    #[default]
+    Bincode,
+    /// JSON, which can be read by other tools.
+    Json,
+    /// CBOR, self-describing like JSON but more compact.
+    Cbor,
+}
+
+impl StorageCodec {
+    fn tag(&self) -> u8 {
+        match self {
+            Self::Bincode => 0,
+            Self::Json => 1,
+            Self::Cbor => 2,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Result<Self> {
+        match tag {
+            0 => Ok(Self::Bincode),
+            1 => Ok(Self::Json),
+            2 => Ok(Self::Cbor),
+            _ => Err(Error::UnsupportedStorageCodec(tag)),
+        }
+    }
+
+    fn encode<V: Serialize>(&self, value: &V) -> Result<Vec<u8>> {
+        match self {
+            Self::Bincode => bincode::serialize(value).map_err(Error::BincodeSerialize),
+            Self::Json => serde_json::to_vec(value).map_err(Error::Serialize),
+            Self::Cbor => {
+                let mut data = vec![];
+                ciborium::into_writer(value, &mut data)
+                    .map_err(|e| Error::StorageCodec(e.to_string()))?;
+                Ok(data)
+            }
+        }
+    }
+
+    fn decode<V: DeserializeOwned>(&self, data: &[u8]) -> Result<V> {
+        match self {
+            Self::Bincode => bincode::deserialize(data).map_err(Error::BincodeDeserialize),
+            Self::Json => serde_json::from_slice(data).map_err(Error::Deserialize),
+            Self::Cbor => {
+                ciborium::from_reader(data).map_err(|e| Error::StorageCodec(e.to_string()))
+            }
+        }
+    }
+}
+
 /// StorageInstance struct
 #[allow(dead_code)]
 pub struct SledStorage {
     db: sled::Db,
+    /// Metadata of the storage, such as the tag of [StorageCodec].
+    meta: sled::Tree,
+    /// Codec of values, which is detected from metadata when opening.
+    codec: StorageCodec,
     cap: u32,
     path: String,
 }
@@ -31,11 +97,86 @@ impl SledStorage {
             .cache_capacity(cap as u64)
             .open()
             .map_err(Error::SledError)?;
-        Ok(Self {
+        let meta = db.open_tree(META_TREE).map_err(Error::SledError)?;
+        // Storage without codec tag is created by old nodes, whose values are encoded by bincode.
+        let codec = match meta.get(CODEC_KEY).map_err(Error::SledError)? {
+            Some(tag) => StorageCodec::from_tag(tag.first().copied().unwrap_or_default())?,
+            None => StorageCodec::default(),
+        };
+        let storage = Self {
             db,
+            meta,
+            codec,
             cap,
             path: path.as_ref().to_string_lossy().to_string(),
-        })
+        };
+        storage.save_codec_tag()?;
+        Ok(storage)
+    }
+
+    /// Encode values by `codec` if the storage is empty.
+    /// Otherwise the codec detected from stored values is kept, so that they can be decoded.
+    /// Use [SledStorage::migrate_codec] to re-encode them.
+    pub fn with_codec(mut self, codec: StorageCodec) -> Result<Self> {
+        if codec == self.codec {
+            return Ok(self);
+        }
+        if !self.db.is_empty() {
+            tracing::warn!(
+                "Storage {} is encoded by {:?}, keep it instead of {:?}",
+                self.path,
+                self.codec,
+                codec
+            );
+            return Ok(self);
+        }
+        self.codec = codec;
+        self.save_codec_tag()?;
+        Ok(self)
+    }
+
+    /// Get the codec of values.
+    pub fn codec(&self) -> StorageCodec {
+        self.codec
+    }
+
+    /// Re-encode all values by `codec`, then record it in metadata.
+    /// All values should be of type `V`, or nothing is changed and an error is returned.
+    pub fn migrate_codec<V>(&mut self, codec: StorageCodec) -> Result<()>
+    where V: Serialize + DeserializeOwned {
+        if codec == self.codec {
+            return Ok(());
+        }
+        let mut entries = vec![];
+        for kv in self.db.iter() {
+            let (k, v) = kv.map_err(Error::SledError)?;
+            let value: V = self.codec.decode(v.as_ref())?;
+            entries.push((k, codec.encode(&value)?));
+        }
+
+        (&*self.db, &self.meta)
+            .transaction(|(db, meta)| {
+                for (k, v) in entries.iter() {
+                    db.insert(k.clone(), v.as_slice())?;
+                }
+                meta.insert(CODEC_KEY, vec![codec.tag()])?;
+                Ok::<(), ConflictableTransactionError<()>>(())
+            })
+            .map_err(|e| match e {
+                TransactionError::Storage(e) => Error::SledError(e),
+                TransactionError::Abort(()) => {
+                    Error::StorageCodec("migration is aborted".to_string())
+                }
+            })?;
+        self.codec = codec;
+        Ok(())
+    }
+
+    fn save_codec_tag(&self) -> Result<()> {
+        self.meta
+            .insert(CODEC_KEY, vec![self.codec.tag()])
+            .map_err(Error::SledError)?;
+        Ok(())
     }
 }
 
@@ -46,16 +187,13 @@ where V: Serialize + DeserializeOwned + Sync
     async fn get(&self, key: &str) -> Result<Option<V>> {
         let v = self.db.get(key).map_err(Error::SledError)?;
         if let Some(v) = v {
-            let v = v.as_ref();
-            return bincode::deserialize(v)
-                .map_err(Error::BincodeDeserialize)
-                .map(|r| Some(r));
+            return self.codec.decode(v.as_ref()).map(|r| Some(r));
         }
         Ok(None)
     }
 
     async fn put(&self, key: &str, value: &V) -> Result<()> {
-        let data = bincode::serialize(&value).map_err(Error::BincodeSerialize)?;
+        let data = self.codec.encode(value)?;
         tracing::debug!("Try inserting key: {:?}", key);
         self.db.insert(key, data).map_err(Error::SledError)?;
         Ok(())
@@ -68,7 +206,7 @@ where V: Serialize + DeserializeOwned + Sync
             .flat_map(|(k, v)| {
                 Some((
                     std::str::from_utf8(k.as_ref()).ok()?.to_string(),
-                    bincode::deserialize(v.as_ref()).ok()?,
+                    self.codec.decode(v.as_ref()).ok()?,
                 ))
             })
             .collect_vec())
@@ -94,6 +232,7 @@ where V: Serialize + DeserializeOwned + Sync
 impl std::fmt::Debug for SledStorage {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("SledStorage")
+            .field("codec", &self.codec)
             .field("cap", &self.cap)
             .field("path", &self.path)
             .finish()
@@ -183,4 +322,81 @@ mod test {
 
         drop(storage)
     }
+
+    #[tokio::test]
+    async fn test_codec_detected_and_migrated() {
+        let path = "tmp/test_db_codec";
+        let _ = std::fs::remove_dir_all(path);
+        let data = TestStorageStruct {
+            content: "test".to_string(),
+        };
+
+        // Values of a new storage are encoded by the codec set.
+        let storage = SledStorage::new_with_cap_and_path(4096, path)
+            .await
+            .unwrap()
+            .with_codec(StorageCodec::Json)
+            .unwrap();
+        assert_eq!(storage.codec(), StorageCodec::Json);
+        storage.put("key", &data).await.unwrap();
+        let raw = storage.db.get("key").unwrap().unwrap();
+        assert!(serde_json::from_slice::<TestStorageStruct>(&raw).is_ok());
+        drop(storage);
+
+        // The tag drives decoding after switching, instead of the codec set.
+        let mut storage = SledStorage::new_with_cap_and_path(4096, path)
+            .await
+            .unwrap()
+            .with_codec(StorageCodec::Cbor)
+            .unwrap();
+        assert_eq!(storage.codec(), StorageCodec::Json);
+        let got: TestStorageStruct = storage.get("key").await.unwrap().unwrap();
+        assert_eq!(got.content, data.content);
+
+        // Values are re-encoded by migration.
+        storage
+            .migrate_codec::<TestStorageStruct>(StorageCodec::Cbor)
+            .unwrap();
+        assert_eq!(storage.codec(), StorageCodec::Cbor);
+        let raw = storage.db.get("key").unwrap().unwrap();
+        assert!(serde_json::from_slice::<TestStorageStruct>(&raw).is_err());
+        assert!(ciborium::from_reader::<TestStorageStruct, _>(raw.as_ref()).is_ok());
+        drop(storage);
+
+        let storage = SledStorage::new_with_cap_and_path(4096, path)
+            .await
+            .unwrap();
+        assert_eq!(storage.codec(), StorageCodec::Cbor);
+        let got: TestStorageStruct = storage.get("key").await.unwrap().unwrap();
+        assert_eq!(got.content, data.content);
+
+        // Migration fails without changes if a value is not of the type.
+        let mut storage = storage;
+        storage.put("number", &101u64).await.unwrap();
+        assert!(storage
+            .migrate_codec::<TestStorageStruct>(StorageCodec::Json)
+            .is_err());
+        assert_eq!(storage.codec(), StorageCodec::Cbor);
+        let got: u64 = storage.get("number").await.unwrap().unwrap();
+        assert_eq!(got, 101);
+        drop(storage);
+
+        // Storage of old nodes has no tag, its values are decoded by bincode.
+        let _ = std::fs::remove_dir_all(path);
+        let storage = SledStorage::new_with_cap_and_path(4096, path)
+            .await
+            .unwrap();
+        storage
+            .db
+            .insert("key", bincode::serialize(&data).unwrap())
+            .unwrap();
+        storage.meta.remove(CODEC_KEY).unwrap();
+        drop(storage);
+        let storage = SledStorage::new_with_cap_and_path(4096, path)
+            .await
+            .unwrap();
+        assert_eq!(storage.codec(), StorageCodec::Bincode);
+        let got: TestStorageStruct = storage.get("key").await.unwrap().unwrap();
+        assert_eq!(got.content, data.content);
+    }
 }
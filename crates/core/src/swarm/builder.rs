@@ -4,15 +4,48 @@
 
 use std::sync::Arc;
 use std::sync::RwLock;
+use std::time::Duration;
 
+use async_lock::Semaphore;
+
+use crate::consts::DEFAULT_DEDUP_WINDOW;
+use crate::consts::DEFAULT_EVENT_CHANNEL_CAPACITY;
+use crate::consts::DEFAULT_INBOX_BLOCK_TIMEOUT_MS;
+use crate::consts::DEFAULT_MAX_MESSAGE_SIZE;
+use crate::dht::Did;
 use crate::dht::PeerRing;
 use crate::dht::VNodeStorage;
+use crate::error::Error;
+use crate::error::Result;
 use crate::measure::MeasureImpl;
+use crate::measure::QualityFn;
+use crate::message::CompressionConfig;
+use crate::message::HandshakeCodec;
+use crate::session::BoxedSigner;
 use crate::session::SessionSk;
 use crate::swarm::callback::SharedSwarmCallback;
+use crate::swarm::callback::SwappableCallback;
 use crate::swarm::callback::SwarmCallback;
+use crate::swarm::config::SwarmConfig;
+use crate::swarm::dedup::DedupWindow;
+use crate::swarm::file::FileReceiver;
+use crate::swarm::rate_limit::RateLimit;
+use crate::swarm::rate_limit::RateLimiter;
+use crate::swarm::reassembly::Reassembly;
+use crate::swarm::reassembly::ReassemblyBudget;
+use crate::swarm::reconnect::ReconnectPolicy;
+use crate::swarm::reconnect::Reconnector;
+#[cfg(feature = "record")]
+use crate::swarm::record::MessageRecorder;
+use crate::swarm::transport::SendBufferPolicy;
 use crate::swarm::transport::SwarmTransport;
+use crate::swarm::transport::VerificationPolicy;
+use crate::swarm::transport_kind::SharedTransportFactory;
+use crate::swarm::transport_kind::TransportKind;
+use crate::swarm::RtcConfig;
 use crate::swarm::Swarm;
+use crate::utils::SharedClock;
+use crate::utils::SystemClock;
 
 struct DefaultCallback;
 impl SwarmCallback for DefaultCallback {}
@@ -22,12 +55,40 @@ pub struct SwarmBuilder {
     network_id: u32,
     ice_servers: String,
     external_address: Option<String>,
+    transport_kind: TransportKind,
+    transport_factories: Vec<(Did, SharedTransportFactory)>,
     dht_succ_max: u8,
     dht_storage: VNodeStorage,
     session_sk: SessionSk,
     session_ttl: Option<usize>,
     measure: Option<MeasureImpl>,
+    quality_fn: Option<QualityFn>,
     callback: Option<SharedSwarmCallback>,
+    send_buffer_policy: SendBufferPolicy,
+    compression: Option<CompressionConfig>,
+    handshake_codec: HandshakeCodec,
+    acceptance_delay: Option<Duration>,
+    verification_policy: VerificationPolicy,
+    idle_timeout: Option<Duration>,
+    max_message_size: usize,
+    max_concurrent_connects: Option<usize>,
+    #[cfg(feature = "record")]
+    recorder: Option<MessageRecorder>,
+    clock: SharedClock,
+    capabilities: Vec<String>,
+    rate_limit: Option<RateLimit>,
+    reconnect_policy: Option<ReconnectPolicy>,
+    reassembly_budget: Option<ReassemblyBudget>,
+    trickle_ice: bool,
+    disable_mdns: bool,
+    buffer_drained_threshold: Option<usize>,
+    file_receiver: Option<Arc<dyn FileReceiver>>,
+    detect_nat: bool,
+    event_channel_capacity: usize,
+    inbox_block_timeout: Duration,
+    dedup_window: usize,
+    rtc_config: RtcConfig,
+    gather_timeout: Option<Duration>,
 }
 
 impl SwarmBuilder {
@@ -42,15 +103,56 @@ impl SwarmBuilder {
             network_id,
             ice_servers: ice_servers.to_string(),
             external_address: None,
+            transport_kind: TransportKind::default(),
+            transport_factories: vec![],
             dht_succ_max: 3,
             dht_storage,
             session_sk,
             session_ttl: None,
             measure: None,
+            quality_fn: None,
             callback: None,
+            send_buffer_policy: SendBufferPolicy::default(),
+            compression: None,
+            handshake_codec: HandshakeCodec::default(),
+            acceptance_delay: None,
+            verification_policy: VerificationPolicy::default(),
+            idle_timeout: None,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            max_concurrent_connects: None,
+            #[cfg(feature = "record")]
+            recorder: None,
+            clock: Arc::new(SystemClock),
+            capabilities: vec![],
+            rate_limit: None,
+            reconnect_policy: None,
+            reassembly_budget: None,
+            trickle_ice: false,
+            disable_mdns: true,
+            buffer_drained_threshold: None,
+            file_receiver: None,
+            detect_nat: false,
+            event_channel_capacity: DEFAULT_EVENT_CHANNEL_CAPACITY,
+            inbox_block_timeout: Duration::from_millis(DEFAULT_INBOX_BLOCK_TIMEOUT_MS),
+            dedup_window: DEFAULT_DEDUP_WINDOW,
+            rtc_config: RtcConfig::default(),
+            gather_timeout: None,
         }
     }
 
+    /// Creates new instance of [SwarmBuilder] with a session delegated by `signer`.
+    /// The account key is only used to sign the session proof, so it can be kept in an HSM
+    /// or a remote KMS. Messages are signed by the delegated session key.
+    pub async fn new_with_signer(
+        network_id: u32,
+        ice_servers: &str,
+        dht_storage: VNodeStorage,
+        signer: &BoxedSigner,
+    ) -> Result<Self> {
+        let session_sk = SessionSk::new_with_signer(signer).await?;
+        Ok(Self::new(network_id, ice_servers, dht_storage, session_sk))
+    }
+
     /// Sets up the maximum length of successors in the DHT.
     pub fn dht_succ_max(mut self, succ_max: u8) -> Self {
         self.dht_succ_max = succ_max;
@@ -76,14 +178,305 @@ impl SwarmBuilder {
         self
     }
 
+    /// Override the function scoring connection quality, which is used by
+    /// [Swarm::connection_quality]. The counters passed to it are provided by [Self::measure].
+    pub fn quality_fn(mut self, f: QualityFn) -> Self {
+        self.quality_fn = Some(f);
+        self
+    }
+
     /// Bind callback for Swarm.
     pub fn callback(mut self, callback: SharedSwarmCallback) -> Self {
         self.callback = Some(callback);
         self
     }
 
+    /// Setup backpressure policy of data channel send buffer.
+    /// See [SendBufferPolicy] for details.
+    pub fn send_buffer_policy(mut self, policy: SendBufferPolicy) -> Self {
+        self.send_buffer_policy = policy;
+        self
+    }
+
+    /// Compress frames larger than the threshold of `config` before sending.
+    /// The algorithm is tagged in each frame, so peers can decode it whatever their own config is.
+    /// [SwarmBuilder::build] fails with [Error::InvalidCompressionLevel] if the level is out of
+    /// range.
+    pub fn compression(mut self, config: CompressionConfig) -> Self {
+        self.compression = Some(config);
+        self
+    }
+
+    /// Encode sdp of handshake messages by `codec`, [HandshakeCodec::Json] by default.
+    /// The codec is tagged in sdp, so peers can decode it whatever their own codec is,
+    /// except old nodes which only understand JSON.
+    pub fn handshake_codec(mut self, codec: HandshakeCodec) -> Self {
+        self.handshake_codec = codec;
+        self
+    }
+
+    /// Pad the time of accepting or rejecting a remote offer to at least `delay`,
+    /// to prevent the timing of handshake from leaking whether a peer is accepted.
+    /// Handshakes that already take longer than `delay` are not slowed down.
+    pub fn acceptance_delay(mut self, delay: Duration) -> Self {
+        self.acceptance_delay = Some(delay);
+        self
+    }
+
+    /// Set how [Swarm::answer_offer] and [Swarm::accept_answer] verify handshake payloads,
+    /// which is [VerificationPolicy::Strict] by default. See [VerificationPolicy] for the risk
+    /// of skipping verification.
+    pub fn verification_policy(mut self, policy: VerificationPolicy) -> Self {
+        self.verification_policy = policy;
+        self
+    }
+
+    /// Close connections without any frame sent or received for `timeout`, which is checked
+    /// in stabilization. Connections of DHT successors and peers pinned by
+    /// [crate::swarm::Swarm::pin] are kept open.
+    /// The event [crate::swarm::callback::SwarmEvent::Disconnected] is emitted for each of them.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Advertise capabilities to peers in handshake, such as `snark`.
+    /// Capabilities advertised by both sides can be queried by [Swarm::peer_capabilities].
+    pub fn capabilities(mut self, capabilities: Vec<String>) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Limit inbound messages from each origin sender by a token bucket.
+    /// Messages over the limit are dropped before handling, see [RateLimit] for details.
+    pub fn rate_limit(mut self, config: RateLimit) -> Self {
+        self.rate_limit = Some(config);
+        self
+    }
+
+    /// Reconnect closed connections matching the predicate of `policy` in stabilization, with
+    /// backoff between attempts. Closed connections are not reconnected by default, except
+    /// pinned peers, see [crate::swarm::Swarm::pin].
+    pub fn reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = Some(policy);
+        self
+    }
+
+    /// Limit chunked messages being reassembled from all connections. The first chunk of a new
+    /// message over budget is rejected, and incomplete messages are evicted after timeout.
+    /// Default is [ReassemblyBudget::default].
+    pub fn reassembly_budget(mut self, budget: ReassemblyBudget) -> Self {
+        self.reassembly_budget = Some(budget);
+        self
+    }
+
+    /// Select the kind of transport connecting to peers, default is [TransportKind::Webrtc].
+    pub fn transport_kind(mut self, kind: TransportKind) -> Self {
+        self.transport_kind = kind;
+        self
+    }
+
+    /// Create the connection to `peer` by `factory` instead of the transport of
+    /// [SwarmBuilder::transport_kind], so that a peer can be reached by another transport.
+    /// Both sides of a connection should use the same kind of transport.
+    pub fn transport_factory(mut self, peer: Did, factory: SharedTransportFactory) -> Self {
+        self.transport_factories.push((peer, factory));
+        self
+    }
+
+    /// Send offer and answer without waiting for ICE candidates gathering, then trickle
+    /// the candidates to peer by [crate::message::Message::IceCandidate] once gathered.
+    /// The candidates are relayed through DHT, so it only helps connections created by
+    /// [Swarm::connect]. It's only supported by native webrtc transport.
+    pub fn trickle_ice(mut self, trickle_ice: bool) -> Self {
+        self.trickle_ice = trickle_ice;
+        self
+    }
+
+    /// Disable mDNS of ICE agent, it's disabled by default. When disabled, the agent doesn't
+    /// bind the mDNS port or resolve `.local` candidates of peers, which is preferred on
+    /// servers. It's only supported by native webrtc transport.
+    pub fn disable_mdns(mut self, disable_mdns: bool) -> Self {
+        self.disable_mdns = disable_mdns;
+        self
+    }
+
+    /// Emit [SwarmEvent::BufferDrained](crate::swarm::callback::SwarmEvent::BufferDrained) once
+    /// bytes buffered in a data channel of a connection drop to `bytes`, so that senders paused
+    /// by [SendBufferPolicy] can resume sending. Not emitted by default.
+    pub fn buffer_drained_threshold(mut self, bytes: usize) -> Self {
+        self.buffer_drained_threshold = Some(bytes);
+        self
+    }
+
+    /// Set options of the RTCConfiguration of connections besides ice servers, such as
+    /// [IceTransportPolicy::Relay](crate::swarm::IceTransportPolicy::Relay) to force all
+    /// traffic through TURN servers, so that the IP address of this node is not exposed to
+    /// peers. It's ignored by `TransportKind::Loopback`.
+    pub fn rtc_config(mut self, rtc_config: RtcConfig) -> Self {
+        self.rtc_config = rtc_config;
+        self
+    }
+
+    /// Cap the time waiting for ICE candidates gathering when creating offers and answers,
+    /// which is 60 seconds by default. An unreachable STUN or TURN server no longer stalls
+    /// handshakes that long: once `timeout` is reached, the offer or answer carries the
+    /// candidates gathered so far, such as host candidates, and the servers which timed out
+    /// are logged. It's ignored by `TransportKind::Loopback` and trickle ICE.
+    pub fn gather_timeout(mut self, timeout: Duration) -> Self {
+        self.gather_timeout = Some(timeout);
+        self
+    }
+
+    /// Detect NAT type by the STUN servers in ice servers when node starts listening, see
+    /// [Swarm::detect_nat]. A warning is logged if it's a symmetric NAT and there is no TURN
+    /// server. Disabled by default.
+    pub fn detect_nat(mut self, enable: bool) -> Self {
+        self.detect_nat = enable;
+        self
+    }
+
+    /// Max number of events buffered by each stream of [Swarm::iter_events], which is
+    /// [DEFAULT_EVENT_CHANNEL_CAPACITY] by default. Events beyond that are dropped and counted
+    /// by [Swarm::dropped_events].
+    pub fn event_channel_capacity(mut self, capacity: usize) -> Self {
+        self.event_channel_capacity = capacity;
+        self
+    }
+
+    /// Max time for a message to wait for room in a full buffer of
+    /// [OverflowMode::Block](crate::swarm::OverflowMode::Block), which is
+    /// [DEFAULT_INBOX_BLOCK_TIMEOUT_MS] by default. The message is dropped after that.
+    pub fn inbox_block_timeout(mut self, timeout: Duration) -> Self {
+        self.inbox_block_timeout = timeout;
+        self
+    }
+
+    /// Keep the signers and tx_ids of the last `n` messages handled by this node, which is
+    /// [DEFAULT_DEDUP_WINDOW] by default. A message arriving again, such as by another relay
+    /// path, is dropped before handling. It's disabled if `n` is 0.
+    pub fn dedup_window(mut self, n: usize) -> Self {
+        self.dedup_window = n;
+        self
+    }
+
+    /// Limit the size of messages in bytes, which is [DEFAULT_MAX_MESSAGE_SIZE] by default.
+    /// Sending a larger message fails with [Error::MessageTooLarge]. A larger message received
+    /// is rejected before its chunks are buffered, and the peer sending it is disconnected.
+    pub fn max_message_size(mut self, bytes: usize) -> Self {
+        self.max_message_size = bytes;
+        self
+    }
+
+    /// Limit handshakes in progress at the same time to `n`, which is at least 1. A handshake is
+    /// in progress from creating its connection until its data channel opens or fails, or the
+    /// connection is closed. The rest wait in queue, so that firing many connects, such as by
+    /// [Swarm::warm_up] or [Swarm::bootstrap_from], doesn't gather ICE candidates all at once.
+    /// Not limited by default.
+    pub fn max_concurrent_connects(mut self, n: usize) -> Self {
+        self.max_concurrent_connects = Some(n.max(1));
+        self
+    }
+
+    /// Record inbound and outbound payloads by the recorder, which can be replayed by
+    /// [crate::swarm::record::replay] later.
+    #[cfg(feature = "record")]
+    pub fn message_recorder(mut self, recorder: MessageRecorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Accept files sent by [Swarm::send_file] of peers, which are written to the writers
+    /// opened by `receiver`. Files are rejected if it's not set.
+    pub fn file_receiver(mut self, receiver: Arc<dyn FileReceiver>) -> Self {
+        self.file_receiver = Some(receiver);
+        self
+    }
+
+    /// Replace the system clock used for connection ages, idle timeouts, session expiry and
+    /// subscription expiry, such as by a [crate::utils::MockClock] in tests.
+    pub fn clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Apply options of `config` which are set, overriding the ones set before.
+    /// The config should be checked by [SwarmConfig::validate] first.
+    pub fn config(mut self, config: &SwarmConfig) -> Self {
+        if let Some(succ_max) = config.dht_succ_max {
+            self = self.dht_succ_max(succ_max);
+        }
+        if let Some(ttl) = config.session_ttl {
+            self = self.session_ttl(ttl);
+        }
+        if let Some(policy) = config.send_buffer_policy {
+            self = self.send_buffer_policy(policy);
+        }
+        if let Some(compression) = config.compression {
+            self = self.compression(compression);
+        }
+        if let Some(codec) = config.handshake_codec {
+            self = self.handshake_codec(codec);
+        }
+        if let Some(delay) = config.acceptance_delay_ms {
+            self = self.acceptance_delay(Duration::from_millis(delay));
+        }
+        if let Some(timeout) = config.idle_timeout_secs {
+            self = self.idle_timeout(Duration::from_secs(timeout));
+        }
+        if let Some(bytes) = config.max_message_size {
+            self = self.max_message_size(bytes);
+        }
+        if let Some(n) = config.max_concurrent_connects {
+            self = self.max_concurrent_connects(n);
+        }
+        if let Some(limit) = config.rate_limit {
+            self = self.rate_limit(limit);
+        }
+        if let Some(trickle_ice) = config.trickle_ice {
+            self = self.trickle_ice(trickle_ice);
+        }
+        if let Some(disable_mdns) = config.disable_mdns {
+            self = self.disable_mdns(disable_mdns);
+        }
+        if let Some(bytes) = config.buffer_drained_threshold {
+            self = self.buffer_drained_threshold(bytes);
+        }
+        if let Some(detect_nat) = config.detect_nat {
+            self = self.detect_nat(detect_nat);
+        }
+        if let Some(capacity) = config.event_channel_capacity {
+            self = self.event_channel_capacity(capacity);
+        }
+        if let Some(n) = config.dedup_window {
+            self = self.dedup_window(n);
+        }
+        if let Some(rtc_config) = config.rtc_config {
+            self = self.rtc_config(rtc_config);
+        }
+        if let Some(timeout) = config.gather_timeout_ms {
+            self = self.gather_timeout(Duration::from_millis(timeout));
+        }
+        self
+    }
+
     /// Try build for `Swarm`.
-    pub fn build(self) -> Swarm {
+    pub fn build(self) -> Result<Swarm> {
+        if self.dht_succ_max < 1 {
+            return Err(Error::SwarmBuildFailed(format!(
+                "dht_succ_max should be at least 1, got {}",
+                self.dht_succ_max
+            )));
+        }
+        if let Some(compression) = &self.compression {
+            compression.validate()?;
+        }
+        if self.event_channel_capacity < 1 {
+            return Err(Error::SwarmBuildFailed(
+                "event_channel_capacity should be at least 1".to_string(),
+            ));
+        }
+
         let dht_did = self.session_sk.account_did();
 
         let dht = Arc::new(PeerRing::new_with_storage(
@@ -92,24 +485,153 @@ impl SwarmBuilder {
             self.dht_storage,
         ));
 
-        let callback = RwLock::new(
+        let callback = SwappableCallback::new(
             self.callback
                 .unwrap_or_else(|| Arc::new(DefaultCallback {})),
         );
 
-        let transport = Arc::new(SwarmTransport::new(
+        let mut transport = SwarmTransport::new(
             self.network_id,
             &self.ice_servers,
             self.external_address,
+            self.transport_kind,
             self.session_sk,
             dht.clone(),
             self.measure,
-        ));
+        );
+        transport.quality_fn = self.quality_fn;
+        transport.send_buffer_policy = self.send_buffer_policy;
+        transport.compression = self.compression;
+        transport.handshake_codec = self.handshake_codec;
+        transport.acceptance_delay = self.acceptance_delay;
+        transport.verification_policy = self.verification_policy;
+        transport.idle_timeout = self.idle_timeout;
+        transport.max_message_size = self.max_message_size;
+        transport.connect_limiter = self
+            .max_concurrent_connects
+            .map(|n| Arc::new(Semaphore::new(n)));
+        #[cfg(feature = "record")]
+        {
+            transport.recorder = self.recorder.map(Arc::new);
+        }
+        transport.clock = self.clock;
+        transport.capabilities = self.capabilities;
+        transport.rate_limiter = self.rate_limit.map(RateLimiter::new);
+        transport.reconnector = self.reconnect_policy.map(Reconnector::new);
+        if let Some(budget) = self.reassembly_budget {
+            transport.reassembly = Reassembly::new(budget);
+        }
+        transport.file_receiver = self.file_receiver;
+        transport.transport_factories = RwLock::new(self.transport_factories.into_iter().collect());
+        transport.detect_nat = self.detect_nat;
+        transport.event_channel_capacity = self.event_channel_capacity;
+        transport.inbox_block_timeout = self.inbox_block_timeout;
+        transport.dedup_window =
+            (self.dedup_window > 0).then(|| DedupWindow::new(self.dedup_window));
+        transport.set_trickle_ice(self.trickle_ice);
+        transport.set_disable_mdns(self.disable_mdns);
+        transport.set_rtc_config(self.rtc_config);
+        if let Some(timeout) = self.gather_timeout {
+            transport.set_gather_timeout(timeout);
+        }
+        transport.set_buffered_amount_low_threshold(self.buffer_drained_threshold);
+        let transport = Arc::new(transport);
 
-        Swarm {
+        Ok(Swarm {
             dht,
             transport,
             callback,
-        }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::DEFAULT_MAX_MESSAGE_SIZE;
+    use crate::ecc::SecretKey;
+    use crate::message::CompressionAlgorithm;
+    use crate::storage::MemStorage;
+    use crate::swarm::IceTransportPolicy;
+
+    fn builder() -> SwarmBuilder {
+        let session_sk = SessionSk::new_with_seckey(&SecretKey::random()).unwrap();
+        SwarmBuilder::new(
+            0,
+            "stun://stun.l.google.com:19302",
+            Box::new(MemStorage::new()),
+            session_sk,
+        )
+    }
+
+    #[test]
+    fn test_load_config_into_builder() {
+        let config: SwarmConfig = serde_json::from_str(
+            r#"{
+                "dht_succ_max": 5,
+                "send_buffer_policy": { "Reject": { "high_water_mark": 65536 } },
+                "handshake_codec": "Cbor",
+                "acceptance_delay_ms": 200,
+                "idle_timeout_secs": 600,
+                "max_concurrent_connects": 4,
+                "rate_limit": { "messages_per_sec": 10, "burst": 20, "disconnect_after": null },
+                "trickle_ice": true,
+                "rtc_config": { "ice_transport_policy": "relay" },
+                "gather_timeout_ms": 3000
+            }"#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+
+        let builder = builder().config(&config);
+        assert_eq!(builder.dht_succ_max, 5);
+        assert_eq!(builder.send_buffer_policy, SendBufferPolicy::Reject {
+            high_water_mark: 65536
+        });
+        assert_eq!(builder.handshake_codec, HandshakeCodec::Cbor);
+        assert_eq!(builder.acceptance_delay, Some(Duration::from_millis(200)));
+        assert_eq!(builder.idle_timeout, Some(Duration::from_secs(600)));
+        assert_eq!(builder.max_concurrent_connects, Some(4));
+        assert_eq!(
+            builder.rate_limit,
+            Some(RateLimit {
+                messages_per_sec: 10,
+                burst: 20,
+                disconnect_after: None,
+            })
+        );
+        assert!(builder.trickle_ice);
+        assert_eq!(builder.rtc_config, RtcConfig {
+            ice_transport_policy: IceTransportPolicy::Relay,
+            ..Default::default()
+        });
+        assert_eq!(builder.gather_timeout, Some(Duration::from_secs(3)));
+
+        // Options not in config keep defaults.
+        assert_eq!(builder.session_ttl, None);
+        assert_eq!(builder.compression, None);
+        assert_eq!(builder.max_message_size, DEFAULT_MAX_MESSAGE_SIZE);
+        assert!(builder.disable_mdns);
+        assert_eq!(builder.buffer_drained_threshold, None);
+        assert!(!builder.detect_nat);
+        assert!(builder.build().is_ok());
+    }
+
+    #[test]
+    fn test_build_with_invalid_compression_level() {
+        let res = builder()
+            .compression(CompressionConfig {
+                algorithm: CompressionAlgorithm::Deflate,
+                level: 100,
+                threshold: 4096,
+            })
+            .build();
+        assert!(matches!(
+            res,
+            Err(Error::InvalidCompressionLevel(
+                CompressionAlgorithm::Deflate,
+                100
+            ))
+        ));
     }
 }
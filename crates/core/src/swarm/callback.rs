@@ -1,23 +1,36 @@
 use std::str::FromStr;
 use std::sync::Arc;
+use std::sync::RwLock;
 
 use async_trait::async_trait;
-use futures::lock::Mutex as FuturesMutex;
 use rings_transport::core::callback::TransportCallback;
+use rings_transport::core::transport::IceCandidate;
 use rings_transport::core::transport::WebrtcConnectionState;
 
-use crate::chunk::ChunkList;
-use crate::chunk::ChunkManager;
+use crate::consts::DECODE_FAILURES_THRESHOLD;
+use crate::consts::PROTOCOL_VERSION;
 use crate::consts::TRANSPORT_MTU;
 use crate::dht::Did;
+use crate::error::Error;
+use crate::measure::MeasureCounter;
+use crate::message::decode_frame_limited;
 use crate::message::HandleMsg;
 use crate::message::Message;
 use crate::message::MessageHandler;
 use crate::message::MessagePayload;
 use crate::message::MessageVerificationExt;
+use crate::message::PayloadSender;
+use crate::swarm::inbox::Inboxes;
+use crate::swarm::rate_limit::RateDecision;
+#[cfg(feature = "record")]
+use crate::swarm::record::Direction;
 use crate::swarm::transport::SwarmTransport;
 
 type CallbackError = Box<dyn std::error::Error>;
+#[cfg(not(feature = "wasm"))]
+type FrameFuture<'a> = futures::future::BoxFuture<'a, Result<(), CallbackError>>;
+#[cfg(feature = "wasm")]
+type FrameFuture<'a> = futures::future::LocalBoxFuture<'a, Result<(), CallbackError>>;
 
 /// The [InnerSwarmCallback] will accept shared [SwarmCallback] trait object.
 #[cfg(feature = "wasm")]
@@ -28,7 +41,7 @@ pub type SharedSwarmCallback = Arc<dyn SwarmCallback>;
 pub type SharedSwarmCallback = Arc<dyn SwarmCallback + Send + Sync>;
 
 /// Used to notify the application of events that occur in the swarm.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[non_exhaustive]
 pub enum SwarmEvent {
     /// Indicates that the connection state of a peer has changed.
@@ -38,6 +51,47 @@ pub enum SwarmEvent {
         /// The final state of the connection.
         state: WebrtcConnectionState,
     },
+    /// Indicates that the connection of a peer is closed by swarm.
+    Disconnected {
+        /// The did of remote peer.
+        peer: Did,
+        /// Why the connection is closed.
+        reason: DisconnectReason,
+    },
+    /// Indicates that bytes buffered in a data channel of peer drop to
+    /// [crate::swarm::SwarmBuilder::buffer_drained_threshold].
+    BufferDrained {
+        /// The did of remote peer.
+        peer: Did,
+    },
+    /// Indicates that an ICE restart by [crate::swarm::Swarm::restart_ice] is finished.
+    IceRestart {
+        /// The did of remote peer.
+        peer: Did,
+        /// Whether the connection is connected again.
+        succeeded: bool,
+    },
+    /// Indicates that a transport is registered for a peer by
+    /// [crate::swarm::Swarm::register_many].
+    TransportRegistered {
+        /// The did of remote peer.
+        peer: Did,
+        /// Whether a connection of peer created by the transport used before is closed.
+        replaced: bool,
+    },
+}
+
+/// Reason of closing a connection by swarm, see [SwarmEvent::Disconnected].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DisconnectReason {
+    /// No frame is sent or received for [crate::swarm::SwarmBuilder::idle_timeout].
+    Idle,
+    /// The peer sent [crate::consts::DECODE_FAILURES_THRESHOLD] frames which cannot be decoded.
+    Misbehaving,
+    /// The peer sent a frame of another [crate::consts::PROTOCOL_VERSION], which is the one
+    /// carried by this variant.
+    IncompatibleVersion(u8),
 }
 
 /// Any object that implements this trait can be used as a callback for the swarm.
@@ -61,12 +115,70 @@ pub trait SwarmCallback {
     }
 }
 
+/// A [SwarmCallback] dispatching to the callback set by [crate::swarm::Swarm::set_callback].
+/// The callback is loaded on each dispatch, so it can be swapped while messages are in flight,
+/// and a dispatch in progress finishes on the callback it started with.
+///
+/// Inbound messages and events are then fed to the [Inboxes] of swarm, which are kept apart
+/// from the callback, so that swapping the callback doesn't drop them.
+#[derive(Clone)]
+pub(crate) struct SwappableCallback {
+    callback: Arc<RwLock<SharedSwarmCallback>>,
+    inboxes: Arc<Inboxes>,
+}
+
+impl SwappableCallback {
+    pub fn new(callback: SharedSwarmCallback) -> Self {
+        Self {
+            callback: Arc::new(RwLock::new(callback)),
+            inboxes: Arc::new(Inboxes::default()),
+        }
+    }
+
+    /// Get the current callback.
+    pub fn load(&self) -> SharedSwarmCallback {
+        self.callback
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Replace the callback for following dispatches.
+    pub fn store(&self, callback: SharedSwarmCallback) {
+        *self.callback.write().unwrap_or_else(|e| e.into_inner()) = callback;
+    }
+
+    /// Inboxes fed after the callback.
+    pub fn inboxes(&self) -> &Inboxes {
+        &self.inboxes
+    }
+}
+
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl SwarmCallback for SwappableCallback {
+    async fn on_validate(&self, payload: &MessagePayload) -> Result<(), CallbackError> {
+        self.load().on_validate(payload).await
+    }
+
+    async fn on_inbound(&self, payload: &MessagePayload) -> Result<(), CallbackError> {
+        self.load().on_inbound(payload).await?;
+        self.inboxes.on_inbound(payload).await;
+        Ok(())
+    }
+
+    async fn on_event(&self, event: &SwarmEvent) -> Result<(), CallbackError> {
+        self.load().on_event(event).await?;
+        self.inboxes.on_event(event);
+        Ok(())
+    }
+}
+
 /// [InnerSwarmCallback] wraps [SharedSwarmCallback] with inner handling for a specific connection.
 pub struct InnerSwarmCallback {
     transport: Arc<SwarmTransport>,
     message_handler: MessageHandler,
     callback: SharedSwarmCallback,
-    chunk_list: FuturesMutex<ChunkList<TRANSPORT_MTU>>,
 }
 
 impl InnerSwarmCallback {
@@ -77,24 +189,289 @@ impl InnerSwarmCallback {
             transport,
             message_handler,
             callback,
-            chunk_list: Default::default(),
         }
     }
 
-    async fn handle_payload(
+    /// Check inbound message against rate limit of its origin sender.
+    /// Return false if the message should be dropped.
+    async fn check_rate_limit(&self, payload: &MessagePayload, message: &Message) -> bool {
+        let Some(limiter) = &self.transport.rate_limiter else {
+            return true;
+        };
+        // Chunks are limited once reassembled.
+        if matches!(message, Message::Chunk(_)) {
+            return true;
+        }
+        let origin = payload.relay.origin_sender();
+        if origin == self.transport.dht.did {
+            return true;
+        }
+
+        let decision = limiter.check(origin);
+        if decision == RateDecision::Accept {
+            return true;
+        }
+        self.transport
+            .record_measure(origin, MeasureCounter::FailedToReceive)
+            .await;
+
+        match decision {
+            RateDecision::Abuse => {
+                tracing::warn!(
+                    target: "rings::swarm",
+                    "Drop message from {origin}, rate limit exceeded persistently"
+                );
+                if payload.signer() == origin && self.transport.get_connection(origin).is_some() {
+                    self.transport
+                        .record_measure(origin, MeasureCounter::Disconnected)
+                        .await;
+                    if let Err(e) = self.transport.disconnect(origin).await {
+                        tracing::error!(
+                            target: "rings::swarm",
+                            "Failed on disconnect {origin}: {e:?}"
+                        );
+                    }
+                }
+                false
+            }
+            _ => {
+                tracing::debug!(
+                    target: "rings::swarm",
+                    "Drop message from {origin}, rate limit exceeded"
+                );
+                false
+            }
+        }
+    }
+
+    /// Check if a message to this node is handled already, such as one arrived by another
+    /// relay path, see [crate::swarm::SwarmBuilder::dedup_window]. Messages relayed by this
+    /// node are not checked, and neither are chunks, which are checked once reassembled.
+    fn is_duplicate(&self, payload: &MessagePayload, message: &Message) -> bool {
+        let Some(window) = &self.transport.dedup_window else {
+            return false;
+        };
+        if matches!(message, Message::Chunk(_))
+            || payload.transaction.destination != self.transport.dht.did
+        {
+            return false;
+        }
+        !window.insert(payload.transaction.signer(), payload.transaction.tx_id)
+    }
+
+    /// Reject a message larger than [SwarmTransport::max_message_size], and disconnect the
+    /// peer sending it.
+    async fn reject_oversized(&self, cid: &str, size: usize) -> CallbackError {
+        tracing::warn!(
+            target: "rings::swarm",
+            "Reject message of {size} bytes from {cid}, it's too large"
+        );
+        if let Ok(peer) = Did::from_str(cid) {
+            self.transport
+                .record_measure(peer, MeasureCounter::FailedToReceive)
+                .await;
+            if let Err(e) = self.transport.disconnect(peer).await {
+                tracing::error!(target: "rings::swarm", "Failed on disconnect {peer}: {e:?}");
+            }
+        }
+        Error::MessageTooLarge(size).into()
+    }
+
+    /// Disconnect the peer of `cid` which sent a frame of protocol `version`. It's not counted
+    /// as a decode failure, since the peer is not misbehaving but speaking another version.
+    async fn reject_incompatible(&self, cid: &str, version: u8) -> CallbackError {
+        tracing::warn!(
+            target: "rings::swarm",
+            "Reject frame of protocol version {version} from {cid}, expect {PROTOCOL_VERSION}"
+        );
+        if let Ok(peer) = Did::from_str(cid) {
+            self.transport
+                .record_measure(peer, MeasureCounter::FailedToReceive)
+                .await;
+            if let Err(e) = self.transport.disconnect(peer).await {
+                tracing::error!(target: "rings::swarm", "Failed on disconnect {peer}: {e:?}");
+            }
+            let event = SwarmEvent::Disconnected {
+                peer,
+                reason: DisconnectReason::IncompatibleVersion(version),
+            };
+            if let Err(e) = self.callback.on_event(&event).await {
+                tracing::error!(target: "rings::swarm", "Failed on handle event {event:?}: {e:?}");
+            }
+        }
+        Error::UnsupportedProtocolVersion(version).into()
+    }
+
+    /// Count a frame from `cid` which cannot be decoded, and disconnect the peer as
+    /// misbehaving once [DECODE_FAILURES_THRESHOLD] frames of it failed within
+    /// [crate::consts::DECODE_FAILURES_WINDOW_MS].
+    async fn reject_undecodable(&self, cid: &str, e: Error) -> CallbackError {
+        let Ok(peer) = Did::from_str(cid) else {
+            return e.into();
+        };
+        let failures = self
+            .transport
+            .decode_failures
+            .observe(peer, self.transport.clock.now_ms());
+        tracing::warn!(
+            target: "rings::swarm",
+            "Failed to decode message from {peer}, {failures} times: {e:?}"
+        );
+        self.transport
+            .record_measure(peer, MeasureCounter::FailedToReceive)
+            .await;
+        if failures >= DECODE_FAILURES_THRESHOLD {
+            if let Err(e) = self.transport.disconnect(peer).await {
+                tracing::error!(target: "rings::swarm", "Failed on disconnect {peer}: {e:?}");
+            }
+            let event = SwarmEvent::Disconnected {
+                peer,
+                reason: DisconnectReason::Misbehaving,
+            };
+            if let Err(e) = self.callback.on_event(&event).await {
+                tracing::error!(target: "rings::swarm", "Failed on handle event {event:?}: {e:?}");
+            }
+        }
+        e.into()
+    }
+
+    /// Verify and handle a payload received from connection of `cid`, or sent to this node
+    /// by itself, see [crate::swarm::Swarm::send_message].
+    pub(crate) async fn on_payload(
+        &self,
+        cid: &str,
+        mut payload: MessagePayload,
+    ) -> Result<(), CallbackError> {
+        let max_size = self.transport.max_message_size;
+        let clock = self.transport.clock.as_ref();
+        if !(payload.verify_by(clock) && payload.transaction.verify_by(clock)) {
+            tracing::error!(
+                target: "rings::swarm",
+                "Cannot verify msg or it's expired: {:?}",
+                payload
+            );
+            return Err("Cannot verify msg or it's expired".into());
+        }
+        if payload
+            .transaction
+            .is_expired_at(self.transport.clock.now_ms())
+        {
+            tracing::warn!(
+                target: "rings::relay",
+                "Drop message {} from {}, reason: Expired",
+                payload.transaction.tx_id,
+                payload.relay.origin_sender()
+            );
+            return Ok(());
+        }
+        let mut message: Message = payload.transaction.data()?;
+        // The payload as received is recorded, rather than the decrypted one.
+        #[cfg(feature = "record")]
+        let received = self.transport.recorder.as_ref().map(|_| payload.clone());
+        // The whole message is not received yet, reject it by the number of chunks. Chunks
+        // larger than TRANSPORT_MTU are rejected by reassembly, so a message of max size is
+        // split to at most this many chunks.
+        if let Message::Chunk(ref chunk) = message {
+            if chunk.chunk[1] > max_size.div_ceil(TRANSPORT_MTU) {
+                let size = chunk.chunk[1].saturating_mul(TRANSPORT_MTU);
+                return Err(self.reject_oversized(cid, size).await);
+            }
+        }
+        if matches!(message, Message::Encrypted(_))
+            && payload.transaction.destination == self.transport.dht.did
+        {
+            payload = payload.decrypt(self.transport.session_sk())?;
+            message = payload.transaction.data()?;
+        }
+        if let Err(e) = self.message_handler.check_scope(&payload, &message) {
+            tracing::warn!(
+                target: "rings::swarm",
+                "Reject message {} from {cid}: {e}",
+                payload.transaction.tx_id
+            );
+            return Err(e.into());
+        }
+        if !self.check_rate_limit(&payload, &message).await {
+            return Ok(());
+        }
+        if self.is_duplicate(&payload, &message) {
+            tracing::debug!(
+                target: "rings::swarm",
+                "Drop duplicate message {} from {cid}",
+                payload.transaction.tx_id
+            );
+            return Ok(());
+        }
+        #[cfg(feature = "record")]
+        if let (Ok(peer), Some(received)) = (Did::from_str(cid), &received) {
+            self.transport
+                .record_payload(Direction::Inbound, peer, received, Some(&payload));
+        }
+        self.callback.on_validate(&payload).await?;
+        self.handle_payload(cid, &payload, message).await
+    }
+
+    /// Handle a frame received from the connection of `cid`, or reassembled from its chunks.
+    /// Bytes and activity of the connection are counted by [TransportCallback::on_message]
+    /// once, not again for reassembled frames. It's boxed since reassembled frames recurse.
+    fn on_frame<'a>(&'a self, cid: &'a str, msg: &'a [u8]) -> FrameFuture<'a> {
+        Box::pin(async move {
+            let max_size = self.transport.max_message_size;
+            let data = match decode_frame_limited(msg, max_size) {
+                Err(Error::MessageTooLarge(size)) => {
+                    return Err(self.reject_oversized(cid, size).await)
+                }
+                Err(Error::UnsupportedProtocolVersion(version)) => {
+                    return Err(self.reject_incompatible(cid, version).await)
+                }
+                Err(e) => return Err(self.reject_undecodable(cid, e).await),
+                Ok(data) => data,
+            };
+            let payload = match MessagePayload::from_bincode(&data) {
+                Ok(payload) => payload,
+                Err(e) => return Err(self.reject_undecodable(cid, e).await),
+            };
+            self.on_payload(cid, payload).await
+        })
+    }
+
+    /// Handle a payload which was verified and accepted before, see [crate::swarm::record::replay].
+    #[cfg(feature = "record")]
+    pub(crate) async fn replay_payload(
         &self,
         cid: &str,
         payload: &MessagePayload,
     ) -> Result<(), CallbackError> {
+        let decrypted = match payload.transaction.data::<Message>()? {
+            Message::Encrypted(_) if payload.transaction.destination == self.transport.dht.did => {
+                Some(payload.decrypt(self.transport.session_sk())?)
+            }
+            _ => None,
+        };
+        let payload = decrypted.as_ref().unwrap_or(payload);
         let message: Message = payload.transaction.data()?;
+        self.callback.on_validate(payload).await?;
+        self.handle_payload(cid, payload, message).await
+    }
 
+    async fn handle_payload(
+        &self,
+        cid: &str,
+        payload: &MessagePayload,
+        message: Message,
+    ) -> Result<(), CallbackError> {
         match &message {
             Message::ConnectNodeSend(ref msg) => self.message_handler.handle(payload, msg).await,
             Message::ConnectNodeReport(ref msg) => self.message_handler.handle(payload, msg).await,
+            Message::ConnectNodeRenegotiate(ref msg) => {
+                self.message_handler.handle(payload, msg).await
+            }
             Message::FindSuccessorSend(ref msg) => self.message_handler.handle(payload, msg).await,
             Message::FindSuccessorReport(ref msg) => {
                 self.message_handler.handle(payload, msg).await
             }
+            Message::LookupProbeSend(ref msg) => self.message_handler.handle(payload, msg).await,
+            Message::LookupProbeReport(ref msg) => self.message_handler.handle(payload, msg).await,
             Message::NotifyPredecessorSend(ref msg) => {
                 self.message_handler.handle(payload, msg).await
             }
@@ -107,6 +484,7 @@ impl InnerSwarmCallback {
                 self.message_handler.handle(payload, msg).await
             }
             Message::OperateVNode(ref msg) => self.message_handler.handle(payload, msg).await,
+            Message::ReplicateVNode(ref msg) => self.message_handler.handle(payload, msg).await,
             Message::CustomMessage(ref msg) => self.message_handler.handle(payload, msg).await,
             Message::QueryForTopoInfoSend(ref msg) => {
                 self.message_handler.handle(payload, msg).await
@@ -114,18 +492,30 @@ impl InnerSwarmCallback {
             Message::QueryForTopoInfoReport(ref msg) => {
                 self.message_handler.handle(payload, msg).await
             }
+            Message::IceCandidate(ref msg) => self.message_handler.handle(payload, msg).await,
+            Message::Encrypted(ref msg) => self.message_handler.handle(payload, msg).await,
+            Message::SubscribeTopic(ref msg) => self.message_handler.handle(payload, msg).await,
+            Message::PublishTopic(ref msg) => self.message_handler.handle(payload, msg).await,
+            Message::FileChunk(ref msg) => self.message_handler.handle(payload, msg).await,
+            Message::FileChunkAck(ref msg) => self.message_handler.handle(payload, msg).await,
+            Message::RouteToKey(ref msg) => self.message_handler.handle(payload, msg).await,
             Message::Chunk(ref msg) => {
-                if let Some(data) = self.chunk_list.lock().await.handle(msg.clone()) {
-                    return self.on_message(cid, &data).await;
+                let peer = payload.relay.origin_sender();
+                let now = self.transport.clock.now_ms();
+                match self.transport.reassembly.handle(peer, msg.clone(), now) {
+                    Ok(Some(data)) => return self.on_frame(cid, &data).await,
+                    Ok(None) => Ok(()),
+                    Err(e) => Err(e),
                 }
-                Ok(())
             }
         }
         .unwrap_or_else(|e| {
-            tracing::error!("Failed to handle_payload: {:?}", e);
+            tracing::error!(target: "rings::swarm", "Failed to handle_payload: {:?}", e);
         });
 
         if payload.transaction.destination == self.transport.dht.did {
+            self.transport.record_relay_report(payload);
+            self.transport.resolve_pending_reply(payload);
             self.callback.on_inbound(payload).await?;
         }
 
@@ -137,13 +527,13 @@ impl InnerSwarmCallback {
 #[cfg_attr(not(feature = "wasm"), async_trait)]
 impl TransportCallback for InnerSwarmCallback {
     async fn on_message(&self, cid: &str, msg: &[u8]) -> Result<(), CallbackError> {
-        let payload = MessagePayload::from_bincode(msg)?;
-        if !(payload.verify() && payload.transaction.verify()) {
-            tracing::error!("Cannot verify msg or it's expired: {:?}", payload);
-            return Err("Cannot verify msg or it's expired".into());
+        if let Ok(peer) = Did::from_str(cid) {
+            self.transport.touch_connection(peer);
+            self.transport
+                .record_measure_by(peer, MeasureCounter::BytesReceived, msg.len() as u64)
+                .await;
         }
-        self.callback.on_validate(&payload).await?;
-        self.handle_payload(cid, &payload).await
+        self.on_frame(cid, msg).await
     }
 
     async fn on_peer_connection_state_change(
@@ -152,16 +542,50 @@ impl TransportCallback for InnerSwarmCallback {
         s: WebrtcConnectionState,
     ) -> Result<(), CallbackError> {
         let Ok(did) = Did::from_str(cid) else {
-            tracing::warn!("on_peer_connection_state_change parse did failed: {}", cid);
+            tracing::warn!(
+                target: "rings::swarm",
+                "on_peer_connection_state_change parse did failed: {}",
+                cid
+            );
             return Ok(());
         };
 
+        if s == WebrtcConnectionState::Failed {
+            self.transport.record_connect_latency(did, false);
+            self.transport.release_connect_permit(did);
+        }
+        // A handshake failed before its data channel opens is retried with a fresh one.
+        if s == WebrtcConnectionState::Failed && self.transport.should_renegotiate(did) {
+            if let Err(e) = self.transport.request_renegotiation(did).await {
+                tracing::warn!(
+                    target: "rings::handshake",
+                    "Failed to renegotiate handshake of {did}: {e:?}"
+                );
+            }
+        }
+
         match s {
             WebrtcConnectionState::Failed
             | WebrtcConnectionState::Disconnected
             | WebrtcConnectionState::Closed => {
+                // A replaced connection may report closing after the new one is connected.
+                if self.transport.is_connected(did) {
+                    tracing::debug!(
+                        target: "rings::handshake",
+                        "ignore {s:?} of {did}, another connection is connected"
+                    );
+                    return Ok(());
+                }
+                // Checked before leaving DHT, so that the peer is still a successor.
+                self.transport.schedule_reconnect(did, s);
                 self.message_handler.leave_dht(did).await?;
             }
+            // A connection recovered after its data channel opened, such as by ICE restart,
+            // joins DHT again since it left on failure.
+            WebrtcConnectionState::Connected if self.transport.is_channel_opened(did) => {
+                self.transport.on_channel_opened(did);
+                self.message_handler.join_dht(did).await?;
+            }
             _ => {}
         };
 
@@ -182,10 +606,15 @@ impl TransportCallback for InnerSwarmCallback {
 
     async fn on_data_channel_open(&self, cid: &str) -> Result<(), CallbackError> {
         let Ok(did) = Did::from_str(cid) else {
-            tracing::warn!("on_data_channel_open parse did failed: {}", cid);
+            tracing::warn!(
+                target: "rings::handshake",
+                "on_data_channel_open parse did failed: {}",
+                cid
+            );
             return Ok(());
         };
 
+        self.transport.on_channel_opened(did);
         self.message_handler.join_dht(did).await?;
 
         // Notify Connected state here instead of on_peer_connection_state_change.
@@ -198,4 +627,40 @@ impl TransportCallback for InnerSwarmCallback {
             })
             .await
     }
+
+    async fn on_buffered_amount_low(&self, cid: &str) -> Result<(), CallbackError> {
+        let Ok(did) = Did::from_str(cid) else {
+            tracing::warn!(
+                target: "rings::swarm",
+                "on_buffered_amount_low parse did failed: {}",
+                cid
+            );
+            return Ok(());
+        };
+
+        self.callback
+            .on_event(&SwarmEvent::BufferDrained { peer: did })
+            .await
+    }
+
+    async fn on_ice_candidate(
+        &self,
+        cid: &str,
+        candidate: IceCandidate,
+    ) -> Result<(), CallbackError> {
+        let Ok(did) = Did::from_str(cid) else {
+            tracing::warn!(
+                target: "rings::handshake",
+                "on_ice_candidate parse did failed: {}",
+                cid
+            );
+            return Ok(());
+        };
+
+        // The connection is not open yet, so the candidate is relayed like the offer and answer.
+        self.transport
+            .send_message(Message::IceCandidate(candidate.into()), did)
+            .await?;
+        Ok(())
+    }
 }
@@ -0,0 +1,171 @@
+#![warn(missing_docs)]
+//! Options of [crate::swarm::SwarmBuilder] that can be loaded from a config file.
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::error::Error;
+use crate::error::Result;
+use crate::message::CompressionConfig;
+use crate::message::HandshakeCodec;
+use crate::swarm::rate_limit::RateLimit;
+use crate::swarm::transport::SendBufferPolicy;
+use crate::swarm::RtcConfig;
+
+/// Serializable options of [SwarmBuilder](crate::swarm::SwarmBuilder), applied by
+/// [SwarmBuilder::config](crate::swarm::SwarmBuilder::config). Options that are
+/// not set keep the defaults of builder. Unknown fields are rejected, so that a typo doesn't
+/// silently fall back to default.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SwarmConfig {
+    /// See [crate::swarm::SwarmBuilder::dht_succ_max].
+    pub dht_succ_max: Option<u8>,
+    /// See [crate::swarm::SwarmBuilder::session_ttl].
+    pub session_ttl: Option<usize>,
+    /// See [crate::swarm::SwarmBuilder::send_buffer_policy].
+    pub send_buffer_policy: Option<SendBufferPolicy>,
+    /// See [crate::swarm::SwarmBuilder::compression].
+    pub compression: Option<CompressionConfig>,
+    /// See [crate::swarm::SwarmBuilder::handshake_codec].
+    pub handshake_codec: Option<HandshakeCodec>,
+    /// See [crate::swarm::SwarmBuilder::acceptance_delay], in milliseconds.
+    pub acceptance_delay_ms: Option<u64>,
+    /// See [crate::swarm::SwarmBuilder::idle_timeout], in seconds.
+    pub idle_timeout_secs: Option<u64>,
+    /// See [crate::swarm::SwarmBuilder::max_message_size].
+    pub max_message_size: Option<usize>,
+    /// See [crate::swarm::SwarmBuilder::max_concurrent_connects].
+    pub max_concurrent_connects: Option<usize>,
+    /// See [crate::swarm::SwarmBuilder::rate_limit].
+    pub rate_limit: Option<RateLimit>,
+    /// See [crate::swarm::SwarmBuilder::trickle_ice].
+    pub trickle_ice: Option<bool>,
+    /// See [crate::swarm::SwarmBuilder::disable_mdns].
+    pub disable_mdns: Option<bool>,
+    /// See [crate::swarm::SwarmBuilder::buffer_drained_threshold].
+    pub buffer_drained_threshold: Option<usize>,
+    /// See [crate::swarm::SwarmBuilder::detect_nat].
+    pub detect_nat: Option<bool>,
+    /// See [crate::swarm::SwarmBuilder::event_channel_capacity].
+    pub event_channel_capacity: Option<usize>,
+    /// See [crate::swarm::SwarmBuilder::dedup_window].
+    pub dedup_window: Option<usize>,
+    /// See [crate::swarm::SwarmBuilder::rtc_config].
+    pub rtc_config: Option<RtcConfig>,
+    /// See [crate::swarm::SwarmBuilder::gather_timeout], in milliseconds.
+    pub gather_timeout_ms: Option<u64>,
+}
+
+impl SwarmConfig {
+    /// Check the options, which fails with [Error::InvalidSwarmConfig] naming the bad field.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |field: &str, reason: &str| -> Result<()> {
+            Err(Error::InvalidSwarmConfig(format!("{field} {reason}")))
+        };
+        if self.dht_succ_max == Some(0) {
+            return invalid("dht_succ_max", "should be at least 1");
+        }
+        if self.session_ttl == Some(0) {
+            return invalid("session_ttl", "should be positive");
+        }
+        if self.max_message_size == Some(0) {
+            return invalid("max_message_size", "should be positive");
+        }
+        if self.max_concurrent_connects == Some(0) {
+            return invalid("max_concurrent_connects", "should be at least 1");
+        }
+        if self.gather_timeout_ms == Some(0) {
+            return invalid("gather_timeout_ms", "should be positive");
+        }
+        if self.event_channel_capacity == Some(0) {
+            return invalid("event_channel_capacity", "should be at least 1");
+        }
+        if let Some(limit) = self.rate_limit {
+            if limit.messages_per_sec == 0 || limit.burst == 0 {
+                return invalid("rate_limit", "should allow at least 1 message");
+            }
+        }
+        if let Some(compression) = self.compression {
+            if let Err(e) = compression.validate() {
+                return invalid("compression", &e.to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::CompressionAlgorithm;
+
+    #[test]
+    fn test_reject_malformed_config() {
+        // Typo of field name.
+        let err = serde_json::from_str::<SwarmConfig>(r#"{ "dht_suc_max": 5 }"#).unwrap_err();
+        assert!(err.to_string().contains("dht_suc_max"));
+        // Wrong type.
+        assert!(serde_json::from_str::<SwarmConfig>(r#"{ "trickle_ice": "yes" }"#).is_err());
+
+        for (config, field) in [
+            (
+                SwarmConfig {
+                    dht_succ_max: Some(0),
+                    ..Default::default()
+                },
+                "dht_succ_max",
+            ),
+            (
+                SwarmConfig {
+                    max_message_size: Some(0),
+                    ..Default::default()
+                },
+                "max_message_size",
+            ),
+            (
+                SwarmConfig {
+                    compression: Some(CompressionConfig {
+                        algorithm: CompressionAlgorithm::Deflate,
+                        level: 100,
+                        threshold: 1024,
+                    }),
+                    ..Default::default()
+                },
+                "compression",
+            ),
+        ] {
+            let Err(Error::InvalidSwarmConfig(reason)) = config.validate() else {
+                panic!("{field} is not rejected");
+            };
+            assert!(reason.starts_with(field), "{reason}");
+        }
+    }
+
+    #[test]
+    fn test_send_buffer_policy_serde_round_trip() {
+        for policy in [
+            SendBufferPolicy::Unbounded,
+            SendBufferPolicy::Reject {
+                high_water_mark: 1024,
+            },
+            SendBufferPolicy::Wait {
+                high_water_mark: 2048,
+                timeout_ms: 500,
+            },
+        ] {
+            let json = serde_json::to_string(&policy).unwrap();
+            assert_eq!(
+                serde_json::from_str::<SendBufferPolicy>(&json).unwrap(),
+                policy
+            );
+
+            let config = SwarmConfig {
+                send_buffer_policy: Some(policy),
+                ..Default::default()
+            };
+            let json = serde_json::to_string(&config).unwrap();
+            assert_eq!(serde_json::from_str::<SwarmConfig>(&json).unwrap(), config);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+#![warn(missing_docs)]
+//! Histograms of the time taken to establish connections, see
+//! [crate::swarm::Swarm::connect_metrics].
+//!
+//! Each attempt is measured from creating its connection until the data channel opens.
+//! An attempt whose connection fails or is closed before that is recorded as failed, with the
+//! time elapsed until then.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::swarm::relay_metrics::Histogram;
+
+/// Name of the histograms of milliseconds taken by connection attempts.
+pub const CONNECT_LATENCY_METRIC: &str = "rings_connect_latency_ms";
+
+const LATENCY_BUCKETS_MS: [u64; 11] = [
+    50, 100, 250, 500, 1000, 2000, 3000, 5000, 8000, 15000, 30000,
+];
+
+/// Histograms of connection attempts, returned by [crate::swarm::Swarm::connect_metrics].
+/// Both are named [CONNECT_LATENCY_METRIC].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConnectMetricsSnapshot {
+    /// Attempts whose data channel opened.
+    pub succeeded: Histogram,
+    /// Attempts whose connection failed or was closed before the data channel opens.
+    pub failed: Histogram,
+}
+
+impl ConnectMetricsSnapshot {
+    /// Average time taken by successful attempts, None if there is no one yet.
+    pub fn avg_succeeded(&self) -> Option<Duration> {
+        (self.succeeded.count > 0)
+            .then(|| Duration::from_millis(self.succeeded.sum / self.succeeded.count))
+    }
+}
+
+pub(crate) struct ConnectMetrics {
+    histograms: Mutex<ConnectMetricsSnapshot>,
+}
+
+impl Default for ConnectMetrics {
+    fn default() -> Self {
+        Self {
+            histograms: Mutex::new(ConnectMetricsSnapshot {
+                succeeded: Histogram::new(CONNECT_LATENCY_METRIC, &LATENCY_BUCKETS_MS),
+                failed: Histogram::new(CONNECT_LATENCY_METRIC, &LATENCY_BUCKETS_MS),
+            }),
+        }
+    }
+}
+
+impl ConnectMetrics {
+    /// Record an attempt started at `started` and finished at `now`, in milliseconds.
+    pub fn observe(&self, started: u128, now: u128, succeeded: bool) {
+        let latency = u64::try_from(now.saturating_sub(started)).unwrap_or(u64::MAX);
+        let mut histograms = self.histograms.lock().unwrap_or_else(|e| e.into_inner());
+        if succeeded {
+            histograms.succeeded.observe(latency);
+        } else {
+            histograms.failed.observe(latency);
+        }
+    }
+
+    pub fn snapshot(&self) -> ConnectMetricsSnapshot {
+        self.histograms
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
@@ -0,0 +1,96 @@
+#![warn(missing_docs)]
+//! Counters of frames which cannot be decoded, see [crate::swarm::Swarm::decode_failures].
+//!
+//! Each frame received from a connection is decoded into a [crate::message::MessagePayload].
+//! A frame failed to decode is counted by its sender instead of being dropped silently. Once a
+//! peer sends [crate::consts::DECODE_FAILURES_THRESHOLD] of them within
+//! [crate::consts::DECODE_FAILURES_WINDOW_MS], it's disconnected as misbehaving. Failures of a
+//! peer are forgotten once it's disconnected for any reason.
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::consts::DECODE_FAILURES_THRESHOLD;
+use crate::consts::DECODE_FAILURES_WINDOW_MS;
+use crate::dht::Did;
+
+/// Name of the counter of frames failed to decode.
+pub const DECODE_FAILURES_METRIC: &str = "rings_decode_failures_total";
+
+/// Counters of frames failed to decode, returned by [crate::swarm::Swarm::decode_failures].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DecodeFailuresSnapshot {
+    /// Name of the counter, [DECODE_FAILURES_METRIC].
+    pub name: String,
+    /// Number of failures of all peers.
+    pub total: u64,
+    /// Number of recent failures of each connected peer, within the window up to its last one.
+    pub by_peer: HashMap<Did, u64>,
+}
+
+impl Default for DecodeFailuresSnapshot {
+    fn default() -> Self {
+        Self {
+            name: DECODE_FAILURES_METRIC.to_string(),
+            total: 0,
+            by_peer: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    total: u64,
+    /// Times of recent failures of each peer, oldest first.
+    by_peer: HashMap<Did, VecDeque<u128>>,
+}
+
+#[derive(Default)]
+pub(crate) struct DecodeFailures {
+    counters: Mutex<Counters>,
+}
+
+impl DecodeFailures {
+    /// Count a failure of `peer` at `now`. Return the number of failures of it within
+    /// [DECODE_FAILURES_WINDOW_MS].
+    pub fn observe(&self, peer: Did, now: u128) -> u64 {
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        counters.total += 1;
+        let times = counters.by_peer.entry(peer).or_default();
+        while times
+            .front()
+            .is_some_and(|t| now.saturating_sub(*t) >= DECODE_FAILURES_WINDOW_MS)
+        {
+            times.pop_front();
+        }
+        times.push_back(now);
+        // The peer is disconnected once it reaches the threshold, no need to keep more.
+        if times.len() as u64 > DECODE_FAILURES_THRESHOLD {
+            times.pop_front();
+        }
+        times.len() as u64
+    }
+
+    /// Forget failures of `peer`, which are still counted in total.
+    pub fn reset(&self, peer: Did) {
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        counters.by_peer.remove(&peer);
+    }
+
+    pub fn snapshot(&self) -> DecodeFailuresSnapshot {
+        let counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        DecodeFailuresSnapshot {
+            total: counters.total,
+            by_peer: counters
+                .by_peer
+                .iter()
+                .map(|(peer, times)| (*peer, times.len() as u64))
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! Dropping of messages delivered more than once, see [crate::swarm::SwarmBuilder::dedup_window].
+//!
+//! A message can reach its destination through more than one relay path. Each transaction
+//! has a unique tx_id chosen by its signer, so the signers and tx_ids of recently handled
+//! messages are kept to drop the copies. A tx_id is only unique per signer, so that a node
+//! can't suppress messages of others by sending the same tx_ids first.
+
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use crate::dht::Did;
+
+type DedupKey = (Did, uuid::Uuid);
+
+/// The signers and tx_ids of the last `capacity` messages handled by this node.
+/// When it's full, the oldest one is evicted.
+pub(crate) struct DedupWindow {
+    capacity: usize,
+    seen: Mutex<(HashSet<DedupKey>, VecDeque<DedupKey>)>,
+}
+
+impl DedupWindow {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            seen: Mutex::new((
+                HashSet::with_capacity(capacity),
+                VecDeque::with_capacity(capacity),
+            )),
+        }
+    }
+
+    /// Remember `tx_id` signed by `signer` as seen. Return false if it's seen already.
+    pub fn insert(&self, signer: Did, tx_id: uuid::Uuid) -> bool {
+        let key = (signer, tx_id);
+        let mut guard = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        let (set, order) = &mut *guard;
+        if !set.insert(key) {
+            return false;
+        }
+        order.push_back(key);
+        if order.len() > self.capacity {
+            if let Some(oldest) = order.pop_front() {
+                set.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evict_oldest_when_full() {
+        let window = DedupWindow::new(2);
+        let signer = Did::from(1u32);
+        let ids = (0..3).map(|_| uuid::Uuid::new_v4()).collect::<Vec<_>>();
+        assert!(window.insert(signer, ids[0]));
+        assert!(!window.insert(signer, ids[0]));
+        assert!(window.insert(signer, ids[1]));
+        assert!(window.insert(signer, ids[2]));
+        // The first one is evicted, so it's not seen anymore.
+        assert!(!window.insert(signer, ids[2]));
+        assert!(window.insert(signer, ids[0]));
+    }
+
+    #[test]
+    fn test_same_tx_id_of_other_signer() {
+        let window = DedupWindow::new(2);
+        let id = uuid::Uuid::new_v4();
+        assert!(window.insert(Did::from(1u32), id));
+        assert!(window.insert(Did::from(2u32), id));
+        assert!(!window.insert(Did::from(1u32), id));
+    }
+}
@@ -0,0 +1,376 @@
+#![warn(missing_docs)]
+//! File transfer between swarms, on top of messages.
+//!
+//! [Swarm::send_file] reads a file in chunks of [FILE_CHUNK_SIZE] and sends them as
+//! [FileChunk] with [Priority::Bulk](crate::message::Priority::Bulk), so that other messages
+//! of the same connection are not blocked. At most [FILE_TRANSFER_WINDOW] chunks are sent
+//! before they're acked by [FileChunkAck]. The receiver writes chunks in order to the writer
+//! opened by its [FileReceiver], and drops out-of-order ones, so a broken transfer can be
+//! resumed from the acked position by [Swarm::send_file_from]. The receiver keeps at most
+//! [FILE_MAX_INCOMING_TRANSFERS] files open, and drops those idle for [FILE_ACK_TIMEOUT_MS].
+
+use std::future::Future;
+use std::io::Read;
+use std::io::Write;
+use std::pin::Pin;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+
+use futures::channel::mpsc;
+use futures::future::Either;
+use futures::StreamExt;
+
+use super::Swarm;
+use crate::consts::FILE_ACK_TIMEOUT_MS;
+use crate::consts::FILE_CHUNK_SIZE;
+use crate::consts::FILE_MAX_INCOMING_TRANSFERS;
+use crate::consts::FILE_TRANSFER_WINDOW;
+use crate::dht::Did;
+use crate::error::Error;
+use crate::error::Result;
+use crate::message::FileChunk;
+use crate::message::FileChunkAck;
+use crate::message::FileMetadata;
+use crate::message::Message;
+use crate::message::PayloadSender;
+use crate::swarm::transport::SwarmTransport;
+use crate::utils;
+use crate::utils::Clock;
+
+/// Receiver of files sent by [Swarm::send_file], set by
+/// [SwarmBuilder::file_receiver](crate::swarm::SwarmBuilder::file_receiver).
+pub trait FileReceiver: Send + Sync {
+    /// Open a writer for a transfer of file from `peer`, which starts at `offset` of the file.
+    /// Return None to reject the transfer.
+    fn open(
+        &self,
+        peer: Did,
+        metadata: &FileMetadata,
+        offset: u64,
+    ) -> Option<Box<dyn Write + Send>>;
+
+    /// Called when the last chunk of a transfer is written and the writer is flushed.
+    /// `received` is the position in the file up to which bytes are written.
+    fn on_complete(
+        &self,
+        peer: Did,
+        transfer_id: uuid::Uuid,
+        metadata: &FileMetadata,
+        received: u64,
+    );
+}
+
+/// Progress of a file transfer, shared by [TransferHandle].
+#[derive(Debug, Default)]
+pub struct TransferProgress {
+    sent: AtomicU64,
+    acked: AtomicU64,
+}
+
+impl TransferProgress {
+    /// Bytes sent by this transfer.
+    pub fn sent_bytes(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    /// Bytes of this transfer written by receiver.
+    pub fn acked_bytes(&self) -> u64 {
+        self.acked.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+type TransferTask = futures::future::BoxFuture<'static, Result<u64>>;
+#[cfg(feature = "wasm")]
+type TransferTask = futures::future::LocalBoxFuture<'static, Result<u64>>;
+
+/// A file transfer started by [Swarm::send_file]. The transfer runs when the handle is awaited,
+/// which resolves to the number of bytes acked by receiver.
+pub struct TransferHandle {
+    transfer_id: uuid::Uuid,
+    progress: Arc<TransferProgress>,
+    task: TransferTask,
+}
+
+impl TransferHandle {
+    /// Id of the transfer, which is also given to [FileReceiver::on_complete].
+    pub fn transfer_id(&self) -> uuid::Uuid {
+        self.transfer_id
+    }
+
+    /// Progress of the transfer, which can be watched while the handle is awaited.
+    pub fn progress(&self) -> Arc<TransferProgress> {
+        self.progress.clone()
+    }
+}
+
+impl Future for TransferHandle {
+    type Output = Result<u64>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.task.as_mut().poll(cx)
+    }
+}
+
+/// A file being received, see [SwarmTransport::receive_file_chunk].
+pub(crate) struct IncomingFile {
+    peer: Did,
+    metadata: FileMetadata,
+    /// The writer is taken while a chunk is being written.
+    writer: Option<Box<dyn Write + Send>>,
+    received: u64,
+    last_active_ms: u128,
+}
+
+impl Swarm {
+    /// Send a file read from `reader` to `peer`, see [crate::swarm::file].
+    /// The peer should set [crate::swarm::SwarmBuilder::file_receiver] to accept it,
+    /// otherwise the transfer fails with [Error::FileTransferRejected].
+    pub fn send_file(
+        &self,
+        peer: Did,
+        reader: impl Read + Send + 'static,
+        metadata: FileMetadata,
+    ) -> TransferHandle {
+        self.send_file_from(peer, reader, metadata, 0)
+    }
+
+    /// Send a file like [Swarm::send_file], starting at `offset` of the file, such as the
+    /// position acked before a transfer is broken. `reader` should start at `offset` too.
+    pub fn send_file_from(
+        &self,
+        peer: Did,
+        reader: impl Read + Send + 'static,
+        metadata: FileMetadata,
+        offset: u64,
+    ) -> TransferHandle {
+        let transfer_id = uuid::Uuid::new_v4();
+        let progress = Arc::new(TransferProgress::default());
+        let task = send_file_chunks(
+            self.transport.clone(),
+            peer,
+            transfer_id,
+            Box::new(reader),
+            metadata,
+            offset,
+            progress.clone(),
+        );
+        TransferHandle {
+            transfer_id,
+            progress,
+            task: Box::pin(task),
+        }
+    }
+}
+
+/// Write a chunk by `writer`, which is returned unless writing fails.
+/// On native, it's written on the blocking threads of runtime, so that a slow writer, such as
+/// a file on disk, doesn't block handling other messages.
+async fn write_chunk(
+    mut writer: Box<dyn Write + Send>,
+    data: Vec<u8>,
+    flush: bool,
+) -> std::io::Result<Box<dyn Write + Send>> {
+    let write = move || {
+        writer.write_all(&data)?;
+        if flush {
+            writer.flush()?;
+        }
+        Ok(writer)
+    };
+    #[cfg(not(feature = "wasm"))]
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        return handle
+            .spawn_blocking(write)
+            .await
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+    }
+    write()
+}
+
+/// Read up to [FILE_CHUNK_SIZE] bytes, less only if the reader reaches its end.
+fn read_chunk(reader: &mut dyn Read) -> Result<Vec<u8>> {
+    let mut buf = vec![0; FILE_CHUNK_SIZE];
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(Error::IOError(e)),
+        }
+    }
+    buf.truncate(len);
+    Ok(buf)
+}
+
+async fn send_file_chunks(
+    transport: Arc<SwarmTransport>,
+    peer: Did,
+    transfer_id: uuid::Uuid,
+    mut reader: Box<dyn Read + Send>,
+    metadata: FileMetadata,
+    offset: u64,
+    progress: Arc<TransferProgress>,
+) -> Result<u64> {
+    let (tx, mut acks) = mpsc::unbounded();
+    transport.file_acks.insert(transfer_id, tx);
+    let result: Result<u64> = async {
+        let window = (FILE_TRANSFER_WINDOW * FILE_CHUNK_SIZE) as u64;
+        let mut metadata = Some(metadata);
+        let mut position = offset;
+        let mut acked = offset;
+        let mut finished = false;
+        loop {
+            while !finished && position - acked < window {
+                let data = read_chunk(reader.as_mut())?;
+                let len = data.len() as u64;
+                finished = data.len() < FILE_CHUNK_SIZE;
+                let chunk = FileChunk {
+                    transfer_id,
+                    metadata: metadata.take(),
+                    offset: position,
+                    data,
+                    last: finished,
+                };
+                transport
+                    .send_message(Message::FileChunk(chunk), peer)
+                    .await?;
+                position += len;
+                progress.sent.store(position - offset, Ordering::Relaxed);
+            }
+            if finished && acked == position {
+                return Ok(acked - offset);
+            }
+
+            let timeout = utils::sleep(Duration::from_millis(FILE_ACK_TIMEOUT_MS));
+            futures::pin_mut!(timeout);
+            let ack = match futures::future::select(acks.next(), timeout).await {
+                Either::Left((Some(ack), _)) => ack,
+                _ => return Err(Error::FileTransferTimeout(transfer_id)),
+            };
+            if ack.rejected {
+                return Err(Error::FileTransferRejected(transfer_id));
+            }
+            acked = acked.max(ack.received.min(position));
+            progress.acked.store(acked - offset, Ordering::Relaxed);
+        }
+    }
+    .await;
+    transport.file_acks.remove(&transfer_id);
+    result
+}
+
+impl SwarmTransport {
+    /// Write a chunk received from `peer` to the writer of its transfer, which is opened by
+    /// [FileReceiver] when the first chunk arrives. Return the ack to send back.
+    ///
+    /// At most [FILE_MAX_INCOMING_TRANSFERS] files are received at the same time, and a file
+    /// without chunks for [FILE_ACK_TIMEOUT_MS] is dropped, since its sender has given up.
+    pub(crate) async fn receive_file_chunk(&self, peer: Did, chunk: &FileChunk) -> FileChunkAck {
+        let ack = |received: u64, rejected: bool| FileChunkAck {
+            transfer_id: chunk.transfer_id,
+            received,
+            rejected,
+        };
+        let now = self.clock.now_ms();
+        self.evict_idle_files(now);
+
+        let mut file = match self.incoming_files.get_mut(&chunk.transfer_id) {
+            Some(file) if file.peer == peer => file,
+            Some(_) => return ack(0, true),
+            None => {
+                let (Some(receiver), Some(metadata)) = (&self.file_receiver, &chunk.metadata)
+                else {
+                    return ack(chunk.offset, true);
+                };
+                if self.incoming_files.len() >= FILE_MAX_INCOMING_TRANSFERS {
+                    tracing::warn!(
+                        "Reject file transfer {} from {peer}, too many files are being received",
+                        chunk.transfer_id
+                    );
+                    return ack(chunk.offset, true);
+                }
+                let Some(writer) = receiver.open(peer, metadata, chunk.offset) else {
+                    return ack(chunk.offset, true);
+                };
+                self.incoming_files
+                    .entry(chunk.transfer_id)
+                    .or_insert(IncomingFile {
+                        peer,
+                        metadata: metadata.clone(),
+                        writer: Some(writer),
+                        received: chunk.offset,
+                        last_active_ms: now,
+                    })
+            }
+        };
+        file.last_active_ms = now;
+
+        // Out-of-order chunks are dropped, the sender can resume from the acked position.
+        // So are chunks arriving while the previous one is being written.
+        if chunk.offset != file.received {
+            return ack(file.received, false);
+        }
+        let Some(writer) = file.writer.take() else {
+            return ack(file.received, false);
+        };
+        // The writer may block, it's written without holding the entry.
+        drop(file);
+
+        let written = write_chunk(writer, chunk.data.clone(), chunk.last).await;
+        let Some(mut file) = self.incoming_files.get_mut(&chunk.transfer_id) else {
+            return ack(chunk.offset, true);
+        };
+        let writer = match written {
+            Ok(writer) => writer,
+            Err(e) => {
+                tracing::error!(
+                    "Failed to write file of transfer {}: {e:?}",
+                    chunk.transfer_id
+                );
+                drop(file);
+                self.incoming_files.remove(&chunk.transfer_id);
+                return ack(chunk.offset, true);
+            }
+        };
+        file.writer = Some(writer);
+        file.received += chunk.data.len() as u64;
+        let received = file.received;
+        drop(file);
+
+        if chunk.last {
+            if let Some((_, file)) = self.incoming_files.remove(&chunk.transfer_id) {
+                if let Some(receiver) = &self.file_receiver {
+                    receiver.on_complete(peer, chunk.transfer_id, &file.metadata, received);
+                }
+            }
+        }
+        ack(received, false)
+    }
+
+    /// Drop incoming files without chunks for [FILE_ACK_TIMEOUT_MS].
+    fn evict_idle_files(&self, now: u128) {
+        self.incoming_files.retain(|id, file| {
+            let idle = now.saturating_sub(file.last_active_ms) >= FILE_ACK_TIMEOUT_MS as u128;
+            if idle {
+                tracing::warn!(
+                    "Drop file transfer {id} from {}, reason: Timeout",
+                    file.peer
+                );
+            }
+            !idle
+        });
+    }
+
+    /// Deliver an ack to the transfer waiting for it.
+    pub(crate) fn resolve_file_ack(&self, ack: &FileChunkAck) {
+        if let Some(tx) = self.file_acks.get(&ack.transfer_id) {
+            let _ = tx.unbounded_send(ack.clone());
+        }
+    }
+}
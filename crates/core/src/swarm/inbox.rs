@@ -0,0 +1,548 @@
+//! Bounded buffers of inbound messages and swarm events, consumed as streams.
+//!
+//! The buffers are fed by [Inboxes] of swarm after the callback set by [Swarm::set_callback],
+//! so that they are kept when the callback is replaced.
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::task::Context;
+use std::task::Poll;
+use std::task::Waker;
+use std::time::Duration;
+
+use futures::future::Either;
+use futures::task::AtomicWaker;
+use futures::Stream;
+
+use super::Swarm;
+use crate::error::Error;
+use crate::error::Result;
+use crate::message::MessagePayload;
+use crate::swarm::callback::SwarmEvent;
+use crate::utils;
+
+/// What to do when the buffer of [BoundedMessages] is full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowMode {
+    /// Wait until the consumer takes a message, so that a slow consumer slows down its senders
+    /// instead of growing the buffer. Only the connection delivering the message waits, for at
+    /// most [SwarmBuilder::inbox_block_timeout], then the message is dropped and counted by
+    /// [BoundedMessages::dropped]. So a consumer which stops taking messages can't stall the
+    /// handling of inbound messages.
+    ///
+    /// [SwarmBuilder::inbox_block_timeout]: crate::swarm::SwarmBuilder::inbox_block_timeout
+    #[default]
+    Block,
+    /// Drop the oldest message in buffer to make room for the new one.
+    /// Dropped messages are counted by [BoundedMessages::dropped].
+    DropOldest,
+}
+
+struct Buffer<T> {
+    capacity: usize,
+    queue: Mutex<VecDeque<T>>,
+    /// Waker of the consumer.
+    waker: AtomicWaker,
+    /// Wakers of pushes waiting for room, see [Buffer::push_or_wait].
+    room: Mutex<Vec<Waker>>,
+    dropped: AtomicU64,
+    closed: AtomicBool,
+}
+
+impl<T> Buffer<T> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            queue: Mutex::new(VecDeque::with_capacity(capacity)),
+            waker: AtomicWaker::new(),
+            room: Mutex::new(vec![]),
+            dropped: AtomicU64::new(0),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    /// Push an item, return true if the oldest one is dropped to make room for it.
+    fn push(&self, item: T) -> bool {
+        let dropped = {
+            let mut queue = self.queue.lock().unwrap();
+            let dropped = queue.len() >= self.capacity;
+            if dropped {
+                queue.pop_front();
+                self.dropped.fetch_add(1, Ordering::SeqCst);
+            }
+            queue.push_back(item);
+            dropped
+        };
+        self.waker.wake();
+        dropped
+    }
+
+    /// Push the item once there is room. It's dropped if the buffer is closed.
+    fn poll_push(&self, item: &mut Option<T>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Poll::Ready(());
+        }
+        {
+            let mut queue = self.queue.lock().unwrap();
+            if queue.len() >= self.capacity {
+                // Registered with the queue locked, so that a pop can't be missed.
+                self.room.lock().unwrap().push(cx.waker().clone());
+                return Poll::Pending;
+            }
+            if let Some(item) = item.take() {
+                queue.push_back(item);
+            }
+        }
+        self.waker.wake();
+        Poll::Ready(())
+    }
+
+    /// Push an item once there is room, waiting at most `timeout`.
+    /// Return false if it's dropped for timeout, which is counted.
+    async fn push_or_wait(&self, item: T, timeout: Duration) -> bool {
+        let mut item = Some(item);
+        let push = futures::future::poll_fn(|cx| self.poll_push(&mut item, cx));
+        let timeout = utils::sleep(timeout);
+        futures::pin_mut!(push, timeout);
+        match futures::future::select(push, timeout).await {
+            Either::Left(_) => true,
+            Either::Right(_) => {
+                self.dropped.fetch_add(1, Ordering::SeqCst);
+                false
+            }
+        }
+    }
+
+    fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.waker.wake();
+        self.wake_room();
+    }
+
+    fn wake_room(&self) {
+        let wakers = std::mem::take(&mut *self.room.lock().unwrap());
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    fn poll_next(&self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.waker.register(cx.waker());
+        let item = self.queue.lock().unwrap().pop_front();
+        if let Some(item) = item {
+            self.wake_room();
+            return Poll::Ready(Some(item));
+        }
+        if self.closed.load(Ordering::SeqCst) {
+            return Poll::Ready(None);
+        }
+        Poll::Pending
+    }
+}
+
+/// Stream of messages sent to this node, created by [Swarm::iter_messages_bounded].
+/// At most `capacity` messages are buffered, see [OverflowMode] for what happens beyond that.
+/// The stream ends when another one is created.
+pub struct BoundedMessages {
+    buffer: Arc<Buffer<MessagePayload>>,
+}
+
+impl BoundedMessages {
+    /// Number of messages dropped because the buffer was full.
+    pub fn dropped(&self) -> u64 {
+        self.buffer.dropped.load(Ordering::SeqCst)
+    }
+}
+
+impl Stream for BoundedMessages {
+    type Item = MessagePayload;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.buffer.poll_next(cx)
+    }
+}
+
+#[derive(Clone)]
+struct MessagesInbox {
+    buffer: Arc<Buffer<MessagePayload>>,
+    mode: OverflowMode,
+    block_timeout: Duration,
+}
+
+/// Buffers of the [BoundedMessages] and [SwarmEvents] of swarm, fed after its callback.
+/// Each of them is replaced by the next one created, which ends the stream of the last one.
+#[derive(Default)]
+pub(crate) struct Inboxes {
+    messages: Mutex<Option<MessagesInbox>>,
+    events: Mutex<Option<Arc<Buffer<SwarmEvent>>>>,
+    /// Number of events dropped by all [SwarmEvents] of swarm.
+    dropped_events: AtomicU64,
+}
+
+impl Inboxes {
+    fn set_messages(&self, inbox: MessagesInbox) {
+        if let Some(last) = self.messages.lock().unwrap().replace(inbox) {
+            last.buffer.close();
+        }
+    }
+
+    fn set_events(&self, buffer: Arc<Buffer<SwarmEvent>>) {
+        if let Some(last) = self.events.lock().unwrap().replace(buffer) {
+            last.close();
+        }
+    }
+
+    pub(crate) async fn on_inbound(&self, payload: &MessagePayload) {
+        let Some(inbox) = self.messages.lock().unwrap().clone() else {
+            return;
+        };
+        match inbox.mode {
+            OverflowMode::Block => {
+                let pushed = inbox
+                    .buffer
+                    .push_or_wait(payload.clone(), inbox.block_timeout)
+                    .await;
+                if !pushed {
+                    let tx_id = payload.transaction.tx_id;
+                    tracing::debug!("Message buffer is full, dropped message {tx_id}");
+                }
+            }
+            OverflowMode::DropOldest => {
+                inbox.buffer.push(payload.clone());
+            }
+        }
+    }
+
+    pub(crate) fn on_event(&self, event: &SwarmEvent) {
+        let Some(buffer) = self.events.lock().unwrap().clone() else {
+            return;
+        };
+        if buffer.push(event.clone()) {
+            let dropped = self.dropped_events.fetch_add(1, Ordering::SeqCst) + 1;
+            tracing::debug!("Event buffer is full, dropped {dropped} events so far");
+        }
+    }
+}
+
+impl Drop for Inboxes {
+    fn drop(&mut self) {
+        if let Some(inbox) = self.messages.lock().unwrap().take() {
+            inbox.buffer.close();
+        }
+        if let Some(buffer) = self.events.lock().unwrap().take() {
+            buffer.close();
+        }
+    }
+}
+
+impl Swarm {
+    /// Iterate messages sent to this node, buffering at most `capacity` of them.
+    ///
+    /// The callback set by [Swarm::set_callback] keeps receiving all the messages and events,
+    /// and replacing it doesn't affect the stream. The stream created by the last call ends.
+    /// Return [Error::InvalidInboxCapacity] if `capacity` is 0.
+    pub fn iter_messages_bounded(
+        &self,
+        capacity: usize,
+        mode: OverflowMode,
+    ) -> Result<BoundedMessages> {
+        if capacity == 0 {
+            return Err(Error::InvalidInboxCapacity);
+        }
+        let buffer = Arc::new(Buffer::new(capacity));
+        self.callback.inboxes().set_messages(MessagesInbox {
+            buffer: buffer.clone(),
+            mode,
+            block_timeout: self.transport.inbox_block_timeout,
+        });
+        Ok(BoundedMessages { buffer })
+    }
+
+    /// Iterate events of swarm, buffering at most [SwarmBuilder::event_channel_capacity] of
+    /// them. When the buffer is full, the oldest event is dropped and counted by
+    /// [Swarm::dropped_events], so a slow consumer never blocks the swarm.
+    ///
+    /// Like [Swarm::iter_messages_bounded], the callback set by [Swarm::set_callback] keeps
+    /// receiving all the messages and events, and the stream created by the last call ends.
+    ///
+    /// [SwarmBuilder::event_channel_capacity]: crate::swarm::SwarmBuilder::event_channel_capacity
+    pub fn iter_events(&self) -> Result<SwarmEvents> {
+        let buffer = Arc::new(Buffer::new(self.transport.event_channel_capacity));
+        self.callback.inboxes().set_events(buffer.clone());
+        Ok(SwarmEvents { buffer })
+    }
+
+    /// Number of events dropped because the buffer of a [SwarmEvents] was full, counted since
+    /// swarm is built.
+    pub fn dropped_events(&self) -> u64 {
+        self.callback
+            .inboxes()
+            .dropped_events
+            .load(Ordering::SeqCst)
+    }
+}
+
+/// Stream of events of swarm, created by [Swarm::iter_events].
+pub struct SwarmEvents {
+    buffer: Arc<Buffer<SwarmEvent>>,
+}
+
+impl SwarmEvents {
+    /// Number of events of this stream dropped because the buffer was full.
+    pub fn dropped(&self) -> u64 {
+        self.buffer.dropped.load(Ordering::SeqCst)
+    }
+}
+
+impl Stream for SwarmEvents {
+    type Item = SwarmEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.buffer.poll_next(cx)
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+#[cfg(test)]
+mod tests {
+    use futures::FutureExt;
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::ecc::tests::gen_ordered_keys;
+    use crate::message::Message;
+    use crate::swarm::callback::SwarmCallback;
+    use crate::tests::default::prepare_node;
+    use crate::tests::default::prepare_node_with_builder;
+    use crate::tests::default::wait_for_msgs;
+    use crate::tests::manually_establish_connection;
+
+    fn drain(messages: &mut BoundedMessages) -> Vec<MessagePayload> {
+        let mut drained = vec![];
+        while let Some(Some(payload)) = messages.next().now_or_never() {
+            drained.push(payload);
+        }
+        drained
+    }
+
+    fn custom_data(payload: &MessagePayload) -> Vec<u8> {
+        match payload.transaction.data().unwrap() {
+            Message::CustomMessage(msg) => msg.0,
+            msg => panic!("Unexpected message {msg:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_iter_messages_drop_oldest() {
+        let keys = gen_ordered_keys(2);
+        let node1 = prepare_node(keys[0]).await;
+        let node2 = prepare_node(keys[1]).await;
+
+        let mut messages = node2
+            .swarm
+            .iter_messages_bounded(4, OverflowMode::DropOldest)
+            .unwrap();
+
+        manually_establish_connection(&node1.swarm, &node2.swarm).await;
+        wait_for_msgs([&node1, &node2]).await;
+        drain(&mut messages);
+        let dropped = messages.dropped();
+
+        for i in 0..20u8 {
+            node1
+                .swarm
+                .send_message(Message::custom(&[i]).unwrap(), node2.did())
+                .await
+                .unwrap();
+        }
+        wait_for_msgs([&node1, &node2]).await;
+
+        let received = drain(&mut messages);
+        assert_eq!(received.iter().map(custom_data).collect::<Vec<_>>(), vec![
+            vec![16],
+            vec![17],
+            vec![18],
+            vec![19]
+        ]);
+        assert_eq!(messages.dropped() - dropped, 16);
+    }
+
+    #[tokio::test]
+    async fn test_iter_messages_block() {
+        let keys = gen_ordered_keys(2);
+        let node1 = prepare_node(keys[0]).await;
+        let node2 = prepare_node(keys[1]).await;
+
+        let mut messages = node2
+            .swarm
+            .iter_messages_bounded(2, OverflowMode::Block)
+            .unwrap();
+
+        manually_establish_connection(&node1.swarm, &node2.swarm).await;
+        wait_for_msgs([&node1, &node2]).await;
+        drain(&mut messages);
+
+        for i in 0..5u8 {
+            node1
+                .swarm
+                .send_message(Message::custom(&[i]).unwrap(), node2.did())
+                .await
+                .unwrap();
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+        // Only the buffered ones are ready, the others wait for the consumer.
+        let received = drain(&mut messages);
+        assert_eq!(received.iter().map(custom_data).collect::<Vec<_>>(), vec![
+            vec![0],
+            vec![1]
+        ]);
+        assert_eq!(messages.dropped(), 0);
+
+        for i in 2..5u8 {
+            let payload = tokio::time::timeout(std::time::Duration::from_secs(5), messages.next())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(custom_data(&payload), vec![i]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_iter_messages_block_times_out() {
+        let keys = gen_ordered_keys(2);
+        let node1 = prepare_node(keys[0]).await;
+        let node2 = prepare_node_with_builder(keys[1], |b| {
+            b.inbox_block_timeout(Duration::from_millis(100))
+        })
+        .await;
+
+        let mut messages = node2
+            .swarm
+            .iter_messages_bounded(1, OverflowMode::Block)
+            .unwrap();
+
+        manually_establish_connection(&node1.swarm, &node2.swarm).await;
+        wait_for_msgs([&node1, &node2]).await;
+        drain(&mut messages);
+        let dropped = messages.dropped();
+
+        // The consumer takes nothing, so messages beyond the buffer are dropped after waiting,
+        // instead of stalling the handling.
+        for i in 0..3u8 {
+            node1
+                .swarm
+                .send_message(Message::custom(&[i]).unwrap(), node2.did())
+                .await
+                .unwrap();
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(messages.dropped() - dropped, 2);
+        let received = drain(&mut messages);
+        assert_eq!(received.iter().map(custom_data).collect::<Vec<_>>(), vec![
+            vec![0]
+        ]);
+
+        node1
+            .swarm
+            .send_message(Message::custom(&[3]).unwrap(), node2.did())
+            .await
+            .unwrap();
+        let payload = tokio::time::timeout(Duration::from_secs(5), messages.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(custom_data(&payload), vec![3]);
+    }
+
+    #[derive(Default)]
+    struct CustomRecorder {
+        received: std::sync::Mutex<Vec<Vec<u8>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl SwarmCallback for CustomRecorder {
+        async fn on_inbound(
+            &self,
+            payload: &MessagePayload,
+        ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+            if let Ok(Message::CustomMessage(msg)) = payload.transaction.data() {
+                self.received.lock().unwrap().push(msg.0);
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_iter_messages_is_replaced_and_kept_by_set_callback() {
+        let keys = gen_ordered_keys(2);
+        let node1 = prepare_node(keys[0]).await;
+        let node2 = prepare_node(keys[1]).await;
+
+        let mut first = node2
+            .swarm
+            .iter_messages_bounded(8, OverflowMode::DropOldest)
+            .unwrap();
+        let mut messages = node2
+            .swarm
+            .iter_messages_bounded(8, OverflowMode::DropOldest)
+            .unwrap();
+        // The stream created first ends.
+        assert!(matches!(first.next().now_or_never(), Some(None)));
+
+        // Replacing the callback keeps the stream.
+        let recorder = Arc::new(CustomRecorder::default());
+        node2.swarm.set_callback(recorder.clone()).unwrap();
+
+        manually_establish_connection(&node1.swarm, &node2.swarm).await;
+        wait_for_msgs([&node1, &node2]).await;
+        drain(&mut messages);
+
+        for i in 0..3u8 {
+            node1
+                .swarm
+                .send_message(Message::custom(&[i]).unwrap(), node2.did())
+                .await
+                .unwrap();
+        }
+        wait_for_msgs([&node1, &node2]).await;
+
+        let expected = vec![vec![0], vec![1], vec![2]];
+        let received = drain(&mut messages);
+        assert_eq!(
+            received.iter().map(custom_data).collect::<Vec<_>>(),
+            expected
+        );
+        // Each message is passed to the callback once.
+        assert_eq!(*recorder.received.lock().unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_iter_events_counts_overflow() {
+        let keys = gen_ordered_keys(2);
+        let node1 = prepare_node(keys[0]).await;
+        let node2 = prepare_node_with_builder(keys[1], |b| b.event_channel_capacity(1)).await;
+
+        let mut events = node2.swarm.iter_events().unwrap();
+        assert_eq!(node2.swarm.dropped_events(), 0);
+
+        // Connecting and closing a connection emits several state changes, while the consumer
+        // takes none of them.
+        manually_establish_connection(&node1.swarm, &node2.swarm).await;
+        wait_for_msgs([&node1, &node2]).await;
+        node2.swarm.disconnect(node1.did()).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+        assert!(node2.swarm.dropped_events() > 0);
+        assert_eq!(node2.swarm.dropped_events(), events.dropped());
+
+        let mut buffered = 0;
+        while let Some(Some(_)) = events.next().now_or_never() {
+            buffered += 1;
+        }
+        assert_eq!(buffered, 1);
+    }
+}
@@ -0,0 +1,483 @@
+//! Lookups of DHT that are driven by swarm, including traced lookup for diagnostics
+//! and warming up finger table and neighbours.
+
+use async_stream::stream;
+use futures::Stream;
+use futures::StreamExt;
+use num_bigint::BigUint;
+
+use super::Swarm;
+use crate::consts::FIND_SUCCESSOR_TIMEOUT_MS;
+use crate::consts::LOOKUP_PROBE_TIMEOUT_MS;
+use crate::consts::WARM_FINGERS_CONCURRENCY;
+use crate::consts::WARM_UP_CONCURRENCY;
+use crate::dht::Chord;
+use crate::dht::Did;
+use crate::dht::PeerRing;
+use crate::dht::PeerRingAction;
+use crate::dht::SuccessorReader;
+use crate::error::Error;
+use crate::error::Result;
+use crate::message::FindSuccessorReportHandler;
+use crate::message::FindSuccessorSend;
+use crate::message::FindSuccessorThen;
+use crate::message::LookupProbeSend;
+use crate::message::Message;
+use crate::message::MessageVerificationExt;
+use crate::message::PayloadSender;
+use crate::swarm::transport::SwarmTransport;
+
+/// A step of a traced lookup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LookupStep {
+    /// The node visited in this step.
+    pub hop: Did,
+    /// The candidate chosen by `hop`. It's the successor of key if `resolved` is true.
+    /// None if `hop` didn't respond.
+    pub candidate: Option<Did>,
+    /// Whether `hop` answered its routing decision in time.
+    pub responded: bool,
+    /// Whether the lookup is resolved by `hop`.
+    pub resolved: bool,
+}
+
+/// Result of [Swarm::warm_fingers].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WarmFingersReport {
+    /// Distinct successors of all finger targets.
+    pub fingers: Vec<Did>,
+    /// Peers that new connections are initiated to.
+    pub connecting: Vec<Did>,
+    /// Number of lookups or connects failed.
+    pub failed: usize,
+}
+
+impl Swarm {
+    /// Fill the finger table in parallel.
+    ///
+    /// The successors of all finger targets are looked up concurrently, then connections to
+    /// those not connected yet are initiated concurrently. At most [WARM_FINGERS_CONCURRENCY]
+    /// lookups or connects are in flight at the same time. A failed lookup or connect is logged
+    /// and counted in the report, and doesn't block the others.
+    ///
+    /// Fingers are added to the finger table when their connections are established.
+    pub async fn warm_fingers(&self) -> Result<WarmFingersReport> {
+        let did = self.did();
+        let mut report = WarmFingersReport::default();
+
+        // Stale fingers may point to peers already gone, the lookups of them are sent
+        // through successor instead.
+        let successor = self.dht.successors().min()?;
+        let mut lookups = vec![];
+        for k in 0..160u32 {
+            let target = did + Did::from(BigUint::from(2u16).pow(k));
+            match self.dht.find_successor(target)? {
+                PeerRingAction::Some(succ) => push_unique(&mut report.fingers, succ),
+                PeerRingAction::RemoteAction(next, _) if self.transport.is_connected(next) => {
+                    lookups.push((target, next))
+                }
+                PeerRingAction::RemoteAction(..) if successor != did => {
+                    lookups.push((target, successor))
+                }
+                _ => report.failed += 1,
+            }
+        }
+
+        let found: Vec<Result<Did>> = futures::stream::iter(lookups)
+            .map(|(target, next)| find_successor_via(&self.transport, target, next))
+            .buffer_unordered(WARM_FINGERS_CONCURRENCY)
+            .collect()
+            .await;
+        for succ in found {
+            match succ {
+                Ok(succ) => push_unique(&mut report.fingers, succ),
+                Err(e) => {
+                    tracing::warn!(target: "rings::swarm", "Failed on looking up finger: {:?}", e);
+                    report.failed += 1;
+                }
+            }
+        }
+
+        let peers: Vec<Did> = report
+            .fingers
+            .iter()
+            .copied()
+            .filter(|peer| *peer != did && self.transport.get_connection(*peer).is_none())
+            .collect();
+        let connected: Vec<(Did, Result<()>)> = futures::stream::iter(peers)
+            .map(|peer| async move { (peer, self.connect(peer).await) })
+            .buffer_unordered(WARM_FINGERS_CONCURRENCY)
+            .collect()
+            .await;
+        for (peer, res) in connected {
+            match res {
+                Ok(()) => report.connecting.push(peer),
+                Err(e) => {
+                    tracing::warn!(
+                        target: "rings::swarm",
+                        "Failed on connecting finger {}: {:?}",
+                        peer,
+                        e
+                    );
+                    report.failed += 1;
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Connect the successors and predecessor of DHT in parallel, so that messages routed to
+    /// the neighbours of this node don't wait for connecting.
+    ///
+    /// Dids already connected or being connected are skipped, so it's fine to call it again.
+    /// At most [WARM_UP_CONCURRENCY] connects are in flight at the same time. A failed connect
+    /// is logged and doesn't block the others.
+    /// Return the Dids that new connections are initiated to.
+    pub async fn warm_up(&self) -> Result<Vec<Did>> {
+        let did = self.did();
+        let mut peers = self.dht.successors().list()?;
+        peers.extend(*self.dht.lock_predecessor()?);
+
+        let mut targets = vec![];
+        for peer in peers {
+            if peer != did && self.transport.get_connection(peer).is_none() {
+                push_unique(&mut targets, peer);
+            }
+        }
+
+        let connected: Vec<(Did, Result<()>)> = futures::stream::iter(targets)
+            .map(|peer| async move { (peer, self.connect(peer).await) })
+            .buffer_unordered(WARM_UP_CONCURRENCY)
+            .collect()
+            .await;
+
+        let mut connecting = vec![];
+        for (peer, res) in connected {
+            match res {
+                Ok(()) => connecting.push(peer),
+                Err(e) => {
+                    tracing::warn!(target: "rings::swarm", "Failed on warming up {peer}: {e:?}")
+                }
+            }
+        }
+        Ok(connecting)
+    }
+
+    /// Trace the lookup of `key` hop by hop.
+    ///
+    /// Each hop is asked for the candidate it would route the lookup to, by calling
+    /// `find_successor` on its own DHT. It's pure observation, the lookup is never forwarded
+    /// and no routing state is changed. The stream ends when the lookup is resolved, a hop
+    /// doesn't respond in [LOOKUP_PROBE_TIMEOUT_MS], or a loop is detected.
+    pub fn lookup_traced(&self, key: Did) -> impl Stream<Item = LookupStep> {
+        let transport = self.transport.clone();
+
+        stream! {
+            let mut hop = transport.dht.did;
+            let mut visited = vec![];
+
+            loop {
+                visited.push(hop);
+
+                let decision = if hop == transport.dht.did {
+                    decide(&transport.dht, key)
+                } else {
+                    probe(&transport, hop, key).await
+                };
+
+                let (candidate, resolved) = match decision {
+                    Ok(decision) => decision,
+                    Err(e) => {
+                        tracing::warn!(
+                            target: "rings::swarm",
+                            "Lookup of {} failed on hop {}: {:?}",
+                            key,
+                            hop,
+                            e
+                        );
+                        yield LookupStep {
+                            hop,
+                            candidate: None,
+                            responded: false,
+                            resolved: false,
+                        };
+                        break;
+                    }
+                };
+
+                yield LookupStep {
+                    hop,
+                    candidate: Some(candidate),
+                    responded: true,
+                    resolved,
+                };
+
+                if resolved || visited.contains(&candidate) {
+                    break;
+                }
+                hop = candidate;
+            }
+        }
+    }
+}
+
+/// Routing decision of a local DHT, the same as handling FindSuccessorSend.
+fn decide(dht: &PeerRing, key: Did) -> Result<(Did, bool)> {
+    match dht.find_successor(key)? {
+        PeerRingAction::Some(did) => Ok((did, true)),
+        PeerRingAction::RemoteAction(next, _) => Ok((next, false)),
+        act => Err(Error::PeerRingUnexpectedAction(act)),
+    }
+}
+
+fn push_unique(dids: &mut Vec<Did>, did: Did) {
+    if !dids.contains(&did) {
+        dids.push(did)
+    }
+}
+
+/// Ask `next` to find the successor of `target`, and wait for the report.
+async fn find_successor_via(transport: &SwarmTransport, target: Did, next: Did) -> Result<Did> {
+    let msg = Message::FindSuccessorSend(FindSuccessorSend {
+        did: target,
+        strict: false,
+        then: FindSuccessorThen::Report(FindSuccessorReportHandler::None),
+    });
+    let reply = transport
+        .send_message_and_wait(msg, next, FIND_SUCCESSOR_TIMEOUT_MS)
+        .await?;
+
+    match reply.transaction.data()? {
+        Message::FindSuccessorReport(report) => Ok(report.did),
+        msg => Err(Error::InvalidMessage(format!(
+            "Unexpected reply of FindSuccessorSend: {:?}",
+            msg
+        ))),
+    }
+}
+
+/// Ask a remote hop for its routing decision.
+async fn probe(transport: &SwarmTransport, hop: Did, key: Did) -> Result<(Did, bool)> {
+    let reply = transport
+        .send_message_and_wait(
+            Message::LookupProbeSend(LookupProbeSend { did: key }),
+            hop,
+            LOOKUP_PROBE_TIMEOUT_MS,
+        )
+        .await?;
+
+    match reply.transaction.data()? {
+        Message::LookupProbeReport(report)
+            if report.did == key && reply.transaction.signer() == hop =>
+        {
+            Ok((report.candidate, report.resolved))
+        }
+        msg => Err(Error::InvalidMessage(format!(
+            "Unexpected reply of LookupProbeSend: {:?}",
+            msg
+        ))),
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dht::SuccessorWriter;
+    use crate::ecc::tests::gen_ordered_keys;
+    use crate::ecc::SecretKey;
+    use crate::tests::default::gen_pure_dht;
+    use crate::tests::default::prepare_node;
+    use crate::tests::default::wait_for_msgs;
+    use crate::tests::manually_establish_connection;
+
+    #[tokio::test]
+    async fn test_lookup_traced() -> Result<()> {
+        let keys = gen_ordered_keys(5);
+        let mut nodes = vec![];
+        for key in keys {
+            nodes.push(prepare_node(key).await);
+        }
+
+        for (i, node1) in nodes.iter().enumerate() {
+            for node2 in nodes.iter().skip(i + 1) {
+                manually_establish_connection(&node1.swarm, &node2.swarm).await;
+            }
+        }
+        wait_for_msgs(nodes.iter()).await;
+
+        // Replay the decisions of each hop to get the expected path.
+        let key = nodes[4].did();
+        let mut expected = vec![];
+        let mut hop = nodes[0].did();
+        loop {
+            let node = nodes.iter().find(|n| n.did() == hop).unwrap();
+            let (candidate, resolved) = decide(&node.dht(), key)?;
+            expected.push(LookupStep {
+                hop,
+                candidate: Some(candidate),
+                responded: true,
+                resolved,
+            });
+            if resolved {
+                break;
+            }
+            hop = candidate;
+        }
+        assert!(expected.len() >= 2);
+
+        let steps: Vec<LookupStep> = nodes[0].swarm.lookup_traced(key).collect().await;
+        assert_eq!(steps, expected);
+        assert_eq!(steps.last().unwrap().candidate, Some(key));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_warm_fingers() -> Result<()> {
+        let keys = gen_ordered_keys(6);
+        let mut nodes = vec![];
+        for key in keys {
+            nodes.push(prepare_node(key).await);
+        }
+        let (ring, joining) = nodes.split_at(5);
+        let joining = &joining[0];
+
+        for (i, node1) in ring.iter().enumerate() {
+            for node2 in ring.iter().skip(i + 1) {
+                manually_establish_connection(&node1.swarm, &node2.swarm).await;
+            }
+        }
+        manually_establish_connection(&joining.swarm, &ring[0].swarm).await;
+        wait_for_msgs(nodes.iter()).await;
+
+        // A stale finger of a peer which is already gone.
+        let dead: Did = SecretKey::random().address().into();
+        joining.dht().lock_finger()?.join(dead);
+
+        let report = joining.swarm.warm_fingers().await?;
+        wait_for_msgs(nodes.iter()).await;
+        assert!(!report.fingers.contains(&dead));
+
+        for k in 0..160u32 {
+            let target = joining.did() + Did::from(BigUint::from(2u16).pow(k));
+            let succ = nodes
+                .iter()
+                .map(|n| n.did())
+                .min_by_key(|did| did.bias(target))
+                .unwrap();
+            if succ == joining.did() {
+                continue;
+            }
+            assert!(report.fingers.contains(&succ));
+            assert!(joining.swarm.transport.get_connection(succ).is_some());
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_warm_up() -> Result<()> {
+        let keys = gen_ordered_keys(5);
+        let mut nodes = vec![];
+        for key in keys {
+            nodes.push(prepare_node(key).await);
+        }
+        let (node, ring) = nodes.split_first().unwrap();
+
+        for (i, node1) in ring.iter().enumerate() {
+            for node2 in ring.iter().skip(i + 1) {
+                manually_establish_connection(&node1.swarm, &node2.swarm).await;
+            }
+        }
+        manually_establish_connection(&node.swarm, &ring[0].swarm).await;
+        wait_for_msgs(nodes.iter()).await;
+
+        // Successors learned from the successor list of ring[0].
+        node.dht()
+            .successors()
+            .extend(&[ring[1].did(), ring[2].did()])?;
+
+        let mut neighbours = node.dht().successors().list()?;
+        neighbours.extend(*node.dht().lock_predecessor()?);
+        assert!(neighbours.contains(&ring[1].did()));
+        assert!(neighbours.contains(&ring[2].did()));
+        let connected = node.swarm.transport.get_connection_ids();
+
+        let connecting = node.swarm.warm_up().await?;
+        wait_for_msgs(nodes.iter()).await;
+
+        for peer in neighbours {
+            assert!(node.swarm.transport.get_connection(peer).is_some());
+            assert_eq!(connecting.contains(&peer), !connected.contains(&peer));
+        }
+
+        // All neighbours are connected or being connected already.
+        assert!(node.swarm.warm_up().await?.is_empty());
+
+        Ok(())
+    }
+
+    /// Finger targets that `dht` looks up remotely, with the next hops of them.
+    fn remote_targets(dht: &PeerRing) -> Result<Vec<(Did, Did)>> {
+        let mut targets = vec![];
+        for k in 0..160u32 {
+            let target = dht.did + Did::from(BigUint::from(2u16).pow(k));
+            if let PeerRingAction::RemoteAction(next, _) = dht.find_successor(target)? {
+                targets.push((target, next));
+            }
+        }
+        Ok(targets)
+    }
+
+    #[tokio::test]
+    async fn test_warm_fingers_faster_than_sequential() -> Result<()> {
+        let mut ring = vec![];
+        for key in gen_ordered_keys(5) {
+            ring.push(prepare_node(key).await);
+        }
+        for (i, node1) in ring.iter().enumerate() {
+            for node2 in ring.iter().skip(i + 1) {
+                manually_establish_connection(&node1.swarm, &node2.swarm).await;
+            }
+        }
+
+        // Join close to a successor, so that many finger targets are looked up remotely.
+        let (key, successor) = loop {
+            let key = SecretKey::random();
+            let did: Did = key.address().into();
+            let successor = ring.iter().min_by_key(|n| n.did().bias(did)).unwrap();
+            let dht = gen_pure_dht(did);
+            dht.join(successor.did())?;
+            if remote_targets(&dht)?.len() >= 12 {
+                break (key, successor);
+            }
+        };
+        let joining = prepare_node(key).await;
+        manually_establish_connection(&joining.swarm, &successor.swarm).await;
+        wait_for_msgs(ring.iter().chain([&joining])).await;
+
+        let targets = remote_targets(&joining.dht())?;
+        assert!(targets.len() >= 12);
+
+        // Fixing fingers one by one waits for each lookup in turn.
+        let start = std::time::Instant::now();
+        for (target, next) in targets {
+            find_successor_via(&joining.swarm.transport, target, next).await?;
+        }
+        let sequential = start.elapsed();
+
+        let start = std::time::Instant::now();
+        let report = joining.swarm.warm_fingers().await?;
+        let warm = start.elapsed();
+
+        assert_eq!(report.failed, 0);
+        assert!(
+            warm < sequential,
+            "warm_fingers took {warm:?}, sequential lookups took {sequential:?}"
+        );
+
+        Ok(())
+    }
+}
@@ -5,26 +5,95 @@
 mod builder;
 /// Callback interface for swarm
 pub mod callback;
+mod config;
+mod connect_metrics;
+mod decode_failures;
+mod dedup;
+pub mod file;
+mod inbox;
+mod lookup;
+pub mod nat;
+mod outbound;
+mod rate_limit;
+mod reassembly;
+mod reconnect;
+#[cfg(feature = "record")]
+pub mod record;
+mod relay_metrics;
+mod sample;
 pub(crate) mod transport;
+mod transport_kind;
 
 use std::sync::Arc;
-use std::sync::RwLock;
+use std::time::Duration;
 
 pub use builder::SwarmBuilder;
+pub use config::SwarmConfig;
+pub use connect_metrics::ConnectMetricsSnapshot;
+pub use connect_metrics::CONNECT_LATENCY_METRIC;
+pub use decode_failures::DecodeFailuresSnapshot;
+pub use decode_failures::DECODE_FAILURES_METRIC;
+pub use file::FileReceiver;
+pub use file::TransferHandle;
+pub use file::TransferProgress;
+use futures::StreamExt;
+pub use inbox::BoundedMessages;
+pub use inbox::OverflowMode;
+pub use inbox::SwarmEvents;
+pub use lookup::LookupStep;
+pub use lookup::WarmFingersReport;
+pub use nat::NatType;
+pub use rate_limit::RateLimit;
+pub use reassembly::ReassemblyBudget;
+pub use reassembly::ReassemblySnapshot;
+pub use reassembly::REASSEMBLY_ACTIVE_TRANSFERS_METRIC;
+pub use reassembly::REASSEMBLY_BUFFERED_BYTES_METRIC;
+pub use reconnect::reconnect_successors;
+pub use reconnect::ReconnectFn;
+pub use reconnect::ReconnectInput;
+pub use reconnect::ReconnectPolicy;
+pub use relay_metrics::Histogram;
+pub use relay_metrics::RelayMetricsSnapshot;
+pub use relay_metrics::RELAY_HOPS_METRIC;
+pub use relay_metrics::RELAY_LATENCY_METRIC;
+pub use rings_transport::core::transport::ConnectionStats;
+use rings_transport::core::transport::WebrtcConnectionState;
+pub use rings_transport::rtc_config::BundlePolicy;
+pub use rings_transport::rtc_config::IceTransportPolicy;
+pub use rings_transport::rtc_config::RtcConfig;
+pub use sample::SampleBias;
+pub use transport::Reachability;
+pub use transport::Route;
+pub use transport::SendBufferPolicy;
+pub use transport::VerificationPolicy;
+pub use transport_kind::AnyConnection;
+pub use transport_kind::SharedConnection;
+pub use transport_kind::SharedTransportFactory;
+pub use transport_kind::TransportFactory;
+pub use transport_kind::TransportKind;
 
 use self::callback::InnerSwarmCallback;
+use self::callback::SwarmEvent;
+use crate::consts::BROADCAST_CONCURRENCY;
+use crate::consts::CONNECT_WAIT_TIMEOUT_MS;
 use crate::dht::Did;
 use crate::dht::PeerRing;
 use crate::dht::Stabilizer;
+use crate::dht::SuccessorReader;
+use crate::ecc::PublicKey;
 use crate::error::Error;
 use crate::error::Result;
 use crate::inspect::ConnectionInspect;
 use crate::inspect::SwarmInspect;
+use crate::measure::MeasureSnapshot;
 use crate::message::Message;
 use crate::message::MessagePayload;
 use crate::message::MessageVerificationExt;
 use crate::message::PayloadSender;
+use crate::message::Priority;
+use crate::session::SessionSk;
 use crate::swarm::callback::SharedSwarmCallback;
+use crate::swarm::callback::SwappableCallback;
 use crate::swarm::transport::SwarmTransport;
 
 /// The transport and dht management.
@@ -33,7 +102,7 @@ pub struct Swarm {
     pub(crate) dht: Arc<PeerRing>,
     /// Swarm tansport.
     pub(crate) transport: Arc<SwarmTransport>,
-    callback: RwLock<SharedSwarmCallback>,
+    callback: SwappableCallback,
 }
 
 impl Swarm {
@@ -47,15 +116,13 @@ impl Swarm {
         self.dht.clone()
     }
 
-    fn callback(&self) -> Result<SharedSwarmCallback> {
-        Ok(self
-            .callback
-            .read()
-            .map_err(|_| Error::CallbackSyncLockError)?
-            .clone())
+    /// Get the callback dispatching to the current callback set by [Swarm::set_callback], then
+    /// to inboxes of [Swarm::iter_messages_bounded] and [Swarm::iter_events].
+    pub(crate) fn callback(&self) -> Result<SharedSwarmCallback> {
+        Ok(Arc::new(self.callback.clone()))
     }
 
-    fn inner_callback(&self) -> Result<InnerSwarmCallback> {
+    pub(crate) fn inner_callback(&self) -> Result<InnerSwarmCallback> {
         Ok(InnerSwarmCallback::new(
             self.transport.clone(),
             self.callback()?,
@@ -63,20 +130,17 @@ impl Swarm {
     }
 
     /// Set callback for swarm.
+    /// The callback is swapped atomically for all connections, including established ones.
+    /// Messages and events being handled by the old callback finish on it, and the following
+    /// ones go to the new callback.
     pub fn set_callback(&self, callback: SharedSwarmCallback) -> Result<()> {
-        let mut inner = self
-            .callback
-            .write()
-            .map_err(|_| Error::CallbackSyncLockError)?;
-
-        *inner = callback;
-
+        self.callback.store(callback);
         Ok(())
     }
 
     /// Create [Stabilizer] for swarm.
     pub fn stabilizer(&self) -> Stabilizer {
-        Stabilizer::new(self.transport.clone())
+        Stabilizer::new(self.transport.clone()).with_callback(Some(Arc::new(self.callback.clone())))
     }
 
     /// Disconnect a connection. There are three steps:
@@ -87,6 +151,12 @@ impl Swarm {
         self.transport.disconnect(peer).await
     }
 
+    /// Sign payloads sent to the connection of peer by a rotated session, without reconnecting.
+    /// Payloads already in flight keep the signatures of the old session.
+    pub fn rekey_connection(&self, peer: Did, session_sk: SessionSk) -> Result<()> {
+        self.transport.rekey_connection(peer, session_sk)
+    }
+
     /// Connect a given Did. If the did is already connected, return directly,
     /// else try prepare offer and establish connection by dht.
     /// This function may returns a pending connection or connected connection.
@@ -94,14 +164,389 @@ impl Swarm {
         if peer == self.did() {
             return Err(Error::ShouldNotConnectSelf);
         }
-        self.transport.connect(peer, self.inner_callback()?).await
+        self.transport
+            .connect(peer, self.inner_callback()?, None)
+            .await
+    }
+
+    /// Connect a given Did like [Swarm::connect], naming the connection by `label`.
+    /// The label is kept until the connection is closed, and listed by [Swarm::peers].
+    pub async fn connect_with_label(&self, peer: Did, label: impl Into<String>) -> Result<()> {
+        if peer == self.did() {
+            return Err(Error::ShouldNotConnectSelf);
+        }
+        self.transport
+            .connect(peer, self.inner_callback()?, Some(label.into()))
+            .await
+    }
+
+    /// Pin a peer, so that its connection is never closed for idleness, and it's reconnected
+    /// in stabilization whenever it's not connected. The peer doesn't need to be connected yet.
+    pub fn pin(&self, peer: Did) -> Result<()> {
+        if peer == self.did() {
+            return Err(Error::ShouldNotConnectSelf);
+        }
+        self.transport.pin(peer);
+        Ok(())
+    }
+
+    /// Unpin a peer pinned by [Swarm::pin]. Its connection is kept, but may be closed for
+    /// idleness again. Return false if it's not pinned.
+    pub fn unpin(&self, peer: Did) -> bool {
+        self.transport.unpin(peer)
+    }
+
+    /// List peers pinned by [Swarm::pin].
+    pub fn pinned_peers(&self) -> Vec<Did> {
+        self.transport.pinned_peers()
+    }
+
+    /// List peers whose connection is closed and waiting to be reconnected, see
+    /// [SwarmBuilder::reconnect_policy].
+    pub fn reconnecting_peers(&self) -> Vec<Did> {
+        self.transport
+            .reconnector
+            .as_ref()
+            .map(|r| r.pending())
+            .unwrap_or_default()
+    }
+
+    /// Connect a given Did like [Swarm::connect], and wait until the data channel is open.
+    /// Return [Error::WaitConnectionTimeout] if it's not open in `timeout_ms`.
+    pub async fn connect_and_wait(&self, peer: Did, timeout_ms: u64) -> Result<()> {
+        if peer == self.did() {
+            return Err(Error::ShouldNotConnectSelf);
+        }
+        self.transport
+            .connect_and_wait(peer, self.inner_callback()?, timeout_ms)
+            .await
+    }
+
+    /// Restart ICE of the connection to a peer, such as after it failed on network changes.
+    /// A new offer is sent by DHT like [Swarm::connect] and answered by the existing connection
+    /// of peer, so the connection is kept. Wait until it's connected again, or return
+    /// [Error::WaitConnectionTimeout] after [CONNECT_WAIT_TIMEOUT_MS]. Peer joins DHT again
+    /// once it's connected, since the failed connection left it.
+    /// A [SwarmEvent::IceRestart] is emitted either way.
+    pub async fn restart_ice(&self, peer: Did) -> Result<()> {
+        let result = self.do_restart_ice(peer).await;
+        let event = SwarmEvent::IceRestart {
+            peer,
+            succeeded: result.is_ok(),
+        };
+        if let Err(e) = self.callback()?.on_event(&event).await {
+            tracing::error!(target: "rings::swarm", "Failed on handle event {event:?}: {e:?}");
+        }
+        result
+    }
+
+    async fn do_restart_ice(&self, peer: Did) -> Result<()> {
+        let offer_msg = self.transport.prepare_ice_restart_offer(peer).await?;
+        self.transport
+            .send_message(Message::ConnectNodeSend(offer_msg), peer)
+            .await?;
+        self.transport
+            .wait_connected(peer, CONNECT_WAIT_TIMEOUT_MS)
+            .await
+    }
+
+    /// Connect each peer of `entries` by its transport from now on, like
+    /// [SwarmBuilder::transport_factory] but at runtime. The table of transports is locked
+    /// once for all entries, which avoids contention when registering many peers, such as on
+    /// bootstrap. A connection of peer created by the transport used before is closed.
+    /// A [SwarmEvent::TransportRegistered] is emitted for each entry.
+    pub async fn register_many(&self, entries: Vec<(Did, SharedTransportFactory)>) -> Result<()> {
+        if entries.iter().any(|(peer, _)| *peer == self.did()) {
+            return Err(Error::ShouldNotConnectSelf);
+        }
+        let callback = self.callback()?;
+        for (peer, replaced) in self.transport.register_transports(entries).await {
+            let event = SwarmEvent::TransportRegistered { peer, replaced };
+            if let Err(e) = callback.on_event(&event).await {
+                tracing::error!(target: "rings::swarm", "Failed on handle event {event:?}: {e:?}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Ask peer for a fresh handshake when the handshake of its connection failed before the
+    /// data channel opens, such as when the answer is produced but never accepted.
+    /// The failed connection is closed, then peer sends a new offer by DHT.
+    /// It's done automatically by the side with the smaller Did when such a connection turns
+    /// failed. Return [Error::HandshakeRenegotiationExhausted] after
+    /// [MAX_HANDSHAKE_RENEGOTIATIONS] tries.
+    ///
+    /// [MAX_HANDSHAKE_RENEGOTIATIONS]: crate::consts::MAX_HANDSHAKE_RENEGOTIATIONS
+    pub async fn renegotiate(&self, peer: Did) -> Result<()> {
+        self.transport.request_renegotiation(peer).await
+    }
+
+    /// Relay messages to `peer` through `via` when the direct connection is gone, such as
+    /// when it degrades and gets closed. The relay header carries no source route, so `via`
+    /// is a single connected hop, and a `via` of more hops fails with [Error::MultiHopRoute].
+    /// Messages are sent directly again once `peer` is connected.
+    /// Remove the route if `via` is empty.
+    pub fn reroute(&self, peer: Did, via: Vec<Did>) -> Result<()> {
+        self.transport.reroute(peer, via)
+    }
+
+    /// Get the route of messages sent to `peer` now, see [Swarm::reroute].
+    pub fn route(&self, peer: Did) -> Result<Route> {
+        self.transport.route(peer)
+    }
+
+    /// Check whether `peer` can be reached now, by its connection, by a route set by
+    /// [Swarm::reroute], or by DHT. It doesn't connect or send anything, so a peer that is
+    /// not known by DHT yet is [Reachability::Unknown], even if it's reachable by lookup.
+    pub fn is_reachable(&self, peer: Did) -> Reachability {
+        self.transport.reachability(peer)
     }
 
     /// Send [Message] to peer.
+    /// A message sent to this node itself is handled locally, see [Swarm::send_to_self].
     pub async fn send_message(&self, msg: Message, destination: Did) -> Result<uuid::Uuid> {
+        if destination == self.did() {
+            return self.send_to_self(msg).await;
+        }
         self.transport.send_message(msg, destination).await
     }
 
+    /// Deliver [Message] to the handler of this node without any transport, as if it's
+    /// received from a peer, so that apps can address all nodes by [Did] uniformly.
+    async fn send_to_self(&self, msg: Message) -> Result<uuid::Uuid> {
+        let did = self.did();
+        let payload = MessagePayload::new_send(msg, self.transport.session_sk(), did, did)?;
+        self.deliver_to_self(payload).await
+    }
+
+    /// Handle a payload sent to this node itself, failing if the handler or the callback
+    /// rejects it, since there is no peer to report the error to.
+    pub(crate) async fn deliver_to_self(&self, payload: MessagePayload) -> Result<uuid::Uuid> {
+        let tx_id = payload.transaction.tx_id;
+        let callback = self.inner_callback()?;
+        callback
+            .on_payload(&self.did().to_string(), payload)
+            .await
+            .map_err(|e| match e.downcast::<Error>() {
+                Ok(e) => *e,
+                Err(e) => Error::SwarmHandleMessage(e.to_string()),
+            })?;
+        Ok(tx_id)
+    }
+
+    /// Send [Message] to `destination` through an explicit first hop `via`, which relays it
+    /// to `destination` like any other message. It's useful when the destination differs
+    /// from the peer that should get the message first, such as anycast.
+    /// The first hop is inferred like [Swarm::send_message] if `via` is None.
+    pub async fn send_message_to(
+        &self,
+        msg: Message,
+        destination: Did,
+        via: Option<Did>,
+    ) -> Result<uuid::Uuid> {
+        let next_hop = match via {
+            Some(via) if via == self.did() => return Err(Error::InvalidNextHop),
+            Some(via) => via,
+            None if destination == self.did() => return self.send_to_self(msg).await,
+            None => self.transport.infer_next_hop(destination, None)?,
+        };
+        self.transport
+            .send_message_by_hop(msg, destination, next_hop)
+            .await
+    }
+
+    /// Send [Message] to peer with specified priority, instead of the default one
+    /// of [Message::priority].
+    pub async fn send_message_with_priority(
+        &self,
+        msg: Message,
+        destination: Did,
+        priority: Priority,
+    ) -> Result<uuid::Uuid> {
+        if destination == self.did() {
+            return self.send_to_self(msg).await;
+        }
+        self.transport
+            .send_message_with_priority(msg, destination, priority)
+            .await
+    }
+
+    /// Send [Message] to peer, encrypted to the public key of its session so that relays
+    /// cannot read it. See [MessagePayload::new_send_encrypted].
+    pub async fn send_encrypted_message(
+        &self,
+        msg: Message,
+        destination: Did,
+        destination_pubkey: PublicKey<33>,
+    ) -> Result<uuid::Uuid> {
+        if destination == self.did() {
+            let did = self.did();
+            let payload = MessagePayload::new_send_encrypted(
+                msg,
+                self.transport.session_sk(),
+                did,
+                did,
+                destination_pubkey,
+            )?;
+            return self.deliver_to_self(payload).await;
+        }
+        self.transport
+            .send_encrypted_message(msg, destination, destination_pubkey)
+            .await
+    }
+
+    /// Send [Message] to each of `dids` concurrently, at most [BROADCAST_CONCURRENCY] at the
+    /// same time. Each Did gets its own payload routed by its own next hop, as sent by
+    /// [Swarm::send_message], so a failure of one Did doesn't affect the others.
+    /// Return the result of each Did, in the order of `dids`.
+    pub async fn broadcast(&self, msg: Message, dids: &[Did]) -> Vec<(Did, Result<()>)> {
+        futures::stream::iter(dids.iter().copied())
+            .map(|did| {
+                let msg = msg.clone();
+                async move { (did, self.send_message(msg, did).await.map(|_| ())) }
+            })
+            .buffered(BROADCAST_CONCURRENCY)
+            .collect()
+            .await
+    }
+
+    /// Get public key of the session of this node, which others use to send messages
+    /// encrypted to this node by [Swarm::send_encrypted_message].
+    pub fn session_pubkey(&self) -> PublicKey<33> {
+        self.transport.session_sk().pubkey()
+    }
+
+    /// Get the lifetime left of the session of this node by the clock of swarm, which is zero
+    /// once it's expired. The session can be rotated before it expires, see
+    /// [Swarm::rekey_connection].
+    pub fn session_ttl_remaining(&self) -> Duration {
+        self.transport
+            .session_sk()
+            .session()
+            .remaining_ttl_by(self.transport.clock.as_ref())
+    }
+
+    /// NAT type of this node detected by [Swarm::detect_nat], [NatType::Unknown] before that.
+    pub fn nat_type(&self) -> NatType {
+        *self
+            .transport
+            .nat_type
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Detect NAT type of this node by the STUN servers in ice servers, see [nat], if it's
+    /// enabled by [SwarmBuilder::detect_nat]. It's called once the node starts listening.
+    /// NAT type is never detected in browser, which keeps [NatType::Unknown].
+    pub async fn detect_nat(&self) -> NatType {
+        if !self.transport.detect_nat {
+            return self.nat_type();
+        }
+        #[cfg(not(feature = "wasm"))]
+        {
+            let nat_type = nat::detect(&self.transport.ice_servers).await;
+            *self
+                .transport
+                .nat_type
+                .write()
+                .unwrap_or_else(|e| e.into_inner()) = nat_type;
+        }
+        self.nat_type()
+    }
+
+    /// Score the connection quality of a peer in 0.0..=1.0, higher is better.
+    /// The score is computed by the [crate::measure::QualityFn] set in [SwarmBuilder::quality_fn],
+    /// or [crate::measure::default_quality] if not set.
+    /// Return None if the peer is not connected.
+    pub async fn connection_quality(&self, peer: Did) -> Option<f64> {
+        self.transport.connection_quality(peer).await
+    }
+
+    /// Get statistics of the underlying transport of the connection to a peer,
+    /// such as bytes sent, bytes received and round trip time.
+    /// Return None if the peer is not connected.
+    pub async fn connection_stats(&self, peer: Did) -> Option<ConnectionStats> {
+        self.transport.connection_stats(peer).await
+    }
+
+    /// Get all counters of a peer in measure, such as messages and bytes sent and received.
+    /// Return None if measure is not set by [SwarmBuilder::measure].
+    pub async fn measure_snapshot(&self, peer: Did) -> Result<Option<MeasureSnapshot>> {
+        self.transport.measure_snapshot(peer).await
+    }
+
+    /// Get histograms of hops and latency of messages sent by this node and reported back,
+    /// named [RELAY_HOPS_METRIC] and [RELAY_LATENCY_METRIC].
+    pub fn relay_metrics(&self) -> RelayMetricsSnapshot {
+        self.transport.relay_metrics.snapshot()
+    }
+
+    /// Get histograms of time taken by connection attempts of this node, successful or not,
+    /// named [CONNECT_LATENCY_METRIC].
+    pub fn connect_metrics(&self) -> ConnectMetricsSnapshot {
+        self.transport.connect_metrics.snapshot()
+    }
+
+    /// Get the average time taken by successful connection attempts, from creating the
+    /// connection to opening its data channel. Return None if no attempt succeeded yet.
+    pub fn avg_connect_latency(&self) -> Option<Duration> {
+        self.connect_metrics().avg_succeeded()
+    }
+
+    /// Get counters of frames received from peers which cannot be decoded, named
+    /// [DECODE_FAILURES_METRIC]. See [crate::consts::DECODE_FAILURES_THRESHOLD].
+    pub fn decode_failures(&self) -> DecodeFailuresSnapshot {
+        self.transport.decode_failures.snapshot()
+    }
+
+    /// Get gauges of chunked messages being reassembled, named
+    /// [REASSEMBLY_ACTIVE_TRANSFERS_METRIC] and [REASSEMBLY_BUFFERED_BYTES_METRIC].
+    /// See [SwarmBuilder::reassembly_budget].
+    pub fn reassembly_metrics(&self) -> ReassemblySnapshot {
+        self.transport.reassembly.snapshot()
+    }
+
+    /// Get capabilities supported by both this node and a connected peer, which are
+    /// negotiated in handshake. See [SwarmBuilder::capabilities].
+    /// Return None if the peer is not connected.
+    pub fn peer_capabilities(&self, peer: Did) -> Option<Vec<String>> {
+        self.transport.peer_capabilities(peer)
+    }
+
+    /// Number of messages from an origin sender dropped by [SwarmBuilder::rate_limit].
+    pub fn rate_limited(&self, sender: Did) -> u64 {
+        self.transport
+            .rate_limiter
+            .as_ref()
+            .map(|l| l.dropped(sender))
+            .unwrap_or_default()
+    }
+
+    /// Max size of messages sent or received, see [SwarmBuilder::max_message_size].
+    pub fn max_message_size(&self) -> usize {
+        self.transport.max_message_size
+    }
+
+    /// Number of handshakes in progress right now, from creating their connection until their
+    /// data channel opens or fails, which is at most [SwarmBuilder::max_concurrent_connects]
+    /// if it's set.
+    pub fn connects_in_progress(&self) -> usize {
+        self.transport.connects_in_progress()
+    }
+
+    /// List peers whose connections are still being established after `age`,
+    /// such as offers never answered.
+    pub fn pending_connections_older_than(&self, age: Duration) -> Vec<Did> {
+        self.transport.pending_connections_older_than(age)
+    }
+
+    /// Close connections that are still being established after `max_age`.
+    /// Return the peers of closed connections.
+    /// It's also called by [Stabilizer] with [crate::consts::PENDING_CONNECTION_MAX_AGE_MS].
+    pub async fn gc_pending_connections(&self, max_age: Duration) -> Result<Vec<Did>> {
+        self.transport.gc_pending_connections(max_age).await
+    }
+
     /// List peers and their connection status.
     pub fn peers(&self) -> Vec<ConnectionInspect> {
         self.transport
@@ -110,10 +555,82 @@ impl Swarm {
             .map(|(did, c)| ConnectionInspect {
                 did: did.to_string(),
                 state: format!("{:?}", c.webrtc_connection_state()),
+                label: c.label().map(|label| label.to_string()),
             })
             .collect()
     }
 
+    /// List peers whose connections are connected.
+    pub fn connected_peers(&self) -> Vec<Did> {
+        self.transport
+            .get_connections()
+            .into_iter()
+            .filter(|(_, c)| c.webrtc_connection_state() == WebrtcConnectionState::Connected)
+            .map(|(did, _)| did)
+            .collect()
+    }
+
+    /// List all Dids known by this node, from both the connections in transport and the
+    /// finger table, successors and predecessor of DHT. The list has no duplicates and
+    /// doesn't contain the Did of this node.
+    /// It's used to take a snapshot for bootstrapping other nodes, see [Swarm::bootstrap_from].
+    pub fn known_dids(&self) -> Result<Vec<Did>> {
+        let did = self.did();
+        let mut dids = self.transport.get_connection_ids();
+        dids.extend(self.dht.lock_finger()?.list().iter().flatten());
+        dids.extend(self.dht.successors().list()?);
+        dids.extend(*self.dht.lock_predecessor()?);
+
+        let mut known = vec![];
+        for d in dids {
+            if d != did && !known.contains(&d) {
+                known.push(d);
+            }
+        }
+        Ok(known)
+    }
+
+    /// Pick at most `n` distinct peers randomly from [Swarm::known_dids], such as for gossip.
+    /// Peers are picked uniformly, or biased by [Swarm::connection_quality], see [SampleBias].
+    pub async fn sample_peers(&self, n: usize, bias: SampleBias) -> Result<Vec<Did>> {
+        let mut candidates = vec![];
+        for did in self.known_dids()? {
+            let quality = match bias {
+                SampleBias::Uniform => None,
+                SampleBias::QualityWeighted => self.connection_quality(did).await,
+            };
+            candidates.push((did, quality));
+        }
+        Ok(sample::sample(
+            &mut rand::thread_rng(),
+            &candidates,
+            n,
+            bias,
+        ))
+    }
+
+    /// Try to connect each of the Dids, such as those exported by [Swarm::known_dids] of
+    /// another node. A failed connect is logged and doesn't block the others.
+    /// Return the Dids that are connected or being connected.
+    pub async fn bootstrap_from(&self, dids: Vec<Did>) -> Vec<Did> {
+        let mut connecting = vec![];
+        for peer in dids {
+            if peer == self.did() || connecting.contains(&peer) {
+                continue;
+            }
+            match self.connect(peer).await {
+                Ok(()) => connecting.push(peer),
+                Err(e) => {
+                    tracing::warn!(
+                        target: "rings::swarm",
+                        "Failed on bootstrapping from {peer}: {e:?}"
+                    )
+                }
+            }
+        }
+        connecting
+    }
+
     /// Check the status of swarm
     pub async fn inspect(&self) -> SwarmInspect {
         SwarmInspect::inspect(self).await
@@ -126,7 +643,7 @@ impl Swarm {
     pub async fn create_offer(&self, peer: Did) -> Result<MessagePayload> {
         let offer_msg = self
             .transport
-            .prepare_connection_offer(peer, self.inner_callback()?)
+            .prepare_connection_offer(peer, self.inner_callback()?, None)
             .await?;
 
         // This payload has fake next_hop.
@@ -141,10 +658,21 @@ impl Swarm {
         Ok(payload)
     }
 
-    /// Answer the offer of remote connection. This function will verify the answer payload and
-    /// will wrap the answer inside a payload with verification.
+    /// Answer the offer of remote connection. This function will verify the offer payload by
+    /// [SwarmBuilder::verification_policy] and will wrap the answer inside a payload with
+    /// verification.
     pub async fn answer_offer(&self, offer_payload: MessagePayload) -> Result<MessagePayload> {
-        if !offer_payload.verify() {
+        self.transport
+            .with_acceptance_delay(self.do_answer_offer(offer_payload))
+            .await
+    }
+
+    async fn do_answer_offer(&self, offer_payload: MessagePayload) -> Result<MessagePayload> {
+        if !self
+            .transport
+            .verification_policy
+            .verify(&offer_payload, self.transport.clock.as_ref())
+        {
             return Err(Error::VerifySignatureFailed);
         }
 
@@ -172,10 +700,14 @@ impl Swarm {
         Ok(answer_payload)
     }
 
-    /// Accept the answer of remote connection. This function will verify the answer payload and
-    /// will return its did with the connection.
+    /// Accept the answer of remote connection. This function will verify the answer payload by
+    /// [SwarmBuilder::verification_policy] and will return its did with the connection.
     pub async fn accept_answer(&self, answer_payload: MessagePayload) -> Result<()> {
-        if !answer_payload.verify() {
+        if !self
+            .transport
+            .verification_policy
+            .verify(&answer_payload, self.transport.clock.as_ref())
+        {
             return Err(Error::VerifySignatureFailed);
         }
 
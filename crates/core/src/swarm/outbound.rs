@@ -0,0 +1,206 @@
+//! Outbound queue of a connection, ordered by [Priority].
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::atomic;
+use std::sync::atomic::AtomicU64;
+use std::sync::Mutex;
+
+use bytes::Bytes;
+use futures::channel::oneshot;
+
+use crate::error::Error;
+use crate::error::Result;
+use crate::message::Priority;
+
+struct Outbound {
+    priority: Priority,
+    seq: u64,
+    frames: VecDeque<Bytes>,
+    result: oneshot::Sender<Result<()>>,
+}
+
+impl PartialEq for Outbound {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority && self.seq == other.seq
+    }
+}
+
+impl Eq for Outbound {}
+
+impl PartialOrd for Outbound {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Outbound {
+    /// The max of heap is the most urgent one, then the earliest one.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .priority
+            .cmp(&self.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+/// Frames waiting to be sent to a connection.
+/// Only one sender drains the queue at a time, it always picks the frame of the highest
+/// [Priority], so that control messages don't wait behind bulk transfers.
+/// A message sent in chunks is pushed as the frames of its chunks. Frames of higher priority
+/// are sent between its chunks, while frames of the same priority wait until all are sent.
+#[derive(Default)]
+pub(crate) struct OutboundQueue {
+    queue: Mutex<BinaryHeap<Outbound>>,
+    sending: futures::lock::Mutex<()>,
+    seq: AtomicU64,
+}
+
+impl OutboundQueue {
+    /// Put frames of a message into queue. The receiver resolves when all of them are sent,
+    /// or one of them fails.
+    pub fn push(&self, priority: Priority, frames: Vec<Bytes>) -> oneshot::Receiver<Result<()>> {
+        let (tx, rx) = oneshot::channel();
+        let seq = self.seq.fetch_add(1, atomic::Ordering::SeqCst);
+        self.queue.lock().unwrap().push(Outbound {
+            priority,
+            seq,
+            frames: frames.into(),
+            result: tx,
+        });
+        rx
+    }
+
+    fn pop(&self) -> Option<Outbound> {
+        self.queue.lock().unwrap().pop()
+    }
+
+    /// Send frames by `send` until the queue is empty.
+    /// Wait if another caller is draining the queue.
+    pub async fn drain<F, Fut>(&self, send: F)
+    where
+        F: Fn(Bytes) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let _sending = self.sending.lock().await;
+        while let Some(mut outbound) = self.pop() {
+            let result = match outbound.frames.pop_front() {
+                Some(frame) => send(frame).await,
+                None => Ok(()),
+            };
+            if result.is_ok() && !outbound.frames.is_empty() {
+                // Put the rest back, frames of higher priority pushed meanwhile go first.
+                self.queue.lock().unwrap().push(outbound);
+                continue;
+            }
+            // The caller may be gone, nobody cares about the result then.
+            let _ = outbound.result.send(result);
+        }
+    }
+
+    /// Put frames of a message into queue, then drain the queue and wait for them to be sent.
+    pub async fn send<F, Fut>(&self, priority: Priority, frames: Vec<Bytes>, send: F) -> Result<()>
+    where
+        F: Fn(Bytes) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let rx = self.push(priority, frames);
+        self.drain(send).await;
+        rx.await.map_err(|_| Error::OutboundQueueDropped)?
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_control_sent_before_bulk() {
+        let queue = OutboundQueue::default();
+        let sent = Mutex::new(vec![]);
+
+        let bulk = queue.push(Priority::Bulk, vec![Bytes::from_static(b"bulk")]);
+        let normal = queue.push(Priority::Normal, vec![Bytes::from_static(b"normal1")]);
+        let normal2 = queue.push(Priority::Normal, vec![Bytes::from_static(b"normal2")]);
+        let control = queue.push(Priority::Control, vec![Bytes::from_static(b"control")]);
+
+        queue
+            .drain(|data| {
+                sent.lock().unwrap().push(data);
+                async { Ok(()) }
+            })
+            .await;
+
+        assert_eq!(sent.into_inner().unwrap(), vec![
+            Bytes::from_static(b"control"),
+            Bytes::from_static(b"normal1"),
+            Bytes::from_static(b"normal2"),
+            Bytes::from_static(b"bulk"),
+        ]);
+        for rx in [bulk, normal, normal2, control] {
+            assert!(rx.await.unwrap().is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_control_sent_between_chunks() {
+        let queue = OutboundQueue::default();
+        let sent = Mutex::new(vec![]);
+        let chunks = || {
+            vec![
+                Bytes::from_static(b"chunk1"),
+                Bytes::from_static(b"chunk2"),
+                Bytes::from_static(b"chunk3"),
+            ]
+        };
+
+        let chunked = queue.push(Priority::Bulk, chunks());
+        let bulk = queue.push(Priority::Bulk, vec![Bytes::from_static(b"bulk")]);
+        let control = Mutex::new(None);
+
+        queue
+            .drain(|data| {
+                // A control message comes while the first chunk is being sent.
+                control.lock().unwrap().get_or_insert_with(|| {
+                    queue.push(Priority::Control, vec![Bytes::from_static(b"control")])
+                });
+                sent.lock().unwrap().push(data);
+                async { Ok(()) }
+            })
+            .await;
+
+        assert_eq!(sent.into_inner().unwrap(), vec![
+            Bytes::from_static(b"chunk1"),
+            Bytes::from_static(b"control"),
+            Bytes::from_static(b"chunk2"),
+            Bytes::from_static(b"chunk3"),
+            Bytes::from_static(b"bulk"),
+        ]);
+        for rx in [chunked, bulk, control.into_inner().unwrap().unwrap()] {
+            assert!(rx.await.unwrap().is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_chunk_fails_message() {
+        let queue = OutboundQueue::default();
+        let sent = Mutex::new(0);
+
+        let chunked = queue.push(Priority::Bulk, vec![
+            Bytes::from_static(b"chunk1"),
+            Bytes::from_static(b"chunk2"),
+        ]);
+        queue
+            .drain(|_| {
+                *sent.lock().unwrap() += 1;
+                async { Err(Error::OutboundQueueDropped) }
+            })
+            .await;
+
+        assert!(chunked.await.unwrap().is_err());
+        assert_eq!(sent.into_inner().unwrap(), 1);
+    }
+}
@@ -0,0 +1,210 @@
+//! Per-sender rate limiting of inbound messages.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::consts::RATE_LIMIT_MAX_TRACKED;
+use crate::dht::Did;
+use crate::utils::get_epoch_ms;
+
+/// Config of the token bucket limiting inbound messages from each origin sender.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimit {
+    /// Number of messages refilled to the bucket per second.
+    pub messages_per_sec: u32,
+    /// Max number of messages that can be received at once.
+    pub burst: u32,
+    /// Disconnect a directly connected sender once this many messages of it are dropped
+    /// in a row. Never disconnect if it's None.
+    pub disconnect_after: Option<u64>,
+}
+
+/// Decision of [RateLimiter] on an inbound message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RateDecision {
+    /// The message is accepted.
+    Accept,
+    /// The message is dropped.
+    Drop,
+    /// The message is dropped, and the sender exceeded [RateLimit::disconnect_after].
+    Abuse,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated_ms: u128,
+    /// Messages dropped in a row.
+    dropped: u64,
+}
+
+impl Bucket {
+    fn refill(&mut self, config: &RateLimit, now: u128) {
+        let elapsed = now.saturating_sub(self.updated_ms) as f64 / 1000.0;
+        self.tokens =
+            (self.tokens + elapsed * config.messages_per_sec as f64).min(config.burst as f64);
+        self.updated_ms = now;
+    }
+
+    fn is_full(&self, config: &RateLimit) -> bool {
+        self.tokens >= config.burst as f64
+    }
+}
+
+/// Token buckets of origin senders.
+/// At most [RATE_LIMIT_MAX_TRACKED] senders are tracked. When it's full, buckets that are
+/// refilled to burst are evicted first since they are the same as new ones, then the least
+/// recently updated one.
+pub(crate) struct RateLimiter {
+    config: RateLimit,
+    buckets: Mutex<HashMap<Did, Bucket>>,
+    dropped: Mutex<HashMap<Did, u64>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimit) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+            dropped: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a token from the bucket of sender.
+    pub fn check(&self, sender: Did) -> RateDecision {
+        self.check_at(sender, get_epoch_ms())
+    }
+
+    fn check_at(&self, sender: Did, now: u128) -> RateDecision {
+        let Ok(mut buckets) = self.buckets.lock() else {
+            return RateDecision::Accept;
+        };
+
+        if !buckets.contains_key(&sender) && buckets.len() >= RATE_LIMIT_MAX_TRACKED {
+            self.evict(&mut buckets, now);
+        }
+
+        let bucket = buckets.entry(sender).or_insert(Bucket {
+            tokens: self.config.burst as f64,
+            updated_ms: now,
+            dropped: 0,
+        });
+        bucket.refill(&self.config, now);
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            bucket.dropped = 0;
+            return RateDecision::Accept;
+        }
+
+        bucket.dropped += 1;
+        let in_a_row = bucket.dropped;
+        drop(buckets);
+        self.count_dropped(sender);
+
+        match self.config.disconnect_after {
+            Some(limit) if in_a_row >= limit => RateDecision::Abuse,
+            _ => RateDecision::Drop,
+        }
+    }
+
+    fn evict(&self, buckets: &mut HashMap<Did, Bucket>, now: u128) {
+        buckets.retain(|_, b| {
+            b.refill(&self.config, now);
+            !b.is_full(&self.config)
+        });
+        if buckets.len() < RATE_LIMIT_MAX_TRACKED {
+            return;
+        }
+        if let Some(oldest) = buckets
+            .iter()
+            .min_by_key(|(_, b)| b.updated_ms)
+            .map(|(did, _)| *did)
+        {
+            buckets.remove(&oldest);
+        }
+    }
+
+    fn count_dropped(&self, sender: Did) {
+        let Ok(mut dropped) = self.dropped.lock() else {
+            return;
+        };
+        if !dropped.contains_key(&sender) && dropped.len() >= RATE_LIMIT_MAX_TRACKED {
+            // Keep the counters bounded, forget the one dropped least.
+            if let Some(least) = dropped.iter().min_by_key(|(_, n)| **n).map(|(did, _)| *did) {
+                dropped.remove(&least);
+            }
+        }
+        *dropped.entry(sender).or_default() += 1;
+    }
+
+    /// Total number of messages dropped from sender.
+    pub fn dropped(&self, sender: Did) -> u64 {
+        self.dropped
+            .lock()
+            .map(|dropped| dropped.get(&sender).copied().unwrap_or_default())
+            .unwrap_or_default()
+    }
+
+    #[cfg(test)]
+    fn tracked(&self) -> usize {
+        self.buckets.lock().unwrap().len()
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecc::SecretKey;
+
+    fn random_did() -> Did {
+        SecretKey::random().address().into()
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let limiter = RateLimiter::new(RateLimit {
+            messages_per_sec: 2,
+            burst: 3,
+            disconnect_after: Some(4),
+        });
+        let did = random_did();
+
+        for _ in 0..3 {
+            assert_eq!(limiter.check_at(did, 0), RateDecision::Accept);
+        }
+        for _ in 0..3 {
+            assert_eq!(limiter.check_at(did, 0), RateDecision::Drop);
+        }
+        assert_eq!(limiter.check_at(did, 0), RateDecision::Abuse);
+        assert_eq!(limiter.dropped(did), 4);
+
+        // Refilled 1 token in 500ms.
+        assert_eq!(limiter.check_at(did, 500), RateDecision::Accept);
+        assert_eq!(limiter.check_at(did, 500), RateDecision::Drop);
+
+        // Other senders are not affected.
+        assert_eq!(limiter.check_at(random_did(), 500), RateDecision::Accept);
+    }
+
+    #[test]
+    fn test_tracked_senders_bounded() {
+        let limiter = RateLimiter::new(RateLimit {
+            messages_per_sec: 1,
+            burst: 1,
+            disconnect_after: None,
+        });
+        let abuser = random_did();
+        assert_eq!(limiter.check_at(abuser, 0), RateDecision::Accept);
+        assert_eq!(limiter.check_at(abuser, 0), RateDecision::Drop);
+
+        for i in 0..RATE_LIMIT_MAX_TRACKED * 2 {
+            limiter.check_at(random_did(), i as u128);
+        }
+        assert!(limiter.tracked() <= RATE_LIMIT_MAX_TRACKED);
+    }
+}
@@ -0,0 +1,377 @@
+#![warn(missing_docs)]
+//! Reassembly of chunked messages received from all connections, see
+//! [crate::swarm::SwarmBuilder::reassembly_budget].
+//!
+//! A message larger than [crate::consts::TRANSPORT_MTU] is sent as [crate::chunk::Chunk]s,
+//! which are buffered until all of them arrive. Each transfer reserves the bytes of all its
+//! chunks when its first chunk arrives. A new transfer over [ReassemblyBudget] is rejected by
+//! [crate::error::Error::ReassemblyBudgetExceeded], so transfers already admitted can always
+//! be finished. Incomplete transfers older than [ReassemblyBudget::timeout] are evicted.
+//! Chunks larger than MTU, out of range, disagreeing on the number of chunks or duplicated
+//! are rejected by [crate::error::Error::InvalidChunk], so a transfer never buffers more
+//! than it reserved.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bytes::Bytes;
+use serde::Deserialize;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::chunk::Chunk;
+use crate::chunk::ChunkList;
+use crate::consts::DEFAULT_REASSEMBLY_MAX_BYTES;
+use crate::consts::DEFAULT_REASSEMBLY_MAX_TRANSFERS;
+use crate::consts::DEFAULT_REASSEMBLY_MAX_TRANSFERS_PER_PEER;
+use crate::consts::DEFAULT_REASSEMBLY_TIMEOUT_MS;
+use crate::dht::Did;
+use crate::error::Error;
+use crate::error::Result;
+
+/// Name of the gauge of chunked messages being reassembled.
+pub const REASSEMBLY_ACTIVE_TRANSFERS_METRIC: &str = "rings_reassembly_active_transfers";
+/// Name of the gauge of bytes buffered by chunked messages being reassembled.
+pub const REASSEMBLY_BUFFERED_BYTES_METRIC: &str = "rings_reassembly_buffered_bytes";
+
+/// Limits of chunked messages being reassembled at the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReassemblyBudget {
+    /// Max number of transfers.
+    pub max_transfers: usize,
+    /// Max number of transfers from a single peer, so that one peer can't take the whole
+    /// budget.
+    pub max_transfers_per_peer: usize,
+    /// Max number of bytes reserved by transfers, which is the number of their chunks times
+    /// MTU.
+    pub max_bytes: usize,
+    /// Incomplete transfers are evicted after this time since their first chunk arrived.
+    pub timeout: Duration,
+}
+
+impl Default for ReassemblyBudget {
+    fn default() -> Self {
+        Self {
+            max_transfers: DEFAULT_REASSEMBLY_MAX_TRANSFERS,
+            max_transfers_per_peer: DEFAULT_REASSEMBLY_MAX_TRANSFERS_PER_PEER,
+            max_bytes: DEFAULT_REASSEMBLY_MAX_BYTES,
+            timeout: Duration::from_millis(DEFAULT_REASSEMBLY_TIMEOUT_MS),
+        }
+    }
+}
+
+/// Metrics of reassembly, returned by [crate::swarm::Swarm::reassembly_metrics].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReassemblySnapshot {
+    /// Gauge [REASSEMBLY_ACTIVE_TRANSFERS_METRIC].
+    pub active_transfers: usize,
+    /// Gauge [REASSEMBLY_BUFFERED_BYTES_METRIC].
+    pub buffered_bytes: usize,
+    /// Number of transfers rejected for exceeding budget.
+    pub rejected: u64,
+    /// Number of incomplete transfers evicted for timeout.
+    pub evicted: u64,
+}
+
+struct Transfer<const MTU: usize> {
+    started_ms: u128,
+    reserved: usize,
+    /// Whether each chunk has arrived, the length is the number of chunks.
+    received: Vec<bool>,
+    remaining: usize,
+    chunks: ChunkList<MTU>,
+}
+
+impl<const MTU: usize> Transfer<MTU> {
+    fn buffered(&self) -> usize {
+        self.chunks.as_vec().iter().map(|c| c.data.len()).sum()
+    }
+}
+
+#[derive(Default)]
+struct ReassemblyState<const MTU: usize> {
+    /// Transfers indexed by the peer sending them and their id.
+    transfers: HashMap<(Did, Uuid), Transfer<MTU>>,
+    reserved: usize,
+    rejected: u64,
+    evicted: u64,
+}
+
+/// Chunked messages being reassembled, limited by [ReassemblyBudget].
+pub(crate) struct Reassembly<const MTU: usize> {
+    budget: ReassemblyBudget,
+    state: Mutex<ReassemblyState<MTU>>,
+}
+
+impl<const MTU: usize> Default for Reassembly<MTU> {
+    fn default() -> Self {
+        Self::new(ReassemblyBudget::default())
+    }
+}
+
+impl<const MTU: usize> Reassembly<MTU> {
+    pub fn new(budget: ReassemblyBudget) -> Self {
+        Self {
+            budget,
+            state: Mutex::new(ReassemblyState::default()),
+        }
+    }
+
+    /// Handle a chunk sent by peer at `now`. Return the message once all its chunks arrive.
+    /// Return [Error::ReassemblyBudgetExceeded] if it's the first chunk of a transfer over
+    /// budget, or [Error::InvalidChunk] if the chunk doesn't fit its transfer.
+    pub fn handle(&self, peer: Did, chunk: Chunk, now: u128) -> Result<Option<Bytes>> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        self.evict_expired(&mut state, now);
+
+        let id = chunk.meta.id;
+        let [index, total] = chunk.chunk;
+        let invalid = |reason: &str| Err(Error::InvalidChunk(id, reason.to_string()));
+        if chunk.data.len() > MTU {
+            return invalid("data is larger than MTU");
+        }
+        if index >= total {
+            return invalid("index is out of range");
+        }
+
+        let key = (peer, id);
+        if !state.transfers.contains_key(&key) {
+            let reserved = total.saturating_mul(MTU);
+            let from_peer = state.transfers.keys().filter(|(p, _)| *p == peer).count();
+            if state.transfers.len() >= self.budget.max_transfers
+                || from_peer >= self.budget.max_transfers_per_peer
+                || state.reserved.saturating_add(reserved) > self.budget.max_bytes
+            {
+                state.rejected += 1;
+                return Err(Error::ReassemblyBudgetExceeded(id));
+            }
+            state.reserved += reserved;
+            state.transfers.insert(key, Transfer {
+                started_ms: now,
+                reserved,
+                received: vec![false; total],
+                remaining: total,
+                chunks: ChunkList::default(),
+            });
+        }
+        let Some(transfer) = state.transfers.get_mut(&key) else {
+            return Ok(None);
+        };
+
+        if transfer.received.len() != total {
+            return invalid("number of chunks is changed");
+        }
+        if transfer.received[index] {
+            return invalid("chunk is duplicated");
+        }
+        transfer.received[index] = true;
+        transfer.remaining -= 1;
+        transfer.chunks.as_vec_mut().push(chunk);
+        if transfer.remaining > 0 {
+            return Ok(None);
+        }
+
+        let data = transfer.chunks.try_withdraw();
+        if let Some(transfer) = state.transfers.remove(&key) {
+            state.reserved -= transfer.reserved;
+        }
+        Ok(data)
+    }
+
+    fn evict_expired(&self, state: &mut ReassemblyState<MTU>, now: u128) {
+        let timeout = self.budget.timeout.as_millis();
+        let expired = state
+            .transfers
+            .iter()
+            .filter(|(_, t)| now.saturating_sub(t.started_ms) >= timeout)
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+        for key in expired {
+            if let Some(transfer) = state.transfers.remove(&key) {
+                tracing::warn!(
+                    target: "rings::swarm",
+                    "Evict incomplete chunked message {} from {}, reason: Timeout",
+                    key.1,
+                    key.0
+                );
+                state.reserved -= transfer.reserved;
+                state.evicted += 1;
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> ReassemblySnapshot {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        ReassemblySnapshot {
+            active_transfers: state.transfers.len(),
+            buffered_bytes: state.transfers.values().map(|t| t.buffered()).sum(),
+            rejected: state.rejected,
+            evicted: state.evicted,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecc::SecretKey;
+    use crate::utils::get_epoch_ms;
+
+    fn chunks(data: &str) -> Vec<Chunk> {
+        ChunkList::<32>::from(&Bytes::from(data.to_string())).into()
+    }
+
+    #[test]
+    fn test_reject_transfers_over_budget() {
+        let reassembly = Reassembly::<32>::new(ReassemblyBudget {
+            max_transfers: 2,
+            max_transfers_per_peer: 2,
+            max_bytes: 32 * 6,
+            timeout: Duration::from_secs(60),
+        });
+        let peer: Did = SecretKey::random().address().into();
+        let now = get_epoch_ms();
+
+        // 3 chunks each.
+        let first = chunks(&"a".repeat(80));
+        let second = chunks(&"b".repeat(80));
+        let third = chunks(&"c".repeat(80));
+        // A single chunk.
+        let small = chunks("d");
+
+        assert_eq!(
+            reassembly.handle(peer, first[0].clone(), now).unwrap(),
+            None
+        );
+        assert_eq!(
+            reassembly.handle(peer, second[0].clone(), now).unwrap(),
+            None
+        );
+        let snapshot = reassembly.snapshot();
+        assert_eq!(snapshot.active_transfers, 2);
+        assert_eq!(snapshot.buffered_bytes, 64);
+
+        // New transfers are rejected, by both number of transfers and bytes.
+        assert!(matches!(
+            reassembly.handle(peer, third[0].clone(), now),
+            Err(Error::ReassemblyBudgetExceeded(id)) if id == third[0].meta.id
+        ));
+        assert!(reassembly.handle(peer, small[0].clone(), now).is_err());
+        assert_eq!(reassembly.snapshot().rejected, 2);
+
+        // Existing transfers continue.
+        assert_eq!(
+            reassembly.handle(peer, first[1].clone(), now).unwrap(),
+            None
+        );
+        let data = reassembly.handle(peer, first[2].clone(), now).unwrap();
+        assert_eq!(data, Some(Bytes::from("a".repeat(80))));
+        let snapshot = reassembly.snapshot();
+        assert_eq!(snapshot.active_transfers, 1);
+        assert_eq!(snapshot.buffered_bytes, 32);
+
+        // The budget is released by finished transfers.
+        let data = reassembly.handle(peer, small[0].clone(), now).unwrap();
+        assert_eq!(data, Some(Bytes::from("d")));
+    }
+
+    #[test]
+    fn test_evict_incomplete_transfers() {
+        let reassembly = Reassembly::<32>::new(ReassemblyBudget {
+            max_transfers: 1,
+            max_transfers_per_peer: 1,
+            max_bytes: 32 * 6,
+            timeout: Duration::from_millis(100),
+        });
+        let peer: Did = SecretKey::random().address().into();
+        let other: Did = SecretKey::random().address().into();
+        let now = get_epoch_ms();
+        let first = chunks(&"a".repeat(80));
+        let second = chunks(&"b".repeat(80));
+
+        assert_eq!(
+            reassembly.handle(peer, first[0].clone(), now).unwrap(),
+            None
+        );
+        // Chunks of another peer belong to another transfer.
+        assert!(reassembly.handle(other, first[1].clone(), now).is_err());
+        assert!(reassembly
+            .handle(peer, second[0].clone(), now + 99)
+            .is_err());
+
+        // The incomplete transfer is evicted, so a new one is admitted.
+        assert_eq!(
+            reassembly
+                .handle(peer, second[0].clone(), now + 100)
+                .unwrap(),
+            None
+        );
+        let snapshot = reassembly.snapshot();
+        assert_eq!(snapshot.active_transfers, 1);
+        assert_eq!(snapshot.evicted, 1);
+    }
+
+    #[test]
+    fn test_reject_invalid_chunks() {
+        let reassembly = Reassembly::<32>::default();
+        let peer: Did = SecretKey::random().address().into();
+        let now = get_epoch_ms();
+        let first = chunks(&"a".repeat(80));
+        let is_invalid = |r: Result<Option<Bytes>>| matches!(r, Err(Error::InvalidChunk(..)));
+
+        let mut oversized = first[0].clone();
+        oversized.data = Bytes::from("a".repeat(33));
+        assert!(is_invalid(reassembly.handle(peer, oversized, now)));
+        let mut out_of_range = first[0].clone();
+        out_of_range.chunk = [3, 3];
+        assert!(is_invalid(reassembly.handle(peer, out_of_range, now)));
+        assert_eq!(reassembly.snapshot().active_transfers, 0);
+
+        assert_eq!(
+            reassembly.handle(peer, first[0].clone(), now).unwrap(),
+            None
+        );
+        assert!(is_invalid(reassembly.handle(peer, first[0].clone(), now)));
+        let mut resized = first[1].clone();
+        resized.chunk = [1, 1000];
+        assert!(is_invalid(reassembly.handle(peer, resized, now)));
+
+        // The transfer is not affected by invalid chunks.
+        assert_eq!(
+            reassembly.handle(peer, first[1].clone(), now).unwrap(),
+            None
+        );
+        let data = reassembly.handle(peer, first[2].clone(), now).unwrap();
+        assert_eq!(data, Some(Bytes::from("a".repeat(80))));
+    }
+
+    #[test]
+    fn test_cap_transfers_per_peer() {
+        let reassembly = Reassembly::<32>::new(ReassemblyBudget {
+            max_transfers: 4,
+            max_transfers_per_peer: 1,
+            max_bytes: 32 * 12,
+            timeout: Duration::from_secs(60),
+        });
+        let peer: Did = SecretKey::random().address().into();
+        let other: Did = SecretKey::random().address().into();
+        let now = get_epoch_ms();
+        let first = chunks(&"a".repeat(80));
+        let second = chunks(&"b".repeat(80));
+
+        assert_eq!(
+            reassembly.handle(peer, first[0].clone(), now).unwrap(),
+            None
+        );
+        assert!(matches!(
+            reassembly.handle(peer, second[0].clone(), now),
+            Err(Error::ReassemblyBudgetExceeded(_))
+        ));
+        // Other peers still have their share.
+        assert_eq!(
+            reassembly.handle(other, second[0].clone(), now).unwrap(),
+            None
+        );
+    }
+}
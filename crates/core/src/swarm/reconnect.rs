@@ -0,0 +1,208 @@
+//! Reconnection of closed connections, see [crate::swarm::SwarmBuilder::reconnect_policy].
+//!
+//! When the connection of a peer whose data channel has opened is closed, the peer is passed
+//! to the predicate of [ReconnectPolicy]. If it's wanted, the peer is connected again in
+//! stabilization: the first attempt is made in the next round, and the following ones wait for
+//! a backoff doubled each time. The peer is forgotten once it's connected or out of attempts.
+//! Connections closed by [crate::swarm::Swarm::disconnect] are never reconnected.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use rings_transport::core::transport::WebrtcConnectionState;
+
+use crate::consts::DEFAULT_RECONNECT_BACKOFF_MS;
+use crate::consts::DEFAULT_RECONNECT_MAX_ATTEMPTS;
+use crate::consts::DEFAULT_RECONNECT_MAX_BACKOFF_MS;
+use crate::dht::Did;
+
+/// Function deciding whether a closed connection should be reconnected.
+#[cfg(not(feature = "wasm"))]
+pub type ReconnectFn = Box<dyn Fn(&ReconnectInput) -> bool + Send + Sync>;
+
+/// Function deciding whether a closed connection should be reconnected.
+#[cfg(feature = "wasm")]
+pub type ReconnectFn = Box<dyn Fn(&ReconnectInput) -> bool>;
+
+/// Inputs of a [ReconnectFn] for a closed connection.
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct ReconnectInput {
+    /// The did of remote peer.
+    pub peer: Did,
+    /// The state closing the connection.
+    pub state: WebrtcConnectionState,
+    /// Whether the peer is a DHT successor when it's closed.
+    pub is_successor: bool,
+    /// Whether the peer is pinned by [crate::swarm::Swarm::pin].
+    pub is_pinned: bool,
+}
+
+/// The default [ReconnectFn], reconnects DHT successors.
+pub fn reconnect_successors(input: &ReconnectInput) -> bool {
+    input.is_successor
+}
+
+/// Policy of reconnecting closed connections.
+pub struct ReconnectPolicy {
+    predicate: ReconnectFn,
+    backoff: Duration,
+    max_backoff: Duration,
+    max_attempts: u32,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self::new(Box::new(reconnect_successors))
+    }
+}
+
+impl ReconnectPolicy {
+    /// Reconnect closed connections matching `predicate`, with the default backoff and
+    /// attempts.
+    pub fn new(predicate: ReconnectFn) -> Self {
+        Self {
+            predicate,
+            backoff: Duration::from_millis(DEFAULT_RECONNECT_BACKOFF_MS),
+            max_backoff: Duration::from_millis(DEFAULT_RECONNECT_MAX_BACKOFF_MS),
+            max_attempts: DEFAULT_RECONNECT_MAX_ATTEMPTS,
+        }
+    }
+
+    /// Wait for `backoff` before the second attempt, doubled for each following one and
+    /// capped by `max_backoff`.
+    pub fn backoff(mut self, backoff: Duration, max_backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Give up a peer after `max_attempts` attempts.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Delay after the `attempts`-th attempt.
+    fn delay_ms(&self, attempts: u32) -> u128 {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+            .as_millis()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PendingReconnect {
+    attempts: u32,
+    due_ms: u128,
+}
+
+/// Peers waiting to be reconnected by [ReconnectPolicy].
+pub(crate) struct Reconnector {
+    policy: ReconnectPolicy,
+    pending: Mutex<HashMap<Did, PendingReconnect>>,
+}
+
+impl Reconnector {
+    pub fn new(policy: ReconnectPolicy) -> Self {
+        Self {
+            policy,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Schedule reconnecting the peer of a closed connection at `now` if it's wanted by the
+    /// policy. A peer already scheduled is kept as it is. Return whether it's scheduled.
+    pub fn schedule(&self, input: &ReconnectInput, now: u128) -> bool {
+        if !(self.policy.predicate)(input) {
+            return false;
+        }
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.entry(input.peer).or_insert(PendingReconnect {
+            attempts: 0,
+            due_ms: now,
+        });
+        true
+    }
+
+    /// Take peers due at `now`. Each of them is counted as an attempt, and scheduled again
+    /// after backoff unless it's out of attempts.
+    pub fn take_due(&self, now: u128) -> Vec<Did> {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        let mut due = vec![];
+        pending.retain(|peer, p| {
+            if p.due_ms > now {
+                return true;
+            }
+            due.push(*peer);
+            p.attempts += 1;
+            p.due_ms = now + self.policy.delay_ms(p.attempts);
+            p.attempts < self.policy.max_attempts
+        });
+        due
+    }
+
+    /// Stop reconnecting peer, such as when it's connected again.
+    pub fn cancel(&self, peer: Did) {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.remove(&peer);
+    }
+
+    /// List peers waiting to be reconnected.
+    pub fn pending(&self) -> Vec<Did> {
+        let pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.keys().copied().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecc::SecretKey;
+
+    fn closed(peer: Did, is_successor: bool) -> ReconnectInput {
+        ReconnectInput {
+            peer,
+            state: WebrtcConnectionState::Closed,
+            is_successor,
+            is_pinned: false,
+        }
+    }
+
+    #[test]
+    fn test_reconnect_with_backoff() {
+        let reconnector = Reconnector::new(
+            ReconnectPolicy::default()
+                .backoff(Duration::from_millis(100), Duration::from_millis(300))
+                .max_attempts(4),
+        );
+        let peer: Did = SecretKey::random().address().into();
+        let other: Did = SecretKey::random().address().into();
+
+        assert!(!reconnector.schedule(&closed(other, false), 0));
+        assert!(reconnector.schedule(&closed(peer, true), 0));
+        // Scheduled again by another closing state.
+        assert!(reconnector.schedule(&closed(peer, true), 50));
+        assert_eq!(reconnector.pending(), vec![peer]);
+
+        // Attempts at 0, 100, 300 and 600.
+        assert_eq!(reconnector.take_due(0), vec![peer]);
+        assert!(reconnector.take_due(99).is_empty());
+        assert_eq!(reconnector.take_due(100), vec![peer]);
+        assert!(reconnector.take_due(299).is_empty());
+        assert_eq!(reconnector.take_due(300), vec![peer]);
+        assert!(reconnector.take_due(599).is_empty());
+        assert_eq!(reconnector.take_due(600), vec![peer]);
+
+        // Out of attempts.
+        assert!(reconnector.pending().is_empty());
+        assert!(reconnector.take_due(10000).is_empty());
+
+        assert!(reconnector.schedule(&closed(peer, true), 0));
+        reconnector.cancel(peer);
+        assert!(reconnector.take_due(0).is_empty());
+    }
+}
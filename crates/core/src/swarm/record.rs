@@ -0,0 +1,188 @@
+#![warn(missing_docs)]
+//! Recording and replaying of message traffic, for debugging.
+//!
+//! A [MessageRecorder] set by [crate::swarm::SwarmBuilder::message_recorder] writes every
+//! outbound [MessagePayload] and every inbound one accepted for handling to a file, one JSON
+//! record per line. The inbound records can be fed back to the handler of a fresh swarm in
+//! order by [replay], which reproduces the handling without network and timing.
+//!
+//! Encrypted payloads are recorded as received, unless [MessageRecorder::plaintext] is enabled.
+//! Records are written by a thread of the recorder, so that the message path never waits for
+//! the file.
+
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+use std::sync::mpsc;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::consts::MESSAGE_RECORDER_QUEUE_SIZE;
+use crate::dht::Did;
+use crate::error::Error;
+use crate::error::Result;
+use crate::message::MessagePayload;
+use crate::swarm::Swarm;
+
+/// Direction of a recorded payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    /// Received from the peer.
+    Inbound,
+    /// Sent to the peer.
+    Outbound,
+}
+
+/// A payload written by [MessageRecorder].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedPayload {
+    /// Timestamp in milliseconds by the clock of swarm.
+    pub ts_ms: u128,
+    /// Whether the payload is received or sent.
+    pub direction: Direction,
+    /// The peer of connection which the payload is received from or sent to.
+    pub peer: Did,
+    /// The payload, which is decrypted only if [MessageRecorder::plaintext] is enabled.
+    pub payload: MessagePayload,
+}
+
+enum Command {
+    Write(Vec<u8>),
+    Flush(mpsc::Sender<std::io::Result<()>>),
+}
+
+/// Writer of [RecordedPayload] lines. Clones share the same writer.
+#[derive(Clone)]
+pub struct MessageRecorder {
+    sender: mpsc::SyncSender<Command>,
+    plaintext: bool,
+}
+
+impl MessageRecorder {
+    /// Create a recorder writing to the file of `path`, which is truncated if it exists.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::create(path).map_err(Error::IOError)?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+
+    /// Create a recorder writing to `writer` on a new thread, which stops once the recorder
+    /// and its clones are dropped.
+    pub fn new(mut writer: impl Write + Send + 'static) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(MESSAGE_RECORDER_QUEUE_SIZE);
+        std::thread::spawn(move || {
+            for command in receiver {
+                match command {
+                    // Every record is flushed, so that a file is still readable if the
+                    // process is killed.
+                    Command::Write(line) => {
+                        if let Err(e) = writer.write_all(&line).and_then(|_| writer.flush()) {
+                            tracing::warn!(target: "rings::swarm", "Failed to write record: {e:?}");
+                        }
+                    }
+                    Command::Flush(done) => {
+                        let _ = done.send(writer.flush());
+                    }
+                }
+            }
+        });
+        Self {
+            sender,
+            plaintext: false,
+        }
+    }
+
+    /// Record inbound payloads after they are decrypted, instead of as received.
+    /// The decrypted body of messages is written to the file then.
+    pub fn plaintext(mut self, enable: bool) -> Self {
+        self.plaintext = enable;
+        self
+    }
+
+    /// Queue a record of payload to be written. `decrypted` is recorded instead of `payload`
+    /// if [MessageRecorder::plaintext] is enabled. The record is dropped with an error if
+    /// too many records are waiting.
+    pub fn record(
+        &self,
+        ts_ms: u128,
+        direction: Direction,
+        peer: Did,
+        payload: &MessagePayload,
+        decrypted: Option<&MessagePayload>,
+    ) -> Result<()> {
+        let payload = match decrypted {
+            Some(decrypted) if self.plaintext => decrypted,
+            _ => payload,
+        };
+        let record = RecordedPayload {
+            ts_ms,
+            direction,
+            peer,
+            payload: payload.clone(),
+        };
+        let mut line = serde_json::to_vec(&record).map_err(Error::Serialize)?;
+        line.push(b'\n');
+        self.sender
+            .try_send(Command::Write(line))
+            .map_err(|_| Error::RecorderUnavailable)
+    }
+
+    /// Block until the records queued before are written and flushed.
+    pub fn flush(&self) -> Result<()> {
+        let (done, wait) = mpsc::channel();
+        self.sender
+            .send(Command::Flush(done))
+            .map_err(|_| Error::RecorderUnavailable)?;
+        wait.recv()
+            .map_err(|_| Error::RecorderUnavailable)?
+            .map_err(Error::IOError)
+    }
+}
+
+/// Read records written by [MessageRecorder] from the file of `path`.
+pub fn load(path: impl AsRef<Path>) -> Result<Vec<RecordedPayload>> {
+    let file = File::open(path).map_err(Error::IOError)?;
+    read_records(BufReader::new(file))
+}
+
+/// Read records written by [MessageRecorder] from `reader`. Empty lines are skipped.
+pub fn read_records(reader: impl BufRead) -> Result<Vec<RecordedPayload>> {
+    let mut records = vec![];
+    for line in reader.lines() {
+        let line = line.map_err(Error::IOError)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        records.push(serde_json::from_str(&line).map_err(Error::Deserialize)?);
+    }
+    Ok(records)
+}
+
+/// Feed inbound `records` to the handler of `swarm` in order, as if they were received from
+/// their peers again. Outbound records are skipped. They are not verified or rate limited
+/// again, since they were accepted when recorded. Encrypted payloads are decrypted by the
+/// session key of `swarm`, so only the swarm which recorded them can replay them.
+/// Return the number of replayed records.
+pub async fn replay(
+    swarm: &Swarm,
+    records: impl IntoIterator<Item = RecordedPayload>,
+) -> Result<usize> {
+    let callback = swarm.inner_callback()?;
+    let mut count = 0;
+    for record in records {
+        if record.direction != Direction::Inbound {
+            continue;
+        }
+        if let Err(e) = callback
+            .replay_payload(&record.peer.to_string(), &record.payload)
+            .await
+        {
+            tracing::warn!("Failed to replay payload from {}: {e:?}", record.peer);
+        }
+        count += 1;
+    }
+    Ok(count)
+}
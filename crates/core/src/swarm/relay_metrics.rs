@@ -0,0 +1,218 @@
+#![warn(missing_docs)]
+//! Histograms of hops and latency of messages relayed to their destination and reported back,
+//! see [crate::swarm::Swarm::relay_metrics].
+//!
+//! The send time of each message originated by this node is kept by its tx_id. When a report
+//! of the same transaction arrives, the length of its relay path, which is the number of hops
+//! it took back, and the time elapsed since sending are recorded.
+
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::consts::RELAY_METRICS_MAX_TRACKED;
+
+/// Name of the histogram of hops taken by reports.
+pub const RELAY_HOPS_METRIC: &str = "rings_relay_hops";
+/// Name of the histogram of milliseconds between sending a message and receiving its report.
+pub const RELAY_LATENCY_METRIC: &str = "rings_relay_latency_ms";
+
+const HOPS_BUCKETS: [u64; 9] = [1, 2, 3, 4, 5, 6, 8, 12, 16];
+const LATENCY_BUCKETS_MS: [u64; 11] = [5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000];
+
+/// A histogram of observed values, with buckets cumulative as in Prometheus.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Histogram {
+    /// Name of the metric.
+    pub name: String,
+    /// Upper bounds of buckets, with the number of observations less than or equal to each.
+    pub buckets: Vec<(u64, u64)>,
+    /// Sum of observations.
+    pub sum: u64,
+    /// Number of observations, including the ones larger than the last bound.
+    pub count: u64,
+}
+
+impl Histogram {
+    pub(crate) fn new(name: &str, bounds: &[u64]) -> Self {
+        Self {
+            name: name.to_string(),
+            buckets: bounds.iter().map(|bound| (*bound, 0)).collect(),
+            sum: 0,
+            count: 0,
+        }
+    }
+
+    pub(crate) fn observe(&mut self, value: u64) {
+        for (bound, count) in self.buckets.iter_mut() {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum = self.sum.saturating_add(value);
+        self.count += 1;
+    }
+
+    /// Number of observations less than or equal to `bound`.
+    /// Return None if `bound` is not an upper bound of buckets.
+    pub fn count_le(&self, bound: u64) -> Option<u64> {
+        self.buckets
+            .iter()
+            .find(|(b, _)| *b == bound)
+            .map(|(_, count)| *count)
+    }
+}
+
+/// Histograms of relayed messages, returned by [crate::swarm::Swarm::relay_metrics].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayMetricsSnapshot {
+    /// Histogram [RELAY_HOPS_METRIC].
+    pub hops: Histogram,
+    /// Histogram [RELAY_LATENCY_METRIC].
+    pub latency_ms: Histogram,
+}
+
+/// Send time of tracked messages, with an index ordered by it to find the oldest one.
+#[derive(Default)]
+struct Tracked {
+    sent_at: HashMap<uuid::Uuid, u128>,
+    by_time: BTreeSet<(u128, uuid::Uuid)>,
+}
+
+/// Send time of messages waiting for report, and histograms of the reported ones.
+/// At most [RELAY_METRICS_MAX_TRACKED] messages are tracked. When it's full, the oldest one
+/// is evicted, since its report is the least likely to arrive.
+pub(crate) struct RelayMetrics {
+    tracked: Mutex<Tracked>,
+    histograms: Mutex<RelayMetricsSnapshot>,
+}
+
+impl Default for RelayMetrics {
+    fn default() -> Self {
+        Self {
+            tracked: Mutex::new(Tracked::default()),
+            histograms: Mutex::new(RelayMetricsSnapshot {
+                hops: Histogram::new(RELAY_HOPS_METRIC, &HOPS_BUCKETS),
+                latency_ms: Histogram::new(RELAY_LATENCY_METRIC, &LATENCY_BUCKETS_MS),
+            }),
+        }
+    }
+}
+
+impl RelayMetrics {
+    /// Keep the send time of a message originated by this node.
+    /// The first send time is kept if the transaction is sent again.
+    pub fn on_sent(&self, tx_id: uuid::Uuid, now: u128) {
+        let mut tracked = self.tracked.lock().unwrap_or_else(|e| e.into_inner());
+        if tracked.sent_at.contains_key(&tx_id) {
+            return;
+        }
+        if tracked.sent_at.len() >= RELAY_METRICS_MAX_TRACKED {
+            if let Some((_, oldest)) = tracked.by_time.pop_first() {
+                tracked.sent_at.remove(&oldest);
+            }
+        }
+        tracked.sent_at.insert(tx_id, now);
+        tracked.by_time.insert((now, tx_id));
+    }
+
+    /// Record hops and latency of a report received by this node.
+    /// Return false if the reported message is not sent by this node or it's reported already.
+    pub fn on_report(&self, tx_id: uuid::Uuid, hops: usize, now: u128) -> bool {
+        let sent = {
+            let mut tracked = self.tracked.lock().unwrap_or_else(|e| e.into_inner());
+            let sent = tracked.sent_at.remove(&tx_id);
+            if let Some(sent) = sent {
+                tracked.by_time.remove(&(sent, tx_id));
+            }
+            sent
+        };
+        let Some(sent) = sent else {
+            return false;
+        };
+        let latency = u64::try_from(now.saturating_sub(sent)).unwrap_or(u64::MAX);
+        let mut histograms = self.histograms.lock().unwrap_or_else(|e| e.into_inner());
+        histograms.hops.observe(hops as u64);
+        histograms.latency_ms.observe(latency);
+        true
+    }
+
+    pub fn snapshot(&self) -> RelayMetricsSnapshot {
+        self.histograms
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative() {
+        let mut histogram = Histogram::new(RELAY_HOPS_METRIC, &HOPS_BUCKETS);
+        for hops in [1, 3, 3, 20] {
+            histogram.observe(hops);
+        }
+        assert_eq!(histogram.count_le(1), Some(1));
+        assert_eq!(histogram.count_le(2), Some(1));
+        assert_eq!(histogram.count_le(3), Some(3));
+        assert_eq!(histogram.count_le(16), Some(3));
+        assert_eq!(histogram.count_le(7), None);
+        assert_eq!(histogram.sum, 27);
+        assert_eq!(histogram.count, 4);
+    }
+
+    #[test]
+    fn test_report_of_tracked_message() {
+        let metrics = RelayMetrics::default();
+        let tx_id = uuid::Uuid::new_v4();
+        metrics.on_sent(tx_id, 1000);
+        // Sending again keeps the first send time.
+        metrics.on_sent(tx_id, 1100);
+
+        assert!(!metrics.on_report(uuid::Uuid::new_v4(), 2, 1200));
+        assert!(metrics.on_report(tx_id, 2, 1200));
+        assert!(!metrics.on_report(tx_id, 2, 1300));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.hops.count, 1);
+        assert_eq!(snapshot.hops.sum, 2);
+        assert_eq!(snapshot.latency_ms.sum, 200);
+        assert_eq!(snapshot.latency_ms.count_le(100), Some(0));
+        assert_eq!(snapshot.latency_ms.count_le(250), Some(1));
+    }
+
+    #[test]
+    fn test_evict_oldest_when_full() {
+        let metrics = RelayMetrics::default();
+        let ids = (0..=RELAY_METRICS_MAX_TRACKED)
+            .map(|_| uuid::Uuid::new_v4())
+            .collect::<Vec<_>>();
+        for (i, tx_id) in ids.iter().enumerate() {
+            metrics.on_sent(*tx_id, i as u128);
+        }
+        assert!(!metrics.on_report(ids[0], 1, 2000));
+        assert!(metrics.on_report(ids[1], 1, 2000));
+    }
+
+    #[test]
+    fn test_reported_message_frees_its_slot() {
+        let metrics = RelayMetrics::default();
+        let ids = (0..=RELAY_METRICS_MAX_TRACKED)
+            .map(|_| uuid::Uuid::new_v4())
+            .collect::<Vec<_>>();
+        for (i, tx_id) in ids[..RELAY_METRICS_MAX_TRACKED].iter().enumerate() {
+            metrics.on_sent(*tx_id, i as u128);
+        }
+        assert!(metrics.on_report(ids[1], 1, 2000));
+        // The slot of the reported one is reused, so the oldest one is still tracked.
+        metrics.on_sent(ids[RELAY_METRICS_MAX_TRACKED], 1500);
+        assert!(metrics.on_report(ids[0], 1, 2000));
+        assert!(metrics.on_report(ids[RELAY_METRICS_MAX_TRACKED], 1, 2000));
+    }
+}
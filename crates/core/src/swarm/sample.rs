@@ -0,0 +1,110 @@
+//! Random sampling of known peers, see [crate::swarm::Swarm::sample_peers].
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::dht::Did;
+
+/// Weight of peers whose connection quality is zero or unknown in
+/// [SampleBias::QualityWeighted], so that they can still be sampled.
+const MIN_SAMPLE_WEIGHT: f64 = 0.01;
+
+/// How peers are picked by [crate::swarm::Swarm::sample_peers].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SampleBias {
+    /// Each peer is picked with the same probability.
+    #[default]
+    Uniform,
+    /// Peers are picked with probability proportional to their connection quality, see
+    /// [crate::swarm::Swarm::connection_quality]. Peers not connected are rarely picked.
+    QualityWeighted,
+}
+
+/// Pick at most `n` distinct peers from `candidates` of peers and their connection quality.
+pub(crate) fn sample<R: Rng + ?Sized>(
+    rng: &mut R,
+    candidates: &[(Did, Option<f64>)],
+    n: usize,
+    bias: SampleBias,
+) -> Vec<Did> {
+    let n = n.min(candidates.len());
+    match bias {
+        SampleBias::Uniform => candidates
+            .choose_multiple(rng, n)
+            .map(|(did, _)| *did)
+            .collect(),
+        SampleBias::QualityWeighted => candidates
+            .choose_multiple_weighted(rng, n, |(_, quality)| {
+                quality
+                    .filter(|q| q.is_finite())
+                    .unwrap_or(0.0)
+                    .max(MIN_SAMPLE_WEIGHT)
+            })
+            .map(|picked| picked.map(|(did, _)| *did).collect())
+            .unwrap_or_else(|e| {
+                tracing::warn!(target: "rings::swarm", "Failed to sample peers by quality: {e}");
+                vec![]
+            }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::ecc::SecretKey;
+
+    fn random_did() -> Did {
+        SecretKey::random().address().into()
+    }
+
+    #[test]
+    fn test_sample_without_replacement() {
+        let mut rng = rand::thread_rng();
+        let candidates = (0..5)
+            .map(|_| (random_did(), Some(1.0)))
+            .collect::<Vec<_>>();
+        for bias in [SampleBias::Uniform, SampleBias::QualityWeighted] {
+            let picked = sample(&mut rng, &candidates, 3, bias);
+            assert_eq!(picked.len(), 3);
+            assert_eq!(picked.iter().collect::<HashSet<_>>().len(), 3);
+
+            // Bounded by the number of candidates.
+            let picked = sample(&mut rng, &candidates, 10, bias);
+            assert_eq!(picked.len(), 5);
+            assert!(sample(&mut rng, &[], 3, bias).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_uniform_sample_covers_all_peers() {
+        let mut rng = rand::thread_rng();
+        let candidates = (0..10).map(|_| (random_did(), None)).collect::<Vec<_>>();
+        let mut seen = HashSet::new();
+        for _ in 0..200 {
+            seen.extend(sample(&mut rng, &candidates, 2, SampleBias::Uniform));
+        }
+        assert_eq!(seen.len(), candidates.len());
+    }
+
+    #[test]
+    fn test_quality_weighted_sample_favors_high_quality_peers() {
+        let mut rng = rand::thread_rng();
+        let good = random_did();
+        let bad = random_did();
+        let unknown = random_did();
+        let candidates = vec![(good, Some(0.9)), (bad, Some(0.1)), (unknown, None)];
+
+        let mut counts = HashMap::<Did, usize>::new();
+        for _ in 0..1000 {
+            for did in sample(&mut rng, &candidates, 1, SampleBias::QualityWeighted) {
+                *counts.entry(did).or_default() += 1;
+            }
+        }
+        let count = |did| counts.get(&did).copied().unwrap_or_default();
+        assert!(count(good) > count(bad) * 3, "{counts:?}");
+        assert!(count(bad) > count(unknown), "{counts:?}");
+    }
+}
@@ -1,9 +1,24 @@
+use std::collections::HashMap;
+use std::future::Future;
 use std::str::FromStr;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::sync::RwLock;
+use std::time::Duration;
 
+use async_lock::Mutex as AsyncMutex;
+use async_lock::MutexGuardArc;
+use async_lock::Semaphore;
+use async_lock::SemaphoreGuardArc;
 use async_trait::async_trait;
 use bytes::Bytes;
-use rings_transport::connection_ref::ConnectionRef;
+use dashmap::DashMap;
+use dashmap::DashSet;
+use futures::channel::mpsc;
+use futures::channel::oneshot;
+use futures::future::Either;
+use futures::FutureExt;
 #[cfg(feature = "dummy")]
 pub use rings_transport::connections::DummyConnection as ConnectionOwner;
 #[cfg(feature = "dummy")]
@@ -13,44 +28,336 @@ pub use rings_transport::connections::WebSysWebrtcConnection as ConnectionOwner;
 #[cfg(feature = "wasm")]
 pub use rings_transport::connections::WebSysWebrtcTransport as Transport;
 #[cfg(all(not(feature = "wasm"), not(feature = "dummy")))]
-use rings_transport::connections::WebrtcConnection as ConnectionOwner;
+pub(crate) use rings_transport::connections::WebrtcConnection as ConnectionOwner;
 #[cfg(all(not(feature = "wasm"), not(feature = "dummy")))]
-use rings_transport::connections::WebrtcTransport as Transport;
+pub(crate) use rings_transport::connections::WebrtcTransport as Transport;
 use rings_transport::core::transport::ConnectionInterface;
-use rings_transport::core::transport::TransportInterface;
+use rings_transport::core::transport::ConnectionStats;
 use rings_transport::core::transport::TransportMessage;
 use rings_transport::core::transport::WebrtcConnectionState;
+use rings_transport::error::Error as TransportError;
+use rings_transport::error::Result as TransportResult;
+use rings_transport::rtc_config::RtcConfig;
+use serde::Deserialize;
+use serde::Serialize;
 
 use crate::chunk::ChunkList;
-use crate::consts::TRANSPORT_MAX_SIZE;
+use crate::consts::DEFAULT_EVENT_CHANNEL_CAPACITY;
+use crate::consts::DEFAULT_INBOX_BLOCK_TIMEOUT_MS;
+use crate::consts::DEFAULT_MAX_MESSAGE_SIZE;
+use crate::consts::MAX_HANDSHAKE_RENEGOTIATIONS;
+use crate::consts::MAX_PENDING_ICE_CANDIDATES;
+use crate::consts::PENDING_CONNECTION_MAX_AGE_MS;
 use crate::consts::TRANSPORT_MTU;
 use crate::dht::Did;
 use crate::dht::LiveDid;
 use crate::dht::PeerRing;
+use crate::dht::SuccessorReader;
 use crate::error::Error;
 use crate::error::Result;
+use crate::measure::default_quality;
+use crate::measure::Measure;
+use crate::measure::MeasureCounter;
 use crate::measure::MeasureImpl;
+use crate::measure::MeasureSnapshot;
+use crate::measure::QualityFn;
+use crate::measure::QualityInput;
+use crate::message::decode_sdp;
+use crate::message::encode_frame;
+use crate::message::CompressionConfig;
+use crate::message::ConnectNodeRenegotiate;
 use crate::message::ConnectNodeReport;
 use crate::message::ConnectNodeSend;
+use crate::message::FileChunkAck;
+use crate::message::HandshakeCodec;
+use crate::message::IceCandidate;
 use crate::message::Message;
 use crate::message::MessagePayload;
+use crate::message::MessageVerificationExt;
 use crate::message::PayloadSender;
+use crate::message::Priority;
+use crate::message::Transaction;
 use crate::session::SessionSk;
 use crate::swarm::callback::InnerSwarmCallback;
+use crate::swarm::connect_metrics::ConnectMetrics;
+use crate::swarm::decode_failures::DecodeFailures;
+use crate::swarm::dedup::DedupWindow;
+use crate::swarm::file::FileReceiver;
+use crate::swarm::file::IncomingFile;
+use crate::swarm::nat::NatType;
+use crate::swarm::outbound::OutboundQueue;
+use crate::swarm::rate_limit::RateLimiter;
+use crate::swarm::reassembly::Reassembly;
+use crate::swarm::reconnect::ReconnectInput;
+use crate::swarm::reconnect::Reconnector;
+#[cfg(feature = "record")]
+use crate::swarm::record::Direction;
+#[cfg(feature = "record")]
+use crate::swarm::record::MessageRecorder;
+use crate::swarm::relay_metrics::RelayMetrics;
+use crate::swarm::transport_kind::AnyConnection;
+use crate::swarm::transport_kind::AnyTransport;
+use crate::swarm::transport_kind::SharedTransportFactory;
+use crate::swarm::transport_kind::TransportFactory;
+use crate::swarm::transport_kind::TransportKind;
+use crate::utils;
+use crate::utils::Clock;
+use crate::utils::SharedClock;
+use crate::utils::SystemClock;
+
+/// Interval of checking buffered amount when waiting for send buffer draining.
+const SEND_BUFFER_POLL_INTERVAL_MS: u64 = 20;
+/// Interval of checking connection state when waiting for it to be connected.
+const CONNECTION_STATE_POLL_INTERVAL_MS: u64 = 50;
+
+/// Backpressure policy applied before writing data to a data channel.
+///
+/// The buffered amount of a connection is the number of bytes queued in its data channels
+/// but not yet transmitted. Once it exceeds the high-water mark, keep sending will overflow
+/// the SCTP send buffer and close the channel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SendBufferPolicy {
+    /// Send without checking the buffered amount.
+    #[default]
+    Unbounded,
+    /// Return [Error::SendBufferFull] immediately when buffered amount exceeds `high_water_mark`.
+    Reject {
+        /// Max bytes allowed to be buffered.
+        high_water_mark: usize,
+    },
+    /// Wait for the buffered amount draining below `high_water_mark`.
+    /// Return [Error::SendBufferFull] if it's not drained in `timeout_ms`.
+    Wait {
+        /// Max bytes allowed to be buffered.
+        high_water_mark: usize,
+        /// Max time to wait in milliseconds.
+        timeout_ms: u64,
+    },
+}
+
+/// Whether handshake payloads given to [crate::swarm::Swarm::answer_offer] and
+/// [crate::swarm::Swarm::accept_answer] are verified before connecting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VerificationPolicy {
+    /// Reject payloads failing verification with [Error::VerifySignatureFailed].
+    #[default]
+    Strict,
+    /// Accept payloads without verifying them, for test harnesses and loopback setups which
+    /// don't sign sessions.
+    ///
+    /// It's insecure: anyone can forge an offer or answer in the name of any did, and the
+    /// connection is then bound to a peer that never signed it. Only available in tests or
+    /// with feature `trust_all`, never enable it in production.
+    #[cfg(any(test, feature = "trust_all"))]
+    TrustAll,
+}
+
+impl VerificationPolicy {
+    /// Check a handshake payload by the policy, with expiry checked by the time of `clock`.
+    pub(crate) fn verify(&self, payload: &MessagePayload, clock: &dyn Clock) -> bool {
+        match self {
+            Self::Strict => payload.verify_by(clock),
+            #[cfg(any(test, feature = "trust_all"))]
+            Self::TrustAll => true,
+        }
+    }
+}
+
+/// How messages to a peer are sent, see [crate::swarm::Swarm::route].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    /// Sent to the connection of the peer directly.
+    Direct,
+    /// Relayed by a hop set by [crate::swarm::Swarm::reroute].
+    Relay(Did),
+    /// Relayed by the next hop inferred by DHT.
+    Dht(Did),
+}
+
+/// Whether a peer can be reached now, see [crate::swarm::Swarm::is_reachable].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reachability {
+    /// Connected to the peer directly.
+    Direct,
+    /// Not connected, but the peer is known by a route set by [crate::swarm::Swarm::reroute]
+    /// or by DHT, and can be reached through these connected hops.
+    Relayed(Vec<Did>),
+    /// No route to the peer is known.
+    Unknown,
+}
+
+/// Permit of a handshake, see [SwarmTransport::acquire_connect_permit].
+struct ConnectPermit {
+    _guard: Option<SemaphoreGuardArc>,
+    in_progress: Arc<AtomicUsize>,
+}
+
+impl Drop for ConnectPermit {
+    fn drop(&mut self) {
+        self.in_progress.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Close the connection created by a handshake attempt when it's dropped before disarmed, such
+/// as when the future of [SwarmTransport::connect] is cancelled before its offer is sent, or
+/// creating the offer fails. A connection created by a later attempt of the same peer is kept.
+struct ConnectGuard<'a> {
+    transport: &'a SwarmTransport,
+    peer: Did,
+    attempt_id: uuid::Uuid,
+    armed: bool,
+}
+
+impl ConnectGuard<'_> {
+    fn disarm(mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for ConnectGuard<'_> {
+    fn drop(&mut self) {
+        if !self.armed || self.transport.connection_attempt(self.peer) != Some(self.attempt_id) {
+            return;
+        }
+        tracing::debug!(
+            target: "rings::handshake",
+            "handshake attempt {} to {} is cancelled, closing its connection",
+            self.attempt_id,
+            self.peer
+        );
+        self.transport.close_abandoned_connection(self.peer);
+    }
+}
 
 pub struct SwarmTransport {
     pub(crate) network_id: u32,
-    transport: Transport,
+    /// Shared with the closing of abandoned connections, see
+    /// [SwarmTransport::close_abandoned_connection].
+    transport: Arc<AnyTransport>,
+    /// Transports used for some peers instead of `transport`, see
+    /// [crate::swarm::SwarmBuilder::transport_factory] and [SwarmTransport::register_transports].
+    pub(crate) transport_factories: RwLock<HashMap<Did, SharedTransportFactory>>,
     session_sk: SessionSk,
     pub(crate) dht: Arc<PeerRing>,
-    #[allow(dead_code)]
     measure: Option<MeasureImpl>,
+    /// Scoring function of connection quality, [default_quality] is used if it's None.
+    pub(crate) quality_fn: Option<QualityFn>,
+    pub(crate) send_buffer_policy: SendBufferPolicy,
+    /// Compression of frames sent by this node, frames are sent uncompressed if it's None.
+    pub(crate) compression: Option<CompressionConfig>,
+    pub(crate) handshake_codec: HandshakeCodec,
+    /// Min duration of deciding to accept or reject a remote offer.
+    pub(crate) acceptance_delay: Option<Duration>,
+    /// Verification of handshake payloads given to swarm directly.
+    pub(crate) verification_policy: VerificationPolicy,
+    /// Connections without activity for this duration are closed, unless they're successors or
+    /// pinned.
+    pub(crate) idle_timeout: Option<Duration>,
+    /// Max size in bytes of frames sent or received, larger ones are rejected.
+    pub(crate) max_message_size: usize,
+    /// Recorder of inbound and outbound payloads, see [crate::swarm::record].
+    #[cfg(feature = "record")]
+    pub(crate) recorder: Option<Arc<MessageRecorder>>,
+    /// Limiter of handshakes in progress, no limit if None.
+    pub(crate) connect_limiter: Option<Arc<Semaphore>>,
+    /// Number of handshakes in progress.
+    connects_in_progress: Arc<AtomicUsize>,
+    /// Permits of handshakes in progress, released once their data channel opens or fails.
+    connect_permits: DashMap<Did, ConnectPermit>,
+    /// Clock of connection ages, idle timeouts, session expiry and subscription expiry.
+    pub(crate) clock: SharedClock,
+    /// Senders waiting for reply of a transaction, indexed by tx_id.
+    pending_replies: DashMap<uuid::Uuid, oneshot::Sender<MessagePayload>>,
+    /// Creation time of connections in milliseconds.
+    connection_created_at: DashMap<Did, u128>,
+    /// Time of the last frame sent or received by each connection in milliseconds.
+    last_activity: DashMap<Did, u128>,
+    /// Hops and latency of messages sent by this node and reported back.
+    pub(crate) relay_metrics: RelayMetrics,
+    /// Time taken by connection attempts until their data channel opens.
+    pub(crate) connect_metrics: ConnectMetrics,
+    /// Frames received from each peer which cannot be decoded.
+    pub(crate) decode_failures: DecodeFailures,
+    /// Chunked messages being reassembled from all connections.
+    pub(crate) reassembly: Reassembly<TRANSPORT_MTU>,
+    /// Creation time of connections whose data channel is not opened yet, in milliseconds.
+    connect_started_at: DashMap<Did, u128>,
+    /// Locks serializing offers and answers applied to the connection of each peer.
+    signaling_locks: DashMap<Did, Arc<AsyncMutex<()>>>,
+    /// Capabilities advertised to peers in handshake.
+    pub(crate) capabilities: Vec<String>,
+    /// Limiter of inbound messages from each origin sender, no limit if it's None.
+    pub(crate) rate_limiter: Option<RateLimiter>,
+    /// Capabilities supported by both sides of each connection, negotiated in handshake.
+    peer_capabilities: DashMap<Did, Vec<String>>,
+    /// Frames waiting to be sent to each connection.
+    outbound: DashMap<Did, Arc<OutboundQueue>>,
+    /// Id of the handshake attempt which created each connection.
+    connection_attempts: DashMap<Did, uuid::Uuid>,
+    /// Labels given to connections at creation, see [SwarmConnection::label].
+    connection_labels: DashMap<Did, String>,
+    /// Sessions replacing `session_sk` when signing payloads sent to each connection.
+    connection_sessions: DashMap<Did, SessionSk>,
+    /// Trickled ICE candidates of each peer, waiting for the remote description to be set,
+    /// with the time the first of them arrived.
+    pub(crate) pending_ice_candidates: DashMap<Did, (u128, Vec<IceCandidate>)>,
+    /// Peers whose remote description is set, so their ICE candidates can be added directly.
+    remote_described: DashSet<Did>,
+    /// Hop relaying messages to each destination when it's not connected directly.
+    routes: DashMap<Did, Did>,
+    /// Subscribers of topics this node is responsible for, with expiry time in milliseconds.
+    pub(crate) topic_subscribers: DashMap<String, HashMap<Did, u128>>,
+    /// Topics subscribed by this node, refreshed in stabilization.
+    pub(crate) subscriptions: DashSet<String>,
+    /// Receiver of files sent by peers, files are rejected if it's None.
+    pub(crate) file_receiver: Option<Arc<dyn FileReceiver>>,
+    /// Files being received, indexed by transfer id.
+    pub(crate) incoming_files: DashMap<uuid::Uuid, IncomingFile>,
+    /// Senders of acks to file transfers of this node, indexed by transfer id.
+    pub(crate) file_acks: DashMap<uuid::Uuid, mpsc::UnboundedSender<FileChunkAck>>,
+    /// Peers pinned by [crate::swarm::Swarm::pin], never closed for idleness and
+    /// reconnected in stabilization.
+    pinned: DashSet<Did>,
+    /// Reconnector of closed connections, never reconnected if it's None.
+    pub(crate) reconnector: Option<Reconnector>,
+    /// Peers whose data channel of the current connection has opened.
+    opened_channels: DashSet<Did>,
+    /// Number of renegotiations of the handshake with each peer, removed once it's opened.
+    renegotiations: DashMap<Did, u8>,
+    /// Ice servers of transport, whose STUN servers are used to detect NAT type.
+    pub(crate) ice_servers: String,
+    /// Detect NAT type when node starts listening, see [crate::swarm::Swarm::detect_nat].
+    pub(crate) detect_nat: bool,
+    /// NAT type detected by [crate::swarm::Swarm::detect_nat].
+    pub(crate) nat_type: RwLock<NatType>,
+    /// Max number of events buffered by each [crate::swarm::SwarmEvents].
+    pub(crate) event_channel_capacity: usize,
+    /// Max time for a message to wait for room in a full buffer of
+    /// [crate::swarm::OverflowMode::Block].
+    pub(crate) inbox_block_timeout: Duration,
+    /// Tx_ids of messages handled recently, to drop duplicates. Not deduplicated if None.
+    pub(crate) dedup_window: Option<DedupWindow>,
 }
 
 #[derive(Clone)]
 pub struct SwarmConnection {
     peer: Did,
-    pub connection: ConnectionRef<ConnectionOwner>,
+    pub connection: AnyConnection,
+    /// Id of the handshake attempt which created this connection.
+    /// It's the `attempt_id` field of the tracing spans of that handshake.
+    pub attempt_id: Option<uuid::Uuid>,
+    label: Option<String>,
+    /// Clock of swarm, to time the waiting of send buffer.
+    clock: SharedClock,
+}
+
+/// Turn the error of applying an sdp in a wrong signaling state, such as accepting an answer
+/// twice, into [Error::InvalidSignalingState].
+fn signaling_error(peer: Did, e: TransportError) -> Error {
+    match e {
+        TransportError::InvalidSignalingState(state) => Error::InvalidSignalingState(peer, state),
+        e => Error::Transport(e),
+    }
 }
 
 impl SwarmTransport {
@@ -58,53 +365,522 @@ impl SwarmTransport {
         network_id: u32,
         ice_servers: &str,
         external_address: Option<String>,
+        transport_kind: TransportKind,
         session_sk: SessionSk,
         dht: Arc<PeerRing>,
         measure: Option<MeasureImpl>,
     ) -> Self {
         Self {
             network_id,
-            transport: Transport::new(ice_servers, external_address),
+            transport: Arc::new(AnyTransport::new(
+                transport_kind,
+                ice_servers,
+                external_address,
+            )),
+            transport_factories: RwLock::new(HashMap::new()),
             session_sk,
             dht,
             measure,
+            quality_fn: None,
+            send_buffer_policy: SendBufferPolicy::default(),
+            compression: None,
+            handshake_codec: HandshakeCodec::default(),
+            acceptance_delay: None,
+            verification_policy: VerificationPolicy::default(),
+            idle_timeout: None,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            #[cfg(feature = "record")]
+            recorder: None,
+            connect_limiter: None,
+            connects_in_progress: Arc::new(AtomicUsize::new(0)),
+            connect_permits: DashMap::new(),
+            clock: Arc::new(SystemClock),
+            pending_replies: DashMap::new(),
+            rate_limiter: None,
+            connection_created_at: DashMap::new(),
+            last_activity: DashMap::new(),
+            relay_metrics: RelayMetrics::default(),
+            connect_metrics: ConnectMetrics::default(),
+            decode_failures: DecodeFailures::default(),
+            reassembly: Reassembly::default(),
+            connect_started_at: DashMap::new(),
+            signaling_locks: DashMap::new(),
+            capabilities: vec![],
+            peer_capabilities: DashMap::new(),
+            outbound: DashMap::new(),
+            connection_attempts: DashMap::new(),
+            connection_labels: DashMap::new(),
+            connection_sessions: DashMap::new(),
+            pending_ice_candidates: DashMap::new(),
+            remote_described: DashSet::new(),
+            routes: DashMap::new(),
+            topic_subscribers: DashMap::new(),
+            subscriptions: DashSet::new(),
+            file_receiver: None,
+            incoming_files: DashMap::new(),
+            file_acks: DashMap::new(),
+            pinned: DashSet::new(),
+            reconnector: None,
+            opened_channels: DashSet::new(),
+            renegotiations: DashMap::new(),
+            ice_servers: ice_servers.to_string(),
+            detect_nat: false,
+            nat_type: RwLock::new(NatType::default()),
+            event_channel_capacity: DEFAULT_EVENT_CHANNEL_CAPACITY,
+            inbox_block_timeout: Duration::from_millis(DEFAULT_INBOX_BLOCK_TIMEOUT_MS),
+            dedup_window: None,
+        }
+    }
+
+    /// Send a message to destination, then wait for the report message of the same transaction.
+    /// Return [Error::WaitReplyTimeout] if no reply arrived in `timeout_ms`.
+    pub(crate) async fn send_message_and_wait<T>(
+        &self,
+        msg: T,
+        destination: Did,
+        timeout_ms: u64,
+    ) -> Result<MessagePayload>
+    where
+        T: Serialize + Send,
+    {
+        let next_hop = self.infer_next_hop(destination, None)?;
+        let payload = MessagePayload::new_send(msg, &self.session_sk, next_hop, destination)?;
+        let tx_id = payload.transaction.tx_id;
+
+        // Register before sending, the reply may arrive before send_payload returns.
+        let (tx, rx) = oneshot::channel();
+        self.pending_replies.insert(tx_id, tx);
+        if let Err(e) = self.send_payload(payload).await {
+            self.pending_replies.remove(&tx_id);
+            return Err(e);
+        }
+
+        let timeout = utils::sleep(Duration::from_millis(timeout_ms));
+        futures::pin_mut!(timeout);
+        let result = futures::future::select(rx, timeout).await;
+        self.pending_replies.remove(&tx_id);
+
+        match result {
+            Either::Left((Ok(reply), _)) => Ok(reply),
+            _ => Err(Error::WaitReplyTimeout(tx_id)),
+        }
+    }
+
+    /// Split data to frames of chunks if it's larger than [TRANSPORT_MTU].
+    fn chunk_frames(&self, peer: Did, data: Bytes) -> Result<Vec<Bytes>> {
+        if data.len() <= TRANSPORT_MTU {
+            return Ok(vec![data]);
+        }
+        ChunkList::<TRANSPORT_MTU>::from(&data)
+            .into_iter()
+            .map(|chunk| {
+                let payload =
+                    MessagePayload::new_send(Message::Chunk(chunk), &self.session_sk, peer, peer)?;
+                encode_frame(&payload.to_bincode()?, None)
+            })
+            .collect()
+    }
+
+    /// Send a frame to the connection, once its send buffer allows.
+    async fn send_frame(&self, conn: &SwarmConnection, data: Bytes) -> Result<()> {
+        conn.apply_send_buffer_policy(self.send_buffer_policy)
+            .await?;
+        conn.send_data(data).await
+    }
+
+    /// Increase the counter of a peer, if measure is set.
+    pub(crate) async fn record_measure(&self, peer: Did, counter: MeasureCounter) {
+        if let Some(measure) = &self.measure {
+            if let Err(e) = measure.incr(peer, counter).await {
+                tracing::warn!(
+                    target: "rings::swarm",
+                    "Failed to record {counter:?} of {peer} in measure: {e:?}"
+                );
+            }
+        }
+    }
+
+    /// Increase the counter of a peer by `n`, if measure is set.
+    pub(crate) async fn record_measure_by(&self, peer: Did, counter: MeasureCounter, n: u64) {
+        if let Some(measure) = &self.measure {
+            if let Err(e) = measure.incr_by(peer, counter, n).await {
+                tracing::warn!(
+                    target: "rings::swarm",
+                    "Failed to record {n} {counter:?} of {peer} in measure: {e:?}"
+                );
+            }
+        }
+    }
+
+    /// Read all counters of a peer from measure. Return None if measure is not set.
+    pub async fn measure_snapshot(&self, peer: Did) -> Result<Option<MeasureSnapshot>> {
+        let Some(measure) = &self.measure else {
+            return Ok(None);
+        };
+        Ok(Some(MeasureSnapshot::read(measure.as_ref(), peer).await?))
+    }
+
+    /// Get statistics of the underlying transport of a connection.
+    /// Return None if there is no connection of the peer.
+    pub async fn connection_stats(&self, peer: Did) -> Option<ConnectionStats> {
+        Some(self.get_connection(peer)?.stats().await)
+    }
+
+    /// Score the connection of a peer in 0.0..=1.0 by `quality_fn`.
+    /// Return None if there is no connection of the peer.
+    pub async fn connection_quality(&self, peer: Did) -> Option<f64> {
+        let conn = self.get_connection(peer)?;
+
+        // Counters unavailable in measure are regarded as nothing sent.
+        let (sent, failed_to_send) = match &self.measure {
+            Some(measure) => {
+                let counts = (
+                    measure.get_count(peer, MeasureCounter::Sent).await,
+                    measure.get_count(peer, MeasureCounter::FailedToSend).await,
+                );
+                match counts {
+                    (Ok(sent), Ok(failed_to_send)) => (sent, failed_to_send),
+                    (Err(e), _) | (_, Err(e)) => {
+                        tracing::warn!(
+                            target: "rings::swarm",
+                            "Failed to get counters of {peer} from measure: {e:?}"
+                        );
+                        (0, 0)
+                    }
+                }
+            }
+            None => (0, 0),
+        };
+        let input = QualityInput {
+            state: conn.webrtc_connection_state(),
+            sent,
+            failed_to_send,
+        };
+
+        let score = match &self.quality_fn {
+            Some(f) => f(&input),
+            None => default_quality(&input),
+        };
+        Some(score.clamp(0.0, 1.0))
+    }
+
+    /// Record hops and latency of a payload reporting a message sent by this node.
+    pub(crate) fn record_relay_report(&self, payload: &MessagePayload) {
+        self.relay_metrics.on_report(
+            payload.transaction.tx_id,
+            payload.relay.path.len(),
+            self.clock.now_ms(),
+        );
+    }
+
+    /// Deliver a payload to the one waiting for its transaction.
+    /// Return false if no one is waiting for it.
+    pub(crate) fn resolve_pending_reply(&self, payload: &MessagePayload) -> bool {
+        match self.pending_replies.remove(&payload.transaction.tx_id) {
+            Some((_, tx)) => tx.send(payload.clone()).is_ok(),
+            None => false,
         }
     }
 
-    /// Create new connection that will be handled by swarm.
-    pub async fn new_connection(&self, peer: Did, callback: InnerSwarmCallback) -> Result<()> {
+    /// Run an acceptance decision and pad its duration to `acceptance_delay` if it's set.
+    /// Both accepting and rejecting will take approximately the same time, so that the timing
+    /// will not leak the reason of decision. Decisions slower than the delay are not affected.
+    pub(crate) async fn with_acceptance_delay<T, F>(&self, decision: F) -> Result<T>
+    where F: Future<Output = Result<T>> {
+        let Some(delay) = self.acceptance_delay else {
+            return decision.await;
+        };
+
+        let start = self.clock.now_ms();
+        let result = decision.await;
+        let elapsed = self.clock.now_ms().saturating_sub(start) as u64;
+        let target = delay.as_millis() as u64;
+        if elapsed < target {
+            utils::sleep(Duration::from_millis(target - elapsed)).await;
+        }
+        result
+    }
+
+    /// Create new connection that will be handled by swarm, named by an optional `label`.
+    pub async fn new_connection(
+        &self,
+        peer: Did,
+        callback: InnerSwarmCallback,
+        label: Option<String>,
+    ) -> Result<()> {
         if peer == self.dht.did {
             return Ok(());
         }
 
+        // Keep the healthy connection, a late connection of the same peer is discarded.
+        if self.is_connected(peer) {
+            tracing::debug!(
+                target: "rings::handshake",
+                "discard new connection of {peer}, it's already connected"
+            );
+            return Err(Error::AlreadyConnected);
+        }
+
         let cid = peer.to_string();
-        self.transport
-            .new_connection(&cid, Box::new(callback))
+        let callback = Box::new(callback);
+        let created = match self.transport_factory(peer) {
+            Some(factory) => factory.new_connection(&cid, callback).await,
+            None => self.transport.new_connection(&cid, callback).await,
+        };
+        created.map_err(Error::Transport)?;
+        self.remote_described.remove(&peer);
+        self.opened_channels.remove(&peer);
+        let now = self.clock.now_ms();
+        self.connection_created_at.insert(peer, now);
+        self.connect_started_at.insert(peer, now);
+        if let Some(label) = label {
+            self.connection_labels.insert(peer, label);
+        } else {
+            self.connection_labels.remove(&peer);
+        }
+        Ok(())
+    }
+
+    /// List peers whose connections are still being established after `age`.
+    pub fn pending_connections_older_than(&self, age: Duration) -> Vec<Did> {
+        self.pending_connections_older_than_at(age, self.clock.now_ms())
+    }
+
+    pub(crate) fn pending_connections_older_than_at(&self, age: Duration, now: u128) -> Vec<Did> {
+        self.get_connections()
+            .into_iter()
+            .filter(|(_, conn)| {
+                matches!(
+                    conn.webrtc_connection_state(),
+                    WebrtcConnectionState::New | WebrtcConnectionState::Connecting
+                )
+            })
+            .filter(|(did, _)| {
+                self.connection_created_at
+                    .get(did)
+                    .map(|created| *created + age.as_millis() <= now)
+                    .unwrap_or(false)
+            })
+            .map(|(did, _)| did)
+            .collect()
+    }
+
+    /// Close connections that are still being established after `max_age`, such as offers
+    /// never answered. Return the peers of closed connections.
+    pub async fn gc_pending_connections(&self, max_age: Duration) -> Result<Vec<Did>> {
+        self.gc_pending_connections_at(max_age, self.clock.now_ms())
             .await
-            .map_err(Error::Transport)
+    }
+
+    pub(crate) async fn gc_pending_connections_at(
+        &self,
+        max_age: Duration,
+        now: u128,
+    ) -> Result<Vec<Did>> {
+        let stale = self.pending_connections_older_than_at(max_age, now);
+        for did in stale.iter() {
+            tracing::info!(
+                target: "rings::swarm",
+                "closing pending connection of {did}, it's older than {max_age:?}"
+            );
+            self.disconnect(*did).await?;
+        }
+        Ok(stale)
+    }
+
+    /// Record activity of the connection of peer, which is a frame sent or received.
+    pub(crate) fn touch_connection(&self, peer: Did) {
+        self.last_activity.insert(peer, self.clock.now_ms());
+    }
+
+    /// List connected peers without activity for `idle_timeout`, except successors in DHT
+    /// and pinned peers.
+    pub(crate) fn idle_connections_at(
+        &self,
+        idle_timeout: Duration,
+        now: u128,
+    ) -> Result<Vec<Did>> {
+        let successors = self.dht.successors().list()?;
+        Ok(self
+            .get_connections()
+            .into_iter()
+            .filter(|(did, conn)| {
+                conn.webrtc_connection_state() == WebrtcConnectionState::Connected
+                    && !successors.contains(did)
+                    && !self.pinned.contains(did)
+            })
+            .filter(|(did, _)| {
+                self.last_activity
+                    .get(did)
+                    .or_else(|| self.connection_created_at.get(did))
+                    .map(|last| *last + idle_timeout.as_millis() <= now)
+                    .unwrap_or(false)
+            })
+            .map(|(did, _)| did)
+            .collect())
+    }
+
+    /// Close connections idle longer than [SwarmTransport::idle_timeout], except successors
+    /// and pinned peers. Return the peers of closed connections.
+    pub(crate) async fn disconnect_idle_connections_at(&self, now: u128) -> Result<Vec<Did>> {
+        let Some(idle_timeout) = self.idle_timeout else {
+            return Ok(vec![]);
+        };
+        let idle = self.idle_connections_at(idle_timeout, now)?;
+        for did in idle.iter() {
+            tracing::info!(
+                target: "rings::swarm",
+                "closing connection of {did}, it's idle longer than {idle_timeout:?}"
+            );
+            self.disconnect(*did).await?;
+        }
+        Ok(idle)
+    }
+
+    /// Pin a peer, so that its connection is kept. Return false if it's already pinned.
+    pub(crate) fn pin(&self, peer: Did) -> bool {
+        self.pinned.insert(peer)
+    }
+
+    /// Unpin a peer. Return false if it's not pinned.
+    pub(crate) fn unpin(&self, peer: Did) -> bool {
+        self.pinned.remove(&peer).is_some()
+    }
+
+    /// List pinned peers.
+    pub(crate) fn pinned_peers(&self) -> Vec<Did> {
+        self.pinned.iter().map(|did| *did).collect()
+    }
+
+    /// Schedule reconnecting peer whose connection is closed by `state`, if it's wanted by
+    /// [crate::swarm::ReconnectPolicy]. Connections closed by [SwarmTransport::disconnect] and
+    /// handshakes whose data channel never opened are skipped.
+    pub(crate) fn schedule_reconnect(&self, peer: Did, state: WebrtcConnectionState) {
+        let Some(reconnector) = &self.reconnector else {
+            return;
+        };
+        if !self.opened_channels.contains(&peer) {
+            return;
+        }
+        let input = ReconnectInput {
+            peer,
+            state,
+            is_successor: self.dht.successors().contains(&peer).unwrap_or(false),
+            is_pinned: self.pinned.contains(&peer),
+        };
+        if reconnector.schedule(&input, self.clock.now_ms()) {
+            tracing::info!(
+                target: "rings::swarm",
+                "connection of {peer} is {state:?}, will reconnect"
+            );
+        }
+    }
+
+    /// Take peers whose reconnecting is due, see [Reconnector::take_due].
+    pub(crate) fn take_due_reconnects(&self) -> Vec<Did> {
+        self.reconnector
+            .as_ref()
+            .map(|r| r.take_due(self.clock.now_ms()))
+            .unwrap_or_default()
+    }
+
+    /// Get the [TransportFactory] registered for peer, None if the default one is used.
+    fn transport_factory(&self, peer: Did) -> Option<SharedTransportFactory> {
+        self.transport_factories
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&peer)
+            .cloned()
+    }
+
+    /// Create the connection to each peer of `entries` by its factory from now on, like
+    /// [crate::swarm::SwarmBuilder::transport_factory]. The table of factories is locked once
+    /// for all entries. A connection of peer created by the transport used before is
+    /// displaced, so it's closed.
+    /// Return each peer with whether a connection of it is closed, in the order of entries.
+    pub async fn register_transports(
+        &self,
+        entries: Vec<(Did, SharedTransportFactory)>,
+    ) -> Vec<(Did, bool)> {
+        let displaced = {
+            let mut factories = self
+                .transport_factories
+                .write()
+                .unwrap_or_else(|e| e.into_inner());
+            entries
+                .into_iter()
+                .map(|(peer, factory)| (peer, factories.insert(peer, factory)))
+                .collect::<Vec<_>>()
+        };
+
+        let mut registered = vec![];
+        for (peer, previous) in displaced {
+            let cid = peer.to_string();
+            let connected = match &previous {
+                Some(factory) => factory.connection(&cid).is_ok(),
+                None => self.transport.connection(&cid).is_ok(),
+            };
+            if connected {
+                self.forget_connection(peer);
+                let closed = match previous {
+                    Some(factory) => factory.close_connection(&cid).await,
+                    None => self.transport.close_connection(&cid).await,
+                };
+                if let Err(e) = closed {
+                    tracing::warn!(target: "rings::swarm", "Failed on close connection {cid}: {e:?}");
+                }
+            }
+            registered.push((peer, connected));
+        }
+        registered
+    }
+
+    /// Get the connection of peer from the transport which created it.
+    fn transport_connection(&self, peer: Did) -> TransportResult<AnyConnection> {
+        let cid = peer.to_string();
+        match self.transport_factory(peer) {
+            Some(factory) => factory.connection(&cid),
+            None => self.transport.connection(&cid),
+        }
     }
 
     /// Get connection by did.
     pub fn get_connection(&self, peer: Did) -> Option<SwarmConnection> {
-        self.transport
-            .connection(&peer.to_string())
+        self.transport_connection(peer)
             .map(|conn| SwarmConnection {
                 peer,
                 connection: conn,
+                attempt_id: self.connection_attempt(peer),
+                label: self.connection_label(peer),
+                clock: self.clock.clone(),
             })
             .ok()
     }
 
-    /// Get all connections in transport.
+    /// Get all connections in transport, including the ones of registered transport factories.
     pub fn get_connections(&self) -> Vec<(Did, SwarmConnection)> {
+        let registered = self
+            .transport_factories
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter_map(|(peer, factory)| {
+                let cid = peer.to_string();
+                factory.connection(&cid).ok().map(|c| (cid, c))
+            })
+            .collect::<Vec<_>>();
         self.transport
             .connections()
             .into_iter()
+            .chain(registered)
             .filter_map(|(k, v)| {
                 Did::from_str(&k).ok().map(|did| {
                     (did, SwarmConnection {
                         peer: did,
                         connection: v,
+                        attempt_id: self.connection_attempt(did),
+                        label: self.connection_label(did),
+                        clock: self.clock.clone(),
                     })
                 })
             })
@@ -113,10 +889,9 @@ impl SwarmTransport {
 
     /// Get dids of all connections in transport.
     pub fn get_connection_ids(&self) -> Vec<Did> {
-        self.transport
-            .connection_ids()
+        self.get_connections()
             .into_iter()
-            .filter_map(|k| Did::from_str(&k).ok())
+            .map(|(did, _)| did)
             .collect()
     }
 
@@ -125,23 +900,165 @@ impl SwarmTransport {
     /// 2) remove from Transport;
     /// 3) close the connection;
     pub async fn disconnect(&self, peer: Did) -> Result<()> {
-        tracing::info!("removing {peer} from DHT");
+        tracing::info!(target: "rings::swarm", "removing {peer} from DHT");
         self.dht.remove(peer)?;
-        self.transport
-            .close_connection(&peer.to_string())
+        self.forget_connection(peer);
+        self.close_transport_connection(peer)
             .await
             .map_err(|e| e.into())
     }
 
+    /// Close the connection of peer by the transport which created it.
+    async fn close_transport_connection(&self, peer: Did) -> TransportResult<()> {
+        let cid = peer.to_string();
+        match self.transport_factory(peer) {
+            Some(factory) => factory.close_connection(&cid).await,
+            None => self.transport.close_connection(&cid).await,
+        }
+    }
+
+    /// Remove the states kept for the connection of peer.
+    fn forget_connection(&self, peer: Did) {
+        self.peer_capabilities.remove(&peer);
+        self.connection_created_at.remove(&peer);
+        self.last_activity.remove(&peer);
+        self.outbound.remove(&peer);
+        self.connection_attempts.remove(&peer);
+        self.connection_labels.remove(&peer);
+        self.connection_sessions.remove(&peer);
+        self.pending_ice_candidates.remove(&peer);
+        self.remote_described.remove(&peer);
+        self.opened_channels.remove(&peer);
+        self.signaling_locks.remove(&peer);
+        self.record_connect_latency(peer, false);
+        self.release_connect_permit(peer);
+        self.decode_failures.reset(peer);
+    }
+
+    /// Lock the signaling of the connection to peer, so that offers and answers of it are
+    /// applied one by one instead of interleaving.
+    async fn lock_signaling(&self, peer: Did) -> MutexGuardArc<()> {
+        let lock = self.signaling_locks.entry(peer).or_default().clone();
+        lock.lock_arc().await
+    }
+
+    /// Close a connection whose handshake is abandoned, see [ConnectGuard].
+    /// It can't wait for closing since it's called on drop. The closing is polled once in
+    /// place, which removes the connection from transport, so that a later attempt of the same
+    /// peer is not closed by it. The rest of closing is spawned.
+    fn close_abandoned_connection(&self, peer: Did) {
+        self.forget_connection(peer);
+        let cid = peer.to_string();
+        let factory = self.transport_factory(peer);
+        let transport = self.transport.clone();
+        let mut closing = Box::pin(async move {
+            let closed = match factory {
+                Some(factory) => factory.close_connection(&cid).await,
+                None => transport.close_connection(&cid).await,
+            };
+            if let Err(e) = closed {
+                tracing::warn!(target: "rings::handshake", "Failed on close connection {cid}: {e:?}");
+            }
+        });
+        if (&mut closing).now_or_never().is_none() {
+            utils::spawn(closing);
+        }
+    }
+
+    /// Get the default transport to configure it, which is only possible before it's shared.
+    fn transport_mut(&mut self) -> &mut AnyTransport {
+        Arc::get_mut(&mut self.transport).expect("transport is configured before it's shared")
+    }
+
+    /// Sign payloads sent to the connection of peer by `session_sk` from now on.
+    /// The connection is kept open, payloads already queued keep their old signatures.
+    /// The session should be of the same account as this node.
+    pub fn rekey_connection(&self, peer: Did, session_sk: SessionSk) -> Result<()> {
+        if session_sk.account_did() != self.session_sk.account_did() {
+            return Err(Error::SessionAccountMismatch(session_sk.account_did()));
+        }
+        if session_sk.session().is_expired_by(self.clock.as_ref()) {
+            return Err(Error::SessionExpired);
+        }
+        if self.get_connection(peer).is_none() {
+            return Err(Error::SwarmMissDidInTable(peer));
+        }
+        self.connection_sessions.insert(peer, session_sk);
+        Ok(())
+    }
+
+    /// Re-sign payload by the session of connection if it's rekeyed.
+    /// The transaction is also re-signed if it's created by this node.
+    fn sign_for_connection(&self, did: Did, payload: MessagePayload) -> Result<MessagePayload> {
+        let Some(session_sk) = self.connection_sessions.get(&did) else {
+            return Ok(payload);
+        };
+
+        let mut transaction = payload.transaction;
+        if transaction.verification.session == self.session_sk.session() {
+            transaction = Transaction::new_with_expiry(
+                transaction.destination,
+                transaction.tx_id,
+                transaction.data,
+                transaction.expires_at,
+                &session_sk,
+            )?;
+        }
+        MessagePayload::new(transaction, &session_sk, payload.relay)
+    }
+
     /// Connect a given Did. If the did is already connected, return Err,
     /// else try prepare offer and establish connection by dht.
-    pub async fn connect(&self, peer: Did, callback: InnerSwarmCallback) -> Result<()> {
-        let offer_msg = self.prepare_connection_offer(peer, callback).await?;
-        self.send_message(Message::ConnectNodeSend(offer_msg), peer)
+    /// The connection is named by `label` if it's given.
+    pub async fn connect(
+        &self,
+        peer: Did,
+        callback: InnerSwarmCallback,
+        label: Option<String>,
+    ) -> Result<()> {
+        // If this future is dropped before the offer is sent, the connection is closed by guard.
+        // A failure of sending keeps the connection like other pending ones.
+        let (offer_msg, guard) = self
+            .prepare_guarded_connection_offer(peer, callback, label)
             .await?;
+        let sent = self
+            .send_message(Message::ConnectNodeSend(offer_msg), peer)
+            .await;
+        guard.disarm();
+        if sent.is_err() {
+            // The handshake can't go on, its connection is left for gc.
+            self.release_connect_permit(peer);
+        }
+        sent?;
         Ok(())
     }
 
+    /// Connect a given Did if it's not connected yet, then wait for its data channel to open.
+    /// Return [Error::WaitConnectionTimeout] if it's not open in `timeout_ms`.
+    pub async fn connect_and_wait(
+        &self,
+        peer: Did,
+        callback: InnerSwarmCallback,
+        timeout_ms: u64,
+    ) -> Result<()> {
+        if self.get_connection(peer).is_none() {
+            self.connect(peer, callback, None).await?;
+        }
+        let conn = self
+            .get_connection(peer)
+            .ok_or(Error::WaitConnectionTimeout(peer))?;
+
+        let open = conn.connection.webrtc_wait_for_data_channel_open();
+        let timeout = utils::sleep(Duration::from_millis(timeout_ms));
+        futures::pin_mut!(open);
+        futures::pin_mut!(timeout);
+
+        match futures::future::select(open, timeout).await {
+            Either::Left((res, _)) => res.map_err(Error::Transport),
+            Either::Right(_) => Err(Error::WaitConnectionTimeout(peer)),
+        }
+    }
+
     /// Get connection by did and check if data channel is open.
     /// This method will return None if the connection is not found.
     /// This method will wait_for_data_channel_open.
@@ -156,11 +1073,12 @@ impl SwarmTransport {
 
         if let Err(e) = conn.connection.webrtc_wait_for_data_channel_open().await {
             tracing::warn!(
+                target: "rings::swarm",
                 "[get_and_check_connection] connection {peer} data channel not open, will be dropped, reason: {e:?}"
             );
 
             if let Err(e) = self.disconnect(peer).await {
-                tracing::error!("Failed on close connection {peer}: {e:?}");
+                tracing::error!(target: "rings::swarm", "Failed on close connection {peer}: {e:?}");
             }
 
             return None;
@@ -169,30 +1087,175 @@ impl SwarmTransport {
         Some(conn)
     }
 
+    /// Wait for a permit of [SwarmTransport::connect_limiter] before creating a connection and
+    /// its offer or answer. The permit is kept by [SwarmTransport::keep_connect_permit] once
+    /// the connection is created, or released when it's dropped before that.
+    async fn acquire_connect_permit(&self) -> ConnectPermit {
+        let guard = match &self.connect_limiter {
+            Some(limiter) => Some(limiter.acquire_arc().await),
+            None => None,
+        };
+        self.connects_in_progress.fetch_add(1, Ordering::SeqCst);
+        ConnectPermit {
+            _guard: guard,
+            in_progress: self.connects_in_progress.clone(),
+        }
+    }
+
+    /// Keep the permit of the handshake of peer until its data channel opens or fails, or
+    /// its connection is closed. A permit of a former handshake of the peer is released.
+    fn keep_connect_permit(&self, peer: Did, permit: ConnectPermit) {
+        self.connect_permits.insert(peer, permit);
+    }
+
+    /// Release the permit of the handshake of peer, see [SwarmTransport::keep_connect_permit].
+    pub(crate) fn release_connect_permit(&self, peer: Did) {
+        self.connect_permits.remove(&peer);
+    }
+
+    /// Write payload to [SwarmTransport::recorder] if it's set, see [MessageRecorder::record].
+    /// Failures are only logged.
+    #[cfg(feature = "record")]
+    pub(crate) fn record_payload(
+        &self,
+        direction: Direction,
+        peer: Did,
+        payload: &MessagePayload,
+        decrypted: Option<&MessagePayload>,
+    ) {
+        let Some(recorder) = &self.recorder else {
+            return;
+        };
+        if let Err(e) = recorder.record(self.clock.now_ms(), direction, peer, payload, decrypted) {
+            tracing::warn!(target: "rings::swarm", "Failed to record payload of {peer}: {e:?}");
+        }
+    }
+
+    /// Number of handshakes in progress, from creating their connection until their data
+    /// channel opens or fails.
+    pub fn connects_in_progress(&self) -> usize {
+        self.connects_in_progress.load(Ordering::SeqCst)
+    }
+
+    /// Get id of the handshake attempt which created the connection of peer.
+    pub(crate) fn connection_attempt(&self, peer: Did) -> Option<uuid::Uuid> {
+        self.connection_attempts.get(&peer).map(|id| *id)
+    }
+
+    /// Get the label given to the connection of peer at creation.
+    pub(crate) fn connection_label(&self, peer: Did) -> Option<String> {
+        self.connection_labels.get(&peer).map(|label| label.clone())
+    }
+
     /// Create new connection and its offer.
+    /// A new attempt id is generated and carried by the offer, so that logs of the answer and
+    /// accept on both sides can be correlated.
     pub async fn prepare_connection_offer(
         &self,
         peer: Did,
         callback: InnerSwarmCallback,
+        label: Option<String>,
     ) -> Result<ConnectNodeSend> {
+        let (offer_msg, guard) = self
+            .prepare_guarded_connection_offer(peer, callback, label)
+            .await?;
+        guard.disarm();
+        Ok(offer_msg)
+    }
+
+    /// Create new connection and its offer like [SwarmTransport::prepare_connection_offer],
+    /// with a [ConnectGuard] of the connection, which is armed until the offer is sent.
+    #[tracing::instrument(
+        target = "rings::handshake",
+        skip(self, callback),
+        fields(peer = %peer, attempt_id = tracing::field::Empty)
+    )]
+    async fn prepare_guarded_connection_offer(
+        &self,
+        peer: Did,
+        callback: InnerSwarmCallback,
+        label: Option<String>,
+    ) -> Result<(ConnectNodeSend, ConnectGuard<'_>)> {
         if self.get_and_check_connection(peer).await.is_some() {
             return Err(Error::AlreadyConnected);
         };
 
-        self.new_connection(peer, callback).await?;
-        let conn = self
-            .transport
-            .connection(&peer.to_string())
-            .map_err(Error::Transport)?;
+        let attempt_id = uuid::Uuid::new_v4();
+        tracing::Span::current().record("attempt_id", tracing::field::display(attempt_id));
+        tracing::debug!(target: "rings::handshake", "preparing offer");
+
+        let permit = self.acquire_connect_permit().await;
+        self.new_connection(peer, callback, label).await?;
+        self.keep_connect_permit(peer, permit);
+        self.connection_attempts.insert(peer, attempt_id);
+        let guard = ConnectGuard {
+            transport: self,
+            peer,
+            attempt_id,
+            armed: true,
+        };
+        let conn = self.transport_connection(peer).map_err(Error::Transport)?;
 
-        let offer = conn.webrtc_create_offer().await.map_err(Error::Transport)?;
-        let offer_str = serde_json::to_string(&offer).map_err(|_| Error::SerializeToString)?;
+        let _signaling = self.lock_signaling(peer).await;
+        let offer = conn
+            .webrtc_create_offer()
+            .await
+            .map_err(|e| signaling_error(peer, e))?;
         let offer_msg = ConnectNodeSend {
-            sdp: offer_str,
+            sdp: self.handshake_codec.encode(&offer)?,
             network_id: self.network_id,
+            capabilities: self.capabilities.clone(),
+            attempt_id: Some(attempt_id),
+            ice_restart: false,
         };
 
-        Ok(offer_msg)
+        Ok((offer_msg, guard))
+    }
+
+    /// Create an ICE restart offer of the existing connection of peer.
+    /// Like [SwarmTransport::prepare_connection_offer], a new attempt id is carried by it.
+    #[tracing::instrument(
+        target = "rings::handshake",
+        skip(self),
+        fields(peer = %peer, attempt_id = tracing::field::Empty)
+    )]
+    pub async fn prepare_ice_restart_offer(&self, peer: Did) -> Result<ConnectNodeSend> {
+        let conn = self
+            .get_connection(peer)
+            .ok_or(Error::SwarmMissDidInTable(peer))?;
+
+        let attempt_id = uuid::Uuid::new_v4();
+        tracing::Span::current().record("attempt_id", tracing::field::display(attempt_id));
+        tracing::debug!(target: "rings::handshake", "preparing ice restart offer");
+
+        let _signaling = self.lock_signaling(peer).await;
+        let offer = conn
+            .connection
+            .webrtc_restart_ice()
+            .await
+            .map_err(|e| signaling_error(peer, e))?;
+        self.connection_attempts.insert(peer, attempt_id);
+
+        Ok(ConnectNodeSend {
+            sdp: self.handshake_codec.encode(&offer)?,
+            network_id: self.network_id,
+            capabilities: self.capabilities.clone(),
+            attempt_id: Some(attempt_id),
+            ice_restart: true,
+        })
+    }
+
+    /// Wait until the connection of peer is connected, such as after an ICE restart.
+    /// Return [Error::WaitConnectionTimeout] if it's not connected in `timeout_ms`.
+    pub(crate) async fn wait_connected(&self, peer: Did, timeout_ms: u64) -> Result<()> {
+        let deadline = self.clock.now_ms() + timeout_ms as u128;
+        while !self.is_connected(peer) {
+            if self.get_connection(peer).is_none() || self.clock.now_ms() >= deadline {
+                return Err(Error::WaitConnectionTimeout(peer));
+            }
+            utils::sleep(Duration::from_millis(CONNECTION_STATE_POLL_INTERVAL_MS)).await;
+        }
+        Ok(())
     }
 
     /// Answer the offer of remote connection.
@@ -202,7 +1265,47 @@ impl SwarmTransport {
         callback: InnerSwarmCallback,
         offer_msg: &ConnectNodeSend,
     ) -> Result<ConnectNodeReport> {
-        let offer = serde_json::from_str(&offer_msg.sdp).map_err(Error::Deserialize)?;
+        self.with_acceptance_delay(self.do_answer_remote_connection(peer, callback, offer_msg))
+            .await
+    }
+
+    #[tracing::instrument(
+        name = "answer_remote_connection",
+        target = "rings::handshake",
+        skip(self, callback, offer_msg),
+        fields(peer = %peer, attempt_id = tracing::field::Empty)
+    )]
+    async fn do_answer_remote_connection(
+        &self,
+        peer: Did,
+        callback: InnerSwarmCallback,
+        offer_msg: &ConnectNodeSend,
+    ) -> Result<ConnectNodeReport> {
+        // Offers of old nodes have no attempt id, a local one is used then.
+        let attempt_id = offer_msg.attempt_id.unwrap_or_else(uuid::Uuid::new_v4);
+        tracing::Span::current().record("attempt_id", tracing::field::display(attempt_id));
+        tracing::debug!(target: "rings::handshake", "answering offer");
+
+        let offer = decode_sdp(&offer_msg.sdp)?;
+
+        // An ICE restart offer renegotiates the existing connection.
+        if offer_msg.ice_restart {
+            let conn = self
+                .get_connection(peer)
+                .ok_or(Error::SwarmMissDidInTable(peer))?;
+            self.connection_attempts.insert(peer, attempt_id);
+            let _signaling = self.lock_signaling(peer).await;
+            let answer = conn
+                .connection
+                .webrtc_answer_offer(offer)
+                .await
+                .map_err(|e| signaling_error(peer, e))?;
+            return Ok(ConnectNodeReport {
+                sdp: self.handshake_codec.encode(&answer)?,
+                capabilities: self.capabilities.clone(),
+                attempt_id: Some(attempt_id),
+            });
+        }
 
         if let Some(swarm_conn) = self.get_connection(peer) {
             // Solve the scenario of creating offers simultaneously.
@@ -227,40 +1330,345 @@ impl SwarmTransport {
             };
         };
 
-        self.new_connection(peer, callback).await?;
-        let conn = self
-            .transport
-            .connection(&peer.to_string())
-            .map_err(Error::Transport)?;
+        let permit = self.acquire_connect_permit().await;
+        self.new_connection(peer, callback, None).await?;
+        self.keep_connect_permit(peer, permit);
+        self.connection_attempts.insert(peer, attempt_id);
 
-        let answer = conn
-            .webrtc_answer_offer(offer)
-            .await
-            .map_err(Error::Transport)?;
-        let answer_str = serde_json::to_string(&answer).map_err(|_| Error::SerializeToString)?;
-        let answer_msg = ConnectNodeReport { sdp: answer_str };
+        let signaling = self.lock_signaling(peer).await;
+        let answered = match self.transport_connection(peer) {
+            Ok(conn) => conn
+                .webrtc_answer_offer(offer)
+                .await
+                .map_err(|e| signaling_error(peer, e)),
+            Err(e) => Err(Error::Transport(e)),
+        };
+        drop(signaling);
+        let answer = match answered {
+            Ok(answer) => answer,
+            Err(e) => {
+                self.release_connect_permit(peer);
+                return Err(e);
+            }
+        };
+        self.on_remote_described(peer).await;
+        let answer_msg = ConnectNodeReport {
+            sdp: self.handshake_codec.encode(&answer)?,
+            capabilities: self.capabilities.clone(),
+            attempt_id: Some(attempt_id),
+        };
+        self.negotiate_capabilities(peer, &offer_msg.capabilities);
 
         Ok(answer_msg)
     }
 
     /// Accept the answer of remote connection.
+    #[tracing::instrument(
+        target = "rings::handshake",
+        skip(self, answer_msg),
+        fields(peer = %peer, attempt_id = tracing::field::Empty)
+    )]
     pub async fn accept_remote_connection(
         &self,
         peer: Did,
         answer_msg: &ConnectNodeReport,
     ) -> Result<()> {
-        let answer = serde_json::from_str(&answer_msg.sdp).map_err(Error::Deserialize)?;
+        if let Some(attempt_id) = self.connection_attempt(peer) {
+            tracing::Span::current().record("attempt_id", tracing::field::display(attempt_id));
+            if answer_msg.attempt_id.is_some_and(|id| id != attempt_id) {
+                tracing::warn!(
+                    target: "rings::handshake",
+                    "answer of another attempt {:?}",
+                    answer_msg.attempt_id
+                );
+            }
+        }
+        tracing::debug!(target: "rings::handshake", "accepting answer");
 
-        let conn = self
-            .transport
-            .connection(&peer.to_string())
-            .map_err(Error::Transport)?;
+        let answer = decode_sdp(&answer_msg.sdp)?;
+
+        let conn = self.transport_connection(peer).map_err(Error::Transport)?;
+        let signaling = self.lock_signaling(peer).await;
         conn.webrtc_accept_answer(answer)
             .await
-            .map_err(Error::Transport)?;
+            .map_err(|e| signaling_error(peer, e))?;
+        drop(signaling);
+        self.on_remote_described(peer).await;
+        self.negotiate_capabilities(peer, &answer_msg.capabilities);
 
         Ok(())
     }
+
+    /// Mark the data channel of peer opened, which finishes the handshake.
+    pub(crate) fn on_channel_opened(&self, peer: Did) {
+        self.opened_channels.insert(peer);
+        self.release_connect_permit(peer);
+        self.renegotiations.remove(&peer);
+        if let Some(reconnector) = &self.reconnector {
+            reconnector.cancel(peer);
+        }
+        self.record_connect_latency(peer, true);
+    }
+
+    /// Whether the data channel of peer is opened, even if its connection failed later.
+    pub(crate) fn is_channel_opened(&self, peer: Did) -> bool {
+        self.opened_channels.contains(&peer)
+    }
+
+    /// Record the time taken by the connection attempt of peer, if its data channel is not
+    /// opened before. An attempt is recorded only once, when it succeeds or fails first.
+    pub(crate) fn record_connect_latency(&self, peer: Did, succeeded: bool) {
+        if let Some((_, started)) = self.connect_started_at.remove(&peer) {
+            self.connect_metrics
+                .observe(started, self.clock.now_ms(), succeeded);
+        }
+    }
+
+    /// Whether the connection of peer is created by a handshake whose data channel is not
+    /// opened yet.
+    pub(crate) fn is_handshaking(&self, peer: Did) -> bool {
+        self.connection_attempt(peer).is_some() && !self.opened_channels.contains(&peer)
+    }
+
+    /// Count a renegotiation of the handshake with peer. Each side counts its own, so that
+    /// peer can't renegotiate forever. Return [Error::HandshakeRenegotiationExhausted] if
+    /// it's beyond [MAX_HANDSHAKE_RENEGOTIATIONS].
+    fn count_renegotiation(&self, peer: Did) -> Result<u8> {
+        let mut count = self.renegotiations.entry(peer).or_insert(0);
+        *count = count.saturating_add(1);
+        let retry = *count;
+        drop(count);
+        if retry > MAX_HANDSHAKE_RENEGOTIATIONS {
+            self.renegotiations.remove(&peer);
+            return Err(Error::HandshakeRenegotiationExhausted(peer));
+        }
+        Ok(retry)
+    }
+
+    /// Whether to ask peer for a fresh handshake when the handshake with peer failed.
+    /// Both sides may see it failed, only the one with the smaller Did asks, so that they
+    /// don't send offers to each other at the same time.
+    pub(crate) fn should_renegotiate(&self, peer: Did) -> bool {
+        self.is_handshaking(peer) && self.dht.did < peer
+    }
+
+    /// Close the connection of peer whose handshake failed before the data channel opens, then
+    /// ask peer for a fresh handshake by [ConnectNodeRenegotiate], which is relayed by DHT.
+    /// Each side allows at most [MAX_HANDSHAKE_RENEGOTIATIONS] renegotiations.
+    pub async fn request_renegotiation(&self, peer: Did) -> Result<()> {
+        let attempt_id = self.connection_attempt(peer);
+        let retry = self.count_renegotiation(peer)?;
+        tracing::info!(
+            target: "rings::handshake",
+            "renegotiating handshake of {peer}, attempt {attempt_id:?}, retry {retry}"
+        );
+
+        if self.get_connection(peer).is_some() {
+            self.disconnect(peer).await?;
+        }
+        self.send_message(
+            Message::ConnectNodeRenegotiate(ConnectNodeRenegotiate { attempt_id }),
+            peer,
+        )
+        .await
+    }
+
+    /// Answer [ConnectNodeRenegotiate] of peer by closing the failed connection and sending a
+    /// fresh offer. A connection whose data channel is opened is kept, it's recovered by ICE
+    /// restart instead.
+    pub(crate) async fn answer_renegotiation(
+        &self,
+        peer: Did,
+        callback: InnerSwarmCallback,
+        msg: &ConnectNodeRenegotiate,
+    ) -> Result<()> {
+        if self.get_connection(peer).is_some() && self.is_channel_opened(peer) {
+            return Err(Error::AlreadyConnected);
+        }
+        let retry = self.count_renegotiation(peer)?;
+        tracing::info!(
+            target: "rings::handshake",
+            "{peer} asks for renegotiation of attempt {:?}, retry {retry}",
+            msg.attempt_id
+        );
+        if self.get_connection(peer).is_some() {
+            self.disconnect(peer).await?;
+        }
+        let offer_msg = self.prepare_connection_offer(peer, callback, None).await?;
+        self.send_message(Message::ConnectNodeSend(offer_msg), peer)
+            .await
+    }
+
+    /// Enable or disable trickle ICE of connections created later.
+    pub(crate) fn set_trickle_ice(&mut self, trickle_ice: bool) {
+        self.transport_mut().set_trickle_ice(trickle_ice)
+    }
+
+    /// Disable or enable mDNS of connections created later.
+    pub(crate) fn set_disable_mdns(&mut self, disable_mdns: bool) {
+        self.transport_mut().set_disable_mdns(disable_mdns)
+    }
+
+    /// Set the options of RTCConfiguration of connections created later.
+    pub(crate) fn set_rtc_config(&mut self, rtc_config: RtcConfig) {
+        self.transport_mut().set_rtc_config(rtc_config)
+    }
+
+    /// Set the max time waiting for ICE candidates gathering of connections created later.
+    pub(crate) fn set_gather_timeout(&mut self, timeout: Duration) {
+        self.transport_mut().set_gather_timeout(timeout)
+    }
+
+    /// Set the low threshold of buffered amount of connections created later.
+    pub(crate) fn set_buffered_amount_low_threshold(&mut self, threshold: Option<usize>) {
+        self.transport_mut()
+            .set_buffered_amount_low_threshold(threshold)
+    }
+
+    /// Add an ICE candidate trickled by peer.
+    /// Candidates may arrive before the offer or answer, they are kept until the remote
+    /// description of the connection is set. At most [MAX_PENDING_ICE_CANDIDATES] of a peer
+    /// are kept, for at most [PENDING_CONNECTION_MAX_AGE_MS] like the handshake itself.
+    pub(crate) async fn add_ice_candidate(&self, peer: Did, candidate: IceCandidate) {
+        let now = self.clock.now_ms();
+        self.pending_ice_candidates.retain(|_, (since, _)| {
+            now.saturating_sub(*since) < PENDING_CONNECTION_MAX_AGE_MS as u128
+        });
+        {
+            let mut pending = self
+                .pending_ice_candidates
+                .entry(peer)
+                .or_insert_with(|| (now, vec![]));
+            if pending.1.len() >= MAX_PENDING_ICE_CANDIDATES {
+                tracing::warn!(
+                    target: "rings::handshake",
+                    "Drop ice candidate of {peer}, too many candidates are pending"
+                );
+                return;
+            }
+            pending.1.push(candidate);
+        }
+        // Checked after pushing, so that the candidate is either flushed here or by
+        // `on_remote_described`.
+        if self.remote_described.contains(&peer) {
+            self.flush_ice_candidates(peer).await;
+        }
+    }
+
+    async fn on_remote_described(&self, peer: Did) {
+        self.remote_described.insert(peer);
+        self.flush_ice_candidates(peer).await;
+    }
+
+    async fn flush_ice_candidates(&self, peer: Did) {
+        let Some((_, (_, candidates))) = self.pending_ice_candidates.remove(&peer) else {
+            return;
+        };
+        let Some(conn) = self.get_connection(peer) else {
+            return;
+        };
+        for candidate in candidates {
+            if let Err(e) = conn
+                .connection
+                .webrtc_add_ice_candidate(candidate.into())
+                .await
+            {
+                tracing::warn!(
+                    target: "rings::handshake",
+                    "Failed on adding ice candidate of {peer}: {e:?}"
+                );
+            }
+        }
+    }
+
+    /// Store capabilities advertised by both this node and the peer.
+    fn negotiate_capabilities(&self, peer: Did, remote: &[String]) {
+        let negotiated = self
+            .capabilities
+            .iter()
+            .filter(|c| remote.contains(c))
+            .cloned()
+            .collect();
+        self.peer_capabilities.insert(peer, negotiated);
+    }
+
+    /// Get capabilities negotiated with a connected peer.
+    /// Return None if the peer is not connected.
+    pub fn peer_capabilities(&self, peer: Did) -> Option<Vec<String>> {
+        self.get_connection(peer)?;
+        Some(
+            self.peer_capabilities
+                .get(&peer)
+                .map(|c| c.clone())
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Relay messages to `peer` through `via` when it's not connected directly.
+    /// Remove the route if `via` is empty, and fail if it has more than one hop.
+    pub fn reroute(&self, peer: Did, via: Vec<Did>) -> Result<()> {
+        let hop = match via[..] {
+            [] => {
+                self.routes.remove(&peer);
+                return Ok(());
+            }
+            [hop] => hop,
+            _ => return Err(Error::MultiHopRoute),
+        };
+        if hop == peer || hop == self.dht.did {
+            return Err(Error::InvalidNextHop);
+        }
+        self.routes.insert(peer, hop);
+        Ok(())
+    }
+
+    /// Get the route of messages sent to `peer` now.
+    pub fn route(&self, peer: Did) -> Result<Route> {
+        if self.is_connected(peer) {
+            return Ok(Route::Direct);
+        }
+        if let Some(hop) = self.rerouted_hop(peer) {
+            return Ok(Route::Relay(hop));
+        }
+        self.infer_next_hop(peer, None).map(Route::Dht)
+    }
+
+    /// Check whether `peer` can be reached by the connections and DHT tables of this node,
+    /// without connecting or sending anything.
+    pub fn reachability(&self, peer: Did) -> Reachability {
+        if self.is_connected(peer) {
+            return Reachability::Direct;
+        }
+        if let Some(hop) = self.rerouted_hop(peer) {
+            return Reachability::Relayed(vec![hop]);
+        }
+        // Any did can be routed toward by DHT, but it's only a path if the peer is known.
+        if self.is_known_by_dht(peer) {
+            if let Ok(hop) = self.infer_next_hop(peer, None) {
+                if hop != peer && self.is_connected(hop) {
+                    return Reachability::Relayed(vec![hop]);
+                }
+            }
+        }
+        Reachability::Unknown
+    }
+
+    /// Check if `peer` is in the successors, finger table or predecessor of DHT.
+    fn is_known_by_dht(&self, peer: Did) -> bool {
+        if self.dht.successors().contains(&peer).unwrap_or(false) {
+            return true;
+        }
+        if self
+            .dht
+            .lock_finger()
+            .is_ok_and(|finger| finger.contains(Some(peer)))
+        {
+            return true;
+        }
+        self.dht
+            .lock_predecessor()
+            .is_ok_and(|predecessor| *predecessor == Some(peer))
+    }
 }
 
 impl SwarmConnection {
@@ -274,6 +1682,58 @@ impl SwarmConnection {
     pub fn webrtc_connection_state(&self) -> WebrtcConnectionState {
         self.connection.webrtc_connection_state()
     }
+
+    /// Get the label given at creation, see [crate::swarm::Swarm::connect_with_label].
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Check if the data channel of this connection is open without waiting.
+    pub fn data_channel_is_open(&self) -> bool {
+        self.connection.webrtc_data_channel_is_open()
+    }
+
+    /// Get statistics of the underlying transport, such as bytes sent and round trip time.
+    pub async fn stats(&self) -> ConnectionStats {
+        self.connection.stats().await
+    }
+
+    /// Get the number of bytes buffered in data channels of this connection.
+    pub async fn buffered_amount(&self) -> Result<usize> {
+        self.connection
+            .webrtc_buffered_amount()
+            .await
+            .map_err(Error::Transport)
+    }
+
+    /// Check buffered amount of connection and apply [SendBufferPolicy] to it.
+    pub async fn apply_send_buffer_policy(&self, policy: SendBufferPolicy) -> Result<()> {
+        let (high_water_mark, timeout_ms) = match policy {
+            SendBufferPolicy::Unbounded => return Ok(()),
+            SendBufferPolicy::Reject { high_water_mark } => (high_water_mark, 0),
+            SendBufferPolicy::Wait {
+                high_water_mark,
+                timeout_ms,
+            } => (high_water_mark, timeout_ms),
+        };
+
+        let deadline = self.clock.now_ms() + timeout_ms as u128;
+        loop {
+            let amount = self.buffered_amount().await?;
+            if amount <= high_water_mark {
+                return Ok(());
+            }
+            if self.clock.now_ms() >= deadline {
+                tracing::warn!(
+                    target: "rings::swarm",
+                    "Send buffer of {} is full, {amount} bytes buffered",
+                    self.peer
+                );
+                return Err(Error::SendBufferFull(amount));
+            }
+            utils::sleep(Duration::from_millis(SEND_BUFFER_POLL_INTERVAL_MS)).await;
+        }
+    }
 }
 
 #[cfg_attr(feature = "wasm", async_trait(?Send))]
@@ -294,38 +1754,99 @@ impl PayloadSender for SwarmTransport {
         conn.webrtc_connection_state() == WebrtcConnectionState::Connected
     }
 
-    async fn do_send_payload(&self, did: Did, payload: MessagePayload) -> Result<()> {
+    fn is_channel_open(&self, did: Did) -> bool {
+        self.get_connection(did)
+            .is_some_and(|conn| conn.data_channel_is_open())
+    }
+
+    fn rerouted_hop(&self, destination: Did) -> Option<Did> {
+        let hop = *self.routes.get(&destination)?;
+        self.is_connected(hop).then_some(hop)
+    }
+
+    async fn do_send_payload(
+        &self,
+        did: Did,
+        payload: MessagePayload,
+        priority: Priority,
+    ) -> Result<()> {
         let conn = self
             .get_and_check_connection(did)
             .await
             .ok_or(Error::SwarmMissDidInTable(did))?;
+        let payload = self.sign_for_connection(did, payload)?;
 
         tracing::debug!(
+            target: "rings::swarm",
             "Try send {:?}, to node {:?}",
             payload.clone(),
             payload.relay.next_hop,
         );
 
-        let data = payload.to_bincode()?;
-        if data.len() > TRANSPORT_MAX_SIZE {
-            tracing::error!("Message is too large: {:?}", payload);
+        let encoded = payload
+            .to_bincode()
+            .and_then(|data| encode_frame(&data, self.compression.as_ref()));
+        let data = match encoded {
+            Ok(data) => data,
+            Err(e) => {
+                let method = payload
+                    .transaction
+                    .data::<Message>()
+                    .map(|msg| msg.method())
+                    .unwrap_or("Unknown");
+                tracing::error!(
+                    target: "rings::swarm",
+                    "Failed to encode {method} to {}: {e:?}",
+                    payload.transaction.destination
+                );
+                self.record_measure(did, MeasureCounter::FailedToSend).await;
+                return Err(Error::PayloadEncode(method.to_string()));
+            }
+        };
+        if data.len() > self.max_message_size {
+            tracing::error!(
+                target: "rings::swarm",
+                "Message {} is too large: {} bytes, limit {} bytes",
+                payload.transaction.tx_id,
+                data.len(),
+                self.max_message_size
+            );
             return Err(Error::MessageTooLarge(data.len()));
         }
 
-        let result = if data.len() > TRANSPORT_MTU {
-            let chunks = ChunkList::<TRANSPORT_MTU>::from(&data);
-            for chunk in chunks {
-                let data =
-                    MessagePayload::new_send(Message::Chunk(chunk), &self.session_sk, did, did)?
-                        .to_bincode()?;
-                conn.send_data(data).await?;
+        #[cfg(feature = "record")]
+        self.record_payload(Direction::Outbound, did, &payload, None);
+
+        let size = data.len() as u64;
+        let frames = self.chunk_frames(did, data)?;
+        let queue = self.outbound.entry(did).or_default().clone();
+        let conn = &conn;
+        let result = queue
+            .send(priority, frames, move |frame| self.send_frame(conn, frame))
+            .await;
+
+        if result.is_ok() {
+            self.touch_connection(did);
+            // Messages originated by this node, whose report may come back.
+            if payload.relay.path == [self.dht.did] {
+                self.relay_metrics
+                    .on_sent(payload.transaction.tx_id, self.clock.now_ms());
             }
-            Ok(())
+        }
+
+        let counter = if result.is_ok() {
+            MeasureCounter::Sent
         } else {
-            conn.send_data(data).await
+            MeasureCounter::FailedToSend
         };
+        self.record_measure(did, counter).await;
+        if result.is_ok() {
+            self.record_measure_by(did, MeasureCounter::BytesSent, size)
+                .await;
+        }
 
         tracing::debug!(
+            target: "rings::swarm",
             "Sent {:?}, to node {:?}",
             payload.clone(),
             payload.relay.next_hop,
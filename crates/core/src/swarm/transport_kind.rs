@@ -0,0 +1,393 @@
+//! Runtime selection of the transport used by swarm.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rings_transport::connection_ref::ConnectionRef;
+#[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+use rings_transport::connections::LoopbackConnection;
+#[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+use rings_transport::connections::LoopbackTransport;
+use rings_transport::core::callback::BoxedTransportCallback;
+use rings_transport::core::transport::ConnectionInterface;
+use rings_transport::core::transport::ConnectionStats;
+use rings_transport::core::transport::IceCandidate;
+use rings_transport::core::transport::TransportInterface;
+use rings_transport::core::transport::TransportMessage;
+use rings_transport::core::transport::WebrtcConnectionState;
+use rings_transport::error::Error as TransportError;
+use rings_transport::error::Result as TransportResult;
+use rings_transport::rtc_config::RtcConfig;
+
+use crate::swarm::transport::ConnectionOwner;
+use crate::swarm::transport::Transport;
+
+/// Kind of transport connecting a swarm to its peers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TransportKind {
+    /// WebRTC data channels established by ICE, the transport used in production.
+    #[default]
+    Webrtc,
+    /// In-memory channels between swarms in the same process, without ICE.
+    /// It's useful for testing multiple nodes in a single process, and is only built with
+    /// feature `loopback`.
+    #[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+    Loopback,
+}
+
+/// Creator of the connections to peers, which decides the transport they are carried by.
+///
+/// The [TransportKind] selected when building swarm is the default one. Other transports,
+/// such as QUIC, can be used for some peers by implementing this trait and registering it by
+/// [crate::swarm::SwarmBuilder::transport_factory]. Connections are identified by the did of
+/// peer as string.
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+pub trait TransportFactory {
+    /// Create a connection of `cid`, whose events are sent to `callback`.
+    async fn new_connection(
+        &self,
+        cid: &str,
+        callback: BoxedTransportCallback,
+    ) -> TransportResult<()>;
+
+    /// Close the connection of `cid` and forget it.
+    async fn close_connection(&self, cid: &str) -> TransportResult<()>;
+
+    /// Get the connection of `cid` created by this factory.
+    /// Connections of transports other than the builtin ones are given as
+    /// [AnyConnection::Extension].
+    fn connection(&self, cid: &str) -> TransportResult<AnyConnection>;
+
+    /// Get all connections created by this factory.
+    fn connections(&self) -> Vec<(String, AnyConnection)>;
+
+    /// Get ids of all connections created by this factory.
+    fn connection_ids(&self) -> Vec<String> {
+        self.connections().into_iter().map(|(cid, _)| cid).collect()
+    }
+}
+
+/// A [TransportFactory] registered for peers by [crate::swarm::SwarmBuilder::transport_factory].
+#[cfg(feature = "wasm")]
+pub type SharedTransportFactory = Arc<dyn TransportFactory>;
+
+/// A [TransportFactory] registered for peers by [crate::swarm::SwarmBuilder::transport_factory].
+#[cfg(not(feature = "wasm"))]
+pub type SharedTransportFactory = Arc<dyn TransportFactory + Send + Sync>;
+
+/// Transport of the [TransportKind] selected when building swarm, the default [TransportFactory].
+pub(crate) enum AnyTransport {
+    Webrtc(Transport),
+    #[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+    Loopback(LoopbackTransport),
+}
+
+/// Connection created by [TransportFactory].
+/// The sdp of each kind is exchanged as json value, so that the handshake messages are
+/// the same as using the underlying transport directly.
+#[derive(Clone)]
+pub enum AnyConnection {
+    /// Connection of webrtc transport.
+    Webrtc(ConnectionRef<ConnectionOwner>),
+    /// Connection of loopback transport.
+    #[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+    Loopback(ConnectionRef<LoopbackConnection>),
+    /// Connection of a transport implemented out of this crate, created by a custom
+    /// [TransportFactory].
+    Extension(SharedConnection),
+}
+
+/// A connection of custom [TransportFactory], see [AnyConnection::Extension].
+#[cfg(feature = "wasm")]
+pub type SharedConnection =
+    Arc<dyn ConnectionInterface<Sdp = serde_json::Value, Error = TransportError>>;
+
+/// A connection of custom [TransportFactory], see [AnyConnection::Extension].
+#[cfg(not(feature = "wasm"))]
+pub type SharedConnection =
+    Arc<dyn ConnectionInterface<Sdp = serde_json::Value, Error = TransportError> + Send + Sync>;
+
+impl AnyTransport {
+    pub fn new(kind: TransportKind, ice_servers: &str, external_address: Option<String>) -> Self {
+        match kind {
+            TransportKind::Webrtc => Self::Webrtc(Transport::new(ice_servers, external_address)),
+            #[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+            TransportKind::Loopback => {
+                Self::Loopback(LoopbackTransport::new(ice_servers, external_address))
+            }
+        }
+    }
+
+    /// Enable or disable trickle ICE of connections created later.
+    /// Only native webrtc supports it, it's ignored by other transports.
+    pub fn set_trickle_ice(&mut self, trickle_ice: bool) {
+        match self {
+            #[cfg(all(not(feature = "wasm"), not(feature = "dummy")))]
+            Self::Webrtc(t) => t.set_trickle_ice(trickle_ice),
+            _ => {
+                if trickle_ice {
+                    tracing::warn!("Trickle ICE is not supported by this transport");
+                }
+            }
+        }
+    }
+
+    /// Disable or enable mDNS of connections created later.
+    /// Only native webrtc supports it, browsers decide it by themselves.
+    pub fn set_disable_mdns(&mut self, disable_mdns: bool) {
+        match self {
+            #[cfg(all(not(feature = "wasm"), not(feature = "dummy")))]
+            Self::Webrtc(t) => t.set_disable_mdns(disable_mdns),
+            _ => tracing::debug!("Ignore disable_mdns({disable_mdns}) of this transport"),
+        }
+    }
+
+    /// Set the options of RTCConfiguration of connections created later.
+    /// Only webrtc transports support it, it's ignored by other transports.
+    pub fn set_rtc_config(&mut self, rtc_config: RtcConfig) {
+        match self {
+            #[cfg(not(feature = "dummy"))]
+            Self::Webrtc(t) => t.set_rtc_config(rtc_config),
+            #[cfg(feature = "dummy")]
+            Self::Webrtc(_) => {
+                tracing::debug!("Ignore rtc_config({rtc_config:?}) of this transport")
+            }
+            #[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+            Self::Loopback(_) => {
+                tracing::debug!("Ignore rtc_config({rtc_config:?}) of this transport")
+            }
+        }
+    }
+
+    /// Set the max time waiting for ICE candidates gathering of connections created later.
+    /// Only webrtc transports support it, it's ignored by other transports.
+    pub fn set_gather_timeout(&mut self, timeout: Duration) {
+        match self {
+            #[cfg(not(feature = "dummy"))]
+            Self::Webrtc(t) => t.set_gather_timeout(timeout),
+            #[cfg(feature = "dummy")]
+            Self::Webrtc(_) => {
+                tracing::debug!("Ignore gather_timeout({timeout:?}) of this transport")
+            }
+            #[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+            Self::Loopback(_) => {
+                tracing::debug!("Ignore gather_timeout({timeout:?}) of this transport")
+            }
+        }
+    }
+
+    /// Set the low threshold of buffered amount of connections created later, at which
+    /// `on_buffered_amount_low` of callback is invoked. It's not invoked if None.
+    pub fn set_buffered_amount_low_threshold(&mut self, threshold: Option<usize>) {
+        match self {
+            Self::Webrtc(t) => t.set_buffered_amount_low_threshold(threshold),
+            #[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+            Self::Loopback(t) => t.set_buffered_amount_low_threshold(threshold),
+        }
+    }
+}
+
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl TransportFactory for AnyTransport {
+    async fn new_connection(
+        &self,
+        cid: &str,
+        callback: BoxedTransportCallback,
+    ) -> TransportResult<()> {
+        match self {
+            Self::Webrtc(t) => t.new_connection(cid, callback).await,
+            #[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+            Self::Loopback(t) => t.new_connection(cid, callback).await,
+        }
+    }
+
+    async fn close_connection(&self, cid: &str) -> TransportResult<()> {
+        match self {
+            Self::Webrtc(t) => t.close_connection(cid).await,
+            #[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+            Self::Loopback(t) => t.close_connection(cid).await,
+        }
+    }
+
+    fn connection(&self, cid: &str) -> TransportResult<AnyConnection> {
+        match self {
+            Self::Webrtc(t) => t.connection(cid).map(AnyConnection::Webrtc),
+            #[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+            Self::Loopback(t) => t.connection(cid).map(AnyConnection::Loopback),
+        }
+    }
+
+    fn connections(&self) -> Vec<(String, AnyConnection)> {
+        match self {
+            Self::Webrtc(t) => t
+                .connections()
+                .into_iter()
+                .map(|(cid, c)| (cid, AnyConnection::Webrtc(c)))
+                .collect(),
+            #[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+            Self::Loopback(t) => t
+                .connections()
+                .into_iter()
+                .map(|(cid, c)| (cid, AnyConnection::Loopback(c)))
+                .collect(),
+        }
+    }
+
+    fn connection_ids(&self) -> Vec<String> {
+        match self {
+            Self::Webrtc(t) => t.connection_ids(),
+            #[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+            Self::Loopback(t) => t.connection_ids(),
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "wasm")))]
+impl AnyConnection {
+    /// See [LoopbackConnection::simulate_ice_failure]. Panic if it's not a loopback connection.
+    pub(crate) fn simulate_ice_failure(&self) -> TransportResult<()> {
+        match self {
+            Self::Loopback(c) => c.simulate_ice_failure(),
+            _ => panic!("ICE failure is only simulated by loopback connections"),
+        }
+    }
+}
+
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl ConnectionInterface for AnyConnection {
+    type Sdp = serde_json::Value;
+    type Error = TransportError;
+
+    async fn send_message(&self, msg: TransportMessage) -> TransportResult<()> {
+        match self {
+            Self::Webrtc(c) => c.send_message(msg).await,
+            #[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+            Self::Loopback(c) => c.send_message(msg).await,
+            Self::Extension(c) => c.send_message(msg).await,
+        }
+    }
+
+    fn webrtc_connection_state(&self) -> WebrtcConnectionState {
+        match self {
+            Self::Webrtc(c) => c.webrtc_connection_state(),
+            #[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+            Self::Loopback(c) => c.webrtc_connection_state(),
+            Self::Extension(c) => c.webrtc_connection_state(),
+        }
+    }
+
+    async fn get_stats(&self) -> Vec<String> {
+        match self {
+            Self::Webrtc(c) => c.get_stats().await,
+            #[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+            Self::Loopback(c) => c.get_stats().await,
+            Self::Extension(c) => c.get_stats().await,
+        }
+    }
+
+    async fn stats(&self) -> ConnectionStats {
+        match self {
+            Self::Webrtc(c) => c.stats().await,
+            #[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+            Self::Loopback(c) => c.stats().await,
+            Self::Extension(c) => c.stats().await,
+        }
+    }
+
+    async fn webrtc_create_offer(&self) -> TransportResult<Self::Sdp> {
+        match self {
+            Self::Webrtc(c) => Ok(serde_json::to_value(c.webrtc_create_offer().await?)?),
+            #[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+            Self::Loopback(c) => Ok(serde_json::to_value(c.webrtc_create_offer().await?)?),
+            Self::Extension(c) => c.webrtc_create_offer().await,
+        }
+    }
+
+    async fn webrtc_restart_ice(&self) -> TransportResult<Self::Sdp> {
+        match self {
+            Self::Webrtc(c) => Ok(serde_json::to_value(c.webrtc_restart_ice().await?)?),
+            #[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+            Self::Loopback(c) => Ok(serde_json::to_value(c.webrtc_restart_ice().await?)?),
+            Self::Extension(c) => c.webrtc_restart_ice().await,
+        }
+    }
+
+    async fn webrtc_answer_offer(&self, offer: Self::Sdp) -> TransportResult<Self::Sdp> {
+        match self {
+            Self::Webrtc(c) => {
+                let offer = serde_json::from_value(offer)?;
+                Ok(serde_json::to_value(c.webrtc_answer_offer(offer).await?)?)
+            }
+            #[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+            Self::Loopback(c) => {
+                let offer = serde_json::from_value(offer)?;
+                Ok(serde_json::to_value(c.webrtc_answer_offer(offer).await?)?)
+            }
+            Self::Extension(c) => c.webrtc_answer_offer(offer).await,
+        }
+    }
+
+    async fn webrtc_accept_answer(&self, answer: Self::Sdp) -> TransportResult<()> {
+        match self {
+            Self::Webrtc(c) => {
+                c.webrtc_accept_answer(serde_json::from_value(answer)?)
+                    .await
+            }
+            #[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+            Self::Loopback(c) => {
+                c.webrtc_accept_answer(serde_json::from_value(answer)?)
+                    .await
+            }
+            Self::Extension(c) => c.webrtc_accept_answer(answer).await,
+        }
+    }
+
+    async fn webrtc_add_ice_candidate(&self, candidate: IceCandidate) -> TransportResult<()> {
+        match self {
+            Self::Webrtc(c) => c.webrtc_add_ice_candidate(candidate).await,
+            #[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+            Self::Loopback(c) => c.webrtc_add_ice_candidate(candidate).await,
+            Self::Extension(c) => c.webrtc_add_ice_candidate(candidate).await,
+        }
+    }
+
+    async fn webrtc_wait_for_data_channel_open(&self) -> TransportResult<()> {
+        match self {
+            Self::Webrtc(c) => c.webrtc_wait_for_data_channel_open().await,
+            #[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+            Self::Loopback(c) => c.webrtc_wait_for_data_channel_open().await,
+            Self::Extension(c) => c.webrtc_wait_for_data_channel_open().await,
+        }
+    }
+
+    fn webrtc_data_channel_is_open(&self) -> bool {
+        match self {
+            Self::Webrtc(c) => c.webrtc_data_channel_is_open(),
+            #[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+            Self::Loopback(c) => c.webrtc_data_channel_is_open(),
+            Self::Extension(c) => c.webrtc_data_channel_is_open(),
+        }
+    }
+
+    async fn webrtc_buffered_amount(&self) -> TransportResult<usize> {
+        match self {
+            Self::Webrtc(c) => c.webrtc_buffered_amount().await,
+            #[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+            Self::Loopback(c) => c.webrtc_buffered_amount().await,
+            Self::Extension(c) => c.webrtc_buffered_amount().await,
+        }
+    }
+
+    async fn close(&self) -> TransportResult<()> {
+        match self {
+            Self::Webrtc(c) => c.close().await,
+            #[cfg(any(feature = "loopback", all(test, not(feature = "wasm"))))]
+            Self::Loopback(c) => c.close().await,
+            Self::Extension(c) => c.close().await,
+        }
+    }
+}
@@ -89,11 +89,23 @@ impl SwarmCallback for NodeCallback {
 }
 
 pub async fn prepare_node(key: SecretKey) -> Node {
+    prepare_node_with_builder(key, |builder| builder).await
+}
+
+/// Prepare a node whose [SwarmBuilder] is customized by `f`.
+pub async fn prepare_node_with_builder(
+    key: SecretKey,
+    f: impl FnOnce(SwarmBuilder) -> SwarmBuilder,
+) -> Node {
     let stun = "stun://stun.l.google.com:19302";
     let storage = Box::new(MemStorage::new());
 
     let session_sk = SessionSk::new_with_seckey(&key).unwrap();
-    let swarm = Arc::new(SwarmBuilder::new(0, stun, storage, session_sk).build());
+    let swarm = Arc::new(
+        f(SwarmBuilder::new(0, stun, storage, session_sk))
+            .build()
+            .unwrap(),
+    );
 
     println!("key: {:?}", key.to_string());
     println!("did: {:?}", swarm.did());
@@ -1,11 +1,53 @@
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use rings_transport::connections::LoopbackTransport;
+use rings_transport::core::callback::BoxedTransportCallback;
+use rings_transport::core::transport::TransportInterface;
 use rings_transport::core::transport::WebrtcConnectionState;
+use rings_transport::error::Result as TransportResult;
+use tokio::time::timeout;
+use tokio::time::Duration;
+use tokio::time::Instant;
 
+use crate::consts::DEFAULT_SESSION_TTL_MS;
+use crate::consts::TRANSPORT_MTU;
+use crate::dht::successor::SuccessorReader;
+use crate::dht::Did;
 use crate::ecc::tests::gen_ordered_keys;
 use crate::ecc::SecretKey;
+use crate::error::Error;
+use crate::error::Result;
+use crate::measure::BehaviourJudgement;
+use crate::measure::Measure;
+use crate::measure::MeasureCounter;
+use crate::measure::MessageSendBehaviour;
+use crate::message::ConnectNodeRenegotiate;
+use crate::message::HandshakeCodec;
+use crate::message::Message;
+use crate::message::MessagePayload;
+use crate::message::MessageVerificationExt;
+use crate::message::PayloadSender;
+use crate::session::SessionSk;
+use crate::swarm::callback::SwarmCallback;
+use crate::swarm::callback::SwarmEvent;
+use crate::swarm::AnyConnection;
+use crate::swarm::SendBufferPolicy;
+use crate::swarm::SharedTransportFactory;
+use crate::swarm::SwarmBuilder;
+use crate::swarm::TransportFactory;
+use crate::swarm::TransportKind;
+use crate::swarm::VerificationPolicy;
 use crate::tests::default::assert_no_more_msg;
 use crate::tests::default::prepare_node;
+use crate::tests::default::prepare_node_with_builder;
 use crate::tests::default::wait_for_msgs;
 use crate::tests::manually_establish_connection;
+use crate::utils::get_epoch_ms;
+use crate::utils::MockClock;
 
 #[tokio::test]
 async fn test_handshake_on_both_sides_ordered() {
@@ -127,3 +169,1336 @@ async fn test_handshake_on_both_sides(key1: SecretKey, key2: SecretKey, key3: Se
         WebrtcConnectionState::Connected,
     )
 }
+
+#[tokio::test]
+async fn test_send_buffer_backpressure() {
+    let keys = gen_ordered_keys(2);
+    let high_water_mark = TRANSPORT_MTU;
+    let policy = SendBufferPolicy::Wait {
+        high_water_mark,
+        timeout_ms: 30 * 1000,
+    };
+
+    let node1 = prepare_node_with_builder(keys[0], |b| b.send_buffer_policy(policy)).await;
+    let node2 = prepare_node(keys[1]).await;
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+    assert_no_more_msg([&node1, &node2]).await;
+
+    let conn = node1.swarm.transport.get_connection(node2.did()).unwrap();
+    let data = vec![42u8; TRANSPORT_MTU / 2];
+    let total = 64;
+
+    // Flood the channel, every send should wait for draining instead of overflowing the buffer.
+    for _ in 0..total {
+        node1
+            .swarm
+            .send_message(Message::custom(&data).unwrap(), node2.did())
+            .await
+            .unwrap();
+        let buffered = conn.buffered_amount().await.unwrap();
+        assert!(
+            buffered <= high_water_mark + TRANSPORT_MTU,
+            "buffered amount {buffered} exceeds high water mark"
+        );
+    }
+
+    let mut received = 0;
+    while received < total {
+        let payload = timeout(Duration::from_secs(30), node2.listen_once())
+            .await
+            .expect("node2 should receive all messages")
+            .unwrap();
+        if let Ok(Message::CustomMessage(_)) = payload.transaction.data() {
+            received += 1;
+        }
+    }
+
+    assert_eq!(
+        conn.webrtc_connection_state(),
+        WebrtcConnectionState::Connected
+    );
+}
+
+#[tokio::test]
+async fn test_trust_all_accepts_unsigned_handshake() {
+    let keys = gen_ordered_keys(3);
+    let trust_all = |b: SwarmBuilder| {
+        b.transport_kind(TransportKind::Loopback)
+            .verification_policy(VerificationPolicy::TrustAll)
+    };
+    let node1 = prepare_node_with_builder(keys[0], trust_all).await;
+    let node2 = prepare_node_with_builder(keys[1], trust_all).await;
+    let node3 = prepare_node_with_builder(keys[2], |b: SwarmBuilder| {
+        b.transport_kind(TransportKind::Loopback)
+    })
+    .await;
+
+    let unsign = |mut payload: MessagePayload| {
+        payload.verification.sig = vec![];
+        payload.transaction.verification.sig = vec![];
+        assert!(!payload.verify());
+        payload
+    };
+
+    // The strict policy by default rejects it.
+    let offer = unsign(node1.swarm.create_offer(node3.did()).await.unwrap());
+    assert!(matches!(
+        node3.swarm.answer_offer(offer).await,
+        Err(Error::VerifySignatureFailed)
+    ));
+
+    let offer = unsign(node1.swarm.create_offer(node2.did()).await.unwrap());
+    let answer = unsign(node2.swarm.answer_offer(offer).await.unwrap());
+    node1.swarm.accept_answer(answer).await.unwrap();
+    wait_for_msgs([&node1, &node2]).await;
+
+    assert!(node2.swarm.transport.get_connection(node1.did()).is_some());
+    let conn = node1.swarm.transport.get_connection(node2.did()).unwrap();
+    assert_eq!(
+        conn.webrtc_connection_state(),
+        WebrtcConnectionState::Connected
+    );
+}
+
+#[tokio::test]
+async fn test_acceptance_delay_pads_reject() {
+    let keys = gen_ordered_keys(2);
+    let delay = Duration::from_secs(5);
+
+    let node1 = prepare_node(keys[0]).await;
+    let node2 = prepare_node_with_builder(keys[1], |b| b.acceptance_delay(delay)).await;
+
+    // Accept path: answer a valid offer.
+    let offer = node1.swarm.create_offer(node2.did()).await.unwrap();
+    let start = Instant::now();
+    let answer = node2.swarm.answer_offer(offer).await.unwrap();
+    let accept_elapsed = start.elapsed();
+    node1.swarm.accept_answer(answer.clone()).await.unwrap();
+
+    // Reject path: answering a payload which is not an offer fails fast, but it's padded to the
+    // delay as well.
+    let start = Instant::now();
+    assert!(node2.swarm.answer_offer(answer).await.is_err());
+    let reject_elapsed = start.elapsed();
+
+    assert!(accept_elapsed >= delay);
+    assert!(reject_elapsed >= delay);
+
+    let diff = if accept_elapsed > reject_elapsed {
+        accept_elapsed - reject_elapsed
+    } else {
+        reject_elapsed - accept_elapsed
+    };
+    assert!(
+        diff < Duration::from_secs(1),
+        "accept took {accept_elapsed:?} but reject took {reject_elapsed:?}"
+    );
+}
+
+#[derive(Default, Clone)]
+struct CountingMeasure(Arc<DashMap<(Did, MeasureCounter), u64>>);
+
+#[async_trait]
+impl Measure for CountingMeasure {
+    async fn incr(&self, did: Did, counter: MeasureCounter) -> Result<()> {
+        self.incr_by(did, counter, 1).await
+    }
+
+    async fn incr_by(&self, did: Did, counter: MeasureCounter, n: u64) -> Result<()> {
+        *self.0.entry((did, counter)).or_insert(0) += n;
+        Ok(())
+    }
+
+    async fn get_count(&self, did: Did, counter: MeasureCounter) -> Result<u64> {
+        Ok(self.0.get(&(did, counter)).map(|c| *c).unwrap_or(0))
+    }
+}
+
+#[async_trait]
+impl BehaviourJudgement for CountingMeasure {
+    async fn good(&self, _did: Did) -> bool {
+        true
+    }
+}
+
+#[tokio::test]
+async fn test_connection_quality_with_failures() {
+    let keys = gen_ordered_keys(3);
+    let measure = CountingMeasure::default();
+
+    let m = measure.clone();
+    let node1 = prepare_node_with_builder(keys[0], |b| b.measure(Box::new(m))).await;
+    let node2 = prepare_node(keys[1]).await;
+    let node3 = prepare_node(keys[2]).await;
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    manually_establish_connection(&node1.swarm, &node3.swarm).await;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+    assert_no_more_msg([&node1, &node2, &node3]).await;
+
+    // Sent messages are counted by swarm.
+    for peer in [node2.did(), node3.did()] {
+        node1
+            .swarm
+            .send_message(Message::custom(b"ping").unwrap(), peer)
+            .await
+            .unwrap();
+    }
+    assert!(
+        measure
+            .get_count(node2.did(), MeasureCounter::Sent)
+            .await
+            .unwrap()
+            > 0
+    );
+
+    for _ in 0..10 {
+        measure
+            .incr(node3.did(), MeasureCounter::FailedToSend)
+            .await
+            .unwrap();
+    }
+
+    let clean = node1.swarm.connection_quality(node2.did()).await.unwrap();
+    let lossy = node1.swarm.connection_quality(node3.did()).await.unwrap();
+    assert!((0.0..=1.0).contains(&clean));
+    assert!((0.0..=1.0).contains(&lossy));
+    assert!(
+        lossy < clean,
+        "lossy {lossy} should score lower than clean {clean}"
+    );
+
+    let unknown = SecretKey::random().address().into();
+    assert!(node1.swarm.connection_quality(unknown).await.is_none());
+}
+
+struct FailingMeasure;
+
+#[async_trait]
+impl Measure for FailingMeasure {
+    async fn incr(&self, _did: Did, _counter: MeasureCounter) -> Result<()> {
+        Err(Error::Measure("backend is down".to_string()))
+    }
+
+    async fn incr_by(&self, _did: Did, _counter: MeasureCounter, _n: u64) -> Result<()> {
+        Err(Error::Measure("backend is down".to_string()))
+    }
+
+    async fn get_count(&self, _did: Did, _counter: MeasureCounter) -> Result<u64> {
+        Err(Error::Measure("backend is down".to_string()))
+    }
+}
+
+#[async_trait]
+impl<const T: i64> MessageSendBehaviour<T> for FailingMeasure {}
+
+#[async_trait]
+impl BehaviourJudgement for FailingMeasure {
+    async fn good(&self, did: Did) -> bool {
+        <Self as MessageSendBehaviour<10>>::good(self, did).await
+    }
+}
+
+#[tokio::test]
+async fn test_connect_with_failing_measure() {
+    let keys = gen_ordered_keys(2);
+    let node1 = prepare_node_with_builder(keys[0], |b| b.measure(Box::new(FailingMeasure))).await;
+    let node2 = prepare_node(keys[1]).await;
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+    assert_no_more_msg([&node1, &node2]).await;
+
+    node1
+        .swarm
+        .send_message(Message::custom(b"ping").unwrap(), node2.did())
+        .await
+        .unwrap();
+    assert!(timeout(Duration::from_secs(3), node2.listen_once())
+        .await
+        .unwrap()
+        .is_some());
+
+    // Unreadable counters are regarded as unknown, which is good.
+    assert!(FailingMeasure.good(node2.did()).await);
+    let quality = node1.swarm.connection_quality(node2.did()).await.unwrap();
+    assert!(quality > 0.0);
+}
+
+#[tokio::test]
+async fn test_bytes_counters() {
+    let keys = gen_ordered_keys(2);
+    let (measure1, measure2) = (CountingMeasure::default(), CountingMeasure::default());
+
+    let (m1, m2) = (measure1.clone(), measure2.clone());
+    let node1 = prepare_node_with_builder(keys[0], |b| {
+        b.transport_kind(TransportKind::Loopback)
+            .measure(Box::new(m1))
+    })
+    .await;
+    let node2 = prepare_node_with_builder(keys[1], |b| {
+        b.transport_kind(TransportKind::Loopback)
+            .measure(Box::new(m2))
+    })
+    .await;
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+    assert_no_more_msg([&node1, &node2]).await;
+
+    let before1 = node1
+        .swarm
+        .measure_snapshot(node2.did())
+        .await
+        .unwrap()
+        .unwrap();
+    let before2 = node2
+        .swarm
+        .measure_snapshot(node1.did())
+        .await
+        .unwrap()
+        .unwrap();
+    // Handshake and DHT messages are counted.
+    assert!(before1.bytes_sent > 0);
+    assert_eq!(before1.bytes_sent, before2.bytes_received);
+
+    for size in [100, 1000] {
+        node1
+            .swarm
+            .send_message(Message::custom(&vec![1; size]).unwrap(), node2.did())
+            .await
+            .unwrap();
+        assert!(timeout(Duration::from_secs(3), node2.listen_once())
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    let after1 = node1
+        .swarm
+        .measure_snapshot(node2.did())
+        .await
+        .unwrap()
+        .unwrap();
+    let after2 = node2
+        .swarm
+        .measure_snapshot(node1.did())
+        .await
+        .unwrap()
+        .unwrap();
+    let sent = after1.bytes_sent - before1.bytes_sent;
+    assert!(sent > 1100, "{sent} bytes sent");
+    assert_eq!(after2.bytes_received - before2.bytes_received, sent);
+    assert_eq!(
+        measure1
+            .get_count(node2.did(), MeasureCounter::BytesSent)
+            .await
+            .unwrap(),
+        after1.bytes_sent
+    );
+    assert_eq!(after1.sent - before1.sent, 2);
+
+    // A message sent in chunks is counted by the bytes of its chunks, not again after reassembly.
+    node1
+        .swarm
+        .send_message(
+            Message::custom(&vec![1; TRANSPORT_MTU * 2]).unwrap(),
+            node2.did(),
+        )
+        .await
+        .unwrap();
+    assert!(timeout(Duration::from_secs(3), node2.listen_once())
+        .await
+        .unwrap()
+        .is_some());
+    let chunked1 = node1
+        .swarm
+        .measure_snapshot(node2.did())
+        .await
+        .unwrap()
+        .unwrap();
+    let chunked2 = node2
+        .swarm
+        .measure_snapshot(node1.did())
+        .await
+        .unwrap()
+        .unwrap();
+    let sent = chunked1.bytes_sent - after1.bytes_sent;
+    let received = chunked2.bytes_received - after2.bytes_received;
+    assert!(
+        received >= sent && received < sent * 2,
+        "{sent} bytes sent but {received} bytes received"
+    );
+
+    // Nothing is counted without measure.
+    let node3 = prepare_node(SecretKey::random()).await;
+    assert!(node3
+        .swarm
+        .measure_snapshot(node1.did())
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_connection_stats() {
+    let keys = gen_ordered_keys(2);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let node2 = prepare_node_with_builder(keys[1], loopback).await;
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+    assert_no_more_msg([&node1, &node2]).await;
+
+    let before = node1.swarm.connection_stats(node2.did()).await.unwrap();
+    node1
+        .swarm
+        .send_message(Message::custom(b"ping").unwrap(), node2.did())
+        .await
+        .unwrap();
+    timeout(Duration::from_secs(3), node2.listen_once())
+        .await
+        .expect("message is not received by node2")
+        .unwrap();
+
+    let sent = node1.swarm.connection_stats(node2.did()).await.unwrap();
+    let received = node2.swarm.connection_stats(node1.did()).await.unwrap();
+    assert!(sent.bytes_sent > before.bytes_sent);
+    assert!(received.bytes_received > 0);
+
+    let unknown = SecretKey::random().address().into();
+    assert!(node1.swarm.connection_stats(unknown).await.is_none());
+}
+
+#[tokio::test]
+async fn test_connect_and_wait() {
+    let keys = gen_ordered_keys(3);
+    let node1 = prepare_node(keys[0]).await;
+    let node2 = prepare_node(keys[1]).await;
+    let node3 = prepare_node(keys[2]).await;
+
+    manually_establish_connection(&node1.swarm, &node3.swarm).await;
+    manually_establish_connection(&node2.swarm, &node3.swarm).await;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+
+    node1
+        .swarm
+        .connect_and_wait(node2.did(), 10 * 1000)
+        .await
+        .unwrap();
+    assert_eq!(
+        node1
+            .swarm
+            .transport
+            .get_connection(node2.did())
+            .unwrap()
+            .webrtc_connection_state(),
+        WebrtcConnectionState::Connected
+    );
+
+    // Already connected, it returns directly.
+    node1
+        .swarm
+        .connect_and_wait(node2.did(), 10 * 1000)
+        .await
+        .unwrap();
+
+    // Nobody answers the offer to a peer that doesn't exist.
+    let ghost: Did = SecretKey::random().address().into();
+    let res = node1.swarm.connect_and_wait(ghost, 500).await;
+    assert!(matches!(res, Err(Error::WaitConnectionTimeout(did)) if did == ghost));
+}
+
+#[tokio::test]
+async fn test_handshake_negotiates_capabilities() {
+    let keys = gen_ordered_keys(3);
+    let caps = |c: &[&str]| c.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+    let node1 =
+        prepare_node_with_builder(keys[0], |b| b.capabilities(caps(&["snark", "compression"])))
+            .await;
+    let node2 =
+        prepare_node_with_builder(keys[1], |b| b.capabilities(caps(&["snark", "chunk"]))).await;
+    let node3 = prepare_node(keys[2]).await;
+
+    assert_eq!(node1.swarm.peer_capabilities(node2.did()), None);
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    manually_establish_connection(&node1.swarm, &node3.swarm).await;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+
+    assert_eq!(
+        node1.swarm.peer_capabilities(node2.did()),
+        Some(caps(&["snark"]))
+    );
+    assert_eq!(
+        node2.swarm.peer_capabilities(node1.did()),
+        Some(caps(&["snark"]))
+    );
+
+    // Node3 advertises nothing.
+    assert_eq!(node1.swarm.peer_capabilities(node3.did()), Some(vec![]));
+    assert_eq!(node3.swarm.peer_capabilities(node1.did()), Some(vec![]));
+
+    node1.swarm.disconnect(node2.did()).await.unwrap();
+    assert_eq!(node1.swarm.peer_capabilities(node2.did()), None);
+}
+
+#[tokio::test]
+async fn test_handshake_with_mixed_codecs() {
+    let keys = gen_ordered_keys(2);
+    let node1 =
+        prepare_node_with_builder(keys[0], |b| b.handshake_codec(HandshakeCodec::Cbor)).await;
+    let node2 = prepare_node(keys[1]).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+
+    for (node, peer) in [(&node1, node2.did()), (&node2, node1.did())] {
+        assert_eq!(
+            node.swarm
+                .transport
+                .get_connection(peer)
+                .unwrap()
+                .webrtc_connection_state(),
+            WebrtcConnectionState::Connected
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_gc_pending_connections() {
+    let keys = gen_ordered_keys(2);
+    let node1 = prepare_node(keys[0]).await;
+    let node2 = prepare_node(keys[1]).await;
+
+    // The offer is never answered.
+    node1.swarm.create_offer(node2.did()).await.unwrap();
+    let max_age = Duration::from_secs(60);
+    assert!(node1
+        .swarm
+        .pending_connections_older_than(max_age)
+        .is_empty());
+    assert!(node1
+        .swarm
+        .gc_pending_connections(max_age)
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(node1.swarm.transport.get_connection(node2.did()).is_some());
+
+    let later = crate::utils::get_epoch_ms() + max_age.as_millis() + 1;
+    assert_eq!(
+        node1
+            .swarm
+            .transport
+            .pending_connections_older_than_at(max_age, later),
+        vec![node2.did()]
+    );
+    let closed = node1
+        .swarm
+        .transport
+        .gc_pending_connections_at(max_age, later)
+        .await
+        .unwrap();
+    assert_eq!(closed, vec![node2.did()]);
+    assert!(node1.swarm.transport.get_connection(node2.did()).is_none());
+}
+
+#[tokio::test]
+async fn test_mock_clock_expires_connection_and_session() {
+    let keys = gen_ordered_keys(3);
+    let clock = Arc::new(MockClock::new(get_epoch_ms()));
+    let node1 = prepare_node_with_builder(keys[0], |b| b.clock(clock.clone())).await;
+    let node2 = prepare_node(keys[1]).await;
+    let node3 = prepare_node(keys[2]).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    node1.swarm.create_offer(node3.did()).await.unwrap();
+    let session_sk = SessionSk::new_with_seckey(&keys[0]).unwrap();
+    node1
+        .swarm
+        .rekey_connection(node2.did(), session_sk.clone())
+        .unwrap();
+
+    let max_age = Duration::from_secs(60);
+    assert!(node1
+        .swarm
+        .pending_connections_older_than(max_age)
+        .is_empty());
+
+    clock.advance(max_age);
+    assert_eq!(node1.swarm.pending_connections_older_than(max_age), vec![
+        node3.did()
+    ]);
+
+    clock.advance(Duration::from_millis(DEFAULT_SESSION_TTL_MS));
+    assert!(matches!(
+        node1.swarm.rekey_connection(node2.did(), session_sk),
+        Err(Error::SessionExpired)
+    ));
+}
+
+#[tokio::test]
+async fn test_wait_connected_times_out_by_mock_clock() {
+    let keys = gen_ordered_keys(2);
+    let clock = Arc::new(MockClock::new(get_epoch_ms()));
+    let node = prepare_node_with_builder(keys[0], |b| b.clock(clock.clone())).await;
+    let peer: Did = keys[1].address().into();
+    node.swarm.create_offer(peer).await.unwrap();
+
+    // The connection is never answered, only the mock clock can end the waiting.
+    let wait = node.swarm.transport.wait_connected(peer, 60 * 1000);
+    let advance = async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        clock.advance(Duration::from_secs(60));
+    };
+    let (res, _) = timeout(Duration::from_secs(5), async {
+        tokio::join!(wait, advance)
+    })
+    .await
+    .expect("waiting should end once the mock clock passes the deadline");
+    assert!(matches!(res, Err(Error::WaitConnectionTimeout(did)) if did == peer));
+}
+
+#[tokio::test]
+async fn test_pending_ice_candidates_are_bounded() {
+    use rings_transport::core::transport::IceCandidate;
+
+    use crate::consts::MAX_PENDING_ICE_CANDIDATES;
+    use crate::consts::PENDING_CONNECTION_MAX_AGE_MS;
+
+    let keys = gen_ordered_keys(3);
+    let clock = Arc::new(MockClock::new(get_epoch_ms()));
+    let node = prepare_node_with_builder(keys[0], |b| b.clock(clock.clone())).await;
+    let peer1: Did = keys[1].address().into();
+    let peer2: Did = keys[2].address().into();
+    let candidate = |i: usize| IceCandidate {
+        candidate: format!("candidate:{i} 1 udp 2130706431 127.0.0.1 {i} typ host"),
+        sdp_mid: Some("0".to_string()),
+        sdp_mline_index: Some(0),
+    };
+    let pending = |peer: Did| {
+        node.swarm
+            .transport
+            .pending_ice_candidates
+            .get(&peer)
+            .map(|p| p.1.len())
+    };
+
+    // Candidates arriving before any offer are kept, up to a limit.
+    for i in 0..MAX_PENDING_ICE_CANDIDATES + 10 {
+        node.swarm
+            .transport
+            .add_ice_candidate(peer1, candidate(i))
+            .await;
+    }
+    assert_eq!(pending(peer1), Some(MAX_PENDING_ICE_CANDIDATES));
+
+    // They expire like a handshake never finished.
+    clock.advance(Duration::from_millis(PENDING_CONNECTION_MAX_AGE_MS));
+    node.swarm
+        .transport
+        .add_ice_candidate(peer2, candidate(0))
+        .await;
+    assert_eq!(pending(peer1), None);
+    assert_eq!(pending(peer2), Some(1));
+}
+
+#[tokio::test]
+async fn test_session_ttl_remaining_by_mock_clock() {
+    let clock = Arc::new(MockClock::new(get_epoch_ms()));
+    let node = prepare_node_with_builder(SecretKey::random(), |b| b.clock(clock.clone())).await;
+
+    let remaining = node.swarm.session_ttl_remaining();
+    assert!(remaining <= Duration::from_millis(DEFAULT_SESSION_TTL_MS));
+    assert!(remaining > Duration::ZERO);
+
+    clock.advance(Duration::from_secs(60));
+    let later = node.swarm.session_ttl_remaining();
+    assert_eq!(remaining - later, Duration::from_secs(60));
+
+    clock.advance(Duration::from_millis(DEFAULT_SESSION_TTL_MS));
+    assert_eq!(node.swarm.session_ttl_remaining(), Duration::ZERO);
+}
+
+#[tokio::test]
+async fn test_duplicate_connection_keeps_connected_one() {
+    let keys = gen_ordered_keys(2);
+    let node1 = prepare_node(keys[0]).await;
+    let node2 = prepare_node(keys[1]).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+    let conn = node1.swarm.transport.get_connection(node2.did()).unwrap();
+    assert_eq!(
+        conn.webrtc_connection_state(),
+        WebrtcConnectionState::Connected
+    );
+
+    // A late connection of the same peer is discarded.
+    let res = node1
+        .swarm
+        .transport
+        .new_connection(node2.did(), node1.swarm.inner_callback().unwrap(), None)
+        .await;
+    assert!(matches!(res, Err(Error::AlreadyConnected)));
+
+    assert_eq!(
+        conn.webrtc_connection_state(),
+        WebrtcConnectionState::Connected
+    );
+    assert!(node1
+        .dht()
+        .successors()
+        .list()
+        .unwrap()
+        .contains(&node2.did()));
+    node1
+        .swarm
+        .send_message(Message::custom(b"still alive").unwrap(), node2.did())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_connection_attempt_id_shared_by_both_sides() {
+    let keys = gen_ordered_keys(2);
+    let node1 = prepare_node(keys[0]).await;
+    let node2 = prepare_node(keys[1]).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+
+    let attempt1 = node1
+        .swarm
+        .transport
+        .get_connection(node2.did())
+        .unwrap()
+        .attempt_id;
+    let attempt2 = node2
+        .swarm
+        .transport
+        .get_connection(node1.did())
+        .unwrap()
+        .attempt_id;
+    assert!(attempt1.is_some());
+    assert_eq!(attempt1, attempt2);
+
+    node1.swarm.disconnect(node2.did()).await.unwrap();
+    assert!(node1
+        .swarm
+        .transport
+        .connection_attempt(node2.did())
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_rekey_connection() {
+    let keys = gen_ordered_keys(2);
+    let node1 = prepare_node(keys[0]).await;
+    let node2 = prepare_node(keys[1]).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+
+    let foreign_sk = SessionSk::new_with_seckey(&SecretKey::random()).unwrap();
+    assert!(matches!(
+        node1.swarm.rekey_connection(node2.did(), foreign_sk),
+        Err(Error::SessionAccountMismatch(_))
+    ));
+
+    let rotated_sk = SessionSk::new_with_seckey(&keys[0]).unwrap();
+    node1
+        .swarm
+        .rekey_connection(node2.did(), rotated_sk.clone())
+        .unwrap();
+
+    node1
+        .swarm
+        .send_message(Message::custom(b"rotated").unwrap(), node2.did())
+        .await
+        .unwrap();
+
+    let payload = node2.listen_once().await.unwrap();
+    assert!(payload.verify());
+    assert_eq!(payload.verification.session, rotated_sk.session());
+    assert_eq!(
+        payload.transaction.verification.session,
+        rotated_sk.session()
+    );
+    assert_eq!(payload.transaction.signer(), node1.did());
+    assert!(node1
+        .swarm
+        .transport
+        .get_and_check_connection(node2.did())
+        .await
+        .is_some());
+}
+
+#[tokio::test]
+async fn test_bootstrap_from_known_dids() {
+    let keys = gen_ordered_keys(4);
+    let node1 = prepare_node(keys[0]).await;
+    let node2 = prepare_node(keys[1]).await;
+    let node3 = prepare_node(keys[2]).await;
+    let node4 = prepare_node(keys[3]).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    manually_establish_connection(&node1.swarm, &node3.swarm).await;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+
+    let mut exported = node1.swarm.known_dids().unwrap();
+    exported.sort();
+    let mut expected = vec![node2.did(), node3.did()];
+    expected.sort();
+    assert_eq!(exported, expected);
+
+    // The snapshot goes through json, like exporting by rpc.
+    let snapshot = serde_json::to_string(&exported).unwrap();
+    let imported: Vec<Did> = serde_json::from_str(&snapshot).unwrap();
+
+    // Offers are sent through node1, which node4 is connected to.
+    manually_establish_connection(&node4.swarm, &node1.swarm).await;
+    let connecting = node4.swarm.bootstrap_from(imported.clone()).await;
+    assert_eq!(connecting, imported);
+    for did in imported {
+        assert!(node4.swarm.transport.get_connection(did).is_some());
+    }
+}
+
+#[tokio::test]
+async fn test_connect_with_label() {
+    let keys = gen_ordered_keys(3);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let node2 = prepare_node_with_builder(keys[1], loopback).await;
+    let node3 = prepare_node_with_builder(keys[2], loopback).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    manually_establish_connection(&node2.swarm, &node3.swarm).await;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+
+    node1
+        .swarm
+        .connect_with_label(node3.did(), "bulk")
+        .await
+        .unwrap();
+    wait_for_msgs([&node1, &node2, &node3]).await;
+
+    let conn = node1.swarm.transport.get_connection(node3.did()).unwrap();
+    assert_eq!(conn.label(), Some("bulk"));
+    assert_eq!(
+        conn.webrtc_connection_state(),
+        WebrtcConnectionState::Connected
+    );
+    let peer = node1
+        .swarm
+        .peers()
+        .into_iter()
+        .find(|p| p.did == node3.did().to_string())
+        .unwrap();
+    assert_eq!(peer.label.as_deref(), Some("bulk"));
+
+    // Connections without label, including the answering side.
+    let conn = node1.swarm.transport.get_connection(node2.did()).unwrap();
+    assert_eq!(conn.label(), None);
+    let conn = node3.swarm.transport.get_connection(node1.did()).unwrap();
+    assert_eq!(conn.label(), None);
+
+    node1.swarm.disconnect(node3.did()).await.unwrap();
+    assert_eq!(node1.swarm.transport.connection_label(node3.did()), None);
+}
+
+#[tokio::test]
+async fn test_restart_ice_recovers_failed_connection() {
+    let keys = gen_ordered_keys(3);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let node2 = prepare_node_with_builder(keys[1], loopback).await;
+    let node3 = prepare_node_with_builder(keys[2], loopback).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    manually_establish_connection(&node2.swarm, &node3.swarm).await;
+    manually_establish_connection(&node1.swarm, &node3.swarm).await;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+
+    let conn = node1.swarm.transport.get_connection(node3.did()).unwrap();
+    let old_attempt = conn.attempt_id;
+    conn.connection.simulate_ice_failure().unwrap();
+    assert_eq!(
+        conn.webrtc_connection_state(),
+        WebrtcConnectionState::Failed
+    );
+
+    // The offer goes through node2 since the failed connection leaves DHT.
+    node1.swarm.restart_ice(node3.did()).await.unwrap();
+
+    let new_attempt = node1.swarm.transport.connection_attempt(node3.did());
+    assert!(new_attempt.is_some());
+    assert_ne!(new_attempt, old_attempt);
+    // The offer is answered by the existing connection of node3.
+    assert_eq!(
+        node3.swarm.transport.connection_attempt(node1.did()),
+        new_attempt
+    );
+    for (node, peer) in [(&node1, node3.did()), (&node3, node1.did())] {
+        assert_eq!(
+            node.swarm
+                .transport
+                .get_connection(peer)
+                .unwrap()
+                .webrtc_connection_state(),
+            WebrtcConnectionState::Connected
+        );
+    }
+    // Both sides join DHT again, which they left on failure.
+    wait_for_msgs([&node1, &node2, &node3]).await;
+    assert!(node1
+        .dht()
+        .successors()
+        .list()
+        .unwrap()
+        .contains(&node3.did()));
+    assert!(node3
+        .dht()
+        .successors()
+        .list()
+        .unwrap()
+        .contains(&node1.did()));
+    node1
+        .swarm
+        .send_message(Message::custom(b"recovered").unwrap(), node3.did())
+        .await
+        .unwrap();
+
+    // Nothing to restart for a peer without connection.
+    let unknown = SecretKey::random().address().into();
+    let res = node1.swarm.restart_ice(unknown).await;
+    assert!(matches!(res, Err(Error::SwarmMissDidInTable(did)) if did == unknown));
+}
+
+#[tokio::test]
+async fn test_max_concurrent_connects() {
+    let keys = gen_ordered_keys(3);
+    let node1 = prepare_node_with_builder(keys[0], |b| {
+        b.transport_kind(TransportKind::Loopback)
+            .max_concurrent_connects(1)
+    })
+    .await;
+    let node2 =
+        prepare_node_with_builder(keys[1], |b| b.transport_kind(TransportKind::Loopback)).await;
+    let node3 =
+        prepare_node_with_builder(keys[2], |b| b.transport_kind(TransportKind::Loopback)).await;
+
+    let offer = node1.swarm.create_offer(node2.did()).await.unwrap();
+    assert_eq!(node1.swarm.connects_in_progress(), 1);
+
+    // The permit is held after the offer is created, until the data channel opens.
+    let next_offer = node1.swarm.create_offer(node3.did());
+    futures::pin_mut!(next_offer);
+    assert!(timeout(Duration::from_millis(100), &mut next_offer)
+        .await
+        .is_err());
+    assert!(node1.swarm.transport.get_connection(node3.did()).is_none());
+
+    let answer = node2.swarm.answer_offer(offer).await.unwrap();
+    node1.swarm.accept_answer(answer).await.unwrap();
+    let next_offer = timeout(Duration::from_secs(3), next_offer)
+        .await
+        .expect("handshake is not started after the former one finished")
+        .unwrap();
+    assert_eq!(node1.swarm.connects_in_progress(), 1);
+
+    let answer = node3.swarm.answer_offer(next_offer).await.unwrap();
+    node1.swarm.accept_answer(answer).await.unwrap();
+    timeout(Duration::from_secs(3), async {
+        while node1.swarm.connects_in_progress() > 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("permit is not released after the data channel opened");
+}
+
+#[tokio::test]
+async fn test_cancelled_connect_closes_its_connection() {
+    let node = prepare_node(SecretKey::random()).await;
+    let peer: Did = SecretKey::random().address().into();
+
+    // Poll until the connection is created, then drop the future before its offer is sent.
+    let mut connect = Box::pin(node.swarm.connect(peer));
+    loop {
+        assert!(futures::poll!(connect.as_mut()).is_pending());
+        if node.swarm.transport.get_connection(peer).is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+    drop(connect);
+    assert!(node.swarm.transport.get_connection(peer).is_none());
+    assert!(node.swarm.transport.connection_attempt(peer).is_none());
+
+    // A connect which is not cancelled keeps its connection, though sending offer fails
+    // without any next hop.
+    let _ = node.swarm.connect(peer).await;
+    assert!(node.swarm.transport.get_connection(peer).is_some());
+}
+
+#[tokio::test]
+async fn test_try_send_payload_to_not_open_channel() -> Result<()> {
+    let keys = gen_ordered_keys(2);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let node2 = prepare_node_with_builder(keys[1], loopback).await;
+    let transport = &node1.swarm.transport;
+    let payload = || {
+        MessagePayload::new_send(
+            Message::custom(b"best effort").unwrap(),
+            transport.session_sk(),
+            node2.did(),
+            node2.did(),
+        )
+        .unwrap()
+    };
+
+    // The connection is created by offer, but its channel is not open before answered.
+    let offer = node1.swarm.create_offer(node2.did()).await?;
+    assert!(transport.get_connection(node2.did()).is_some());
+    let res = timeout(
+        Duration::from_millis(100),
+        transport.try_send_payload(node2.did(), payload()),
+    )
+    .await
+    .expect("try_send_payload should not wait for the channel");
+    assert!(matches!(res, Err(Error::ChannelNotReady(did)) if did == node2.did()));
+
+    // Not connected at all.
+    let unknown = SecretKey::random().address().into();
+    let res = transport.try_send_payload(unknown, payload()).await;
+    assert!(matches!(res, Err(Error::ChannelNotReady(did)) if did == unknown));
+
+    let answer = node2.swarm.answer_offer(offer).await?;
+    node1.swarm.accept_answer(answer).await?;
+    transport.try_send_payload(node2.did(), payload()).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_renegotiate_failed_handshake() -> Result<()> {
+    let keys = gen_ordered_keys(3);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let node2 = prepare_node_with_builder(keys[1], loopback).await;
+    let node3 = prepare_node_with_builder(keys[2], loopback).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    manually_establish_connection(&node2.swarm, &node3.swarm).await;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+
+    // Node3 answers the offer of node1, then ICE fails on both sides before the answer is
+    // accepted.
+    let offer = node1
+        .swarm
+        .transport
+        .prepare_connection_offer(node3.did(), node1.swarm.inner_callback()?, None)
+        .await?;
+    node3
+        .swarm
+        .transport
+        .answer_remote_connection(node1.did(), node3.swarm.inner_callback()?, &offer)
+        .await?;
+    assert!(node1.swarm.transport.is_handshaking(node3.did()));
+    assert!(node3.swarm.transport.is_handshaking(node1.did()));
+    node3
+        .swarm
+        .transport
+        .get_connection(node1.did())
+        .unwrap()
+        .connection
+        .simulate_ice_failure()?;
+
+    // Only node1, which has the smaller Did, asks for renegotiation. Node3 closes its side
+    // and sends a fresh offer to node1 through node2.
+    wait_for_msgs([&node1, &node2, &node3]).await;
+
+    let conn = timeout(
+        Duration::from_secs(10),
+        node1.swarm.transport.get_and_check_connection(node3.did()),
+    )
+    .await
+    .expect("data channel should open by the second handshake")
+    .expect("connection of the second handshake should exist");
+    assert!(conn.attempt_id.is_some());
+    assert_ne!(conn.attempt_id, offer.attempt_id);
+    assert_eq!(
+        node3.swarm.transport.connection_attempt(node1.did()),
+        conn.attempt_id
+    );
+    wait_for_msgs([&node1, &node2, &node3]).await;
+    assert!(!node1.swarm.transport.is_handshaking(node3.did()));
+
+    // An opened connection is not torn down by renegotiation.
+    let res = node3
+        .swarm
+        .transport
+        .answer_renegotiation(
+            node1.did(),
+            node3.swarm.inner_callback()?,
+            &ConnectNodeRenegotiate { attempt_id: None },
+        )
+        .await;
+    assert!(matches!(res, Err(Error::AlreadyConnected)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_connect_latency_recorded() {
+    let keys = gen_ordered_keys(3);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let node2 = prepare_node_with_builder(keys[1], loopback).await;
+    let node3 = prepare_node_with_builder(keys[2], loopback).await;
+    assert!(node1.swarm.avg_connect_latency().is_none());
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+    let metrics = node1.swarm.connect_metrics();
+    assert_eq!(metrics.succeeded.count, 1);
+    assert_eq!(metrics.failed.count, 0);
+    assert!(node1.swarm.avg_connect_latency().is_some());
+    assert_eq!(node2.swarm.connect_metrics().succeeded.count, 1);
+
+    // An offer never answered is recorded as failed once it's closed.
+    node1.swarm.create_offer(node3.did()).await.unwrap();
+    node1.swarm.disconnect(node3.did()).await.unwrap();
+    let metrics = node1.swarm.connect_metrics();
+    assert_eq!(metrics.succeeded.count, 1);
+    assert_eq!(metrics.failed.count, 1);
+}
+
+/// A [TransportFactory] creating loopback connections, which counts connections it created.
+/// Connections are given as [AnyConnection::Extension] if `extension` is set, like a transport
+/// implemented out of this crate.
+struct LoopbackFactory {
+    transport: LoopbackTransport,
+    created: AtomicUsize,
+    extension: bool,
+}
+
+impl LoopbackFactory {
+    fn new() -> Self {
+        Self {
+            transport: LoopbackTransport::new("", None),
+            created: AtomicUsize::new(0),
+            extension: false,
+        }
+    }
+
+    fn extension() -> Self {
+        Self {
+            extension: true,
+            ..Self::new()
+        }
+    }
+
+    fn wrap(&self, conn: AnyConnection) -> AnyConnection {
+        if self.extension {
+            AnyConnection::Extension(Arc::new(conn))
+        } else {
+            conn
+        }
+    }
+}
+
+#[async_trait]
+impl TransportFactory for LoopbackFactory {
+    async fn new_connection(
+        &self,
+        cid: &str,
+        callback: BoxedTransportCallback,
+    ) -> TransportResult<()> {
+        self.created.fetch_add(1, Ordering::SeqCst);
+        self.transport.new_connection(cid, callback).await
+    }
+
+    async fn close_connection(&self, cid: &str) -> TransportResult<()> {
+        self.transport.close_connection(cid).await
+    }
+
+    fn connection(&self, cid: &str) -> TransportResult<AnyConnection> {
+        self.transport
+            .connection(cid)
+            .map(|c| self.wrap(AnyConnection::Loopback(c)))
+    }
+
+    fn connections(&self) -> Vec<(String, AnyConnection)> {
+        self.transport
+            .connections()
+            .into_iter()
+            .map(|(cid, c)| (cid, self.wrap(AnyConnection::Loopback(c))))
+            .collect()
+    }
+}
+
+#[tokio::test]
+async fn test_transport_factory_of_peer() {
+    let keys = gen_ordered_keys(2);
+    let did1: Did = keys[0].address().into();
+    let did2: Did = keys[1].address().into();
+    let factory1 = Arc::new(LoopbackFactory::new());
+    let factory2 = Arc::new(LoopbackFactory::extension());
+
+    // Both nodes use webrtc by default, but connect to each other by their factories.
+    let f1 = factory1.clone();
+    let node1 = prepare_node_with_builder(keys[0], |b| b.transport_factory(did2, f1)).await;
+    let f2 = factory2.clone();
+    let node2 = prepare_node_with_builder(keys[1], |b| b.transport_factory(did1, f2)).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+    assert_eq!(factory1.created.load(Ordering::SeqCst), 1);
+    assert_eq!(factory2.created.load(Ordering::SeqCst), 1);
+
+    let conn = node1.swarm.transport.get_connection(did2).unwrap();
+    assert!(matches!(conn.connection, AnyConnection::Loopback(_)));
+    let conn = node2.swarm.transport.get_connection(did1).unwrap();
+    assert!(matches!(conn.connection, AnyConnection::Extension(_)));
+    assert_eq!(
+        conn.webrtc_connection_state(),
+        WebrtcConnectionState::Connected
+    );
+    assert_eq!(node1.swarm.transport.get_connection_ids(), vec![did2]);
+
+    node1
+        .swarm
+        .send_message(Message::custom(b"hello").unwrap(), did2)
+        .await
+        .unwrap();
+    let payload = timeout(Duration::from_secs(3), node2.listen_once())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(
+        payload.transaction.data(),
+        Ok(Message::CustomMessage(_))
+    ));
+
+    node1.swarm.disconnect(did2).await.unwrap();
+    assert!(node1.swarm.transport.get_connection(did2).is_none());
+    assert!(factory1.connection(&did2.to_string()).is_err());
+}
+
+#[derive(Default)]
+struct RegisterRecorder {
+    events: std::sync::Mutex<Vec<(Did, bool)>>,
+}
+
+#[async_trait]
+impl SwarmCallback for RegisterRecorder {
+    async fn on_event(
+        &self,
+        event: &SwarmEvent,
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        if let SwarmEvent::TransportRegistered { peer, replaced } = event {
+            self.events.lock().unwrap().push((*peer, *replaced));
+        }
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_register_many_transports() {
+    let keys = gen_ordered_keys(1);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let recorder = Arc::new(RegisterRecorder::default());
+    node1.swarm.set_callback(recorder.clone()).unwrap();
+
+    let peers: Vec<Did> = (0..50)
+        .map(|_| SecretKey::random().address().into())
+        .collect();
+    let register = |factory: &Arc<LoopbackFactory>, peers: &[Did]| {
+        peers
+            .iter()
+            .map(|peer| (*peer, factory.clone() as SharedTransportFactory))
+            .collect::<Vec<_>>()
+    };
+
+    // The first five peers are connected by another factory, the next five by the default
+    // transport.
+    let old = Arc::new(LoopbackFactory::new());
+    node1
+        .swarm
+        .register_many(register(&old, &peers[..5]))
+        .await
+        .unwrap();
+    for peer in peers[..10].iter() {
+        node1.swarm.create_offer(*peer).await.unwrap();
+    }
+    assert_eq!(old.created.load(Ordering::SeqCst), 5);
+    assert_eq!(node1.swarm.transport.get_connections().len(), 10);
+    recorder.events.lock().unwrap().clear();
+
+    let new = Arc::new(LoopbackFactory::new());
+    node1
+        .swarm
+        .register_many(register(&new, &peers))
+        .await
+        .unwrap();
+
+    // All are registered, and the displaced connections are closed.
+    assert_eq!(
+        node1
+            .swarm
+            .transport
+            .transport_factories
+            .read()
+            .unwrap()
+            .len(),
+        50
+    );
+    assert_eq!(
+        *recorder.events.lock().unwrap(),
+        peers
+            .iter()
+            .enumerate()
+            .map(|(i, peer)| (*peer, i < 10))
+            .collect::<Vec<_>>()
+    );
+    assert!(old.connections().is_empty());
+    assert!(node1.swarm.transport.get_connections().is_empty());
+
+    // Connections are created by the registered factory from now on.
+    node1.swarm.create_offer(peers[7]).await.unwrap();
+    assert_eq!(new.created.load(Ordering::SeqCst), 1);
+    assert!(new.connection(&peers[7].to_string()).is_ok());
+
+    let err = node1
+        .swarm
+        .register_many(register(&new, &[node1.did()]))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::ShouldNotConnectSelf));
+}
+
+#[tokio::test]
+async fn test_accept_answer_twice() {
+    let keys = gen_ordered_keys(2);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let node2 = prepare_node_with_builder(keys[1], loopback).await;
+
+    let offer = node1.swarm.create_offer(node2.did()).await.unwrap();
+    let answer = node2.swarm.answer_offer(offer).await.unwrap();
+    node1.swarm.accept_answer(answer.clone()).await.unwrap();
+    wait_for_msgs([&node1, &node2]).await;
+
+    // The answer is applied in stable state the second time.
+    let err = node1.swarm.accept_answer(answer).await.unwrap_err();
+    assert!(matches!(err, Error::InvalidSignalingState(did, _) if did == node2.did()));
+
+    // The connection is kept.
+    let conn = node1.swarm.transport.get_connection(node2.did()).unwrap();
+    assert_eq!(
+        conn.webrtc_connection_state(),
+        WebrtcConnectionState::Connected
+    );
+}
@@ -1,21 +1,46 @@
+use std::io::Cursor;
+use std::io::Write;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::Mutex;
 
+use async_trait::async_trait;
 use rings_transport::core::transport::WebrtcConnectionState;
 use tokio::time::sleep;
 use tokio::time::Duration;
 
+use crate::consts::PROTOCOL_VERSION;
+use crate::consts::TOPIC_MAX_SUBSCRIPTIONS_PER_SUBSCRIBER;
 use crate::dht::successor::SuccessorReader;
 use crate::dht::vnode::VirtualNode;
+use crate::dht::Chord;
+use crate::dht::Did;
 use crate::ecc::tests::gen_ordered_keys;
 use crate::ecc::SecretKey;
+use crate::error::Error;
 use crate::error::Result;
 use crate::message;
 use crate::message::Encoder;
+use crate::message::FileMetadata;
 use crate::message::FindSuccessorReportHandler;
 use crate::message::FindSuccessorThen;
 use crate::message::Message;
+use crate::message::PayloadSender;
+use crate::message::PublishTopic;
+use crate::message::RouteToKey;
 use crate::prelude::vnode::VNodeOperation;
+use crate::swarm::callback::SwarmCallback;
+use crate::swarm::FileReceiver;
+use crate::swarm::RateLimit;
+use crate::swarm::Reachability;
+use crate::swarm::Route;
+use crate::swarm::SwarmBuilder;
+use crate::swarm::TransportKind;
+use crate::tests::default::assert_no_more_msg;
 use crate::tests::default::prepare_node;
+use crate::tests::default::prepare_node_with_builder;
+use crate::tests::default::wait_for_msgs;
+use crate::tests::default::Node;
 use crate::tests::manually_establish_connection;
 
 #[tokio::test]
@@ -400,3 +425,1286 @@ async fn test_handle_storage() -> Result<()> {
     assert_eq!(data.data[0].clone().decode::<String>().unwrap(), message);
     Ok(())
 }
+
+#[tokio::test]
+async fn test_handle_rate_limit() -> Result<()> {
+    let keys = gen_ordered_keys(2);
+    let node1 = prepare_node(keys[0]).await;
+    let node2 = prepare_node_with_builder(keys[1], |b| {
+        b.rate_limit(RateLimit {
+            messages_per_sec: 1,
+            burst: 3,
+            disconnect_after: None,
+        })
+    })
+    .await;
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    // Bucket is refilled during waiting.
+    wait_for_msgs([&node1, &node2]).await;
+
+    for i in 0..10u8 {
+        node1
+            .swarm
+            .send_message(Message::custom(&[i])?, node2.did())
+            .await?;
+    }
+
+    let mut received = 0;
+    while let Ok(Some(payload)) =
+        tokio::time::timeout(Duration::from_secs(3), node2.listen_once()).await
+    {
+        if let Message::CustomMessage(_) = payload.transaction.data()? {
+            received += 1;
+        }
+    }
+
+    assert!((3..=4).contains(&received), "received {received}");
+    assert_eq!(received + node2.swarm.rate_limited(node1.did()), 10);
+    assert_eq!(node1.swarm.rate_limited(node2.did()), 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_send_message_too_large() -> Result<()> {
+    let keys = gen_ordered_keys(2);
+    let node1 = prepare_node_with_builder(keys[0], |b| b.max_message_size(1024)).await;
+    let node2 = prepare_node(keys[1]).await;
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+
+    let result = node1
+        .swarm
+        .send_message(Message::custom(&[0; 2048])?, node2.did())
+        .await;
+    assert!(matches!(result, Err(Error::MessageTooLarge(_))));
+
+    node1
+        .swarm
+        .send_message(Message::custom(b"small")?, node2.did())
+        .await?;
+    let payload = tokio::time::timeout(Duration::from_secs(3), node2.listen_once())
+        .await
+        .expect("small message is not received")
+        .unwrap();
+    assert!(matches!(
+        payload.transaction.data()?,
+        Message::CustomMessage(_)
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reject_inbound_message_too_large() -> Result<()> {
+    let keys = gen_ordered_keys(2);
+    let node1 = prepare_node(keys[0]).await;
+    let node2 = prepare_node_with_builder(keys[1], |b| b.max_message_size(100_000)).await;
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+
+    // It's sent in chunks, the first of them is enough to reject it.
+    let _ = node1
+        .swarm
+        .send_message(Message::custom(&[0; 200_000])?, node2.did())
+        .await;
+
+    while let Ok(Some(payload)) =
+        tokio::time::timeout(Duration::from_secs(3), node2.listen_once()).await
+    {
+        assert!(!matches!(
+            payload.transaction.data()?,
+            Message::CustomMessage(_)
+        ));
+    }
+    assert!(node2.swarm.transport.get_connection(node1.did()).is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_count_undecodable_messages() -> Result<()> {
+    use rings_transport::core::callback::TransportCallback;
+
+    use crate::consts::DECODE_FAILURES_THRESHOLD;
+    use crate::swarm::DECODE_FAILURES_METRIC;
+
+    let keys = gen_ordered_keys(2);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let node2 = prepare_node_with_builder(keys[1], loopback).await;
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+
+    // A raw frame whose data is not a payload.
+    let malformed = [PROTOCOL_VERSION, 0, 0xde, 0xad, 0xbe, 0xef];
+    let callback = node2.swarm.inner_callback()?;
+    let cid = node1.did().to_string();
+    assert!(callback.on_message(&cid, &malformed).await.is_err());
+
+    let failures = node2.swarm.decode_failures();
+    assert_eq!(failures.name, DECODE_FAILURES_METRIC);
+    assert_eq!(failures.total, 1);
+    assert_eq!(failures.by_peer.get(&node1.did()), Some(&1));
+    assert!(node2.swarm.transport.get_connection(node1.did()).is_some());
+
+    // The peer keeps sending them, so it's disconnected as misbehaving.
+    for _ in 1..DECODE_FAILURES_THRESHOLD {
+        assert!(callback.on_message(&cid, &malformed).await.is_err());
+    }
+    let failures = node2.swarm.decode_failures();
+    assert_eq!(failures.total, DECODE_FAILURES_THRESHOLD);
+    assert!(failures.by_peer.is_empty());
+    assert!(node2.swarm.transport.get_connection(node1.did()).is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_decode_failures_are_counted_within_window() -> Result<()> {
+    use rings_transport::core::callback::TransportCallback;
+
+    use crate::consts::DECODE_FAILURES_THRESHOLD;
+    use crate::consts::DECODE_FAILURES_WINDOW_MS;
+    use crate::utils::get_epoch_ms;
+    use crate::utils::MockClock;
+
+    let keys = gen_ordered_keys(2);
+    let clock = Arc::new(MockClock::new(get_epoch_ms()));
+    let node1 = prepare_node_with_builder(keys[0], |b: SwarmBuilder| {
+        b.transport_kind(TransportKind::Loopback)
+    })
+    .await;
+    let node2 = prepare_node_with_builder(keys[1], |b: SwarmBuilder| {
+        b.transport_kind(TransportKind::Loopback)
+            .clock(clock.clone())
+    })
+    .await;
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+
+    let malformed = [PROTOCOL_VERSION, 0, 0xde, 0xad, 0xbe, 0xef];
+    let callback = node2.swarm.inner_callback()?;
+    let cid = node1.did().to_string();
+
+    // Failures spread over more than the window never reach the threshold.
+    for _ in 1..DECODE_FAILURES_THRESHOLD {
+        assert!(callback.on_message(&cid, &malformed).await.is_err());
+    }
+    clock.advance(Duration::from_millis(DECODE_FAILURES_WINDOW_MS as u64));
+    assert!(callback.on_message(&cid, &malformed).await.is_err());
+    let failures = node2.swarm.decode_failures();
+    assert_eq!(failures.total, DECODE_FAILURES_THRESHOLD);
+    assert_eq!(failures.by_peer.get(&node1.did()), Some(&1));
+    assert!(node2.swarm.transport.get_connection(node1.did()).is_some());
+
+    // Failures of a peer are forgotten once it's disconnected.
+    node2.swarm.disconnect(node1.did()).await?;
+    assert!(node2.swarm.decode_failures().by_peer.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_disconnect_peer_of_other_protocol_version() -> Result<()> {
+    use rings_transport::core::callback::TransportCallback;
+
+    let keys = gen_ordered_keys(2);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let node2 = prepare_node_with_builder(keys[1], loopback).await;
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+
+    // A frame of the previous version, which is not counted as a decode failure.
+    let frame = [PROTOCOL_VERSION - 1, 0, 0xde, 0xad, 0xbe, 0xef];
+    let callback = node2.swarm.inner_callback()?;
+    let cid = node1.did().to_string();
+    assert!(callback.on_message(&cid, &frame).await.is_err());
+
+    assert_eq!(node2.swarm.decode_failures().total, 0);
+    assert!(node2.swarm.transport.get_connection(node1.did()).is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_relay_over_loopback() -> Result<()> {
+    let keys = gen_ordered_keys(3);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let node2 = prepare_node_with_builder(keys[1], loopback).await;
+    let node3 = prepare_node_with_builder(keys[2], loopback).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    manually_establish_connection(&node2.swarm, &node3.swarm).await;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+    node1.assert_transports(vec![node2.did()]);
+    node3.assert_transports(vec![node2.did()]);
+
+    node1
+        .swarm
+        .send_message(Message::custom(b"over loopback")?, node3.did())
+        .await?;
+
+    let payload = tokio::time::timeout(Duration::from_secs(3), node3.listen_once())
+        .await
+        .expect("message is not relayed to node3")
+        .unwrap();
+    let Message::CustomMessage(msg) = payload.transaction.data()? else {
+        panic!("unexpected message");
+    };
+    assert_eq!(msg.0, b"over loopback".to_vec());
+    assert_eq!(payload.relay.origin_sender(), node1.did());
+    assert_eq!(payload.relay.path, vec![node1.did(), node2.did()]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_send_message_to_via_first_hop() -> Result<()> {
+    let keys = gen_ordered_keys(3);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let node2 = prepare_node_with_builder(keys[1], loopback).await;
+    let node3 = prepare_node_with_builder(keys[2], loopback).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    manually_establish_connection(&node2.swarm, &node3.swarm).await;
+    manually_establish_connection(&node1.swarm, &node3.swarm).await;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+
+    // node3 is connected to node1, but the message goes through node2 first.
+    node1
+        .swarm
+        .send_message_to(
+            Message::custom(b"via node2")?,
+            node3.did(),
+            Some(node2.did()),
+        )
+        .await?;
+
+    let relayed = tokio::time::timeout(Duration::from_secs(3), node2.listen_once())
+        .await
+        .expect("message is not sent to node2")
+        .unwrap();
+    assert_eq!(relayed.relay.destination, node3.did());
+    assert_eq!(relayed.transaction.destination, node3.did());
+
+    let payload = tokio::time::timeout(Duration::from_secs(3), node3.listen_once())
+        .await
+        .expect("message is not relayed to node3")
+        .unwrap();
+    let Message::CustomMessage(msg) = payload.transaction.data()? else {
+        panic!("unexpected message");
+    };
+    assert_eq!(msg.0, b"via node2");
+    assert_eq!(payload.relay.path, vec![node1.did(), node2.did()]);
+
+    assert!(node1
+        .swarm
+        .send_message_to(Message::custom(b"loop")?, node3.did(), Some(node1.did()))
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reroute_report_when_return_path_is_gone() -> Result<()> {
+    let keys = gen_ordered_keys(4);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let node2 = prepare_node_with_builder(keys[1], loopback).await;
+    let node3 = prepare_node_with_builder(keys[2], loopback).await;
+    let node4 = prepare_node_with_builder(keys[3], loopback).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    manually_establish_connection(&node2.swarm, &node3.swarm).await;
+    manually_establish_connection(&node3.swarm, &node4.swarm).await;
+    manually_establish_connection(&node4.swarm, &node1.swarm).await;
+    wait_for_msgs([&node1, &node2, &node3, &node4]).await;
+
+    node1
+        .swarm
+        .send_message_to(Message::custom(b"ping")?, node3.did(), Some(node2.did()))
+        .await?;
+    let payload = loop {
+        let payload = tokio::time::timeout(Duration::from_secs(3), node3.listen_once())
+            .await
+            .expect("message is not relayed to node3")
+            .unwrap();
+        if let Message::CustomMessage(_) = payload.transaction.data()? {
+            break payload;
+        }
+    };
+    assert_eq!(payload.relay.path, vec![node1.did(), node2.did()]);
+
+    // node2 on the return path is gone, the report goes through node4 instead.
+    node3.swarm.disconnect(node2.did()).await?;
+    node3
+        .swarm
+        .transport
+        .send_report_message(&payload, Message::custom(b"pong")?)
+        .await?;
+
+    loop {
+        let report = tokio::time::timeout(Duration::from_secs(3), node1.listen_once())
+            .await
+            .expect("report is not received by node1")
+            .unwrap();
+        if let Message::CustomMessage(msg) = report.transaction.data()? {
+            assert_eq!(msg.0, b"pong");
+            assert_eq!(report.transaction.tx_id, payload.transaction.tx_id);
+            assert_eq!(report.relay.path, vec![node3.did(), node4.did()]);
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_relay_metrics_of_reported_message() -> Result<()> {
+    let keys = gen_ordered_keys(4);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let node2 = prepare_node_with_builder(keys[1], loopback).await;
+    let node3 = prepare_node_with_builder(keys[2], loopback).await;
+    let node4 = prepare_node_with_builder(keys[3], loopback).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    manually_establish_connection(&node2.swarm, &node3.swarm).await;
+    manually_establish_connection(&node3.swarm, &node4.swarm).await;
+    wait_for_msgs([&node1, &node2, &node3, &node4]).await;
+
+    // Pin the path node1 -> node2 -> node3 -> node4 and back.
+    node2.swarm.reroute(node4.did(), vec![node3.did()])?;
+    node3.swarm.reroute(node1.did(), vec![node2.did()])?;
+
+    node1
+        .swarm
+        .send_message_to(Message::custom(b"ping")?, node4.did(), Some(node2.did()))
+        .await?;
+    let payload = loop {
+        let payload = tokio::time::timeout(Duration::from_secs(3), node4.listen_once())
+            .await
+            .expect("message is not relayed to node4")
+            .unwrap();
+        if let Message::CustomMessage(_) = payload.transaction.data()? {
+            break payload;
+        }
+    };
+    assert_eq!(payload.relay.path, vec![
+        node1.did(),
+        node2.did(),
+        node3.did()
+    ]);
+    assert_eq!(node1.swarm.relay_metrics().hops.count, 0);
+
+    node4
+        .swarm
+        .transport
+        .send_report_message(&payload, Message::custom(b"pong")?)
+        .await?;
+    loop {
+        let report = tokio::time::timeout(Duration::from_secs(3), node1.listen_once())
+            .await
+            .expect("report is not received by node1")
+            .unwrap();
+        if let Message::CustomMessage(_) = report.transaction.data()? {
+            assert_eq!(report.transaction.tx_id, payload.transaction.tx_id);
+            break;
+        }
+    }
+
+    let metrics = node1.swarm.relay_metrics();
+    assert_eq!(metrics.hops.name, "rings_relay_hops");
+    assert_eq!(metrics.hops.count, 1);
+    assert_eq!(metrics.hops.sum, 3);
+    assert_eq!(metrics.hops.count_le(2), Some(0));
+    assert_eq!(metrics.hops.count_le(3), Some(1));
+    assert_eq!(metrics.latency_ms.name, "rings_relay_latency_ms");
+    assert_eq!(metrics.latency_ms.count, 1);
+
+    // Relays don't record messages they forward.
+    assert_eq!(node2.swarm.relay_metrics().hops.count, 0);
+    assert_eq!(node3.swarm.relay_metrics().hops.count, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_relay_encrypted_message() -> Result<()> {
+    let keys = gen_ordered_keys(3);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let node2 = prepare_node_with_builder(keys[1], loopback).await;
+    let node3 = prepare_node_with_builder(keys[2], loopback).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    manually_establish_connection(&node2.swarm, &node3.swarm).await;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+
+    let secret = b"only for node3".to_vec();
+    node1
+        .swarm
+        .send_encrypted_message(
+            Message::custom(&secret)?,
+            node3.did(),
+            node3.swarm.session_pubkey(),
+        )
+        .await?;
+
+    // The middle hop can route the payload, but cannot read the body.
+    let relayed = tokio::time::timeout(Duration::from_secs(3), node2.listen_once())
+        .await
+        .expect("message is not relayed by node2")
+        .unwrap();
+    assert_eq!(relayed.relay.destination, node3.did());
+    let Message::Encrypted(encrypted) = relayed.transaction.data()? else {
+        panic!("unexpected message");
+    };
+    assert!(!encrypted
+        .0
+        .windows(secret.len())
+        .any(|w| w == secret.as_slice()));
+    assert!(relayed.decrypt(node2.swarm.transport.session_sk()).is_err());
+
+    // The destination gets the decrypted body.
+    let payload = tokio::time::timeout(Duration::from_secs(3), node3.listen_once())
+        .await
+        .expect("message is not relayed to node3")
+        .unwrap();
+    let Message::CustomMessage(msg) = payload.transaction.data()? else {
+        panic!("unexpected message");
+    };
+    assert_eq!(msg.0, secret);
+    assert_eq!(payload.relay.origin_sender(), node1.did());
+    assert_eq!(payload.relay.path, vec![node1.did(), node2.did()]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reroute_after_direct_link_severed() -> Result<()> {
+    let keys = gen_ordered_keys(3);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let node2 = prepare_node_with_builder(keys[1], loopback).await;
+    let node3 = prepare_node_with_builder(keys[2], loopback).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    manually_establish_connection(&node2.swarm, &node3.swarm).await;
+    manually_establish_connection(&node1.swarm, &node3.swarm).await;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+
+    assert!(node1.swarm.reroute(node3.did(), vec![node3.did()]).is_err());
+    assert!(matches!(
+        node1
+            .swarm
+            .reroute(node3.did(), vec![node2.did(), node2.did()]),
+        Err(Error::MultiHopRoute)
+    ));
+    node1.swarm.reroute(node3.did(), vec![node2.did()])?;
+    assert_eq!(node1.swarm.route(node3.did())?, Route::Direct);
+
+    node1.swarm.disconnect(node3.did()).await?;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+    assert_eq!(node1.swarm.route(node3.did())?, Route::Relay(node2.did()));
+
+    node1
+        .swarm
+        .send_message(Message::custom(b"via relay")?, node3.did())
+        .await?;
+
+    let payload = tokio::time::timeout(Duration::from_secs(3), node3.listen_once())
+        .await
+        .expect("message is not relayed to node3")
+        .unwrap();
+    let Message::CustomMessage(msg) = payload.transaction.data()? else {
+        panic!("unexpected message");
+    };
+    assert_eq!(msg.0, b"via relay".to_vec());
+    assert_eq!(payload.relay.path, vec![node1.did(), node2.did()]);
+
+    // Removing the route falls back to DHT.
+    node1.swarm.reroute(node3.did(), vec![])?;
+    assert!(matches!(node1.swarm.route(node3.did())?, Route::Dht(_)));
+    Ok(())
+}
+
+/// Wait for a message of topic delivered to the node, skipping other messages.
+async fn wait_for_published(node: &Node) -> Option<PublishTopic> {
+    let recv = async {
+        loop {
+            let payload = node.listen_once().await?;
+            if let Ok(Message::PublishTopic(msg)) = payload.transaction.data() {
+                if msg.delivered {
+                    return Some(msg);
+                }
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(3), recv)
+        .await
+        .ok()
+        .flatten()
+}
+
+#[tokio::test]
+async fn test_publish_to_subscribers() -> Result<()> {
+    let keys = gen_ordered_keys(3);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let node2 = prepare_node_with_builder(keys[1], loopback).await;
+    let node3 = prepare_node_with_builder(keys[2], loopback).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    manually_establish_connection(&node2.swarm, &node3.swarm).await;
+    manually_establish_connection(&node1.swarm, &node3.swarm).await;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+
+    let topic = "news";
+    node2.swarm.subscribe(topic).await?;
+    node3.swarm.subscribe(topic).await?;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+
+    node1.swarm.publish(topic, b"hello".to_vec()).await?;
+    for node in [&node2, &node3] {
+        let msg = wait_for_published(node)
+            .await
+            .expect("published message is not delivered");
+        assert_eq!(msg.topic, topic);
+        assert_eq!(msg.data, b"hello".to_vec());
+        assert_eq!(msg.publisher()?, node1.did());
+
+        // The publisher can't be forged by the responsible node.
+        let mut forged = msg.clone();
+        forged.data = b"forged".to_vec();
+        assert!(forged.publisher().is_err());
+    }
+    wait_for_msgs([&node1, &node2, &node3]).await;
+
+    node3.swarm.unsubscribe(topic).await?;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+
+    node1.swarm.publish(topic, b"again".to_vec()).await?;
+    let msg = wait_for_published(&node2)
+        .await
+        .expect("published message is not delivered");
+    assert_eq!(msg.data, b"again".to_vec());
+    assert!(wait_for_published(&node3).await.is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_subscriptions_are_capped() -> Result<()> {
+    let node = prepare_node(SecretKey::random()).await;
+    let topics = (0..=TOPIC_MAX_SUBSCRIPTIONS_PER_SUBSCRIBER)
+        .map(|i| format!("topic{i}"))
+        .collect::<Vec<_>>();
+    // Current node is responsible for all topics without other nodes.
+    for topic in topics.iter() {
+        node.swarm.subscribe(topic).await?;
+    }
+    let recorded = node
+        .swarm
+        .transport
+        .topic_subscribers
+        .iter()
+        .filter(|e| e.value().contains_key(&node.did()))
+        .count();
+    assert_eq!(recorded, TOPIC_MAX_SUBSCRIPTIONS_PER_SUBSCRIBER);
+
+    // Refreshing a recorded subscription is still allowed.
+    node.swarm.subscribe(&topics[0]).await?;
+    assert!(node
+        .swarm
+        .transport
+        .topic_subscribers
+        .contains_key(&topics[0]));
+    Ok(())
+}
+
+/// Wait for a message routed to key delivered to the node, skipping other messages.
+async fn wait_for_routed(node: &Node) -> Option<RouteToKey> {
+    let recv = async {
+        loop {
+            let payload = node.listen_once().await?;
+            if let Ok(Message::RouteToKey(msg)) = payload.transaction.data() {
+                return Some(msg);
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(3), recv)
+        .await
+        .ok()
+        .flatten()
+}
+
+#[tokio::test]
+async fn test_send_to_key() -> Result<()> {
+    let keys = gen_ordered_keys(3);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let mut nodes = vec![];
+    for key in keys {
+        nodes.push(prepare_node_with_builder(key, loopback).await);
+    }
+    for (i, node1) in nodes.iter().enumerate() {
+        for node2 in nodes.iter().skip(i + 1) {
+            manually_establish_connection(&node1.swarm, &node2.swarm).await;
+        }
+    }
+    wait_for_msgs(nodes.iter()).await;
+
+    // Find the node responsible for the key by storing a value under it.
+    let key = "The quick brown fox jumps over the lazy dog";
+    nodes[0]
+        .swarm
+        .vnode_put(key, b"value".to_vec(), Duration::from_secs(600), 1)
+        .await?;
+    wait_for_msgs(nodes.iter()).await;
+    let vid = VirtualNode::gen_did(key)?;
+    let mut holders = vec![];
+    for (i, node) in nodes.iter().enumerate() {
+        if node.dht().storage.get(&vid.to_string()).await?.is_some() {
+            holders.push(i);
+        }
+    }
+    assert_eq!(holders.len(), 1);
+    let responsible = &nodes[holders[0]];
+
+    // Every node reaches the responsible one, including itself.
+    for sender in nodes.iter() {
+        sender
+            .swarm
+            .send_to_key(vid, Message::custom(b"to key")?)
+            .await?;
+        let msg = wait_for_routed(responsible)
+            .await
+            .expect("message to key is not delivered");
+        assert_eq!(msg.key, vid);
+        let Message::CustomMessage(custom) = msg.message()? else {
+            panic!("Expect custom message");
+        };
+        assert_eq!(custom.0, b"to key".to_vec());
+    }
+    for node in nodes.iter() {
+        if node.did() != responsible.did() {
+            assert!(wait_for_routed(node).await.is_none());
+        }
+    }
+
+    // Nested messages to key are rejected.
+    let nested = Message::RouteToKey(RouteToKey::new(vid, &Message::custom(b"to key")?)?);
+    assert!(nodes[0].swarm.send_to_key(vid, nested).await.is_err());
+    Ok(())
+}
+
+#[cfg(feature = "record")]
+#[tokio::test]
+async fn test_record_and_replay() -> Result<()> {
+    use crate::message::MessagePayload;
+    use crate::swarm::record;
+    use crate::swarm::record::Direction;
+    use crate::swarm::record::MessageRecorder;
+
+    async fn drain(node: &Node) -> Vec<MessagePayload> {
+        let mut payloads = vec![];
+        while let Ok(Some(payload)) =
+            tokio::time::timeout(Duration::from_secs(1), node.listen_once()).await
+        {
+            payloads.push(payload);
+        }
+        payloads
+    }
+
+    let keys = gen_ordered_keys(2);
+    let path = std::env::temp_dir().join(format!("rings-record-{}.jsonl", uuid::Uuid::new_v4()));
+    let recorder = MessageRecorder::create(&path)?;
+
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let node2 =
+        prepare_node_with_builder(keys[1], |b| loopback(b).message_recorder(recorder.clone()))
+            .await;
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    for i in 0..3u8 {
+        node1
+            .swarm
+            .send_message(Message::custom(&[i])?, node2.did())
+            .await?;
+    }
+    wait_for_msgs([&node1, &node2]).await;
+
+    recorder.flush()?;
+    let records = record::load(&path)?;
+    std::fs::remove_file(&path).unwrap();
+    assert!(records.windows(2).all(|w| w[0].ts_ms <= w[1].ts_ms));
+    assert!(records.iter().any(|r| r.direction == Direction::Outbound));
+    let inbound: Vec<MessagePayload> = records
+        .iter()
+        .filter(|r| r.direction == Direction::Inbound)
+        .inspect(|r| assert_eq!(r.peer, node1.did()))
+        .map(|r| r.payload.clone())
+        .collect();
+    let customs: Vec<Vec<u8>> = inbound
+        .iter()
+        .filter_map(|p| match p.transaction.data().unwrap() {
+            Message::CustomMessage(msg) => Some(msg.0),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(customs, vec![vec![0], vec![1], vec![2]]);
+
+    // Fresh swarms of the same key see the same payloads in the same order.
+    for _ in 0..2 {
+        let node = prepare_node(keys[1]).await;
+        let count = record::replay(&node.swarm, records.clone()).await?;
+        assert_eq!(count, inbound.len());
+        assert_eq!(drain(&node).await, inbound);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "record")]
+#[tokio::test]
+async fn test_record_encrypted_message() -> Result<()> {
+    use crate::swarm::record;
+    use crate::swarm::record::Direction;
+    use crate::swarm::record::MessageRecorder;
+
+    let keys = gen_ordered_keys(3);
+    let dir = std::env::temp_dir();
+    let paths = [0, 1].map(|_| dir.join(format!("rings-record-{}.jsonl", uuid::Uuid::new_v4())));
+    let ciphertext = MessageRecorder::create(&paths[0])?;
+    let plaintext = MessageRecorder::create(&paths[1])?.plaintext(true);
+
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let node2 = prepare_node_with_builder(keys[1], |b| {
+        loopback(b).message_recorder(ciphertext.clone())
+    })
+    .await;
+    let node3 =
+        prepare_node_with_builder(keys[2], |b| loopback(b).message_recorder(plaintext.clone()))
+            .await;
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    manually_establish_connection(&node1.swarm, &node3.swarm).await;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+
+    for node in [&node2, &node3] {
+        node1
+            .swarm
+            .send_encrypted_message(
+                Message::custom(b"secret")?,
+                node.did(),
+                node.swarm.session_pubkey(),
+            )
+            .await?;
+        assert!(
+            tokio::time::timeout(Duration::from_secs(3), node.listen_once())
+                .await
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    let mut messages = vec![];
+    for (recorder, path) in [(ciphertext, &paths[0]), (plaintext, &paths[1])] {
+        recorder.flush()?;
+        let records = record::load(path)?;
+        std::fs::remove_file(path).unwrap();
+        let record = records
+            .into_iter()
+            .filter(|r| r.direction == Direction::Inbound)
+            .last()
+            .unwrap();
+        messages.push(record.payload.transaction.data::<Message>()?);
+    }
+    // Only the recorder opting in plaintext writes the decrypted body.
+    assert!(matches!(messages[0], Message::Encrypted(_)));
+    assert!(matches!(messages[1], Message::CustomMessage(ref msg) if msg.0 == b"secret"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_send_message_to_self() -> Result<()> {
+    let keys = gen_ordered_keys(1);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+
+    let tx_id = node1
+        .swarm
+        .send_message(Message::custom(b"to myself")?, node1.did())
+        .await?;
+    let payload = tokio::time::timeout(Duration::from_secs(3), node1.listen_once())
+        .await
+        .expect("message is not delivered to self")
+        .unwrap();
+    let Message::CustomMessage(msg) = payload.transaction.data()? else {
+        panic!("unexpected message");
+    };
+    assert_eq!(msg.0, b"to myself".to_vec());
+    assert_eq!(payload.transaction.tx_id, tx_id);
+    assert_eq!(payload.relay.origin_sender(), node1.did());
+    assert_eq!(payload.relay.destination, node1.did());
+
+    // Encrypted message to self is decrypted by the local handler as well.
+    node1
+        .swarm
+        .send_encrypted_message(
+            Message::custom(b"secret to myself")?,
+            node1.did(),
+            node1.swarm.session_pubkey(),
+        )
+        .await?;
+    let payload = tokio::time::timeout(Duration::from_secs(3), node1.listen_once())
+        .await
+        .expect("encrypted message is not delivered to self")
+        .unwrap();
+    let Message::CustomMessage(msg) = payload.transaction.data()? else {
+        panic!("unexpected message");
+    };
+    assert_eq!(msg.0, b"secret to myself".to_vec());
+
+    assert!(node1.swarm.transport.get_connections().is_empty());
+    Ok(())
+}
+
+struct RejectingCallback;
+
+#[async_trait]
+impl SwarmCallback for RejectingCallback {
+    async fn on_validate(
+        &self,
+        _payload: &message::MessagePayload,
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        Err("rejected by callback".into())
+    }
+}
+
+#[tokio::test]
+async fn test_send_message_to_self_rejected() -> Result<()> {
+    let keys = gen_ordered_keys(1);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    node1.swarm.set_callback(Arc::new(RejectingCallback))?;
+
+    let err = node1
+        .swarm
+        .send_message(Message::custom(b"to myself")?, node1.did())
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::SwarmHandleMessage(ref e) if e == "rejected by callback"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_is_reachable() -> Result<()> {
+    let keys = gen_ordered_keys(4);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let node2 = prepare_node_with_builder(keys[1], loopback).await;
+    let node3 = prepare_node_with_builder(keys[2], loopback).await;
+    let node4 = prepare_node_with_builder(keys[3], loopback).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    manually_establish_connection(&node2.swarm, &node3.swarm).await;
+    manually_establish_connection(&node2.swarm, &node4.swarm).await;
+    wait_for_msgs([&node1, &node2, &node3, &node4]).await;
+
+    assert_eq!(node1.swarm.is_reachable(node2.did()), Reachability::Direct);
+
+    // Known by DHT, and relayed by node2 which precedes it.
+    node1.dht().join(node3.did())?;
+    assert_eq!(
+        node1.swarm.is_reachable(node3.did()),
+        Reachability::Relayed(vec![node2.did()])
+    );
+
+    // Relayed by an explicit route.
+    node1.swarm.reroute(node4.did(), vec![node2.did()])?;
+    assert_eq!(
+        node1.swarm.is_reachable(node4.did()),
+        Reachability::Relayed(vec![node2.did()])
+    );
+
+    let unknown = Did::from(42u32);
+    assert_eq!(node1.swarm.is_reachable(unknown), Reachability::Unknown);
+
+    // Checking doesn't connect.
+    node1.assert_transports(vec![node2.did()]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_broadcast_with_unreachable_peer() -> Result<()> {
+    let keys = gen_ordered_keys(4);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let node2 = prepare_node_with_builder(keys[2], loopback).await;
+    let node3 = prepare_node_with_builder(keys[3], loopback).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    manually_establish_connection(&node1.swarm, &node3.swarm).await;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+
+    // A successor known by DHT but never connected, so it's the next hop of itself.
+    let unreachable: Did = keys[1].address().into();
+    node1.dht().join(unreachable)?;
+
+    let dids = [node2.did(), unreachable, node3.did()];
+    let results = node1
+        .swarm
+        .broadcast(Message::custom(b"to all")?, &dids)
+        .await;
+    let results_dids: Vec<Did> = results.iter().map(|(did, _)| *did).collect();
+    assert_eq!(results_dids, dids);
+    assert!(results[0].1.is_ok());
+    assert!(results[1].1.is_err());
+    assert!(results[2].1.is_ok());
+
+    for node in [&node2, &node3] {
+        let payload = tokio::time::timeout(Duration::from_secs(3), node.listen_once())
+            .await
+            .expect("broadcast is not received")
+            .unwrap();
+        let Message::CustomMessage(msg) = payload.transaction.data()? else {
+            panic!("unexpected message");
+        };
+        assert_eq!(msg.0, b"to all".to_vec());
+        assert_eq!(payload.relay.destination, node.did());
+    }
+    Ok(())
+}
+
+/// Receiver keeping the file in memory. A resumed transfer overwrites bytes after its offset.
+#[derive(Default)]
+struct MemoryFileReceiver {
+    data: Arc<Mutex<Vec<u8>>>,
+    completed: Mutex<Vec<(uuid::Uuid, u64)>>,
+}
+
+struct SharedWriter(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl FileReceiver for MemoryFileReceiver {
+    fn open(
+        &self,
+        _peer: Did,
+        _metadata: &FileMetadata,
+        offset: u64,
+    ) -> Option<Box<dyn Write + Send>> {
+        self.data.lock().unwrap().truncate(offset as usize);
+        Some(Box::new(SharedWriter(self.data.clone())))
+    }
+
+    fn on_complete(
+        &self,
+        _peer: Did,
+        transfer_id: uuid::Uuid,
+        _metadata: &FileMetadata,
+        received: u64,
+    ) {
+        self.completed.lock().unwrap().push((transfer_id, received));
+    }
+}
+
+#[tokio::test]
+async fn test_send_file() -> Result<()> {
+    let keys = gen_ordered_keys(2);
+    let receiver = Arc::new(MemoryFileReceiver::default());
+    let node1 = prepare_node_with_builder(keys[0], |b: SwarmBuilder| {
+        b.transport_kind(TransportKind::Loopback)
+    })
+    .await;
+    let node2 = prepare_node_with_builder(keys[1], |b: SwarmBuilder| {
+        b.transport_kind(TransportKind::Loopback)
+            .file_receiver(receiver.clone())
+    })
+    .await;
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+
+    let data = (0..5 * 1024 * 1024)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<_>>();
+    let size = data.len() as u64;
+    let metadata = FileMetadata {
+        name: "data.bin".to_string(),
+        size,
+    };
+
+    let handle = node1
+        .swarm
+        .send_file(node2.did(), Cursor::new(data.clone()), metadata.clone());
+    let transfer_id = handle.transfer_id();
+    let progress = handle.progress();
+    assert_eq!(handle.await?, size);
+    assert_eq!(progress.sent_bytes(), size);
+    assert_eq!(progress.acked_bytes(), size);
+    assert!(*receiver.data.lock().unwrap() == data);
+    let completed = receiver.completed.lock().unwrap().clone();
+    assert_eq!(completed, vec![(transfer_id, size)]);
+
+    // Resume from an offset, only the rest of file is sent.
+    let offset = 3 * 1024 * 1024;
+    let handle = node1.swarm.send_file_from(
+        node2.did(),
+        Cursor::new(data[offset..].to_vec()),
+        metadata.clone(),
+        offset as u64,
+    );
+    let transfer_id = handle.transfer_id();
+    assert_eq!(handle.await?, size - offset as u64);
+    assert!(*receiver.data.lock().unwrap() == data);
+    let completed = receiver.completed.lock().unwrap().clone();
+    assert_eq!(completed.last(), Some(&(transfer_id, size)));
+
+    // Files are rejected by a node without file receiver.
+    let handle = node2
+        .swarm
+        .send_file(node1.did(), Cursor::new(data), metadata);
+    let transfer_id = handle.transfer_id();
+    assert!(matches!(
+        handle.await,
+        Err(Error::FileTransferRejected(id)) if id == transfer_id
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_incoming_files_are_bounded() -> Result<()> {
+    use crate::consts::FILE_ACK_TIMEOUT_MS;
+    use crate::consts::FILE_MAX_INCOMING_TRANSFERS;
+    use crate::message::FileChunk;
+    use crate::utils::get_epoch_ms;
+    use crate::utils::MockClock;
+
+    let keys = gen_ordered_keys(2);
+    let clock = Arc::new(MockClock::new(get_epoch_ms()));
+    let receiver = Arc::new(MemoryFileReceiver::default());
+    let node = prepare_node_with_builder(keys[0], |b: SwarmBuilder| {
+        b.transport_kind(TransportKind::Loopback)
+            .file_receiver(receiver.clone())
+            .clock(clock.clone())
+    })
+    .await;
+    let peer: Did = keys[1].address().into();
+    let first_chunk = || FileChunk {
+        transfer_id: uuid::Uuid::new_v4(),
+        metadata: Some(FileMetadata {
+            name: "data.bin".to_string(),
+            size: 2,
+        }),
+        offset: 0,
+        data: vec![0],
+        last: false,
+    };
+
+    for _ in 0..FILE_MAX_INCOMING_TRANSFERS {
+        let ack = node
+            .swarm
+            .transport
+            .receive_file_chunk(peer, &first_chunk())
+            .await;
+        assert!(!ack.rejected);
+        assert_eq!(ack.received, 1);
+    }
+    let ack = node
+        .swarm
+        .transport
+        .receive_file_chunk(peer, &first_chunk())
+        .await;
+    assert!(ack.rejected);
+
+    // Idle transfers are dropped after timeout, so new ones are accepted again.
+    clock.advance(Duration::from_millis(FILE_ACK_TIMEOUT_MS));
+    let ack = node
+        .swarm
+        .transport
+        .receive_file_chunk(peer, &first_chunk())
+        .await;
+    assert!(!ack.rejected);
+    assert_eq!(node.swarm.transport.incoming_files.len(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_drop_duplicate_message() -> Result<()> {
+    use crate::message::MessagePayload;
+
+    let keys = gen_ordered_keys(3);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let node2 = prepare_node_with_builder(keys[1], |b| loopback(b).dedup_window(1024)).await;
+    let node3 = prepare_node_with_builder(keys[2], loopback).await;
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    manually_establish_connection(&node1.swarm, &node3.swarm).await;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+
+    // The same payload arrives twice, as if it's relayed by two paths.
+    for node in [&node2, &node3] {
+        let payload = MessagePayload::new_send(
+            Message::custom(b"only once")?,
+            node1.swarm.transport.session_sk(),
+            node.did(),
+            node.did(),
+        )?;
+        node1.swarm.transport.send_payload(payload.clone()).await?;
+        node1.swarm.transport.send_payload(payload).await?;
+    }
+
+    let payload = node2.listen_once().await.unwrap();
+    let Message::CustomMessage(msg) = payload.transaction.data()? else {
+        panic!("unexpected message");
+    };
+    assert_eq!(msg.0, b"only once".to_vec());
+    assert_no_more_msg([&node2]).await;
+
+    // Duplicates are handled again if dedup is disabled, which is the default.
+    for _ in 0..2 {
+        let payload = node3.listen_once().await.unwrap();
+        assert!(matches!(
+            payload.transaction.data()?,
+            Message::CustomMessage(_)
+        ));
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_drop_expired_message_at_first_hop() -> Result<()> {
+    use crate::message::MessagePayload;
+
+    let keys = gen_ordered_keys(3);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let node2 = prepare_node_with_builder(keys[1], loopback).await;
+    let node3 = prepare_node_with_builder(keys[2], loopback).await;
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    manually_establish_connection(&node2.swarm, &node3.swarm).await;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+
+    let now = crate::utils::get_epoch_ms();
+    for (expires_at, data) in [(now - 1, "too late"), (now + 60 * 1000, "just in time")] {
+        let payload = MessagePayload::new_send_with_expiry(
+            Message::custom(data.as_bytes())?,
+            node1.swarm.transport.session_sk(),
+            node2.did(),
+            node3.did(),
+            Some(expires_at),
+        )?;
+        node1.swarm.transport.send_payload(payload).await?;
+    }
+
+    // The first deadline is passed already, so node2 drops it instead of relaying.
+    let payload = node3.listen_once().await.unwrap();
+    let Message::CustomMessage(msg) = payload.transaction.data()? else {
+        panic!("unexpected message");
+    };
+    assert_eq!(msg.0, b"just in time".to_vec());
+    assert_no_more_msg([&node3]).await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reject_message_out_of_delegated_scope() -> Result<()> {
+    use crate::message::MessagePayload;
+    use crate::message::MessageVerificationExt;
+    use crate::session::SessionScope;
+
+    let keys = gen_ordered_keys(2);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let node2 = prepare_node_with_builder(keys[1], loopback).await;
+    let sub = node1
+        .swarm
+        .transport
+        .session_sk()
+        .delegate(SessionScope::SEND_MESSAGE, 60 * 1000)?;
+    let callback = node2.swarm.inner_callback()?;
+
+    // Connecting with a send-only session is rejected.
+    let offer = node1
+        .swarm
+        .transport
+        .prepare_connection_offer(node2.did(), node1.swarm.inner_callback()?, None)
+        .await?;
+    let payload = MessagePayload::new_send(
+        Message::ConnectNodeSend(offer),
+        &sub,
+        node2.did(),
+        node2.did(),
+    )?;
+    let err = callback
+        .on_payload(&node1.did().to_string(), payload)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::NoPermission(did, _)) if *did == node1.did()
+    ));
+    assert!(node2.swarm.transport.get_connection(node1.did()).is_none());
+
+    // Sending a message is allowed.
+    let payload =
+        MessagePayload::new_send(Message::custom(b"hello")?, &sub, node2.did(), node2.did())?;
+    callback
+        .on_payload(&node1.did().to_string(), payload)
+        .await
+        .unwrap();
+    let payload = node2.listen_once().await.unwrap();
+    assert_eq!(payload.transaction.signer(), node1.did());
+    assert!(matches!(
+        payload.transaction.data()?,
+        Message::CustomMessage(_)
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_swap_callback_of_established_connection() -> Result<()> {
+    use crate::tests::default::NodeCallback;
+
+    let keys = gen_ordered_keys(2);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let node2 = prepare_node_with_builder(keys[1], loopback).await;
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+
+    node1
+        .swarm
+        .send_message(Message::custom(b"before")?, node2.did())
+        .await?;
+    let payload = node2.listen_once().await.unwrap();
+    let Message::CustomMessage(msg) = payload.transaction.data()? else {
+        panic!("unexpected message");
+    };
+    assert_eq!(msg.0, b"before".to_vec());
+
+    // Swap the callback while the connection is kept.
+    let (message_tx, mut message_rx) = tokio::sync::mpsc::unbounded_channel();
+    node2
+        .swarm
+        .set_callback(Arc::new(NodeCallback { message_tx }))?;
+
+    node1
+        .swarm
+        .send_message(Message::custom(b"after")?, node2.did())
+        .await?;
+    let payload = tokio::time::timeout(Duration::from_secs(3), message_rx.recv())
+        .await
+        .expect("message is not handled by the new callback")
+        .unwrap();
+    let Message::CustomMessage(msg) = payload.transaction.data()? else {
+        panic!("unexpected message");
+    };
+    assert_eq!(msg.0, b"after".to_vec());
+    assert_no_more_msg([&node2]).await;
+    Ok(())
+}
@@ -1,18 +1,32 @@
 use std::sync::Arc;
 use std::time::Duration;
 
+use async_trait::async_trait;
 use tokio::time::sleep;
 
 use crate::dht::successor::SuccessorReader;
 use crate::dht::Chord;
+use crate::dht::Did;
+use crate::ecc::tests::gen_ordered_keys;
 use crate::ecc::SecretKey;
 use crate::error::Error;
 use crate::error::Result;
 use crate::inspect::DHTInspect;
 use crate::inspect::SwarmInspect;
+use crate::message::Message;
+use crate::swarm::callback::DisconnectReason;
+use crate::swarm::callback::SwarmCallback;
+use crate::swarm::callback::SwarmEvent;
+use crate::swarm::SwarmBuilder;
+use crate::swarm::TransportKind;
+use crate::tests::default::assert_no_more_msg;
 use crate::tests::default::gen_pure_dht;
 use crate::tests::default::prepare_node;
+use crate::tests::default::prepare_node_with_builder;
+use crate::tests::default::wait_for_msgs;
 use crate::tests::manually_establish_connection;
+use crate::utils::get_epoch_ms;
+use crate::utils::MockClock;
 
 #[tokio::test]
 async fn test_stabilization_once() -> Result<()> {
@@ -155,3 +169,224 @@ async fn test_stabilization_final_dht() -> Result<()> {
 
     Ok(())
 }
+
+#[derive(Default)]
+struct DisconnectRecorder {
+    events: std::sync::Mutex<Vec<(Did, DisconnectReason)>>,
+}
+
+#[async_trait]
+impl SwarmCallback for DisconnectRecorder {
+    async fn on_event(
+        &self,
+        event: &SwarmEvent,
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        if let SwarmEvent::Disconnected { peer, reason } = event {
+            self.events.lock().unwrap().push((*peer, *reason));
+        }
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_disconnect_idle_connections() -> Result<()> {
+    let keys = gen_ordered_keys(3);
+    let clock = Arc::new(MockClock::new(get_epoch_ms()));
+    let node1 = prepare_node_with_builder(keys[0], |b: SwarmBuilder| {
+        b.transport_kind(TransportKind::Loopback)
+            .dht_succ_max(1)
+            .idle_timeout(Duration::from_secs(1))
+            .clock(clock.clone())
+    })
+    .await;
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node2 = prepare_node_with_builder(keys[1], loopback).await;
+    let node3 = prepare_node_with_builder(keys[2], loopback).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    manually_establish_connection(&node1.swarm, &node3.swarm).await;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+    assert_eq!(node1.dht().successors().list()?, vec![node2.did()]);
+
+    // The stabilizer reports to the callback set after it's created.
+    let stabilizer = node1.swarm.stabilizer();
+    let recorder = Arc::new(DisconnectRecorder::default());
+    node1.swarm.set_callback(recorder.clone())?;
+
+    // Sending a message keeps the connection active.
+    node1
+        .swarm
+        .send_message(Message::custom(b"ping")?, node3.did())
+        .await?;
+    assert!(stabilizer.disconnect_idle_connections().await?.is_empty());
+
+    // The successor is kept even if it's idle.
+    clock.advance(Duration::from_millis(1500));
+    assert_eq!(stabilizer.disconnect_idle_connections().await?, vec![
+        node3.did()
+    ]);
+    node1.assert_transports(vec![node2.did()]);
+    assert_eq!(*recorder.events.lock().unwrap(), vec![(
+        node3.did(),
+        DisconnectReason::Idle
+    )]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_pinned_connection_is_kept_and_reconnected() -> Result<()> {
+    let keys = gen_ordered_keys(3);
+    let clock = Arc::new(MockClock::new(get_epoch_ms()));
+    let node1 = prepare_node_with_builder(keys[0], |b: SwarmBuilder| {
+        b.transport_kind(TransportKind::Loopback)
+            .dht_succ_max(1)
+            .idle_timeout(Duration::from_secs(1))
+            .clock(clock.clone())
+    })
+    .await;
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node2 = prepare_node_with_builder(keys[1], loopback).await;
+    let node3 = prepare_node_with_builder(keys[2], loopback).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    manually_establish_connection(&node1.swarm, &node3.swarm).await;
+    manually_establish_connection(&node2.swarm, &node3.swarm).await;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+    assert_eq!(node1.dht().successors().list()?, vec![node2.did()]);
+
+    assert!(matches!(
+        node1.swarm.pin(node1.did()),
+        Err(Error::ShouldNotConnectSelf)
+    ));
+    node1.swarm.pin(node3.did())?;
+    assert_eq!(node1.swarm.pinned_peers(), vec![node3.did()]);
+    let stabilizer = node1.swarm.stabilizer();
+
+    // The pinned peer is kept even if it's idle.
+    clock.advance(Duration::from_millis(1500));
+    assert!(stabilizer.disconnect_idle_connections().await?.is_empty());
+    node1.assert_transports(vec![node2.did(), node3.did()]);
+
+    // Nothing to reconnect while it's connected.
+    assert!(stabilizer.reconnect_pinned_peers().await?.is_empty());
+
+    // The pinned peer is reconnected after it's closed.
+    node1.swarm.disconnect(node3.did()).await?;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+    assert_eq!(
+        stabilizer.reconnect_pinned_peers().await?,
+        vec![node3.did()]
+    );
+    wait_for_msgs([&node1, &node2, &node3]).await;
+    node1.assert_transports(vec![node2.did(), node3.did()]);
+
+    // The unpinned peer is closed for idleness again.
+    assert!(node1.swarm.unpin(node3.did()));
+    assert!(!node1.swarm.unpin(node3.did()));
+    clock.advance(Duration::from_millis(1500));
+    assert_eq!(stabilizer.disconnect_idle_connections().await?, vec![
+        node3.did()
+    ]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reconnect_closed_successor() -> Result<()> {
+    use crate::swarm::ReconnectPolicy;
+
+    let keys = gen_ordered_keys(3);
+    let node1 = prepare_node_with_builder(keys[0], |b: SwarmBuilder| {
+        b.transport_kind(TransportKind::Loopback)
+            .dht_succ_max(1)
+            .reconnect_policy(ReconnectPolicy::default())
+    })
+    .await;
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node2 = prepare_node_with_builder(keys[1], loopback).await;
+    let node3 = prepare_node_with_builder(keys[2], loopback).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    manually_establish_connection(&node1.swarm, &node3.swarm).await;
+    manually_establish_connection(&node2.swarm, &node3.swarm).await;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+    assert_eq!(node1.dht().successors().list()?, vec![node2.did()]);
+    let stabilizer = node1.swarm.stabilizer();
+
+    // Nothing to reconnect while it's connected.
+    assert!(node1.swarm.reconnecting_peers().is_empty());
+    assert!(stabilizer.reconnect_closed_peers().await?.is_empty());
+
+    // The successor closing the connection is reconnected.
+    node2.swarm.disconnect(node1.did()).await?;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+    assert_eq!(node1.swarm.reconnecting_peers(), vec![node2.did()]);
+    assert_eq!(
+        stabilizer.reconnect_closed_peers().await?,
+        vec![node2.did()]
+    );
+    wait_for_msgs([&node1, &node2, &node3]).await;
+    node1.assert_transports(vec![node2.did(), node3.did()]);
+    assert!(node1.swarm.reconnecting_peers().is_empty());
+
+    // The peer closed by swarm itself is not reconnected.
+    node1.swarm.disconnect(node3.did()).await?;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+    assert!(node1.swarm.reconnecting_peers().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_pause_stabilization() -> Result<()> {
+    let keys = gen_ordered_keys(2);
+    let loopback = |b: SwarmBuilder| b.transport_kind(TransportKind::Loopback);
+    let node1 = prepare_node_with_builder(keys[0], loopback).await;
+    let node2 = prepare_node_with_builder(keys[1], loopback).await;
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+    assert_no_more_msg([&node1, &node2]).await;
+
+    let stabilizer = node1.swarm.stabilizer();
+    node1.dht().pause_stabilization();
+    assert!(node1.dht().is_stabilization_paused());
+    stabilizer.stabilize().await?;
+    assert_no_more_msg([&node1, &node2]).await;
+    assert_eq!(node1.dht().stabilization_stats(), Default::default());
+
+    node1.dht().resume_stabilization();
+    stabilizer.stabilize().await?;
+    let payload = node2.listen_once().await.unwrap();
+    assert!(matches!(
+        payload.transaction.data::<Message>()?,
+        Message::NotifyPredecessorSend(_)
+    ));
+    let stats = node1.dht().stabilization_stats();
+    assert_eq!(stats.rounds, 1);
+    assert!(stats.successor_checks >= 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_set_stabilize_interval() -> Result<()> {
+    let node = prepare_node(SecretKey::random()).await;
+    assert_eq!(node.dht().stabilize_interval(), None);
+    node.dht()
+        .set_stabilize_interval(Duration::from_millis(100));
+    assert_eq!(
+        node.dht().stabilize_interval(),
+        Some(Duration::from_millis(100))
+    );
+
+    // The interval set overrides the one the stabilizer is started with.
+    let stabilizer = Arc::new(node.swarm.stabilizer());
+    let handle = tokio::spawn(stabilizer.wait(Duration::from_secs(3600)));
+    sleep(Duration::from_millis(500)).await;
+    assert!(node.dht().stabilization_stats().rounds >= 2);
+
+    // No round is counted while paused.
+    node.dht().pause_stabilization();
+    let rounds = node.dht().stabilization_stats().rounds;
+    sleep(Duration::from_millis(500)).await;
+    assert_eq!(node.dht().stabilization_stats().rounds, rounds);
+    handle.abort();
+    Ok(())
+}
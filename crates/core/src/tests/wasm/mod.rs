@@ -30,7 +30,11 @@ pub async fn prepare_node(key: SecretKey) -> Arc<Swarm> {
             .unwrap(),
     );
 
-    let swarm = Arc::new(SwarmBuilder::new(0, stun, storage, session_sk).build());
+    let swarm = Arc::new(
+        SwarmBuilder::new(0, stun, storage, session_sk)
+            .build()
+            .unwrap(),
+    );
 
     println!("key: {:?}", key.to_string());
     println!("did: {:?}", swarm.did());
@@ -1,8 +1,97 @@
 //! Utils for ring-core
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
 use chrono::Utc;
+
 /// Get local utc timestamp (millisecond)
 pub fn get_epoch_ms() -> u128 {
-    Utc::now().timestamp_millis() as u128
+    SystemClock.now_ms()
+}
+
+/// Source of current time for expiry checks and timeouts, so that they can be tested
+/// without waiting for real time to pass.
+pub trait Clock: Send + Sync {
+    /// Current utc timestamp in milliseconds.
+    fn now_ms(&self) -> u128;
+}
+
+/// Shared [Clock] used by swarm.
+pub type SharedClock = Arc<dyn Clock>;
+
+/// [Clock] reading the system time. It's the default clock.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u128 {
+        Utc::now().timestamp_millis() as u128
+    }
+}
+
+/// [Clock] that only moves when it's told to, for tests.
+#[derive(Debug, Default)]
+pub struct MockClock {
+    now_ms: AtomicU64,
+}
+
+impl MockClock {
+    /// Create a clock stopped at `now_ms`.
+    pub fn new(now_ms: u128) -> Self {
+        Self {
+            now_ms: AtomicU64::new(now_ms as u64),
+        }
+    }
+
+    /// Set current time to `now_ms`.
+    pub fn set(&self, now_ms: u128) {
+        self.now_ms.store(now_ms as u64, Ordering::SeqCst);
+    }
+
+    /// Move current time forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        self.now_ms
+            .fetch_add(duration.as_millis() as u64, Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now_ms(&self) -> u128 {
+        self.now_ms.load(Ordering::SeqCst) as u128
+    }
+}
+
+/// Sleep for a while, works on both native and browser environment.
+pub async fn sleep(duration: std::time::Duration) {
+    #[cfg(not(feature = "wasm"))]
+    futures_timer::Delay::new(duration).await;
+    #[cfg(feature = "wasm")]
+    let _ = js_utils::window_sleep(duration.as_millis() as i32).await;
+}
+
+/// Run a future in background, works on both native and browser environment.
+/// On native, the future is polled once in place if there is no tokio runtime.
+#[cfg(not(feature = "wasm"))]
+pub fn spawn<F>(fut: F)
+where F: std::future::Future<Output = ()> + Send + 'static {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => {
+            handle.spawn(fut);
+        }
+        Err(_) => {
+            tracing::warn!("No runtime to spawn future, it's polled only once");
+            let _ = futures::FutureExt::now_or_never(fut);
+        }
+    }
+}
+
+/// Run a future in background, works on both native and browser environment.
+#[cfg(feature = "wasm")]
+pub fn spawn<F>(fut: F)
+where F: std::future::Future<Output = ()> + 'static {
+    wasm_bindgen_futures::spawn_local(fut)
 }
 
 #[cfg(feature = "wasm")]
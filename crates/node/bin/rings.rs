@@ -105,10 +105,11 @@ struct NewSessionCommand {
 struct RunCommand {
     #[arg(
         long,
-        help = "Rings node external api listen address. If not provided, use external_api_addr in config file or 127.0.0.1:50001",
+        help = "Rings node external api listen address, which can be repeated to listen on multiple addresses. If not provided, use external_api_addr in config file or 127.0.0.1:50001",
+        value_delimiter = ',',
         env
     )]
-    pub external_api_addr: Option<String>,
+    pub external_api_addr: Vec<String>,
 
     #[arg(
         long,
@@ -141,6 +142,27 @@ struct RunCommand {
     #[arg(long, help = "external ip address", env)]
     pub external_ip: Option<String>,
 
+    #[arg(
+        long,
+        help = "Maximum length of successors in DHT. If not provided, use swarm.dht_succ_max in config file or 3",
+        env
+    )]
+    pub dht_succ_max: Option<u8>,
+
+    #[arg(
+        long,
+        help = "Close connections idle for this many seconds. If not provided, use swarm.idle_timeout_secs in config file or never",
+        env
+    )]
+    pub idle_timeout_secs: Option<u64>,
+
+    #[arg(
+        long,
+        help = "Maximum number of concurrent connection handshakes. If not provided, use swarm.max_concurrent_connects in config file or unlimited",
+        env
+    )]
+    pub max_concurrent_connects: Option<usize>,
+
     #[arg(
         long,
         help = "Storage files location. If not provided, use storage.path in config file or ~/.local/share/rings",
@@ -186,7 +208,10 @@ impl ClientArgs {
     async fn new_client(&self) -> anyhow::Result<Client> {
         let c = config::Config::read_fs(&self.config_args.config)?;
         let endpoint_url = self.endpoint_url.as_ref().unwrap_or(&c.endpoint_url);
-        Client::new(endpoint_url)
+        match self.ecdsa_key.or(c.ecdsa_key) {
+            Some(key) => Client::new_with_signer(endpoint_url, key),
+            None => Client::new(endpoint_url),
+        }
     }
 }
 
@@ -406,12 +431,21 @@ async fn daemon_run(args: RunCommand) -> anyhow::Result<()> {
     if let Some(stabilize_interval) = args.stabilize_interval {
         c.stabilize_interval = stabilize_interval;
     }
-    if let Some(external_api_addr) = args.external_api_addr {
-        c.external_api_addr = external_api_addr;
+    if !args.external_api_addr.is_empty() {
+        c.external_api_addr = args.external_api_addr;
     }
     if let Some(internal_api_port) = args.internal_api_port {
         c.internal_api_port = internal_api_port;
     }
+    if let Some(dht_succ_max) = args.dht_succ_max {
+        c.swarm.dht_succ_max = Some(dht_succ_max);
+    }
+    if let Some(idle_timeout_secs) = args.idle_timeout_secs {
+        c.swarm.idle_timeout_secs = Some(idle_timeout_secs);
+    }
+    if let Some(max_concurrent_connects) = args.max_concurrent_connects {
+        c.swarm.max_concurrent_connects = Some(max_concurrent_connects);
+    }
 
     let pc = ProcessorConfig::try_from(c.clone())?;
     let bc = BackendConfig::from(c.clone());
@@ -458,8 +492,13 @@ async fn daemon_run(args: RunCommand) -> anyhow::Result<()> {
     let _ = futures::join!(
         processor.listen(),
         service_loop_register(&processor, backend_service_names),
-        run_internal_api(c.internal_api_port, processor_clone2),
-        run_external_api(c.external_api_addr, processor_clone1),
+        run_internal_api(
+            c.internal_api_port,
+            processor_clone2,
+            c.bootstrap_seed,
+            c.rpc_authorized_dids,
+        ),
+        run_external_api(c.external_api_addr, processor_clone1, c.bootstrap_seed),
     );
 
     Ok(())
@@ -97,6 +97,15 @@ impl BackendBehaviour {
                     cb(self.clone(), provider.clone(), ctx, m).await?;
                 }
             }
+            BackendMessage::Request { .. } => {
+                if let Some(func) = &self.get_handler("Request") {
+                    let m = js_value::serialize(msg)?;
+                    let cb = js_func::of4::<BackendBehaviour, Provider, JsValue, JsValue>(func);
+                    cb(self.clone(), provider.clone(), ctx, m).await?;
+                }
+            }
+            // Chunks are reassembled, and responses are resolved by Backend before handling.
+            BackendMessage::Chunk(_) | BackendMessage::Response { .. } => {}
         }
         if let Some(ext) = &self.extend_handler.clone().into_inner() {
             ext.handle_message(provider.into(), payload, msg)
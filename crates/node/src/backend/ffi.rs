@@ -53,6 +53,16 @@ pub struct FFIBackendBehaviour {
             ) -> (),
         >,
     >,
+    unhandled_message_handler: Option<
+        Box<
+            extern "C" fn(
+                *const FFIBackendBehaviourWithRuntime,
+                *const ProviderPtr,
+                *const c_char,
+                *const c_char,
+            ) -> (),
+        >,
+    >,
 }
 
 /// A wrapper for FFIbackendbehaviour, we needs runtime to make async request work
@@ -63,8 +73,10 @@ pub struct FFIBackendBehaviourWithRuntime {
     runtime: Arc<Runtime>,
 }
 
+/// Call the handler if it's registered, with message serialized to string.
+/// Evaluates to whether the handler is called.
 macro_rules! handle_backend_message {
-    ($self:ident, $provider:ident, $handler:ident, $payload: ident, $message:ident) => {
+    ($self:ident, $provider:ident, $handler:ident, $payload: ident, $message:expr) => {
         if let Some(handler) = &$self.behaviour.$handler {
             let rt = $self.runtime.clone();
 
@@ -72,15 +84,17 @@ macro_rules! handle_backend_message {
             provider_with_runtime.check_arc();
             let provider_ptr: ProviderPtr = (&provider_with_runtime).into();
             let payload = serde_json::to_string(&$payload)?;
-            let message = serde_json::to_string(&$message)?;
             let payload = CString::new(payload)?;
-            let message = CString::new(message)?;
+            let message = CString::new($message)?;
             handler(
                 $self as *const FFIBackendBehaviourWithRuntime,
                 &provider_ptr as *const ProviderPtr,
                 payload.as_ptr(),
                 message.as_ptr(),
             );
+            true
+        } else {
+            false
         }
     };
 }
@@ -100,17 +114,45 @@ impl FFIBackendBehaviourWithRuntime {
         payload: &MessagePayload,
         msg: &BackendMessage,
     ) -> Result<(), Error> {
-        match msg {
-            BackendMessage::PlainText(m) => {
-                handle_backend_message!(self, provider, paintext_message_handler, payload, m)
-            }
-            BackendMessage::Extension(m) => {
-                handle_backend_message!(self, provider, extension_message_handler, payload, m)
-            }
-            BackendMessage::ServiceMessage(m) => {
-                handle_backend_message!(self, provider, service_message_handler, payload, m)
-            }
-            _ => (),
+        let handled = match msg {
+            BackendMessage::PlainText(m) => handle_backend_message!(
+                self,
+                provider,
+                paintext_message_handler,
+                payload,
+                serde_json::to_string(m)?
+            ),
+            BackendMessage::Extension(m) => handle_backend_message!(
+                self,
+                provider,
+                extension_message_handler,
+                payload,
+                serde_json::to_string(m)?
+            ),
+            BackendMessage::ServiceMessage(m) => handle_backend_message!(
+                self,
+                provider,
+                service_message_handler,
+                payload,
+                serde_json::to_string(m)?
+            ),
+            _ => false,
+        };
+        if !handled {
+            self.do_handle_unhandled(provider, payload, msg.variant_name())?;
+        }
+        Ok(())
+    }
+
+    /// Call the unhandled message handler with the variant name as message.
+    fn do_handle_unhandled(
+        &self,
+        provider: Arc<Provider>,
+        payload: &MessagePayload,
+        variant: &str,
+    ) -> Result<(), Error> {
+        if !handle_backend_message!(self, provider, unhandled_message_handler, payload, variant) {
+            tracing::debug!("BackendMessage {variant} is not handled");
         }
         Ok(())
     }
@@ -148,6 +190,29 @@ pub extern "C" fn new_ffi_backend_behaviour(
         paintext_message_handler: paintext_message_handler.map(Box::new),
         service_message_handler: service_message_handler.map(Box::new),
         extension_message_handler: extension_message_handler.map(Box::new),
+        unhandled_message_handler: None,
+    }
+}
+
+/// Set the handler of backend messages which are not handled, called with the variant name as
+/// message.
+/// # Safety
+///
+/// * This function dereferences the raw pointer of behaviour
+#[no_mangle]
+pub unsafe extern "C" fn set_ffi_backend_unhandled_message_handler(
+    behaviour: *mut FFIBackendBehaviour,
+    unhandled_message_handler: Option<
+        extern "C" fn(
+            *const FFIBackendBehaviourWithRuntime,
+            *const ProviderPtr,
+            *const c_char,
+            *const c_char,
+        ) -> (),
+    >,
+) {
+    if let Some(behaviour) = behaviour.as_mut() {
+        behaviour.unhandled_message_handler = unhandled_message_handler.map(Box::new);
     }
 }
 
@@ -163,4 +228,138 @@ impl MessageHandler<BackendMessage> for FFIBackendBehaviourWithRuntime {
             .await
             .map_err(|e| e.into())
     }
+
+    fn handles(&self, msg: &BackendMessage) -> bool {
+        match msg {
+            BackendMessage::PlainText(_) => self.behaviour.paintext_message_handler.is_some(),
+            BackendMessage::Extension(_) => self.behaviour.extension_message_handler.is_some(),
+            BackendMessage::ServiceMessage(_) => self.behaviour.service_message_handler.is_some(),
+            _ => false,
+        }
+    }
+
+    async fn on_unhandled(
+        &self,
+        provider: Arc<Provider>,
+        payload: &MessagePayload,
+        variant: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.do_handle_unhandled(provider, payload, variant)
+            .map_err(|e| e.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+    use std::sync::Mutex;
+
+    use rings_core::ecc::SecretKey;
+    use rings_core::message::Message;
+
+    use super::*;
+    use crate::prelude::SessionSk;
+    use crate::tests::native::prepare_processor;
+
+    static UNHANDLED: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+    extern "C" fn on_unhandled(
+        _behaviour: *const FFIBackendBehaviourWithRuntime,
+        _provider: *const ProviderPtr,
+        _payload: *const c_char,
+        variant: *const c_char,
+    ) {
+        let variant = unsafe { CStr::from_ptr(variant) }.to_str().unwrap();
+        UNHANDLED.lock().unwrap().push(variant.to_string());
+    }
+
+    #[test]
+    fn test_unhandled_message_calls_fallback() {
+        let runtime = Arc::new(Runtime::new().unwrap());
+        let provider = Arc::new(Provider::from_processor(Arc::new(
+            runtime.block_on(prepare_processor()),
+        )));
+        // Only the fallback is registered, so that no variant has a handler.
+        let mut behaviour = new_ffi_backend_behaviour(None, None, None);
+        unsafe { set_ffi_backend_unhandled_message_handler(&mut behaviour, Some(on_unhandled)) };
+        let behaviour = FFIBackendBehaviourWithRuntime::new(behaviour, runtime.clone());
+
+        let key = SecretKey::random();
+        let did = key.address().into();
+        let session_sk = SessionSk::new_with_seckey(&key).unwrap();
+        let msg = BackendMessage::PlainText("hello".to_string());
+        let payload = MessagePayload::new_send(
+            Message::custom(&bincode::serialize(&msg).unwrap()).unwrap(),
+            &session_sk,
+            did,
+            did,
+        )
+        .unwrap();
+
+        runtime
+            .block_on(behaviour.handle_message(provider, &payload, &msg))
+            .unwrap();
+        assert_eq!(*UNHANDLED.lock().unwrap(), vec!["PlainText".to_string()]);
+    }
+
+    static TUPLE_UNHANDLED: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+    extern "C" fn on_tuple_unhandled(
+        _behaviour: *const FFIBackendBehaviourWithRuntime,
+        _provider: *const ProviderPtr,
+        _payload: *const c_char,
+        variant: *const c_char,
+    ) {
+        let variant = unsafe { CStr::from_ptr(variant) }.to_str().unwrap();
+        TUPLE_UNHANDLED.lock().unwrap().push(variant.to_string());
+    }
+
+    extern "C" fn on_plaintext(
+        _behaviour: *const FFIBackendBehaviourWithRuntime,
+        _provider: *const ProviderPtr,
+        _payload: *const c_char,
+        _message: *const c_char,
+    ) {
+    }
+
+    #[test]
+    fn test_unhandled_message_of_tuple_calls_fallback_once() {
+        let runtime = Arc::new(Runtime::new().unwrap());
+        let provider = Arc::new(Provider::from_processor(Arc::new(
+            runtime.block_on(prepare_processor()),
+        )));
+        // The fallback is registered to the member which doesn't handle PlainText.
+        let plaintext = FFIBackendBehaviourWithRuntime::new(
+            new_ffi_backend_behaviour(Some(on_plaintext), None, None),
+            runtime.clone(),
+        );
+        let mut fallback = new_ffi_backend_behaviour(None, None, None);
+        unsafe {
+            set_ffi_backend_unhandled_message_handler(&mut fallback, Some(on_tuple_unhandled))
+        };
+        let fallback = FFIBackendBehaviourWithRuntime::new(fallback, runtime.clone());
+        let behaviour = (plaintext, fallback);
+
+        let key = SecretKey::random();
+        let did = key.address().into();
+        let session_sk = SessionSk::new_with_seckey(&key).unwrap();
+        for msg in [
+            BackendMessage::PlainText("hello".to_string()),
+            BackendMessage::Extension(vec![1, 2, 3].into()),
+        ] {
+            let payload = MessagePayload::new_send(
+                Message::custom(&bincode::serialize(&msg).unwrap()).unwrap(),
+                &session_sk,
+                did,
+                did,
+            )
+            .unwrap();
+            runtime
+                .block_on(behaviour.handle_message(provider.clone(), &payload, &msg))
+                .unwrap();
+        }
+        assert_eq!(*TUPLE_UNHANDLED.lock().unwrap(), vec![
+            "Extension".to_string()
+        ]);
+    }
 }
@@ -11,10 +11,12 @@ use async_trait::async_trait;
 use rings_core::message::CustomMessage;
 use rings_core::message::Message;
 use rings_core::message::MessagePayload;
+use rings_core::message::PublishTopic;
 use rings_core::swarm::callback::SwarmCallback;
 use rings_derive::wasm_export;
 
 use crate::backend::types::BackendMessage;
+use crate::backend::types::BackendMessageAssembler;
 use crate::backend::types::MessageHandler;
 use crate::provider::Provider;
 
@@ -36,12 +38,17 @@ type HandlerTrait = dyn MessageHandler<BackendMessage>;
 pub struct Backend {
     provider: Arc<Provider>,
     handler: Box<HandlerTrait>,
+    assembler: BackendMessageAssembler,
 }
 
 impl Backend {
     /// Create a new backend instance with Provider and Handler functions
     pub fn new(provider: Arc<Provider>, handler: Box<HandlerTrait>) -> Self {
-        Self { provider, handler }
+        Self {
+            provider,
+            handler,
+            assembler: BackendMessageAssembler::default(),
+        }
     }
 
     async fn on_backend_message(
@@ -104,11 +111,34 @@ impl SwarmCallback for Backend {
     async fn on_inbound(&self, payload: &MessagePayload) -> Result<(), Box<dyn std::error::Error>> {
         let data: Message = payload.transaction.data()?;
 
-        let Message::CustomMessage(CustomMessage(msg)) = data else {
-            return Ok(());
+        let msg = match data {
+            Message::CustomMessage(CustomMessage(msg)) => msg,
+            Message::PublishTopic(PublishTopic {
+                data,
+                delivered: true,
+                ..
+            }) => data,
+            _ => return Ok(()),
         };
 
-        let backend_msg = bincode::deserialize(&msg)?;
+        let backend_msg = match bincode::deserialize(&msg)? {
+            BackendMessage::Chunk(chunk) => {
+                let sender = payload.transaction.signer();
+                let Some(backend_msg) = self.assembler.handle(sender, chunk)? else {
+                    return Ok(());
+                };
+                backend_msg
+            }
+            backend_msg => backend_msg,
+        };
+        if let BackendMessage::Response { id, msg } = backend_msg {
+            let sender = payload.transaction.signer();
+            let msg = BackendMessage::decode_nested(&msg)?;
+            if !self.provider.resolve_call(sender, id, msg) {
+                tracing::warn!("Drop response {id} from {sender:?}, no call is waiting for it");
+            }
+            return Ok(());
+        }
         tracing::debug!("backend_message received: {backend_msg:?}");
 
         self.on_backend_message(payload, &backend_msg).await?;
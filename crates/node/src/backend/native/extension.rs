@@ -133,7 +133,7 @@ impl Extension {
             if let Ok(h) = Self::load(p).await {
                 handlers.push(h)
             } else {
-                log::error!("Failed on loading extension {:?}", p)
+                tracing::error!("Failed on loading extension {:?}", p)
             }
         }
         Ok(Self { handlers })
@@ -283,7 +283,7 @@ pub mod loader {
                     }
                 }
                 Err(e) => {
-                    log::error!("{:?}", e);
+                    tracing::error!("{:?}", e);
                     Self::default()
                 }
             }
@@ -305,7 +305,7 @@ pub mod loader {
                     }
                 }
                 Err(e) => {
-                    log::error!("{:?}", e);
+                    tracing::error!("{:?}", e);
                     None
                 }
             }
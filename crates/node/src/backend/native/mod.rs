@@ -55,6 +55,15 @@ impl MessageHandler<BackendMessage> for BackendBehaviour {
     ) -> Result<(), Box<dyn std::error::Error>> {
         self.handle_backend_message(provider, payload, msg).await
     }
+
+    fn handles(&self, msg: &BackendMessage) -> bool {
+        matches!(
+            msg,
+            BackendMessage::Extension(_)
+                | BackendMessage::ServiceMessage(_)
+                | BackendMessage::PlainText(_)
+        )
+    }
 }
 
 impl BackendBehaviour {
@@ -93,7 +102,10 @@ impl BackendBehaviour {
                 tracing::info!("BackendMessage from {peer_did:?} PlainText: {text:?}");
                 Ok(())
             }
-            _ => Ok(()),
+            _ => {
+                self.on_unhandled(provider, payload, msg.variant_name())
+                    .await
+            }
         }
     }
 }
@@ -204,6 +204,16 @@ impl SNARKBehaviour {
         })
     }
 
+    /// Get ids of tasks sent but not verified yet, as an array of strings
+    pub fn pending_task_ids(&self) -> Result<JsValue> {
+        Ok(js_value::serialize(&self.pending_tasks())?)
+    }
+
+    /// Get verified results of tasks, as a map from task id to whether the proof is valid
+    pub fn verified_result_map(&self) -> Result<JsValue> {
+        Ok(js_value::serialize(&self.verified_results())?)
+    }
+
     /// create new instance for browser
     /// which support syntax `new SNARKBehaviour` in browser env
     #[wasm_bindgen(constructor)]
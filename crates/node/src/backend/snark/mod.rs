@@ -1,7 +1,13 @@
 //! SNARK Backend
 //! ================
 
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::sync::Mutex;
 
 use dashmap::DashMap;
 use rings_core::dht::Did;
@@ -9,6 +15,7 @@ use rings_core::message::MessagePayload;
 use rings_derive::wasm_export;
 use rings_rpc::method::Method;
 use rings_snark::circuit;
+use rings_snark::prelude::ff;
 use rings_snark::prelude::nova::provider;
 use rings_snark::prelude::nova::provider::hyperkzg;
 use rings_snark::prelude::nova::provider::ipa_pc;
@@ -30,11 +37,17 @@ use super::types::snark::SNARKTaskMessage;
 use super::types::snark::SNARKVerifyTask;
 use crate::backend::types::BackendMessage;
 use crate::backend::types::MessageHandler;
+use crate::consts::CAPABILITY_SNARK;
+use crate::consts::SNARK_MAX_KEPT_PROOFS;
+#[cfg(feature = "node")]
+use crate::consts::SNARK_MAX_QUEUED_PROOF_TASKS;
+use crate::consts::SNARK_REQUEST_TIMEOUT;
 use crate::error::Error;
 use crate::error::Result;
 use crate::provider::Provider;
 
-type TaskId = uuid::Uuid;
+/// Id of a proof task sent to provers.
+pub type TaskId = uuid::Uuid;
 
 #[cfg(feature = "browser")]
 pub mod browser;
@@ -46,6 +59,126 @@ pub struct SNARKTaskManager {
     task: DashMap<TaskId, SNARKProofTask>,
     /// map of task_id and result
     verified: DashMap<TaskId, bool>,
+    /// map of task_id and received proof, kept to verify again by
+    /// [SNARKBehaviour::revalidate_all]. See [SNARKBehaviour::keep_proof].
+    proofs: DashMap<TaskId, SNARKVerifyTask>,
+    /// task_id of kept proofs in the order they are received
+    proof_order: Arc<Mutex<VecDeque<TaskId>>>,
+    /// verifier keys derived by [SNARKBehaviour::verify_proof], by digest of r1cs of circuit
+    verifier_keys: DashMap<[u8; 32], CachedVerifierKey>,
+    /// workers proving received tasks, tasks are proved in place if not set
+    #[cfg(feature = "node")]
+    workers: Option<SNARKWorkerPool>,
+}
+
+/// Verifier key derived from a circuit, one for each curve of [CircuitEnum].
+#[derive(Clone)]
+enum CachedVerifierKey {
+    Pallas(
+        Arc<
+            VerifierKey<
+                provider::PallasEngine,
+                provider::VestaEngine,
+                spartan::snark::RelaxedR1CSSNARK<
+                    provider::PallasEngine,
+                    ipa_pc::EvaluationEngine<provider::PallasEngine>,
+                >,
+                spartan::snark::RelaxedR1CSSNARK<
+                    provider::VestaEngine,
+                    ipa_pc::EvaluationEngine<provider::VestaEngine>,
+                >,
+            >,
+        >,
+    ),
+    Vesta(
+        Arc<
+            VerifierKey<
+                provider::VestaEngine,
+                provider::PallasEngine,
+                spartan::snark::RelaxedR1CSSNARK<
+                    provider::VestaEngine,
+                    ipa_pc::EvaluationEngine<provider::VestaEngine>,
+                >,
+                spartan::snark::RelaxedR1CSSNARK<
+                    provider::PallasEngine,
+                    ipa_pc::EvaluationEngine<provider::PallasEngine>,
+                >,
+            >,
+        >,
+    ),
+    Bn256KZG(
+        Arc<
+            VerifierKey<
+                provider::Bn256EngineKZG,
+                provider::GrumpkinEngine,
+                spartan::snark::RelaxedR1CSSNARK<
+                    provider::Bn256EngineKZG,
+                    hyperkzg::EvaluationEngine<provider::Bn256EngineKZG>,
+                >,
+                spartan::snark::RelaxedR1CSSNARK<
+                    provider::GrumpkinEngine,
+                    ipa_pc::EvaluationEngine<provider::GrumpkinEngine>,
+                >,
+            >,
+        >,
+    ),
+}
+
+/// Bounded pool of workers proving [SNARKProofTask] on blocking threads.
+/// At most `size` tasks are proved concurrently, and at most `queued` others wait in queue.
+/// Tasks beyond that are rejected.
+#[cfg(feature = "node")]
+#[derive(Clone)]
+pub struct SNARKWorkerPool {
+    permits: Arc<tokio::sync::Semaphore>,
+    slots: Arc<tokio::sync::Semaphore>,
+}
+
+#[cfg(feature = "node")]
+impl SNARKWorkerPool {
+    /// Create a pool with `size` workers, at least one, and a queue of
+    /// [SNARK_MAX_QUEUED_PROOF_TASKS] tasks.
+    pub fn new(size: usize) -> Self {
+        Self::with_queue(size, SNARK_MAX_QUEUED_PROOF_TASKS)
+    }
+
+    /// Create a pool with `size` workers, at least one, and a queue of `queued` tasks.
+    pub fn with_queue(size: usize, queued: usize) -> Self {
+        let size = size.max(1);
+        Self {
+            permits: Arc::new(tokio::sync::Semaphore::new(size)),
+            slots: Arc::new(tokio::sync::Semaphore::new(size + queued)),
+        }
+    }
+
+    /// Take a place in the pool for a task, and return the future proving it with
+    /// [SNARKBehaviour::handle_snark_proof_task] once a worker is free. The place is kept
+    /// until the future completes or is dropped. Fail if the queue is full.
+    pub fn try_prove(
+        &self,
+        task: SNARKProofTask,
+    ) -> Result<impl std::future::Future<Output = Result<SNARKVerifyTask>> + Send + 'static> {
+        let slot = self.slots.clone().try_acquire_owned().map_err(|_| {
+            Error::SNARKHandleMessage("Too many SNARK proof tasks in queue".to_string())
+        })?;
+        let permits = self.permits.clone();
+        Ok(async move {
+            let _slot = slot;
+            let _permit = permits
+                .acquire()
+                .await
+                .map_err(|e| Error::SNARKHandleMessage(e.to_string()))?;
+            tokio::task::spawn_blocking(move || SNARKBehaviour::handle_snark_proof_task(&task))
+                .await
+                .map_err(|e| Error::SNARKHandleMessage(e.to_string()))?
+        })
+    }
+
+    /// Prove a task with [SNARKBehaviour::handle_snark_proof_task] once a worker is free,
+    /// see [SNARKWorkerPool::try_prove].
+    pub async fn prove(&self, task: SNARKProofTask) -> Result<SNARKVerifyTask> {
+        self.try_prove(task)?.await
+    }
 }
 
 /// SNARK message handler
@@ -75,6 +208,42 @@ impl AsRef<SNARKVerifyTask> for &SNARKVerifyTask {
 }
 
 impl SNARKBehaviour {
+    /// Create a behaviour proving received tasks with a [SNARKWorkerPool] of `size` workers,
+    /// so that independent tasks are proved concurrently without blocking message handling.
+    /// Proofs are sent back as they complete, which may be out of order.
+    #[cfg(feature = "node")]
+    pub fn with_workers(size: usize) -> Self {
+        Self {
+            inner: Arc::new(SNARKTaskManager {
+                workers: Some(SNARKWorkerPool::new(size)),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Send proof of a task back to the verifier.
+    async fn send_proof(
+        provider: Arc<Provider>,
+        verifier: Did,
+        task_id: TaskId,
+        proof: SNARKVerifyTask,
+    ) -> Result<()> {
+        let resp: BackendMessage = SNARKTaskMessage {
+            task_id,
+            task: SNARKTask::SNARKVerify(proof),
+        }
+        .into();
+        let params = resp.into_send_backend_message_request(verifier)?;
+        provider
+            .request_internal_with_timeout(
+                Method::SendBackendMessage.to_string(),
+                serde_json::to_value(params)?,
+                std::time::Duration::from_secs(SNARK_REQUEST_TIMEOUT),
+            )
+            .await?;
+        Ok(())
+    }
+
     /// Generate proof task
     pub fn gen_proof_task(circuits: Vec<Circuit>) -> Result<SNARKProofTask> {
         SNARKTaskBuilder::gen_proof_task(circuits)
@@ -98,6 +267,14 @@ impl SNARKBehaviour {
         task_ref: impl AsRef<SNARKProofTask>,
         did: Did,
     ) -> Result<String> {
+        if let Some(caps) = provider.peer_capabilities(did) {
+            if !caps.iter().any(|c| c == CAPABILITY_SNARK) {
+                return Err(Error::CapabilityNotSupported(
+                    did.to_string(),
+                    CAPABILITY_SNARK.to_string(),
+                ));
+            }
+        }
         let task_id = uuid::Uuid::new_v4();
         let task = task_ref.as_ref().clone();
         let msg: BackendMessage = SNARKTaskMessage {
@@ -107,19 +284,100 @@ impl SNARKBehaviour {
         .into();
         let params = msg.into_send_backend_message_request(did)?;
         #[cfg(not(target_arch = "wasm32"))]
-        provider.request(Method::SendBackendMessage, params).await?;
+        provider
+            .request_with_timeout(
+                Method::SendBackendMessage,
+                params,
+                std::time::Duration::from_secs(SNARK_REQUEST_TIMEOUT),
+            )
+            .await?;
         #[cfg(target_arch = "wasm32")]
         {
             let req = rings_core::utils::js_value::serialize(&params)?;
-            let promise = provider.request(Method::SendBackendMessage.to_string(), req);
+            let promise = provider.request_with_timeout(
+                Method::SendBackendMessage.to_string(),
+                req,
+                SNARK_REQUEST_TIMEOUT as u32 * 1000,
+            );
             wasm_bindgen_futures::JsFuture::from(promise)
                 .await
                 .map_err(|e| Error::JsError(format!("Failed to send backend messate: {:?}", e)))?;
         }
         self.task.insert(task_id, task);
-        tracing::info!("sent proof request");
+        tracing::info!(target: "rings::snark", "sent proof request");
         Ok(task_id.to_string())
     }
+
+    /// Get ids of tasks sent but not verified yet.
+    pub fn pending_tasks(&self) -> Vec<TaskId> {
+        self.task
+            .iter()
+            .map(|t| *t.key())
+            .filter(|id| !self.verified.contains_key(id))
+            .collect()
+    }
+
+    /// Get verified results of tasks, whether the proof of each task is valid.
+    pub fn verified_results(&self) -> HashMap<TaskId, bool> {
+        self.verified
+            .iter()
+            .map(|r| (*r.key(), *r.value()))
+            .collect()
+    }
+
+    /// Keep a received proof to verify again by [SNARKBehaviour::revalidate_all].
+    /// At most [SNARK_MAX_KEPT_PROOFS] proofs are kept, the earliest received are dropped
+    /// beyond that.
+    fn keep_proof(&self, task_id: TaskId, proof: SNARKVerifyTask) {
+        let mut order = self.proof_order.lock().unwrap_or_else(|e| e.into_inner());
+        if self.proofs.insert(task_id, proof).is_none() {
+            order.push_back(task_id);
+        }
+        while order.len() > SNARK_MAX_KEPT_PROOFS {
+            let Some(task_id) = order.pop_front() else {
+                break;
+            };
+            self.proofs.remove(&task_id);
+        }
+    }
+
+    /// Verify every kept proof again against its current task, such as after the circuit
+    /// of a task is updated, and update the results. A proof failing to verify is invalid.
+    /// Like [SNARKBehaviour::verify_proof], the verifier key is derived from the circuit of
+    /// task rather than the one carried by the proof.
+    /// Return ids of tasks whose result is changed.
+    pub fn revalidate_all(&self) -> HashSet<TaskId> {
+        // Collect proofs and tasks first, so that no guard of map is held while verifying.
+        let proofs = self
+            .proofs
+            .iter()
+            .filter_map(|proof| {
+                let task = self.task.get(proof.key())?;
+                Some((*proof.key(), proof.value().clone(), task.value().clone()))
+            })
+            .collect::<Vec<_>>();
+
+        let mut changed = HashSet::new();
+        for (task_id, proof, task) in proofs {
+            let verified = task
+                .circuit(0)
+                .and_then(|circuit| {
+                    let public_inputs = circuit.public_inputs();
+                    self.verify_proof(&proof, &circuit, public_inputs, task.num_steps())
+                })
+                .unwrap_or_else(|e| {
+                    tracing::warn!(
+                        target: "rings::snark",
+                        "Failed to verify task {task_id}: {e:?}"
+                    );
+                    false
+                });
+            if self.verified.insert(task_id, verified) != Some(verified) {
+                changed.insert(task_id);
+            }
+        }
+        changed
+    }
 }
 
 #[wasm_export]
@@ -133,6 +391,12 @@ impl SNARKBehaviour {
             Ok(false)
         }
     }
+
+    /// Cancel all pending tasks. Proofs of them received later are ignored,
+    /// while verified results are kept.
+    pub fn cancel_all(&self) {
+        self.task.retain(|id, _| self.verified.contains_key(id));
+    }
 }
 
 /// Types for circuit
@@ -147,7 +411,7 @@ pub enum CircuitGenerator {
 
 /// Supported prime field
 #[wasm_export]
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SupportedPrimeField {
     /// field of vesta curve
     Vesta,
@@ -157,6 +421,31 @@ pub enum SupportedPrimeField {
     Bn256KZG,
 }
 
+impl FromStr for SupportedPrimeField {
+    type Err = Error;
+
+    /// Parse name of field, which is one of `vesta`, `pallas` and `bn256_kzg`.
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "vesta" => Ok(Self::Vesta),
+            "pallas" => Ok(Self::Pallas),
+            "bn256_kzg" => Ok(Self::Bn256KZG),
+            _ => Err(Error::UnsupportedCurve(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for SupportedPrimeField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Vesta => "vesta",
+            Self::Pallas => "pallas",
+            Self::Bn256KZG => "bn256_kzg",
+        };
+        write!(f, "{name}")
+    }
+}
+
 /// Input type
 #[wasm_export]
 #[derive(Deserialize, Serialize)]
@@ -234,17 +523,56 @@ impl Circuit {
     pub fn from_json(s: String) -> Result<Circuit> {
         Ok(serde_json::from_str(&s)?)
     }
+
+    /// public inputs of circuit, which are the statement to prove
+    pub fn public_inputs(&self) -> Vec<Field> {
+        match &self.inner {
+            CircuitEnum::Vesta(c) => c.get_public_inputs().into_iter().map(Field::from).collect(),
+            CircuitEnum::Pallas(c) => c.get_public_inputs().into_iter().map(Field::from).collect(),
+            CircuitEnum::Bn256KZG(c) => {
+                c.get_public_inputs().into_iter().map(Field::from).collect()
+            }
+        }
+    }
+
+    /// shape of circuit, see [CircuitInfo]
+    pub fn info(&self) -> CircuitInfo {
+        match &self.inner {
+            CircuitEnum::Vesta(c) => c.into(),
+            CircuitEnum::Pallas(c) => c.into(),
+            CircuitEnum::Bn256KZG(c) => c.into(),
+        }
+    }
+}
+
+/// Shape of a circuit, which can be checked before proving it
+#[wasm_export]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitInfo {
+    /// number of public inputs
+    pub num_inputs: usize,
+    /// number of r1cs constraints
+    pub num_constraints: usize,
+}
+
+impl<F: ff::PrimeField> From<&circuit::Circuit<F>> for CircuitInfo {
+    fn from(c: &circuit::Circuit<F>) -> Self {
+        Self {
+            num_inputs: c.num_public_inputs(),
+            num_constraints: c.num_constraints(),
+        }
+    }
 }
 
 /// Field type
 #[wasm_export]
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 pub struct Field {
     value: FieldEnum,
 }
 
 /// Supported prime field
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 pub enum FieldEnum {
     /// field of vesta curve
     Vesta(<provider::VestaEngine as Engine>::Scalar),
@@ -272,6 +600,30 @@ impl Field {
     }
 }
 
+impl From<<provider::VestaEngine as Engine>::Scalar> for Field {
+    fn from(v: <provider::VestaEngine as Engine>::Scalar) -> Self {
+        Self {
+            value: FieldEnum::Vesta(v),
+        }
+    }
+}
+
+impl From<<provider::PallasEngine as Engine>::Scalar> for Field {
+    fn from(v: <provider::PallasEngine as Engine>::Scalar) -> Self {
+        Self {
+            value: FieldEnum::Pallas(v),
+        }
+    }
+}
+
+impl From<<provider::Bn256EngineKZG as Engine>::Scalar> for Field {
+    fn from(v: <provider::Bn256EngineKZG as Engine>::Scalar) -> Self {
+        Self {
+            value: FieldEnum::Bn256KZG(v),
+        }
+    }
+}
+
 /// Snark builder
 #[wasm_export]
 pub struct SNARKTaskBuilder {
@@ -280,7 +632,18 @@ pub struct SNARKTaskBuilder {
 
 #[wasm_export]
 impl SNARKTaskBuilder {
-    /// Load r1cs sand witness from local path
+    /// Load r1cs sand witness from local path, with the field given by name,
+    /// such as `vesta` of config. See [SupportedPrimeField::from_str].
+    pub async fn from_local_with_field_name(
+        r1cs_path: String,
+        witness_wasm_path: String,
+        field: String,
+    ) -> Result<SNARKTaskBuilder> {
+        let field = field.parse::<SupportedPrimeField>()?;
+        Self::from_local(r1cs_path, witness_wasm_path, field).await
+    }
+
+    /// Load r1cs sand witness from local path.
     pub async fn from_local(
         r1cs_path: String,
         witness_wasm_path: String,
@@ -378,6 +741,27 @@ impl SNARKTaskBuilder {
         }
     }
 
+    /// Download r1cs and witness wasm from http(s) urls.
+    /// Each download is limited by [r1cs::DEFAULT_MAX_DOWNLOAD_SIZE], and its SHA-256 is
+    /// verified if the expected one in hex is given.
+    pub async fn from_url(
+        r1cs_url: String,
+        witness_wasm_url: String,
+        field: SupportedPrimeField,
+        r1cs_sha256: Option<String>,
+        witness_wasm_sha256: Option<String>,
+    ) -> Result<SNARKTaskBuilder> {
+        let r1cs = r1cs::Download::new(r1cs_url)
+            .sha256(r1cs_sha256)
+            .fetch()
+            .await?;
+        let witness_wasm = r1cs::Download::new(witness_wasm_url)
+            .sha256(witness_wasm_sha256)
+            .fetch()
+            .await?;
+        Self::from_bytes(r1cs, &witness_wasm, field)
+    }
+
     /// generate recursive circuits
     pub fn gen_circuits(
         &self,
@@ -550,6 +934,42 @@ impl SNARKTaskBuilder {
 }
 
 impl SNARKTaskBuilder {
+    /// Load r1cs and witness wasm from bytes, such as downloaded by [SNARKTaskBuilder::from_url].
+    fn from_bytes(
+        r1cs: Vec<u8>,
+        witness_wasm: &[u8],
+        field: SupportedPrimeField,
+    ) -> Result<SNARKTaskBuilder> {
+        let witness_calculator = r1cs::load_circom_witness_calculator_from_bytes(witness_wasm)?;
+        let circuit_generator = match field {
+            SupportedPrimeField::Vesta => {
+                type F = <provider::VestaEngine as Engine>::Scalar;
+                let r1cs = r1cs::load_r1cs_from_bytes::<F>(r1cs, r1cs::Format::Bin)?;
+                CircuitGenerator::Vesta(circuit::WasmCircuitGenerator::<F>::new(
+                    r1cs,
+                    witness_calculator,
+                ))
+            }
+            SupportedPrimeField::Pallas => {
+                type F = <provider::PallasEngine as Engine>::Scalar;
+                let r1cs = r1cs::load_r1cs_from_bytes::<F>(r1cs, r1cs::Format::Bin)?;
+                CircuitGenerator::Pallas(circuit::WasmCircuitGenerator::<F>::new(
+                    r1cs,
+                    witness_calculator,
+                ))
+            }
+            SupportedPrimeField::Bn256KZG => {
+                type F = <provider::Bn256EngineKZG as Engine>::Scalar;
+                let r1cs = r1cs::load_r1cs_from_bytes::<F>(r1cs, r1cs::Format::Bin)?;
+                CircuitGenerator::Bn256KZG(circuit::WasmCircuitGenerator::<F>::new(
+                    r1cs,
+                    witness_calculator,
+                ))
+            }
+        };
+        Ok(Self { circuit_generator })
+    }
+
     /// Generate proof task
     pub fn gen_proof_task(circuits: Vec<Circuit>) -> Result<SNARKProofTask> {
         let task = match &circuits[0].inner {
@@ -699,6 +1119,34 @@ impl SNARKProofTask {
                 .collect(),
         }
     }
+
+    /// Number of steps to fold, see [SNARKGenerator::num_steps]
+    pub fn num_steps(&self) -> usize {
+        match self {
+            SNARKProofTask::PallasVasta(g) => g.num_steps(),
+            SNARKProofTask::VastaPallas(g) => g.num_steps(),
+            SNARKProofTask::Bn256KZGGrumpkin(g) => g.num_steps(),
+        }
+    }
+
+    /// The `i`th circuit, see [SNARKGenerator::circuit]
+    pub fn circuit(&self, i: usize) -> Result<Circuit> {
+        let inner = match self {
+            SNARKProofTask::PallasVasta(g) => CircuitEnum::Pallas(g.circuit(i)?.clone()),
+            SNARKProofTask::VastaPallas(g) => CircuitEnum::Vesta(g.circuit(i)?.clone()),
+            SNARKProofTask::Bn256KZGGrumpkin(g) => CircuitEnum::Bn256KZG(g.circuit(i)?.clone()),
+        };
+        Ok(Circuit { inner })
+    }
+
+    /// Shape of the `i`th circuit, see [SNARKGenerator::circuit_info]
+    pub fn circuit_info(&self, i: usize) -> Result<CircuitInfo> {
+        match self {
+            SNARKProofTask::PallasVasta(g) => g.circuit_info(i),
+            SNARKProofTask::VastaPallas(g) => g.circuit_info(i),
+            SNARKProofTask::Bn256KZGGrumpkin(g) => g.circuit_info(i),
+        }
+    }
 }
 
 impl<E1, E2> SNARKGenerator<E1, E2>
@@ -718,6 +1166,33 @@ where
         Ok(())
     }
 
+    /// Public inputs of the first circuit, which are verified with the proof.
+    /// They can be checked before running the expensive [SNARKGenerator::prove].
+    pub fn public_inputs(&self) -> Vec<Field>
+    where Field: From<E1::Scalar> {
+        self.circuits
+            .first()
+            .map(|c| c.get_public_inputs().into_iter().map(Field::from).collect())
+            .unwrap_or_default()
+    }
+
+    /// Number of steps to fold, which is the number of circuits.
+    pub fn num_steps(&self) -> usize {
+        self.circuits.len()
+    }
+
+    /// The `i`th circuit, return [Error::SNARKCircuitOutOfRange] if there is no one.
+    pub fn circuit(&self, i: usize) -> Result<&circuit::Circuit<E1::Scalar>> {
+        self.circuits
+            .get(i)
+            .ok_or(Error::SNARKCircuitOutOfRange(i, self.circuits.len()))
+    }
+
+    /// Shape of the `i`th circuit, return [Error::SNARKCircuitOutOfRange] if there is no one.
+    pub fn circuit_info(&self, i: usize) -> Result<CircuitInfo> {
+        self.circuit(i).map(CircuitInfo::from)
+    }
+
     /// Split a SNARKGenerator task to multiple, by split circuits into multiple
     pub fn split(&self, n: usize) -> Vec<Self> {
         let SNARKGenerator {
@@ -777,7 +1252,7 @@ where
 impl SNARKBehaviour {
     /// Handle proof task
     pub fn handle_snark_proof_task<T: AsRef<SNARKProofTask>>(data: T) -> Result<SNARKVerifyTask> {
-        tracing::debug!("SNARK proof start");
+        tracing::debug!(target: "rings::snark", "SNARK proof start");
         let ret = match data.as_ref() {
             SNARKProofTask::VastaPallas(s) => {
                 type E1 = provider::VestaEngine;
@@ -833,19 +1308,95 @@ impl SNARKBehaviour {
                 )?))
             }
         };
-        tracing::debug!("SNARK proof success");
+        tracing::debug!(target: "rings::snark", "SNARK proof success");
         ret
     }
 
-    /// Handle verify task
+    /// Handle verify task.
+    /// The proof of a task received from network is a field of the decoded [BackendMessage], so
+    /// it's held as a whole string here. Only callers holding the encoded proof, such as chunks of
+    /// it, can avoid that by [SNARKBehaviour::handle_snark_verify_reader].
     pub fn handle_snark_verify_task<T: AsRef<SNARKVerifyTask>, F: AsRef<SNARKProofTask>>(
         data: T,
         snark: F,
     ) -> Result<bool> {
-        tracing::debug!("SNARK verify start");
         let snark = snark.as_ref();
+        let proof = match (data.as_ref(), snark) {
+            (SNARKVerifyTask::PallasVasta(p), SNARKProofTask::PallasVasta(_)) => p,
+            (SNARKVerifyTask::VastaPallas(p), SNARKProofTask::VastaPallas(_)) => p,
+            (SNARKVerifyTask::Bn256KZGGrumpkin(p), SNARKProofTask::Bn256KZGGrumpkin(_)) => p,
+            _ => return Err(Error::SNARKCurveNotMatch()),
+        };
+        Self::handle_snark_verify_reader(proof.as_bytes(), snark)
+    }
+
+    /// Handle verify task with the JSON encoded proof read from `reader`, such as
+    /// [rings_core::chunk::ChunkReader] of reassembled chunks, so that large proofs are
+    /// deserialized without holding the whole JSON string. The curve of proof is the one
+    /// of `snark`.
+    pub fn handle_snark_verify_reader<R: std::io::Read, F: AsRef<SNARKProofTask>>(
+        reader: R,
+        snark: F,
+    ) -> Result<bool> {
+        tracing::debug!(target: "rings::snark", "SNARK verify start");
+        let reader = std::io::BufReader::new(reader);
+        let ret = match snark.as_ref() {
+            SNARKProofTask::PallasVasta(t) => {
+                type E1 = provider::PallasEngine;
+                type E2 = provider::VestaEngine;
+                type EE1 = ipa_pc::EvaluationEngine<E1>;
+                type EE2 = ipa_pc::EvaluationEngine<E2>;
+                type S1 = spartan::snark::RelaxedR1CSSNARK<E1, EE1>;
+                type S2 = spartan::snark::RelaxedR1CSSNARK<E2, EE2>;
+                let proof = serde_json::from_reader::<_, SNARKProof<E1, E2, S1, S2>>(reader)?;
+                let ret = t.verify::<S1, S2>(proof.proof, proof.vk);
+                Ok(ret.is_ok())
+            }
+            SNARKProofTask::VastaPallas(t) => {
+                type E1 = provider::VestaEngine;
+                type E2 = provider::PallasEngine;
+                type EE1 = ipa_pc::EvaluationEngine<E1>;
+                type EE2 = ipa_pc::EvaluationEngine<E2>;
+                type S1 = spartan::snark::RelaxedR1CSSNARK<E1, EE1>;
+                type S2 = spartan::snark::RelaxedR1CSSNARK<E2, EE2>;
+                let proof = serde_json::from_reader::<_, SNARKProof<E1, E2, S1, S2>>(reader)?;
+                let ret = t.verify::<S1, S2>(proof.proof, proof.vk);
+                Ok(ret.is_ok())
+            }
+            SNARKProofTask::Bn256KZGGrumpkin(t) => {
+                type E1 = provider::Bn256EngineKZG;
+                type E2 = provider::GrumpkinEngine;
+                type EE1 = hyperkzg::EvaluationEngine<E1>;
+                type EE2 = ipa_pc::EvaluationEngine<E2>;
+                type S1 = spartan::snark::RelaxedR1CSSNARK<E1, EE1>; // non-preprocessing SNARK
+                type S2 = spartan::snark::RelaxedR1CSSNARK<E2, EE2>; // non-preprocessing SNARK
+                let proof = serde_json::from_reader::<_, SNARKProof<E1, E2, S1, S2>>(reader)?;
+                let ret = t.verify::<S1, S2>(proof.proof, proof.vk);
+                Ok(ret.is_ok())
+            }
+        };
+        tracing::debug!(target: "rings::snark", "SNARK verify success");
+        ret
+    }
+
+    /// Verify a proof without the proof task, for verifiers that never ran the prove step.
+    /// The verifier key is derived from `circuit`, any circuit of the proven shape, instead of
+    /// the one carried by the proof, which is chosen by the prover. `public_inputs` are the
+    /// public inputs of the first circuit and `steps` is the number of circuits folded.
+    /// The key is derived once for each r1cs and cached, see [circuit::Circuit::r1cs_digest].
+    pub fn verify_proof<T: AsRef<SNARKVerifyTask>>(
+        &self,
+        data: T,
+        circuit: &Circuit,
+        public_inputs: Vec<Field>,
+        steps: usize,
+    ) -> Result<bool> {
+        tracing::debug!(target: "rings::snark", "SNARK verify proof start");
         let ret = match data.as_ref() {
             SNARKVerifyTask::PallasVasta(p) => {
+                let CircuitEnum::Pallas(c) = &circuit.inner else {
+                    return Err(Error::SNARKCurveNotMatch());
+                };
                 type E1 = provider::PallasEngine;
                 type E2 = provider::VestaEngine;
                 type EE1 = ipa_pc::EvaluationEngine<E1>;
@@ -853,14 +1404,33 @@ impl SNARKBehaviour {
                 type S1 = spartan::snark::RelaxedR1CSSNARK<E1, EE1>;
                 type S2 = spartan::snark::RelaxedR1CSSNARK<E2, EE2>;
                 let proof = serde_json::from_str::<SNARKProof<E1, E2, S1, S2>>(p)?;
-                if let SNARKProofTask::PallasVasta(t) = snark {
-                    let ret = t.verify::<S1, S2>(proof.proof, proof.vk);
-                    Ok(ret.is_ok())
-                } else {
-                    Err(Error::SNARKCurveNotMatch())
-                }
+                let inputs = public_inputs
+                    .into_iter()
+                    .map(|f| match f.value {
+                        FieldEnum::Pallas(x) => Ok(x),
+                        _ => Err(Error::SNARKCurveNotMatch()),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let digest = c.r1cs_digest();
+                let cached = self.verifier_keys.get(&digest).map(|k| k.value().clone());
+                let vk = match cached {
+                    Some(CachedVerifierKey::Pallas(vk)) => vk,
+                    _ => {
+                        let pp: Arc<_> = SNARK::<E1, E2>::gen_pp::<S1, S2>(c.clone())?.into();
+                        let (_, vk) = SNARK::<E1, E2>::compress_setup::<S1, S2>(pp)?;
+                        let vk = Arc::new(vk);
+                        self.verifier_keys
+                            .insert(digest, CachedVerifierKey::Pallas(vk.clone()));
+                        vk
+                    }
+                };
+                let ret = SNARK::<E1, E2>::compress_verify(proof.proof, vk, steps, inputs);
+                Ok(ret.is_ok())
             }
             SNARKVerifyTask::VastaPallas(p) => {
+                let CircuitEnum::Vesta(c) = &circuit.inner else {
+                    return Err(Error::SNARKCurveNotMatch());
+                };
                 type E1 = provider::VestaEngine;
                 type E2 = provider::PallasEngine;
                 type EE1 = ipa_pc::EvaluationEngine<E1>;
@@ -868,14 +1438,33 @@ impl SNARKBehaviour {
                 type S1 = spartan::snark::RelaxedR1CSSNARK<E1, EE1>;
                 type S2 = spartan::snark::RelaxedR1CSSNARK<E2, EE2>;
                 let proof = serde_json::from_str::<SNARKProof<E1, E2, S1, S2>>(p)?;
-                if let SNARKProofTask::VastaPallas(t) = snark {
-                    let ret = t.verify::<S1, S2>(proof.proof, proof.vk);
-                    Ok(ret.is_ok())
-                } else {
-                    Err(Error::SNARKCurveNotMatch())
-                }
+                let inputs = public_inputs
+                    .into_iter()
+                    .map(|f| match f.value {
+                        FieldEnum::Vesta(x) => Ok(x),
+                        _ => Err(Error::SNARKCurveNotMatch()),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let digest = c.r1cs_digest();
+                let cached = self.verifier_keys.get(&digest).map(|k| k.value().clone());
+                let vk = match cached {
+                    Some(CachedVerifierKey::Vesta(vk)) => vk,
+                    _ => {
+                        let pp: Arc<_> = SNARK::<E1, E2>::gen_pp::<S1, S2>(c.clone())?.into();
+                        let (_, vk) = SNARK::<E1, E2>::compress_setup::<S1, S2>(pp)?;
+                        let vk = Arc::new(vk);
+                        self.verifier_keys
+                            .insert(digest, CachedVerifierKey::Vesta(vk.clone()));
+                        vk
+                    }
+                };
+                let ret = SNARK::<E1, E2>::compress_verify(proof.proof, vk, steps, inputs);
+                Ok(ret.is_ok())
             }
             SNARKVerifyTask::Bn256KZGGrumpkin(p) => {
+                let CircuitEnum::Bn256KZG(c) = &circuit.inner else {
+                    return Err(Error::SNARKCurveNotMatch());
+                };
                 type E1 = provider::Bn256EngineKZG;
                 type E2 = provider::GrumpkinEngine;
                 type EE1 = hyperkzg::EvaluationEngine<E1>;
@@ -883,15 +1472,31 @@ impl SNARKBehaviour {
                 type S1 = spartan::snark::RelaxedR1CSSNARK<E1, EE1>; // non-preprocessing SNARK
                 type S2 = spartan::snark::RelaxedR1CSSNARK<E2, EE2>; // non-preprocessing SNARK
                 let proof = serde_json::from_str::<SNARKProof<E1, E2, S1, S2>>(p)?;
-                if let SNARKProofTask::Bn256KZGGrumpkin(t) = snark {
-                    let ret = t.verify::<S1, S2>(proof.proof, proof.vk);
-                    Ok(ret.is_ok())
-                } else {
-                    Err(Error::SNARKCurveNotMatch())
-                }
+                let inputs = public_inputs
+                    .into_iter()
+                    .map(|f| match f.value {
+                        FieldEnum::Bn256KZG(x) => Ok(x),
+                        _ => Err(Error::SNARKCurveNotMatch()),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let digest = c.r1cs_digest();
+                let cached = self.verifier_keys.get(&digest).map(|k| k.value().clone());
+                let vk = match cached {
+                    Some(CachedVerifierKey::Bn256KZG(vk)) => vk,
+                    _ => {
+                        let pp: Arc<_> = SNARK::<E1, E2>::gen_pp::<S1, S2>(c.clone())?.into();
+                        let (_, vk) = SNARK::<E1, E2>::compress_setup::<S1, S2>(pp)?;
+                        let vk = Arc::new(vk);
+                        self.verifier_keys
+                            .insert(digest, CachedVerifierKey::Bn256KZG(vk.clone()));
+                        vk
+                    }
+                };
+                let ret = SNARK::<E1, E2>::compress_verify(proof.proof, vk, steps, inputs);
+                Ok(ret.is_ok())
             }
         };
-        tracing::debug!("SNARK verify success");
+        tracing::debug!(target: "rings::snark", "SNARK verify proof success");
         ret
     }
 }
@@ -926,25 +1531,33 @@ impl MessageHandler<SNARKTaskMessage> for SNARKBehaviour {
         let verifier = ctx.relay.origin_sender();
         match &msg.task {
             SNARKTask::SNARKProof(t) => {
-                let proof = Self::handle_snark_proof_task(t)?;
-                let resp: BackendMessage = SNARKTaskMessage {
-                    task_id: msg.task_id,
-                    task: SNARKTask::SNARKVerify(proof),
+                #[cfg(feature = "node")]
+                if let Some(workers) = &self.workers {
+                    let task_id = msg.task_id;
+                    let proving = workers.try_prove(t.clone())?;
+                    tokio::spawn(async move {
+                        let sent = match proving.await {
+                            Ok(proof) => Self::send_proof(provider, verifier, task_id, proof).await,
+                            Err(e) => Err(e),
+                        };
+                        if let Err(e) = sent {
+                            tracing::error!(
+                                target: "rings::snark",
+                                "Failed to prove task {task_id}: {e:?}"
+                            );
+                        }
+                    });
+                    return Ok(());
                 }
-                .into();
-                let params = resp.into_send_backend_message_request(verifier)?;
-                provider
-                    .request_internal(
-                        Method::SendBackendMessage.to_string(),
-                        serde_json::to_value(params)?,
-                    )
-                    .await?;
+                let proof = Self::handle_snark_proof_task(t)?;
+                Self::send_proof(provider, verifier, msg.task_id, proof).await?;
                 Ok(())
             }
             SNARKTask::SNARKVerify(t) => {
                 if let Some(task) = self.task.get(&msg.task_id) {
                     let verified = Self::handle_snark_verify_task(t, task.value())?;
                     self.verified.insert(msg.task_id, verified);
+                    self.keep_proof(msg.task_id, t.clone());
                 }
                 Ok(())
             }
@@ -964,7 +1577,149 @@ impl MessageHandler<BackendMessage> for SNARKBehaviour {
         if let BackendMessage::SNARKTaskMessage(msg) = msg {
             Ok(self.handle_message(provider.clone(), ctx, msg).await?)
         } else {
-            Ok(())
+            MessageHandler::<BackendMessage>::on_unhandled(self, provider, ctx, msg.variant_name())
+                .await
+        }
+    }
+
+    fn handles(&self, msg: &BackendMessage) -> bool {
+        matches!(msg, BackendMessage::SNARKTaskMessage(_))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tests::native::snark::simple_circuits;
+    use crate::tests::native::snark::simple_input;
+    use crate::tests::native::snark::simple_task_builder;
+
+    #[tokio::test]
+    async fn test_list_and_cancel_pending_tasks() {
+        let circuits = simple_circuits(2).await;
+        let task = SNARKBehaviour::gen_proof_task(circuits).unwrap();
+
+        let behaviour = SNARKBehaviour::default();
+        let ids = (0..3).map(|_| uuid::Uuid::new_v4()).collect::<Vec<_>>();
+        for id in &ids {
+            behaviour.task.insert(*id, task.clone());
+        }
+        behaviour.verified.insert(ids[0], true);
+
+        let mut pending = behaviour.pending_tasks();
+        pending.sort();
+        let mut expected = ids[1..].to_vec();
+        expected.sort();
+        assert_eq!(pending, expected);
+        assert_eq!(
+            behaviour.verified_results(),
+            HashMap::from([(ids[0], true)])
+        );
+
+        behaviour.cancel_all();
+        assert!(behaviour.pending_tasks().is_empty());
+        assert_eq!(
+            behaviour.verified_results(),
+            HashMap::from([(ids[0], true)])
+        );
+        assert!(behaviour.get_task_result(ids[0].to_string()).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_revalidate_all() {
+        let snark_task_builder = simple_task_builder().await;
+        let gen_task = |x: u64, y: u64| {
+            let circuits = snark_task_builder
+                .gen_circuits(simple_input(x, y), vec![], 2)
+                .unwrap();
+            SNARKBehaviour::gen_proof_task(circuits).unwrap()
+        };
+        let task = gen_task(4, 2);
+        let proof = SNARKBehaviour::handle_snark_proof_task(&task).unwrap();
+
+        let behaviour = SNARKBehaviour::default();
+        let ids = (0..2).map(|_| uuid::Uuid::new_v4()).collect::<Vec<_>>();
+        for id in &ids {
+            behaviour.task.insert(*id, task.clone());
+            behaviour.keep_proof(*id, proof.clone());
+            behaviour.verified.insert(*id, true);
         }
+        assert!(behaviour.revalidate_all().is_empty());
+
+        // The circuit of a task is changed, so that its proof is no longer valid.
+        behaviour.task.insert(ids[0], gen_task(5, 3));
+        assert_eq!(behaviour.revalidate_all(), HashSet::from([ids[0]]));
+        assert_eq!(
+            behaviour.verified_results(),
+            HashMap::from([(ids[0], false), (ids[1], true)])
+        );
+        assert!(behaviour.revalidate_all().is_empty());
+        // Both circuits are of the same r1cs, so they share a verifier key.
+        assert_eq!(behaviour.verifier_keys.len(), 1);
+
+        // Proofs received earliest are dropped once too many are kept.
+        for _ in ids.len()..SNARK_MAX_KEPT_PROOFS {
+            behaviour.keep_proof(uuid::Uuid::new_v4(), proof.clone());
+        }
+        assert_eq!(behaviour.proofs.len(), SNARK_MAX_KEPT_PROOFS);
+        assert!(behaviour.proofs.contains_key(&ids[0]));
+        behaviour.keep_proof(uuid::Uuid::new_v4(), proof.clone());
+        assert_eq!(behaviour.proofs.len(), SNARK_MAX_KEPT_PROOFS);
+        assert!(!behaviour.proofs.contains_key(&ids[0]));
+        assert!(behaviour.proofs.contains_key(&ids[1]));
+    }
+
+    #[tokio::test]
+    async fn test_public_inputs() {
+        let expected = vec![
+            Field::from_u64(4u64, SupportedPrimeField::Vesta),
+            Field::from_u64(2u64, SupportedPrimeField::Vesta),
+        ];
+        let circuits = simple_circuits(2).await;
+        assert_eq!(circuits[0].public_inputs(), expected);
+
+        let task = SNARKBehaviour::gen_proof_task(circuits).unwrap();
+        let SNARKProofTask::VastaPallas(generator) = &task else {
+            panic!("Wrong curve, expect vesta");
+        };
+        assert_eq!(generator.public_inputs(), expected);
+        assert_ne!(generator.public_inputs(), vec![Field::from_u64(
+            4u64,
+            SupportedPrimeField::Pallas
+        )]);
+    }
+
+    #[test]
+    fn test_supported_prime_field_from_str() {
+        for (name, field) in [
+            ("vesta", SupportedPrimeField::Vesta),
+            ("pallas", SupportedPrimeField::Pallas),
+            ("bn256_kzg", SupportedPrimeField::Bn256KZG),
+        ] {
+            assert_eq!(name.parse::<SupportedPrimeField>().unwrap(), field);
+            assert_eq!(field.to_string(), name);
+        }
+
+        assert!(matches!(
+            "secp256k1".parse::<SupportedPrimeField>(),
+            Err(Error::UnsupportedCurve(name)) if name == "secp256k1"
+        ));
+        assert!(matches!(
+            "Vesta".parse::<SupportedPrimeField>(),
+            Err(Error::UnsupportedCurve(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_from_local_with_unsupported_curve() {
+        let wasm = "../snark/src/tests/native/circoms/simple_bn256.wasm";
+        let r1cs = "../snark/src/tests/native/circoms/simple_bn256.r1cs";
+        let res = SNARKTaskBuilder::from_local_with_field_name(
+            r1cs.to_string(),
+            wasm.to_string(),
+            "bn254".to_string(),
+        )
+        .await;
+        assert!(matches!(res, Err(Error::UnsupportedCurve(name)) if name == "bn254"));
     }
 }
@@ -1,15 +1,24 @@
 #![warn(missing_docs)]
 
 //! Backend Message Types.
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::io::ErrorKind as IOErrorKind;
 use std::sync::Arc;
+use std::sync::Mutex;
 
 use bytes::Bytes;
+use rings_core::chunk::Chunk;
+use rings_core::chunk::ChunkList;
+use rings_core::chunk::ChunkManager;
+use rings_core::dht::Did;
 use rings_core::message::MessagePayload;
 use rings_rpc::protos::rings_node::SendBackendMessageRequest;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::consts::BACKEND_MAX_PENDING_TRANSFERS;
+use crate::consts::BACKEND_MAX_PENDING_TRANSFERS_PER_SENDER;
 use crate::error::Error;
 use crate::provider::Provider;
 
@@ -32,6 +41,25 @@ pub enum BackendMessage {
     /// SNARK with curve pallas and vesta
     #[cfg(feature = "snark")]
     SNARKTaskMessage(snark::SNARKTaskMessage),
+    /// A chunk of a serialized backend message which is too large to send at once.
+    /// Chunks are reassembled by [BackendMessageAssembler] before handling.
+    Chunk(Chunk),
+    /// A request sent by [Provider::call], which is answered by [Provider::respond] with the
+    /// same id.
+    Request {
+        /// Correlation id of the request.
+        id: uuid::Uuid,
+        /// The request message, encoded by [BackendMessage::encode_nested].
+        msg: Bytes,
+    },
+    /// A response to [BackendMessage::Request] of the same id.
+    /// It's delivered to the waiting [Provider::call] instead of the handler.
+    Response {
+        /// Correlation id of the request.
+        id: uuid::Uuid,
+        /// The response message, encoded by [BackendMessage::encode_nested].
+        msg: Bytes,
+    },
 }
 
 /// ServiceMessage
@@ -128,6 +156,28 @@ pub trait MessageHandler<T> {
         ctx: &MessagePayload,
         data: &T,
     ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Called by a handler of [BackendMessage] with the message which it doesn't handle, named by
+    /// [BackendMessage::variant_name]. Only logs it by default.
+    async fn on_unhandled(
+        &self,
+        _provider: Arc<Provider>,
+        ctx: &MessagePayload,
+        variant: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        tracing::debug!(
+            "BackendMessage {variant} from {:?} is not handled",
+            ctx.transaction.signer()
+        );
+        Ok(())
+    }
+
+    /// Whether the handler handles the message. A tuple of handlers passes a message only to
+    /// its members which handle it, and calls [MessageHandler::on_unhandled] of them when none
+    /// does. Every message is handled by default.
+    fn handles(&self, _data: &T) -> bool {
+        true
+    }
 }
 
 impl From<ServiceMessage> for BackendMessage {
@@ -162,9 +212,15 @@ impl From<IOErrorKind> for TunnelDefeat {
 ///         ctx: &MessagePayload,
 ///         msg: &BackendMessage,
 ///     ) -> std::result::Result<(), Box<dyn std::error::Error>> {
-///         self.0.handle_message(provider.clone(), ctx, msg).await?;
-///         self.1.handle_message(provider.clone(), ctx, msg).await?;
-///         self.2.handle_message(provider.clone(), ctx, msg).await?;
+///         let mut handled = false;
+///         if self.0.handles(msg) {
+///             handled = true;
+///             self.0.handle_message(provider.clone(), ctx, msg).await?;
+///         }
+///         ...
+///         if !handled {
+///             self.on_unhandled(provider, ctx, msg.variant_name()).await?;
+///         }
 ///         Ok(())
 ///     }
 /// }
@@ -181,11 +237,34 @@ macro_rules! impl_message_handler_for_tuple {
                 ctx: &MessagePayload,
                 msg: &BackendMessage,
             ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+                let mut handled = false;
                 $(
-                    self.$n.handle_message(provider.clone(), ctx, msg).await?;
+                    if self.$n.handles(msg) {
+                        handled = true;
+                        self.$n.handle_message(provider.clone(), ctx, msg).await?;
+                    }
                 )+
+                if !handled {
+                    self.on_unhandled(provider, ctx, msg.variant_name()).await?;
+                }
                 Ok(())
             }
+
+            async fn on_unhandled(
+                &self,
+                provider: Arc<Provider>,
+                ctx: &MessagePayload,
+                variant: &str,
+            ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+                $(
+                    self.$n.on_unhandled(provider.clone(), ctx, variant).await?;
+                )+
+                Ok(())
+            }
+
+            fn handles(&self, msg: &BackendMessage) -> bool {
+                false $(|| self.$n.handles(msg))+
+            }
         }
     };
 
@@ -200,11 +279,34 @@ macro_rules! impl_message_handler_for_tuple {
                 ctx: &MessagePayload,
                 msg: &BackendMessage,
             ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+                let mut handled = false;
                 $(
-                    self.$n.handle_message(provider.clone(), ctx, msg).await?;
+                    if self.$n.handles(msg) {
+                        handled = true;
+                        self.$n.handle_message(provider.clone(), ctx, msg).await?;
+                    }
                 )+
+                if !handled {
+                    self.on_unhandled(provider, ctx, msg.variant_name()).await?;
+                }
                 Ok(())
             }
+
+            async fn on_unhandled(
+                &self,
+                provider: Arc<Provider>,
+                ctx: &MessagePayload,
+                variant: &str,
+            ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+                $(
+                    self.$n.on_unhandled(provider.clone(), ctx, variant).await?;
+                )+
+                Ok(())
+            }
+
+            fn handles(&self, msg: &BackendMessage) -> bool {
+                false $(|| self.$n.handles(msg))+
+            }
         }
     };
 }
@@ -228,6 +330,64 @@ impl_message_handler_for_tuple!(T1, T2, T3, T4; 0, 1, 2, 3; wasm);
 impl_message_handler_for_tuple!(T1, T2, T3, T4, T5; 0, 1, 2, 3, 4; wasm);
 
 impl BackendMessage {
+    /// Name of the variant, which is given to [MessageHandler::on_unhandled].
+    pub fn variant_name(&self) -> &'static str {
+        match self {
+            BackendMessage::Extension(_) => "Extension",
+            BackendMessage::ServiceMessage(_) => "ServiceMessage",
+            BackendMessage::PlainText(_) => "PlainText",
+            #[cfg(feature = "snark")]
+            BackendMessage::SNARKTaskMessage(_) => "SNARKTaskMessage",
+            BackendMessage::Chunk(_) => "Chunk",
+            BackendMessage::Request { .. } => "Request",
+            BackendMessage::Response { .. } => "Response",
+        }
+    }
+
+    /// Encode the message carried by [BackendMessage::Request] or [BackendMessage::Response].
+    /// The message is kept as bytes rather than nested, so that decoding a received message
+    /// never recurses. Requests, responses and chunks can't be carried.
+    pub fn encode_nested(&self) -> Result<Bytes, Error> {
+        if !self.is_nestable() {
+            return Err(Error::EncodeError);
+        }
+        bincode::serialize(self)
+            .map(Bytes::from)
+            .map_err(|_| Error::EncodeError)
+    }
+
+    /// Decode the message carried by [BackendMessage::Request] or [BackendMessage::Response],
+    /// see [BackendMessage::encode_nested].
+    pub fn decode_nested(data: &[u8]) -> Result<BackendMessage, Error> {
+        let msg: BackendMessage = bincode::deserialize(data).map_err(|_| Error::DecodeError)?;
+        if !msg.is_nestable() {
+            return Err(Error::DecodeError);
+        }
+        Ok(msg)
+    }
+
+    fn is_nestable(&self) -> bool {
+        !matches!(
+            self,
+            BackendMessage::Chunk(_)
+                | BackendMessage::Request { .. }
+                | BackendMessage::Response { .. }
+        )
+    }
+
+    /// Split the message into [BackendMessage::Chunk]s if it's serialized larger than `mtu`.
+    /// Otherwise, the message itself is returned.
+    pub fn split(self, mtu: usize) -> Result<Vec<BackendMessage>, Error> {
+        let data = bincode::serialize(&self).map_err(|_| Error::EncodeError)?;
+        if data.len() <= mtu {
+            return Ok(vec![self]);
+        }
+        Ok(BackendChunks::split(&Bytes::from(data), mtu)
+            .into_iter()
+            .map(BackendMessage::Chunk)
+            .collect())
+    }
+
     /// Convert to SendBackendMessageRequest
     pub fn into_send_backend_message_request(
         self,
@@ -239,3 +399,274 @@ impl BackendMessage {
         })
     }
 }
+
+/// Chunks of a backend message being reassembled. Their size is decided by sender, see
+/// [BackendMessage::split], so the MTU of list is not used.
+type BackendChunks = ChunkList<{ usize::MAX }>;
+
+/// Number of distinct transfers in chunks.
+fn count_transfers(chunks: &BackendChunks) -> usize {
+    chunks
+        .as_vec()
+        .iter()
+        .map(|c| c.meta.id)
+        .collect::<HashSet<_>>()
+        .len()
+}
+
+/// Reassemble [BackendMessage::Chunk]s, keyed by sender and transfer id of chunks.
+/// Chunks can arrive in any order. Incomplete transfers are discarded once their ttl expired.
+///
+/// At most `max_per_sender` transfers of a sender and `max_total` transfers of all senders
+/// are in progress, chunks starting more transfers are rejected.
+pub struct BackendMessageAssembler {
+    transfers: Mutex<HashMap<Did, BackendChunks>>,
+    max_per_sender: usize,
+    max_total: usize,
+}
+
+impl Default for BackendMessageAssembler {
+    fn default() -> Self {
+        Self::with_limits(
+            BACKEND_MAX_PENDING_TRANSFERS_PER_SENDER,
+            BACKEND_MAX_PENDING_TRANSFERS,
+        )
+    }
+}
+
+impl BackendMessageAssembler {
+    /// Create an assembler keeping at most `max_per_sender` transfers of a sender and
+    /// `max_total` transfers of all senders in progress.
+    pub fn with_limits(max_per_sender: usize, max_total: usize) -> Self {
+        Self {
+            transfers: Mutex::new(HashMap::new()),
+            max_per_sender,
+            max_total,
+        }
+    }
+
+    /// Handle a chunk from sender, return the message once all chunks of it are received.
+    /// Fail with [Error::TooManyPendingTransfers] if the chunk starts a transfer beyond limits.
+    pub fn handle(&self, sender: Did, chunk: Chunk) -> Result<Option<BackendMessage>, Error> {
+        let mut transfers = self.transfers.lock().map_err(|_| Error::Lock)?;
+
+        for chunks in transfers.values_mut() {
+            chunks.remove_expired();
+        }
+        transfers.retain(|_, chunks| !chunks.as_vec().is_empty());
+
+        let id = chunk.meta.id;
+        let is_new = transfers.get(&sender).map_or(true, |chunks| {
+            chunks.as_vec().iter().all(|c| c.meta.id != id)
+        });
+        if is_new {
+            let of_sender = transfers.get(&sender).map_or(0, count_transfers);
+            let total = transfers.values().map(count_transfers).sum::<usize>();
+            if of_sender >= self.max_per_sender || total >= self.max_total {
+                return Err(Error::TooManyPendingTransfers);
+            }
+        }
+
+        let Some(data) = transfers.entry(sender).or_default().handle(chunk) else {
+            return Ok(None);
+        };
+        bincode::deserialize(&data)
+            .map(Some)
+            .map_err(|_| Error::DecodeError)
+    }
+
+    /// Number of senders having incomplete transfers.
+    pub fn pending(&self) -> usize {
+        self.transfers
+            .lock()
+            .map(|transfers| {
+                transfers
+                    .values()
+                    .filter(|chunks| !chunks.as_vec().is_empty())
+                    .count()
+            })
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rings_core::chunk::ChunkMeta;
+    use rings_core::ecc::SecretKey;
+
+    use super::*;
+
+    const TEST_MTU: usize = 64;
+
+    fn split_text(text: &str) -> Vec<Chunk> {
+        BackendMessage::PlainText(text.to_string())
+            .split(TEST_MTU)
+            .unwrap()
+            .into_iter()
+            .map(|m| match m {
+                BackendMessage::Chunk(c) => c,
+                _ => panic!("Expect chunk"),
+            })
+            .collect()
+    }
+
+    fn assert_text(msg: Option<BackendMessage>, text: &str) {
+        match msg {
+            Some(BackendMessage::PlainText(t)) => assert_eq!(t, text),
+            _ => panic!("Expect plain text"),
+        }
+    }
+
+    #[test]
+    fn test_small_message_not_split() {
+        let msgs = BackendMessage::PlainText("hi".to_string())
+            .split(TEST_MTU)
+            .unwrap();
+        assert_eq!(msgs.len(), 1);
+        assert!(matches!(msgs[0], BackendMessage::PlainText(_)));
+    }
+
+    #[test]
+    fn test_reassemble_in_order() {
+        let text = "hello world ".repeat(100);
+        let sender = SecretKey::random().address().into();
+        let assembler = BackendMessageAssembler::default();
+
+        let chunks = split_text(&text);
+        let last = chunks.len() - 1;
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            let msg = assembler.handle(sender, chunk).unwrap();
+            if i < last {
+                assert!(msg.is_none());
+            } else {
+                assert_text(msg, &text);
+            }
+        }
+        assert_eq!(assembler.pending(), 0);
+    }
+
+    #[test]
+    fn test_reassemble_out_of_order_and_interleaved() {
+        let text1 = "hello world ".repeat(100);
+        let text2 = "foo bar ".repeat(100);
+        let sender1: Did = SecretKey::random().address().into();
+        let sender2: Did = SecretKey::random().address().into();
+        let assembler = BackendMessageAssembler::default();
+
+        let mut chunks1 = split_text(&text1);
+        chunks1.reverse();
+        let chunks2 = split_text(&text2);
+        // Same transfer id from another sender must not be mixed in.
+        let mut forged = chunks1[0].clone();
+        forged.data = Bytes::from_static(b"forged");
+        assert!(assembler.handle(sender2, forged).unwrap().is_none());
+
+        let mut done1 = None;
+        let mut done2 = None;
+        for (c1, c2) in chunks1.into_iter().zip(chunks2.into_iter()) {
+            if let Some(m) = assembler.handle(sender1, c1).unwrap() {
+                done1 = Some(m);
+            }
+            if let Some(m) = assembler.handle(sender2, c2).unwrap() {
+                done2 = Some(m);
+            }
+        }
+        assert_text(done1, &text1);
+        assert_text(done2, &text2);
+    }
+
+    #[test]
+    fn test_dropped_chunk_discarded_after_ttl() {
+        let text = "hello world ".repeat(100);
+        let sender = SecretKey::random().address().into();
+        let assembler = BackendMessageAssembler::default();
+
+        let mut chunks = split_text(&text);
+        for chunk in chunks.iter_mut() {
+            chunk.meta = ChunkMeta {
+                ttl_ms: 100,
+                ..chunk.meta
+            };
+        }
+        let dropped = chunks.remove(1);
+
+        for chunk in chunks {
+            assert!(assembler.handle(sender, chunk).unwrap().is_none());
+        }
+        assert_eq!(assembler.pending(), 1);
+
+        std::thread::sleep(std::time::Duration::from_millis(200));
+
+        // The incomplete transfer is discarded once expired, a late chunk never completes it.
+        assert!(assembler.handle(sender, dropped).unwrap().is_none());
+        assert_eq!(assembler.pending(), 0);
+    }
+
+    #[test]
+    fn test_pending_transfers_limited() {
+        let text = "hello world ".repeat(100);
+        let sender1: Did = SecretKey::random().address().into();
+        let sender2: Did = SecretKey::random().address().into();
+        let sender3: Did = SecretKey::random().address().into();
+        let assembler = BackendMessageAssembler::with_limits(2, 3);
+
+        let transfer1 = split_text(&text);
+        assert!(assembler
+            .handle(sender1, transfer1[0].clone())
+            .unwrap()
+            .is_none());
+        assert!(assembler
+            .handle(sender1, split_text(&text)[0].clone())
+            .unwrap()
+            .is_none());
+        // More chunks of a transfer in progress are accepted, a new transfer is not.
+        assert!(assembler
+            .handle(sender1, transfer1[1].clone())
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            assembler.handle(sender1, split_text(&text)[0].clone()),
+            Err(Error::TooManyPendingTransfers)
+        ));
+
+        let mut transfer2 = split_text(&text);
+        let last = transfer2.pop().unwrap();
+        for chunk in transfer2 {
+            assert!(assembler.handle(sender2, chunk).unwrap().is_none());
+        }
+        assert!(matches!(
+            assembler.handle(sender3, split_text(&text)[0].clone()),
+            Err(Error::TooManyPendingTransfers)
+        ));
+        assert_eq!(assembler.pending(), 2);
+
+        // A completed transfer frees its place.
+        assert_text(assembler.handle(sender2, last).unwrap(), &text);
+        assert!(assembler
+            .handle(sender3, split_text(&text)[0].clone())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_nested_message() {
+        let msg = BackendMessage::PlainText("hello".to_string());
+        let data = msg.encode_nested().unwrap();
+        assert!(matches!(
+            BackendMessage::decode_nested(&data).unwrap(),
+            BackendMessage::PlainText(text) if text == "hello"
+        ));
+
+        // Requests and responses can't carry each other.
+        let request = BackendMessage::Request {
+            id: uuid::Uuid::new_v4(),
+            msg: data,
+        };
+        assert!(matches!(request.encode_nested(), Err(Error::EncodeError)));
+        let data = bincode::serialize(&request).unwrap();
+        assert!(matches!(
+            BackendMessage::decode_nested(&data),
+            Err(Error::DecodeError)
+        ));
+    }
+}
@@ -1,6 +1,17 @@
 use crate::prelude::rings_core::consts::*;
 
-pub const BACKEND_MTU: usize = TRANSPORT_MAX_SIZE - TRANSPORT_MTU;
+/// Bytes reserved for the payload wrapping a chunk of backend message, such as signatures and
+/// relay path. The rest of max message size of swarm is the data of the chunk.
+pub const BACKEND_CHUNK_OVERHEAD: usize = 4 * 1024;
+/// Max number of chunked backend messages reassembled at once from a sender, chunks of more
+/// transfers are rejected until some complete or expire
+pub const BACKEND_MAX_PENDING_TRANSFERS_PER_SENDER: usize = 8;
+/// Max number of chunked backend messages reassembled at once from all senders
+pub const BACKEND_MAX_PENDING_TRANSFERS: usize = 256;
+/// Capability of handling SNARK tasks, advertised in handshake
+pub const CAPABILITY_SNARK: &str = "snark";
+/// Capability of reassembling chunked backend messages, advertised in handshake
+pub const CAPABILITY_CHUNK: &str = "chunk";
 /// Redundant setting of vnode data storage
 pub const DATA_REDUNDANT: u16 = 6;
 /// Connect Behaviour
@@ -11,3 +22,10 @@ pub const MSG_SEND_FAILED_LIMIT: i64 = 10;
 pub const MSG_RECV_FAILED_LIMIT: i64 = 10;
 /// Timeout for proxied TCP connections
 pub const TCP_SERVER_TIMEOUT: u64 = 30;
+/// Timeout of sending SNARK tasks and proofs through provider, in seconds
+pub const SNARK_REQUEST_TIMEOUT: u64 = 30;
+/// Max number of SNARK proof tasks waiting for a free worker, more tasks are rejected
+pub const SNARK_MAX_QUEUED_PROOF_TASKS: usize = 64;
+/// Max number of received SNARK proofs kept to verify again, the earliest received are dropped
+/// beyond that
+pub const SNARK_MAX_KEPT_PROOFS: usize = 256;
@@ -20,6 +20,10 @@ pub enum Error {
     InternalRpcError(#[from] jsonrpc_core::Error) = 102,
     #[error("Uuid error: {0}")]
     UuidError(#[from] uuid::Error) = 103,
+    #[error("Request timeout after {0:?}.")]
+    RequestTimeout(std::time::Duration) = 104,
+    #[error("Request cancelled.")]
+    RequestCancelled = 105,
     #[error("Connection not found.")]
     ConnectionNotFound = 203,
     #[error("Create connection error: {0}.")]
@@ -62,6 +66,8 @@ pub enum Error {
     NoPermission = 504,
     #[error("Connect error, {0}")]
     ConnectError(rings_core::error::Error) = 600,
+    #[error("Connect timeout, {0}")]
+    ConnectTimeout(rings_core::error::Error) = 602,
     #[error("Send message error: {0}")]
     SendMessage(rings_core::error::Error) = 601,
     #[error("vnode action error: {0}")]
@@ -90,6 +96,10 @@ pub enum Error {
     Swarm(rings_core::error::Error) = 808,
     #[error("Invalid logging level: {0}")]
     InvalidLoggingLevel(String) = 809,
+    #[error("Peer {0} doesn't support {1}")]
+    CapabilityNotSupported(String, String) = 810,
+    #[error("Too many chunked transfers in progress")]
+    TooManyPendingTransfers = 811,
     #[error("Create File Error: {0}")]
     CreateFileError(String) = 900,
     #[error("Open File Error: {0}")]
@@ -139,6 +149,10 @@ pub enum Error {
     SNARKBigIntValueEmpty() = 1405,
     #[error("Failed to load string to PrimeField")]
     FailedToLoadFF() = 1406,
+    #[error("Unsupported curve {0}, should be one of vesta, pallas and bn256_kzg")]
+    UnsupportedCurve(String) = 1407,
+    #[error("Circuit index {0} out of range, there are {1} circuits")]
+    SNARKCircuitOutOfRange(usize, usize) = 1408,
     #[error("Extend Backend Error {0}")]
     BackendError(String) = 1501,
 }
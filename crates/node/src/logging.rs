@@ -145,13 +145,17 @@ pub mod node {
         set_panic_hook();
 
         let subscriber = Registry::default();
-        let level_filter = filter::LevelFilter::from_level(level.into());
+        // Directives in RUST_LOG override the level for their targets, such as
+        // `RUST_LOG=rings::relay=debug`, see the targets in docs of rings-core.
+        let env_filter = filter::EnvFilter::builder()
+            .with_default_directive(filter::LevelFilter::from_level(level.into()).into())
+            .from_env_lossy();
 
         // Stderr
         let subscriber = subscriber.with(
             fmt::layer()
                 .with_writer(std::io::stderr)
-                .with_filter(level_filter),
+                .with_filter(env_filter),
         );
         // Enable log compatible layer to convert log record to tracing span.
         // We will ignore any errors that returned by this functions.
@@ -11,6 +11,8 @@ use chrono::Utc;
 use dashmap::mapref::one::RefMut;
 use dashmap::DashMap;
 use rings_core::dht::Did;
+use rings_core::error::Error;
+use rings_core::error::Result;
 use rings_core::measure;
 use rings_core::measure::Measure;
 use rings_core::measure::MeasureCounter;
@@ -82,10 +84,10 @@ impl PeriodicCounter {
         }
     }
 
-    // Check period, then increase
-    fn incr(&mut self) -> (u64, bool) {
+    // Check period, then increase by n
+    fn incr(&mut self, n: u64) -> (u64, bool) {
         let is_refreshed = self.refresh();
-        self.count += 1;
+        self.count += n;
         (self.barely_get(), is_refreshed)
     }
 
@@ -114,27 +116,18 @@ impl PeriodicMeasure {
         &self,
         did: Did,
         counter: MeasureCounter,
-    ) -> RefMut<'_, (Did, MeasureCounter), Mutex<PeriodicCounter>> {
+    ) -> Result<RefMut<'_, (Did, MeasureCounter), Mutex<PeriodicCounter>>> {
         let k = Self::gen_storage_key(did, counter);
-        let count = self
-            .storage
-            .get(&k)
-            .await
-            .unwrap_or_else(|e| {
-                log::error!("Failed to get counter: {:?}", e);
-                Some(0)
-            })
-            .unwrap_or(0);
-        self.counters
+        let count = self.storage.get(&k).await?.unwrap_or(0);
+        Ok(self
+            .counters
             .entry((did, counter))
-            .or_insert_with(|| Mutex::new(PeriodicCounter::new(DURATION, count)))
+            .or_insert_with(|| Mutex::new(PeriodicCounter::new(DURATION, count))))
     }
 
-    async fn save_counter(&self, did: Did, counter: MeasureCounter, count: u64) {
+    async fn save_counter(&self, did: Did, counter: MeasureCounter, count: u64) -> Result<()> {
         let k = Self::gen_storage_key(did, counter);
-        self.storage.put(&k, &count).await.unwrap_or_else(|e| {
-            log::error!("Failed to save counter: {:?}", e);
-        })
+        self.storage.put(&k, &count).await
     }
 }
 
@@ -142,36 +135,38 @@ impl PeriodicMeasure {
 #[cfg_attr(feature = "browser", async_trait(?Send))]
 impl Measure for PeriodicMeasure {
     /// `incr` increments the counter of the given peer.
-    async fn incr(&self, did: Did, counter: MeasureCounter) {
+    async fn incr(&self, did: Did, counter: MeasureCounter) -> Result<()> {
+        self.incr_by(did, counter, 1).await
+    }
+
+    /// `incr_by` increases the counter of the given peer by `n`.
+    async fn incr_by(&self, did: Did, counter: MeasureCounter, n: u64) -> Result<()> {
         let (count, is_refreshed) = {
-            let c = self.ensure_counter(did, counter).await;
-            let result = if let Ok(mut c) = c.lock() {
-                c.incr()
-            } else {
-                return;
-            };
-            result
+            let c = self.ensure_counter(did, counter).await?;
+            let mut c = c
+                .lock()
+                .map_err(|_| Error::Measure("counter lock is poisoned".to_string()))?;
+            c.incr(n)
         };
         if is_refreshed {
-            self.save_counter(did, counter, count).await;
+            self.save_counter(did, counter, count).await?;
         }
+        Ok(())
     }
 
     /// `get_count` returns the counter of a peer in the current or previous period.
-    async fn get_count(&self, did: Did, counter: MeasureCounter) -> u64 {
+    async fn get_count(&self, did: Did, counter: MeasureCounter) -> Result<u64> {
         let (count, is_refreshed) = {
-            let c = self.ensure_counter(did, counter).await;
-            let result = if let Ok(mut c) = c.lock() {
-                c.get()
-            } else {
-                return 0;
-            };
-            result
+            let c = self.ensure_counter(did, counter).await?;
+            let mut c = c
+                .lock()
+                .map_err(|_| Error::Measure("counter lock is poisoned".to_string()))?;
+            c.get()
         };
         if is_refreshed {
-            self.save_counter(did, counter, count).await;
+            self.save_counter(did, counter, count).await?;
         }
-        count
+        Ok(count)
     }
 }
 
@@ -203,24 +198,60 @@ mod tests {
         let did2 = Did::from_str("0x999999cf1046e68e36E1aA2E0E07105eDDD1f08E").unwrap();
 
         let measure = PeriodicMeasure::new(ms);
-        assert_eq!(measure.get_count(did1, MeasureCounter::Sent).await, 0);
-        assert_eq!(measure.get_count(did2, MeasureCounter::Sent).await, 0);
-        assert_eq!(measure.get_count(did1, MeasureCounter::Received).await, 0);
-        assert_eq!(measure.get_count(did2, MeasureCounter::Received).await, 0);
-
-        measure.incr(did1, MeasureCounter::Sent).await;
-        measure.incr(did1, MeasureCounter::Received).await;
-
-        measure.incr(did2, MeasureCounter::Sent).await;
-        measure.incr(did2, MeasureCounter::Sent).await;
-        measure.incr(did2, MeasureCounter::Received).await;
-        measure.incr(did2, MeasureCounter::Received).await;
-        measure.incr(did2, MeasureCounter::Received).await;
-
-        assert_eq!(measure.get_count(did1, MeasureCounter::Sent).await, 1);
-        assert_eq!(measure.get_count(did2, MeasureCounter::Sent).await, 2);
-        assert_eq!(measure.get_count(did1, MeasureCounter::Received).await, 1);
-        assert_eq!(measure.get_count(did2, MeasureCounter::Received).await, 3);
+        assert_eq!(
+            measure.get_count(did1, MeasureCounter::Sent).await.unwrap(),
+            0
+        );
+        assert_eq!(
+            measure.get_count(did2, MeasureCounter::Sent).await.unwrap(),
+            0
+        );
+        assert_eq!(
+            measure
+                .get_count(did1, MeasureCounter::Received)
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            measure
+                .get_count(did2, MeasureCounter::Received)
+                .await
+                .unwrap(),
+            0
+        );
+
+        measure.incr(did1, MeasureCounter::Sent).await.unwrap();
+        measure.incr(did1, MeasureCounter::Received).await.unwrap();
+
+        measure.incr(did2, MeasureCounter::Sent).await.unwrap();
+        measure.incr(did2, MeasureCounter::Sent).await.unwrap();
+        measure.incr(did2, MeasureCounter::Received).await.unwrap();
+        measure.incr(did2, MeasureCounter::Received).await.unwrap();
+        measure.incr(did2, MeasureCounter::Received).await.unwrap();
+
+        assert_eq!(
+            measure.get_count(did1, MeasureCounter::Sent).await.unwrap(),
+            1
+        );
+        assert_eq!(
+            measure.get_count(did2, MeasureCounter::Sent).await.unwrap(),
+            2
+        );
+        assert_eq!(
+            measure
+                .get_count(did1, MeasureCounter::Received)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            measure
+                .get_count(did2, MeasureCounter::Received)
+                .await
+                .unwrap(),
+            3
+        );
     }
 
     #[tokio::test]
@@ -230,39 +261,84 @@ mod tests {
         let did = Did::from_str("0x11E807fcc88dD319270493fB2e822e388Fe36ab0").unwrap();
 
         let measure = PeriodicMeasure::new(ms);
-        assert_eq!(measure.get_count(did, MeasureCounter::Sent).await, 0);
-        assert_eq!(measure.get_count(did, MeasureCounter::Received).await, 0);
+        assert_eq!(
+            measure.get_count(did, MeasureCounter::Sent).await.unwrap(),
+            0
+        );
+        assert_eq!(
+            measure
+                .get_count(did, MeasureCounter::Received)
+                .await
+                .unwrap(),
+            0
+        );
 
-        measure.incr(did, MeasureCounter::Sent).await;
-        measure.incr(did, MeasureCounter::Sent).await;
-        measure.incr(did, MeasureCounter::Received).await;
+        measure.incr(did, MeasureCounter::Sent).await.unwrap();
+        measure.incr(did, MeasureCounter::Sent).await.unwrap();
+        measure.incr(did, MeasureCounter::Received).await.unwrap();
 
         // Will take current count since previous count is 0.
-        assert_eq!(measure.get_count(did, MeasureCounter::Sent).await, 2);
-        assert_eq!(measure.get_count(did, MeasureCounter::Received).await, 1);
+        assert_eq!(
+            measure.get_count(did, MeasureCounter::Sent).await.unwrap(),
+            2
+        );
+        assert_eq!(
+            measure
+                .get_count(did, MeasureCounter::Received)
+                .await
+                .unwrap(),
+            1
+        );
 
         tokio::time::sleep(std::time::Duration::from_secs(DURATION)).await;
 
-        measure.incr(did, MeasureCounter::Sent).await;
-        measure.incr(did, MeasureCounter::Received).await;
-        measure.incr(did, MeasureCounter::Received).await;
-        measure.incr(did, MeasureCounter::Received).await;
+        measure.incr(did, MeasureCounter::Sent).await.unwrap();
+        measure.incr(did, MeasureCounter::Received).await.unwrap();
+        measure.incr(did, MeasureCounter::Received).await.unwrap();
+        measure.incr(did, MeasureCounter::Received).await.unwrap();
 
         // Will take previous count.
-        assert_eq!(measure.get_count(did, MeasureCounter::Sent).await, 2);
-        assert_eq!(measure.get_count(did, MeasureCounter::Received).await, 1);
+        assert_eq!(
+            measure.get_count(did, MeasureCounter::Sent).await.unwrap(),
+            2
+        );
+        assert_eq!(
+            measure
+                .get_count(did, MeasureCounter::Received)
+                .await
+                .unwrap(),
+            1
+        );
 
         tokio::time::sleep(std::time::Duration::from_secs(DURATION)).await;
 
         // Will take previous count.
-        assert_eq!(measure.get_count(did, MeasureCounter::Sent).await, 1);
-        assert_eq!(measure.get_count(did, MeasureCounter::Received).await, 3);
+        assert_eq!(
+            measure.get_count(did, MeasureCounter::Sent).await.unwrap(),
+            1
+        );
+        assert_eq!(
+            measure
+                .get_count(did, MeasureCounter::Received)
+                .await
+                .unwrap(),
+            3
+        );
 
         tokio::time::sleep(std::time::Duration::from_secs(DURATION)).await;
 
         // Will take previous count.
-        assert_eq!(measure.get_count(did, MeasureCounter::Sent).await, 0);
-        assert_eq!(measure.get_count(did, MeasureCounter::Received).await, 0);
+        assert_eq!(
+            measure.get_count(did, MeasureCounter::Sent).await.unwrap(),
+            0
+        );
+        assert_eq!(
+            measure
+                .get_count(did, MeasureCounter::Received)
+                .await
+                .unwrap(),
+            0
+        );
     }
 
     #[tokio::test]
@@ -276,19 +352,31 @@ mod tests {
 
         let did = Did::from_str("0x11E807fcc88dD319270493fB2e822e388Fe36ab0").unwrap();
         let measure = PeriodicMeasure::new(ms);
-        assert_eq!(measure.get_count(did, MeasureCounter::Sent).await, 0);
-        assert_eq!(measure.get_count(did, MeasureCounter::Received).await, 0);
+        assert_eq!(
+            measure.get_count(did, MeasureCounter::Sent).await.unwrap(),
+            0
+        );
+        assert_eq!(
+            measure
+                .get_count(did, MeasureCounter::Received)
+                .await
+                .unwrap(),
+            0
+        );
 
-        measure.incr(did, MeasureCounter::Sent).await;
-        measure.incr(did, MeasureCounter::Sent).await;
-        measure.incr(did, MeasureCounter::Received).await;
+        measure.incr(did, MeasureCounter::Sent).await.unwrap();
+        measure.incr(did, MeasureCounter::Sent).await.unwrap();
+        measure.incr(did, MeasureCounter::Received).await.unwrap();
 
         tokio::time::sleep(std::time::Duration::from_secs(DURATION)).await;
 
         // Flush to storage.
-        let c1 = measure.get_count(did, MeasureCounter::Sent).await;
+        let c1 = measure.get_count(did, MeasureCounter::Sent).await.unwrap();
         assert_eq!(c1, 2);
-        let c2 = measure.get_count(did, MeasureCounter::Received).await;
+        let c2 = measure
+            .get_count(did, MeasureCounter::Received)
+            .await
+            .unwrap();
         assert_eq!(c2, 1);
 
         // Release lock of measure storage.
@@ -303,7 +391,16 @@ mod tests {
         let measure2 = PeriodicMeasure::new(ms2);
 
         // Will take previous count from storage.
-        assert_eq!(measure2.get_count(did, MeasureCounter::Sent).await, 2);
-        assert_eq!(measure2.get_count(did, MeasureCounter::Received).await, 1);
+        assert_eq!(
+            measure2.get_count(did, MeasureCounter::Sent).await.unwrap(),
+            2
+        );
+        assert_eq!(
+            measure2
+                .get_count(did, MeasureCounter::Received)
+                .await
+                .unwrap(),
+            1
+        );
     }
 }
@@ -24,6 +24,7 @@ use futures::select;
 use futures::FutureExt;
 use futures::Stream;
 use futures_timer::Delay;
+use rings_core::ecc::SecretKey;
 use rings_rpc::jsonrpc::Client as RpcClient;
 use rings_rpc::protos::rings_node::*;
 
@@ -55,6 +56,13 @@ impl Client {
         Ok(Self { client: rpc_client })
     }
 
+    /// Creates a new Client instance which signs requests by `key`,
+    /// for nodes only allowing authorized requests.
+    pub fn new_with_signer(endpoint_url: &str, key: SecretKey) -> anyhow::Result<Self> {
+        let rpc_client = RpcClient::new_with_signer(endpoint_url, key);
+        Ok(Self { client: rpc_client })
+    }
+
     /// Establishes a WebRTC connection with a remote peer using HTTP as the signaling channel.
     ///
     /// This function allows two peers to establish a WebRTC connection using HTTP,
@@ -11,7 +11,9 @@ use crate::backend::native::service::ServiceConfig;
 use crate::backend::native::BackendConfig;
 use crate::error::Error;
 use crate::error::Result;
+use crate::prelude::rings_core::dht::Did;
 use crate::prelude::rings_core::ecc::SecretKey;
+use crate::prelude::rings_core::swarm::SwarmConfig;
 use crate::prelude::SessionSk;
 use crate::processor::ProcessorConfig;
 use crate::processor::ProcessorConfigSerialized;
@@ -47,6 +49,25 @@ where P: AsRef<std::path::Path> {
     expect.to_str().unwrap().to_string()
 }
 
+/// Deserialize a single value or a list of values into a list.
+fn one_or_many<'de, D, T>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        One(T),
+        Many(Vec<T>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(v) => vec![v],
+        OneOrMany::Many(v) => v,
+    })
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Config {
     pub network_id: u32,
@@ -56,7 +77,9 @@ pub struct Config {
     pub session_manager: Option<String>,
     pub session_sk: Option<String>,
     pub internal_api_port: u16,
-    pub external_api_addr: String,
+    /// Listen addresses of external api, which can be a single address or a list.
+    #[serde(deserialize_with = "one_or_many")]
+    pub external_api_addr: Vec<String>,
     pub endpoint_url: String,
     pub ice_servers: String,
     pub stabilize_interval: u64,
@@ -72,6 +95,18 @@ pub struct Config {
     /// its deserialization is equivalent to `ExtensionConfig(vec![])` in Rust.
     #[serde(default)]
     pub extension: ExtensionConfig,
+    /// A bootstrap seed is the node others connect to first.
+    /// It's reported ready by `/health` before having any connection.
+    #[serde(default)]
+    pub bootstrap_seed: bool,
+    /// Dids allowed to call the internal api, including websockets, by signing requests.
+    /// Any request is allowed if it's empty.
+    #[serde(default)]
+    pub rpc_authorized_dids: Vec<Did>,
+    /// Other options of swarm, such as `dht_succ_max` and `idle_timeout_secs`.
+    /// Options that are not set keep the defaults of swarm.
+    #[serde(default)]
+    pub swarm: SwarmConfig,
 }
 
 impl TryFrom<Config> for ProcessorConfigSerialized {
@@ -96,12 +131,14 @@ impl TryFrom<Config> for ProcessorConfigSerialized {
             })
         };
 
+        config.swarm.validate()?;
         let mut cs = Self::new(
             config.network_id,
             config.ice_servers,
             session_sk,
             config.stabilize_interval,
-        );
+        )
+        .swarm(config.swarm);
 
         cs = if let Some(ext_ip) = config.external_ip {
             cs.external_address(ext_ip)
@@ -139,7 +176,7 @@ impl Config {
             session_manager: None,
             session_sk: Some(session_sk),
             internal_api_port: DEFAULT_INTERNAL_API_PORT,
-            external_api_addr: DEFAULT_EXTERNAL_API_ADDR.to_string(),
+            external_api_addr: vec![DEFAULT_EXTERNAL_API_ADDR.to_string()],
             endpoint_url: DEFAULT_ENDPOINT_URL.to_string(),
             ice_servers: DEFAULT_ICE_SERVERS.to_string(),
             stabilize_interval: DEFAULT_STABILIZE_INTERVAL,
@@ -148,6 +185,9 @@ impl Config {
             data_storage: DEFAULT_DATA_STORAGE_CONFIG.clone(),
             measure_storage: DEFAULT_MEASURE_STORAGE_CONFIG.clone(),
             extension: ExtensionConfig::default(),
+            bootstrap_seed: false,
+            rpc_authorized_dids: vec![],
+            swarm: SwarmConfig::default(),
         }
     }
 
@@ -168,7 +208,7 @@ impl Config {
         tracing::debug!("Read config from: {:?}", path);
         let f = fs::File::open(path).map_err(|e| Error::OpenFileError(e.to_string()))?;
         let f_rdr = io::BufReader::new(f);
-        serde_yaml::from_reader(f_rdr).map_err(|_| Error::EncodeError)
+        serde_yaml::from_reader(f_rdr).map_err(Error::SerdeYamlError)
     }
 }
 
@@ -212,5 +252,23 @@ measure_storage:
         let cfg: Config = serde_yaml::from_str(yaml).unwrap();
         assert_eq!(cfg.extension, ExtensionConfig::default());
         assert_eq!(cfg.services, vec![]);
+        assert_eq!(cfg.external_api_addr, vec!["127.0.0.1:50001".to_string()]);
+    }
+
+    #[test]
+    fn test_deserialization_of_multiple_external_api_addrs() {
+        let mut cfg = serde_yaml::to_value(Config::new("session_sk")).unwrap();
+        cfg["external_api_addr"] =
+            serde_yaml::from_str("[127.0.0.1:50001, '[::1]:50001']").unwrap();
+        let cfg: Config = serde_yaml::from_value(cfg).unwrap();
+        assert_eq!(cfg.external_api_addr, vec![
+            "127.0.0.1:50001".to_string(),
+            "[::1]:50001".to_string()
+        ]);
+
+        // Written config can be read again.
+        let yaml = serde_yaml::to_string(&cfg).unwrap();
+        let cfg: Config = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(cfg.external_api_addr.len(), 2);
     }
 }
@@ -0,0 +1,221 @@
+//! Authentication of requests to internal api by signature, see [rings_rpc::auth].
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::http::Request;
+use axum::middleware::Next;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use rings_core::dht::Did;
+use rings_core::utils::get_epoch_ms;
+use rings_rpc::auth::recover_signer;
+use rings_rpc::auth::MAX_TIMESTAMP_SKEW_MS;
+use rings_rpc::auth::SIGNATURE_HEADER;
+use rings_rpc::auth::TIMESTAMP_HEADER;
+
+use super::http_error::HttpError;
+use crate::error::Error;
+use crate::error::Result;
+
+/// Authenticator of requests signed by authorized Dids.
+pub(crate) struct RpcAuth {
+    authorized_dids: Vec<Did>,
+    /// Signatures of accepted requests with their timestamps, kept while the timestamps
+    /// are within [MAX_TIMESTAMP_SKEW_MS], so that a request can't be replayed meanwhile.
+    seen: Mutex<HashMap<String, u128>>,
+}
+
+impl RpcAuth {
+    /// Any request is allowed if `authorized_dids` is empty.
+    pub(crate) fn new(authorized_dids: Vec<Did>) -> Self {
+        Self {
+            authorized_dids,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.authorized_dids.is_empty()
+    }
+
+    /// Check that the request is signed by one of authorized Dids at a time close to `now_ms`,
+    /// and it's not seen before.
+    /// Return [Error::NoPermission] if the signature is missing, invalid, made by others,
+    /// expired or replayed.
+    pub(crate) fn check(
+        &self,
+        now_ms: u128,
+        method: &str,
+        path: &str,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<()> {
+        if self.is_open() {
+            return Ok(());
+        }
+
+        let (Some(signature), Some(timestamp)) =
+            (headers.get(SIGNATURE_HEADER), headers.get(TIMESTAMP_HEADER))
+        else {
+            tracing::warn!("Reject unsigned rpc request");
+            return Err(Error::NoPermission);
+        };
+        let signature = signature.to_str().map_err(|_| Error::InvalidHeaders)?;
+        let timestamp_ms = timestamp
+            .to_str()
+            .ok()
+            .and_then(|ts| ts.parse::<u128>().ok())
+            .ok_or(Error::InvalidHeaders)?;
+        if now_ms.abs_diff(timestamp_ms) > MAX_TIMESTAMP_SKEW_MS {
+            tracing::warn!("Reject rpc request signed at {timestamp_ms}, now is {now_ms}");
+            return Err(Error::NoPermission);
+        }
+
+        let signer = recover_signer(timestamp_ms, method, path, body, signature).map_err(|e| {
+            tracing::warn!("Reject rpc request with invalid signature: {e:?}");
+            Error::NoPermission
+        })?;
+        if !self.authorized_dids.contains(&signer) {
+            tracing::warn!("Reject rpc request signed by unauthorized {signer}");
+            return Err(Error::NoPermission);
+        }
+
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        seen.retain(|_, ts| now_ms.abs_diff(*ts) <= MAX_TIMESTAMP_SKEW_MS);
+        if seen.insert(signature.to_string(), timestamp_ms).is_some() {
+            tracing::warn!("Reject replayed rpc request signed by {signer}");
+            return Err(Error::NoPermission);
+        }
+        Ok(())
+    }
+}
+
+/// Middleware rejecting requests not authorized by [RpcAuth], for all routes of internal api.
+pub(crate) async fn rpc_auth_middleware(
+    State(auth): State<Arc<RpcAuth>>,
+    req: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    if auth.is_open() {
+        return next.run(req).await;
+    }
+    let (parts, body) = req.into_parts();
+    let Ok(body) = hyper::body::to_bytes(body).await else {
+        return HttpError::BadRequest.into_response();
+    };
+    let checked = auth.check(
+        get_epoch_ms(),
+        parts.method.as_str(),
+        parts.uri.path(),
+        &parts.headers,
+        &body,
+    );
+    if checked.is_err() {
+        return HttpError::Unauthorized.into_response();
+    }
+    next.run(Request::from_parts(parts, Body::from(body))).await
+}
+
+#[cfg(test)]
+mod test {
+    use axum::http::HeaderValue;
+    use rings_core::ecc::SecretKey;
+    use rings_rpc::auth::sign_request;
+
+    use super::*;
+
+    const BODY: &str = r#"{"jsonrpc":"2.0","method":"nodeDid","params":{},"id":1}"#;
+    const NOW: u128 = 1_700_000_000_000;
+
+    fn signed_headers(key: &SecretKey, timestamp_ms: u128, path: &str, body: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let sig = sign_request(key, timestamp_ms, "POST", path, body.as_bytes());
+        headers.insert(SIGNATURE_HEADER, HeaderValue::from_str(&sig).unwrap());
+        headers.insert(
+            TIMESTAMP_HEADER,
+            HeaderValue::from_str(&timestamp_ms.to_string()).unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn test_rpc_auth_signed() {
+        let key = SecretKey::random();
+        let auth = RpcAuth::new(vec![key.address().into()]);
+
+        let headers = signed_headers(&key, NOW, "/", BODY);
+        assert!(auth
+            .check(NOW, "POST", "/", &headers, BODY.as_bytes())
+            .is_ok());
+
+        // The signature is bound to the body and the path.
+        let headers = signed_headers(&key, NOW + 1, "/", BODY);
+        let other_body = BODY.replace("nodeDid", "disconnect");
+        assert!(matches!(
+            auth.check(NOW, "POST", "/", &headers, other_body.as_bytes()),
+            Err(Error::NoPermission)
+        ));
+        assert!(matches!(
+            auth.check(NOW, "POST", "/ws", &headers, BODY.as_bytes()),
+            Err(Error::NoPermission)
+        ));
+    }
+
+    #[test]
+    fn test_rpc_auth_unsigned() {
+        let auth = RpcAuth::new(vec![SecretKey::random().address().into()]);
+        assert!(matches!(
+            auth.check(NOW, "POST", "/", &HeaderMap::new(), BODY.as_bytes()),
+            Err(Error::NoPermission)
+        ));
+
+        // Auth is disabled without authorized dids.
+        let auth = RpcAuth::new(vec![]);
+        assert!(auth
+            .check(NOW, "POST", "/", &HeaderMap::new(), BODY.as_bytes())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_rpc_auth_unauthorized_key() {
+        let auth = RpcAuth::new(vec![SecretKey::random().address().into()]);
+        let stranger = SecretKey::random();
+        let headers = signed_headers(&stranger, NOW, "/", BODY);
+        assert!(matches!(
+            auth.check(NOW, "POST", "/", &headers, BODY.as_bytes()),
+            Err(Error::NoPermission)
+        ));
+    }
+
+    #[test]
+    fn test_rpc_auth_replay() {
+        let key = SecretKey::random();
+        let auth = RpcAuth::new(vec![key.address().into()]);
+
+        let headers = signed_headers(&key, NOW, "/", BODY);
+        assert!(auth
+            .check(NOW, "POST", "/", &headers, BODY.as_bytes())
+            .is_ok());
+        assert!(matches!(
+            auth.check(NOW + 1, "POST", "/", &headers, BODY.as_bytes()),
+            Err(Error::NoPermission)
+        ));
+
+        // A request signed too long ago or ahead is rejected.
+        let expired = signed_headers(&key, NOW - MAX_TIMESTAMP_SKEW_MS - 1, "/", BODY);
+        assert!(matches!(
+            auth.check(NOW, "POST", "/", &expired, BODY.as_bytes()),
+            Err(Error::NoPermission)
+        ));
+        let ahead = signed_headers(&key, NOW + MAX_TIMESTAMP_SKEW_MS + 1, "/", BODY);
+        assert!(matches!(
+            auth.check(NOW, "POST", "/", &ahead, BODY.as_bytes()),
+            Err(Error::NoPermission)
+        ));
+    }
+}
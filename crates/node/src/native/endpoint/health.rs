@@ -0,0 +1,102 @@
+//! Health check of node, used by liveness and readiness probes.
+
+use std::time::Duration;
+
+use rings_core::dht::SuccessorReader;
+use rings_core::swarm::NatType;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::processor::Processor;
+
+/// Response of `/health`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthResponse {
+    /// The node has at least one connected peer, or it's a bootstrap seed.
+    pub ready: bool,
+    /// Did of the node.
+    pub did: String,
+    /// Number of connected peers.
+    pub connected_peers: usize,
+    /// Number of successors in DHT.
+    pub dht_successors: usize,
+    /// Number of connections still being established, such as offers not answered yet.
+    pub pending_offers: usize,
+    /// NAT type of the node, `unknown` if it's not detected, see
+    /// [rings_core::swarm::SwarmConfig::detect_nat].
+    pub nat_type: NatType,
+}
+
+/// Check health of node. A bootstrap seed is ready without any connection, since other
+/// nodes connect to it first.
+/// It only reads counters of swarm, and never waits for remote peers.
+pub(crate) fn node_health(processor: &Processor, bootstrap_seed: bool) -> HealthResponse {
+    let swarm = &processor.swarm;
+    let connected_peers = swarm.connected_peers().len();
+    let dht_successors = swarm.dht().successors().len().unwrap_or_else(|e| {
+        tracing::warn!("Failed to read successors: {e:?}");
+        0
+    });
+    let pending_offers = swarm.pending_connections_older_than(Duration::ZERO).len();
+
+    HealthResponse {
+        ready: bootstrap_seed || connected_peers > 0,
+        did: swarm.did().to_string(),
+        connected_peers,
+        dht_successors,
+        pending_offers,
+        nat_type: swarm.nat_type(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rings_rpc::protos::rings_node::AcceptAnswerRequest;
+    use rings_rpc::protos::rings_node::AnswerOfferRequest;
+    use rings_rpc::protos::rings_node::CreateOfferRequest;
+    use rings_rpc::protos::rings_node_handler::HandleRpc;
+
+    use super::*;
+    use crate::tests::native::prepare_processor;
+
+    #[tokio::test]
+    async fn test_health_ready_after_connected() {
+        let p1 = prepare_processor().await;
+        let p2 = prepare_processor().await;
+
+        let health = node_health(&p1, false);
+        assert!(!health.ready);
+        assert_eq!(health.did, p1.did().to_string());
+        assert_eq!(health.connected_peers, 0);
+        assert_eq!(health.nat_type, NatType::Unknown);
+        assert!(node_health(&p1, true).ready);
+
+        let offer = p1
+            .handle_rpc(CreateOfferRequest {
+                did: p2.did().to_string(),
+            })
+            .await
+            .unwrap()
+            .offer;
+        assert_eq!(node_health(&p1, false).pending_offers, 1);
+
+        let answer = p2
+            .handle_rpc(AnswerOfferRequest { offer })
+            .await
+            .unwrap()
+            .answer;
+        p1.handle_rpc(AcceptAnswerRequest { answer }).await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while !node_health(&p1, false).ready {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await
+        .expect("node should be ready once connected");
+
+        let health = node_health(&p1, false);
+        assert_eq!(health.connected_peers, 1);
+        assert_eq!(health.pending_offers, 0);
+    }
+}
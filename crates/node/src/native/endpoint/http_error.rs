@@ -5,6 +5,7 @@ use axum::response::Response;
 #[derive(Debug)]
 pub enum HttpError {
     BadRequest,
+    Unauthorized,
     Internal,
 }
 
@@ -12,6 +13,7 @@ impl IntoResponse for HttpError {
     fn into_response(self) -> Response {
         let (code, msg) = match self {
             HttpError::BadRequest => (StatusCode::BAD_REQUEST, "Bad Request"),
+            HttpError::Unauthorized => (StatusCode::UNAUTHORIZED, "Unauthorized"),
             HttpError::Internal => (StatusCode::INTERNAL_SERVER_ERROR, "Internal error"),
         };
 
@@ -1,11 +1,15 @@
 //! rings-node service run with `Swarm` and chord stabilization.
 #![warn(missing_docs)]
+mod auth;
+mod health;
 mod http_error;
 mod ws;
 
 use std::net::SocketAddr;
+use std::net::TcpListener;
 use std::sync::Arc;
 
+use anyhow::Context;
 use axum::extract::ConnectInfo;
 use axum::extract::State;
 use axum::extract::WebSocketUpgrade;
@@ -14,10 +18,15 @@ use axum::routing::get;
 use axum::routing::post;
 use axum::Router;
 use jsonrpc_core::MetaIoHandler;
+use rings_core::dht::Did;
 use rings_rpc::protos::rings_node::NodeInfoResponse;
 use tower_http::cors::CorsLayer;
 
+use self::auth::rpc_auth_middleware;
+use self::auth::RpcAuth;
+pub use self::health::HealthResponse;
 use self::http_error::HttpError;
+pub use self::ws::SignalingFrame;
 use crate::processor::Processor;
 
 /// JSON-RPC state
@@ -31,7 +40,6 @@ where M: jsonrpc_core::Middleware<Arc<Processor>>
 
 /// websocket state
 #[derive(Clone)]
-#[allow(dead_code)]
 pub struct WsState {
     processor: Arc<Processor>,
 }
@@ -40,13 +48,22 @@ pub struct WsState {
 #[derive(Clone)]
 pub struct StatusState {
     processor: Arc<Processor>,
+    bootstrap_seed: bool,
 }
 
 struct ExternalRpcMiddleware;
 struct InternalRpcMiddleware;
 
-/// Run a web server to handle jsonrpc request locally
-pub async fn run_internal_api(port: u16, processor: Arc<Processor>) -> anyhow::Result<()> {
+/// Run a web server to handle jsonrpc request locally.
+/// A `bootstrap_seed` node is reported ready by `/health` without any connection.
+/// If `authorized_dids` is not empty, only requests signed by them are handled on all routes,
+/// see [rings_rpc::auth].
+pub async fn run_internal_api(
+    port: u16,
+    processor: Arc<Processor>,
+    bootstrap_seed: bool,
+    authorized_dids: Vec<Did>,
+) -> anyhow::Result<()> {
     let binding_addr = SocketAddr::from(([127, 0, 0, 1], port));
 
     let jsonrpc_handler = MetaIoHandler::with_middleware(InternalRpcMiddleware);
@@ -54,58 +71,125 @@ pub async fn run_internal_api(port: u16, processor: Arc<Processor>) -> anyhow::R
         processor: processor.clone(),
         io_handler: jsonrpc_handler,
     });
+    let auth = Arc::new(RpcAuth::new(authorized_dids));
 
     let ws_state = Arc::new(WsState {
         processor: processor.clone(),
     });
 
-    let status_state = Arc::new(StatusState { processor });
+    let status_state = Arc::new(StatusState {
+        processor,
+        bootstrap_seed,
+    });
 
     let axum_make_service = Router::new()
         .route(
             "/",
             post(jsonrpc_io_handler).with_state(jsonrpc_state.clone()),
         )
-        .route("/ws", get(ws_handler).with_state(ws_state))
-        .route("/status", get(status_handler).with_state(status_state))
+        .route("/ws", get(ws_handler).with_state(ws_state.clone()))
+        .route("/signaling", get(signaling_handler).with_state(ws_state))
+        .route(
+            "/status",
+            get(status_handler).with_state(status_state.clone()),
+        )
+        .route("/health", get(health_handler).with_state(status_state))
+        .layer(axum::middleware::from_fn_with_state(
+            auth,
+            rpc_auth_middleware,
+        ))
         .layer(CorsLayer::permissive())
         .layer(axum::middleware::from_fn(node_info_header))
         .into_make_service_with_connect_info::<SocketAddr>();
 
     println!("JSON-RPC endpoint: http://{}", binding_addr);
     println!("WebSocket endpoint: http://{}/ws", binding_addr);
+    println!("Signaling endpoint: http://{}/signaling", binding_addr);
     axum::Server::bind(&binding_addr)
         .serve(axum_make_service)
         .await?;
     Ok(())
 }
 
-/// Run a web server to handle jsonrpc request from external
-pub async fn run_external_api(addr: String, processor: Arc<Processor>) -> anyhow::Result<()> {
-    let binding_addr = addr.parse().unwrap();
+/// Run a web server to handle jsonrpc request from external on each of `addrs`.
+/// All of them are bound before serving, which fails with the first address that cannot be bound.
+/// A `bootstrap_seed` node is reported ready by `/health` without any connection.
+pub async fn run_external_api(
+    addrs: Vec<String>,
+    processor: Arc<Processor>,
+    bootstrap_seed: bool,
+) -> anyhow::Result<()> {
+    let listeners = bind_external_api(&addrs)?;
+    for addr in addrs.iter() {
+        println!("JSON-RPC endpoint: http://{}", addr);
+        println!("Signaling endpoint: http://{}/signaling", addr);
+    }
+    serve_external_api(listeners, processor, bootstrap_seed).await
+}
+
+/// Bind a listener on each of `addrs`, reporting the address which fails.
+fn bind_external_api(addrs: &[String]) -> anyhow::Result<Vec<TcpListener>> {
+    if addrs.is_empty() {
+        anyhow::bail!("No external api address is provided");
+    }
+    addrs
+        .iter()
+        .map(|addr| {
+            let binding_addr: SocketAddr = addr
+                .parse()
+                .with_context(|| format!("Invalid external api address {addr}"))?;
+            TcpListener::bind(binding_addr)
+                .with_context(|| format!("Failed to bind external api address {addr}"))
+        })
+        .collect()
+}
 
+/// Serve external api on all `listeners`, sharing the same `processor`.
+async fn serve_external_api(
+    listeners: Vec<TcpListener>,
+    processor: Arc<Processor>,
+    bootstrap_seed: bool,
+) -> anyhow::Result<()> {
     let jsonrpc_handler = MetaIoHandler::with_middleware(ExternalRpcMiddleware);
+    // External api is open to other nodes for handshake.
     let jsonrpc_state = Arc::new(JsonRpcState {
         processor: processor.clone(),
         io_handler: jsonrpc_handler,
     });
 
-    let status_state = Arc::new(StatusState { processor });
+    let ws_state = Arc::new(WsState {
+        processor: processor.clone(),
+    });
 
-    let axum_make_service = Router::new()
+    let status_state = Arc::new(StatusState {
+        processor,
+        bootstrap_seed,
+    });
+
+    let router = Router::new()
         .route(
             "/",
             post(jsonrpc_io_handler).with_state(jsonrpc_state.clone()),
         )
-        .route("/status", get(status_handler).with_state(status_state))
+        .route("/signaling", get(signaling_handler).with_state(ws_state))
+        .route(
+            "/status",
+            get(status_handler).with_state(status_state.clone()),
+        )
+        .route("/health", get(health_handler).with_state(status_state))
         .layer(CorsLayer::permissive())
-        .layer(axum::middleware::from_fn(node_info_header))
-        .into_make_service_with_connect_info::<SocketAddr>();
-
-    println!("JSON-RPC endpoint: http://{}", addr);
-    axum::Server::bind(&binding_addr)
-        .serve(axum_make_service)
-        .await?;
+        .layer(axum::middleware::from_fn(node_info_header));
+
+    let servers = listeners
+        .into_iter()
+        .map(|listener| {
+            let axum_make_service = router
+                .clone()
+                .into_make_service_with_connect_info::<SocketAddr>();
+            Ok(axum::Server::from_tcp(listener)?.serve(axum_make_service))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    futures::future::try_join_all(servers).await?;
     Ok(())
 }
 
@@ -148,6 +232,13 @@ async fn status_handler(
     Ok(axum::Json(info))
 }
 
+async fn health_handler(State(state): State<Arc<StatusState>>) -> axum::Json<HealthResponse> {
+    axum::Json(self::health::node_health(
+        &state.processor,
+        state.bootstrap_seed,
+    ))
+}
+
 /// JSON response struct
 #[derive(Debug, Clone)]
 pub struct JsonResponse(String);
@@ -167,6 +258,15 @@ async fn ws_handler(
     ws.on_upgrade(move |socket| self::ws::handle_socket(state, socket))
 }
 
+async fn signaling_handler(
+    State(state): State<Arc<WsState>>,
+    ws: WebSocketUpgrade,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+) -> impl IntoResponse {
+    tracing::info!("signaling ws connected, remote: {}", addr);
+    ws.on_upgrade(move |socket| self::ws::handle_signaling_socket(state, socket))
+}
+
 mod jsonrpc_middleware_impl {
     use std::future::Future;
 
@@ -237,3 +337,80 @@ mod jsonrpc_middleware_impl {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use rings_rpc::protos::rings_node::AnswerOfferRequest;
+    use rings_rpc::protos::rings_node::CreateOfferRequest;
+    use rings_rpc::protos::rings_node_handler::HandleRpc;
+
+    use super::*;
+    use crate::tests::native::prepare_processor;
+
+    #[tokio::test]
+    async fn test_external_api_on_multiple_addrs() {
+        let server = Arc::new(prepare_processor().await);
+        let listeners =
+            bind_external_api(&["127.0.0.1:0".to_string(), "127.0.0.1:0".to_string()]).unwrap();
+        let addrs = listeners
+            .iter()
+            .map(|l| l.local_addr().unwrap())
+            .collect::<Vec<_>>();
+        tokio::spawn(serve_external_api(listeners, server.clone(), false));
+
+        for addr in addrs {
+            let client = prepare_processor().await;
+            let offer = client
+                .handle_rpc(CreateOfferRequest {
+                    did: server.did().to_string(),
+                })
+                .await
+                .unwrap()
+                .offer;
+            let answer = rings_rpc::jsonrpc::Client::new(&format!("http://{addr}"))
+                .answer_offer(&AnswerOfferRequest { offer })
+                .await
+                .unwrap()
+                .answer;
+            assert!(!answer.is_empty(), "{addr} should answer offer");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_describe_methods() {
+        use rings_rpc::describe::MethodDescription;
+        use rings_rpc::protos::rings_node_handler::InternalRpcHandler;
+
+        let processor = Arc::new(prepare_processor().await);
+        let result = InternalRpcHandler
+            .handle_request(processor, "describe".to_string(), serde_json::json!({}))
+            .await
+            .unwrap();
+        let methods: Vec<MethodDescription> = serde_json::from_value(result).unwrap();
+
+        let send = methods
+            .iter()
+            .find(|m| m.method == "sendBackendMessage")
+            .expect("sendBackendMessage should be described");
+        assert_eq!(send.params_schema["type"], "object");
+        assert_eq!(
+            send.params_schema["properties"]["destination_did"]["type"],
+            "string"
+        );
+        assert_eq!(send.params_schema["properties"]["data"]["type"], "string");
+        assert!(methods.iter().any(|m| m.method == "nodeInfo"));
+        assert!(!methods.iter().any(|m| m.method == "describe"));
+    }
+
+    #[test]
+    fn test_bind_external_api_reports_failed_addr() {
+        let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+        let taken_addr = taken.local_addr().unwrap().to_string();
+
+        let err = bind_external_api(&["127.0.0.1:0".to_string(), taken_addr.clone()]).unwrap_err();
+        assert!(err.to_string().contains(&taken_addr), "{err}");
+
+        let err = bind_external_api(&["not an addr".to_string()]).unwrap_err();
+        assert!(err.to_string().contains("not an addr"), "{err}");
+    }
+}
@@ -1,9 +1,15 @@
 use std::sync::Arc;
 
+use axum::extract::ws::Message;
 use axum::extract::ws::WebSocket;
 use futures::StreamExt;
+use rings_rpc::protos::rings_node::AnswerOfferRequest;
+use rings_rpc::protos::rings_node_handler::HandleRpc;
+use serde::Deserialize;
+use serde::Serialize;
 
 use super::WsState;
+use crate::processor::Processor;
 
 /// Actual websocket statemachine (one will be spawned per connection)
 pub async fn handle_socket(_ws_state: Arc<WsState>, socket: WebSocket) {
@@ -42,3 +48,138 @@ pub async fn handle_socket(_ws_state: Arc<WsState>, socket: WebSocket) {
     }
     tracing::info!("WS over");
 }
+
+/// Frames exchanged on the signaling websocket, serialized as json text.
+///
+/// A client sends [SignalingFrame::Offer] with an encoded payload of `ConnectNodeSend`,
+/// and receives [SignalingFrame::Answer] with an encoded payload of `ConnectNodeReport`
+/// on the same socket.
+///
+/// Only offers are accepted from clients. An answer can only complete a handshake started
+/// by the node, and anyone reaching the endpoint could inject one into a handshake it never
+/// took part in, so answers go through the internal jsonrpc `acceptAnswer` instead.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SignalingFrame {
+    /// Encoded offer payload, same as `offer` of `AnswerOfferRequest`.
+    Offer {
+        /// Encoded payload of `ConnectNodeSend`.
+        offer: String,
+    },
+    /// Encoded answer payload, same as `answer` of `AcceptAnswerRequest`.
+    Answer {
+        /// Encoded payload of `ConnectNodeReport`.
+        answer: String,
+    },
+    /// Failed to handle the received frame.
+    Error {
+        /// Reason of failure.
+        reason: String,
+    },
+}
+
+/// Handle a frame received from signaling websocket, return the frame to reply.
+pub(crate) async fn handle_signaling_frame(
+    processor: &Processor,
+    frame: SignalingFrame,
+) -> SignalingFrame {
+    let result = match frame {
+        SignalingFrame::Offer { offer } => processor
+            .handle_rpc(AnswerOfferRequest { offer })
+            .await
+            .map(|resp| SignalingFrame::Answer {
+                answer: resp.answer,
+            }),
+        frame => {
+            return SignalingFrame::Error {
+                reason: format!("Unexpected frame: {frame:?}"),
+            }
+        }
+    };
+    result.unwrap_or_else(|e| SignalingFrame::Error { reason: e.message })
+}
+
+/// Signaling websocket statemachine (one will be spawned per connection).
+/// Frames of a socket are handled one by one, so that the negotiations of a socket never
+/// run concurrently.
+pub async fn handle_signaling_socket(ws_state: Arc<WsState>, mut socket: WebSocket) {
+    while let Some(Ok(msg)) = socket.recv().await {
+        let reply = match msg {
+            Message::Text(text) => match serde_json::from_str(&text) {
+                Ok(frame) => handle_signaling_frame(&ws_state.processor, frame).await,
+                Err(e) => SignalingFrame::Error {
+                    reason: format!("Invalid frame: {e}"),
+                },
+            },
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        let Ok(reply) = serde_json::to_string(&reply) else {
+            tracing::error!("Failed to serialize signaling frame: {:?}", reply);
+            continue;
+        };
+        if let Err(e) = socket.send(Message::Text(reply)).await {
+            tracing::warn!("Failed to send signaling frame: {:?}", e);
+            break;
+        }
+    }
+    tracing::info!("Signaling WS over");
+}
+
+#[cfg(test)]
+mod test {
+    use rings_rpc::protos::rings_node::CreateOfferRequest;
+
+    use super::*;
+    use crate::tests::native::prepare_processor;
+
+    #[tokio::test]
+    async fn test_signaling_handshake() {
+        let p1 = prepare_processor().await;
+        let p2 = prepare_processor().await;
+
+        let offer = p1
+            .handle_rpc(CreateOfferRequest {
+                did: p2.did().to_string(),
+            })
+            .await
+            .unwrap()
+            .offer;
+
+        let SignalingFrame::Answer { answer } =
+            handle_signaling_frame(&p2, SignalingFrame::Offer { offer }).await
+        else {
+            panic!("Offer should be answered");
+        };
+
+        // Answers are never accepted from external signaling.
+        assert!(matches!(
+            handle_signaling_frame(&p1, SignalingFrame::Answer { answer }).await,
+            SignalingFrame::Error { .. }
+        ));
+        assert!(matches!(
+            handle_signaling_frame(&p1, SignalingFrame::Offer {
+                offer: "".to_string()
+            })
+            .await,
+            SignalingFrame::Error { .. }
+        ));
+    }
+
+    #[test]
+    fn test_signaling_frame_json() {
+        let frame: SignalingFrame =
+            serde_json::from_str(r#"{"type":"offer","offer":"encoded"}"#).unwrap();
+        assert_eq!(frame, SignalingFrame::Offer {
+            offer: "encoded".to_string()
+        });
+        assert_eq!(
+            serde_json::to_string(&SignalingFrame::Answer {
+                answer: "encoded".to_string()
+            })
+            .unwrap(),
+            r#"{"type":"answer","answer":"encoded"}"#
+        );
+    }
+}
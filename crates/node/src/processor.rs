@@ -6,8 +6,13 @@ use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 
+use dashmap::DashMap;
+use futures::channel::oneshot;
+use futures::FutureExt;
+use rings_core::consts::CONNECT_WAIT_TIMEOUT_MS;
 use rings_core::dht::Did;
 use rings_core::dht::VNodeStorage;
+use rings_core::error::Error as CoreError;
 use rings_core::measure::MeasureImpl;
 use rings_core::message::Encoded;
 use rings_core::message::Encoder;
@@ -16,11 +21,16 @@ use rings_core::prelude::uuid;
 use rings_core::storage::MemStorage;
 use rings_core::swarm::Swarm;
 use rings_core::swarm::SwarmBuilder;
+use rings_core::swarm::SwarmConfig;
 use rings_rpc::protos::rings_node::*;
 use serde::Deserialize;
 use serde::Serialize;
 
 use crate::backend::types::BackendMessage;
+use crate::consts::BACKEND_CHUNK_OVERHEAD;
+use crate::consts::CAPABILITY_CHUNK;
+#[cfg(feature = "snark")]
+use crate::consts::CAPABILITY_SNARK;
 use crate::consts::DATA_REDUNDANT;
 use crate::error::Error;
 use crate::error::Result;
@@ -47,6 +57,8 @@ pub struct ProcessorConfig {
     session_sk: SessionSk,
     /// Stabilization interval.
     stabilize_interval: Duration,
+    /// Other options of swarm.
+    swarm: SwarmConfig,
 }
 
 #[wasm_export]
@@ -64,6 +76,7 @@ impl ProcessorConfig {
             external_address: None,
             session_sk,
             stabilize_interval: Duration::from_secs(stabilize_interval),
+            swarm: SwarmConfig::default(),
         }
     }
 
@@ -97,6 +110,9 @@ pub struct ProcessorConfigSerialized {
     session_sk: String,
     /// An unsigned integer representing the stabilization interval in seconds.
     stabilize_interval: u64,
+    /// Other options of swarm, which keep the defaults of swarm if not set.
+    #[serde(default)]
+    swarm: SwarmConfig,
 }
 
 impl ProcessorConfigSerialized {
@@ -113,6 +129,7 @@ impl ProcessorConfigSerialized {
             external_address: None,
             session_sk,
             stabilize_interval,
+            swarm: SwarmConfig::default(),
         }
     }
 
@@ -122,6 +139,12 @@ impl ProcessorConfigSerialized {
         self.external_address = Some(external_address);
         self
     }
+
+    /// Sets up other options of swarm, see [SwarmConfig].
+    pub fn swarm(mut self, swarm: SwarmConfig) -> Self {
+        self.swarm = swarm;
+        self
+    }
 }
 
 impl TryFrom<ProcessorConfig> for ProcessorConfigSerialized {
@@ -133,6 +156,7 @@ impl TryFrom<ProcessorConfig> for ProcessorConfigSerialized {
             external_address: ins.external_address.clone(),
             session_sk: ins.session_sk.dump()?,
             stabilize_interval: ins.stabilize_interval.as_secs(),
+            swarm: ins.swarm.clone(),
         })
     }
 }
@@ -146,6 +170,7 @@ impl TryFrom<ProcessorConfigSerialized> for ProcessorConfig {
             external_address: ins.external_address.clone(),
             session_sk: SessionSk::from_str(&ins.session_sk)?,
             stabilize_interval: Duration::from_secs(ins.stabilize_interval),
+            swarm: ins.swarm.clone(),
         })
     }
 }
@@ -187,6 +212,7 @@ pub struct ProcessorBuilder {
     storage: Option<VNodeStorage>,
     measure: Option<MeasureImpl>,
     stabilize_interval: Duration,
+    swarm: SwarmConfig,
 }
 
 /// Processor for rings-node rpc server
@@ -195,6 +221,8 @@ pub struct Processor {
     /// a swarm instance
     pub swarm: Arc<Swarm>,
     stabilize_interval: Duration,
+    /// Calls waiting for response, indexed by correlation id, with the did they're sent to.
+    pending_calls: Arc<DashMap<uuid::Uuid, (Did, oneshot::Sender<BackendMessage>)>>,
 }
 
 impl ProcessorBuilder {
@@ -215,6 +243,7 @@ impl ProcessorBuilder {
             storage: None,
             measure: None,
             stabilize_interval: config.stabilize_interval,
+            swarm: config.swarm.clone(),
         })
     }
 
@@ -239,8 +268,10 @@ impl ProcessorBuilder {
 
         let storage = self.storage.unwrap_or_else(|| Box::new(MemStorage::new()));
 
+        self.swarm.validate().map_err(Error::Swarm)?;
         let mut swarm_builder =
-            SwarmBuilder::new(self.network_id, &self.ice_servers, storage, self.session_sk);
+            SwarmBuilder::new(self.network_id, &self.ice_servers, storage, self.session_sk)
+                .config(&self.swarm);
 
         if let Some(external_address) = self.external_address {
             swarm_builder = swarm_builder.external_address(external_address);
@@ -249,11 +280,18 @@ impl ProcessorBuilder {
         if let Some(measure) = self.measure {
             swarm_builder = swarm_builder.measure(measure);
         }
-        let swarm = Arc::new(swarm_builder.build());
+
+        #[allow(unused_mut)]
+        let mut capabilities = vec![CAPABILITY_CHUNK.to_string()];
+        #[cfg(feature = "snark")]
+        capabilities.push(CAPABILITY_SNARK.to_string());
+        swarm_builder = swarm_builder.capabilities(capabilities);
+        let swarm = Arc::new(swarm_builder.build().map_err(Error::Swarm)?);
 
         Ok(Processor {
             swarm,
             stabilize_interval: self.stabilize_interval,
+            pending_calls: Arc::new(DashMap::new()),
         })
     }
 }
@@ -264,8 +302,13 @@ impl Processor {
         self.swarm.did()
     }
 
-    /// Run stabilization daemon
+    /// Run stabilization daemon. NAT type is detected in background meanwhile, if it's enabled
+    /// by [SwarmConfig::detect_nat].
     pub async fn listen(&self) {
+        let swarm = self.swarm.clone();
+        rings_core::utils::spawn(async move {
+            swarm.detect_nat().await;
+        });
         let stabilizer = self.swarm.stabilizer();
         Arc::new(stabilizer).wait(self.stabilize_interval).await
     }
@@ -280,6 +323,18 @@ impl Processor {
         Ok(())
     }
 
+    /// Connect a peer with web3 did, and wait until the connection is ready to send data.
+    /// If `timeout_ms` is None, [CONNECT_WAIT_TIMEOUT_MS] is used.
+    pub async fn connect_with_did_and_wait(&self, did: Did, timeout_ms: Option<u64>) -> Result<()> {
+        self.swarm
+            .connect_and_wait(did, timeout_ms.unwrap_or(CONNECT_WAIT_TIMEOUT_MS))
+            .await
+            .map_err(|e| match e {
+                CoreError::WaitConnectionTimeout(_) => Error::ConnectTimeout(e),
+                _ => Error::ConnectError(e),
+            })
+    }
+
     /// Disconnect a peer with web3 did.
     pub async fn disconnect(&self, did: Did) -> Result<()> {
         self.swarm
@@ -304,14 +359,127 @@ impl Processor {
             .map_err(Error::SendMessage)
     }
 
+    /// Max size of data in a chunk of backend message, so that the chunk fits in
+    /// [rings_core::swarm::Swarm::max_message_size].
+    pub fn backend_mtu(&self) -> usize {
+        self.swarm
+            .max_message_size()
+            .saturating_sub(BACKEND_CHUNK_OVERHEAD)
+            .max(1)
+    }
+
     /// Send custom message to a did.
+    /// Message larger than [Processor::backend_mtu] is sent in chunks, the tx_id of the last chunk
+    /// is returned.
+    /// Message is sent as a whole to connected peers which didn't advertise [CAPABILITY_CHUNK].
     pub async fn send_backend_message(
         &self,
         destination: Did,
         backend_msg: BackendMessage,
     ) -> Result<uuid::Uuid> {
+        let msgs = match self.swarm.peer_capabilities(destination) {
+            Some(caps) if !caps.iter().any(|c| c == CAPABILITY_CHUNK) => vec![backend_msg],
+            _ => backend_msg.split(self.backend_mtu())?,
+        };
+        let mut tx_id = None;
+        for msg in msgs {
+            let msg_bytes = bincode::serialize(&msg).map_err(|_| Error::EncodeError)?;
+            tx_id = Some(self.send_message(destination, &msg_bytes).await?);
+        }
+        tx_id.ok_or(Error::EncodeError)
+    }
+
+    /// Send `request` to `destination` as [BackendMessage::Request], then wait for the
+    /// [BackendMessage::Response] of it, see [Processor::respond].
+    /// Return [Error::RequestTimeout] if no response arrived in `timeout`.
+    pub async fn call(
+        &self,
+        destination: Did,
+        request: BackendMessage,
+        timeout: Duration,
+    ) -> Result<BackendMessage> {
+        let id = uuid::Uuid::new_v4();
+        let request = BackendMessage::Request {
+            id,
+            msg: request.encode_nested()?,
+        };
+
+        // Register before sending, the response may arrive before sending returns.
+        let (tx, rx) = oneshot::channel();
+        self.pending_calls.insert(id, (destination, tx));
+        if let Err(e) = self.send_backend_message(destination, request).await {
+            self.pending_calls.remove(&id);
+            return Err(e);
+        }
+
+        let rx = rx.fuse();
+        let timer = rings_core::utils::sleep(timeout).fuse();
+        futures::pin_mut!(rx, timer);
+        let response = futures::select! {
+            response = rx => response.ok(),
+            _ = timer => None,
+        };
+        self.pending_calls.remove(&id);
+        response.ok_or(Error::RequestTimeout(timeout))
+    }
+
+    /// Answer the [BackendMessage::Request] of `id` from `destination` with `response`.
+    pub async fn respond(
+        &self,
+        destination: Did,
+        id: uuid::Uuid,
+        response: BackendMessage,
+    ) -> Result<uuid::Uuid> {
+        let response = BackendMessage::Response {
+            id,
+            msg: response.encode_nested()?,
+        };
+        self.send_backend_message(destination, response).await
+    }
+
+    /// Deliver a response of `id` from `sender` to the call waiting for it.
+    /// Return false if there is no such call, such as it's timed out, or it's sent to another
+    /// did.
+    pub(crate) fn resolve_call(
+        &self,
+        sender: Did,
+        id: uuid::Uuid,
+        response: BackendMessage,
+    ) -> bool {
+        match self
+            .pending_calls
+            .remove_if(&id, |_, (destination, _)| *destination == sender)
+        {
+            Some((_, (_, tx))) => tx.send(response).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Subscribe a topic, see [Swarm::subscribe].
+    /// Messages published to the topic are handled like backend messages sent to this node.
+    pub async fn subscribe(&self, topic: &str) -> Result<()> {
+        self.swarm
+            .subscribe(topic)
+            .await
+            .map_err(Error::SendMessage)
+    }
+
+    /// Unsubscribe a topic, see [Swarm::unsubscribe].
+    pub async fn unsubscribe(&self, topic: &str) -> Result<()> {
+        self.swarm
+            .unsubscribe(topic)
+            .await
+            .map_err(Error::SendMessage)
+    }
+
+    /// Publish backend message to subscribers of a topic, see [Swarm::publish].
+    /// The message is published as a whole, without being split into chunks.
+    pub async fn publish(&self, topic: &str, backend_msg: BackendMessage) -> Result<()> {
         let msg_bytes = bincode::serialize(&backend_msg).map_err(|_| Error::EncodeError)?;
-        self.send_message(destination, &msg_bytes).await
+        self.swarm
+            .publish(topic, msg_bytes)
+            .await
+            .map_err(Error::SendMessage)
     }
 
     /// check local cache of dht
@@ -502,4 +670,113 @@ mod test {
             got_msg1
         );
     }
+
+    struct EchoHandler;
+
+    #[async_trait]
+    impl crate::backend::types::MessageHandler<BackendMessage> for EchoHandler {
+        async fn handle_message(
+            &self,
+            provider: Arc<crate::provider::Provider>,
+            ctx: &MessagePayload,
+            msg: &BackendMessage,
+        ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+            use rings_core::message::MessageVerificationExt;
+
+            // Requests of plain text are answered in upper case, others are ignored.
+            if let BackendMessage::Request { id, msg } = msg {
+                if let BackendMessage::PlainText(text) = BackendMessage::decode_nested(msg)? {
+                    let response = BackendMessage::PlainText(text.to_uppercase());
+                    provider
+                        .respond(ctx.transaction.signer(), *id, response)
+                        .await?;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_processor_call() {
+        use crate::provider::Provider;
+
+        let p1 = prepare_processor().await;
+        let p2 = prepare_processor().await;
+        let provider1 = Provider::from_processor(Arc::new(p1.clone()));
+        let provider2 = Provider::from_processor(Arc::new(p2.clone()));
+        provider1.set_backend_callback(EchoHandler).unwrap();
+        provider2.set_backend_callback(EchoHandler).unwrap();
+
+        let offer = p1.swarm.create_offer(p2.did()).await.unwrap();
+        let answer = p2.swarm.answer_offer(offer).await.unwrap();
+        p1.swarm.accept_answer(answer).await.unwrap();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        let timeout = Duration::from_secs(5);
+        let response = provider1
+            .call(
+                p2.did(),
+                BackendMessage::PlainText("ping".to_string()),
+                timeout,
+            )
+            .await
+            .unwrap();
+        assert!(matches!(response, BackendMessage::PlainText(text) if text == "PING"));
+        assert!(p1.pending_calls.is_empty());
+
+        // No response to other requests, so the call times out.
+        let timeout = Duration::from_millis(500);
+        let result = provider1
+            .call(p2.did(), BackendMessage::Extension(vec![1].into()), timeout)
+            .await;
+        assert!(matches!(result, Err(Error::RequestTimeout(t)) if t == timeout));
+        assert!(p1.pending_calls.is_empty());
+
+        // Requests can't be nested.
+        let nested = BackendMessage::Request {
+            id: uuid::Uuid::new_v4(),
+            msg: vec![].into(),
+        };
+        let result = provider1.call(p2.did(), nested, timeout).await;
+        assert!(matches!(result, Err(Error::EncodeError)));
+        assert!(p1.pending_calls.is_empty());
+
+        // A response nobody is waiting for is dropped.
+        assert!(!p1.resolve_call(
+            p2.did(),
+            uuid::Uuid::new_v4(),
+            BackendMessage::PlainText("late".to_string())
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_backend_mtu_follows_max_message_size() {
+        use rings_core::consts::DEFAULT_MAX_MESSAGE_SIZE;
+        use rings_core::storage::MemStorage;
+
+        let processor = prepare_processor().await;
+        assert_eq!(
+            processor.backend_mtu(),
+            DEFAULT_MAX_MESSAGE_SIZE - BACKEND_CHUNK_OVERHEAD
+        );
+
+        let session_sk = SessionSk::new_with_seckey(&SecretKey::random()).unwrap();
+        let config = ProcessorConfigSerialized::new(
+            0,
+            "stun://stun.l.google.com:19302".to_string(),
+            session_sk.dump().unwrap(),
+            3,
+        )
+        .swarm(SwarmConfig {
+            max_message_size: Some(100_000),
+            ..Default::default()
+        });
+        let config = ProcessorConfig::try_from(config).unwrap();
+        let processor = ProcessorBuilder::from_config(&config)
+            .unwrap()
+            .storage(Box::new(MemStorage::new()))
+            .build()
+            .unwrap();
+        assert_eq!(processor.backend_mtu(), 100_000 - BACKEND_CHUNK_OVERHEAD);
+    }
 }
@@ -220,6 +220,30 @@ impl Provider {
         })
     }
 
+    /// Request local rpc interface, the promise is rejected if there is no response in
+    /// `timeout_ms` or the requests of provider are cancelled.
+    pub fn request_with_timeout(
+        &self,
+        method: String,
+        params: JsValue,
+        timeout_ms: u32,
+    ) -> js_sys::Promise {
+        let ins = self.clone();
+        future_to_promise(async move {
+            let params =
+                js_value::json_value(params).map_err(|e| JsError::new(e.to_string().as_str()))?;
+            let ret = ins
+                .request_internal_with_timeout(
+                    method,
+                    params,
+                    std::time::Duration::from_millis(timeout_ms.into()),
+                )
+                .await
+                .map_err(JsError::from)?;
+            Ok(js_value::serialize(&ret).map_err(JsError::from)?)
+        })
+    }
+
     /// listen message.
     pub fn listen(&self) -> js_sys::Promise {
         let p = self.processor.clone();
@@ -232,7 +256,7 @@ impl Provider {
 
     /// connect peer with remote jsonrpc server url
     pub fn connect_peer_via_http(&self, remote_url: String) -> js_sys::Promise {
-        log::debug!("remote_url: {}", remote_url);
+        tracing::debug!("remote_url: {}", remote_url);
         self.request(
             "ConnectPeerViaHttp".to_string(),
             js_value::serialize(&ConnectPeerViaHttpRequest { url: remote_url }).unwrap(),
@@ -262,6 +286,34 @@ impl Provider {
         })
     }
 
+    /// connect peer with web3 address, the returned promise resolves once the data channel
+    /// is open. It rejects with `{ code, message }` on failure or when it's not open in
+    /// `timeout_ms`, which defaults to 8 seconds.
+    /// example:
+    /// ```typescript
+    /// await provider1.connect_and_wait(provider3.address(), undefined, 5000)
+    /// await provider1.send_message(provider3.address(), "hello")
+    /// ```
+    pub fn connect_and_wait(
+        &self,
+        address: String,
+        addr_type: Option<AddressType>,
+        timeout_ms: Option<u32>,
+    ) -> js_sys::Promise {
+        let p = self.processor.clone();
+        future_to_promise(async move {
+            let did = get_did(address.as_str(), addr_type.unwrap_or(AddressType::DEFAULT))?;
+            if let Err(e) = p
+                .connect_with_did_and_wait(did, timeout_ms.map(u64::from))
+                .await
+            {
+                let err = jsonrpc_core::Error::from(e);
+                return Err(js_value::serialize(&err).map_err(JsError::from)?);
+            }
+            Ok(JsValue::null())
+        })
+    }
+
     /// get info for self, will return build version and inspection of swarm
     pub fn get_node_info(&self) -> js_sys::Promise {
         let p = self.processor.clone();
@@ -4,12 +4,16 @@
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 
+use futures::FutureExt;
+use rings_core::dht::Did;
 use rings_core::dht::VNodeStorage;
 use rings_core::session::SessionSkBuilder;
 use rings_core::storage::MemStorage;
 use rings_core::swarm::callback::SharedSwarmCallback;
 use rings_rpc::protos::rings_node_handler::InternalRpcHandler;
+use tokio_util::sync::CancellationToken;
 
 use crate::backend::types::BackendMessage;
 use crate::backend::types::MessageHandler;
@@ -38,6 +42,7 @@ pub mod ffi;
 pub struct Provider {
     processor: Arc<Processor>,
     handler: InternalRpcHandler,
+    cancel_token: CancellationToken,
 }
 
 /// Async signer, without Send required
@@ -64,6 +69,7 @@ impl Provider {
         Self {
             processor,
             handler: InternalRpcHandler,
+            cancel_token: CancellationToken::new(),
         }
     }
     /// Create a provider instance with storage name
@@ -86,6 +92,7 @@ impl Provider {
         Ok(Provider {
             processor,
             handler: InternalRpcHandler,
+            cancel_token: CancellationToken::new(),
         })
     }
 
@@ -160,6 +167,36 @@ impl Provider {
             .map_err(Error::InternalError)
     }
 
+    /// Send a request to `did` and wait for its response in `timeout`, see [Processor::call].
+    pub async fn call(
+        &self,
+        did: Did,
+        request: BackendMessage,
+        timeout: Duration,
+    ) -> Result<BackendMessage> {
+        self.processor.call(did, request, timeout).await
+    }
+
+    /// Answer a [BackendMessage::Request] of `id` from `did`, see [Processor::respond].
+    pub async fn respond(
+        &self,
+        did: Did,
+        id: uuid::Uuid,
+        response: BackendMessage,
+    ) -> Result<uuid::Uuid> {
+        self.processor.respond(did, id, response).await
+    }
+
+    pub(crate) fn resolve_call(&self, did: Did, id: uuid::Uuid, response: BackendMessage) -> bool {
+        self.processor.resolve_call(did, id, response)
+    }
+
+    /// Get capabilities negotiated with a connected peer.
+    /// Return None if the peer is not connected directly.
+    pub fn peer_capabilities(&self, did: Did) -> Option<Vec<String>> {
+        self.processor.swarm.peer_capabilities(did)
+    }
+
     /// Request local rpc interface
     /// the internal rpc interface is provide by rings_rpc
     pub async fn request_internal(
@@ -173,6 +210,47 @@ impl Provider {
             .await
             .map_err(Error::InternalRpcError)
     }
+
+    /// Request local rpc interface like [Provider::request_internal], but fail with
+    /// [Error::RequestTimeout] if there is no response in `timeout`, or with
+    /// [Error::RequestCancelled] once [Provider::cancel_requests] is called.
+    pub async fn request_internal_with_timeout(
+        &self,
+        method: String,
+        params: serde_json::Value,
+        timeout: Duration,
+    ) -> Result<serde_json::Value> {
+        with_timeout(
+            self.request_internal(method, params),
+            timeout,
+            &self.cancel_token,
+        )
+        .await
+    }
+
+    /// Abort outstanding requests made with timeout, and the ones made later.
+    /// It's used when the node is shutting down.
+    pub fn cancel_requests(&self) {
+        self.cancel_token.cancel();
+    }
+}
+
+/// Race a request against a timer and a cancellation token.
+pub(crate) async fn with_timeout<T>(
+    request: impl std::future::Future<Output = Result<T>>,
+    timeout: Duration,
+    cancel_token: &CancellationToken,
+) -> Result<T> {
+    let request = request.fuse();
+    let timer = rings_core::utils::sleep(timeout).fuse();
+    let cancelled = cancel_token.cancelled().fuse();
+    futures::pin_mut!(request, timer, cancelled);
+
+    futures::select! {
+        ret = request => ret,
+        _ = timer => Err(Error::RequestTimeout(timeout)),
+        _ = cancelled => Err(Error::RequestCancelled),
+    }
 }
 
 #[cfg(feature = "node")]
@@ -190,8 +268,54 @@ impl Provider {
         self.request_internal(method.to_string(), params).await
     }
 
+    /// A request function with timeout for native provider,
+    /// see [Provider::request_internal_with_timeout].
+    pub async fn request_with_timeout<T>(
+        &self,
+        method: rings_rpc::method::Method,
+        params: T,
+        timeout: Duration,
+    ) -> Result<serde_json::Value>
+    where
+        T: serde::Serialize,
+    {
+        let params = serde_json::to_value(params)?;
+        self.request_internal_with_timeout(method.to_string(), params, timeout)
+            .await
+    }
+
     /// Listen messages
     pub async fn listen(&self) {
         self.processor.listen().await;
     }
 }
+
+#[cfg(all(test, feature = "node"))]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_request_timeout() {
+        let token = CancellationToken::new();
+        let never = std::future::pending::<Result<()>>();
+        let ret = with_timeout(never, Duration::from_millis(100), &token).await;
+        assert!(matches!(ret, Err(Error::RequestTimeout(_))));
+
+        let ret = with_timeout(async { Ok(1) }, Duration::from_secs(10), &token).await;
+        assert_eq!(ret.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_request_cancelled() {
+        let token = CancellationToken::new();
+        let cancel = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            cancel.cancel();
+        });
+
+        let never = std::future::pending::<Result<()>>();
+        let ret = with_timeout(never, Duration::from_secs(10), &token).await;
+        assert!(matches!(ret, Err(Error::RequestCancelled)));
+    }
+}
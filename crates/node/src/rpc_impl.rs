@@ -325,6 +325,21 @@ impl HandleRpc<NodeDidRequest, NodeDidResponse> for Processor {
     }
 }
 
+#[cfg_attr(feature = "browser", async_trait(?Send))]
+#[cfg_attr(not(feature = "browser"), async_trait)]
+impl HandleRpc<ExportBootstrapRequest, ExportBootstrapResponse> for Processor {
+    async fn handle_rpc(&self, _req: ExportBootstrapRequest) -> Result<ExportBootstrapResponse> {
+        let dids = self
+            .swarm
+            .known_dids()
+            .map_err(|_| Error::new(ErrorCode::InternalError))?
+            .into_iter()
+            .map(|did| did.to_string())
+            .collect();
+        Ok(ExportBootstrapResponse { dids })
+    }
+}
+
 /// Get did from string or return InvalidParam Error
 fn s2d(s: &str) -> Result<Did> {
     Did::from_str(s).map_err(|_| Error::invalid_params(format!("Invalid Did: {s}")))
@@ -1,26 +1,248 @@
 use crate::backend::snark::*;
 
-#[tokio::test]
-pub async fn test_gen_proof_and_verify() {
-    let wasm = "../snark/src/tests/native/circoms/simple_bn256.wasm";
-    let r1cs = "../snark/src/tests/native/circoms/simple_bn256.r1cs";
-    let snark_task_builder = SNARKTaskBuilder::from_local(
-        r1cs.to_string(),
-        wasm.to_string(),
-        crate::backend::snark::SupportedPrimeField::Vesta,
+/// Wasm of the simple circuit in tests of rings-snark.
+pub const SIMPLE_WASM: &str = "../snark/src/tests/native/circoms/simple_bn256.wasm";
+/// R1CS of the simple circuit in tests of rings-snark.
+pub const SIMPLE_R1CS: &str = "../snark/src/tests/native/circoms/simple_bn256.r1cs";
+
+/// Task builder of the simple circuit, on curve vesta.
+pub async fn simple_task_builder() -> SNARKTaskBuilder {
+    SNARKTaskBuilder::from_local(
+        SIMPLE_R1CS.to_string(),
+        SIMPLE_WASM.to_string(),
+        SupportedPrimeField::Vesta,
     )
     .await
-    .unwrap();
-    type F = crate::backend::snark::Field;
-    let input: Input = vec![("step_in".to_string(), vec![
-        F::from_u64(4u64, SupportedPrimeField::Vesta),
-        F::from_u64(2u64, SupportedPrimeField::Vesta),
+    .unwrap()
+}
+
+/// Public input `step_in` of the simple circuit, on curve vesta.
+pub fn simple_input(x: u64, y: u64) -> Input {
+    vec![("step_in".to_string(), vec![
+        Field::from_u64(x, SupportedPrimeField::Vesta),
+        Field::from_u64(y, SupportedPrimeField::Vesta),
     ])]
-    .into();
-    let circuits = snark_task_builder.gen_circuits(input, vec![], 5).unwrap();
+    .into()
+}
+
+/// Circuits of the simple circuit folding `steps` times from `step_in` (4, 2).
+pub async fn simple_circuits(steps: usize) -> Vec<Circuit> {
+    simple_task_builder()
+        .await
+        .gen_circuits(simple_input(4, 2), vec![], steps)
+        .unwrap()
+}
+
+#[tokio::test]
+pub async fn test_gen_proof_and_verify() {
+    let circuits = simple_circuits(5).await;
     assert_eq!(circuits.len(), 5);
     let task = SNARKBehaviour::gen_proof_task(circuits).unwrap();
     let proof = SNARKBehaviour::handle_snark_proof_task(&task).unwrap();
     let ret = SNARKBehaviour::handle_snark_verify_task(&proof, &task).unwrap();
     assert!(ret)
 }
+
+#[tokio::test]
+pub async fn test_verify_proof_without_task() {
+    let snark_task_builder = simple_task_builder().await;
+    let circuits = snark_task_builder
+        .gen_circuits(simple_input(4, 2), vec![], 5)
+        .unwrap();
+    let task = SNARKBehaviour::gen_proof_task(circuits).unwrap();
+    let proof = SNARKBehaviour::handle_snark_proof_task(&task).unwrap();
+
+    // Verify with only the proof, public inputs and steps, the proof task is dropped.
+    // The verifier key is derived from a circuit of the same shape, whatever its inputs are.
+    drop(task);
+    let circuit = snark_task_builder
+        .gen_circuits(simple_input(1, 1), vec![], 1)
+        .unwrap()
+        .remove(0);
+    let public_inputs = |x: u64, y: u64| {
+        simple_input(x, y)
+            .into_iter()
+            .flat_map(|(_, v)| v)
+            .collect::<Vec<_>>()
+    };
+    let behaviour = SNARKBehaviour::default();
+    assert!(behaviour
+        .verify_proof(&proof, &circuit, public_inputs(4, 2), 5)
+        .unwrap());
+    assert!(!behaviour
+        .verify_proof(&proof, &circuit, public_inputs(4, 2), 4)
+        .unwrap());
+    assert!(!behaviour
+        .verify_proof(&proof, &circuit, public_inputs(4, 3), 5)
+        .unwrap());
+
+    let wrong_curve = vec![Field::from_u64(4u64, SupportedPrimeField::Pallas)];
+    assert!(behaviour
+        .verify_proof(&proof, &circuit, wrong_curve, 5)
+        .is_err());
+}
+
+#[tokio::test]
+pub async fn test_verify_proof_from_chunks() {
+    use bytes::Bytes;
+    use rings_core::chunk::ChunkList;
+
+    use crate::backend::types::snark::SNARKVerifyTask;
+
+    let circuits = simple_circuits(5).await;
+    let task = SNARKBehaviour::gen_proof_task(circuits).unwrap();
+    let SNARKVerifyTask::VastaPallas(proof) =
+        SNARKBehaviour::handle_snark_proof_task(&task).unwrap()
+    else {
+        panic!("unexpected curve");
+    };
+
+    // The proof is read from chunks without joining them into one string.
+    let data: Bytes = proof.into_bytes().into();
+    let chunks = ChunkList::<1024>::from(&data);
+    assert!(chunks.as_vec().len() > 1);
+    let reader = chunks.try_withdraw_reader().unwrap();
+    assert!(SNARKBehaviour::handle_snark_verify_reader(reader, &task).unwrap());
+
+    let truncated = data.slice(..data.len() / 2);
+    assert!(SNARKBehaviour::handle_snark_verify_reader(truncated.as_ref(), &task).is_err());
+}
+
+#[tokio::test]
+pub async fn test_worker_pool_proves_concurrently() {
+    let circuits = simple_circuits(5).await;
+    let task = SNARKBehaviour::gen_proof_task(circuits).unwrap();
+    // Two workers and one more task in queue.
+    let pool = SNARKWorkerPool::with_queue(2, 1);
+
+    let proving = (0..3)
+        .map(|_| pool.try_prove(task.clone()).unwrap())
+        .collect::<Vec<_>>();
+    // The queue is full until one of them completes.
+    assert!(pool.try_prove(task.clone()).is_err());
+
+    let proofs = futures::future::join_all(proving).await;
+    for proof in proofs {
+        assert!(SNARKBehaviour::handle_snark_verify_task(&proof.unwrap(), &task).unwrap());
+    }
+    let proof = pool.prove(task.clone()).await.unwrap();
+    assert!(SNARKBehaviour::handle_snark_verify_task(&proof, &task).unwrap());
+}
+
+/// Serve the circom files by http, return the base url.
+fn serve_circoms(r1cs: &str, wasm: &str) -> String {
+    let r1cs = std::fs::read(r1cs).unwrap();
+    let wasm = std::fs::read(wasm).unwrap();
+    let app = axum::Router::new()
+        .route(
+            "/simple.r1cs",
+            axum::routing::get(move || async move { r1cs }),
+        )
+        .route(
+            "/simple.wasm",
+            axum::routing::get(move || async move { wasm }),
+        );
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = axum::Server::from_tcp(listener)
+        .unwrap()
+        .serve(app.into_make_service());
+    tokio::spawn(server);
+    format!("http://{addr}")
+}
+
+fn sha256_hex(path: &str) -> String {
+    use sha2::Digest;
+    sha2::Sha256::digest(std::fs::read(path).unwrap())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[tokio::test]
+pub async fn test_snark_task_builder_from_url() {
+    let (wasm, r1cs) = (SIMPLE_WASM, SIMPLE_R1CS);
+    let base = serve_circoms(r1cs, wasm);
+    let r1cs_url = format!("{base}/simple.r1cs");
+    let wasm_url = format!("{base}/simple.wasm");
+
+    let snark_task_builder = SNARKTaskBuilder::from_url(
+        r1cs_url.clone(),
+        wasm_url.clone(),
+        SupportedPrimeField::Vesta,
+        Some(sha256_hex(r1cs)),
+        Some(sha256_hex(wasm)),
+    )
+    .await
+    .unwrap();
+    let circuits = snark_task_builder
+        .gen_circuits(simple_input(4, 2), vec![], 2)
+        .unwrap();
+    assert_eq!(circuits.len(), 2);
+
+    // Checksum is optional.
+    SNARKTaskBuilder::from_url(
+        r1cs_url.clone(),
+        wasm_url.clone(),
+        SupportedPrimeField::Vesta,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let res = SNARKTaskBuilder::from_url(
+        r1cs_url.clone(),
+        wasm_url.clone(),
+        SupportedPrimeField::Vesta,
+        Some(sha256_hex(wasm)),
+        None,
+    )
+    .await;
+    assert!(matches!(
+        res,
+        Err(crate::error::Error::RingsSNARKError(
+            rings_snark::error::Error::ChecksumMismatch { url, .. }
+        )) if url == r1cs_url
+    ));
+
+    let res = SNARKTaskBuilder::from_url(
+        r1cs.to_string(),
+        wasm_url.clone(),
+        SupportedPrimeField::Vesta,
+        None,
+        None,
+    )
+    .await;
+    assert!(matches!(
+        res,
+        Err(crate::error::Error::RingsSNARKError(
+            rings_snark::error::Error::UnsupportedUrl(_)
+        ))
+    ));
+
+    let res = rings_snark::r1cs::Download::new(wasm_url.clone())
+        .max_size(16)
+        .fetch()
+        .await;
+    assert!(matches!(
+        res,
+        Err(rings_snark::error::Error::DownloadTooLarge(url, 16)) if url == wasm_url
+    ));
+}
+
+#[tokio::test]
+pub async fn test_num_steps_and_circuit_info() {
+    let circuits = simple_circuits(5).await;
+    let info = circuits[0].info();
+    assert_eq!(info.num_inputs, 2);
+    assert!(info.num_constraints > 0);
+
+    let task = SNARKBehaviour::gen_proof_task(circuits).unwrap();
+    assert_eq!(task.num_steps(), 5);
+    assert_eq!(task.circuit_info(4).unwrap(), info);
+    assert!(matches!(
+        task.circuit_info(5),
+        Err(crate::error::Error::SNARKCircuitOutOfRange(5, 5))
+    ));
+}
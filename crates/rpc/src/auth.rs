@@ -0,0 +1,53 @@
+//! Signature based authentication of rpc requests.
+//!
+//! A client signs each request by its secret key, and sends the signature in
+//! [SIGNATURE_HEADER] and the time of signing in [TIMESTAMP_HEADER]. The timestamp, the
+//! http method, the path and the body of request are signed together. The server recovers
+//! the signer from the signature, then checks it against the Dids authorized. Requests
+//! whose timestamp is beyond [MAX_TIMESTAMP_SKEW_MS] from the clock of server are rejected,
+//! so that a captured request can't be replayed later.
+
+use rings_core::dht::Did;
+use rings_core::ecc::SecretKey;
+
+use crate::error::Error;
+use crate::error::Result;
+
+/// Header carrying base64 encoded signature of request.
+pub const SIGNATURE_HEADER: &str = "X-Rings-Signature";
+/// Header carrying utc timestamp in milliseconds when the request is signed.
+pub const TIMESTAMP_HEADER: &str = "X-Rings-Timestamp";
+/// Max difference in milliseconds between the timestamp of a request and the clock of server.
+pub const MAX_TIMESTAMP_SKEW_MS: u128 = 30 * 1000;
+
+/// Bytes to sign for a request.
+fn signed_message(timestamp_ms: u128, method: &str, path: &str, body: &[u8]) -> Vec<u8> {
+    let mut msg = format!("{timestamp_ms}\n{method}\n{path}\n").into_bytes();
+    msg.extend_from_slice(body);
+    msg
+}
+
+/// Sign request by `key` at `timestamp_ms`, return the value of [SIGNATURE_HEADER].
+pub fn sign_request(
+    key: &SecretKey,
+    timestamp_ms: u128,
+    method: &str,
+    path: &str,
+    body: &[u8],
+) -> String {
+    base64::encode(key.sign_raw(&signed_message(timestamp_ms, method, path, body)))
+}
+
+/// Recover Did of the signer of request from the value of [SIGNATURE_HEADER].
+pub fn recover_signer(
+    timestamp_ms: u128,
+    method: &str,
+    path: &str,
+    body: &[u8],
+    signature: &str,
+) -> Result<Did> {
+    let sig = base64::decode(signature).map_err(|_| Error::InvalidSignature)?;
+    let msg = signed_message(timestamp_ms, method, path, body);
+    let pubkey = rings_core::ecc::recover(&msg, sig).map_err(|_| Error::InvalidSignature)?;
+    Ok(pubkey.address().into())
+}
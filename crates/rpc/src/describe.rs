@@ -0,0 +1,270 @@
+//! Description of rpc methods, returned by [crate::method::Method::Describe].
+//!
+//! Methods are the rpcs of services in `rings_node.proto`, which also defines their request
+//! and response messages, so the description is derived from the same source as the types
+//! and never drifts from them. JSON schemas of messages follow their serde form: nested
+//! messages are objects, `repeated` fields are arrays of their items, and `optional` fields
+//! and nested messages may be null.
+#![warn(missing_docs)]
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::json;
+use serde_json::Value;
+
+const PROTO: &str = include_str!("protos/rings_node.proto");
+
+/// Parameters and result of an rpc method, described as JSON schemas.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MethodDescription {
+    /// Name of method, such as `sendBackendMessage`.
+    pub method: String,
+    /// Schema of the params object.
+    pub params_schema: Value,
+    /// Schema of the result object.
+    pub result_schema: Value,
+}
+
+/// A field of message in proto.
+struct Field {
+    name: &'static str,
+    ty: &'static str,
+    repeated: bool,
+    optional: bool,
+}
+
+type Messages = HashMap<&'static str, Vec<Field>>;
+
+/// Describe rpcs of `service` in `rings_node.proto`, in the order they are defined.
+pub fn describe_service(service: &str) -> Vec<MethodDescription> {
+    let messages = parse_messages();
+    service_rpcs(service)
+        .into_iter()
+        .map(|(name, req, resp)| MethodDescription {
+            method: method_name(name),
+            params_schema: schema_of_message(req, &messages),
+            result_schema: schema_of_message(resp, &messages),
+        })
+        .collect()
+}
+
+/// Name of the rpc method of `rpc`, such as `sendBackendMessage` of `SendBackendMessage`.
+fn method_name(rpc: &str) -> String {
+    let mut chars = rpc.chars();
+    chars
+        .next()
+        .map(|c| c.to_ascii_lowercase().to_string() + chars.as_str())
+        .unwrap_or_default()
+}
+
+/// Name and body of each top level block started by `keyword` at the beginning of a line,
+/// such as `message` or `service`.
+fn blocks(keyword: &str) -> Vec<(&'static str, &'static str)> {
+    let prefix = format!("{keyword} ");
+    PROTO
+        .match_indices(&prefix)
+        .filter(|(start, _)| *start == 0 || PROTO[..*start].ends_with('\n'))
+        .filter_map(|(start, _)| {
+            let block = &PROTO[start + prefix.len()..];
+            let open = block.find('{')?;
+            let close = block.find('}')?;
+            Some((block[..open].trim(), &block[open + 1..close]))
+        })
+        .collect()
+}
+
+/// Lines of a block without comments.
+fn statements(body: &'static str) -> impl Iterator<Item = &'static str> {
+    body.lines()
+        .map(|line| line.split("//").next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+}
+
+fn parse_messages() -> Messages {
+    blocks("message")
+        .into_iter()
+        .map(|(name, body)| {
+            let fields = statements(body)
+                .filter_map(|line| {
+                    let decl = line.split('=').next()?;
+                    let mut words = decl.split_whitespace().collect::<Vec<_>>();
+                    let name = words.pop()?;
+                    let ty = words.pop()?;
+                    Some(Field {
+                        name,
+                        ty,
+                        repeated: words.contains(&"repeated"),
+                        optional: words.contains(&"optional"),
+                    })
+                })
+                .collect();
+            (name, fields)
+        })
+        .collect()
+}
+
+/// Rpcs of `service` as names of the rpc, its request and its response.
+fn service_rpcs(service: &str) -> Vec<(&'static str, &'static str, &'static str)> {
+    blocks("service")
+        .into_iter()
+        .filter(|(name, _)| *name == service)
+        .flat_map(|(_, body)| statements(body))
+        .filter_map(|line| {
+            let line = line.strip_prefix("rpc ")?;
+            let (name, rest) = line.split_once('(')?;
+            let (req, rest) = rest.split_once(')')?;
+            let (_, resp) = rest.split_once('(')?;
+            let (resp, _) = resp.split_once(')')?;
+            Some((name.trim(), req.trim(), resp.trim()))
+        })
+        .collect()
+}
+
+fn schema_of_message(name: &str, messages: &Messages) -> Value {
+    let properties = messages
+        .get(name)
+        .map(|fields| {
+            fields
+                .iter()
+                .map(|field| (field.name.to_string(), schema_of_field(field, messages)))
+                .collect::<serde_json::Map<_, _>>()
+        })
+        .unwrap_or_default();
+    json!({ "type": "object", "properties": properties })
+}
+
+fn schema_of_field(field: &Field, messages: &Messages) -> Value {
+    let mut ty = match field.ty {
+        "string" => json!({ "type": "string" }),
+        "bool" => json!({ "type": "boolean" }),
+        "double" | "float" => json!({ "type": "number" }),
+        "bytes" => json!({ "type": "array", "items": { "type": "integer" } }),
+        ty if messages.contains_key(ty) => schema_of_message(ty, messages),
+        _ => json!({ "type": "integer" }),
+    };
+    if field.repeated {
+        return json!({ "type": "array", "items": ty });
+    }
+    // Nested messages of proto3 are optional, like fields marked `optional`.
+    if field.optional || messages.contains_key(field.ty) {
+        ty["type"] = json!([ty["type"].clone(), "null"]);
+    }
+    ty
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::method::Method;
+
+    /// Every method. The match below fails to compile once a method is added, as a reminder
+    /// to list it here.
+    const ALL_METHODS: [Method; 18] = [
+        Method::ConnectPeerViaHttp,
+        Method::ConnectWithDid,
+        Method::ConnectWithSeed,
+        Method::ListPeers,
+        Method::CreateOffer,
+        Method::AnswerOffer,
+        Method::AcceptAnswer,
+        Method::Disconnect,
+        Method::SendCustomMessage,
+        Method::SendBackendMessage,
+        Method::PublishMessageToTopic,
+        Method::FetchTopicMessages,
+        Method::RegisterService,
+        Method::LookupService,
+        Method::NodeInfo,
+        Method::NodeDid,
+        Method::ExportBootstrap,
+        Method::Describe,
+    ];
+
+    fn is_rpc(method: &Method) -> bool {
+        match method {
+            Method::ConnectPeerViaHttp
+            | Method::ConnectWithDid
+            | Method::ConnectWithSeed
+            | Method::ListPeers
+            | Method::CreateOffer
+            | Method::AnswerOffer
+            | Method::AcceptAnswer
+            | Method::Disconnect
+            | Method::SendCustomMessage
+            | Method::SendBackendMessage
+            | Method::PublishMessageToTopic
+            | Method::FetchTopicMessages
+            | Method::RegisterService
+            | Method::LookupService
+            | Method::NodeInfo
+            | Method::NodeDid
+            | Method::ExportBootstrap => true,
+            // Describe is answered by the handler itself.
+            Method::Describe => false,
+        }
+    }
+
+    #[test]
+    fn test_describe_all_methods() {
+        let described = describe_service("InternalService")
+            .into_iter()
+            .map(|m| m.method)
+            .collect::<Vec<_>>();
+        for method in ALL_METHODS.iter() {
+            assert_eq!(
+                described.contains(&method.to_string()),
+                is_rpc(method),
+                "{method:?}"
+            );
+        }
+        for name in described.iter() {
+            assert!(Method::try_from(name.as_str()).is_ok(), "{name}");
+        }
+        assert_eq!(described.len(), ALL_METHODS.len() - 1);
+    }
+
+    #[test]
+    fn test_describe_nested_and_repeated_fields() {
+        let methods = describe_service("InternalService");
+        let describe = |name: &str| {
+            methods
+                .iter()
+                .find(|m| m.method == name)
+                .unwrap_or_else(|| panic!("{name} should be described"))
+                .clone()
+        };
+
+        let seed = describe("connectWithSeed").params_schema;
+        assert_eq!(seed["properties"]["peers"]["type"], "array");
+        assert_eq!(
+            seed["properties"]["peers"]["items"]["properties"]["url"]["type"],
+            "string"
+        );
+
+        let info = describe("nodeInfo").result_schema;
+        let swarm = &info["properties"]["swarm"];
+        assert_eq!(swarm["type"], json!(["object", "null"]));
+        let dht = &swarm["properties"]["dht"]["properties"];
+        assert_eq!(dht["successors"]["items"]["type"], "string");
+        assert_eq!(dht["predecessor"]["type"], json!(["string", "null"]));
+        assert_eq!(
+            dht["finger_table_ranges"]["items"]["properties"]["start"]["type"],
+            "integer"
+        );
+
+        let fetch = describe("fetchTopicMessages").params_schema;
+        assert_eq!(fetch["properties"]["skip"]["type"], "integer");
+    }
+
+    #[test]
+    fn test_describe_services() {
+        let external = describe_service("ExternalService")
+            .into_iter()
+            .map(|m| m.method)
+            .collect::<Vec<_>>();
+        assert_eq!(external, vec!["answerOffer", "nodeInfo", "nodeDid"]);
+        assert!(describe_service("UnknownService").is_empty());
+    }
+}
@@ -1,16 +1,23 @@
 //! rings-rpc client
 
+use rings_core::ecc::SecretKey;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
+use crate::auth::sign_request;
+use crate::auth::SIGNATURE_HEADER;
+use crate::auth::TIMESTAMP_HEADER;
+use crate::describe::MethodDescription;
 use crate::method::Method;
 use crate::prelude::reqwest::Client as HttpClient;
+use crate::prelude::reqwest::Url;
 use crate::protos::rings_node::*;
 
 /// Wrap json_client send request between nodes or browsers.
 pub struct Client {
     client: HttpClient,
     endpoint_url: String,
+    signer: Option<SecretKey>,
 }
 
 /// The errors returned by the client.
@@ -42,6 +49,16 @@ impl Client {
         Self {
             client: HttpClient::default(),
             endpoint_url: endpoint_url.to_string(),
+            signer: None,
+        }
+    }
+
+    /// Creates a new Client instance which signs each request by `signer`,
+    /// for servers requiring signed requests. See [crate::auth].
+    pub fn new_with_signer(endpoint_url: &str, signer: SecretKey) -> Self {
+        Self {
+            signer: Some(signer),
+            ..Self::new(endpoint_url)
         }
     }
 
@@ -69,12 +86,21 @@ impl Client {
     async fn do_jsonrpc_request(&self, req: &jsonrpc_core::Request) -> Result<serde_json::Value> {
         let body = serde_json::to_string(req).map_err(|e| RpcError::Client(e.to_string()))?;
 
-        let req = self
+        let mut req = self
             .client
             .post(self.endpoint_url.as_str())
             .header("content-type", "application/json")
-            .header("accept", "application/json")
-            .body(body);
+            .header("accept", "application/json");
+        if let Some(signer) = &self.signer {
+            let url =
+                Url::parse(&self.endpoint_url).map_err(|e| RpcError::Client(e.to_string()))?;
+            let timestamp_ms = rings_core::utils::get_epoch_ms();
+            let sig = sign_request(signer, timestamp_ms, "POST", url.path(), body.as_bytes());
+            req = req
+                .header(TIMESTAMP_HEADER, timestamp_ms.to_string())
+                .header(SIGNATURE_HEADER, sig);
+        }
+        let req = req.body(body);
 
         let resp = req
             .send()
@@ -211,4 +237,18 @@ impl Client {
     pub async fn node_did(&self, req: &NodeDidRequest) -> Result<NodeDidResponse> {
         self.call_method(Method::NodeDid, req).await
     }
+
+    /// Export dids known by the node, which can be used to bootstrap other nodes.
+    pub async fn export_bootstrap(
+        &self,
+        req: &ExportBootstrapRequest,
+    ) -> Result<ExportBootstrapResponse> {
+        self.call_method(Method::ExportBootstrap, req).await
+    }
+
+    /// Describe params and result of methods supported by the node.
+    pub async fn describe(&self) -> Result<Vec<MethodDescription>> {
+        self.call_method(Method::Describe, &serde_json::json!({}))
+            .await
+    }
 }
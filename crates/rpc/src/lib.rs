@@ -1,4 +1,6 @@
 //! rings rpc library
+pub mod auth;
+pub mod describe;
 pub mod error;
 pub mod jsonrpc;
 pub mod method;
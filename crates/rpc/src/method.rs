@@ -40,6 +40,10 @@ pub enum Method {
     NodeInfo,
     /// Retrieve Node DID
     NodeDid,
+    /// Export known dids for bootstrapping other nodes
+    ExportBootstrap,
+    /// Describe params and result of supported methods
+    Describe,
 }
 
 impl Method {
@@ -62,6 +66,8 @@ impl Method {
             Method::LookupService => "lookupService",
             Method::NodeInfo => "nodeInfo",
             Method::NodeDid => "nodeDid",
+            Method::ExportBootstrap => "exportBootstrap",
+            Method::Describe => "describe",
         }
     }
 }
@@ -93,6 +99,8 @@ impl TryFrom<&str> for Method {
             "lookupService" => Method::LookupService,
             "nodeInfo" => Method::NodeInfo,
             "nodeDid" => Method::NodeDid,
+            "exportBootstrap" => Method::ExportBootstrap,
+            "describe" => Method::Describe,
             _ => return Err(Error::InvalidMethod),
         })
     }
@@ -54,6 +54,7 @@ impl From<ConnectionInspect> for rings_node::PeerInfo {
         rings_node::PeerInfo {
             did: value.did,
             state: value.state,
+            label: value.label,
         }
     }
 }
@@ -6,6 +6,8 @@ pub struct PeerInfo {
     pub did: ::prost::alloc::string::String,
     #[prost(string, tag = "2")]
     pub state: ::prost::alloc::string::String,
+    #[prost(string, optional, tag = "3")]
+    pub label: ::core::option::Option<::prost::alloc::string::String>,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -281,3 +283,14 @@ pub struct NodeDidResponse {
     #[prost(string, tag = "1")]
     pub did: ::prost::alloc::string::String,
 }
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExportBootstrapRequest {}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExportBootstrapResponse {
+    #[prost(string, repeated, tag = "1")]
+    pub dids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
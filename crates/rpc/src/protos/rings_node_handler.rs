@@ -6,6 +6,8 @@ use jsonrpc_core::types::error::ErrorCode;
 use jsonrpc_core::Result;
 
 use super::rings_node::*;
+use crate::describe::describe_service;
+use crate::describe::MethodDescription;
 use crate::method::Method;
 
 /// Used for processor to match rpc request and response.
@@ -25,6 +27,12 @@ pub struct InternalRpcHandler;
 pub struct ExternalRpcHandler;
 
 impl InternalRpcHandler {
+    /// Describe all methods handled by [InternalRpcHandler], returned by [Method::Describe].
+    /// They are the rpcs of `InternalService` in `rings_node.proto`.
+    pub fn describe(&self) -> Vec<MethodDescription> {
+        describe_service("InternalService")
+    }
+
     /// Handle rpc request.
     pub async fn handle_request<P>(
         &self,
@@ -48,7 +56,8 @@ impl InternalRpcHandler {
             + HandleRpc<RegisterServiceRequest, RegisterServiceResponse>
             + HandleRpc<LookupServiceRequest, LookupServiceResponse>
             + HandleRpc<NodeInfoRequest, NodeInfoResponse>
-            + HandleRpc<NodeDidRequest, NodeDidResponse>,
+            + HandleRpc<NodeDidRequest, NodeDidResponse>
+            + HandleRpc<ExportBootstrapRequest, ExportBootstrapResponse>,
     {
         let method = Method::try_from(method.as_str()).map_err(|_| Error {
             code: ErrorCode::MethodNotFound,
@@ -153,11 +162,26 @@ impl InternalRpcHandler {
                 let resp = processor.handle_rpc(req).await?;
                 serde_json::to_value(resp).map_err(|_| Error::new(ErrorCode::ParseError))
             }
+            Method::ExportBootstrap => {
+                let req = serde_json::from_value::<ExportBootstrapRequest>(params)
+                    .map_err(|e| Error::invalid_params(e.to_string()))?;
+                let resp = processor.handle_rpc(req).await?;
+                serde_json::to_value(resp).map_err(|_| Error::new(ErrorCode::ParseError))
+            }
+            Method::Describe => {
+                serde_json::to_value(self.describe()).map_err(|_| Error::new(ErrorCode::ParseError))
+            }
         }
     }
 }
 
 impl ExternalRpcHandler {
+    /// Describe all methods handled by [ExternalRpcHandler], returned by [Method::Describe].
+    /// They are the rpcs of `ExternalService` in `rings_node.proto`.
+    pub fn describe(&self) -> Vec<MethodDescription> {
+        describe_service("ExternalService")
+    }
+
     /// Handle rpc request.
     pub async fn handle_request<P>(
         &self,
@@ -195,6 +219,9 @@ impl ExternalRpcHandler {
                 let resp = processor.handle_rpc(req).await?;
                 serde_json::to_value(resp).map_err(|_| Error::new(ErrorCode::ParseError))
             }
+            Method::Describe => {
+                serde_json::to_value(self.describe()).map_err(|_| Error::new(ErrorCode::ParseError))
+            }
             _ => Err(Error {
                 code: ErrorCode::InvalidRequest,
                 message: format!("method {} is not allowed", method.as_str()),
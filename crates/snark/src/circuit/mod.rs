@@ -189,7 +189,7 @@ impl<F: PrimeField> WasmCircuitGenerator<F> {
                 r1cs: self.r1cs.clone(),
                 witness: witness.clone(),
             };
-            log::trace!("witness: {:?}, r1cs: {:?}", witness, self.r1cs);
+            log::trace!(target: "rings::snark", "witness: {:?}, r1cs: {:?}", witness, self.r1cs);
             latest_output = reshape(&public_input, &circom.get_public_outputs());
             ret.push(circom);
         }
@@ -218,6 +218,21 @@ impl<F: PrimeField> Circuit<F> {
         let output_count = (self.r1cs.num_inputs - 1) / 2;
         self.witness[1 + output_count..self.r1cs.num_inputs].to_vec()
     }
+
+    /// number of public inputs, which is the same as number of public outputs
+    pub fn num_public_inputs(&self) -> usize {
+        (self.r1cs.num_inputs - 1) / 2
+    }
+
+    /// number of constraints of r1cs
+    pub fn num_constraints(&self) -> usize {
+        self.r1cs.constraints.len()
+    }
+
+    /// digest of r1cs, the same for every circuit generated from it, see [R1CS::digest]
+    pub fn r1cs_digest(&self) -> [u8; 32] {
+        self.r1cs.digest()
+    }
 }
 
 /// Implement StepCircuit for our Circuit
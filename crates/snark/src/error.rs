@@ -36,6 +36,22 @@ pub enum Error {
     /// Io Error
     #[error("IO error: {0}")]
     IOError(#[from] std::io::Error),
+    /// Url of download is not http or https
+    #[error("Unsupported url {0}, should be http or https")]
+    UnsupportedUrl(String),
+    /// Downloaded resource exceeds the size limit
+    #[error("Resource at {0} is larger than {1} bytes")]
+    DownloadTooLarge(String, usize),
+    /// Checksum of downloaded resource is not expected
+    #[error("Checksum mismatch of {url}, expected {expected} but got {actual}")]
+    ChecksumMismatch {
+        /// Url of resource
+        url: String,
+        /// Expected SHA-256 in hex
+        expected: String,
+        /// Actual SHA-256 in hex
+        actual: String,
+    },
     /// Error on call nova snark
     #[error("Error on nova snark: {0}")]
     NovaError(#[from] nova_snark::errors::NovaError),
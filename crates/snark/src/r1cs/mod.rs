@@ -6,6 +6,8 @@ use std::io::Cursor;
 use ff::PrimeField;
 use serde::Deserialize;
 use serde::Serialize;
+use sha2::Digest;
+use sha2::Sha256;
 use wasmer::Module;
 
 use crate::error::Error;
@@ -28,6 +30,27 @@ pub struct R1CS<F: PrimeField> {
     pub constraints: Vec<Constraint<F>>,
 }
 
+impl<F: PrimeField> R1CS<F> {
+    /// SHA-256 digest of the shape and constraints of r1cs, which identifies the circuit
+    /// regardless of its witness.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        for n in [self.num_inputs, self.num_aux, self.num_variables] {
+            hasher.update((n as u64).to_le_bytes());
+        }
+        for (a, b, c) in &self.constraints {
+            for lc in [a, b, c] {
+                hasher.update((lc.len() as u64).to_le_bytes());
+                for (i, coeff) in lc {
+                    hasher.update((*i as u64).to_le_bytes());
+                    hasher.update(coeff.to_repr());
+                }
+            }
+        }
+        hasher.finalize().into()
+    }
+}
+
 /// Path of a r1cs
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Path {
@@ -55,14 +78,114 @@ pub(crate) async fn fetch(url: &str) -> Result<Cursor<Vec<u8>>> {
     Ok(Cursor::new(bytes.to_vec()))
 }
 
+/// Max size in bytes of a [Download] by default.
+pub const DEFAULT_MAX_DOWNLOAD_SIZE: usize = 64 * 1024 * 1024;
+
+/// A remote resource to download from a http(s) url, which is checked by size and checksum.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Download {
+    /// Url of the resource, should be http or https.
+    pub url: String,
+    /// Max size in bytes, downloading is aborted once it's exceeded.
+    pub max_size: usize,
+    /// Expected SHA-256 of the resource in hex, not checked if it's None.
+    pub sha256: Option<String>,
+}
+
+impl Download {
+    /// Create a download of url, limited by [DEFAULT_MAX_DOWNLOAD_SIZE].
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            max_size: DEFAULT_MAX_DOWNLOAD_SIZE,
+            sha256: None,
+        }
+    }
+
+    /// Set max size in bytes.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Set expected SHA-256 in hex.
+    pub fn sha256(mut self, sha256: Option<String>) -> Self {
+        self.sha256 = sha256;
+        self
+    }
+
+    /// Download the resource chunk by chunk, then verify its checksum if expected.
+    pub async fn fetch(&self) -> Result<Vec<u8>> {
+        if !(self.url.starts_with("http://") || self.url.starts_with("https://")) {
+            return Err(Error::UnsupportedUrl(self.url.clone()));
+        }
+        let too_large = || Error::DownloadTooLarge(self.url.clone(), self.max_size);
+
+        let resp = reqwest::get(&self.url).await?.error_for_status()?;
+        if resp
+            .content_length()
+            .is_some_and(|len| len > self.max_size as u64)
+        {
+            return Err(too_large());
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let data = {
+            let mut resp = resp;
+            let mut data = vec![];
+            while let Some(chunk) = resp.chunk().await? {
+                if data.len() + chunk.len() > self.max_size {
+                    return Err(too_large());
+                }
+                data.extend_from_slice(&chunk);
+            }
+            data
+        };
+        // Response of browser is not streamed by chunks.
+        #[cfg(target_arch = "wasm32")]
+        let data = {
+            let data = resp.bytes().await?.to_vec();
+            if data.len() > self.max_size {
+                return Err(too_large());
+            }
+            data
+        };
+
+        if let Some(expected) = &self.sha256 {
+            let actual = Sha256::digest(&data)
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<String>();
+            if !actual.eq_ignore_ascii_case(expected.trim()) {
+                return Err(Error::ChecksumMismatch {
+                    url: self.url.clone(),
+                    expected: expected.clone(),
+                    actual,
+                });
+            }
+        }
+        Ok(data)
+    }
+}
+
+/// Load r1cs from bytes, such as downloaded by [Download].
+/// Malformed data is returned as error rather than panic, since the bytes may come from
+/// an untrusted source.
+pub fn load_r1cs_from_bytes<F: PrimeField>(data: Vec<u8>, format: Format) -> Result<R1CS<F>> {
+    let data = Cursor::new(data);
+    match format {
+        Format::Json => reader::try_load_r1cs_from_json::<F, Cursor<Vec<u8>>>(data),
+        Format::Bin => reader::try_load_r1cs_from_bin::<F, Cursor<Vec<u8>>>(data),
+    }
+}
+
 /// Fetch remote r1cs
 pub async fn load_r1cs_remote<F: PrimeField>(url: &str, format: Format) -> Result<R1CS<F>> {
     let data = fetch(url).await?;
-    let ret = match format {
-        Format::Json => reader::load_r1cs_from_json::<F, Cursor<Vec<u8>>>(data),
-        Format::Bin => reader::load_r1cs_from_bin::<F, Cursor<Vec<u8>>>(data),
-    };
-    Ok(ret)
+    match format {
+        Format::Json => reader::try_load_r1cs_from_json::<F, Cursor<Vec<u8>>>(data),
+        Format::Bin => reader::try_load_r1cs_from_bin::<F, Cursor<Vec<u8>>>(data),
+    }
 }
 
 /// Load local r1cs
@@ -70,11 +193,11 @@ pub fn load_r1cs_local<F: PrimeField>(
     path: impl AsRef<std::path::Path>,
     format: Format,
 ) -> Result<R1CS<F>> {
-    let ret = match format {
-        Format::Json => reader::load_r1cs_from_json_file::<F>(path),
-        Format::Bin => reader::load_r1cs_from_bin_file::<F>(path),
-    };
-    Ok(ret)
+    let f = std::io::BufReader::new(std::fs::File::open(path)?);
+    match format {
+        Format::Json => reader::try_load_r1cs_from_json::<F, _>(f),
+        Format::Bin => reader::try_load_r1cs_from_bin::<F, _>(f),
+    }
 }
 
 /// Load r1cs, the resource path can be remote local, and both bin and json are supported
@@ -126,9 +249,14 @@ pub fn load_circom_witness_calculator_local(
 
 /// Load witness calculator from remote path
 pub async fn load_circom_witness_calculator_remote(path: &str) -> Result<WitnessCalculator> {
-    let store = WitnessCalculator::new_store();
     let data = fetch(path).await?;
-    let module = Module::from_binary(&store, data.get_ref().as_slice())?;
+    load_circom_witness_calculator_from_bytes(data.get_ref())
+}
+
+/// Load witness calculator from bytes of wasm, such as downloaded by [Download].
+pub fn load_circom_witness_calculator_from_bytes(data: &[u8]) -> Result<WitnessCalculator> {
+    let store = WitnessCalculator::new_store();
+    let module = Module::from_binary(&store, data)?;
     WitnessCalculator::from_module(module, store)
 }
 
@@ -27,7 +27,6 @@ use byteorder::LittleEndian;
 use byteorder::ReadBytesExt;
 use crypto_bigint::U256;
 use ff::PrimeField;
-use itertools::Itertools;
 use serde::Deserialize;
 use serde::Serialize;
 
@@ -182,8 +181,8 @@ fn read_field<R: Read, Fr: PrimeField>(mut reader: R) -> Result<Fr> {
         // TODO: may need to reverse order?
         *digit = reader.read_u8()?;
     }
-    let fr = Fr::from_repr(repr).unwrap();
-    Ok(fr)
+    Option::from(Fr::from_repr(repr))
+        .ok_or_else(|| Error::InvalidDataWhenReadingR1CS("Field element out of range".to_string()))
 }
 
 fn read_header<R: Read>(mut reader: R, size: u64, expected_prime: &str) -> Result<Header> {
@@ -222,7 +221,8 @@ fn read_constraint_vec<R: Read, Fr: PrimeField>(
     _header: &Header,
 ) -> Result<Vec<(usize, Fr)>> {
     let n_vec = reader.read_u32::<LittleEndian>()? as usize;
-    let mut vec = Vec::with_capacity(n_vec);
+    // n_vec is untrusted, the vec grows as elements are read.
+    let mut vec = vec![];
     for _ in 0..n_vec {
         vec.push((
             reader.read_u32::<LittleEndian>()? as usize,
@@ -234,11 +234,11 @@ fn read_constraint_vec<R: Read, Fr: PrimeField>(
 
 fn read_constraints<R: Read, Fr: PrimeField>(
     mut reader: R,
-    _size: u64,
+    size: u64,
     header: &Header,
 ) -> Result<Vec<Constraint<Fr>>> {
-    // todo check section size
-    let mut vec = Vec::with_capacity(header.n_constraints as usize);
+    // Each constraint takes at least 12 bytes for the lengths of its three vecs.
+    let mut vec = Vec::with_capacity((header.n_constraints as usize).min(size as usize / 12));
     for _ in 0..header.n_constraints {
         vec.push((
             read_constraint_vec::<&mut R, Fr>(&mut reader, header)?,
@@ -259,7 +259,7 @@ fn read_map<R: Read>(mut reader: R, size: u64, header: &Header) -> Result<Vec<u6
     for _ in 0..header.n_wires {
         vec.push(reader.read_u64::<LittleEndian>()?);
     }
-    if vec[0] != 0 {
+    if vec.first() != Some(&0) {
         return Err(Error::InvalidDataWhenReadingR1CS(
             "Wire 0 should always be mapped to 0".to_string(),
         ));
@@ -285,6 +285,9 @@ fn from_reader<Fr: PrimeField, R: Read + Seek>(mut reader: R) -> Result<R1CSFile
     }
 
     let num_sections = reader.read_u32::<LittleEndian>()?;
+    let pos = reader.stream_position()?;
+    let stream_len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(pos))?;
 
     // section type -> file offset
     let mut section_offsets = HashMap::<u32, u64>::new();
@@ -295,6 +298,11 @@ fn from_reader<Fr: PrimeField, R: Read + Seek>(mut reader: R) -> Result<R1CSFile
         let section_type = reader.read_u32::<LittleEndian>()?;
         let section_size = reader.read_u64::<LittleEndian>()?;
         let offset = reader.stream_position()?;
+        if offset.saturating_add(section_size) > stream_len {
+            return Err(Error::InvalidDataWhenReadingR1CS(
+                "Section exceeds the end of data".to_string(),
+            ));
+        }
         section_offsets.insert(section_type, offset);
         section_sizes.insert(section_type, section_size);
         reader.seek(SeekFrom::Current(section_size as i64))?;
@@ -304,12 +312,21 @@ fn from_reader<Fr: PrimeField, R: Read + Seek>(mut reader: R) -> Result<R1CSFile
     let constraint_type = 2;
     let wire2label_type = 3;
 
-    reader.seek(SeekFrom::Start(*section_offsets.get(&header_type).unwrap()))?;
-    let header = read_header(
-        &mut reader,
-        *section_sizes.get(&header_type).unwrap(),
-        Fr::MODULUS,
-    )?;
+    let section = |section_type: u32| -> Result<(u64, u64)> {
+        match (
+            section_offsets.get(&section_type),
+            section_sizes.get(&section_type),
+        ) {
+            (Some(offset), Some(size)) => Ok((*offset, *size)),
+            _ => Err(Error::InvalidDataWhenReadingR1CS(format!(
+                "Missing section {section_type}"
+            ))),
+        }
+    };
+
+    let (offset, size) = section(header_type)?;
+    reader.seek(SeekFrom::Start(offset))?;
+    let header = read_header(&mut reader, size, Fr::MODULUS)?;
     if header.field_size != 32 {
         return Err(Error::InvalidDataWhenReadingR1CS(
             "This parser only supports 32-byte fields".to_string(),
@@ -319,23 +336,13 @@ fn from_reader<Fr: PrimeField, R: Read + Seek>(mut reader: R) -> Result<R1CSFile
     //     return Err(Error::new(ErrorKind::InvalidData, "This parser only supports bn256".to_string()));
     // }
 
-    reader.seek(SeekFrom::Start(
-        *section_offsets.get(&constraint_type).unwrap(),
-    ))?;
-    let constraints = read_constraints::<&mut R, Fr>(
-        &mut reader,
-        *section_sizes.get(&constraint_type).unwrap(),
-        &header,
-    )?;
-
-    reader.seek(SeekFrom::Start(
-        *section_offsets.get(&wire2label_type).unwrap(),
-    ))?;
-    let wire_mapping = read_map(
-        &mut reader,
-        *section_sizes.get(&wire2label_type).unwrap(),
-        &header,
-    )?;
+    let (offset, size) = section(constraint_type)?;
+    reader.seek(SeekFrom::Start(offset))?;
+    let constraints = read_constraints::<&mut R, Fr>(&mut reader, size, &header)?;
+
+    let (offset, size) = section(wire2label_type)?;
+    reader.seek(SeekFrom::Start(offset))?;
+    let wire_mapping = read_map(&mut reader, size, &header)?;
 
     Ok(R1CSFile {
         version,
@@ -347,16 +354,23 @@ fn from_reader<Fr: PrimeField, R: Read + Seek>(mut reader: R) -> Result<R1CSFile
 
 /// load r1cs from bin by a reader
 pub fn load_r1cs_from_bin<Fr: PrimeField, R: Read + Seek>(reader: R) -> R1CS<Fr> {
-    let file = from_reader(reader).expect("unable to read.");
-    let num_inputs = (1 + file.header.n_pub_in + file.header.n_pub_out) as usize;
+    try_load_r1cs_from_bin(reader).expect("unable to read.")
+}
+
+/// load r1cs from bin by a reader, return error on malformed data instead of panic
+pub fn try_load_r1cs_from_bin<Fr: PrimeField, R: Read + Seek>(reader: R) -> Result<R1CS<Fr>> {
+    let file = from_reader(reader)?;
+    let num_inputs = 1 + file.header.n_pub_in as usize + file.header.n_pub_out as usize;
     let num_variables = file.header.n_wires as usize;
-    let num_aux = num_variables - num_inputs;
-    R1CS {
+    let num_aux = num_variables
+        .checked_sub(num_inputs)
+        .ok_or_else(|| Error::InvalidDataWhenReadingR1CS("Fewer wires than inputs".to_string()))?;
+    Ok(R1CS {
         num_aux,
         num_inputs,
         num_variables,
         constraints: file.constraints,
-    }
+    })
 }
 
 /// load r1cs file by filename with autodetect encoding (bin or json)
@@ -379,33 +393,53 @@ pub fn load_r1cs_from_json_file<Fr: PrimeField>(filename: impl AsRef<Path>) -> R
 
 /// load r1cs from json by a reader
 pub fn load_r1cs_from_json<Fr: PrimeField, R: Read>(reader: R) -> R1CS<Fr> {
-    let circuit_json: CircuitJson = serde_json::from_reader(reader).expect("unable to read.");
+    try_load_r1cs_from_json(reader).expect("unable to read.")
+}
+
+/// load r1cs from json by a reader, return error on malformed data instead of panic
+pub fn try_load_r1cs_from_json<Fr: PrimeField, R: Read>(reader: R) -> Result<R1CS<Fr>> {
+    let circuit_json: CircuitJson = serde_json::from_reader(reader)
+        .map_err(|e| Error::InvalidDataWhenReadingR1CS(e.to_string()))?;
 
     let num_inputs = circuit_json.num_inputs + circuit_json.num_outputs + 1;
-    let num_aux = circuit_json.num_variables - num_inputs;
+    let num_aux = circuit_json
+        .num_variables
+        .checked_sub(num_inputs)
+        .ok_or_else(|| Error::InvalidDataWhenReadingR1CS("Fewer wires than inputs".to_string()))?;
 
     let convert_constraint = |lc: &BTreeMap<String, String>| {
         lc.iter()
-            .map(|(index, coeff)| (index.parse().unwrap(), Fr::from_str_vartime(coeff).unwrap()))
-            .collect_vec()
+            .map(|(index, coeff)| {
+                let index = index.parse().map_err(|_| {
+                    Error::InvalidDataWhenReadingR1CS(format!("Invalid index {index}"))
+                })?;
+                let coeff = Fr::from_str_vartime(coeff).ok_or_else(|| {
+                    Error::InvalidDataWhenReadingR1CS(format!("Invalid coefficient {coeff}"))
+                })?;
+                Ok((index, coeff))
+            })
+            .collect::<Result<Vec<_>>>()
     };
 
     let constraints = circuit_json
         .constraints
         .iter()
-        .map(|c| {
-            (
-                convert_constraint(&c[0]),
-                convert_constraint(&c[1]),
-                convert_constraint(&c[2]),
-            )
+        .map(|c| match c.as_slice() {
+            [a, b, c] => Ok((
+                convert_constraint(a)?,
+                convert_constraint(b)?,
+                convert_constraint(c)?,
+            )),
+            _ => Err(Error::InvalidDataWhenReadingR1CS(
+                "Constraint should have 3 linear combinations".to_string(),
+            )),
         })
-        .collect_vec();
+        .collect::<Result<Vec<_>>>()?;
 
-    R1CS {
+    Ok(R1CS {
         num_inputs,
         num_aux,
         num_variables: circuit_json.num_variables,
         constraints,
-    }
+    })
 }
@@ -90,3 +90,19 @@ pub async fn test_load_witness_remote() -> Result<()> {
     assert_eq![witness[0], F::from(1u64)];
     Ok(())
 }
+
+#[test]
+pub fn test_load_malformed_r1cs() {
+    type F = <VestaEngine as Engine>::Scalar;
+    let data = std::fs::read("src/tests/native/circoms/test_sha256.r1cs").unwrap();
+    assert!(r1cs::load_r1cs_from_bytes::<F>(data.clone(), r1cs::Format::Bin).is_ok());
+
+    // Truncated, corrupted or garbage data is an error instead of panic.
+    let truncated = data[..data.len() / 2].to_vec();
+    assert!(r1cs::load_r1cs_from_bytes::<F>(truncated, r1cs::Format::Bin).is_err());
+    let mut corrupted = data.clone();
+    corrupted[12..20].fill(0xff);
+    assert!(r1cs::load_r1cs_from_bytes::<F>(corrupted, r1cs::Format::Bin).is_err());
+    assert!(r1cs::load_r1cs_from_bytes::<F>(b"r1cs".to_vec(), r1cs::Format::Bin).is_err());
+    assert!(r1cs::load_r1cs_from_bytes::<F>(b"{}".to_vec(), r1cs::Format::Json).is_err());
+}
@@ -3,6 +3,7 @@
 use bytes::Bytes;
 
 use crate::core::callback::BoxedTransportCallback;
+use crate::core::transport::IceCandidate;
 use crate::core::transport::TransportMessage;
 use crate::core::transport::WebrtcConnectionState;
 use crate::notifier::Notifier;
@@ -63,6 +64,20 @@ impl InnerTransportCallback {
         }
     }
 
+    /// This method is invoked when the buffered amount of a data channel drops to its low threshold.
+    pub async fn on_buffered_amount_low(&self) {
+        if let Err(e) = self.callback.on_buffered_amount_low(&self.cid).await {
+            tracing::error!("Callback on_buffered_amount_low failed: {e:?}");
+        }
+    }
+
+    /// This method is invoked when a local ICE candidate is gathered.
+    pub async fn on_ice_candidate(&self, candidate: IceCandidate) {
+        if let Err(e) = self.callback.on_ice_candidate(&self.cid, candidate).await {
+            tracing::error!("Callback on_ice_candidate failed: {e:?}");
+        }
+    }
+
     async fn handle_message(&self, msg: &TransportMessage) {
         match msg {
             TransportMessage::Custom(bytes) => {
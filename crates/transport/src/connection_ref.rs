@@ -8,6 +8,8 @@ use serde::de::DeserializeOwned;
 use serde::Serialize;
 
 use crate::core::transport::ConnectionInterface;
+use crate::core::transport::ConnectionStats;
+use crate::core::transport::IceCandidate;
 use crate::core::transport::TransportMessage;
 use crate::core::transport::WebrtcConnectionState;
 use crate::error::Error;
@@ -74,10 +76,21 @@ where
         c.get_stats().await
     }
 
+    async fn stats(&self) -> ConnectionStats {
+        let Ok(c) = self.upgrade() else {
+            return ConnectionStats::default();
+        };
+        c.stats().await
+    }
+
     async fn webrtc_create_offer(&self) -> Result<Self::Sdp> {
         self.upgrade()?.webrtc_create_offer().await
     }
 
+    async fn webrtc_restart_ice(&self) -> Result<Self::Sdp> {
+        self.upgrade()?.webrtc_restart_ice().await
+    }
+
     async fn webrtc_answer_offer(&self, offer: Self::Sdp) -> Result<Self::Sdp> {
         self.upgrade()?.webrtc_answer_offer(offer).await
     }
@@ -86,10 +99,24 @@ where
         self.upgrade()?.webrtc_accept_answer(answer).await
     }
 
+    async fn webrtc_add_ice_candidate(&self, candidate: IceCandidate) -> Result<()> {
+        self.upgrade()?.webrtc_add_ice_candidate(candidate).await
+    }
+
     async fn webrtc_wait_for_data_channel_open(&self) -> Result<()> {
         self.upgrade()?.webrtc_wait_for_data_channel_open().await
     }
 
+    fn webrtc_data_channel_is_open(&self) -> bool {
+        self.upgrade()
+            .map(|c| c.webrtc_data_channel_is_open())
+            .unwrap_or(false)
+    }
+
+    async fn webrtc_buffered_amount(&self) -> Result<usize> {
+        self.upgrade()?.webrtc_buffered_amount().await
+    }
+
     async fn close(&self) -> Result<()> {
         self.upgrade()?.close().await
     }
@@ -122,10 +149,21 @@ where
         c.get_stats().await
     }
 
+    async fn stats(&self) -> ConnectionStats {
+        let Ok(c) = self.upgrade() else {
+            return ConnectionStats::default();
+        };
+        c.stats().await
+    }
+
     async fn webrtc_create_offer(&self) -> Result<Self::Sdp> {
         self.upgrade()?.webrtc_create_offer().await
     }
 
+    async fn webrtc_restart_ice(&self) -> Result<Self::Sdp> {
+        self.upgrade()?.webrtc_restart_ice().await
+    }
+
     async fn webrtc_answer_offer(&self, offer: Self::Sdp) -> Result<Self::Sdp> {
         self.upgrade()?.webrtc_answer_offer(offer).await
     }
@@ -134,10 +172,24 @@ where
         self.upgrade()?.webrtc_accept_answer(answer).await
     }
 
+    async fn webrtc_add_ice_candidate(&self, candidate: IceCandidate) -> Result<()> {
+        self.upgrade()?.webrtc_add_ice_candidate(candidate).await
+    }
+
     async fn webrtc_wait_for_data_channel_open(&self) -> Result<()> {
         self.upgrade()?.webrtc_wait_for_data_channel_open().await
     }
 
+    fn webrtc_data_channel_is_open(&self) -> bool {
+        self.upgrade()
+            .map(|c| c.webrtc_data_channel_is_open())
+            .unwrap_or(false)
+    }
+
+    async fn webrtc_buffered_amount(&self) -> Result<usize> {
+        self.upgrade()?.webrtc_buffered_amount().await
+    }
+
     async fn close(&self) -> Result<()> {
         self.upgrade()?.close().await
     }
@@ -1,3 +1,5 @@
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::time::Duration;
@@ -50,6 +52,8 @@ pub struct DummyConnection {
     remote_rand_id: Arc<Mutex<Option<String>>>,
     event_listener: JoinHandle<()>,
     webrtc_connection_state: Arc<Mutex<WebrtcConnectionState>>,
+    /// Bytes sent to remote but not yet handled by it, simulating `bufferedAmount` of data channel.
+    buffered_amount: Arc<AtomicUsize>,
 }
 
 /// [DummyTransport] manages all the [DummyConnection] and
@@ -81,6 +85,7 @@ impl DummyConnection {
             remote_rand_id: Default::default(),
             event_listener,
             webrtc_connection_state: Arc::new(Mutex::new(WebrtcConnectionState::New)),
+            buffered_amount: Arc::new(AtomicUsize::new(0)),
         }
     }
 
@@ -95,6 +100,13 @@ impl DummyConnection {
                 if SEND_MESSAGE_DELAY {
                     random_delay().await;
                 }
+                if let Some(remote_conn) = self.remote_conn() {
+                    let _ = remote_conn.buffered_amount.fetch_update(
+                        Ordering::SeqCst,
+                        Ordering::SeqCst,
+                        |x| Some(x.saturating_sub(data.len())),
+                    );
+                }
                 self.callback.on_message(&data).await
             }
         }
@@ -147,6 +159,9 @@ impl DummyTransport {
 
         Self { pool: Pool::new() }
     }
+
+    /// Buffered amount is not simulated by dummy connections, so the threshold is ignored.
+    pub fn set_buffered_amount_low_threshold(&mut self, _threshold: Option<usize>) {}
 }
 
 #[async_trait]
@@ -158,6 +173,7 @@ impl ConnectionInterface for DummyConnection {
         self.webrtc_wait_for_data_channel_open().await?;
 
         let data = bincode::serialize(&msg).map(Bytes::from)?;
+        self.buffered_amount.fetch_add(data.len(), Ordering::SeqCst);
         self.remote_conn()
             .unwrap()
             .event_sender
@@ -181,6 +197,10 @@ impl ConnectionInterface for DummyConnection {
         Ok(self.rand_id.clone())
     }
 
+    async fn webrtc_restart_ice(&self) -> Result<Self::Sdp> {
+        Ok(self.rand_id.clone())
+    }
+
     async fn webrtc_answer_offer(&self, offer: Self::Sdp) -> Result<Self::Sdp> {
         // Set remote rand id before setting state so that the remote connection can be found in callback.
         self.set_remote_rand_id(offer);
@@ -219,6 +239,17 @@ impl ConnectionInterface for DummyConnection {
         }
     }
 
+    fn webrtc_data_channel_is_open(&self) -> bool {
+        matches!(
+            self.webrtc_connection_state(),
+            WebrtcConnectionState::Connected | WebrtcConnectionState::Connecting
+        )
+    }
+
+    async fn webrtc_buffered_amount(&self) -> Result<usize> {
+        Ok(self.buffered_amount.load(Ordering::SeqCst))
+    }
+
     async fn close(&self) -> Result<()> {
         CONNS.remove(&self.rand_id);
         self.event_listener.abort();
@@ -0,0 +1,470 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::callback::InnerTransportCallback;
+use crate::connection_ref::ConnectionRef;
+use crate::core::callback::BoxedTransportCallback;
+use crate::core::transport::ConnectionInterface;
+use crate::core::transport::ConnectionStats;
+use crate::core::transport::TransportInterface;
+use crate::core::transport::TransportMessage;
+use crate::core::transport::WebrtcConnectionState;
+use crate::error::Error;
+use crate::error::Result;
+use crate::notifier::Notifier;
+use crate::pool::Pool;
+
+lazy_static! {
+    static ref CONNS: DashMap<String, Arc<LoopbackConnection>> = DashMap::new();
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+enum Event {
+    PeerConnectionStateChange(WebrtcConnectionState),
+    DataChannelOpen,
+    DataChannelClose,
+    BufferedAmountLow,
+    Message(Bytes),
+}
+
+/// A connection to another transport in the same process.
+/// Messages are delivered in order over an in-memory channel, without ICE or any network.
+/// The sdp of offer and answer is the id of the local end.
+pub struct LoopbackConnection {
+    id: String,
+    callback: InnerTransportCallback,
+    event_sender: mpsc::UnboundedSender<Event>,
+    remote_id: Mutex<Option<String>>,
+    event_listener: JoinHandle<()>,
+    webrtc_connection_state: Mutex<WebrtcConnectionState>,
+    /// Whether an offer of this end is waiting for its answer, like the `have-local-offer`
+    /// signaling state of webrtc.
+    local_offer: AtomicBool,
+    /// Bytes sent to remote but not yet handled by it, simulating `bufferedAmount` of data channel.
+    buffered_amount: AtomicUsize,
+    /// Notify `on_buffered_amount_low` once [LoopbackConnection::buffered_amount] drops to it.
+    buffered_amount_low_threshold: Option<usize>,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+}
+
+/// [LoopbackTransport] manages all the [LoopbackConnection] and
+/// provides methods to create, get and close connections.
+pub struct LoopbackTransport {
+    buffered_amount_low_threshold: Option<usize>,
+    pool: Pool<LoopbackConnection>,
+}
+
+impl LoopbackConnection {
+    fn new(callback: InnerTransportCallback, buffered_amount_low_threshold: Option<usize>) -> Self {
+        let id = format!("loopback-{}", NEXT_ID.fetch_add(1, Ordering::SeqCst));
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let event_listener = {
+            let id = id.clone();
+            tokio::spawn(async move {
+                while let Some(ev) = rx.recv().await {
+                    let Some(conn) = CONNS.get(&id).map(|c| c.clone()) else {
+                        break;
+                    };
+                    conn.handle_event(ev).await;
+                }
+            })
+        };
+
+        Self {
+            id,
+            callback,
+            event_sender: tx,
+            remote_id: Mutex::new(None),
+            event_listener,
+            webrtc_connection_state: Mutex::new(WebrtcConnectionState::New),
+            local_offer: AtomicBool::new(false),
+            buffered_amount: AtomicUsize::new(0),
+            buffered_amount_low_threshold,
+            bytes_sent: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+        }
+    }
+
+    async fn handle_event(&self, event: Event) {
+        match event {
+            Event::PeerConnectionStateChange(state) => {
+                self.callback.on_peer_connection_state_change(state).await
+            }
+            Event::DataChannelOpen => self.callback.on_data_channel_open().await,
+            Event::DataChannelClose => self.callback.on_data_channel_close(),
+            Event::BufferedAmountLow => self.callback.on_buffered_amount_low().await,
+            Event::Message(data) => {
+                if let Some(remote_conn) = self.remote_conn() {
+                    remote_conn.drain_buffered_amount(data.len());
+                }
+                self.bytes_received
+                    .fetch_add(data.len() as u64, Ordering::SeqCst);
+                self.callback.on_message(&data).await
+            }
+        }
+    }
+
+    fn remote_conn(&self) -> Option<Arc<LoopbackConnection>> {
+        let id = self.remote_id.lock().unwrap().clone()?;
+        CONNS.get(&id).map(|c| c.clone())
+    }
+
+    /// Decrease buffered amount by bytes handled by remote, and notify if it drops to the
+    /// low threshold.
+    fn drain_buffered_amount(&self, len: usize) {
+        let Ok(prev) = self
+            .buffered_amount
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |x| {
+                Some(x.saturating_sub(len))
+            })
+        else {
+            return;
+        };
+        if let Some(threshold) = self.buffered_amount_low_threshold {
+            if prev > threshold && prev.saturating_sub(len) <= threshold {
+                self.send_event(Event::BufferedAmountLow);
+            }
+        }
+    }
+
+    fn set_remote_id(&self, id: String) {
+        *self.remote_id.lock().unwrap() = Some(id);
+    }
+
+    /// Fail both ends of the connection as if ICE failed, so that ICE restart can be tested.
+    /// The connection is connected again once an ICE restart offer is answered and accepted.
+    pub fn simulate_ice_failure(&self) {
+        self.set_webrtc_connection_state(WebrtcConnectionState::Failed);
+        if let Some(remote_conn) = self.remote_conn() {
+            remote_conn.set_webrtc_connection_state(WebrtcConnectionState::Failed);
+        }
+    }
+
+    fn send_event(&self, event: Event) {
+        // The listener is gone only if the connection is closed.
+        let _ = self.event_sender.send(event);
+    }
+
+    fn set_webrtc_connection_state(&self, state: WebrtcConnectionState) {
+        {
+            let mut webrtc_connection_state = self.webrtc_connection_state.lock().unwrap();
+
+            if state == *webrtc_connection_state {
+                return;
+            }
+
+            *webrtc_connection_state = state;
+        }
+
+        self.send_event(Event::PeerConnectionStateChange(state));
+
+        if state == WebrtcConnectionState::Connected {
+            self.send_event(Event::DataChannelOpen);
+        }
+
+        if matches!(
+            state,
+            WebrtcConnectionState::Closed | WebrtcConnectionState::Disconnected
+        ) {
+            self.send_event(Event::DataChannelClose);
+        }
+    }
+}
+
+impl ConnectionRef<LoopbackConnection> {
+    /// See [LoopbackConnection::simulate_ice_failure].
+    pub fn simulate_ice_failure(&self) -> Result<()> {
+        self.upgrade()?.simulate_ice_failure();
+        Ok(())
+    }
+}
+
+impl LoopbackTransport {
+    /// Create a new [LoopbackTransport] instance.
+    /// Ice servers and external address are ignored since there is no ICE.
+    pub fn new(_ice_servers: &str, _external_address: Option<String>) -> Self {
+        Self {
+            buffered_amount_low_threshold: None,
+            pool: Pool::new(),
+        }
+    }
+
+    /// Set the low threshold of buffered amount of connections created later, like
+    /// `bufferedAmountLowThreshold` of data channel.
+    /// It's not notified if the threshold is None, which is the default.
+    pub fn set_buffered_amount_low_threshold(&mut self, threshold: Option<usize>) {
+        self.buffered_amount_low_threshold = threshold;
+    }
+}
+
+#[async_trait]
+impl ConnectionInterface for LoopbackConnection {
+    type Sdp = String;
+    type Error = Error;
+
+    async fn send_message(&self, msg: TransportMessage) -> Result<()> {
+        self.webrtc_wait_for_data_channel_open().await?;
+
+        let remote_conn = self
+            .remote_conn()
+            .ok_or_else(|| Error::ConnectionNotFound(self.id.clone()))?;
+
+        let data = bincode::serialize(&msg).map(Bytes::from)?;
+        self.buffered_amount.fetch_add(data.len(), Ordering::SeqCst);
+        self.bytes_sent
+            .fetch_add(data.len() as u64, Ordering::SeqCst);
+        remote_conn.send_event(Event::Message(data));
+
+        Ok(())
+    }
+
+    fn webrtc_connection_state(&self) -> WebrtcConnectionState {
+        *self.webrtc_connection_state.lock().unwrap()
+    }
+
+    async fn get_stats(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    async fn stats(&self) -> ConnectionStats {
+        ConnectionStats {
+            bytes_sent: self.bytes_sent.load(Ordering::SeqCst),
+            bytes_received: self.bytes_received.load(Ordering::SeqCst),
+            ..Default::default()
+        }
+    }
+
+    async fn webrtc_create_offer(&self) -> Result<Self::Sdp> {
+        self.set_webrtc_connection_state(WebrtcConnectionState::New);
+        self.local_offer.store(true, Ordering::SeqCst);
+        Ok(self.id.clone())
+    }
+
+    async fn webrtc_restart_ice(&self) -> Result<Self::Sdp> {
+        // The state is kept until the answer is accepted, like ICE checking on the old one.
+        self.local_offer.store(true, Ordering::SeqCst);
+        Ok(self.id.clone())
+    }
+
+    async fn webrtc_answer_offer(&self, offer: Self::Sdp) -> Result<Self::Sdp> {
+        if self.local_offer.load(Ordering::SeqCst) {
+            return Err(Error::InvalidSignalingState(
+                "have-local-offer when answering offer".to_string(),
+            ));
+        }
+        if !CONNS.contains_key(&offer) {
+            return Err(Error::ConnectionNotFound(offer));
+        }
+        // Set remote id before setting state so that the remote connection can be found in callback.
+        self.set_remote_id(offer);
+        self.set_webrtc_connection_state(WebrtcConnectionState::Connecting);
+        Ok(self.id.clone())
+    }
+
+    async fn webrtc_accept_answer(&self, answer: Self::Sdp) -> Result<()> {
+        if !self.local_offer.load(Ordering::SeqCst) {
+            return Err(Error::InvalidSignalingState(
+                "no local offer when accepting answer".to_string(),
+            ));
+        }
+        let remote_conn = CONNS
+            .get(&answer)
+            .map(|c| c.clone())
+            .ok_or_else(|| Error::ConnectionNotFound(answer.clone()))?;
+
+        // Set remote id before setting state so that the remote connection can be found in callback.
+        self.set_remote_id(answer);
+        self.local_offer.store(false, Ordering::SeqCst);
+        self.set_webrtc_connection_state(WebrtcConnectionState::Connected);
+        remote_conn.set_webrtc_connection_state(WebrtcConnectionState::Connected);
+
+        Ok(())
+    }
+
+    async fn webrtc_wait_for_data_channel_open(&self) -> Result<()> {
+        // The connecting state means an offer is answered but not accepted by the other side yet.
+        // Messages sent in this state are queued by the remote and handled in order.
+        if matches!(
+            self.webrtc_connection_state(),
+            WebrtcConnectionState::Connected | WebrtcConnectionState::Connecting
+        ) {
+            Ok(())
+        } else {
+            Err(Error::DataChannelOpen(
+                "State is not connected in loopback connection".to_string(),
+            ))
+        }
+    }
+
+    fn webrtc_data_channel_is_open(&self) -> bool {
+        matches!(
+            self.webrtc_connection_state(),
+            WebrtcConnectionState::Connected | WebrtcConnectionState::Connecting
+        )
+    }
+
+    async fn webrtc_buffered_amount(&self) -> Result<usize> {
+        Ok(self.buffered_amount.load(Ordering::SeqCst))
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.set_webrtc_connection_state(WebrtcConnectionState::Closed);
+        CONNS.remove(&self.id);
+        self.event_listener.abort();
+
+        if let Some(remote_conn) = self.remote_conn() {
+            if remote_conn.webrtc_connection_state() != WebrtcConnectionState::Closed {
+                remote_conn.set_webrtc_connection_state(WebrtcConnectionState::Disconnected);
+                remote_conn.set_webrtc_connection_state(WebrtcConnectionState::Closed);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TransportInterface for LoopbackTransport {
+    type Connection = LoopbackConnection;
+    type Error = Error;
+
+    async fn new_connection(&self, cid: &str, callback: BoxedTransportCallback) -> Result<()> {
+        if let Ok(existed_conn) = self.pool.connection(cid) {
+            if matches!(
+                existed_conn.webrtc_connection_state(),
+                WebrtcConnectionState::New
+                    | WebrtcConnectionState::Connecting
+                    | WebrtcConnectionState::Connected
+            ) {
+                return Err(Error::ConnectionAlreadyExists(cid.to_string()));
+            }
+        }
+
+        let inner_callback = InnerTransportCallback::new(cid, callback, Notifier::default());
+        let conn = LoopbackConnection::new(inner_callback, self.buffered_amount_low_threshold);
+
+        self.pool.safely_insert(cid, conn)?;
+
+        let conn = self.connection(cid)?.upgrade()?;
+        CONNS.insert(conn.id.clone(), conn);
+
+        Ok(())
+    }
+
+    async fn close_connection(&self, cid: &str) -> Result<()> {
+        self.pool.safely_remove(cid).await
+    }
+
+    fn connection(&self, cid: &str) -> Result<ConnectionRef<Self::Connection>> {
+        self.pool.connection(cid)
+    }
+
+    fn connections(&self) -> Vec<(String, ConnectionRef<Self::Connection>)> {
+        self.pool.connections()
+    }
+
+    fn connection_ids(&self) -> Vec<String> {
+        self.pool.connection_ids()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::sync::Semaphore;
+
+    use super::*;
+    use crate::core::callback::TransportCallback;
+
+    type CallbackResult = std::result::Result<(), Box<dyn std::error::Error>>;
+
+    /// Handle a message only when a permit of gate is available.
+    struct GatedCallback {
+        gate: Arc<Semaphore>,
+    }
+
+    #[async_trait]
+    impl TransportCallback for GatedCallback {
+        async fn on_message(&self, _cid: &str, _msg: &[u8]) -> CallbackResult {
+            self.gate.acquire().await.unwrap().forget();
+            Ok(())
+        }
+    }
+
+    struct DrainedCallback {
+        drained: mpsc::UnboundedSender<String>,
+    }
+
+    #[async_trait]
+    impl TransportCallback for DrainedCallback {
+        async fn on_buffered_amount_low(&self, cid: &str) -> CallbackResult {
+            self.drained.send(cid.to_string()).unwrap();
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_buffered_amount_low() {
+        let (drained_tx, mut drained_rx) = mpsc::unbounded_channel();
+        let gate = Arc::new(Semaphore::new(0));
+
+        let mut transport1 = LoopbackTransport::new("", None);
+        transport1.set_buffered_amount_low_threshold(Some(256));
+        let transport2 = LoopbackTransport::new("", None);
+        transport1
+            .new_connection(
+                "peer2",
+                Box::new(DrainedCallback {
+                    drained: drained_tx,
+                }),
+            )
+            .await
+            .unwrap();
+        transport2
+            .new_connection("peer1", Box::new(GatedCallback { gate: gate.clone() }))
+            .await
+            .unwrap();
+        let conn1 = transport1.connection("peer2").unwrap();
+        let conn2 = transport2.connection("peer1").unwrap();
+
+        let offer = conn1.webrtc_create_offer().await.unwrap();
+        let answer = conn2.webrtc_answer_offer(offer).await.unwrap();
+        conn1.webrtc_accept_answer(answer).await.unwrap();
+
+        // Fill the buffer while the remote doesn't handle anything.
+        for _ in 0..8 {
+            let msg = TransportMessage::Custom(vec![0; 100]);
+            conn1.send_message(msg).await.unwrap();
+        }
+        assert!(conn1.webrtc_buffered_amount().await.unwrap() > 800);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(drained_rx.try_recv().is_err());
+
+        // Draining to the threshold is notified once.
+        gate.add_permits(8);
+        let cid = tokio::time::timeout(Duration::from_secs(1), drained_rx.recv())
+            .await
+            .expect("drain is not notified")
+            .unwrap();
+        assert_eq!(cid, "peer2");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(conn1.webrtc_buffered_amount().await.unwrap(), 0);
+        assert!(drained_rx.try_recv().is_err());
+    }
+}
@@ -1,9 +1,12 @@
 //! Default using `WebrtcConnection` for native environment.
 //! Plus a `WebSysWebrtcConnection` for wasm environment.
 //! Also provide a `DummyConnection` for testing.
+//! And a `LoopbackConnection` connecting transports in the same process without ICE.
 
 #[cfg(feature = "dummy")]
 mod dummy;
+#[cfg(feature = "loopback")]
+mod loopback;
 #[cfg(feature = "native-webrtc")]
 mod native_webrtc;
 #[cfg(feature = "web-sys-webrtc")]
@@ -13,6 +16,10 @@ mod web_sys_webrtc;
 pub use crate::connections::dummy::DummyConnection;
 #[cfg(feature = "dummy")]
 pub use crate::connections::dummy::DummyTransport;
+#[cfg(feature = "loopback")]
+pub use crate::connections::loopback::LoopbackConnection;
+#[cfg(feature = "loopback")]
+pub use crate::connections::loopback::LoopbackTransport;
 #[cfg(feature = "native-webrtc")]
 pub use crate::connections::native_webrtc::WebrtcConnection;
 #[cfg(feature = "native-webrtc")]
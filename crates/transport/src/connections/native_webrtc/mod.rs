@@ -1,4 +1,5 @@
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use bytes::Bytes;
@@ -7,13 +8,20 @@ use webrtc::data_channel::data_channel_message::DataChannelMessage;
 use webrtc::data_channel::data_channel_state::RTCDataChannelState;
 use webrtc::data_channel::RTCDataChannel;
 use webrtc::ice::mdns::MulticastDnsMode;
+use webrtc::ice_transport::ice_candidate::RTCIceCandidate;
+use webrtc::ice_transport::ice_candidate::RTCIceCandidateInit;
 use webrtc::ice_transport::ice_candidate_type::RTCIceCandidateType;
 use webrtc::ice_transport::ice_credential_type::RTCIceCredentialType;
 use webrtc::ice_transport::ice_server::RTCIceServer;
 use webrtc::peer_connection::configuration::RTCConfiguration;
+use webrtc::peer_connection::offer_answer_options::RTCOfferOptions;
 use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
+use webrtc::peer_connection::policy::bundle_policy::RTCBundlePolicy;
+use webrtc::peer_connection::policy::ice_transport_policy::RTCIceTransportPolicy;
 use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
+use webrtc::peer_connection::signaling_state::RTCSignalingState;
 use webrtc::peer_connection::RTCPeerConnection;
+use webrtc::stats::StatsReportType;
 
 use crate::callback::InnerTransportCallback;
 use crate::connection_ref::ConnectionRef;
@@ -23,15 +31,21 @@ use crate::core::pool::RoundRobin;
 use crate::core::pool::RoundRobinPool;
 use crate::core::pool::StatusPool;
 use crate::core::transport::ConnectionInterface;
+use crate::core::transport::ConnectionStats;
+use crate::core::transport::IceCandidate;
 use crate::core::transport::TransportInterface;
 use crate::core::transport::TransportMessage;
 use crate::core::transport::WebrtcConnectionState;
 use crate::error::Error;
 use crate::error::Result;
+use crate::ice_server::ungathered_urls;
 use crate::ice_server::IceCredentialType;
 use crate::ice_server::IceServer;
 use crate::notifier::Notifier;
 use crate::pool::Pool;
+use crate::rtc_config::BundlePolicy;
+use crate::rtc_config::IceTransportPolicy;
+use crate::rtc_config::RtcConfig;
 
 const WEBRTC_WAIT_FOR_DATA_CHANNEL_OPEN_TIMEOUT: u8 = 8; // seconds
 const WEBRTC_GATHER_TIMEOUT: u8 = 60; // seconds
@@ -66,6 +80,10 @@ pub struct WebrtcConnection {
     webrtc_data_channel: Arc<RoundRobinPool<Arc<RTCDataChannel>>>,
     webrtc_data_channel_state_notifier: Notifier,
     cancel_token: CancellationToken,
+    trickle_ice: bool,
+    /// Max time waiting for ICE candidates gathering, and ice servers to blame if it times out.
+    gather_timeout: Duration,
+    ice_servers: Vec<IceServer>,
 }
 
 /// [WebrtcTransport] manages all the [WebrtcConnection] and
@@ -73,6 +91,11 @@ pub struct WebrtcConnection {
 pub struct WebrtcTransport {
     ice_servers: Vec<IceServer>,
     external_address: Option<String>,
+    trickle_ice: bool,
+    disable_mdns: bool,
+    buffered_amount_low_threshold: Option<usize>,
+    rtc_config: RtcConfig,
+    gather_timeout: Duration,
     pool: Pool<WebrtcConnection>,
 }
 
@@ -81,29 +104,64 @@ impl WebrtcConnection {
         webrtc_conn: RTCPeerConnection,
         webrtc_data_channel: Arc<RoundRobinPool<Arc<RTCDataChannel>>>,
         webrtc_data_channel_state_notifier: Notifier,
+        trickle_ice: bool,
+        gather_timeout: Duration,
+        ice_servers: Vec<IceServer>,
     ) -> Self {
         Self {
             webrtc_conn,
             webrtc_data_channel,
             webrtc_data_channel_state_notifier,
             cancel_token: CancellationToken::new(),
+            trickle_ice,
+            gather_timeout,
+            ice_servers,
         }
     }
 
+    /// Get local sdp. Without trickle ICE, wait for all the candidates gathered, so that
+    /// they are included in sdp. If gathering is not completed in `gather_timeout`, the sdp
+    /// carries the candidates gathered so far.
     async fn webrtc_gather(&self) -> Result<String> {
+        if self.trickle_ice {
+            return self.local_sdp().await;
+        }
+
         let mut gathering_complete_promise = self.webrtc_conn.gathering_complete_promise().await;
-        let gathering_complete_promise_with_timeout = tokio::time::timeout(
-            std::time::Duration::from_secs(WEBRTC_GATHER_TIMEOUT.into()),
-            gathering_complete_promise.recv(),
-        );
+        let gathering_complete_promise_with_timeout =
+            tokio::time::timeout(self.gather_timeout, gathering_complete_promise.recv());
 
-        tokio::select! {
+        let timed_out = tokio::select! {
             _ = self.cancel_token.cancelled() => {
                 return Err(Error::WebrtcLocalSdpGenerationError("Local connection closed".to_string()))
             }
-            _ = gathering_complete_promise_with_timeout => {}
+            res = gathering_complete_promise_with_timeout => res.is_err()
+        };
+
+        let sdp = self.local_sdp().await?;
+        if timed_out {
+            tracing::warn!(
+                "ICE gathering is not completed in {:?}, continue with gathered candidates, timed out servers: {:?}",
+                self.gather_timeout,
+                ungathered_urls(&self.ice_servers, &sdp)
+            );
+        }
+        Ok(sdp)
+    }
+
+    /// Check the signaling state before applying a remote sdp, so that an sdp applied in a
+    /// wrong order returns [Error::InvalidSignalingState].
+    fn check_signaling_state(&self, expected: RTCSignalingState) -> Result<()> {
+        let state = self.webrtc_conn.signaling_state();
+        if state != expected {
+            return Err(Error::InvalidSignalingState(format!(
+                "{state}, expected {expected}"
+            )));
         }
+        Ok(())
+    }
 
+    async fn local_sdp(&self) -> Result<String> {
         Ok(self
             .webrtc_conn
             .local_description()
@@ -123,9 +181,70 @@ impl WebrtcTransport {
         Self {
             ice_servers,
             external_address,
+            trickle_ice: false,
+            disable_mdns: true,
+            buffered_amount_low_threshold: None,
+            rtc_config: RtcConfig::default(),
+            gather_timeout: Duration::from_secs(WEBRTC_GATHER_TIMEOUT.into()),
             pool: Pool::new(),
         }
     }
+
+    /// Enable or disable trickle ICE of connections created later.
+    /// With trickle ICE, offer and answer are returned without waiting for candidates gathering,
+    /// and each candidate is notified by `on_ice_candidate` of
+    /// [TransportCallback](crate::core::callback::TransportCallback) once gathered.
+    pub fn set_trickle_ice(&mut self, trickle_ice: bool) {
+        self.trickle_ice = trickle_ice;
+    }
+
+    /// Disable or enable mDNS of connections created later, it's disabled by default.
+    /// When disabled, the ICE agent neither binds the mDNS port nor resolves `.local`
+    /// candidates of peers. When enabled, `.local` candidates of peers are resolved, but
+    /// local candidates are still gathered with their IP addresses.
+    pub fn set_disable_mdns(&mut self, disable_mdns: bool) {
+        self.disable_mdns = disable_mdns;
+    }
+
+    /// Set the low threshold of buffered amount of data channels created later.
+    /// Once the buffered amount of a data channel drops to it, `on_buffered_amount_low` of
+    /// [TransportCallback](crate::core::callback::TransportCallback) is invoked.
+    /// It's not notified if the threshold is None, which is the default.
+    pub fn set_buffered_amount_low_threshold(&mut self, threshold: Option<usize>) {
+        self.buffered_amount_low_threshold = threshold;
+    }
+
+    /// Set the options of RTCConfiguration of connections created later, such as
+    /// [IceTransportPolicy::Relay] to force all traffic through TURN servers.
+    pub fn set_rtc_config(&mut self, rtc_config: RtcConfig) {
+        self.rtc_config = rtc_config;
+    }
+
+    /// Set the max time waiting for ICE candidates gathering of offers and answers, which is
+    /// 60 seconds by default. Once it's reached, the offer or answer carries the candidates
+    /// gathered so far, and ice servers which may have timed out are logged.
+    /// It has no effect with trickle ICE.
+    pub fn set_gather_timeout(&mut self, timeout: Duration) {
+        self.gather_timeout = timeout;
+    }
+
+    fn webrtc_config(&self) -> RTCConfiguration {
+        RTCConfiguration {
+            ice_servers: self.ice_servers.iter().cloned().map(|x| x.into()).collect(),
+            bundle_policy: self.rtc_config.bundle_policy.into(),
+            ice_transport_policy: self.rtc_config.ice_transport_policy.into(),
+            ice_candidate_pool_size: self.rtc_config.ice_candidate_pool_size,
+            ..Default::default()
+        }
+    }
+
+    fn mdns_mode(&self) -> MulticastDnsMode {
+        if self.disable_mdns {
+            MulticastDnsMode::Disabled
+        } else {
+            MulticastDnsMode::QueryOnly
+        }
+    }
 }
 
 #[async_trait]
@@ -148,6 +267,41 @@ impl ConnectionInterface for WebrtcConnection {
             .collect()
     }
 
+    async fn stats(&self) -> ConnectionStats {
+        let reports = self.webrtc_conn.get_stats().await.reports;
+
+        let Some(pair) = reports
+            .values()
+            .filter_map(|r| match r {
+                StatsReportType::CandidatePair(p) => Some(p),
+                _ => None,
+            })
+            .max_by_key(|p| (p.nominated, p.bytes_sent + p.bytes_received))
+        else {
+            return ConnectionStats::default();
+        };
+
+        let address = |id: &str| match reports.get(id) {
+            Some(StatsReportType::LocalCandidate(c) | StatsReportType::RemoteCandidate(c)) => {
+                format!("{}:{}", c.ip, c.port)
+            }
+            _ => id.to_string(),
+        };
+
+        ConnectionStats {
+            bytes_sent: pair.bytes_sent,
+            bytes_received: pair.bytes_received,
+            packets_lost: pair.packets_discarded_on_send as u64,
+            current_rtt: (pair.current_round_trip_time > 0.0)
+                .then(|| std::time::Duration::from_secs_f64(pair.current_round_trip_time)),
+            selected_candidate_pair: Some(format!(
+                "{} -> {}",
+                address(&pair.local_candidate_id),
+                address(&pair.remote_candidate_id)
+            )),
+        }
+    }
+
     fn webrtc_connection_state(&self) -> WebrtcConnectionState {
         self.webrtc_conn.connection_state().into()
     }
@@ -161,8 +315,22 @@ impl ConnectionInterface for WebrtcConnection {
         self.webrtc_gather().await
     }
 
+    async fn webrtc_restart_ice(&self) -> Result<Self::Sdp> {
+        let options = RTCOfferOptions {
+            ice_restart: true,
+            ..Default::default()
+        };
+        let setting_offer = self.webrtc_conn.create_offer(Some(options)).await?;
+        self.webrtc_conn
+            .set_local_description(setting_offer.clone())
+            .await?;
+
+        self.webrtc_gather().await
+    }
+
     async fn webrtc_answer_offer(&self, offer: Self::Sdp) -> Result<Self::Sdp> {
         tracing::debug!("webrtc_answer_offer, offer: {offer:?}");
+        self.check_signaling_state(RTCSignalingState::Stable)?;
         let offer = RTCSessionDescription::offer(offer)?;
         self.webrtc_conn.set_remote_description(offer).await?;
 
@@ -176,6 +344,7 @@ impl ConnectionInterface for WebrtcConnection {
 
     async fn webrtc_accept_answer(&self, answer: Self::Sdp) -> Result<()> {
         tracing::debug!("webrtc_accept_answer, answer: {answer:?}");
+        self.check_signaling_state(RTCSignalingState::HaveLocalOffer)?;
         let answer = RTCSessionDescription::answer(answer)?;
         self.webrtc_conn
             .set_remote_description(answer)
@@ -183,6 +352,19 @@ impl ConnectionInterface for WebrtcConnection {
             .map_err(|e| e.into())
     }
 
+    async fn webrtc_add_ice_candidate(&self, candidate: IceCandidate) -> Result<()> {
+        tracing::debug!("webrtc_add_ice_candidate, candidate: {candidate:?}");
+        self.webrtc_conn
+            .add_ice_candidate(RTCIceCandidateInit {
+                candidate: candidate.candidate,
+                sdp_mid: candidate.sdp_mid,
+                sdp_mline_index: candidate.sdp_mline_index,
+                username_fragment: None,
+            })
+            .await
+            .map_err(|e| e.into())
+    }
+
     async fn webrtc_wait_for_data_channel_open(&self) -> Result<()> {
         if matches!(
             self.webrtc_connection_state(),
@@ -210,6 +392,23 @@ impl ConnectionInterface for WebrtcConnection {
         }
     }
 
+    fn webrtc_data_channel_is_open(&self) -> bool {
+        !matches!(
+            self.webrtc_connection_state(),
+            WebrtcConnectionState::Failed
+                | WebrtcConnectionState::Closed
+                | WebrtcConnectionState::Disconnected
+        ) && self.webrtc_data_channel.all_ready().unwrap_or(false)
+    }
+
+    async fn webrtc_buffered_amount(&self) -> Result<usize> {
+        let mut amount = 0;
+        for channel in self.webrtc_data_channel.items()? {
+            amount += channel.buffered_amount().await;
+        }
+        Ok(amount)
+    }
+
     async fn close(&self) -> Result<()> {
         self.cancel_token.cancel();
         self.webrtc_conn.close().await.map_err(|e| e.into())
@@ -236,21 +435,14 @@ impl TransportInterface for WebrtcTransport {
         //
         // Setup webrtc connection env
         //
-        let ice_servers = self.ice_servers.iter().cloned().map(|x| x.into()).collect();
-
-        let webrtc_config = RTCConfiguration {
-            ice_servers,
-            ..Default::default()
-        };
+        let webrtc_config = self.webrtc_config();
 
         let mut setting = webrtc::api::setting_engine::SettingEngine::default();
         if let Some(ref addr) = self.external_address {
             tracing::debug!("setting external ip {:?}", addr);
             setting.set_nat_1to1_ips(vec![addr.to_string()], RTCIceCandidateType::Host);
-            setting.set_ice_multicast_dns_mode(MulticastDnsMode::Disabled);
-        } else {
-            setting.set_ice_multicast_dns_mode(MulticastDnsMode::Disabled);
         }
+        setting.set_ice_multicast_dns_mode(self.mdns_mode());
 
         let webrtc_api = webrtc::api::APIBuilder::new()
             .with_setting_engine(setting)
@@ -325,6 +517,32 @@ impl TransportInterface for WebrtcTransport {
             })
         }));
 
+        if self.trickle_ice {
+            let ice_candidate_inner_cb = inner_cb.clone();
+            webrtc_conn.on_ice_candidate(Box::new(move |c: Option<RTCIceCandidate>| {
+                let inner_cb = ice_candidate_inner_cb.clone();
+
+                Box::pin(async move {
+                    // None means gathering is complete.
+                    let Some(c) = c else {
+                        return;
+                    };
+                    match c.to_json() {
+                        Ok(init) => {
+                            inner_cb
+                                .on_ice_candidate(IceCandidate {
+                                    candidate: init.candidate,
+                                    sdp_mid: init.sdp_mid,
+                                    sdp_mline_index: init.sdp_mline_index,
+                                })
+                                .await
+                        }
+                        Err(e) => tracing::error!("Failed to serialize ice candidate: {e:?}"),
+                    }
+                })
+            }));
+        }
+
         //
         // Create data channel
         //
@@ -332,6 +550,17 @@ impl TransportInterface for WebrtcTransport {
             let ch = webrtc_conn
                 .create_data_channel(&format!("rings_data_channel_{}", i), None)
                 .await?;
+            if let Some(threshold) = self.buffered_amount_low_threshold {
+                ch.set_buffered_amount_low_threshold(threshold).await;
+                let buffered_amount_low_inner_cb = inner_cb.clone();
+                ch.on_buffered_amount_low(Box::new(move || {
+                    let inner_cb = buffered_amount_low_inner_cb.clone();
+                    Box::pin(async move {
+                        inner_cb.on_buffered_amount_low().await;
+                    })
+                }))
+                .await;
+            }
             channel_pool.push(ch)?;
         }
 
@@ -342,6 +571,9 @@ impl TransportInterface for WebrtcTransport {
             webrtc_conn,
             channel_pool,
             webrtc_data_channel_state_notifier,
+            self.trickle_ice,
+            self.gather_timeout,
+            self.ice_servers.clone(),
         );
 
         self.pool.safely_insert(cid, conn)?;
@@ -385,6 +617,25 @@ impl From<IceServer> for RTCIceServer {
     }
 }
 
+impl From<BundlePolicy> for RTCBundlePolicy {
+    fn from(s: BundlePolicy) -> Self {
+        match s {
+            BundlePolicy::Balanced => Self::Balanced,
+            BundlePolicy::MaxCompat => Self::MaxCompat,
+            BundlePolicy::MaxBundle => Self::MaxBundle,
+        }
+    }
+}
+
+impl From<IceTransportPolicy> for RTCIceTransportPolicy {
+    fn from(s: IceTransportPolicy) -> Self {
+        match s {
+            IceTransportPolicy::All => Self::All,
+            IceTransportPolicy::Relay => Self::Relay,
+        }
+    }
+}
+
 impl From<RTCPeerConnectionState> for WebrtcConnectionState {
     fn from(s: RTCPeerConnectionState) -> Self {
         match s {
@@ -398,3 +649,159 @@ impl From<RTCPeerConnectionState> for WebrtcConnectionState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::core::callback::TransportCallback;
+
+    struct CandidateCollector(mpsc::UnboundedSender<IceCandidate>);
+
+    #[async_trait]
+    impl TransportCallback for CandidateCollector {
+        async fn on_ice_candidate(
+            &self,
+            _cid: &str,
+            candidate: IceCandidate,
+        ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+            self.0.send(candidate).unwrap();
+            Ok(())
+        }
+    }
+
+    fn trickle_transport() -> WebrtcTransport {
+        let mut transport = WebrtcTransport::new("stun://stun.l.google.com:19302", None);
+        transport.set_trickle_ice(true);
+        transport
+    }
+
+    #[tokio::test]
+    async fn test_trickle_ice_with_delayed_candidates() {
+        let transport1 = trickle_transport();
+        let transport2 = trickle_transport();
+        let (tx1, mut rx1) = mpsc::unbounded_channel();
+        let (tx2, mut rx2) = mpsc::unbounded_channel();
+
+        transport1
+            .new_connection("conn2", Box::new(CandidateCollector(tx1)))
+            .await
+            .unwrap();
+        transport2
+            .new_connection("conn1", Box::new(CandidateCollector(tx2)))
+            .await
+            .unwrap();
+        let conn1 = transport1.connection("conn2").unwrap();
+        let conn2 = transport2.connection("conn1").unwrap();
+
+        let offer = conn1.webrtc_create_offer().await.unwrap();
+        let answer = conn2.webrtc_answer_offer(offer).await.unwrap();
+        conn1.webrtc_accept_answer(answer).await.unwrap();
+
+        // Candidates arrive well after offer and answer are exchanged.
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let forward1 = {
+            let conn2 = conn2.clone();
+            tokio::spawn(async move {
+                while let Some(candidate) = rx1.recv().await {
+                    conn2.webrtc_add_ice_candidate(candidate).await.unwrap();
+                }
+            })
+        };
+        let forward2 = {
+            let conn1 = conn1.clone();
+            tokio::spawn(async move {
+                while let Some(candidate) = rx2.recv().await {
+                    conn1.webrtc_add_ice_candidate(candidate).await.unwrap();
+                }
+            })
+        };
+
+        conn1.webrtc_wait_for_data_channel_open().await.unwrap();
+        conn2.webrtc_wait_for_data_channel_open().await.unwrap();
+        assert_eq!(
+            conn1.webrtc_connection_state(),
+            WebrtcConnectionState::Connected
+        );
+
+        forward1.abort();
+        forward2.abort();
+    }
+
+    #[tokio::test]
+    async fn test_disable_mdns() {
+        let mut transport = WebrtcTransport::new("stun://stun.l.google.com:19302", None);
+        assert_eq!(transport.mdns_mode(), MulticastDnsMode::Disabled);
+
+        let (tx, _rx) = mpsc::unbounded_channel();
+        transport
+            .new_connection("conn", Box::new(CandidateCollector(tx)))
+            .await
+            .unwrap();
+        let offer = transport
+            .connection("conn")
+            .unwrap()
+            .webrtc_create_offer()
+            .await
+            .unwrap();
+        assert!(!offer.contains(".local"));
+
+        transport.set_disable_mdns(false);
+        assert_eq!(transport.mdns_mode(), MulticastDnsMode::QueryOnly);
+    }
+
+    #[tokio::test]
+    async fn test_rtc_config_of_connection() {
+        let mut transport = WebrtcTransport::new("stun://stun.l.google.com:19302", None);
+        transport.set_rtc_config(RtcConfig {
+            bundle_policy: BundlePolicy::MaxBundle,
+            ice_transport_policy: IceTransportPolicy::Relay,
+            ice_candidate_pool_size: 2,
+        });
+
+        let (tx, _rx) = mpsc::unbounded_channel();
+        transport
+            .new_connection("conn", Box::new(CandidateCollector(tx)))
+            .await
+            .unwrap();
+        let config = transport
+            .connection("conn")
+            .unwrap()
+            .upgrade()
+            .unwrap()
+            .webrtc_conn
+            .get_configuration()
+            .await;
+        assert_eq!(config.bundle_policy, RTCBundlePolicy::MaxBundle);
+        assert_eq!(config.ice_transport_policy, RTCIceTransportPolicy::Relay);
+        assert_eq!(config.ice_candidate_pool_size, 2);
+    }
+
+    #[tokio::test]
+    async fn test_gather_timeout_with_unresponsive_stun_server() {
+        // A STUN server which never responds.
+        let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let ice_servers = format!("stun://{}", server.local_addr().unwrap());
+        let mut transport = WebrtcTransport::new(&ice_servers, None);
+        transport.set_gather_timeout(Duration::from_secs(1));
+
+        let (tx, _rx) = mpsc::unbounded_channel();
+        transport
+            .new_connection("conn", Box::new(CandidateCollector(tx)))
+            .await
+            .unwrap();
+        let started = tokio::time::Instant::now();
+        let offer = transport
+            .connection("conn")
+            .unwrap()
+            .webrtc_create_offer()
+            .await
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(3));
+        assert!(offer.contains("typ host"));
+        assert!(!offer.contains("typ srflx"));
+    }
+}
@@ -1,4 +1,5 @@
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use js_sys::Array;
@@ -7,18 +8,23 @@ use wasm_bindgen::JsValue;
 use wasm_bindgen_futures::spawn_local;
 use wasm_bindgen_futures::JsFuture;
 use web_sys::MessageEvent;
+use web_sys::RtcBundlePolicy;
 use web_sys::RtcConfiguration;
 use web_sys::RtcDataChannel;
 use web_sys::RtcDataChannelEvent;
 use web_sys::RtcDataChannelState;
+use web_sys::RtcIceCandidateInit;
 use web_sys::RtcIceCredentialType;
 use web_sys::RtcIceGatheringState;
 use web_sys::RtcIceServer;
+use web_sys::RtcIceTransportPolicy;
+use web_sys::RtcOfferOptions;
 use web_sys::RtcPeerConnection;
 use web_sys::RtcPeerConnectionState;
 use web_sys::RtcSdpType;
 use web_sys::RtcSessionDescription;
 use web_sys::RtcSessionDescriptionInit;
+use web_sys::RtcSignalingState;
 use web_sys::RtcStatsReport;
 
 use crate::callback::InnerTransportCallback;
@@ -29,15 +35,20 @@ use crate::core::pool::RoundRobin;
 use crate::core::pool::RoundRobinPool;
 use crate::core::pool::StatusPool;
 use crate::core::transport::ConnectionInterface;
+use crate::core::transport::IceCandidate;
 use crate::core::transport::TransportInterface;
 use crate::core::transport::TransportMessage;
 use crate::core::transport::WebrtcConnectionState;
 use crate::error::Error;
 use crate::error::Result;
+use crate::ice_server::ungathered_urls;
 use crate::ice_server::IceCredentialType;
 use crate::ice_server::IceServer;
 use crate::notifier::Notifier;
 use crate::pool::Pool;
+use crate::rtc_config::BundlePolicy;
+use crate::rtc_config::IceTransportPolicy;
+use crate::rtc_config::RtcConfig;
 
 const WEBRTC_WAIT_FOR_DATA_CHANNEL_OPEN_TIMEOUT: u8 = 8; // seconds
 const WEBRTC_GATHER_TIMEOUT: u8 = 60; // seconds
@@ -73,12 +84,18 @@ pub struct WebSysWebrtcConnection {
     webrtc_conn: RtcPeerConnection,
     webrtc_data_channel: Arc<RoundRobinPool<RtcDataChannel>>,
     webrtc_data_channel_state_notifier: Notifier,
+    /// Max time waiting for ICE candidates gathering, and ice servers to blame if it times out.
+    gather_timeout: Duration,
+    ice_servers: Vec<IceServer>,
 }
 
 /// [WebSysWebrtcTransport] manages all the [WebSysWebrtcConnection] and
 /// provides methods to create, get and close connections.
 pub struct WebSysWebrtcTransport {
     ice_servers: Vec<IceServer>,
+    buffered_amount_low_threshold: Option<usize>,
+    rtc_config: RtcConfig,
+    gather_timeout: Duration,
     pool: Pool<WebSysWebrtcConnection>,
 }
 
@@ -87,12 +104,28 @@ impl WebSysWebrtcConnection {
         webrtc_conn: RtcPeerConnection,
         webrtc_data_channel: Arc<RoundRobinPool<RtcDataChannel>>,
         webrtc_data_channel_state_notifier: Notifier,
+        gather_timeout: Duration,
+        ice_servers: Vec<IceServer>,
     ) -> Self {
         Self {
             webrtc_conn,
             webrtc_data_channel,
             webrtc_data_channel_state_notifier,
+            gather_timeout,
+            ice_servers,
+        }
+    }
+
+    /// Check the signaling state before applying a remote sdp, so that an sdp applied in a
+    /// wrong order returns [Error::InvalidSignalingState].
+    fn check_signaling_state(&self, expected: RtcSignalingState) -> Result<()> {
+        let state = self.webrtc_conn.signaling_state();
+        if state != expected {
+            return Err(Error::InvalidSignalingState(format!(
+                "{state:?}, expected {expected:?}"
+            )));
         }
+        Ok(())
     }
 
     async fn webrtc_gather(&self) -> Result<String> {
@@ -112,20 +145,24 @@ impl WebSysWebrtcConnection {
             .set_onicegatheringstatechange(Some(c.as_ref().unchecked_ref()));
         c.forget();
 
-        notifier.set_timeout(WEBRTC_GATHER_TIMEOUT);
+        notifier.set_timeout_ms(self.gather_timeout.as_millis().min(i32::MAX as u128) as i32);
         notifier.await;
-        if self.webrtc_conn.ice_gathering_state() != RtcIceGatheringState::Complete {
-            return Err(Error::WebrtcLocalSdpGenerationError(format!(
-                "Webrtc gathering is not completed in {WEBRTC_GATHER_TIMEOUT} seconds"
-            )));
-        }
 
-        self.webrtc_conn
+        let sdp = self
+            .webrtc_conn
             .local_description()
             .ok_or(Error::WebrtcLocalSdpGenerationError(
                 "local_description is None".to_string(),
             ))
-            .map(|x| x.sdp())
+            .map(|x| x.sdp())?;
+        if self.webrtc_conn.ice_gathering_state() != RtcIceGatheringState::Complete {
+            tracing::warn!(
+                "ICE gathering is not completed in {:?}, continue with gathered candidates, timed out servers: {:?}",
+                self.gather_timeout,
+                ungathered_urls(&self.ice_servers, &sdp)
+            );
+        }
+        Ok(sdp)
     }
 }
 
@@ -136,9 +173,33 @@ impl WebSysWebrtcTransport {
 
         Self {
             ice_servers,
+            buffered_amount_low_threshold: None,
+            rtc_config: RtcConfig::default(),
+            gather_timeout: Duration::from_secs(WEBRTC_GATHER_TIMEOUT.into()),
             pool: Pool::new(),
         }
     }
+
+    /// Set the low threshold of buffered amount of data channels created later.
+    /// Once the buffered amount of a data channel drops to it, `on_buffered_amount_low` of
+    /// [TransportCallback](crate::core::callback::TransportCallback) is invoked.
+    /// It's not notified if the threshold is None, which is the default.
+    pub fn set_buffered_amount_low_threshold(&mut self, threshold: Option<usize>) {
+        self.buffered_amount_low_threshold = threshold;
+    }
+
+    /// Set the options of RTCConfiguration of connections created later, such as
+    /// [IceTransportPolicy::Relay] to force all traffic through TURN servers.
+    pub fn set_rtc_config(&mut self, rtc_config: RtcConfig) {
+        self.rtc_config = rtc_config;
+    }
+
+    /// Set the max time waiting for ICE candidates gathering of offers and answers, which is
+    /// 60 seconds by default. Once it's reached, the offer or answer carries the candidates
+    /// gathered so far, and ice servers which may have timed out are logged.
+    pub fn set_gather_timeout(&mut self, timeout: Duration) {
+        self.gather_timeout = timeout;
+    }
 }
 
 #[async_trait(?Send)]
@@ -186,8 +247,28 @@ impl ConnectionInterface for WebSysWebrtcConnection {
         self.webrtc_gather().await
     }
 
+    async fn webrtc_restart_ice(&self) -> Result<Self::Sdp> {
+        let mut options = RtcOfferOptions::new();
+        options.ice_restart(true);
+        let promise = self
+            .webrtc_conn
+            .create_offer_with_rtc_offer_options(&options);
+        let offer_js_value = JsFuture::from(promise).await.map_err(Error::WebSysWebrtc)?;
+        let offer = RtcSessionDescription::from(offer_js_value);
+        let sdp = offer.sdp();
+
+        let mut set_local_init = RtcSessionDescriptionInit::new(RtcSdpType::Offer);
+        set_local_init.sdp(&sdp);
+
+        let promise = self.webrtc_conn.set_local_description(&set_local_init);
+        JsFuture::from(promise).await.map_err(Error::WebSysWebrtc)?;
+
+        self.webrtc_gather().await
+    }
+
     async fn webrtc_answer_offer(&self, offer: Self::Sdp) -> Result<Self::Sdp> {
         tracing::debug!("webrtc_answer_offer, offer: {offer:?}");
+        self.check_signaling_state(RtcSignalingState::Stable)?;
 
         let mut set_remote_init = RtcSessionDescriptionInit::new(RtcSdpType::Offer);
         set_remote_init.sdp(&offer);
@@ -211,6 +292,7 @@ impl ConnectionInterface for WebSysWebrtcConnection {
 
     async fn webrtc_accept_answer(&self, answer: Self::Sdp) -> Result<()> {
         tracing::debug!("webrtc_accept_answer, answer: {answer:?}");
+        self.check_signaling_state(RtcSignalingState::HaveLocalOffer)?;
 
         let mut set_remote_init = RtcSessionDescriptionInit::new(RtcSdpType::Answer);
         set_remote_init.sdp(&answer);
@@ -221,6 +303,21 @@ impl ConnectionInterface for WebSysWebrtcConnection {
         Ok(())
     }
 
+    async fn webrtc_add_ice_candidate(&self, candidate: IceCandidate) -> Result<()> {
+        tracing::debug!("webrtc_add_ice_candidate, candidate: {candidate:?}");
+
+        let mut init = RtcIceCandidateInit::new(&candidate.candidate);
+        init.sdp_mid(candidate.sdp_mid.as_deref());
+        init.sdp_m_line_index(candidate.sdp_mline_index);
+
+        let promise = self
+            .webrtc_conn
+            .add_ice_candidate_with_opt_rtc_ice_candidate_init(Some(&init));
+        JsFuture::from(promise).await.map_err(Error::WebSysWebrtc)?;
+
+        Ok(())
+    }
+
     async fn webrtc_wait_for_data_channel_open(&self) -> Result<()> {
         if matches!(
             self.webrtc_connection_state(),
@@ -248,6 +345,24 @@ impl ConnectionInterface for WebSysWebrtcConnection {
         }
     }
 
+    fn webrtc_data_channel_is_open(&self) -> bool {
+        !matches!(
+            self.webrtc_connection_state(),
+            WebrtcConnectionState::Failed
+                | WebrtcConnectionState::Closed
+                | WebrtcConnectionState::Disconnected
+        ) && self.webrtc_data_channel.all_ready().unwrap_or(false)
+    }
+
+    async fn webrtc_buffered_amount(&self) -> Result<usize> {
+        Ok(self
+            .webrtc_data_channel
+            .items()?
+            .iter()
+            .map(|channel| channel.buffered_amount() as usize)
+            .sum())
+    }
+
     async fn close(&self) -> Result<()> {
         self.webrtc_conn.close();
         Ok(())
@@ -278,6 +393,15 @@ impl TransportInterface for WebSysWebrtcTransport {
         let ice_servers: js_sys::Array =
             js_sys::Array::from_iter(self.ice_servers.iter().cloned().map(RtcIceServer::from));
         config.ice_servers(&ice_servers.into());
+        config.bundle_policy(self.rtc_config.bundle_policy.into());
+        config.ice_transport_policy(self.rtc_config.ice_transport_policy.into());
+        // iceCandidatePoolSize is not bound by web-sys yet.
+        js_sys::Reflect::set(
+            &config,
+            &JsValue::from_str("iceCandidatePoolSize"),
+            &JsValue::from(self.rtc_config.ice_candidate_pool_size),
+        )
+        .map_err(Error::WebSysWebrtc)?;
 
         //
         // Create webrtc connection
@@ -399,6 +523,19 @@ impl TransportInterface for WebSysWebrtcTransport {
         //
         for i in 0..DATA_CHANNEL_POOL_SIZE {
             let ch = webrtc_conn.create_data_channel(&format!("rings_data_channel_{}", i));
+            if let Some(threshold) = self.buffered_amount_low_threshold {
+                ch.set_buffered_amount_low_threshold(threshold as u32);
+                let buffered_amount_low_inner_cb = inner_cb.clone();
+                let on_buffered_amount_low = Box::new(move || {
+                    let inner_cb = buffered_amount_low_inner_cb.clone();
+                    spawn_local(async move {
+                        inner_cb.on_buffered_amount_low().await;
+                    })
+                });
+                let c = Closure::wrap(on_buffered_amount_low as Box<dyn FnMut()>);
+                ch.set_onbufferedamountlow(Some(c.as_ref().unchecked_ref()));
+                c.forget();
+            }
             channel_pool.push(ch)?;
         }
 
@@ -409,6 +546,8 @@ impl TransportInterface for WebSysWebrtcTransport {
             webrtc_conn,
             channel_pool,
             webrtc_data_channel_state_notifier,
+            self.gather_timeout,
+            self.ice_servers.clone(),
         );
 
         self.pool.safely_insert(cid, conn)?;
@@ -442,6 +581,25 @@ impl From<IceCredentialType> for RtcIceCredentialType {
     }
 }
 
+impl From<BundlePolicy> for RtcBundlePolicy {
+    fn from(s: BundlePolicy) -> Self {
+        match s {
+            BundlePolicy::Balanced => Self::Balanced,
+            BundlePolicy::MaxCompat => Self::MaxCompat,
+            BundlePolicy::MaxBundle => Self::MaxBundle,
+        }
+    }
+}
+
+impl From<IceTransportPolicy> for RtcIceTransportPolicy {
+    fn from(s: IceTransportPolicy) -> Self {
+        match s {
+            IceTransportPolicy::All => Self::All,
+            IceTransportPolicy::Relay => Self::Relay,
+        }
+    }
+}
+
 impl From<IceServer> for RtcIceServer {
     fn from(s: IceServer) -> Self {
         let mut ret = RtcIceServer::new();
@@ -7,6 +7,7 @@
 
 use async_trait::async_trait;
 
+use crate::core::transport::IceCandidate;
 use crate::core::transport::WebrtcConnectionState;
 
 type CallbackError = Box<dyn std::error::Error>;
@@ -33,6 +34,24 @@ pub trait TransportCallback {
     ) -> Result<(), CallbackError> {
         Ok(())
     }
+
+    /// This method is invoked when the buffered amount of a data channel drops to its low
+    /// threshold, if the threshold is set by the transport. Senders paused by backpressure can
+    /// resume sending then.
+    async fn on_buffered_amount_low(&self, _cid: &str) -> Result<(), CallbackError> {
+        Ok(())
+    }
+
+    /// This method is invoked when a local ICE candidate is gathered, if trickle ICE is enabled.
+    /// The candidate should be sent to remote peer, which adds it by
+    /// [ConnectionInterface::webrtc_add_ice_candidate](super::transport::ConnectionInterface::webrtc_add_ice_candidate).
+    async fn on_ice_candidate(
+        &self,
+        _cid: &str,
+        _candidate: IceCandidate,
+    ) -> Result<(), CallbackError> {
+        Ok(())
+    }
 }
 
 /// The `new_connection` method of
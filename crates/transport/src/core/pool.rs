@@ -67,6 +67,15 @@ impl<T: Clone> RoundRobinPool<T> {
         pool.push(item);
         Ok(())
     }
+
+    /// Clone all items of the pool into a vector.
+    pub fn items(&self) -> Result<Vec<T>> {
+        let pool = self
+            .pool
+            .read()
+            .map_err(|_| Error::RwLockRead("Failed to read RR pool".to_string()))?;
+        Ok(pool.clone())
+    }
 }
 
 impl<T: Clone> RoundRobin<T> for RoundRobinPool<T> {
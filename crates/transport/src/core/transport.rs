@@ -20,6 +20,33 @@ pub enum TransportMessage {
     Custom(Vec<u8>),
 }
 
+/// An ICE candidate exchanged after offer and answer when trickle ICE is enabled.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct IceCandidate {
+    /// The candidate line of sdp, such as `candidate:1 1 udp 2130706431 ...`.
+    pub candidate: String,
+    /// The media stream identification tag of the candidate.
+    pub sdp_mid: Option<String>,
+    /// The index of media description in sdp of the candidate.
+    pub sdp_mline_index: Option<u16>,
+}
+
+/// Statistics of the underlying transport of a connection.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ConnectionStats {
+    /// Bytes sent through the selected candidate pair.
+    pub bytes_sent: u64,
+    /// Bytes received through the selected candidate pair.
+    pub bytes_received: u64,
+    /// Packets discarded before sending, which is the closest figure to packet loss
+    /// that ICE exposes for data channels.
+    pub packets_lost: u64,
+    /// The latest round trip time measured by ICE, if any.
+    pub current_rtt: Option<std::time::Duration>,
+    /// The selected candidate pair, formatted as `local_address -> remote_address`.
+    pub selected_candidate_pair: Option<String>,
+}
+
 /// The state of the WebRTC connection.
 /// This enum is used to define a same interface for all the platforms.
 #[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
@@ -77,18 +104,46 @@ pub trait ConnectionInterface {
     /// This is a debug method to dump the stats of webrtc connection.
     async fn get_stats(&self) -> Vec<String>;
 
+    /// Get the statistics of the underlying transport.
+    /// Connections without such statistics return the default value.
+    async fn stats(&self) -> ConnectionStats {
+        ConnectionStats::default()
+    }
+
     /// Create a webrtc offer to start handshake.
     async fn webrtc_create_offer(&self) -> Result<Self::Sdp, Self::Error>;
 
+    /// Create a webrtc offer with ICE restart, to recover the connection when the network
+    /// changes, such as switching from Wi-Fi to cellular. The remote peer answers it by
+    /// [ConnectionInterface::webrtc_answer_offer] on its existing connection, and the answer
+    /// is accepted by [ConnectionInterface::webrtc_accept_answer].
+    /// Connections without ICE create an ordinary offer by [ConnectionInterface::webrtc_create_offer].
+    async fn webrtc_restart_ice(&self) -> Result<Self::Sdp, Self::Error> {
+        self.webrtc_create_offer().await
+    }
+
     /// Accept a webrtc offer from remote peer and give back an answer.
     async fn webrtc_answer_offer(&self, offer: Self::Sdp) -> Result<Self::Sdp, Self::Error>;
 
     /// Accept a webrtc answer from remote peer.
     async fn webrtc_accept_answer(&self, answer: Self::Sdp) -> Result<(), Self::Error>;
 
+    /// Add an ICE candidate trickled by remote peer.
+    /// It should be called after the remote description is set by answering offer or
+    /// accepting answer. Connections without ICE ignore it.
+    async fn webrtc_add_ice_candidate(&self, _candidate: IceCandidate) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
     /// Wait for the data channel to be opened after handshake.
     async fn webrtc_wait_for_data_channel_open(&self) -> Result<(), Self::Error>;
 
+    /// Check if the data channel is open without waiting, so that messages can be sent at once.
+    fn webrtc_data_channel_is_open(&self) -> bool;
+
+    /// Get the number of bytes queued in data channels but not yet sent to the remote peer.
+    async fn webrtc_buffered_amount(&self) -> Result<usize, Self::Error>;
+
     /// Close the webrtc connection.
     async fn close(&self) -> Result<(), Self::Error>;
 }
@@ -30,6 +30,9 @@ pub enum Error {
     #[error("Bincode error: {0}")]
     Bincode(#[from] bincode::Error),
 
+    #[error("Serde json error: {0}")]
+    SerdeJson(#[from] serde_json::Error),
+
     #[error("IceServer error: {0}")]
     IceServer(#[from] IceServerError),
 
@@ -48,6 +51,9 @@ pub enum Error {
     #[error("Connection {0} is released")]
     ConnectionReleased(String),
 
+    #[error("Operation can not be run in current signaling state: {0}")]
+    InvalidSignalingState(String),
+
     #[error("Rwlock try write failed: {0}")]
     RwLockWrite(String),
 
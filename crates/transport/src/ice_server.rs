@@ -93,10 +93,30 @@ impl FromStr for IceServer {
     }
 }
 
+/// Urls of `ice_servers` of which no candidate is found in `sdp`, which tells the servers
+/// timed out when gathering is not completed in time. Candidates don't tell which server they
+/// come from, so STUN servers are judged by server reflexive candidates, and TURN servers
+/// by relayed candidates.
+pub fn ungathered_urls(ice_servers: &[IceServer], sdp: &str) -> Vec<String> {
+    let has_candidate = |typ: &str| {
+        let typ = format!(" typ {typ}");
+        sdp.lines()
+            .any(|line| line.starts_with("a=candidate:") && line.contains(&typ))
+    };
+    let (srflx, relay) = (has_candidate("srflx"), has_candidate("relay"));
+    ice_servers
+        .iter()
+        .flat_map(|server| server.urls.iter())
+        .filter(|url| (url.starts_with("stun:") && !srflx) || (url.starts_with("turn:") && !relay))
+        .cloned()
+        .collect()
+}
+
 #[cfg(test)]
 mod test {
     use std::str::FromStr;
 
+    use super::ungathered_urls;
     use super::IceServer;
 
     #[test]
@@ -130,4 +150,18 @@ mod test {
 
         assert!(ret_e.is_err());
     }
+
+    #[test]
+    fn test_ungathered_urls() {
+        let servers =
+            IceServer::vec_from_str("stun://stun.example.org:3478;turn://turn.example.org:3478")
+                .unwrap();
+        let sdp = "v=0\r\n\
+                   a=candidate:1 1 udp 2130706431 192.168.1.2 50000 typ host\r\n\
+                   a=candidate:2 1 udp 1694498815 1.2.3.4 50000 typ srflx raddr 0.0.0.0 rport 50000\r\n";
+        assert_eq!(ungathered_urls(&servers, sdp), vec![
+            "turn:turn.example.org:3478".to_string()
+        ]);
+        assert_eq!(ungathered_urls(&servers, "v=0\r\n").len(), 2);
+    }
 }
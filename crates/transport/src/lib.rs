@@ -9,3 +9,4 @@ pub mod error;
 pub mod ice_server;
 pub mod notifier;
 pub mod pool;
+pub mod rtc_config;
@@ -45,9 +45,13 @@ impl Notifier {
     /// Wake the notifier after the specified time.
     #[cfg(feature = "web-sys-webrtc")]
     pub fn set_timeout(&self, seconds: u8) {
-        use wasm_bindgen::JsCast;
+        self.set_timeout_ms(seconds as i32 * 1000)
+    }
 
-        let millis = seconds as i32 * 1000;
+    /// Wake the notifier after the specified milliseconds.
+    #[cfg(feature = "web-sys-webrtc")]
+    pub fn set_timeout_ms(&self, millis: i32) {
+        use wasm_bindgen::JsCast;
 
         let this = self.clone();
         let wake = wasm_bindgen::closure::Closure::once_into_js(move || {
@@ -0,0 +1,47 @@
+//! This module contains the RtcConfig structure.
+
+use serde::Deserialize;
+use serde::Serialize;
+
+/// Which media tracks to gather ICE candidates for, see
+/// <https://www.w3.org/TR/webrtc/#rtcbundlepolicy-enum>.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BundlePolicy {
+    /// Gather candidates for each media type in use.
+    #[default]
+    Balanced,
+    /// Gather candidates for each track.
+    MaxCompat,
+    /// Gather candidates for only one track.
+    MaxBundle,
+}
+
+/// Which ICE candidates are allowed to be used, see
+/// <https://www.w3.org/TR/webrtc/#rtcicetransportpolicy-enum>.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum IceTransportPolicy {
+    /// Use all types of candidates.
+    #[default]
+    All,
+    /// Use only candidates relayed by TURN servers, so that the IP address of this node is
+    /// never exposed to peers.
+    Relay,
+}
+
+/// Options of the underlying RTCConfiguration of connections besides ICE servers.
+///
+/// Each Connection converts it to the configuration of its underlying library when
+/// creating a connection.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct RtcConfig {
+    /// BundlePolicy of connections.
+    pub bundle_policy: BundlePolicy,
+    /// IceTransportPolicy of connections. Set it to [IceTransportPolicy::Relay] to force all
+    /// traffic through TURN servers.
+    pub ice_transport_policy: IceTransportPolicy,
+    /// Number of ICE candidates gathered before a connection is used.
+    pub ice_candidate_pool_size: u8,
+}
@@ -49,6 +49,10 @@ typedef struct FFIBackendBehaviour {
                                      const struct ProviderPtr*,
                                      const char*,
                                      const char*);
+  void (**unhandled_message_handler)(const struct FFIBackendBehaviourWithRuntime*,
+                                     const struct ProviderPtr*,
+                                     const char*,
+                                     const char*);
 } FFIBackendBehaviour;
 
 /**
@@ -67,6 +71,19 @@ struct FFIBackendBehaviour new_ffi_backend_behaviour(void (*paintext_message_han
                                                                                        const char*,
                                                                                        const char*));
 
+/**
+ * Set the handler of backend messages which are not handled, called with the variant name as
+ * message.
+ * # Safety
+ *
+ * * This function dereferences the raw pointer of behaviour
+ */
+void set_ffi_backend_unhandled_message_handler(struct FFIBackendBehaviour *behaviour,
+                                               void (*unhandled_message_handler)(const struct FFIBackendBehaviourWithRuntime*,
+                                                                                 const struct ProviderPtr*,
+                                                                                 const char*,
+                                                                                 const char*));
+
 void init_logging(enum LogLevel level);
 
 /**
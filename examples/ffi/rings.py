@@ -53,10 +53,12 @@ def request(provider, method, data):
 def create_provider(acc,
                     on_paintext_message=default_handler,
                     on_service_message=default_handler,
-                    on_extension_message=default_handler):
+                    on_extension_message=default_handler,
+                    on_unhandled_message=default_handler):
 
     rings.init_logging(rings.Debug)
     callback = rings.new_ffi_backend_behaviour(on_paintext_message, on_service_message, on_extension_message)
+    rings.set_ffi_backend_unhandled_message_handler(ffi.addressof(callback), on_unhandled_message)
     provider = rings.new_provider_with_callback(
         0,
         "stun://stun.l.google.com".encode(),